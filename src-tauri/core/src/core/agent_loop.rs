@@ -16,6 +16,7 @@ use crate::llm::types::{
 use crate::storage::models::*;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc;
 
 /// Agent loop configuration
 pub struct AgentLoop {
@@ -141,9 +142,28 @@ impl AgentLoop {
             max_tokens: self.config.max_tokens.map(|t| t as i32),
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            template: None,
+            trim_strategy: None,
+            logprobs: None,
+            top_logprobs: None,
             provider_options: None,
             request_id: Some(ctx.task_id.clone()),
             trace_context: None,
+            timeout_ms: None,
+            dry_run: None,
+            builtin_tools: None,
+            strip_headers: None,
+            max_attempts: None,
+            image_input_check: None,
+            reasoning_effort: None,
+            stall_timeout_ms: None,
+            idempotent: None,
         };
 
         // Run stream
@@ -151,11 +171,16 @@ impl AgentLoop {
         let mut state = StreamProcessorState::default();
         let timeout = Duration::from_secs(300);
 
-        let result = runner
-            .stream(request, timeout, |event| {
-                self.process_stream_event(&mut state, event, ctx);
-            })
-            .await;
+        // Each event is awaited into the bounded runtime channel, so a slow UI
+        // consumer fills both buffers and pauses the HTTP read
+        let (sender, mut receiver) = mpsc::channel(self.config.stream_buffer_size.max(1));
+        let produce = runner.stream_to(request, timeout, sender);
+        let consume = async {
+            while let Some(event) = receiver.recv().await {
+                self.process_stream_event(&mut state, event, ctx).await;
+            }
+        };
+        let (result, ()) = tokio::join!(produce, consume);
 
         if let Err(e) = result {
             return Ok(AgentLoopResult::Error { message: e });
@@ -180,10 +205,13 @@ impl AgentLoop {
         }
 
         if state.finish_reason.is_some() {
-            let _ = self.event_sender.send(RuntimeEvent::Done {
-                session_id: ctx.session_id.clone(),
-                finish_reason: state.finish_reason.clone(),
-            });
+            let _ = self
+                .event_sender
+                .send(RuntimeEvent::Done {
+                    session_id: ctx.session_id.clone(),
+                    finish_reason: state.finish_reason.clone(),
+                })
+                .await;
         }
 
         Ok(AgentLoopResult::Completed {
@@ -192,7 +220,7 @@ impl AgentLoop {
     }
 
     /// Process a stream event from the LLM
-    fn process_stream_event(
+    async fn process_stream_event(
         &self,
         state: &mut StreamProcessorState,
         event: StreamEvent,
@@ -203,10 +231,13 @@ impl AgentLoop {
                 state.accumulated_text.push_str(&text);
 
                 // Emit token event
-                let _ = self.event_sender.send(RuntimeEvent::Token {
-                    session_id: ctx.session_id.clone(),
-                    token: text,
-                });
+                let _ = self
+                    .event_sender
+                    .send(RuntimeEvent::Token {
+                        session_id: ctx.session_id.clone(),
+                        token: text,
+                    })
+                    .await;
             }
             StreamEvent::ToolCall {
                 tool_call_id,
@@ -223,20 +254,26 @@ impl AgentLoop {
                 state.tool_calls.push(tool_request.clone());
 
                 // Emit tool call requested event
-                let _ = self.event_sender.send(RuntimeEvent::ToolCallRequested {
-                    task_id: ctx.task_id.clone(),
-                    request: tool_request,
-                });
+                let _ = self
+                    .event_sender
+                    .send(RuntimeEvent::ToolCallRequested {
+                        task_id: ctx.task_id.clone(),
+                        request: tool_request,
+                    })
+                    .await;
             }
             StreamEvent::ReasoningStart {
                 id,
                 provider_metadata: _,
             } => {
                 // Emit reasoning start event
-                let _ = self.event_sender.send(RuntimeEvent::ReasoningStart {
-                    session_id: ctx.session_id.clone(),
-                    id,
-                });
+                let _ = self
+                    .event_sender
+                    .send(RuntimeEvent::ReasoningStart {
+                        session_id: ctx.session_id.clone(),
+                        id,
+                    })
+                    .await;
             }
             StreamEvent::ReasoningDelta {
                 id,
@@ -244,18 +281,24 @@ impl AgentLoop {
                 provider_metadata: _,
             } => {
                 // Emit reasoning delta event
-                let _ = self.event_sender.send(RuntimeEvent::ReasoningDelta {
-                    session_id: ctx.session_id.clone(),
-                    id,
-                    text,
-                });
+                let _ = self
+                    .event_sender
+                    .send(RuntimeEvent::ReasoningDelta {
+                        session_id: ctx.session_id.clone(),
+                        id,
+                        text,
+                    })
+                    .await;
             }
             StreamEvent::ReasoningEnd { id } => {
                 // Emit reasoning end event
-                let _ = self.event_sender.send(RuntimeEvent::ReasoningEnd {
-                    session_id: ctx.session_id.clone(),
-                    id,
-                });
+                let _ = self
+                    .event_sender
+                    .send(RuntimeEvent::ReasoningEnd {
+                        session_id: ctx.session_id.clone(),
+                        id,
+                    })
+                    .await;
             }
             StreamEvent::Usage {
                 input_tokens,
@@ -263,15 +306,19 @@ impl AgentLoop {
                 total_tokens,
                 cached_input_tokens,
                 cache_creation_input_tokens,
+                ..
             } => {
-                let _ = self.event_sender.send(RuntimeEvent::Usage {
-                    session_id: ctx.session_id.clone(),
-                    input_tokens,
-                    output_tokens,
-                    total_tokens,
-                    cached_input_tokens,
-                    cache_creation_input_tokens,
-                });
+                let _ = self
+                    .event_sender
+                    .send(RuntimeEvent::Usage {
+                        session_id: ctx.session_id.clone(),
+                        input_tokens,
+                        output_tokens,
+                        total_tokens,
+                        cached_input_tokens,
+                        cache_creation_input_tokens,
+                    })
+                    .await;
             }
             StreamEvent::Done { finish_reason } => {
                 state.finish_reason = finish_reason;
@@ -315,6 +362,7 @@ impl AgentLoop {
                     }
                 },
                 provider_options: None,
+                cache: false,
             },
             MessageRole::Assistant => LlmMessage::Assistant {
                 content: match &message.content {
@@ -344,6 +392,7 @@ impl AgentLoop {
                     }
                 },
                 provider_options: None,
+                cache: false,
             },
             MessageRole::System => LlmMessage::System {
                 content: match &message.content {
@@ -356,6 +405,7 @@ impl AgentLoop {
                     }
                 },
                 provider_options: None,
+                cache: false,
             },
             MessageRole::Tool => {
                 let parts = match &message.content {
@@ -383,6 +433,7 @@ impl AgentLoop {
                 LlmMessage::Tool {
                     content: parts,
                     provider_options: None,
+                    cache: false,
                 }
             }
         }
@@ -454,10 +505,13 @@ impl AgentLoop {
             .await;
 
         // Emit completion event
-        let _ = self.event_sender.send(RuntimeEvent::ToolCallCompleted {
-            task_id: ctx.task_id.clone(),
-            result: result.clone(),
-        });
+        let _ = self
+            .event_sender
+            .send(RuntimeEvent::ToolCallCompleted {
+                task_id: ctx.task_id.clone(),
+                result: result.clone(),
+            })
+            .await;
 
         result
     }
@@ -494,11 +548,14 @@ impl AgentLoop {
 
     #[allow(dead_code)]
     /// Stream a token to the event channel
-    fn stream_token(&self, session_id: &str, token: &str) {
-        let _ = self.event_sender.send(RuntimeEvent::Token {
-            session_id: session_id.to_string(),
-            token: token.to_string(),
-        });
+    async fn stream_token(&self, session_id: &str, token: &str) {
+        let _ = self
+            .event_sender
+            .send(RuntimeEvent::Token {
+                session_id: session_id.to_string(),
+                token: token.to_string(),
+            })
+            .await;
     }
 }
 
@@ -536,13 +593,16 @@ impl AgentLoopFactory {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::sync::mpsc;
+    use crate::database::Database;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::testing::mock_server::start_sequence_server;
+    use crate::llm::types::ProtocolType;
+    use tempfile::TempDir;
 
-    async fn create_test_loop() -> (AgentLoop, mpsc::UnboundedReceiver<RuntimeEvent>) {
-        use crate::database::Database;
-        use tempfile::TempDir;
+    const DELTA_COUNT: usize = 32;
 
-        let (tx, rx) = mpsc::unbounded_channel();
+    async fn create_test_loop() -> (AgentLoop, EventReceiver) {
+        let (tx, rx) = event_channel();
         let registry = Arc::new(ToolRegistry::create_default().await);
         let dispatcher = Arc::new(ToolDispatcher::new(registry));
         let provider_registry = ProviderRegistry::default();
@@ -640,4 +700,129 @@ mod tests {
         assert!(prompt.contains("User: Hello"));
         assert!(prompt.contains("Assistant: Hi there!"));
     }
+
+    fn sse_body() -> String {
+        let mut body = String::new();
+        for i in 0..DELTA_COUNT {
+            body.push_str(&format!(
+                "data: {{\"choices\":[{{\"index\":0,\"delta\":{{\"content\":\"{}\"}}}}]}}\n\n",
+                i
+            ));
+        }
+        body.push_str(
+            "data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        );
+        body.push_str("data: [DONE]\n\n");
+        body
+    }
+
+    async fn create_streaming_loop(
+        base_url: String,
+        event_sender: EventSender,
+    ) -> (AgentLoop, TempDir) {
+        let (dir, api_keys) = api_key_manager_with(&[("api_key_test", "test-key")]).await;
+
+        let provider_registry = ProviderRegistry::new(vec![provider_config(
+            "test",
+            ProtocolType::OpenAiCompatible,
+            &base_url,
+        )]);
+        let tools = Arc::new(ToolRegistry::create_default().await);
+        let config = AgentLoopConfig {
+            enable_tools: false,
+            stream_buffer_size: 1,
+            ..AgentLoopConfig::default()
+        };
+        let agent_loop = AgentLoop::new(
+            config,
+            Arc::new(ToolDispatcher::new(tools)),
+            event_sender,
+            provider_registry,
+            api_keys,
+        );
+        (agent_loop, dir)
+    }
+
+    fn streaming_context() -> (AgentLoopContext, Vec<Message>) {
+        let ctx = AgentLoopContext {
+            session_id: "test-session".to_string(),
+            task_id: "test-task".to_string(),
+            workspace_root: "/tmp".to_string(),
+            worktree_path: None,
+            settings: TaskSettings::default(),
+            messages: vec![],
+            model: Some("test-model@test".to_string()),
+            llm_state: None,
+        };
+        let messages = vec![Message {
+            id: "msg-1".to_string(),
+            session_id: "test-session".to_string(),
+            role: MessageRole::User,
+            content: MessageContent::Text {
+                text: "hi".to_string(),
+            },
+            created_at: 0,
+            tool_call_id: None,
+            parent_id: None,
+        }];
+        (ctx, messages)
+    }
+
+    #[tokio::test]
+    async fn stalled_ui_consumer_bounds_queued_runtime_events() {
+        let (base_url, _hits) = start_sequence_server(vec![(200, sse_body())]).expect("server");
+        let (tx, mut rx) = mpsc::channel(2);
+        let (agent_loop, _dir) = create_streaming_loop(base_url, tx).await;
+        let (ctx, messages) = streaming_context();
+
+        // Nobody drains the runtime channel, so the loop must park instead of queueing tokens
+        let stalled = tokio::time::timeout(
+            Duration::from_secs(1),
+            agent_loop.run_iteration(&ctx, &messages),
+        )
+        .await;
+        assert!(
+            stalled.is_err(),
+            "agent loop should wait for the UI consumer"
+        );
+
+        let mut queued = 0;
+        while rx.try_recv().is_ok() {
+            queued += 1;
+        }
+        assert_eq!(queued, 2);
+    }
+
+    #[tokio::test]
+    async fn slow_ui_consumer_still_receives_every_token() {
+        let (base_url, _hits) = start_sequence_server(vec![(200, sse_body())]).expect("server");
+        let (tx, mut rx) = mpsc::channel(2);
+        let (agent_loop, _dir) = create_streaming_loop(base_url, tx).await;
+        let (ctx, messages) = streaming_context();
+
+        let consumer = tokio::spawn(async move {
+            let mut tokens = Vec::new();
+            while let Some(event) = rx.recv().await {
+                tokio::time::sleep(Duration::from_millis(2)).await;
+                if let RuntimeEvent::Token { token, .. } = event {
+                    tokens.push(token);
+                }
+            }
+            tokens
+        });
+
+        let result = agent_loop
+            .run_iteration(&ctx, &messages)
+            .await
+            .expect("iteration");
+        drop(agent_loop);
+        let tokens = consumer.await.expect("consumer");
+
+        let expected: Vec<String> = (0..DELTA_COUNT).map(|i| i.to_string()).collect();
+        assert_eq!(tokens, expected);
+        match result {
+            AgentLoopResult::Completed { message } => assert_eq!(message, expected.concat()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
 }
@@ -225,11 +225,13 @@ impl CoreRuntime {
         *task_state.write().await = RuntimeTaskState::Running;
 
         // Emit state change event
-        let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
-            task_id: task.id.clone(),
-            state: RuntimeTaskState::Running,
-            previous_state: RuntimeTaskState::Pending,
-        });
+        let _ = event_sender
+            .send(RuntimeEvent::TaskStateChanged {
+                task_id: task.id.clone(),
+                state: RuntimeTaskState::Running,
+                previous_state: RuntimeTaskState::Pending,
+            })
+            .await;
 
         // Create agent loop with full LLM integration
         let agent_loop = AgentLoopFactory::create_standard(
@@ -257,11 +259,13 @@ impl CoreRuntime {
             .add_message(initial_message.clone())
             .await
         {
-            let _ = event_sender.send(RuntimeEvent::Error {
-                task_id: Some(task.id.clone()),
-                session_id: Some(task.session_id.clone()),
-                message: format!("Failed to add message: {}", e),
-            });
+            let _ = event_sender
+                .send(RuntimeEvent::Error {
+                    task_id: Some(task.id.clone()),
+                    session_id: Some(task.session_id.clone()),
+                    message: format!("Failed to add message: {}", e),
+                })
+                .await;
             self.complete_task(
                 &task,
                 RuntimeTaskState::Failed,
@@ -272,10 +276,12 @@ impl CoreRuntime {
             return;
         }
 
-        let _ = event_sender.send(RuntimeEvent::MessageCreated {
-            session_id: task.session_id.clone(),
-            message: initial_message,
-        });
+        let _ = event_sender
+            .send(RuntimeEvent::MessageCreated {
+                session_id: task.session_id.clone(),
+                message: initial_message,
+            })
+            .await;
 
         // Build agent loop context
         let workspace_root = input
@@ -350,10 +356,12 @@ impl CoreRuntime {
                         .session_manager
                         .add_message(assistant_message.clone())
                         .await;
-                    let _ = event_sender.send(RuntimeEvent::MessageCreated {
-                        session_id: task.session_id.clone(),
-                        message: assistant_message.clone(),
-                    });
+                    let _ = event_sender
+                        .send(RuntimeEvent::MessageCreated {
+                            session_id: task.session_id.clone(),
+                            message: assistant_message.clone(),
+                        })
+                        .await;
                     messages.push(assistant_message);
 
                     self.complete_task(&task, RuntimeTaskState::Completed, None, &event_sender)
@@ -381,10 +389,12 @@ impl CoreRuntime {
                             .session_manager
                             .add_message(assistant_message.clone())
                             .await;
-                        let _ = event_sender.send(RuntimeEvent::MessageCreated {
-                            session_id: task.session_id.clone(),
-                            message: assistant_message.clone(),
-                        });
+                        let _ = event_sender
+                            .send(RuntimeEvent::MessageCreated {
+                                session_id: task.session_id.clone(),
+                                message: assistant_message.clone(),
+                            })
+                            .await;
                         messages.push(assistant_message);
                     }
 
@@ -412,10 +422,12 @@ impl CoreRuntime {
                         .session_manager
                         .add_message(tool_calls_message.clone())
                         .await;
-                    let _ = event_sender.send(RuntimeEvent::MessageCreated {
-                        session_id: task.session_id.clone(),
-                        message: tool_calls_message.clone(),
-                    });
+                    let _ = event_sender
+                        .send(RuntimeEvent::MessageCreated {
+                            session_id: task.session_id.clone(),
+                            message: tool_calls_message.clone(),
+                        })
+                        .await;
                     messages.push(tool_calls_message);
 
                     for call in tool_calls {
@@ -437,10 +449,12 @@ impl CoreRuntime {
                         {
                             true if !auto_approve => {
                                 *task_state.write().await = RuntimeTaskState::WaitingForUser;
-                                let _ = event_sender.send(RuntimeEvent::ToolCallRequested {
-                                    task_id: task.id.clone(),
-                                    request: call,
-                                });
+                                let _ = event_sender
+                                    .send(RuntimeEvent::ToolCallRequested {
+                                        task_id: task.id.clone(),
+                                        request: call,
+                                    })
+                                    .await;
                                 return;
                             }
                             _ => {
@@ -449,10 +463,12 @@ impl CoreRuntime {
                                     .clone()
                                     .execute(call.clone(), tool_context)
                                     .await;
-                                let _ = event_sender.send(RuntimeEvent::ToolCallCompleted {
-                                    task_id: task.id.clone(),
-                                    result: result.clone(),
-                                });
+                                let _ = event_sender
+                                    .send(RuntimeEvent::ToolCallCompleted {
+                                        task_id: task.id.clone(),
+                                        result: result.clone(),
+                                    })
+                                    .await;
 
                                 let stored_result = StoredToolResult {
                                     tool_call_id: result.tool_call_id.clone(),
@@ -483,10 +499,12 @@ impl CoreRuntime {
                                     .session_manager
                                     .add_message(tool_result_message.clone())
                                     .await;
-                                let _ = event_sender.send(RuntimeEvent::MessageCreated {
-                                    session_id: task.session_id.clone(),
-                                    message: tool_result_message.clone(),
-                                });
+                                let _ = event_sender
+                                    .send(RuntimeEvent::MessageCreated {
+                                        session_id: task.session_id.clone(),
+                                        message: tool_result_message.clone(),
+                                    })
+                                    .await;
                                 messages.push(tool_result_message);
                             }
                         }
@@ -494,10 +512,12 @@ impl CoreRuntime {
                 }
                 Ok(AgentLoopResult::WaitingForApproval { request }) => {
                     *task_state.write().await = RuntimeTaskState::WaitingForUser;
-                    let _ = event_sender.send(RuntimeEvent::ToolCallRequested {
-                        task_id: task.id.clone(),
-                        request,
-                    });
+                    let _ = event_sender
+                        .send(RuntimeEvent::ToolCallRequested {
+                            task_id: task.id.clone(),
+                            request,
+                        })
+                        .await;
                     break;
                 }
                 Ok(AgentLoopResult::Error { message }) => {
@@ -575,24 +595,30 @@ impl CoreRuntime {
             .await;
 
         // Emit completion event
-        let _ = event_sender.send(RuntimeEvent::TaskStateChanged {
-            task_id: task.id.clone(),
-            state: final_state,
-            previous_state,
-        });
+        let _ = event_sender
+            .send(RuntimeEvent::TaskStateChanged {
+                task_id: task.id.clone(),
+                state: final_state,
+                previous_state,
+            })
+            .await;
 
-        let _ = event_sender.send(RuntimeEvent::TaskCompleted {
-            task_id: task.id.clone(),
-            session_id: task.session_id.clone(),
-        });
+        let _ = event_sender
+            .send(RuntimeEvent::TaskCompleted {
+                task_id: task.id.clone(),
+                session_id: task.session_id.clone(),
+            })
+            .await;
 
         if let Some(err) = error {
             log::error!("[Runtime] Task {} failed: {}", task.id, err);
-            let _ = event_sender.send(RuntimeEvent::Error {
-                task_id: Some(task.id.clone()),
-                session_id: Some(task.session_id.clone()),
-                message: err,
-            });
+            let _ = event_sender
+                .send(RuntimeEvent::Error {
+                    task_id: Some(task.id.clone()),
+                    session_id: Some(task.session_id.clone()),
+                    message: err,
+                })
+                .await;
         }
     }
 
@@ -608,8 +634,7 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
-    async fn create_test_runtime() -> (CoreRuntime, TempDir, mpsc::UnboundedReceiver<RuntimeEvent>)
-    {
+    async fn create_test_runtime() -> (CoreRuntime, TempDir, EventReceiver) {
         let temp_dir = TempDir::new().unwrap();
         let storage = Storage::new(
             temp_dir.path().to_path_buf(),
@@ -618,7 +643,7 @@ mod tests {
         .await
         .expect("Failed to create storage");
 
-        let (tx, rx) = mpsc::unbounded_channel();
+        let (tx, rx) = event_channel();
         let provider_registry = ProviderRegistry::default();
         let db = storage.settings.get_db();
         let api_key_manager = ApiKeyManager::new(db, temp_dir.path().to_path_buf());
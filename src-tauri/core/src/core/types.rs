@@ -1,6 +1,7 @@
 //! Core Runtime Types
 //! Types used by the core runtime for task/session lifecycle and agent loop
 
+use crate::llm::ai_services::stream_runner::DEFAULT_STREAM_BUFFER_SIZE;
 use crate::storage::models::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -138,6 +139,13 @@ pub struct AgentLoopConfig {
     pub enable_tools: bool,
     /// Tools available to the agent
     pub available_tools: Vec<String>,
+    /// Stream events buffered between the provider and the loop before reading pauses
+    #[serde(default = "default_stream_buffer_size")]
+    pub stream_buffer_size: usize,
+}
+
+fn default_stream_buffer_size() -> usize {
+    DEFAULT_STREAM_BUFFER_SIZE
 }
 
 impl Default for AgentLoopConfig {
@@ -148,6 +156,7 @@ impl Default for AgentLoopConfig {
             temperature: 0.7,
             enable_tools: true,
             available_tools: vec![],
+            stream_buffer_size: DEFAULT_STREAM_BUFFER_SIZE,
         }
     }
 }
@@ -219,11 +228,21 @@ pub enum RuntimeEvent {
     },
 }
 
+/// Runtime events queued for the UI before the agent loop stops reading the stream
+pub const RUNTIME_EVENT_BUFFER_SIZE: usize = 256;
+
 /// Channel sender for runtime events
-pub type EventSender = mpsc::UnboundedSender<RuntimeEvent>;
+///
+/// Bounded so a slow consumer holds the agent loop, and through it the provider stream
+pub type EventSender = mpsc::Sender<RuntimeEvent>;
 
 /// Channel receiver for runtime events
-pub type EventReceiver = mpsc::UnboundedReceiver<RuntimeEvent>;
+pub type EventReceiver = mpsc::Receiver<RuntimeEvent>;
+
+/// Create a runtime event channel with the default capacity
+pub fn event_channel() -> (EventSender, EventReceiver) {
+    mpsc::channel(RUNTIME_EVENT_BUFFER_SIZE)
+}
 
 /// Handle to a running task for external control
 #[derive(Debug, Clone)]
@@ -129,8 +129,9 @@ mod tests {
     use crate::database::Database;
     use crate::llm::auth::api_key_manager::ApiKeyManager;
     use crate::llm::providers::provider_registry::ProviderRegistry;
+    use crate::llm::testing::fixtures::provider_config;
     use crate::llm::types::{
-        AuthType, ModelConfig, ModelPricing, ModelsConfiguration, ProtocolType, ProviderConfig,
+        ModelConfig, ModelPricing, ModelsConfiguration, ProtocolType, ProviderConfig,
     };
     use std::collections::HashMap;
     use std::sync::Arc;
@@ -155,19 +156,8 @@ mod tests {
             .expect("set api key");
 
         let provider_config = ProviderConfig {
-            id: "openai".to_string(),
-            name: "openai".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "http://localhost".to_string(),
             api_key_name: "TEST_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: AuthType::Bearer,
+            ..provider_config("openai", ProtocolType::OpenAiCompatible, "http://localhost")
         };
         let registry = ProviderRegistry::new(vec![provider_config]);
 
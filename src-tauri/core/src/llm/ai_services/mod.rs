@@ -1,9 +1,11 @@
 pub mod completion_service;
 pub mod context_compaction_service;
 pub mod git_message_service;
+pub mod model_comparison;
 pub mod model_resolver;
 pub mod pricing_service;
 pub mod prompt_enhancement_service;
+pub mod request_bundle;
 pub mod stream_collector;
 pub mod stream_runner;
 pub mod task_title_service;
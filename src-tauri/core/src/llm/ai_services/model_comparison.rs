@@ -0,0 +1,266 @@
+// Side-by-side model comparison
+// One request fans out to several provider/model pairs at once and their streams are
+// interleaved, each event tagged with the pair it came from
+
+use crate::llm::ai_services::stream_runner::{StreamRunner, DEFAULT_STREAM_BUFFER_SIZE};
+use crate::llm::types::{StreamEvent, StreamTextRequest};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Provider and model one comparison source sends to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ComparisonTarget {
+    #[serde(rename = "providerId")]
+    pub provider_id: String,
+    pub model: String,
+}
+
+impl ComparisonTarget {
+    /// `model@provider` identifier that pins the model to this provider
+    pub fn model_identifier(&self) -> String {
+        format!("{}@{}", self.model, self.provider_id)
+    }
+}
+
+/// Event of one comparison source, tagged with the source's index in the target list
+#[derive(Debug, Clone, Serialize)]
+pub struct TaggedStreamEvent {
+    pub source: usize,
+    pub event: StreamEvent,
+}
+
+/// Stream `request` to every target at once through `runner`, forwarding the events of all
+/// sources into `sender` as they arrive
+/// Returns each target's result in target order; see `fan_out` for how failures are reported
+pub async fn compare_to(
+    runner: &StreamRunner,
+    request: &StreamTextRequest,
+    targets: &[ComparisonTarget],
+    timeout: Duration,
+    sender: mpsc::Sender<TaggedStreamEvent>,
+) -> Vec<Result<(), String>> {
+    fan_out(
+        targets.len(),
+        move |source, source_sender| {
+            let mut request = request.clone();
+            request.model = targets[source].model_identifier();
+            runner.stream_to(request, timeout, source_sender)
+        },
+        sender,
+    )
+    .await
+}
+
+/// Run `produce` for sources `0..sources` concurrently, each with its own sender, and forward
+/// their events into `sender` tagged with the source index
+/// A source that fails ends with an `Error` event carrying its message; the other sources keep
+/// streaming. Returns once every source finished, with their results in order
+pub async fn fan_out<F, Fut>(
+    sources: usize,
+    produce: F,
+    sender: mpsc::Sender<TaggedStreamEvent>,
+) -> Vec<Result<(), String>>
+where
+    F: Fn(usize, mpsc::Sender<StreamEvent>) -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let runs = (0..sources).map(|source| {
+        let (source_sender, mut receiver) = mpsc::channel(DEFAULT_STREAM_BUFFER_SIZE);
+        let produce = produce(source, source_sender);
+        let sender = sender.clone();
+        async move {
+            let forward = async {
+                // A dropped consumer stops forwarding; the source still runs to completion
+                while let Some(event) = receiver.recv().await {
+                    if sender
+                        .send(TaggedStreamEvent { source, event })
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            };
+            let (result, ()) = tokio::join!(produce, forward);
+            if let Err(message) = &result {
+                let event = StreamEvent::Error {
+                    message: message.clone(),
+                };
+                let _ = sender.send(TaggedStreamEvent { source, event }).await;
+            }
+            result
+        }
+    });
+    join_all(runs).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::llm::auth::api_key_manager::ApiKeyManager;
+    use crate::llm::error::LlmError;
+    use crate::llm::providers::provider::{Provider, ProviderContext};
+    use crate::llm::testing::mock_provider::MockProvider;
+    use crate::llm::types::{Message, MessageContent};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    async fn api_keys() -> (TempDir, ApiKeyManager) {
+        let dir = TempDir::new().expect("temp dir");
+        let db_path = dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect().await.expect("db connect");
+        let api_keys = ApiKeyManager::new(db, dir.path().to_path_buf());
+        (dir, api_keys)
+    }
+
+    fn context<'a>(
+        provider: &'a MockProvider,
+        api_keys: &'a ApiKeyManager,
+        messages: &'a [Message],
+    ) -> ProviderContext<'a> {
+        ProviderContext {
+            provider_config: provider.config(),
+            api_key_manager: api_keys,
+            model: "mock-model",
+            messages,
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        }
+    }
+
+    /// Fan out to `providers`, each sending its script one event at a time
+    async fn compare_mocks(
+        providers: &[MockProvider],
+        api_keys: &ApiKeyManager,
+    ) -> (Vec<Result<(), String>>, Vec<TaggedStreamEvent>) {
+        let messages = vec![Message::User {
+            content: MessageContent::Text("Which is faster?".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let messages = messages.as_slice();
+        let (sender, mut receiver) = mpsc::channel(64);
+        let results = fan_out(
+            providers.len(),
+            move |source, source_sender| {
+                let provider = &providers[source];
+                async move {
+                    let events = provider
+                        .stream(&context(provider, api_keys, messages))
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    for event in events {
+                        let _ = source_sender.send(event).await;
+                        tokio::task::yield_now().await;
+                    }
+                    Ok(())
+                }
+            },
+            sender,
+        )
+        .await;
+        let mut events = Vec::new();
+        while let Some(event) = receiver.recv().await {
+            events.push(event);
+        }
+        (results, events)
+    }
+
+    fn texts_of(events: &[TaggedStreamEvent], source: usize) -> Vec<&str> {
+        events
+            .iter()
+            .filter(|tagged| tagged.source == source)
+            .filter_map(|tagged| match &tagged.event {
+                StreamEvent::TextDelta { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn interleaves_sources_and_tags_each_event() {
+        let (_dir, api_keys) = api_keys().await;
+        let providers = [
+            MockProvider::new("openai").with_text("gpt-4o", &["A", "B", "C"]),
+            MockProvider::new("anthropic").with_text("claude-sonnet-4-5", &["x", "y", "z"]),
+        ];
+
+        let (results, events) = compare_mocks(&providers, &api_keys).await;
+
+        assert!(results.iter().all(Result::is_ok), "{:?}", results);
+        assert_eq!(texts_of(&events, 0), ["A", "B", "C"]);
+        assert_eq!(texts_of(&events, 1), ["x", "y", "z"]);
+        let starts: Vec<_> = events
+            .iter()
+            .filter_map(|tagged| match &tagged.event {
+                StreamEvent::Start { model, .. } => Some((tagged.source, model.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert!(starts.contains(&(0, "gpt-4o")), "{:?}", starts);
+        assert!(starts.contains(&(1, "claude-sonnet-4-5")), "{:?}", starts);
+        // The second source starts before the first one is done
+        let first_of_second = events.iter().position(|tagged| tagged.source == 1);
+        let last_of_first = events.iter().rposition(|tagged| tagged.source == 0);
+        assert!(first_of_second < last_of_first, "{:?}", events);
+    }
+
+    #[tokio::test]
+    async fn a_failing_source_does_not_stop_the_others() {
+        let (_dir, api_keys) = api_keys().await;
+        let providers = [
+            MockProvider::new("broken")
+                .with_text("mock-model", &["never sent"])
+                .with_error(LlmError::Auth("bad key".to_string())),
+            MockProvider::new("working").with_text("mock-model", &["still", " here"]),
+        ];
+
+        let (results, events) = compare_mocks(&providers, &api_keys).await;
+
+        assert!(results[0].as_ref().is_err_and(|e| e.contains("bad key")));
+        assert!(results[1].is_ok());
+        let failed: Vec<_> = events.iter().filter(|tagged| tagged.source == 0).collect();
+        assert_eq!(failed.len(), 1);
+        match &failed[0].event {
+            StreamEvent::Error { message } => assert!(message.contains("bad key")),
+            other => panic!("Expected Error, got {:?}", other),
+        }
+        assert_eq!(texts_of(&events, 1), ["still", " here"]);
+    }
+
+    #[test]
+    fn targets_pin_the_model_to_their_provider() {
+        let target = ComparisonTarget {
+            provider_id: "openrouter".to_string(),
+            model: "gpt-4o".to_string(),
+        };
+
+        assert_eq!(target.model_identifier(), "gpt-4o@openrouter");
+    }
+}
@@ -151,6 +151,7 @@ mod tests {
             output_tokens: 50,
             cached_input_tokens: Some(40),
             cache_creation_input_tokens: Some(10),
+            estimated: false,
         };
 
         let cost = service
@@ -184,6 +185,7 @@ mod tests {
             output_tokens: 60,
             cached_input_tokens: Some(30),
             cache_creation_input_tokens: Some(20),
+            estimated: false,
         };
 
         let cost = service
@@ -210,6 +212,7 @@ mod tests {
             output_tokens: 5,
             cached_input_tokens: None,
             cache_creation_input_tokens: None,
+            estimated: false,
         };
 
         let cost = service
@@ -232,6 +235,7 @@ mod tests {
             output_tokens: 500,
             cached_input_tokens: None,
             cache_creation_input_tokens: None,
+            estimated: false,
         };
 
         let cost = service
@@ -259,6 +263,7 @@ mod tests {
             output_tokens: 0,
             cached_input_tokens: None,
             cache_creation_input_tokens: None,
+            estimated: false,
         };
 
         let cost = service
@@ -281,6 +286,7 @@ mod tests {
             output_tokens: 50,
             cached_input_tokens: None,
             cache_creation_input_tokens: None,
+            estimated: false,
         };
 
         let cost = service
@@ -304,6 +310,7 @@ mod tests {
             output_tokens: 50,
             cached_input_tokens: Some(20),
             cache_creation_input_tokens: Some(10),
+            estimated: false,
         };
 
         let cost = service
@@ -335,6 +342,7 @@ mod tests {
                 output_tokens: 500,
                 cached_input_tokens: None,
                 cache_creation_input_tokens: None,
+                estimated: false,
             },
             model_configs: configs,
         };
@@ -359,6 +367,7 @@ mod tests {
             output_tokens: 500_000,
             cached_input_tokens: Some(100_000),
             cache_creation_input_tokens: Some(50_000),
+            estimated: false,
         };
 
         let cost = service
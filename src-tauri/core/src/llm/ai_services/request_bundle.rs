@@ -0,0 +1,230 @@
+// Portable request bundles for support
+// A user's request is exported with the request it built, credentials redacted, so it can be
+// sent again on another machine with that machine's own keys
+
+use crate::llm::ai_services::stream_runner::StreamRunner;
+use crate::llm::providers::provider::DryRunRequest;
+use crate::llm::request_log;
+use crate::llm::types::{StreamEvent, StreamTextRequest};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Format version written into new bundles; newer bundles are rejected on import
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Everything needed to reproduce one request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestBundle {
+    pub version: u32,
+    #[serde(rename = "appVersion")]
+    pub app_version: String,
+    #[serde(rename = "exportedAt")]
+    pub exported_at: i64,
+    #[serde(rename = "providerId")]
+    pub provider_id: String,
+    /// Model key the request resolved to on the exporting machine
+    pub model: String,
+    /// The request as the caller made it, before templates and trimming were applied
+    pub request: StreamTextRequest,
+    /// What was sent for it, with credentials redacted
+    pub http: DryRunRequest,
+}
+
+impl RequestBundle {
+    /// Parse an exported bundle
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let bundle: Self = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid request bundle: {} / 请求包格式无效", e))?;
+        if bundle.version > BUNDLE_VERSION {
+            return Err(format!(
+                "Request bundle version {} is newer than the supported {} / 请求包版本过新",
+                bundle.version, BUNDLE_VERSION
+            ));
+        }
+        Ok(bundle)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize request bundle: {}", e))
+    }
+
+    /// Request that sends the bundle again, pinned to the provider it was exported from
+    /// The request id is dropped so the replay is not mistaken for the original on either side
+    pub fn replay_request(&self) -> StreamTextRequest {
+        StreamTextRequest {
+            model: format!("{}@{}", self.model, self.provider_id),
+            dry_run: None,
+            request_id: None,
+            ..self.request.clone()
+        }
+    }
+}
+
+/// Bundle `request` the way `runner` would send it, without sending it
+/// Credentials are redacted from the headers, URL and every JSON field of the bundle,
+/// provider options included; message text is kept as-is
+pub async fn export(
+    runner: &StreamRunner,
+    request: StreamTextRequest,
+    timeout: Duration,
+) -> Result<RequestBundle, String> {
+    let (model, provider_id, _, _) = runner.resolve_model_info(&request.model).await?;
+    let request = StreamTextRequest {
+        dry_run: None,
+        // Spans of the exporting machine mean nothing on the replaying one
+        trace_context: None,
+        ..request
+    };
+    let mut http = None;
+    runner
+        .stream(
+            StreamTextRequest {
+                dry_run: Some(true),
+                ..request.clone()
+            },
+            timeout,
+            |event| {
+                if let StreamEvent::DryRun(built) = event {
+                    http = Some(built);
+                }
+            },
+        )
+        .await?;
+    let http =
+        http.ok_or_else(|| "Dry run did not build a request / 试运行未生成请求".to_string())?;
+
+    let bundle = RequestBundle {
+        version: BUNDLE_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: chrono::Utc::now().timestamp_millis(),
+        provider_id,
+        model,
+        request,
+        http,
+    };
+    // The dry run already hid the credentials it knows of; this covers the request fields too
+    let value = serde_json::to_value(&bundle)
+        .map_err(|e| format!("Failed to serialize request bundle: {}", e))?;
+    serde_json::from_value(request_log::redactor().redact_json(&value))
+        .map_err(|e| format!("Failed to redact request bundle: {}", e))
+}
+
+/// Send `bundle` again through `runner` with this machine's credentials
+pub async fn replay<F>(
+    runner: &StreamRunner,
+    bundle: &RequestBundle,
+    timeout: Duration,
+    on_event: F,
+) -> Result<(), String>
+where
+    F: FnMut(StreamEvent) + Send,
+{
+    log::info!(
+        "[RequestBundle] Replaying {} request exported at {} by version {}",
+        bundle.provider_id,
+        bundle.exported_at,
+        bundle.app_version
+    );
+    runner
+        .stream(bundle.replay_request(), timeout, on_event)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::providers::provider_registry::ProviderRegistry;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::testing::mock_server::start_capture_server;
+    use crate::llm::types::ProtocolType;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+
+    const API_KEY: &str = "sk-test-0123456789";
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    async fn runner(base_url: String) -> (StreamRunner, TempDir) {
+        let (dir, api_keys) = api_key_manager_with(&[("api_key_test", API_KEY)]).await;
+
+        let registry = ProviderRegistry::new(vec![provider_config(
+            "test",
+            ProtocolType::OpenAiCompatible,
+            &base_url,
+        )]);
+        (StreamRunner::new(registry, api_keys), dir)
+    }
+
+    fn request() -> StreamTextRequest {
+        serde_json::from_value(json!({
+            "model": "test-model@test",
+            "messages": [{ "role": "user", "content": "Why does my build fail?" }],
+            "temperature": 0.3,
+            "maxTokens": 256,
+            "providerOptions": { "openai": { "apiKey": "sk-leaked-in-options" } }
+        }))
+        .expect("request")
+    }
+
+    #[tokio::test]
+    async fn exported_bundle_round_trips_and_replays_the_same_body() {
+        let (base_url, captured) =
+            start_capture_server(200, b"data: [DONE]\n\n".to_vec()).expect("server");
+        let (runner, _dir) = runner(base_url).await;
+
+        let bundle = export(&runner, request(), TIMEOUT).await.expect("export");
+        let json = bundle.to_json().expect("to json");
+        let imported = RequestBundle::from_json(&json).expect("from json");
+
+        assert_eq!(
+            serde_json::to_value(&imported).expect("value"),
+            serde_json::to_value(&bundle).expect("value")
+        );
+        assert_eq!(imported.provider_id, "test");
+        assert_eq!(imported.model, "test-model");
+        assert_eq!(imported.replay_request().model, "test-model@test");
+
+        replay(&runner, &imported, TIMEOUT, |_| {})
+            .await
+            .expect("replay");
+        let sent = captured.recv().expect("captured request");
+        let body: Value = serde_json::from_slice(&sent.body).expect("json body");
+        assert_eq!(body, imported.http.body);
+        // Sent with the replaying machine's own key, not the redacted one
+        assert_eq!(
+            sent.header("authorization"),
+            Some(format!("Bearer {}", API_KEY).as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn exported_bundles_contain_no_secrets() {
+        // A dry run sends nothing, so the endpoint is never contacted
+        let (runner, _dir) = runner("http://127.0.0.1:9".to_string()).await;
+
+        let json = export(&runner, request(), TIMEOUT)
+            .await
+            .expect("export")
+            .to_json()
+            .expect("to json");
+
+        for secret in [API_KEY, "sk-leaked-in-options"] {
+            assert!(!json.contains(secret), "{} leaked into {}", secret, json);
+        }
+        assert!(json.contains("[REDACTED]"), "{}", json);
+        assert!(json.contains("Why does my build fail?"), "{}", json);
+    }
+
+    #[test]
+    fn rejects_bundles_from_a_newer_format() {
+        let err = RequestBundle::from_json(&format!(
+            r#"{{"version":{},"appVersion":"9.0.0","exportedAt":0,"providerId":"test",
+            "model":"m","request":{{"model":"m","messages":[]}},
+            "http":{{"url":"","headers":{{}},"body":null}}}}"#,
+            BUNDLE_VERSION + 1
+        ))
+        .expect_err("newer bundle");
+
+        assert!(err.contains("newer than the supported"), "{}", err);
+    }
+}
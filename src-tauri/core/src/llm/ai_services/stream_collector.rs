@@ -1,4 +1,5 @@
 use crate::llm::ai_services::stream_runner::StreamRunner;
+use crate::llm::request_id::RequestIds;
 use crate::llm::types::{Message, StreamEvent, StreamTextRequest};
 use futures_util::StreamExt;
 use std::time::{Duration, Instant};
@@ -21,6 +22,7 @@ impl StreamCollector {
         let mut first_delta_time: Option<Duration> = None;
         let mut delta_count = 0;
         let mut full_text = String::new();
+        let mut request_ids = None;
 
         let timeout = timeout.unwrap_or(Duration::from_secs(300));
 
@@ -39,6 +41,7 @@ impl StreamCollector {
                             delta_count += 1;
                             full_text.push_str(&text);
                         }
+                        StreamEvent::RequestIds(ids) => request_ids = Some(ids),
                         StreamEvent::Done { .. } => break,
                         StreamEvent::Error { message } => {
                             return Err(format!("Stream error: {}", message));
@@ -59,6 +62,7 @@ impl StreamCollector {
             total_time_ms: total_time.as_millis() as u64,
             time_to_first_delta_ms: first_delta_time.map(|d| d.as_millis() as u64),
             delta_count,
+            request_ids,
         })
     }
 
@@ -72,6 +76,7 @@ impl StreamCollector {
         let mut first_delta_time: Option<Duration> = None;
         let mut delta_count = 0;
         let mut full_text = String::new();
+        let mut request_ids = None;
 
         runner
             .stream(request, timeout, |event| match event {
@@ -82,6 +87,7 @@ impl StreamCollector {
                     delta_count += 1;
                     full_text.push_str(&text);
                 }
+                StreamEvent::RequestIds(ids) => request_ids = Some(ids),
                 StreamEvent::Error { message } => {
                     log::error!("Stream error: {}", message);
                 }
@@ -96,6 +102,7 @@ impl StreamCollector {
             total_time_ms: total_time.as_millis() as u64,
             time_to_first_delta_ms: first_delta_time.map(|d| d.as_millis() as u64),
             delta_count,
+            request_ids,
         })
     }
 
@@ -106,6 +113,7 @@ impl StreamCollector {
             messages: vec![Message::User {
                 content: crate::llm::types::MessageContent::Text(prompt),
                 provider_options: None,
+                cache: false,
             }],
             tools: None,
             stream: Some(true),
@@ -113,9 +121,28 @@ impl StreamCollector {
             max_tokens: None,
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            template: None,
+            trim_strategy: None,
+            logprobs: None,
+            top_logprobs: None,
             provider_options: None,
             request_id: None,
             trace_context: None,
+            timeout_ms: None,
+            dry_run: None,
+            builtin_tools: None,
+            strip_headers: None,
+            max_attempts: None,
+            image_input_check: None,
+            reasoning_effort: None,
+            stall_timeout_ms: None,
+            idempotent: None,
         }
     }
 }
@@ -126,6 +153,8 @@ pub struct CollectResult {
     pub total_time_ms: u64,
     pub time_to_first_delta_ms: Option<u64>,
     pub delta_count: u32,
+    /// Client and provider IDs of the last call, for matching against provider logs
+    pub request_ids: Option<RequestIds>,
 }
 
 #[cfg(test)]
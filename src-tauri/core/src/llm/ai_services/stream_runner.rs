@@ -1,13 +1,36 @@
-use crate::llm::protocols::stream_parser::StreamParseState;
-use crate::llm::providers::provider::ProviderContext;
+use crate::llm::cancellation::cancellable;
+use crate::llm::context_window;
+use crate::llm::error::LlmError;
+use crate::llm::http_client::resolve_timeout;
+use crate::llm::image_input;
+use crate::llm::moderation;
+use crate::llm::protocols::stream_parser::{
+    parse_frame, split_frame, take_frame, StreamParseState,
+};
+use crate::llm::providers::provider::{BuiltRequest, CredentialRotation, ProviderContext};
 use crate::llm::providers::provider_registry::ProviderRegistry;
-use crate::llm::types::{StreamEvent, StreamTextRequest};
-use futures_util::StreamExt;
+use crate::llm::rate_limit::RateLimitInfo;
+use crate::llm::request_log;
+use crate::llm::retry::{RetryBudget, RetryPolicy, StreamReplay};
+use crate::llm::stream_metrics::StreamMetricsRecorder;
+use crate::llm::streaming::stall;
+use crate::llm::tokenize::UsageFallback;
+use crate::llm::types::{Message, StreamEvent, StreamTextRequest};
 use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Overall request deadline when the request sets no `timeout_ms`
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Events parsed ahead of the consumer before the HTTP read pauses
+pub const DEFAULT_STREAM_BUFFER_SIZE: usize = 64;
 
 pub struct StreamRunner {
     registry: ProviderRegistry,
     api_keys: crate::llm::auth::api_key_manager::ApiKeyManager,
+    cancel_token: Option<CancellationToken>,
+    buffer_size: usize,
 }
 
 impl StreamRunner {
@@ -15,9 +38,28 @@ impl StreamRunner {
         registry: ProviderRegistry,
         api_keys: crate::llm::auth::api_key_manager::ApiKeyManager,
     ) -> Self {
-        Self { registry, api_keys }
+        Self {
+            registry,
+            api_keys,
+            cancel_token: None,
+            buffer_size: DEFAULT_STREAM_BUFFER_SIZE,
+        }
     }
 
+    /// Abort the request and stop reading the stream once `token` is cancelled
+    pub fn with_cancel_token(mut self, token: CancellationToken) -> Self {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Capacity of the channel between the HTTP reader and the event consumer (min 1)
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size.max(1);
+        self
+    }
+
+    /// Stream events to `on_event` through a bounded channel, so a slow
+    /// consumer stops the response body from being read instead of queueing events
     pub async fn stream<F>(
         &self,
         request: StreamTextRequest,
@@ -27,7 +69,91 @@ impl StreamRunner {
     where
         F: FnMut(StreamEvent) + Send,
     {
-        let (_model_key, provider_id, provider_model_name) =
+        let (sender, mut receiver) = mpsc::channel(self.buffer_size);
+        let produce = self.stream_to(request, timeout, sender);
+        let consume = async {
+            // Ends once the producer drops the sender, after everything queued was delivered
+            while let Some(event) = receiver.recv().await {
+                on_event(event);
+            }
+        };
+        let (result, ()) = tokio::join!(produce, consume);
+        result
+    }
+
+    /// Send parsed events into `sender`, waiting for capacity when it is full
+    /// Returns early without error if the receiver is dropped
+    /// A stream that breaks off before its first event is resent under the provider's retry
+    /// policy; once anything was delivered the error is returned instead
+    /// The last event is always `Metrics`, also after an error or a cancellation
+    pub async fn stream_to(
+        &self,
+        request: StreamTextRequest,
+        timeout: Duration,
+        sender: mpsc::Sender<StreamEvent>,
+    ) -> Result<(), String> {
+        let mut metrics = StreamMetricsRecorder::start();
+        let result = self
+            .run_stream(request, timeout, &sender, &mut metrics)
+            .await;
+        let cancelled = self
+            .cancel_token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled);
+        let metrics = metrics.finish(cancelled);
+        log::debug!("[StreamRunner] Stream metrics: {:?}", metrics);
+        let _ = sender.send(StreamEvent::Metrics(metrics)).await;
+        result
+    }
+
+    /// Prompt tokens `messages` would cost on `model` (`model@provider` or a model key), using
+    /// the provider's counting endpoint where it has one and the local estimate otherwise
+    pub async fn count_tokens(&self, messages: &[Message], model: &str) -> Result<usize, String> {
+        let (_, provider_id, provider_model_name, _) = self.resolve_model_info(model).await?;
+        let provider = self
+            .registry
+            .create_provider(&provider_id)
+            .ok_or_else(|| format!("Provider not found: {}", provider_id))?;
+        let provider_ctx = ProviderContext {
+            provider_config: provider.config(),
+            api_key_manager: &self.api_keys,
+            model: &provider_model_name,
+            messages,
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: self.cancel_token.as_ref(),
+            stream: false,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+        provider.count_tokens(&provider_ctx).await
+    }
+
+    async fn run_stream(
+        &self,
+        mut request: StreamTextRequest,
+        timeout: Duration,
+        sender: &mpsc::Sender<StreamEvent>,
+        metrics: &mut StreamMetricsRecorder,
+    ) -> Result<(), String> {
+        let (model_key, provider_id, provider_model_name, context_window) =
             self.resolve_model_info(&request.model).await?;
 
         let provider = self
@@ -35,7 +161,29 @@ impl StreamRunner {
             .create_provider(&provider_id)
             .ok_or_else(|| format!("Provider not found: {}", provider_id))?;
         let provider_config = provider.config();
+        if let Some(template) = request.template.take() {
+            template.render_messages(&mut request.messages)?;
+        }
+        context_window::fit_request(&mut request, context_window);
+        // Loading the model list is only worth it when there are images to check
+        if image_input::has_image_content(&request.messages) {
+            let models = self.api_keys.load_models_config().await?;
+            image_input::check(
+                &request.messages,
+                &model_key,
+                &models,
+                provider.capabilities().supports_vision,
+                request.image_input_check.unwrap_or_default(),
+            )?;
+        }
+        // A dry run makes no network calls, moderation included
+        let dry_run = request.dry_run.unwrap_or(false);
+        if !dry_run {
+            let prompt = moderation::latest_user_text(&request.messages);
+            moderation::precheck(&self.api_keys, &self.registry, &provider_id, &prompt).await?;
+        }
 
+        let retry_budget = request.max_attempts.map(RetryBudget::new);
         let provider_ctx = ProviderContext {
             provider_config,
             api_key_manager: &self.api_keys,
@@ -46,102 +194,200 @@ impl StreamRunner {
             max_tokens: request.max_tokens,
             top_p: request.top_p,
             top_k: request.top_k,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.as_deref(),
+            response_format: request.response_format.as_ref(),
+            user_id: request.user_id.as_deref(),
+            base_url_override: request.base_url_override.as_deref(),
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            cancel_token: self.cancel_token.as_ref(),
+            stream: true,
+            logprobs: request.logprobs.unwrap_or(false),
+            top_logprobs: request.top_logprobs,
+            dry_run,
+            builtin_tools: request.builtin_tools.as_deref().unwrap_or_default(),
+            strip_headers: request.strip_headers.as_deref().unwrap_or_default(),
+            retry_budget: retry_budget.as_ref(),
+            reasoning_effort: request.reasoning_effort,
+            idempotent: request.idempotent.unwrap_or(true),
         };
 
         let built_request = provider.build_complete_request(&provider_ctx).await?;
-
-        let client = reqwest::Client::builder()
-            .connect_timeout(Duration::from_secs(10))
-            .timeout(Duration::from_secs(300))
-            .gzip(false)
-            .brotli(false)
-            .tcp_nodelay(true)
-            .pool_max_idle_per_host(5)
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
-
-        let mut req_builder = client.post(&built_request.url);
-        for (key, value) in &built_request.headers {
-            req_builder = req_builder.header(key, value);
+        if provider_ctx.dry_run {
+            let _ = sender
+                .send(StreamEvent::DryRun(built_request.dry_run()))
+                .await;
+            let _ = sender
+                .send(StreamEvent::Done {
+                    finish_reason: None,
+                })
+                .await;
+            return Ok(());
         }
-        req_builder = req_builder
-            .header("Accept", "text/event-stream")
-            .json(&built_request.body);
 
-        let response = req_builder
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
-        let status = response.status().as_u16();
-        if status >= 400 {
-            let text = response.text().await.unwrap_or_default();
-            return Err(format!("HTTP error {}: {}", status, text));
-        }
+        request_log::log_request(
+            "AI Service",
+            "POST",
+            &built_request.url,
+            &built_request.headers,
+            Some(&built_request.body),
+        );
+        // `timeout_ms: 0` leaves the request without a deadline
+        let request_timeout = resolve_timeout(request.timeout_ms, DEFAULT_REQUEST_TIMEOUT);
+        let policy =
+            RetryPolicy::for_provider(provider_config).for_idempotency(built_request.idempotent);
+        // A 429 is retried with the next key of the pool rather than the throttled one
+        let mut rotation = CredentialRotation::new(
+            provider.as_ref(),
+            &provider_ctx,
+            built_request,
+            |built: &BuiltRequest| built.stream_request(&client, &built.url, request_timeout),
+        );
+        // `timeout` caps every wait for a chunk; the request's stall window applies when shorter
+        let stall_window = stall::stall_timeout(&request).map_or(timeout, |w| w.min(timeout));
 
-        let mut stream = response.bytes_stream();
-        let mut buffer: Vec<u8> = Vec::new();
-        let mut state = StreamParseState::default();
+        // A stream that breaks off is resent only while nothing has reached the consumer
+        let mut replay = StreamReplay::new(policy).with_budget(provider_ctx.retry_budget);
+        let mut usage_fallback = UsageFallback::new(provider.protocol_type(), &request.messages);
 
-        while let Some(chunk) = tokio::time::timeout(timeout, stream.next())
-            .await
-            .map_err(|_| format!("Stream timeout after {:?}", timeout))?
-        {
-            let bytes = chunk.map_err(|e| format!("Stream error: {}", e))?;
-            if bytes.is_empty() {
-                continue;
+        'attempt: loop {
+            let attempt = rotation.request();
+            let response = cancellable(
+                provider_ctx.cancel_token,
+                policy.send_rotating_within(attempt, provider_ctx.retry_budget, &mut rotation),
+            )
+            .await?
+            .map_err(|e| format!("Request failed: {}", e))?;
+            let built_request = rotation.built();
+            let mut request_ids = built_request.request_ids.clone();
+            request_ids.record(response.headers());
+
+            let status = response.status().as_u16();
+            if status >= 400 {
+                if status == 429 {
+                    if let Some(api_key) = &built_request.api_key {
+                        self.api_keys.mark_key_rate_limited(api_key);
+                    }
+                }
+                let response_headers = response.headers().clone();
+                let text = response.text().await.unwrap_or_default();
+                request_log::log_response("AI Service", &built_request.url, status, Some(&text));
+                log::warn!("[StreamRunner] HTTP error {} for {:?}", status, request_ids);
+                return Err(LlmError::from_response_parts(status, &response_headers, text).into());
             }
-            buffer.extend_from_slice(&bytes);
-
-            while let Some((idx, delimiter_len)) = find_sse_delimiter(&buffer) {
-                let event_bytes = buffer[..idx].to_vec();
-                buffer.drain(..idx + delimiter_len);
-
-                let event_str = String::from_utf8(event_bytes)
-                    .map_err(|e| format!("Invalid UTF-8 in SSE event: {}", e))?;
-
-                if let Some(parsed) = parse_sse_event(&event_str) {
-                    let parsed_result = provider
-                        .parse_stream_event_with_context(
-                            &provider_ctx,
-                            parsed.event.as_deref(),
-                            &parsed.data,
-                            &mut state,
-                        )
-                        .await;
-
-                    match parsed_result {
-                        Ok(Some(event)) => {
-                            on_event(event);
-
-                            if !state.pending_events.is_empty() {
-                                for pending in state.pending_events.drain(..) {
-                                    on_event(pending);
-                                }
-                            }
+
+            request_log::log_response("AI Service", &built_request.url, status, None);
+            // Metadata is sent for every attempt, since a resent stream is a new call on the
+            // provider side, and does not count as delivered output
+            let sent = cancellable(
+                provider_ctx.cancel_token,
+                sender.send(StreamEvent::RequestIds(request_ids)),
+            )
+            .await?;
+            if sent.is_err() {
+                return Ok(());
+            }
+            if let Some(info) = RateLimitInfo::from_headers(response.headers()) {
+                let sent = cancellable(
+                    provider_ctx.cancel_token,
+                    sender.send(StreamEvent::RateLimit(info)),
+                )
+                .await?;
+                if sent.is_err() {
+                    return Ok(());
+                }
+            }
+
+            let mut stream = response.bytes_stream();
+            let stream_format = provider.stream_format();
+            let mut buffer: Vec<u8> = Vec::new();
+            let mut state = StreamParseState {
+                tolerant_json: provider.config().tolerant_stream_json,
+                ..StreamParseState::default()
+            };
+
+            loop {
+                let next = cancellable(
+                    provider_ctx.cancel_token,
+                    stall::next_chunk(&mut stream, Some(stall_window)),
+                )
+                .await?;
+                let chunk = match next {
+                    Ok(Some(chunk)) => {
+                        chunk.map_err(|e| LlmError::Network(format!("Stream error: {}", e)))
+                    }
+                    Ok(None) => return Ok(()),
+                    Err(stalled) => Err(stalled),
+                };
+                let bytes = match chunk {
+                    Ok(bytes) => bytes,
+                    Err(err) => match replay.next_delay(&err) {
+                        Some(delay) => {
+                            log::warn!(
+                                "[StreamRunner] {} before any event was delivered, resending after {}ms",
+                                err,
+                                delay.as_millis()
+                            );
+                            cancellable(provider_ctx.cancel_token, tokio::time::sleep(delay))
+                                .await?;
+                            continue 'attempt;
                         }
-                        Ok(None) => {
-                            if !state.pending_events.is_empty() {
-                                for pending in state.pending_events.drain(..) {
-                                    on_event(pending);
+                        None => return Err(err.to_string()),
+                    },
+                };
+                if bytes.is_empty() {
+                    continue;
+                }
+                buffer.extend_from_slice(&bytes);
+
+                while let Some(event_bytes) = take_frame(stream_format, &mut buffer) {
+                    let event_str = String::from_utf8(event_bytes)
+                        .map_err(|e| format!("Invalid UTF-8 in SSE event: {}", e))?;
+
+                    let frames = parse_frame(stream_format, &event_str)
+                        .map(|frame| split_frame(frame, state.tolerant_json))
+                        .unwrap_or_default();
+                    for parsed in frames {
+                        let parsed_result = provider
+                            .parse_stream_event_with_context(
+                                &provider_ctx,
+                                parsed.event.as_deref(),
+                                &parsed.data,
+                                &mut state,
+                            )
+                            .await;
+
+                        let events = parsed_result?
+                            .into_iter()
+                            .chain(std::mem::take(&mut state.pending_events));
+                        for event in events {
+                            let estimate = usage_fallback.before(&event);
+                            for event in estimate.into_iter().chain(Some(event)) {
+                                metrics.observe(&event);
+                                let sent =
+                                    cancellable(provider_ctx.cancel_token, sender.send(event))
+                                        .await?;
+                                if sent.is_err() {
+                                    // Consumer went away, nobody is left to read the rest
+                                    return Ok(());
                                 }
+                                replay.mark_delivered();
                             }
                         }
-                        Err(err) => return Err(err),
                     }
                 }
             }
         }
-
-        Ok(())
     }
 
-    async fn resolve_model_info(
+    pub(crate) async fn resolve_model_info(
         &self,
         model_identifier: &str,
-    ) -> Result<(String, String, String), String> {
+    ) -> Result<(String, String, String, Option<u32>), String> {
         let models = self.api_keys.load_models_config().await?;
         let api_keys = self.api_keys.load_api_keys().await?;
         let custom_providers = self.api_keys.load_custom_providers().await?;
@@ -162,41 +408,587 @@ impl StreamRunner {
                 &models,
             );
 
-        Ok((model_key, provider_id, provider_model_name))
+        let context_window = models
+            .models
+            .get(&model_key)
+            .and_then(|model| model.context_length);
+
+        Ok((model_key, provider_id, provider_model_name, context_window))
     }
 }
 
-fn find_sse_delimiter(buf: &[u8]) -> Option<(usize, usize)> {
-    if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
-        return Some((pos, 4));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ai_services::stream_collector::StreamCollector;
+    use crate::llm::request_id::DEFAULT_REQUEST_ID_HEADER;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::testing::mock_server::{
+        start_capture_sequence_server, start_capture_server_with_headers, start_chunked_server,
+        start_raw_server, start_sequence_server,
+    };
+    use crate::llm::types::{MessageContent, ProtocolType, ProviderConfig};
+    use tempfile::TempDir;
+
+    const DELTA_COUNT: usize = 32;
+
+    fn sse_body() -> String {
+        let mut body = String::new();
+        for i in 0..DELTA_COUNT {
+            body.push_str(&format!(
+                "data: {{\"choices\":[{{\"index\":0,\"delta\":{{\"content\":\"{}\"}}}}]}}\n\n",
+                i
+            ));
+        }
+        body.push_str(
+            "data: {\"choices\":[{\"index\":0,\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+        );
+        body.push_str("data: [DONE]\n\n");
+        body
     }
-    if let Some(pos) = buf.windows(2).position(|w| w == b"\n\n") {
-        return Some((pos, 2));
+
+    async fn setup_runner(base_url: String) -> (StreamRunner, TempDir) {
+        setup_runner_with(base_url, false).await
     }
-    None
-}
 
-struct SseEvent {
-    event: Option<String>,
-    data: String,
-}
+    async fn setup_runner_with(
+        base_url: String,
+        tolerant_stream_json: bool,
+    ) -> (StreamRunner, TempDir) {
+        let (dir, api_keys) = api_key_manager_with(&[("api_key_test", "test-key")]).await;
+
+        let registry = ProviderRegistry::new(vec![ProviderConfig {
+            tolerant_stream_json,
+            ..provider_config("test", ProtocolType::OpenAiCompatible, &base_url)
+        }]);
+
+        (StreamRunner::new(registry, api_keys), dir)
+    }
 
-fn parse_sse_event(raw: &str) -> Option<SseEvent> {
-    let mut event: Option<String> = None;
-    let mut data_lines = Vec::new();
-    for line in raw.lines() {
-        if let Some(rest) = line.strip_prefix("event:") {
-            event = Some(rest.trim().to_string());
-        } else if let Some(rest) = line.strip_prefix("data:") {
-            let data = rest.strip_prefix(' ').unwrap_or(rest);
-            data_lines.push(data.to_string());
+    fn request() -> StreamTextRequest {
+        StreamTextRequest {
+            model: "test-model@test".to_string(),
+            messages: vec![Message::User {
+                content: MessageContent::Text("hi".to_string()),
+                provider_options: None,
+                cache: false,
+            }],
+            tools: None,
+            stream: Some(true),
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            template: None,
+            trim_strategy: None,
+            logprobs: None,
+            top_logprobs: None,
+            provider_options: None,
+            request_id: None,
+            trace_context: None,
+            timeout_ms: None,
+            dry_run: None,
+            builtin_tools: None,
+            strip_headers: None,
+            max_attempts: None,
+            image_input_check: None,
+            reasoning_effort: None,
+            stall_timeout_ms: None,
+            idempotent: None,
         }
     }
-    if data_lines.is_empty() {
-        return None;
+
+    /// 200 response announcing `declared_len` body bytes; the connection closes after `body`
+    fn raw_sse_response(body: &str, declared_len: usize) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            declared_len, body
+        )
+    }
+
+    #[tokio::test]
+    async fn resends_stream_that_drops_before_first_event() {
+        let body = sse_body();
+        let (base_url, hits) = start_raw_server(vec![
+            raw_sse_response("", 64),
+            raw_sse_response(&body, body.len()),
+        ])
+        .expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+
+        let mut deltas = 0;
+        runner
+            .stream(request(), Duration::from_secs(5), |event| {
+                if matches!(event, StreamEvent::TextDelta { .. }) {
+                    deltas += 1;
+                }
+            })
+            .await
+            .expect("stream after resend");
+
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(deltas, DELTA_COUNT);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_headers_do_not_stop_a_resend() {
+        let body = sse_body();
+        // Announces rate-limit quotas like OpenAI does on every response, then drops
+        let first = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+            x-ratelimit-remaining-requests: 99\r\nContent-Length: 64\r\n\
+            Connection: close\r\n\r\n"
+            .to_string();
+        let (base_url, hits) =
+            start_raw_server(vec![first, raw_sse_response(&body, body.len())]).expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+
+        let mut rate_limits = 0;
+        let mut deltas = 0;
+        runner
+            .stream(request(), Duration::from_secs(5), |event| match event {
+                StreamEvent::RateLimit(_) => rate_limits += 1,
+                StreamEvent::TextDelta { .. } => deltas += 1,
+                _ => {}
+            })
+            .await
+            .expect("stream after resend");
+
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(rate_limits, 1);
+        assert_eq!(deltas, DELTA_COUNT);
+    }
+
+    #[tokio::test]
+    async fn http_error_reports_the_status_and_provider_message() {
+        let (base_url, _hits) = start_sequence_server(vec![(
+            400,
+            r#"{"error":{"message":"Unknown model test-model"}}"#.to_string(),
+        )])
+        .expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+
+        let err = runner
+            .stream(request(), Duration::from_secs(5), |_| {})
+            .await
+            .expect_err("http error");
+
+        assert!(err.contains("HTTP 400"), "{}", err);
+        assert!(err.contains("Unknown model test-model"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn throttled_request_is_retried_with_the_next_pooled_key() {
+        let (base_url, captured) =
+            start_capture_sequence_server(vec![(429, "slow down".to_string()), (200, sse_body())])
+                .expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+        runner
+            .api_keys
+            .set_setting("api_key_test", "key-a,key-b")
+            .await
+            .expect("set key pool");
+
+        let mut deltas = 0;
+        runner
+            .stream(request(), Duration::from_secs(5), |event| {
+                if matches!(event, StreamEvent::TextDelta { .. }) {
+                    deltas += 1;
+                }
+            })
+            .await
+            .expect("stream after rotating keys");
+
+        let keys: Vec<String> = captured
+            .try_iter()
+            .filter_map(|request| request.header("Authorization").map(str::to_string))
+            .collect();
+        assert_eq!(keys.len(), 2);
+        assert_ne!(keys[0], keys[1], "429 retry should switch keys");
+        assert!(runner
+            .api_keys
+            .is_key_rate_limited(keys[0].trim_start_matches("Bearer ")));
+        assert_eq!(deltas, DELTA_COUNT);
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_request_is_not_resent() {
+        let body = sse_body();
+        let (base_url, hits) = start_raw_server(vec![
+            raw_sse_response("", 64),
+            raw_sse_response(&body, body.len()),
+        ])
+        .expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+
+        let mut deltas = 0;
+        let err = runner
+            .stream(
+                StreamTextRequest {
+                    idempotent: Some(false),
+                    ..request()
+                },
+                Duration::from_secs(5),
+                |event| {
+                    if matches!(event, StreamEvent::TextDelta { .. }) {
+                        deltas += 1;
+                    }
+                },
+            )
+            .await
+            .expect_err("stream error");
+
+        assert!(err.contains("Stream error"), "{}", err);
+        assert_eq!(deltas, 0);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_resend_after_partial_stream_delivery() {
+        let body = sse_body();
+        let partial: String = body.split_inclusive("\n\n").take(2).collect();
+        let (base_url, hits) = start_raw_server(vec![
+            raw_sse_response(&partial, body.len()),
+            raw_sse_response(&body, body.len()),
+        ])
+        .expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+
+        let mut text = Vec::new();
+        let err = runner
+            .stream(request(), Duration::from_secs(5), |event| {
+                if let StreamEvent::TextDelta { text: delta } = event {
+                    text.push(delta);
+                }
+            })
+            .await
+            .expect_err("stream error");
+
+        assert!(err.contains("Stream error"), "{}", err);
+        assert_eq!(text, vec!["0".to_string(), "1".to_string()]);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn stalled_stream_fails_once_keep_alives_stop() {
+        let delta = b"data: {\"choices\":[{\"index\":0,\"delta\":{\"content\":\"hi\"}}]}\n\n";
+        let mut chunks = vec![delta.to_vec()];
+        chunks.extend(std::iter::repeat_n(b": keep-alive\n\n".to_vec(), 4));
+        chunks.push(b"data: [DONE]\n\n".to_vec());
+        let (base_url, release) =
+            start_chunked_server("text/event-stream", chunks).expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+        // Keep-alives come in well inside the window, then the provider goes quiet
+        std::thread::spawn(move || {
+            for _ in 0..4 {
+                std::thread::sleep(Duration::from_millis(150));
+                let _ = release.send(());
+            }
+            std::thread::sleep(Duration::from_secs(5));
+        });
+
+        let started = std::time::Instant::now();
+        let mut text = Vec::new();
+        let err = runner
+            .stream(
+                StreamTextRequest {
+                    stall_timeout_ms: Some(300),
+                    ..request()
+                },
+                Duration::from_secs(5),
+                |event| {
+                    if let StreamEvent::TextDelta { text: delta } = event {
+                        text.push(delta);
+                    }
+                },
+            )
+            .await
+            .expect_err("stalled stream");
+
+        assert!(err.contains("Stream stalled"), "{}", err);
+        assert_eq!(text, vec!["hi".to_string()]);
+        assert!(
+            started.elapsed() >= Duration::from_millis(600),
+            "{:?}",
+            started.elapsed()
+        );
+        assert!(
+            started.elapsed() < Duration::from_secs(5),
+            "{:?}",
+            started.elapsed()
+        );
+    }
+
+    #[tokio::test]
+    async fn tolerant_provider_repairs_malformed_chunks() {
+        let delta = |text: &str| {
+            format!(
+                r#"{{"choices":[{{"index":0,"delta":{{"content":"{}"}}}}]}}"#,
+                text
+            )
+        };
+        // Concatenated objects, trailing commas, then a chunk beyond repair
+        let body = [
+            format!("data: {}{}", delta("a"), delta("b")),
+            r#"data: {"choices":[{"index":0,"delta":{"content":"c"},}],}"#.to_string(),
+            "data: {oops".to_string(),
+            r#"data: {"choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}"#.to_string(),
+            "data: [DONE]".to_string(),
+        ]
+        .map(|frame| frame + "\n\n")
+        .concat();
+
+        let (base_url, _hits) = start_sequence_server(vec![(200, body.clone())]).expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+        let strict = runner
+            .stream(request(), Duration::from_secs(5), |_| {})
+            .await;
+        assert!(strict.is_err());
+
+        let (base_url, _hits) = start_sequence_server(vec![(200, body)]).expect("server");
+        let (runner, _dir) = setup_runner_with(base_url, true).await;
+        let mut text = Vec::new();
+        let mut events = Vec::new();
+        runner
+            .stream(request(), Duration::from_secs(5), |event| {
+                if let StreamEvent::TextDelta { text: delta } = &event {
+                    text.push(delta.clone());
+                }
+                events.push(event);
+            })
+            .await
+            .expect("tolerant stream");
+
+        assert_eq!(text, vec!["a", "b", "c"]);
+        assert!(matches!(
+            events.as_slice(),
+            [.., StreamEvent::Done { .. }, StreamEvent::Metrics(_)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn sends_client_request_id_and_reports_the_provider_id() {
+        let (base_url, captured) = start_capture_server_with_headers(
+            200,
+            sse_body().into_bytes(),
+            vec![("x-request-id", "req_7f1e2d")],
+        )
+        .expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+
+        let result =
+            StreamCollector::collect_with_runner(&runner, request(), Duration::from_secs(5))
+                .await
+                .expect("collect");
+
+        let ids = result.request_ids.expect("request ids");
+        let sent = captured.recv().expect("captured request");
+        assert_eq!(
+            sent.header(DEFAULT_REQUEST_ID_HEADER),
+            Some(ids.client.as_str())
+        );
+        assert_eq!(ids.provider.as_deref(), Some("req_7f1e2d"));
+    }
+
+    #[tokio::test]
+    async fn slow_consumer_holds_producer_at_buffer_capacity() {
+        let (base_url, _hits) = start_sequence_server(vec![(200, sse_body())]).expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+        let (sender, mut receiver) = mpsc::channel(2);
+
+        // Nobody reads, so the producer must park once the buffer is full
+        let produced = tokio::time::timeout(
+            Duration::from_secs(1),
+            runner.stream_to(request(), Duration::from_secs(5), sender),
+        )
+        .await;
+        assert!(produced.is_err(), "producer should wait for the consumer");
+
+        let mut queued = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            queued.push(event);
+        }
+        assert_eq!(queued.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn stream_delivers_every_event_through_small_buffer() {
+        let (base_url, _hits) = start_sequence_server(vec![(200, sse_body())]).expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+        let runner = runner.with_buffer_size(1);
+
+        let mut text = Vec::new();
+        let mut events = Vec::new();
+        runner
+            .stream(request(), Duration::from_secs(5), |event| {
+                if let StreamEvent::TextDelta { text: delta } = &event {
+                    text.push(delta.clone());
+                }
+                events.push(event);
+            })
+            .await
+            .expect("stream");
+
+        let expected: Vec<String> = (0..DELTA_COUNT).map(|i| i.to_string()).collect();
+        assert_eq!(text, expected);
+        assert!(matches!(
+            events.as_slice(),
+            [.., StreamEvent::Done { .. }, StreamEvent::Metrics(_)]
+        ));
+    }
+
+    #[tokio::test]
+    async fn cancelled_stream_drains_queued_events_and_returns() {
+        let (base_url, _hits) = start_sequence_server(vec![(200, sse_body())]).expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+        let token = CancellationToken::new();
+        let runner = runner.with_cancel_token(token.clone()).with_buffer_size(4);
+
+        let mut received = 0;
+        let mut last = None;
+        let result = runner
+            .stream(request(), Duration::from_secs(5), |event| {
+                received += 1;
+                if received == 1 {
+                    token.cancel();
+                }
+                last = Some(event);
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert!(received < DELTA_COUNT);
+        let Some(StreamEvent::Metrics(metrics)) = last else {
+            panic!("stream should end with metrics, got {:?}", last);
+        };
+        assert!(metrics.cancelled);
+    }
+
+    #[tokio::test]
+    async fn finished_stream_reports_time_to_first_token_and_rate() {
+        let (base_url, _hits) = start_sequence_server(vec![(200, sse_body())]).expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+
+        let mut metrics = None;
+        runner
+            .stream(request(), Duration::from_secs(5), |event| {
+                if let StreamEvent::Metrics(reported) = event {
+                    metrics = Some(reported);
+                }
+            })
+            .await
+            .expect("stream");
+
+        let metrics = metrics.expect("metrics event");
+        assert_eq!(metrics.delta_count, DELTA_COUNT as u32);
+        assert!(metrics.time_to_first_token_ms.is_some());
+        assert!(metrics.time_to_first_token_ms <= Some(metrics.duration_ms));
+        assert!(!metrics.cancelled);
+    }
+
+    #[tokio::test]
+    async fn dry_run_reports_the_built_request_without_sending_it() {
+        let (base_url, hits) = start_sequence_server(vec![(200, sse_body())]).expect("server");
+        let (runner, _dir) = setup_runner(base_url.clone()).await;
+
+        let mut events = Vec::new();
+        runner
+            .stream(
+                StreamTextRequest {
+                    dry_run: Some(true),
+                    ..request()
+                },
+                Duration::from_secs(5),
+                |event| events.push(event),
+            )
+            .await
+            .expect("dry run");
+
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+        let [StreamEvent::DryRun(built), StreamEvent::Done { .. }, StreamEvent::Metrics(_)] =
+            events.as_slice()
+        else {
+            panic!("unexpected events: {:?}", events);
+        };
+        assert_eq!(built.url, format!("{}/chat/completions", base_url));
+        assert_eq!(built.headers["authorization"], "[REDACTED]");
+        assert_eq!(built.body["model"], "test-model");
+        assert_eq!(built.body["messages"][0]["content"], "hi");
+    }
+
+    #[tokio::test]
+    async fn stream_without_usage_reports_an_estimate_before_done() {
+        let (base_url, _hits) = start_sequence_server(vec![(200, sse_body())]).expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+
+        let mut events = Vec::new();
+        runner
+            .stream(request(), Duration::from_secs(5), |event| {
+                events.push(event)
+            })
+            .await
+            .expect("stream");
+
+        let [.., StreamEvent::Usage {
+            input_tokens,
+            output_tokens,
+            estimated,
+            ..
+        }, StreamEvent::Done { .. }, StreamEvent::Metrics(_)] = events.as_slice()
+        else {
+            panic!("unexpected events: {:?}", events);
+        };
+        assert!(*estimated);
+        assert!(*input_tokens > 0);
+        // The deltas spell out 0 to 31, 54 characters at four per token
+        assert_eq!(*output_tokens, 14);
+    }
+
+    #[tokio::test]
+    async fn count_tokens_resolves_the_model_and_counts_with_its_provider() {
+        let (base_url, captured) =
+            start_capture_sequence_server(vec![(200, r#"{"input_tokens":9}"#.to_string())])
+                .expect("server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_claude", "sk-ant-test")]).await;
+        let registry = ProviderRegistry::new(vec![provider_config(
+            "claude",
+            ProtocolType::Anthropic,
+            &format!("{}/v1", base_url),
+        )]);
+        let runner = StreamRunner::new(registry, api_keys);
+
+        let count = runner
+            .count_tokens(&request().messages, "claude-sonnet-4-5@claude")
+            .await
+            .expect("count");
+
+        assert_eq!(count, 9);
+        let sent = captured.recv().expect("captured request");
+        assert!(
+            sent.url.ends_with("/v1/messages/count_tokens"),
+            "{}",
+            sent.url
+        );
+        let body: serde_json::Value = serde_json::from_slice(&sent.body).expect("json body");
+        assert_eq!(body["model"], "claude-sonnet-4-5");
+    }
+
+    #[tokio::test]
+    async fn count_tokens_estimates_locally_for_providers_without_an_endpoint() {
+        let (base_url, hits) = start_sequence_server(Vec::new()).expect("server");
+        let (runner, _dir) = setup_runner(base_url).await;
+
+        let count = runner
+            .count_tokens(&request().messages, "test-model@test")
+            .await
+            .expect("count");
+
+        assert!(count > 0);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
     }
-    Some(SseEvent {
-        event,
-        data: data_lines.join("\n"),
-    })
 }
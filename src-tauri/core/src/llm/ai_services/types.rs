@@ -72,6 +72,9 @@ pub struct TokenUsage {
     pub cached_input_tokens: Option<u32>,
     #[serde(rename = "cacheCreationInputTokens")]
     pub cache_creation_input_tokens: Option<u32>,
+    /// Estimated by `tokenize` because the provider reported no usage
+    #[serde(default)]
+    pub estimated: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
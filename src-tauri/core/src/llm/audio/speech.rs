@@ -0,0 +1,360 @@
+// Text-to-speech client for OpenAI-compatible `/audio/speech` endpoints
+// The audio body is read chunk by chunk into a byte buffer, never decoded as text, or
+// forwarded chunk by chunk so playback can start before synthesis finishes
+
+use crate::llm::auth::api_key_manager::{ApiKeyManager, ProviderCredentials};
+use crate::llm::cancellation::cancellable;
+use crate::llm::error::LlmError;
+use crate::llm::providers::provider::BaseProvider;
+use crate::llm::retry::RetryPolicy;
+use crate::llm::types::ProviderConfig;
+use futures_util::StreamExt;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Audio container returned by the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeechFormat {
+    #[default]
+    Mp3,
+    Opus,
+    Wav,
+}
+
+impl SpeechFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::Opus => "audio/ogg",
+            Self::Wav => "audio/wav",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SpeechRequest {
+    pub text: String,
+    pub voice: String,
+    pub model: String,
+    pub response_format: SpeechFormat,
+    /// Playback speed multiplier; providers accept 0.25 to 4.0
+    pub speed: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SpeechApiRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+    voice: &'a str,
+    response_format: SpeechFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed: Option<f32>,
+}
+
+impl<'a> From<&'a SpeechRequest> for SpeechApiRequest<'a> {
+    fn from(request: &'a SpeechRequest) -> Self {
+        Self {
+            model: &request.model,
+            input: &request.text,
+            voice: &request.voice,
+            response_format: request.response_format,
+            speed: request.speed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SynthesizedSpeech {
+    pub audio: Vec<u8>,
+    pub mime_type: String,
+}
+
+/// Progress of a streamed synthesis, in order: one `Format`, then the audio
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpeechStreamEvent {
+    /// Container of the audio, known once the response headers arrive
+    Format { mime_type: String },
+    /// Next bytes of the audio as the provider sent them
+    Audio(Vec<u8>),
+}
+
+pub type SpeechEventSender = mpsc::UnboundedSender<SpeechStreamEvent>;
+
+pub struct SpeechClient {
+    config: ProviderConfig,
+}
+
+impl SpeechClient {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self { config }
+    }
+
+    pub async fn synthesize(
+        &self,
+        api_keys: &ApiKeyManager,
+        request: SpeechRequest,
+    ) -> Result<SynthesizedSpeech, LlmError> {
+        let (response, mime_type) = self.send(api_keys, &request).await?;
+
+        let capacity = response.content_length().unwrap_or_default() as usize;
+        let mut audio = Vec::with_capacity(capacity);
+        let mut chunks = response.bytes_stream();
+        while let Some(chunk) = chunks.next().await {
+            audio.extend_from_slice(&chunk?);
+        }
+
+        if audio.is_empty() {
+            return Err(self.empty_audio());
+        }
+
+        Ok(SynthesizedSpeech { audio, mime_type })
+    }
+
+    /// Like `synthesize`, sending the audio on `events` as it arrives instead of buffering it
+    /// Cancelling `cancel`, or dropping the receiver, stops the download and returns `Cancelled`
+    pub async fn synthesize_streaming(
+        &self,
+        api_keys: &ApiKeyManager,
+        request: SpeechRequest,
+        events: &SpeechEventSender,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<(), LlmError> {
+        let (response, mime_type) = cancellable(cancel, self.send(api_keys, &request)).await??;
+        // A receiver that went away no longer wants the audio
+        let forward = |event| events.send(event).map_err(|_| LlmError::Cancelled);
+        forward(SpeechStreamEvent::Format { mime_type })?;
+
+        let mut received = 0;
+        let mut chunks = response.bytes_stream();
+        while let Some(chunk) = cancellable(cancel, chunks.next()).await? {
+            let chunk = chunk?;
+            if chunk.is_empty() {
+                continue;
+            }
+            received += chunk.len();
+            forward(SpeechStreamEvent::Audio(chunk.to_vec()))?;
+        }
+
+        if received == 0 {
+            return Err(self.empty_audio());
+        }
+        Ok(())
+    }
+
+    /// Successful response to a speech request, with the mime type of its audio
+    async fn send(
+        &self,
+        api_keys: &ApiKeyManager,
+        request: &SpeechRequest,
+    ) -> Result<(reqwest::Response, String), LlmError> {
+        let api_key = match api_keys.get_credentials_rotating(&self.config).await? {
+            ProviderCredentials::Token(token) => token,
+            ProviderCredentials::None => {
+                return Err(LlmError::Auth(format!(
+                    "API key not configured for {} speech / {} 语音合成未配置 API 密钥",
+                    self.config.name, self.config.name
+                )))
+            }
+        };
+
+        let base = BaseProvider::new(self.config.clone());
+        let base_url = base.resolve_base_url_with_fallback(api_keys).await?;
+        let url = format!("{}/audio/speech", base_url.trim_end_matches('/'));
+
+        let client = crate::llm::http_client::shared_client().map_err(LlmError::Other)?;
+        let http_request = client
+            .post(&url)
+            .timeout(REQUEST_TIMEOUT)
+            .bearer_auth(&api_key)
+            .json(&SpeechApiRequest::from(request));
+
+        let response = RetryPolicy::for_provider(&self.config)
+            .send(http_request)
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            if status.as_u16() == 429 {
+                api_keys.mark_key_rate_limited(&api_key);
+            }
+            let headers = response.headers().clone();
+            let body = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(LlmError::from_response_parts(
+                status.as_u16(),
+                &headers,
+                format!(
+                    "{} speech synthesis failed ({}): {} / {} 语音合成失败",
+                    self.config.name, status, body, self.config.name
+                ),
+            ));
+        }
+
+        let mime_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .filter(|value| value.starts_with("audio/"))
+            .unwrap_or_else(|| request.response_format.mime_type())
+            .to_string();
+        Ok((response, mime_type))
+    }
+
+    fn empty_audio(&self) -> LlmError {
+        LlmError::InvalidResponse(format!(
+            "{} returned empty audio / 未返回音频数据",
+            self.config.name
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::testing::mock_server::{start_capture_server, start_chunked_server};
+    use crate::llm::types::ProtocolType;
+    use serde_json::json;
+
+    #[test]
+    fn request_body_contains_voice_and_format() {
+        let request = SpeechRequest {
+            text: "Build finished".to_string(),
+            voice: "alloy".to_string(),
+            model: "tts-1".to_string(),
+            response_format: SpeechFormat::Opus,
+            speed: Some(1.25),
+        };
+
+        let body = serde_json::to_value(SpeechApiRequest::from(&request)).expect("serialize");
+
+        assert_eq!(
+            body,
+            json!({
+                "model": "tts-1",
+                "input": "Build finished",
+                "voice": "alloy",
+                "response_format": "opus",
+                "speed": 1.25
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn synthesize_returns_binary_audio_with_mime_type() {
+        // Bytes that are not valid UTF-8, as in real audio
+        let audio = vec![0x49, 0x44, 0x33, 0xff, 0xfb, 0x90, 0x00];
+        let (base_url, captured) =
+            start_capture_server(200, audio.clone()).expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+
+        let config = provider_config("openai", ProtocolType::OpenAiCompatible, &base_url);
+        let client = SpeechClient::new(config);
+        let speech = client
+            .synthesize(
+                &api_keys,
+                SpeechRequest {
+                    text: "Hello".to_string(),
+                    voice: "nova".to_string(),
+                    model: "tts-1".to_string(),
+                    response_format: SpeechFormat::Wav,
+                    speed: None,
+                },
+            )
+            .await
+            .expect("synthesize");
+
+        assert_eq!(speech.audio, audio);
+        assert_eq!(speech.mime_type, "audio/wav");
+        let request = captured.recv().expect("captured request");
+        assert_eq!(request.url, "/audio/speech");
+        let body: serde_json::Value = serde_json::from_slice(&request.body).expect("json body");
+        assert_eq!(body["voice"], "nova");
+        assert_eq!(body["response_format"], "wav");
+        assert!(body.get("speed").is_none());
+    }
+
+    fn speech_request() -> SpeechRequest {
+        SpeechRequest {
+            text: "A long chapter".to_string(),
+            voice: "alloy".to_string(),
+            model: "tts-1".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn synthesize_streaming_forwards_chunks_as_they_arrive() {
+        let chunks = vec![vec![0x49, 0x44, 0x33], vec![0xff, 0xfb], vec![0x90, 0x00]];
+        let (base_url, release) =
+            start_chunked_server("audio/mpeg", chunks.clone()).expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+        let config = provider_config("openai", ProtocolType::OpenAiCompatible, &base_url);
+        let client = SpeechClient::new(config);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+
+        let synthesis = client.synthesize_streaming(&api_keys, speech_request(), &tx, None);
+        let receiving = async {
+            let mut received = Vec::new();
+            while let Some(event) = rx.recv().await {
+                // The server holds back each chunk until the previous one was seen here
+                if matches!(event, SpeechStreamEvent::Audio(_)) {
+                    let _ = release.send(());
+                }
+                received.push(event);
+                if received.len() == chunks.len() + 1 {
+                    break;
+                }
+            }
+            received
+        };
+        let (result, received) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(synthesis, receiving)
+        })
+        .await
+        .expect("chunks were buffered instead of streamed");
+
+        result.expect("synthesize");
+        let mut expected = vec![SpeechStreamEvent::Format {
+            mime_type: "audio/mpeg".to_string(),
+        }];
+        expected.extend(chunks.into_iter().map(SpeechStreamEvent::Audio));
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn cancelling_synthesize_streaming_stops_the_download() {
+        // The second chunk is never released, so only cancellation can end the stream
+        let (base_url, _release) =
+            start_chunked_server("audio/mpeg", vec![vec![0x49, 0x44, 0x33], vec![0xff]])
+                .expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+        let config = provider_config("openai", ProtocolType::OpenAiCompatible, &base_url);
+        let client = SpeechClient::new(config);
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let token = CancellationToken::new();
+
+        let synthesis = client.synthesize_streaming(&api_keys, speech_request(), &tx, Some(&token));
+        let cancelling = async {
+            while let Some(event) = rx.recv().await {
+                if matches!(event, SpeechStreamEvent::Audio(_)) {
+                    token.cancel();
+                }
+            }
+        };
+        let result = tokio::select! {
+            result = synthesis => result,
+            _ = cancelling => unreachable!("sender outlives the synthesis"),
+            _ = tokio::time::sleep(Duration::from_secs(5)) => panic!("download kept running"),
+        };
+
+        assert_eq!(result, Err(LlmError::Cancelled));
+    }
+}
@@ -1,17 +1,21 @@
 use crate::llm::types::CustomProvidersConfiguration;
 use crate::llm::types::{AuthType, ModelsConfiguration, ProviderConfig};
-use reqwest::Client;
 use serde_json::Value;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 use tauri::State;
 use tokio::sync::{Mutex, RwLock};
 
 use crate::database::Database;
+use crate::llm::auth::oauth::{refresh_claude_oauth_tokens, refresh_openai_oauth_tokens};
+use crate::llm::auth::secret_store::{is_encrypted, is_secret_setting, SecretCipher};
 
 const MODELS_CACHE_TTL: Duration = Duration::from_secs(300); // 5 minutes
+const DEFAULT_KEY_COOLDOWN: Duration = Duration::from_secs(60);
+const DEFAULT_OAUTH_REFRESH_SKEW: Duration = Duration::from_secs(60);
 
 const SETTINGS_SELECT: &str = "SELECT value FROM settings WHERE key = $1";
 const CUSTOM_PROVIDERS_FILENAME: &str = "custom-providers.json";
@@ -31,6 +35,12 @@ pub struct ApiKeyManager {
     db: Arc<Database>,
     app_data_dir: PathBuf,
     models_cache: RwLock<Option<ModelsCacheEntry>>,
+    key_rotation: Arc<KeyRotation>,
+    /// Held while an OAuth token is refreshed so concurrent requests refresh it once
+    oauth_refresh_lock: Arc<Mutex<()>>,
+    oauth_refresh_skew: Duration,
+    /// Encrypts credential settings at rest when configured
+    secret_cipher: Option<Arc<SecretCipher>>,
 }
 
 impl std::fmt::Debug for ApiKeyManager {
@@ -47,12 +57,83 @@ struct ModelsCacheEntry {
     custom_models_mtime: Option<SystemTime>,
 }
 
+/// Round-robin state for providers configured with several comma-separated API keys
+/// Shared between clones so every stream handler rotates through the same pool
+#[derive(Debug)]
+struct KeyRotation {
+    counters: std::sync::Mutex<HashMap<String, Arc<AtomicUsize>>>,
+    cooldowns: std::sync::Mutex<HashMap<String, Instant>>,
+    cooldown: Duration,
+}
+
+impl KeyRotation {
+    fn new(cooldown: Duration) -> Self {
+        Self {
+            counters: std::sync::Mutex::new(HashMap::new()),
+            cooldowns: std::sync::Mutex::new(HashMap::new()),
+            cooldown,
+        }
+    }
+
+    fn next_index(&self, provider_id: &str) -> usize {
+        let counter = self
+            .counters
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(provider_id.to_string())
+            .or_insert_with(|| Arc::new(AtomicUsize::new(0)))
+            .clone();
+        counter.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Pick the next key that is not cooling down, starting from the rotation counter
+    /// When every key is cooling down, the one that recovers first is used
+    fn select<'a>(&self, provider_id: &str, pool: &'a [String]) -> &'a str {
+        let start = self.next_index(provider_id);
+        let now = Instant::now();
+        let mut cooldowns = self.cooldowns.lock().unwrap_or_else(|e| e.into_inner());
+        cooldowns.retain(|_, until| *until > now);
+
+        let mut fallback: Option<(&'a str, Instant)> = None;
+        for offset in 0..pool.len() {
+            let key = pool[(start + offset) % pool.len()].as_str();
+            match cooldowns.get(key) {
+                None => return key,
+                Some(until) => match fallback {
+                    Some((_, best)) if best <= *until => {}
+                    _ => fallback = Some((key, *until)),
+                },
+            }
+        }
+        fallback.map(|(key, _)| key).unwrap_or(pool[0].as_str())
+    }
+
+    fn is_cooling_down(&self, api_key: &str) -> bool {
+        self.cooldowns
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(api_key)
+            .is_some_and(|until| *until > Instant::now())
+    }
+
+    fn cool_down(&self, api_key: &str) {
+        self.cooldowns
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(api_key.to_string(), Instant::now() + self.cooldown);
+    }
+}
+
 impl Clone for ApiKeyManager {
     fn clone(&self) -> Self {
         Self {
             db: self.db.clone(),
             app_data_dir: self.app_data_dir.clone(),
             models_cache: RwLock::new(None),
+            key_rotation: self.key_rotation.clone(),
+            oauth_refresh_lock: self.oauth_refresh_lock.clone(),
+            oauth_refresh_skew: self.oauth_refresh_skew,
+            secret_cipher: self.secret_cipher.clone(),
         }
     }
 }
@@ -63,9 +144,31 @@ impl ApiKeyManager {
             db,
             app_data_dir,
             models_cache: RwLock::new(None),
+            key_rotation: Arc::new(KeyRotation::new(DEFAULT_KEY_COOLDOWN)),
+            oauth_refresh_lock: Arc::new(Mutex::new(())),
+            oauth_refresh_skew: DEFAULT_OAUTH_REFRESH_SKEW,
+            secret_cipher: None,
         }
     }
 
+    /// Use a custom cooldown window for keys reported as rate limited (default 60s)
+    pub fn with_key_cooldown(mut self, cooldown: Duration) -> Self {
+        self.key_rotation = Arc::new(KeyRotation::new(cooldown));
+        self
+    }
+
+    /// Refresh OAuth tokens this long before they expire (default 60s)
+    pub fn with_oauth_refresh_skew(mut self, skew: Duration) -> Self {
+        self.oauth_refresh_skew = skew;
+        self
+    }
+
+    /// Store credential settings encrypted; plaintext values are re-encrypted when first read
+    pub fn with_secret_cipher(mut self, cipher: SecretCipher) -> Self {
+        self.secret_cipher = Some(Arc::new(cipher));
+        self
+    }
+
     /// Load models configuration with caching (5 minutes TTL)
     pub async fn load_models_config(&self) -> Result<ModelsConfiguration, String> {
         let custom_models_mtime = self.custom_models_modified_time().await?;
@@ -116,6 +219,11 @@ impl ApiKeyManager {
         *cache = None;
     }
 
+    /// Directory holding app configuration and caches
+    pub fn app_data_dir(&self) -> &Path {
+        &self.app_data_dir
+    }
+
     fn custom_providers_path(&self) -> PathBuf {
         self.app_data_dir.join(CUSTOM_PROVIDERS_FILENAME)
     }
@@ -168,6 +276,14 @@ impl ApiKeyManager {
     }
 
     pub async fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
+        let value = self.get_raw_setting(key).await?;
+        match value {
+            Some(value) if is_secret_setting(key) => self.reveal_secret(key, value).await.map(Some),
+            other => Ok(other),
+        }
+    }
+
+    async fn get_raw_setting(&self, key: &str) -> Result<Option<String>, String> {
         let result = self
             .db
             .query(SETTINGS_SELECT, vec![Value::String(key.to_string())])
@@ -182,6 +298,15 @@ impl ApiKeyManager {
     }
 
     pub async fn set_setting(&self, key: &str, value: &str) -> Result<(), String> {
+        match &self.secret_cipher {
+            Some(cipher) if is_secret_setting(key) && !value.is_empty() => {
+                self.set_raw_setting(key, &cipher.encrypt(value)?).await
+            }
+            _ => self.set_raw_setting(key, value).await,
+        }
+    }
+
+    async fn set_raw_setting(&self, key: &str, value: &str) -> Result<(), String> {
         let now = chrono::Utc::now().timestamp_millis();
         self.db
             .execute(
@@ -212,7 +337,8 @@ impl ApiKeyManager {
                 let value_str = value.as_str().unwrap_or_default();
                 if let Some(provider_id) = key_str.strip_prefix("api_key_") {
                     if !value_str.is_empty() {
-                        api_keys.insert(provider_id.to_string(), value_str.to_string());
+                        let value = self.reveal_secret(key_str, value_str.to_string()).await?;
+                        api_keys.insert(provider_id.to_string(), value);
                     }
                 }
             }
@@ -221,6 +347,26 @@ impl ApiKeyManager {
         Ok(api_keys)
     }
 
+    /// Decrypt a stored credential, migrating legacy plaintext to ciphertext on the way
+    async fn reveal_secret(&self, key: &str, value: String) -> Result<String, String> {
+        if is_encrypted(&value) {
+            let cipher = self.secret_cipher.as_ref().ok_or_else(|| {
+                format!(
+                    "Setting {} is encrypted but no encryption key is configured / 设置已加密但未配置密钥",
+                    key
+                )
+            })?;
+            return cipher.decrypt(&value);
+        }
+        if let Some(cipher) = &self.secret_cipher {
+            if !value.is_empty() {
+                self.set_raw_setting(key, &cipher.encrypt(&value)?).await?;
+                log::info!("Encrypted plaintext credential setting {}", key);
+            }
+        }
+        Ok(value)
+    }
+
     pub async fn load_custom_providers(&self) -> Result<CustomProvidersConfiguration, String> {
         let path = self.custom_providers_path();
 
@@ -305,41 +451,209 @@ impl ApiKeyManager {
                     }
                 }
 
-                let api_key = self
-                    .get_setting(&format!("api_key_{}", provider.id))
-                    .await?
-                    .unwrap_or_default();
-                if !api_key.is_empty() {
-                    return Ok(ProviderCredentials::Token(api_key));
-                }
+                let pool = self.load_api_key_pool(provider).await?;
+                Ok(ProviderCredentials::Token(pool[0].clone()))
+            }
+        }
+    }
 
-                if let Ok(custom) = self.load_custom_providers().await {
-                    if let Some(custom_provider) = custom.providers.get(&provider.id) {
-                        if !custom_provider.api_key.trim().is_empty() {
-                            return Ok(ProviderCredentials::Token(custom_provider.api_key.clone()));
-                        }
-                    }
+    /// Like `get_credentials`, but cycles through a comma-separated key pool
+    /// Keys reported through `mark_key_rate_limited` are skipped until their cooldown ends
+    pub async fn get_credentials_rotating(
+        &self,
+        provider: &ProviderConfig,
+    ) -> Result<ProviderCredentials, String> {
+        if !matches!(
+            provider.auth_type,
+            AuthType::Bearer | AuthType::ApiKey | AuthType::OAuthBearer
+        ) {
+            return self.get_credentials(provider).await;
+        }
+        if provider.supports_oauth {
+            if let Some(token) = self.get_oauth_token(&provider.id).await? {
+                if !token.trim().is_empty() {
+                    return Ok(ProviderCredentials::Token(token));
                 }
+            }
+        }
 
-                Err(format!(
-                    "API key not configured for provider {}",
-                    provider.id
-                ))
+        let pool = self.load_api_key_pool(provider).await?;
+        let key = self.key_rotation.select(&provider.id, &pool);
+        Ok(ProviderCredentials::Token(key.to_string()))
+    }
+
+    /// Exclude a key from rotation for the cooldown window after the provider throttled it
+    pub fn mark_key_rate_limited(&self, api_key: &str) {
+        self.key_rotation.cool_down(api_key);
+    }
+
+    /// Whether `api_key` is still inside the cooldown window of a 429
+    pub fn is_key_rate_limited(&self, api_key: &str) -> bool {
+        self.key_rotation.is_cooling_down(api_key)
+    }
+
+    async fn load_api_key_pool(&self, provider: &ProviderConfig) -> Result<Vec<String>, String> {
+        let api_key = self
+            .get_setting(&format!("api_key_{}", provider.id))
+            .await?
+            .unwrap_or_default();
+        let pool = parse_key_pool(&api_key);
+        if !pool.is_empty() {
+            return Ok(pool);
+        }
+
+        if let Ok(custom) = self.load_custom_providers().await {
+            if let Some(custom_provider) = custom.providers.get(&provider.id) {
+                let pool = parse_key_pool(&custom_provider.api_key);
+                if !pool.is_empty() {
+                    return Ok(pool);
+                }
             }
         }
+
+        Err(format!(
+            "API key not configured for provider {}",
+            provider.id
+        ))
     }
 
     async fn get_oauth_token(&self, provider_id: &str) -> Result<Option<String>, String> {
         match provider_id {
-            "openai" => self.get_setting("openai_oauth_access_token").await,
-            "anthropic" => self.get_setting("claude_oauth_access_token").await,
             "github_copilot" => match self.get_valid_github_copilot_token().await {
                 Ok(token) => Ok(Some(token)),
                 Err(_) => self.get_setting(GITHUB_COPILOT_COPILOT_TOKEN_KEY).await,
             },
+            _ => self.get_fresh_oauth_token(provider_id).await,
+        }
+    }
+
+    /// Return the stored access token, refreshing it first when it expires within the skew
+    /// Tokens without an expiry or refresh token are returned as stored
+    async fn get_fresh_oauth_token(&self, provider_id: &str) -> Result<Option<String>, String> {
+        let keys = OAuthSettingKeys::for_provider(provider_id);
+        let access_token = self.get_setting(&keys.access_token).await?;
+        if !self.oauth_token_expiring(&keys).await? {
+            return Ok(access_token);
+        }
 
-            _ => Ok(None),
+        let _guard = self.oauth_refresh_lock.lock().await;
+        // Another request may have refreshed the token while this one waited
+        if !self.oauth_token_expiring(&keys).await? {
+            return self.get_setting(&keys.access_token).await;
         }
+        let refresh_token = self
+            .get_setting(&keys.refresh_token)
+            .await?
+            .filter(|token| !token.trim().is_empty());
+        let Some(refresh_token) = refresh_token else {
+            return Ok(access_token);
+        };
+
+        // OpenAI and Claude use their login flows' own refresh, which also updates the other
+        // settings those flows write, such as the OpenAI account id
+        let client = crate::llm::http_client::shared_client()?;
+        let refreshed = match provider_id {
+            "openai" => refresh_openai_oauth_tokens(&client, &refresh_token, self)
+                .await
+                .map(|tokens| tokens.access_token),
+            "anthropic" => refresh_claude_oauth_tokens(&client, &refresh_token, self)
+                .await
+                .map(|tokens| tokens.access_token),
+            _ => {
+                let Some(endpoint) = self.oauth_refresh_endpoint(provider_id).await? else {
+                    return Ok(access_token);
+                };
+                match refresh_oauth_token(&client, &endpoint, &refresh_token).await {
+                    Ok(tokens) => {
+                        self.save_oauth_tokens(&keys, &tokens).await?;
+                        Ok(tokens.access_token)
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        let access_token = refreshed.map_err(|e| {
+            format!(
+                "OAuth session for {} expired and could not be refreshed, please sign in again: {} / OAuth 登录已过期且刷新失败，请重新登录",
+                provider_id, e
+            )
+        })?;
+        log::info!("Refreshed OAuth token for {}", provider_id);
+        Ok(Some(access_token))
+    }
+
+    async fn oauth_token_expiring(&self, keys: &OAuthSettingKeys) -> Result<bool, String> {
+        let expires_at = self
+            .get_setting(&keys.expires_at)
+            .await?
+            .and_then(|value| value.trim().parse::<i64>().ok());
+        Ok(match expires_at {
+            Some(expires_at) => {
+                chrono::Utc::now().timestamp() + self.oauth_refresh_skew.as_secs() as i64
+                    >= expires_at
+            }
+            None => false,
+        })
+    }
+
+    /// Refresh endpoint stored by the generic device flow for `provider_id`
+    async fn oauth_refresh_endpoint(
+        &self,
+        provider_id: &str,
+    ) -> Result<Option<OAuthRefreshEndpoint>, String> {
+        let token_url = self
+            .get_setting(&format!("oauth_token_url_{}", provider_id))
+            .await?;
+        let client_id = self
+            .get_setting(&format!("oauth_client_id_{}", provider_id))
+            .await?;
+        Ok(match (token_url, client_id) {
+            (Some(token_url), Some(client_id)) if !token_url.trim().is_empty() => {
+                Some(OAuthRefreshEndpoint {
+                    token_url,
+                    client_id,
+                })
+            }
+            _ => None,
+        })
+    }
+
+    async fn save_oauth_tokens(
+        &self,
+        keys: &OAuthSettingKeys,
+        tokens: &OAuthTokens,
+    ) -> Result<(), String> {
+        self.set_setting(&keys.access_token, &tokens.access_token)
+            .await?;
+        if let Some(refresh_token) = &tokens.refresh_token {
+            self.set_setting(&keys.refresh_token, refresh_token).await?;
+        }
+        let expires_at = tokens
+            .expires_in
+            .map(|expires_in| (chrono::Utc::now().timestamp() + expires_in as i64).to_string())
+            .unwrap_or_default();
+        self.set_setting(&keys.expires_at, &expires_at).await
+    }
+
+    /// Store tokens from the generic device flow, with the endpoint used to refresh them
+    pub async fn store_oauth_tokens(
+        &self,
+        provider_id: &str,
+        tokens: &OAuthTokens,
+        refresh_endpoint: &OAuthRefreshEndpoint,
+    ) -> Result<(), String> {
+        self.set_setting(
+            &format!("oauth_token_url_{}", provider_id),
+            &refresh_endpoint.token_url,
+        )
+        .await?;
+        self.set_setting(
+            &format!("oauth_client_id_{}", provider_id),
+            &refresh_endpoint.client_id,
+        )
+        .await?;
+        self.save_oauth_tokens(&OAuthSettingKeys::for_provider(provider_id), tokens)
+            .await
     }
 
     async fn get_valid_github_copilot_token(&self) -> Result<String, String> {
@@ -372,10 +686,7 @@ impl ApiKeyManager {
             .await?
             .filter(|value| !value.trim().is_empty());
 
-        let client = Client::builder()
-            .timeout(Duration::from_secs(20))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
         let base_domain = enterprise_url
             .as_deref()
@@ -390,6 +701,7 @@ impl ApiKeyManager {
 
         let response = client
             .get(&token_url)
+            .timeout(Duration::from_secs(20))
             .header("Accept", "application/json")
             .header("Authorization", format!("Bearer {}", access_token))
             .header("User-Agent", GITHUB_COPILOT_USER_AGENT)
@@ -485,6 +797,109 @@ impl ApiKeyManager {
     }
 }
 
+/// Tokens returned by an OAuth token endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OAuthTokens {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// Lifetime of the access token in seconds
+    pub expires_in: Option<u64>,
+}
+
+/// Token endpoint and client used for the `refresh_token` grant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OAuthRefreshEndpoint {
+    pub token_url: String,
+    pub client_id: String,
+}
+
+/// Setting keys holding a provider's OAuth session; `expires_at` is in unix seconds
+struct OAuthSettingKeys {
+    access_token: String,
+    refresh_token: String,
+    expires_at: String,
+}
+
+impl OAuthSettingKeys {
+    fn for_provider(provider_id: &str) -> Self {
+        // OpenAI and Claude keep the keys their login flows have always written
+        let prefix = match provider_id {
+            "openai" => "openai_oauth".to_string(),
+            "anthropic" => "claude_oauth".to_string(),
+            _ => {
+                return Self {
+                    access_token: format!("oauth_access_token_{}", provider_id),
+                    refresh_token: format!("oauth_refresh_token_{}", provider_id),
+                    expires_at: format!("oauth_expires_at_{}", provider_id),
+                }
+            }
+        };
+        Self {
+            access_token: format!("{}_access_token", prefix),
+            refresh_token: format!("{}_refresh_token", prefix),
+            expires_at: format!("{}_expires_at", prefix),
+        }
+    }
+}
+
+async fn refresh_oauth_token(
+    client: &reqwest::Client,
+    endpoint: &OAuthRefreshEndpoint,
+    refresh_token: &str,
+) -> Result<OAuthTokens, String> {
+    let response = client
+        .post(&endpoint.token_url)
+        .timeout(Duration::from_secs(20))
+        .header("Accept", "application/json")
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", endpoint.client_id.as_str()),
+            ("refresh_token", refresh_token),
+        ])
+        .send()
+        .await
+        .map_err(|e| format!("Refresh request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(format!("Token refresh failed ({}): {}", status, text));
+    }
+
+    let payload: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse refresh response: {}", e))?;
+    let access_token = payload["access_token"]
+        .as_str()
+        .ok_or("Missing access_token in response")?
+        .to_string();
+    Ok(OAuthTokens {
+        access_token,
+        // Keep the old refresh token when the server does not rotate it
+        refresh_token: Some(
+            payload["refresh_token"]
+                .as_str()
+                .unwrap_or(refresh_token)
+                .to_string(),
+        ),
+        expires_in: Some(payload["expires_in"].as_u64().unwrap_or(3600)),
+    })
+}
+
+/// Split a stored API key setting into its comma-separated keys
+pub fn parse_key_pool(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
 pub fn normalize_domain(url: &str) -> String {
     url.trim()
         .trim_start_matches("https://")
@@ -503,6 +918,7 @@ pub enum ProviderCredentials {
 pub struct LlmState {
     pub registry: Mutex<crate::llm::providers::provider_registry::ProviderRegistry>,
     pub api_keys: Mutex<ApiKeyManager>,
+    pub cancellations: crate::llm::cancellation::RequestCancellations,
 }
 
 impl LlmState {
@@ -512,8 +928,16 @@ impl LlmState {
                 crate::llm::providers::provider_registry::ProviderRegistry::new(providers),
             ),
             api_keys: Mutex::new(ApiKeyManager::new(db, app_data_dir)),
+            cancellations: Default::default(),
         }
     }
+
+    /// Store credentials encrypted under `cipher`, see `ApiKeyManager::with_secret_cipher`
+    pub fn with_secret_cipher(mut self, cipher: SecretCipher) -> Self {
+        let api_keys = self.api_keys.get_mut();
+        *api_keys = api_keys.clone().with_secret_cipher(cipher);
+        self
+    }
 }
 
 #[tauri::command]
@@ -530,6 +954,8 @@ pub async fn llm_set_setting(
 mod tests {
     use super::*;
     use crate::database::Database;
+    use crate::llm::testing::fixtures;
+    use crate::llm::testing::mock_server::start_sequence_server;
     use crate::llm::types::ProtocolType;
     use std::collections::HashMap;
     use std::sync::Arc;
@@ -630,19 +1056,9 @@ mod tests {
 
     fn provider_config(id: &str, auth_type: AuthType, supports_oauth: bool) -> ProviderConfig {
         ProviderConfig {
-            id: id.to_string(),
-            name: "Test".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://example.com".to_string(),
-            api_key_name: "TEST_API_KEY".to_string(),
-            supports_oauth,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
             auth_type,
+            supports_oauth,
+            ..fixtures::provider_config(id, ProtocolType::OpenAiCompatible, "https://example.com")
         }
     }
 
@@ -689,6 +1105,258 @@ mod tests {
         }
     }
 
+    fn device_flow_tokens(access_token: &str, expires_in: u64) -> OAuthTokens {
+        OAuthTokens {
+            access_token: access_token.to_string(),
+            refresh_token: Some("refresh-1".to_string()),
+            expires_in: Some(expires_in),
+        }
+    }
+
+    fn refresh_endpoint(token_url: &str) -> OAuthRefreshEndpoint {
+        OAuthRefreshEndpoint {
+            token_url: token_url.to_string(),
+            client_id: "client-abc".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_credentials_returns_device_flow_token() {
+        let ctx = setup().await;
+        ctx.api_keys
+            .store_oauth_tokens(
+                "acme",
+                &device_flow_tokens("device-token", 3600),
+                &refresh_endpoint("http://127.0.0.1:9/token"),
+            )
+            .await
+            .expect("store oauth token");
+        let provider = provider_config("acme", AuthType::Bearer, true);
+        let result = ctx.api_keys.get_credentials(&provider).await;
+        match result {
+            Ok(ProviderCredentials::Token(value)) => assert_eq!(value, "device-token"),
+            _ => panic!("Unexpected credentials"),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_credentials_refreshes_token_inside_skew() {
+        let ctx = setup().await;
+        let body = serde_json::json!({
+            "access_token": "fresh-token",
+            "refresh_token": "refresh-2",
+            "expires_in": 3600
+        })
+        .to_string();
+        let (base_url, hits) = start_sequence_server(vec![(200, body)]).expect("mock server");
+        // Expires in 30s, inside the default 60s skew
+        ctx.api_keys
+            .store_oauth_tokens(
+                "acme",
+                &device_flow_tokens("stale-token", 30),
+                &refresh_endpoint(&format!("{}/token", base_url)),
+            )
+            .await
+            .expect("store oauth token");
+        let provider = provider_config("acme", AuthType::Bearer, true);
+
+        let first = token(
+            ctx.api_keys
+                .get_credentials(&provider)
+                .await
+                .expect("creds"),
+        );
+        let second = token(
+            ctx.api_keys
+                .get_credentials(&provider)
+                .await
+                .expect("creds"),
+        );
+
+        assert_eq!(first, "fresh-token");
+        assert_eq!(second, "fresh-token");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        let stored_refresh = ctx
+            .api_keys
+            .get_setting("oauth_refresh_token_acme")
+            .await
+            .expect("read refresh token");
+        assert_eq!(stored_refresh.as_deref(), Some("refresh-2"));
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_refresh_token_once() {
+        let ctx = setup().await;
+        let body =
+            serde_json::json!({ "access_token": "fresh-token", "expires_in": 3600 }).to_string();
+        let (base_url, hits) =
+            start_sequence_server(vec![(200, body.clone()), (200, body)]).expect("mock server");
+        ctx.api_keys
+            .store_oauth_tokens(
+                "acme",
+                &device_flow_tokens("stale-token", 0),
+                &refresh_endpoint(&format!("{}/token", base_url)),
+            )
+            .await
+            .expect("store oauth token");
+        let provider = provider_config("acme", AuthType::Bearer, true);
+        let other = ctx.api_keys.clone();
+
+        let (first, second) = tokio::join!(
+            ctx.api_keys.get_credentials(&provider),
+            other.get_credentials(&provider)
+        );
+
+        assert_eq!(token(first.expect("creds")), "fresh-token");
+        assert_eq!(token(second.expect("creds")), "fresh-token");
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_asks_user_to_sign_in_again() {
+        let ctx = setup().await;
+        let body = serde_json::json!({ "error": "invalid_grant" }).to_string();
+        let (base_url, _hits) = start_sequence_server(vec![(400, body)]).expect("mock server");
+        ctx.api_keys
+            .store_oauth_tokens(
+                "acme",
+                &device_flow_tokens("stale-token", 0),
+                &refresh_endpoint(&format!("{}/token", base_url)),
+            )
+            .await
+            .expect("store oauth token");
+        let provider = provider_config("acme", AuthType::Bearer, true);
+
+        let err = ctx
+            .api_keys
+            .get_credentials(&provider)
+            .await
+            .expect_err("refresh should fail");
+
+        assert!(err.contains("please sign in again"));
+        assert!(err.contains("invalid_grant"));
+    }
+
+    #[tokio::test]
+    async fn plaintext_credentials_are_encrypted_on_first_read() {
+        let ctx = setup().await;
+        ctx.api_keys
+            .set_setting("api_key_openai", "sk-plain")
+            .await
+            .expect("set plaintext key");
+        let encrypted = ctx
+            .api_keys
+            .clone()
+            .with_secret_cipher(SecretCipher::new(&[7; 32]));
+
+        let value = encrypted
+            .get_setting("api_key_openai")
+            .await
+            .expect("read key");
+
+        assert_eq!(value.as_deref(), Some("sk-plain"));
+        let stored = encrypted
+            .get_raw_setting("api_key_openai")
+            .await
+            .expect("read raw")
+            .unwrap_or_default();
+        assert!(is_encrypted(&stored));
+        assert_eq!(
+            encrypted
+                .get_setting("api_key_openai")
+                .await
+                .expect("read key"),
+            Some("sk-plain".to_string())
+        );
+        // Without the key the ciphertext is reported instead of being used as a credential
+        assert!(ctx.api_keys.get_setting("api_key_openai").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn frontend_settings_survive_a_raw_batch_round_trip() {
+        let ctx = setup().await;
+        // The frontend writes and batch-reads the settings table directly, bypassing Rust
+        let written = [
+            ("api_key_openai", "sk-ui"),
+            ("telegram_remote_token", "tg-token"),
+            ("feishu_remote_verification_token", "fs-token"),
+        ];
+        for (key, value) in written {
+            ctx.api_keys
+                .set_raw_setting(key, value)
+                .await
+                .expect("frontend write");
+        }
+
+        let api_keys = ctx.api_keys.load_api_keys().await.expect("load keys");
+        assert_eq!(api_keys.get("openai").map(String::as_str), Some("sk-ui"));
+        let encrypted = ctx
+            .api_keys
+            .clone()
+            .with_secret_cipher(SecretCipher::new(&[7; 32]));
+        for key in ["telegram_remote_token", "feishu_remote_verification_token"] {
+            encrypted.get_setting(key).await.expect("read setting");
+        }
+
+        let batch = ctx
+            .api_keys
+            .db
+            .query(
+                "SELECT key, value FROM settings WHERE key IN ($1, $2, $3)",
+                written
+                    .iter()
+                    .map(|(key, _)| Value::String(key.to_string()))
+                    .collect(),
+            )
+            .await
+            .expect("frontend batch read");
+        assert_eq!(batch.rows.len(), written.len());
+        for row in batch.rows {
+            let key = row.get("key").and_then(|v| v.as_str()).unwrap_or_default();
+            let value = row.get("value").and_then(|v| v.as_str());
+            let expected = written.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+            assert_eq!(value, expected, "{}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn secret_cipher_only_encrypts_credentials() {
+        let ctx = setup().await;
+        let encrypted = ctx
+            .api_keys
+            .clone()
+            .with_secret_cipher(SecretCipher::new(&[7; 32]));
+        encrypted
+            .set_setting("openai_oauth_refresh_token", "refresh")
+            .await
+            .expect("set token");
+        encrypted
+            .set_setting("openai_oauth_expires_at", "123")
+            .await
+            .expect("set expiry");
+
+        let raw_token = encrypted
+            .get_raw_setting("openai_oauth_refresh_token")
+            .await
+            .expect("read raw")
+            .unwrap_or_default();
+        let raw_expiry = encrypted
+            .get_raw_setting("openai_oauth_expires_at")
+            .await
+            .expect("read raw");
+
+        assert!(is_encrypted(&raw_token));
+        assert_eq!(raw_expiry.as_deref(), Some("123"));
+        assert_eq!(
+            encrypted
+                .get_setting("openai_oauth_refresh_token")
+                .await
+                .expect("read token")
+                .as_deref(),
+            Some("refresh")
+        );
+    }
+
     #[tokio::test]
     async fn get_credentials_falls_back_to_api_key() {
         let ctx = setup().await;
@@ -739,4 +1407,93 @@ mod tests {
             .expect("no header");
         assert!(other_headers.get("chatgpt-account-id").is_none());
     }
+
+    fn token(credentials: ProviderCredentials) -> String {
+        match credentials {
+            ProviderCredentials::Token(value) => value,
+            ProviderCredentials::None => panic!("Unexpected credentials"),
+        }
+    }
+
+    #[test]
+    fn parse_key_pool_splits_and_trims() {
+        assert_eq!(parse_key_pool(" k1, k2 ,,k3 "), vec!["k1", "k2", "k3"]);
+        assert!(parse_key_pool(" , ").is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_credentials_uses_first_key_of_pool() {
+        let ctx = setup().await;
+        ctx.api_keys
+            .set_setting("api_key_openai", "k1,k2")
+            .await
+            .expect("set api keys");
+        let provider = provider_config("openai", AuthType::Bearer, false);
+        let result = ctx.api_keys.get_credentials(&provider).await;
+        assert_eq!(token(result.expect("credentials")), "k1");
+    }
+
+    #[tokio::test]
+    async fn get_credentials_rotating_cycles_through_pool() {
+        let ctx = setup().await;
+        ctx.api_keys
+            .set_setting("api_key_openai", "k1, k2, k3")
+            .await
+            .expect("set api keys");
+        let provider = provider_config("openai", AuthType::Bearer, false);
+
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            let creds = ctx.api_keys.get_credentials_rotating(&provider).await;
+            order.push(token(creds.expect("credentials")));
+        }
+
+        assert_eq!(order, vec!["k1", "k2", "k3", "k1"]);
+    }
+
+    #[tokio::test]
+    async fn get_credentials_rotating_skips_cooled_down_keys() {
+        let ctx = setup().await;
+        let api_keys = ctx
+            .api_keys
+            .clone()
+            .with_key_cooldown(Duration::from_millis(50));
+        api_keys
+            .set_setting("api_key_openai", "k1,k2")
+            .await
+            .expect("set api keys");
+        let provider = provider_config("openai", AuthType::Bearer, false);
+
+        api_keys.mark_key_rate_limited("k1");
+        for _ in 0..3 {
+            let creds = api_keys.get_credentials_rotating(&provider).await;
+            assert_eq!(token(creds.expect("credentials")), "k2");
+        }
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        let mut order = Vec::new();
+        for _ in 0..2 {
+            let creds = api_keys.get_credentials_rotating(&provider).await;
+            order.push(token(creds.expect("credentials")));
+        }
+        order.sort();
+        assert_eq!(order, vec!["k1", "k2"]);
+    }
+
+    #[tokio::test]
+    async fn get_credentials_rotating_falls_back_when_all_keys_cool_down() {
+        let ctx = setup().await;
+        ctx.api_keys
+            .set_setting("api_key_openai", "k1,k2")
+            .await
+            .expect("set api keys");
+        let provider = provider_config("openai", AuthType::Bearer, false);
+
+        ctx.api_keys.mark_key_rate_limited("k2");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        ctx.api_keys.mark_key_rate_limited("k1");
+
+        let creds = ctx.api_keys.get_credentials_rotating(&provider).await;
+        assert_eq!(token(creds.expect("credentials")), "k2");
+    }
 }
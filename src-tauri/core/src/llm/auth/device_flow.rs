@@ -0,0 +1,444 @@
+// OAuth 2.0 device authorization grant (RFC 8628) for providers with `supports_oauth`
+// The user enters a short code in the browser while the app polls the token endpoint
+
+use crate::llm::auth::api_key_manager::{LlmState, OAuthRefreshEndpoint, OAuthTokens};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::State;
+
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+/// Extra wait the spec requires after each `slow_down` response
+const SLOW_DOWN_STEP: Duration = Duration::from_secs(5);
+/// Poll interval used when the server does not send one
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Endpoints and client registration for a provider's device flow
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceFlowConfig {
+    pub device_authorization_url: String,
+    pub token_url: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DeviceAuthorizationResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    #[serde(default)]
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    #[serde(default)]
+    interval: Option<u64>,
+}
+
+/// What the UI shows the user while the flow is pending
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    /// Seconds to wait between polls
+    pub interval: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TokenEndpointResponse {
+    access_token: Option<String>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Result of one poll of the token endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PollOutcome {
+    /// The user has not approved the request yet
+    Pending,
+    /// Polling too fast; the interval must grow before the next poll
+    SlowDown,
+    Authorized(OAuthTokens),
+    Failed(String),
+}
+
+impl PollOutcome {
+    fn from_response(response: TokenEndpointResponse) -> Self {
+        if let Some(access_token) = response.access_token.filter(|t| !t.trim().is_empty()) {
+            return Self::Authorized(OAuthTokens {
+                access_token,
+                refresh_token: response.refresh_token,
+                expires_in: response.expires_in,
+            });
+        }
+        match response.error.as_deref() {
+            Some("authorization_pending") => Self::Pending,
+            Some("slow_down") => Self::SlowDown,
+            Some(error) => Self::Failed(
+                response
+                    .error_description
+                    .unwrap_or_else(|| format!("OAuth error: {}", error)),
+            ),
+            None => Self::Failed("Unknown OAuth response".to_string()),
+        }
+    }
+}
+
+/// Polling schedule for a pending device authorization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollState {
+    pub interval: Duration,
+    /// Time left before the device code expires
+    pub remaining: Duration,
+}
+
+impl PollState {
+    pub fn new(authorization: &DeviceAuthorization) -> Self {
+        Self {
+            interval: Duration::from_secs(authorization.interval),
+            remaining: Duration::from_secs(authorization.expires_in),
+        }
+    }
+
+    /// Account for one poll; returns false once the device code has expired
+    pub fn advance(&mut self, outcome: &PollOutcome) -> bool {
+        if *outcome == PollOutcome::SlowDown {
+            self.interval += SLOW_DOWN_STEP;
+        }
+        self.remaining = self.remaining.saturating_sub(self.interval);
+        !self.remaining.is_zero()
+    }
+}
+
+pub struct DeviceFlowClient {
+    config: DeviceFlowConfig,
+    client: reqwest::Client,
+}
+
+impl DeviceFlowClient {
+    pub fn new(config: DeviceFlowConfig) -> Result<Self, String> {
+        let client = crate::llm::http_client::shared_client()?;
+        Ok(Self { config, client })
+    }
+
+    /// Request a device code and the user code to display
+    pub async fn start(&self) -> Result<DeviceAuthorization, String> {
+        let mut form = vec![("client_id", self.config.client_id.as_str())];
+        if let Some(scope) = self.config.scope.as_deref() {
+            form.push(("scope", scope));
+        }
+
+        let response = self
+            .client
+            .post(&self.config.device_authorization_url)
+            .timeout(REQUEST_TIMEOUT)
+            .header("Accept", "application/json")
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| format!("Device code request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(format!(
+                "Device code request failed ({}): {} / 获取设备码失败",
+                status, text
+            ));
+        }
+
+        let data: DeviceAuthorizationResponse = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse device code response: {}", e))?;
+
+        Ok(DeviceAuthorization {
+            device_code: data.device_code,
+            user_code: data.user_code,
+            verification_uri: data.verification_uri,
+            verification_uri_complete: data.verification_uri_complete,
+            expires_in: data.expires_in,
+            interval: data.interval.unwrap_or(DEFAULT_POLL_INTERVAL.as_secs()),
+        })
+    }
+
+    /// Poll the token endpoint once
+    pub async fn poll(&self, device_code: &str) -> Result<PollOutcome, String> {
+        let response = self
+            .client
+            .post(&self.config.token_url)
+            .timeout(REQUEST_TIMEOUT)
+            .header("Accept", "application/json")
+            .form(&[
+                ("client_id", self.config.client_id.as_str()),
+                ("device_code", device_code),
+                ("grant_type", DEVICE_CODE_GRANT_TYPE),
+            ])
+            .send()
+            .await
+            .map_err(|e| format!("Token request failed: {}", e))?;
+
+        // Pending and slow_down come back as 400 with an `error` body
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read token response: {}", e))?;
+        match serde_json::from_str::<TokenEndpointResponse>(&text) {
+            Ok(data) => Ok(PollOutcome::from_response(data)),
+            Err(_) => Ok(PollOutcome::Failed(format!(
+                "Token request failed ({}): {}",
+                status, text
+            ))),
+        }
+    }
+
+    /// Poll until the user approves, the request is denied, or the device code expires
+    pub async fn wait_for_token(
+        &self,
+        authorization: &DeviceAuthorization,
+    ) -> Result<OAuthTokens, String> {
+        let mut state = PollState::new(authorization);
+        loop {
+            let outcome = self.poll(&authorization.device_code).await?;
+            match &outcome {
+                PollOutcome::Authorized(tokens) => return Ok(tokens.clone()),
+                PollOutcome::Failed(message) => {
+                    return Err(format!("{} / 授权失败", message));
+                }
+                PollOutcome::Pending | PollOutcome::SlowDown => {}
+            }
+            if !state.advance(&outcome) {
+                return Err(
+                    "Device code expired, please sign in again / 设备码已过期，请重新登录"
+                        .to_string(),
+                );
+            }
+            tokio::time::sleep(state.interval).await;
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceFlowStartRequest {
+    pub provider_id: String,
+    pub config: DeviceFlowConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceFlowPollRequest {
+    pub provider_id: String,
+    pub config: DeviceFlowConfig,
+    pub device_code: String,
+}
+
+#[derive(Serialize)]
+pub struct DeviceFlowPollResponse {
+    /// `pending`, `slow_down`, `success` or `failed`
+    #[serde(rename = "type")]
+    pub result_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[tauri::command]
+pub async fn llm_oauth_device_start(
+    request: DeviceFlowStartRequest,
+) -> Result<DeviceAuthorization, String> {
+    log::info!(
+        "[DeviceFlow] Starting device authorization for {}",
+        request.provider_id
+    );
+    DeviceFlowClient::new(request.config)?.start().await
+}
+
+/// Poll once; the frontend calls this every `interval` seconds, as with GitHub Copilot
+#[tauri::command]
+pub async fn llm_oauth_device_poll(
+    request: DeviceFlowPollRequest,
+    state: State<'_, LlmState>,
+) -> Result<DeviceFlowPollResponse, String> {
+    let refresh_endpoint = OAuthRefreshEndpoint {
+        token_url: request.config.token_url.clone(),
+        client_id: request.config.client_id.clone(),
+    };
+    let outcome = DeviceFlowClient::new(request.config)?
+        .poll(&request.device_code)
+        .await?;
+    let (result_type, error) = match outcome {
+        PollOutcome::Pending => ("pending", None),
+        PollOutcome::SlowDown => ("slow_down", None),
+        PollOutcome::Authorized(tokens) => {
+            let api_keys = state.api_keys.lock().await;
+            api_keys
+                .store_oauth_tokens(&request.provider_id, &tokens, &refresh_endpoint)
+                .await?;
+            ("success", None)
+        }
+        PollOutcome::Failed(message) => ("failed", Some(message)),
+    };
+    Ok(DeviceFlowPollResponse {
+        result_type: result_type.to_string(),
+        error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::mock_server::start_sequence_server;
+    use serde_json::json;
+    use std::sync::atomic::Ordering;
+
+    fn authorization(interval: u64, expires_in: u64) -> DeviceAuthorization {
+        DeviceAuthorization {
+            device_code: "device-123".to_string(),
+            user_code: "WDJB-MJHT".to_string(),
+            verification_uri: "https://example.com/device".to_string(),
+            verification_uri_complete: None,
+            expires_in,
+            interval,
+        }
+    }
+
+    fn config(base_url: &str) -> DeviceFlowConfig {
+        DeviceFlowConfig {
+            device_authorization_url: format!("{}/device/code", base_url),
+            token_url: format!("{}/oauth/token", base_url),
+            client_id: "client-abc".to_string(),
+            scope: Some("offline_access".to_string()),
+        }
+    }
+
+    fn token_error(error: &str) -> TokenEndpointResponse {
+        TokenEndpointResponse {
+            access_token: None,
+            refresh_token: None,
+            expires_in: None,
+            error: Some(error.to_string()),
+            error_description: None,
+        }
+    }
+
+    #[test]
+    fn maps_token_endpoint_responses_to_outcomes() {
+        assert_eq!(
+            PollOutcome::from_response(token_error("authorization_pending")),
+            PollOutcome::Pending
+        );
+        assert_eq!(
+            PollOutcome::from_response(token_error("slow_down")),
+            PollOutcome::SlowDown
+        );
+        assert_eq!(
+            PollOutcome::from_response(TokenEndpointResponse {
+                access_token: None,
+                refresh_token: None,
+                expires_in: None,
+                error: Some("access_denied".to_string()),
+                error_description: Some("The user denied the request".to_string()),
+            }),
+            PollOutcome::Failed("The user denied the request".to_string())
+        );
+        assert_eq!(
+            PollOutcome::from_response(TokenEndpointResponse {
+                access_token: Some("token-xyz".to_string()),
+                refresh_token: Some("refresh-xyz".to_string()),
+                expires_in: Some(3600),
+                error: None,
+                error_description: None,
+            }),
+            PollOutcome::Authorized(OAuthTokens {
+                access_token: "token-xyz".to_string(),
+                refresh_token: Some("refresh-xyz".to_string()),
+                expires_in: Some(3600),
+            })
+        );
+    }
+
+    #[test]
+    fn slow_down_grows_interval_and_expiry_stops_polling() {
+        let mut state = PollState::new(&authorization(5, 30));
+
+        assert!(state.advance(&PollOutcome::Pending));
+        assert_eq!(state.interval, Duration::from_secs(5));
+        assert!(state.advance(&PollOutcome::SlowDown));
+        assert_eq!(state.interval, Duration::from_secs(10));
+        assert_eq!(state.remaining, Duration::from_secs(15));
+        // The next wait would run past the device code's lifetime
+        assert!(!state.advance(&PollOutcome::SlowDown));
+    }
+
+    #[tokio::test]
+    async fn start_returns_user_code_and_default_interval() {
+        let body = json!({
+            "device_code": "device-123",
+            "user_code": "WDJB-MJHT",
+            "verification_uri": "https://example.com/device",
+            "expires_in": 900
+        })
+        .to_string();
+        let (base_url, _hits) = start_sequence_server(vec![(200, body)]).expect("mock server");
+
+        let authorization = DeviceFlowClient::new(config(&base_url))
+            .expect("client")
+            .start()
+            .await
+            .expect("start flow");
+
+        assert_eq!(authorization.user_code, "WDJB-MJHT");
+        assert_eq!(authorization.interval, 5);
+    }
+
+    #[tokio::test]
+    async fn wait_for_token_polls_through_pending_responses() {
+        let pending = json!({ "error": "authorization_pending" }).to_string();
+        let granted = json!({ "access_token": "token-xyz", "token_type": "bearer" }).to_string();
+        let (base_url, hits) =
+            start_sequence_server(vec![(400, pending.clone()), (400, pending), (200, granted)])
+                .expect("mock server");
+
+        let tokens = DeviceFlowClient::new(config(&base_url))
+            .expect("client")
+            .wait_for_token(&authorization(0, 60))
+            .await
+            .expect("token");
+
+        assert_eq!(tokens.access_token, "token-xyz");
+        assert_eq!(tokens.refresh_token, None);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn wait_for_token_stops_when_user_denies() {
+        let denied = json!({
+            "error": "access_denied",
+            "error_description": "The user denied the request"
+        })
+        .to_string();
+        let (base_url, _hits) = start_sequence_server(vec![(400, denied)]).expect("mock server");
+
+        let err = DeviceFlowClient::new(config(&base_url))
+            .expect("client")
+            .wait_for_token(&authorization(0, 60))
+            .await
+            .expect_err("denied");
+
+        assert!(err.contains("The user denied the request"));
+    }
+}
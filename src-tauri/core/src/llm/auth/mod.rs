@@ -1,3 +1,5 @@
 pub mod api_key_manager;
+pub mod device_flow;
 pub mod oauth;
 pub mod openai_usage;
+pub mod secret_store;
@@ -7,16 +7,16 @@ use std::time::{Duration, Instant};
 use tauri::State;
 use tokio::sync::Mutex;
 
-const OPENAI_CLIENT_ID: &str = "app_EMoamEEZ73f0CkXaXp7hrann";
+pub(crate) const OPENAI_CLIENT_ID: &str = "app_EMoamEEZ73f0CkXaXp7hrann";
 const OPENAI_REDIRECT_URI: &str = "http://localhost:1455/auth/callback";
 const OPENAI_AUTH_URL: &str = "https://auth.openai.com/oauth/authorize";
-const OPENAI_TOKEN_URL: &str = "https://auth.openai.com/oauth/token";
+pub(crate) const OPENAI_TOKEN_URL: &str = "https://auth.openai.com/oauth/token";
 const OPENAI_OAUTH_SCOPE: &str = "openid profile email offline_access";
 
-const CLAUDE_CLIENT_ID: &str = "app_01Kcx9v5mR2eGz4B2KG1hp6P";
+pub(crate) const CLAUDE_CLIENT_ID: &str = "app_01Kcx9v5mR2eGz4B2KG1hp6P";
 const CLAUDE_REDIRECT_URI: &str = "http://localhost:1455/auth/callback";
 const CLAUDE_AUTH_URL: &str = "https://claude.ai/oauth/authorize";
-const CLAUDE_TOKEN_URL: &str = "https://claude.ai/oauth/token";
+pub(crate) const CLAUDE_TOKEN_URL: &str = "https://claude.ai/oauth/token";
 
 const GITHUB_COPILOT_ACCESS_TOKEN_KEY: &str = "github_copilot_oauth_access_token";
 const GITHUB_COPILOT_COPILOT_TOKEN_KEY: &str = "github_copilot_oauth_copilot_token";
@@ -612,12 +612,20 @@ pub async fn llm_claude_oauth_refresh(
     request: ClaudeOAuthRefreshRequest,
     state: State<'_, LlmState>,
 ) -> Result<ClaudeOAuthRefreshResponse, String> {
+    let api_keys = state.api_keys.lock().await;
     let client = reqwest::Client::new();
+    refresh_claude_oauth_tokens(&client, &request.refresh_token, &api_keys).await
+}
 
+pub(crate) async fn refresh_claude_oauth_tokens(
+    client: &reqwest::Client,
+    refresh_token: &str,
+    api_keys: &ApiKeyManager,
+) -> Result<ClaudeOAuthRefreshResponse, String> {
     let params = [
         ("grant_type", "refresh_token"),
         ("client_id", CLAUDE_CLIENT_ID),
-        ("refresh_token", &request.refresh_token),
+        ("refresh_token", refresh_token),
     ];
 
     let response = client
@@ -651,13 +659,12 @@ pub async fn llm_claude_oauth_refresh(
     let refresh_token = token_response["refresh_token"]
         .as_str()
         .map(|s| s.to_string())
-        .unwrap_or(request.refresh_token);
+        .unwrap_or(refresh_token.to_string());
 
     let expires_in = token_response["expires_in"].as_i64().unwrap_or(3600);
     let expires_at = chrono::Utc::now().timestamp() + expires_in;
 
     // Save to settings
-    let api_keys = state.api_keys.lock().await;
     api_keys
         .set_setting("claude_oauth_access_token", &access_token)
         .await?;
@@ -30,7 +30,9 @@ async fn fetch_usage_with_token(
         .maybe_set_openai_account_header("openai", &mut headers)
         .await?;
 
-    let mut request = client.get(OPENAI_USAGE_DEFAULT_URL);
+    let mut request = client
+        .get(OPENAI_USAGE_DEFAULT_URL)
+        .timeout(Duration::from_secs(20));
     for (key, value) in headers {
         request = request.header(&key, value);
     }
@@ -76,10 +78,7 @@ pub async fn fetch_openai_oauth_usage(api_keys: &ApiKeyManager) -> Result<Value,
         .unwrap_or_default();
     let refresh_token = load_refresh_token(api_keys).await?;
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(20))
-        .build()
-        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+    let client = crate::llm::http_client::shared_client()?;
 
     if token.trim().is_empty() {
         let Some(refresh_token) = refresh_token else {
@@ -0,0 +1,275 @@
+// Encryption at rest for credentials kept in the settings table
+// Values are sealed with AES-256-GCM under a master key from a pluggable source
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use rand::RngCore;
+
+/// Marks a settings value as ciphertext; anything without it is legacy plaintext
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+pub const MASTER_KEY_LEN: usize = 32;
+pub const SALT_LEN: usize = 16;
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+/// Where the master key comes from
+pub trait MasterKeySource: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn master_key(&self) -> Result<[u8; MASTER_KEY_LEN], String>;
+}
+
+/// Random master key kept in the OS keychain, created on first use
+#[cfg(feature = "keychain")]
+pub struct KeychainKeySource {
+    service: String,
+    account: String,
+}
+
+#[cfg(feature = "keychain")]
+impl KeychainKeySource {
+    pub fn new(service: impl Into<String>, account: impl Into<String>) -> Self {
+        Self {
+            service: service.into(),
+            account: account.into(),
+        }
+    }
+}
+
+#[cfg(feature = "keychain")]
+impl MasterKeySource for KeychainKeySource {
+    fn name(&self) -> &'static str {
+        "keychain"
+    }
+
+    fn master_key(&self) -> Result<[u8; MASTER_KEY_LEN], String> {
+        let entry = keyring::Entry::new(&self.service, &self.account)
+            .map_err(|e| format!("Failed to open keychain entry: {}", e))?;
+        match entry.get_password() {
+            Ok(encoded) => decode_master_key(&encoded),
+            Err(keyring::Error::NoEntry) => {
+                let mut key = [0u8; MASTER_KEY_LEN];
+                rand::thread_rng().fill_bytes(&mut key);
+                entry
+                    .set_password(&STANDARD.encode(key))
+                    .map_err(|e| format!("Failed to store key in keychain: {}", e))?;
+                Ok(key)
+            }
+            Err(e) => Err(format!(
+                "Failed to read key from keychain: {} / 读取系统钥匙串失败",
+                e
+            )),
+        }
+    }
+}
+
+/// Master key derived from a user passphrase with PBKDF2-HMAC-SHA256
+pub struct PassphraseKeySource {
+    passphrase: String,
+    salt: Vec<u8>,
+}
+
+impl PassphraseKeySource {
+    /// `salt` must be stored and reused, or existing values cannot be decrypted
+    pub fn new(passphrase: impl Into<String>, salt: Vec<u8>) -> Self {
+        Self {
+            passphrase: passphrase.into(),
+            salt,
+        }
+    }
+
+    pub fn generate_salt() -> Vec<u8> {
+        let mut salt = vec![0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        salt
+    }
+}
+
+impl MasterKeySource for PassphraseKeySource {
+    fn name(&self) -> &'static str {
+        "passphrase"
+    }
+
+    fn master_key(&self) -> Result<[u8; MASTER_KEY_LEN], String> {
+        if self.passphrase.is_empty() {
+            return Err("Encryption passphrase is empty / 加密口令为空".to_string());
+        }
+        let mut key = [0u8; MASTER_KEY_LEN];
+        pbkdf2::pbkdf2_hmac::<sha2::Sha256>(
+            self.passphrase.as_bytes(),
+            &self.salt,
+            PBKDF2_ROUNDS,
+            &mut key,
+        );
+        Ok(key)
+    }
+}
+
+#[cfg(feature = "keychain")]
+fn decode_master_key(encoded: &str) -> Result<[u8; MASTER_KEY_LEN], String> {
+    STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| <[u8; MASTER_KEY_LEN]>::try_from(bytes).ok())
+        .ok_or_else(|| "Stored master key is malformed / 存储的主密钥格式错误".to_string())
+}
+
+/// Whether a settings value was written by `SecretCipher::encrypt`
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Key prefixes of the settings that hold credentials and are stored encrypted
+const SECRET_SETTING_PREFIXES: &[&str] = &[
+    "api_key_",
+    "custom_api_key_",
+    "coding_plan_api_key_",
+    "oauth_access_token_",
+    "oauth_refresh_token_",
+    "openai_oauth_access_token",
+    "openai_oauth_refresh_token",
+    "claude_oauth_access_token",
+    "claude_oauth_refresh_token",
+    "github_copilot_oauth_access_token",
+    "github_copilot_oauth_copilot_token",
+    "talkcody_auth_token",
+];
+
+/// Settings that hold credentials and are stored encrypted
+pub fn is_secret_setting(key: &str) -> bool {
+    SECRET_SETTING_PREFIXES
+        .iter()
+        .any(|prefix| key.starts_with(prefix))
+}
+
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl std::fmt::Debug for SecretCipher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretCipher").finish_non_exhaustive()
+    }
+}
+
+impl SecretCipher {
+    pub fn new(key: &[u8; MASTER_KEY_LEN]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        }
+    }
+
+    pub fn from_source(source: &dyn MasterKeySource) -> Result<Self, String> {
+        let key = source
+            .master_key()
+            .map_err(|e| format!("Failed to load {} encryption key: {}", source.name(), e))?;
+        Ok(Self::new(&key))
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| "Failed to encrypt secret / 加密失败".to_string())?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!("{}{}", ENCRYPTED_PREFIX, STANDARD.encode(sealed)))
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String, String> {
+        let encoded = value
+            .strip_prefix(ENCRYPTED_PREFIX)
+            .ok_or_else(|| "Value is not encrypted".to_string())?;
+        let sealed = STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("Malformed encrypted value: {}", e))?;
+        if sealed.len() < NONCE_LEN {
+            return Err("Malformed encrypted value: too short".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                "Failed to decrypt secret, the encryption key may have changed / 解密失败，密钥可能已更改"
+                    .to_string()
+            })?;
+        String::from_utf8(plaintext).map_err(|e| format!("Decrypted secret is not UTF-8: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedKey([u8; MASTER_KEY_LEN]);
+
+    impl MasterKeySource for FixedKey {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        fn master_key(&self) -> Result<[u8; MASTER_KEY_LEN], String> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let cipher = SecretCipher::from_source(&FixedKey([7; MASTER_KEY_LEN])).expect("cipher");
+
+        let sealed = cipher.encrypt("sk-test-123").expect("encrypt");
+
+        assert!(is_encrypted(&sealed));
+        assert!(!sealed.contains("sk-test-123"));
+        assert_ne!(sealed, cipher.encrypt("sk-test-123").expect("encrypt"));
+        assert_eq!(cipher.decrypt(&sealed).expect("decrypt"), "sk-test-123");
+    }
+
+    #[test]
+    fn decrypt_fails_with_a_different_key() {
+        let sealed = SecretCipher::from_source(&FixedKey([1; MASTER_KEY_LEN]))
+            .expect("cipher")
+            .encrypt("sk-test-123")
+            .expect("encrypt");
+
+        let err = SecretCipher::from_source(&FixedKey([2; MASTER_KEY_LEN]))
+            .expect("cipher")
+            .decrypt(&sealed)
+            .expect_err("wrong key");
+
+        assert!(err.contains("encryption key may have changed"));
+    }
+
+    #[test]
+    fn passphrase_source_derives_the_same_key_from_the_same_salt() {
+        let salt = PassphraseKeySource::generate_salt();
+        let first = PassphraseKeySource::new("correct horse", salt.clone())
+            .master_key()
+            .expect("derive");
+        let second = PassphraseKeySource::new("correct horse", salt)
+            .master_key()
+            .expect("derive");
+
+        assert_eq!(first, second);
+        assert!(PassphraseKeySource::new("", vec![0; SALT_LEN])
+            .master_key()
+            .is_err());
+    }
+
+    #[test]
+    fn detects_plaintext_and_secret_settings() {
+        assert!(!is_encrypted("sk-plain"));
+        assert!(is_secret_setting("api_key_openai"));
+        assert!(is_secret_setting("openai_oauth_refresh_token"));
+        assert!(is_secret_setting("talkcody_auth_token"));
+        assert!(is_secret_setting("oauth_refresh_token_custom"));
+        assert!(!is_secret_setting("openai_oauth_expires_at"));
+        assert!(!is_secret_setting("telegram_remote_token"));
+        assert!(!is_secret_setting("feishu_remote_verification_token"));
+        assert!(!is_secret_setting("theme"));
+    }
+}
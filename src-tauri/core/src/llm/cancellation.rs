@@ -0,0 +1,130 @@
+// Cancellation of in-flight provider requests
+// Each stream registers a token under its request id so the UI "Stop" action can abort it
+
+use crate::llm::error::LlmError;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio_util::sync::CancellationToken;
+
+/// Tokens of running requests, keyed by request id
+#[derive(Debug, Clone, Default)]
+pub struct RequestCancellations {
+    tokens: Arc<Mutex<HashMap<String, CancellationToken>>>,
+}
+
+impl RequestCancellations {
+    /// Create and track a token for `request_id`, replacing any previous one
+    pub fn register(&self, request_id: &str) -> CancellationToken {
+        let token = CancellationToken::new();
+        let previous = self
+            .tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(request_id.to_string(), token.clone());
+        if let Some(previous) = previous {
+            previous.cancel();
+        }
+        token
+    }
+
+    /// Cancel a running request; returns false when no request has that id
+    pub fn cancel(&self, request_id: &str) -> bool {
+        let token = self
+            .tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(request_id);
+        match token {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stop tracking a finished request
+    pub fn remove(&self, request_id: &str) {
+        self.tokens
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(request_id);
+    }
+}
+
+/// Run `future` until it completes or `cancel` fires
+/// Dropping the future on cancellation also drops any connection it owns
+pub async fn cancellable<F: Future>(
+    cancel: Option<&CancellationToken>,
+    future: F,
+) -> Result<F::Output, LlmError> {
+    match cancel {
+        Some(token) => tokio::select! {
+            biased;
+            _ = token.cancelled() => Err(LlmError::Cancelled),
+            output = future => Ok(output),
+        },
+        None => Ok(future.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{stream, StreamExt};
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn cancelling_mid_stream_ends_polling_promptly() {
+        let token = CancellationToken::new();
+        // One chunk, then a body that never finishes
+        let mut body = stream::iter(vec!["data: first\n\n"]).chain(stream::pending());
+
+        let first = cancellable(Some(&token), body.next())
+            .await
+            .expect("first chunk");
+        assert_eq!(first, Some("data: first\n\n"));
+
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let result = cancellable(Some(&token), body.next()).await;
+
+        assert_eq!(result, Err(LlmError::Cancelled));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn runs_to_completion_without_token() {
+        let output = cancellable(None, async { 42 }).await;
+        assert_eq!(output, Ok(42));
+    }
+
+    #[test]
+    fn cancels_registered_request_by_id() {
+        let cancellations = RequestCancellations::default();
+        let token = cancellations.register("req-1");
+
+        assert!(!cancellations.cancel("req-2"));
+        assert!(cancellations.cancel("req-1"));
+        assert!(token.is_cancelled());
+        // Cancelling removes the entry
+        assert!(!cancellations.cancel("req-1"));
+    }
+
+    #[test]
+    fn removing_finished_request_does_not_cancel_it() {
+        let cancellations = RequestCancellations::default();
+        let token = cancellations.register("req-1");
+
+        cancellations.remove("req-1");
+
+        assert!(!token.is_cancelled());
+        assert!(!cancellations.cancel("req-1"));
+    }
+}
@@ -3,6 +3,8 @@ use crate::llm::ai_services::context_compaction_service::ContextCompactionServic
 use crate::llm::ai_services::git_message_service::GitMessageService;
 use crate::llm::ai_services::pricing_service::PricingService;
 use crate::llm::ai_services::prompt_enhancement_service::PromptEnhancementService;
+use crate::llm::ai_services::request_bundle::{self, RequestBundle};
+use crate::llm::ai_services::stream_runner::StreamRunner;
 use crate::llm::ai_services::task_title_service::TaskTitleService;
 use crate::llm::ai_services::types::{
     CalculateCostRequest, CalculateCostResult, CompletionContext, CompletionResult,
@@ -11,18 +13,31 @@ use crate::llm::ai_services::types::{
     TitleGenerationResult,
 };
 use crate::llm::auth::api_key_manager::LlmState;
+use crate::llm::embeddings::client::EmbeddingClient;
+use crate::llm::http_client::{self, HttpSettings, SendWithMiddleware};
 use crate::llm::models::model_registry::ModelRegistry;
 use crate::llm::models::model_sync;
+use crate::llm::pricing::{self, CostEstimate, PriceOverrides};
+use crate::llm::providers::provider::{
+    DryRunRequest, HealthStatus, ProviderCapabilities, ProviderContext,
+};
+use crate::llm::providers::provider_configs;
+use crate::llm::request_log::{self, RequestLogSettings};
 use crate::llm::streaming::stream_handler::StreamHandler;
 use crate::llm::transcription::service::TranscriptionService;
 use crate::llm::transcription::types::TranscriptionContext;
 use crate::llm::types::{
-    AvailableModel, CustomProviderConfig, ImageDownloadRequest, ImageDownloadResponse,
-    ImageGenerationRequest, ImageGenerationResponse, ModelsConfiguration, StreamResponse,
+    AvailableModel, CustomProviderConfig, EmbeddingRequest, EmbeddingResponse, GeneratedImage,
+    ImageDownloadRequest, ImageDownloadResponse, ImageGenerationRequest, ImageGenerationResponse,
+    ImageRefreshRequest, Message, ModelInfo, ModelsConfiguration, StreamEvent, StreamResponse,
     StreamTextRequest, TranscriptionRequest, TranscriptionResponse,
 };
+use std::time::Duration;
 use tauri::{Manager, State, Window};
 
+/// Upper bound for building a dry-run request, which needs no provider round trip
+const DRY_RUN_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[tauri::command]
 pub async fn llm_get_provider_configs(
     state: State<'_, LlmState>,
@@ -31,6 +46,11 @@ pub async fn llm_get_provider_configs(
     Ok(registry.providers())
 }
 
+#[tauri::command]
+pub async fn llm_get_provider_presets() -> Result<Vec<crate::llm::types::ProviderConfig>, String> {
+    Ok(provider_configs::provider_presets())
+}
+
 #[tauri::command]
 pub async fn llm_get_models_config(
     state: State<'_, LlmState>,
@@ -63,20 +83,101 @@ pub async fn llm_stream_text(
         .clone()
         .unwrap_or_else(|| "0".to_string());
 
+    // Only caller-provided ids can be targeted by llm_cancel_stream
+    let cancellations = state.cancellations.clone();
+    let cancel_token = (request_id != "0").then(|| cancellations.register(&request_id));
+
     let request_id_clone = request_id.clone();
     // Spawn the streaming process in a background task so the command returns immediately
     tauri::async_runtime::spawn(async move {
+        let tracked = cancel_token.is_some();
         if let Err(e) = handler
-            .stream_completion(window, request, request_id_clone)
+            .stream_completion(window, request, request_id_clone.clone(), cancel_token)
             .await
         {
             log::error!("[llm_stream_text] Stream error: {}", e);
         }
+        if tracked {
+            cancellations.remove(&request_id_clone);
+        }
     });
 
     Ok(StreamResponse { request_id })
 }
 
+/// Build the request `llm_stream_text` would send, with credentials redacted, without sending it
+#[tauri::command]
+pub async fn llm_dry_run_request(
+    request: StreamTextRequest,
+    state: State<'_, LlmState>,
+) -> Result<DryRunRequest, String> {
+    let (registry, api_keys) = {
+        let registry = state.registry.lock().await;
+        let api_keys = state.api_keys.lock().await;
+        (registry.clone(), api_keys.clone())
+    };
+
+    let request = StreamTextRequest {
+        dry_run: Some(true),
+        ..request
+    };
+    let mut built = None;
+    StreamRunner::new(registry, api_keys)
+        .stream(request, DRY_RUN_TIMEOUT, |event| {
+            if let StreamEvent::DryRun(request) = event {
+                built = Some(request);
+            }
+        })
+        .await?;
+    built.ok_or_else(|| "Dry run did not build a request / 试运行未生成请求".to_string())
+}
+
+/// Prompt tokens `messages` would cost on `model`, counted before anything is sent
+#[tauri::command]
+pub async fn llm_count_tokens(
+    messages: Vec<Message>,
+    model: String,
+    state: State<'_, LlmState>,
+) -> Result<usize, String> {
+    let (registry, api_keys) = {
+        let registry = state.registry.lock().await;
+        let api_keys = state.api_keys.lock().await;
+        (registry.clone(), api_keys.clone())
+    };
+
+    StreamRunner::new(registry, api_keys)
+        .count_tokens(&messages, &model)
+        .await
+}
+
+/// Bundle the request `llm_stream_text` would send, credentials redacted, for a support export
+#[tauri::command]
+pub async fn llm_export_request_bundle(
+    request: StreamTextRequest,
+    state: State<'_, LlmState>,
+) -> Result<RequestBundle, String> {
+    let (registry, api_keys) = {
+        let registry = state.registry.lock().await;
+        let api_keys = state.api_keys.lock().await;
+        (registry.clone(), api_keys.clone())
+    };
+    request_bundle::export(
+        &StreamRunner::new(registry, api_keys),
+        request,
+        DRY_RUN_TIMEOUT,
+    )
+    .await
+}
+
+/// Abort a running stream; returns false when it already finished
+#[tauri::command]
+pub async fn llm_cancel_stream(
+    request_id: String,
+    state: State<'_, LlmState>,
+) -> Result<bool, String> {
+    Ok(state.cancellations.cancel(&request_id))
+}
+
 #[tauri::command]
 pub async fn llm_list_available_models(
     state: State<'_, LlmState>,
@@ -86,6 +187,155 @@ pub async fn llm_list_available_models(
     ModelRegistry::compute_available_models(&api_keys, &registry).await
 }
 
+#[tauri::command]
+pub async fn llm_list_provider_models(
+    provider_id: String,
+    state: State<'_, LlmState>,
+) -> Result<Vec<ModelInfo>, String> {
+    let (registry, api_keys) = {
+        let registry = state.registry.lock().await;
+        let api_keys = state.api_keys.lock().await;
+        (registry.clone(), api_keys.clone())
+    };
+
+    let provider = registry
+        .create_provider(&provider_id)
+        .ok_or_else(|| format!("Provider not found: {}", provider_id))?;
+    let ctx = ProviderContext {
+        provider_config: provider.config(),
+        api_key_manager: &api_keys,
+        model: "",
+        messages: &[],
+        tools: None,
+        temperature: None,
+        max_tokens: None,
+        top_p: None,
+        top_k: None,
+        frequency_penalty: None,
+        presence_penalty: None,
+        stop: None,
+        response_format: None,
+        user_id: None,
+        base_url_override: None,
+        provider_options: None,
+        trace_context: None,
+        cancel_token: None,
+        stream: true,
+        logprobs: false,
+        top_logprobs: None,
+        dry_run: false,
+        builtin_tools: &[],
+        strip_headers: &[],
+        retry_budget: None,
+        reasoning_effort: None,
+        idempotent: true,
+    };
+    provider.list_models(&ctx).await.map_err(String::from)
+}
+
+/// Send a minimal request to verify the provider's key, for the settings status indicator
+#[tauri::command]
+pub async fn llm_check_provider_health(
+    provider_id: String,
+    model: Option<String>,
+    state: State<'_, LlmState>,
+) -> Result<HealthStatus, String> {
+    let (registry, api_keys) = {
+        let registry = state.registry.lock().await;
+        let api_keys = state.api_keys.lock().await;
+        (registry.clone(), api_keys.clone())
+    };
+
+    let provider = registry
+        .create_provider(&provider_id)
+        .ok_or_else(|| format!("Provider not found: {}", provider_id))?;
+    let ctx = ProviderContext {
+        provider_config: provider.config(),
+        api_key_manager: &api_keys,
+        model: model.as_deref().unwrap_or_default(),
+        messages: &[],
+        tools: None,
+        temperature: None,
+        max_tokens: None,
+        top_p: None,
+        top_k: None,
+        frequency_penalty: None,
+        presence_penalty: None,
+        stop: None,
+        response_format: None,
+        user_id: None,
+        base_url_override: None,
+        provider_options: None,
+        trace_context: None,
+        cancel_token: None,
+        stream: true,
+        logprobs: false,
+        top_logprobs: None,
+        dry_run: false,
+        builtin_tools: &[],
+        strip_headers: &[],
+        retry_budget: None,
+        reasoning_effort: None,
+        idempotent: true,
+    };
+    provider.health_check(&ctx).await
+}
+
+#[tauri::command]
+pub async fn llm_get_http_settings() -> Result<HttpSettings, String> {
+    Ok(http_client::http_settings())
+}
+
+#[tauri::command]
+pub async fn llm_set_http_settings(
+    settings: HttpSettings,
+    state: State<'_, LlmState>,
+) -> Result<(), String> {
+    let api_keys = state.api_keys.lock().await;
+    http_client::save_http_settings(&api_keys, settings).await
+}
+
+#[tauri::command]
+pub async fn llm_get_request_log_settings() -> Result<RequestLogSettings, String> {
+    Ok(request_log::request_log_settings())
+}
+
+#[tauri::command]
+pub async fn llm_set_request_log_settings(
+    settings: RequestLogSettings,
+    state: State<'_, LlmState>,
+) -> Result<(), String> {
+    let api_keys = state.api_keys.lock().await;
+    request_log::save_request_log_settings(&api_keys, settings).await
+}
+
+#[tauri::command]
+pub async fn llm_get_price_overrides(state: State<'_, LlmState>) -> Result<PriceOverrides, String> {
+    let api_keys = state.api_keys.lock().await;
+    pricing::load_price_overrides(&api_keys).await
+}
+
+#[tauri::command]
+pub async fn llm_set_price_overrides(
+    overrides: PriceOverrides,
+    state: State<'_, LlmState>,
+) -> Result<(), String> {
+    let api_keys = state.api_keys.lock().await;
+    pricing::save_price_overrides(&api_keys, &overrides).await
+}
+
+#[tauri::command]
+pub async fn llm_estimate_cost(
+    provider_id: String,
+    model: String,
+    usage: crate::llm::ai_services::types::TokenUsage,
+    state: State<'_, LlmState>,
+) -> Result<Option<CostEstimate>, String> {
+    let api_keys = state.api_keys.lock().await;
+    let table = pricing::load_price_table(&api_keys).await?;
+    Ok(table.estimate_cost(&usage, &provider_id, &model))
+}
+
 #[tauri::command]
 pub async fn llm_register_custom_provider(
     config: CustomProviderConfig,
@@ -121,6 +371,14 @@ pub async fn llm_register_custom_provider(
         headers: None,
         extra_body: None,
         auth_type: crate::llm::types::AuthType::Bearer,
+        retry_policy: None,
+        model_aliases: Default::default(),
+        default_model: None,
+        tolerant_stream_json: false,
+        max_request_bytes: None,
+        multipart_images: false,
+        organization: None,
+        project: None,
     });
     Ok(())
 }
@@ -213,7 +471,42 @@ pub async fn llm_generate_image(
 
     let custom_providers = api_keys.load_custom_providers().await?;
 
-    crate::llm::image_generation::service::ImageGenerationService::generate(
+    // A caller-provided request id lets llm_cancel_stream abort the generation
+    let request_id = request.request_id.clone().filter(|id| !id.is_empty());
+    let cancel_token = request_id
+        .as_deref()
+        .map(|id| state.cancellations.register(id));
+    let result = crate::llm::image_generation::service::ImageGenerationService::generate(
+        &api_keys,
+        &registry,
+        &custom_providers,
+        &models,
+        request,
+        cancel_token.as_ref(),
+    )
+    .await;
+    if let Some(request_id) = &request_id {
+        state.cancellations.remove(request_id);
+    }
+    result
+}
+
+/// Renew a generated image whose URL has expired or is about to
+#[tauri::command]
+pub async fn llm_refresh_image_url(
+    request: ImageRefreshRequest,
+    state: State<'_, LlmState>,
+) -> Result<GeneratedImage, String> {
+    let (registry, api_keys, models) = {
+        let registry = state.registry.lock().await;
+        let api_keys = state.api_keys.lock().await;
+        let models = api_keys.load_models_config().await?;
+        (registry.clone(), api_keys.clone(), models)
+    };
+
+    let custom_providers = api_keys.load_custom_providers().await?;
+
+    crate::llm::image_generation::service::ImageGenerationService::refresh_image_url(
         &api_keys,
         &registry,
         &custom_providers,
@@ -223,6 +516,42 @@ pub async fn llm_generate_image(
     .await
 }
 
+#[tauri::command]
+pub async fn llm_create_embeddings(
+    request: EmbeddingRequest,
+    state: State<'_, LlmState>,
+) -> Result<EmbeddingResponse, String> {
+    let (provider, api_keys) = {
+        let registry = state.registry.lock().await;
+        let api_keys = state.api_keys.lock().await;
+        let provider = registry
+            .provider(&request.provider_id)
+            .cloned()
+            .ok_or_else(|| format!("Provider not found: {}", request.provider_id))?;
+        (provider, api_keys.clone())
+    };
+
+    let embeddings = EmbeddingClient::new(provider)
+        .create_embeddings(&api_keys, &request.model, request.input)
+        .await?;
+    Ok(EmbeddingResponse {
+        provider: request.provider_id,
+        model: request.model,
+        embeddings,
+    })
+}
+
+#[tauri::command]
+pub async fn llm_get_provider_capabilities(
+    provider_id: String,
+    state: State<'_, LlmState>,
+) -> Result<ProviderCapabilities, String> {
+    let registry = state.registry.lock().await;
+    registry
+        .capabilities(&provider_id)
+        .ok_or_else(|| format!("Provider not found: {}", provider_id))
+}
+
 /// Download image from URL (bypasses browser CORS restrictions)
 #[tauri::command]
 pub async fn llm_download_image(
@@ -235,15 +564,13 @@ pub async fn llm_download_image(
         request.url
     );
 
-    let client = reqwest::Client::builder()
-        .timeout(Duration::from_secs(60))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let client = crate::llm::http_client::shared_client()?;
 
     let response = client
         .get(&request.url)
+        .timeout(Duration::from_secs(60))
         .header("Accept", "image/*,*/*")
-        .send()
+        .send_with_middleware()
         .await
         .map_err(|e| format!("Failed to download image: {}", e))?;
 
@@ -0,0 +1,397 @@
+// Context window fitting
+// Conversations longer than the model's context are trimmed from the oldest turn before the
+// request is built; system prompts are always kept and the latest message is never dropped
+
+use crate::llm::types::{ContentPart, Message, MessageContent, StreamTextRequest, ToolDefinition};
+use serde::{Deserialize, Serialize};
+
+/// Rough characters per token for English text and code, used when no tokenizer is at hand
+const CHARS_PER_TOKEN: usize = 4;
+/// Role markers and separators each message costs on top of its content
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+/// Flat estimate for an image or video part, whose real cost depends on its resolution
+const MEDIA_TOKENS: usize = 1_000;
+/// Space kept free for the reply when the request sets no `max_tokens`
+const DEFAULT_OUTPUT_RESERVE: usize = 4_096;
+/// Characters of each dropped message quoted by `SummarizeOldest`
+const SUMMARY_EXCERPT_CHARS: usize = 160;
+/// Share of the budget a summary may take, as a divisor
+const SUMMARY_BUDGET_DIVISOR: usize = 8;
+
+/// What happens to the oldest messages that no longer fit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrimStrategy {
+    /// Remove them
+    #[default]
+    DropOldest,
+    /// Replace them with one message quoting the start of the most recent ones
+    SummarizeOldest,
+}
+
+/// Outcome of `trim_messages`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrimReport {
+    /// Messages removed, not counting an added summary
+    pub dropped: usize,
+    /// Estimated prompt tokens after trimming
+    pub estimated_tokens: usize,
+}
+
+/// Rough token count of `text`; rounds up so estimates err toward trimming early
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+pub fn estimate_message_tokens(message: &Message) -> usize {
+    let content = match message {
+        Message::System { content, .. } => estimate_tokens(content),
+        Message::User { content, .. } | Message::Assistant { content, .. } => match content {
+            MessageContent::Text(text) => estimate_tokens(text),
+            MessageContent::Parts(parts) => parts.iter().map(estimate_part_tokens).sum(),
+        },
+        Message::Tool { content, .. } => content.iter().map(estimate_part_tokens).sum(),
+    };
+    content + MESSAGE_OVERHEAD_TOKENS
+}
+
+fn estimate_part_tokens(part: &ContentPart) -> usize {
+    match part {
+        ContentPart::Text { text } | ContentPart::Reasoning { text, .. } => estimate_tokens(text),
+        ContentPart::Image { .. } | ContentPart::Video { .. } => MEDIA_TOKENS,
+        ContentPart::ToolCall {
+            tool_name, input, ..
+        } => estimate_tokens(tool_name) + estimate_tokens(&input.to_string()),
+        ContentPart::ToolResult {
+            tool_name, output, ..
+        } => estimate_tokens(tool_name) + estimate_tokens(&output.to_string()),
+    }
+}
+
+/// Tokens left for the messages once the reply and the tool definitions are set aside
+pub fn prompt_budget(
+    context_window: u32,
+    max_tokens: Option<i32>,
+    tools: Option<&[ToolDefinition]>,
+) -> usize {
+    let window = context_window as usize;
+    let reserve = max_tokens
+        .filter(|tokens| *tokens > 0)
+        .map(|tokens| tokens as usize)
+        .unwrap_or_else(|| DEFAULT_OUTPUT_RESERVE.min(window / 4));
+    let tools = tools
+        .and_then(|tools| serde_json::to_string(tools).ok())
+        .map(|json| estimate_tokens(&json))
+        .unwrap_or(0);
+    window.saturating_sub(reserve).saturating_sub(tools)
+}
+
+/// Remove the oldest non-system messages until the estimate fits `budget`
+/// Tool results leave together with the call before them, so no kept call loses its result
+pub fn trim_messages(
+    messages: &mut Vec<Message>,
+    budget: usize,
+    strategy: TrimStrategy,
+) -> TrimReport {
+    let tokens: Vec<usize> = messages.iter().map(estimate_message_tokens).collect();
+    let total: usize = tokens.iter().sum();
+    if total <= budget {
+        return TrimReport {
+            dropped: 0,
+            estimated_tokens: total,
+        };
+    }
+
+    let history: Vec<usize> = (0..messages.len())
+        .filter(|&i| !matches!(messages[i], Message::System { .. }))
+        .collect();
+    let summary_budget = budget / SUMMARY_BUDGET_DIVISOR;
+    let summary_for = |cut: usize| match strategy {
+        TrimStrategy::DropOldest => None,
+        TrimStrategy::SummarizeOldest if cut == 0 => None,
+        TrimStrategy::SummarizeOldest => {
+            let dropped: Vec<&Message> = history[..cut].iter().map(|&i| &messages[i]).collect();
+            Some(summarize(&dropped, summary_budget))
+        }
+    };
+
+    let mut cut = 0;
+    let mut remaining = total;
+    let mut summary = None;
+    while cut + 1 < history.len() {
+        let summary_tokens = summary.as_ref().map_or(0, estimate_message_tokens);
+        if remaining + summary_tokens <= budget {
+            break;
+        }
+        remaining -= tokens[history[cut]];
+        cut += 1;
+        while cut + 1 < history.len() && matches!(messages[history[cut]], Message::Tool { .. }) {
+            remaining -= tokens[history[cut]];
+            cut += 1;
+        }
+        summary = summary_for(cut);
+    }
+    if cut == 0 {
+        log::warn!(
+            "[ContextWindow] ~{} prompt tokens exceed the {} token budget, nothing left to drop",
+            total,
+            budget
+        );
+        return TrimReport {
+            dropped: 0,
+            estimated_tokens: total,
+        };
+    }
+
+    let first_dropped = history[0];
+    let dropped: Vec<usize> = history[..cut].to_vec();
+    let estimated_tokens = remaining + summary.as_ref().map_or(0, estimate_message_tokens);
+    let mut kept = Vec::with_capacity(messages.len() - cut + 1);
+    for (index, message) in std::mem::take(messages).into_iter().enumerate() {
+        if index == first_dropped {
+            kept.extend(summary.take());
+        }
+        if dropped.binary_search(&index).is_err() {
+            kept.push(message);
+        }
+    }
+    *messages = kept;
+
+    log::info!(
+        "[ContextWindow] Trimmed {} of the oldest messages to fit ~{} of {} tokens",
+        cut,
+        estimated_tokens,
+        budget
+    );
+    TrimReport {
+        dropped: cut,
+        estimated_tokens,
+    }
+}
+
+/// Trim the request's messages by its `trim_strategy` when the model's context window is known
+pub fn fit_request(
+    request: &mut StreamTextRequest,
+    context_window: Option<u32>,
+) -> Option<TrimReport> {
+    let strategy = request.trim_strategy?;
+    let budget = prompt_budget(
+        context_window?,
+        request.max_tokens,
+        request.tools.as_deref(),
+    );
+    Some(trim_messages(&mut request.messages, budget, strategy))
+}
+
+/// User message listing the start of each dropped message, trimmed to `max_tokens` by
+/// leaving out the oldest lines first
+fn summarize(dropped: &[&Message], max_tokens: usize) -> Message {
+    let header = format!("[Summary of {} earlier messages]", dropped.len());
+    let mut chars_left = (max_tokens * CHARS_PER_TOKEN).saturating_sub(header.len());
+    let mut lines = Vec::new();
+    for message in dropped.iter().rev() {
+        let line = format!("{}: {}", role(message), excerpt(message));
+        let len = line.chars().count() + 1;
+        if len > chars_left {
+            break;
+        }
+        chars_left -= len;
+        lines.push(line);
+    }
+    lines.push(header);
+    lines.reverse();
+
+    Message::User {
+        content: MessageContent::Text(lines.join("\n")),
+        provider_options: None,
+        cache: false,
+    }
+}
+
+fn role(message: &Message) -> &'static str {
+    match message {
+        Message::System { .. } => "system",
+        Message::User { .. } => "user",
+        Message::Assistant { .. } => "assistant",
+        Message::Tool { .. } => "tool",
+    }
+}
+
+/// First words of a message on one line, with tool calls and results named
+fn excerpt(message: &Message) -> String {
+    let parts: Vec<String> = match message {
+        Message::System { content, .. } => vec![content.clone()],
+        Message::User { content, .. } | Message::Assistant { content, .. } => match content {
+            MessageContent::Text(text) => vec![text.clone()],
+            MessageContent::Parts(parts) => parts.iter().filter_map(part_excerpt).collect(),
+        },
+        Message::Tool { content, .. } => content.iter().filter_map(part_excerpt).collect(),
+    };
+    let text = parts
+        .join(" ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if text.chars().count() <= SUMMARY_EXCERPT_CHARS {
+        return text;
+    }
+    let mut short: String = text.chars().take(SUMMARY_EXCERPT_CHARS).collect();
+    short.push('…');
+    short
+}
+
+fn part_excerpt(part: &ContentPart) -> Option<String> {
+    match part {
+        ContentPart::Text { text } => Some(text.clone()),
+        ContentPart::Image { .. } => Some("[image]".to_string()),
+        ContentPart::Video { .. } => Some("[video]".to_string()),
+        ContentPart::ToolCall { tool_name, .. } => Some(format!("[called {}]", tool_name)),
+        ContentPart::ToolResult {
+            tool_name, output, ..
+        } => Some(format!("[{} returned {}]", tool_name, output)),
+        ContentPart::Reasoning { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn system(text: &str) -> Message {
+        Message::System {
+            content: text.to_string(),
+            provider_options: None,
+            cache: false,
+        }
+    }
+
+    fn user(text: &str) -> Message {
+        Message::User {
+            content: MessageContent::Text(text.to_string()),
+            provider_options: None,
+            cache: false,
+        }
+    }
+
+    fn assistant(text: &str) -> Message {
+        Message::Assistant {
+            content: MessageContent::Text(text.to_string()),
+            provider_options: None,
+            cache: false,
+        }
+    }
+
+    fn total(messages: &[Message]) -> usize {
+        messages.iter().map(estimate_message_tokens).sum()
+    }
+
+    /// A system prompt followed by ten turns of 400 characters each
+    fn conversation() -> Vec<Message> {
+        let mut messages = vec![system("You are a careful reviewer.")];
+        for turn in 0..10 {
+            let text = format!("turn {} {}", turn, "x".repeat(392));
+            messages.push(if turn % 2 == 0 {
+                user(&text)
+            } else {
+                assistant(&text)
+            });
+        }
+        messages
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_system_prompt_within_budget() {
+        let mut messages = conversation();
+
+        let report = trim_messages(&mut messages, 400, TrimStrategy::DropOldest);
+
+        assert!(matches!(
+            &messages[0],
+            Message::System { content, .. } if content == "You are a careful reviewer."
+        ));
+        assert!(total(&messages) <= 400);
+        assert_eq!(report.estimated_tokens, total(&messages));
+        assert_eq!(report.dropped, 10 - (messages.len() - 1));
+        // The newest turns are the ones kept
+        assert!(matches!(
+            messages.last(),
+            Some(Message::Assistant {
+                content: MessageContent::Text(text),
+                ..
+            }) if text.starts_with("turn 9 ")
+        ));
+    }
+
+    #[test]
+    fn summarize_oldest_replaces_dropped_turns_with_a_summary() {
+        let mut messages = conversation();
+
+        let report = trim_messages(&mut messages, 600, TrimStrategy::SummarizeOldest);
+
+        assert!(report.dropped > 0);
+        assert!(matches!(messages[0], Message::System { .. }));
+        let Message::User {
+            content: MessageContent::Text(summary),
+            ..
+        } = &messages[1]
+        else {
+            panic!("expected a summary after the system prompt");
+        };
+        assert!(summary.starts_with(&format!("[Summary of {} earlier messages]", report.dropped)));
+        assert!(summary.contains(&format!("turn {} ", report.dropped - 1)));
+        assert!(total(&messages) <= 600);
+        assert_eq!(report.estimated_tokens, total(&messages));
+    }
+
+    #[test]
+    fn tool_results_are_dropped_with_their_call() {
+        let call = Message::Assistant {
+            content: MessageContent::Parts(vec![ContentPart::ToolCall {
+                tool_call_id: "call_1".to_string(),
+                tool_name: "read_file".to_string(),
+                input: json!({ "path": "src/main.rs" }),
+                provider_metadata: None,
+            }]),
+            provider_options: None,
+            cache: false,
+        };
+        let result = Message::Tool {
+            content: vec![ContentPart::ToolResult {
+                tool_call_id: "call_1".to_string(),
+                tool_name: "read_file".to_string(),
+                output: json!("x".repeat(800)),
+            }],
+            provider_options: None,
+            cache: false,
+        };
+        let mut messages = vec![system("sys"), call, result, user("what changed?")];
+
+        trim_messages(&mut messages, 50, TrimStrategy::DropOldest);
+
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[1], Message::User { .. }));
+    }
+
+    #[test]
+    fn fitting_conversations_and_the_last_message_are_left_alone() {
+        let mut messages = conversation();
+        let report = trim_messages(&mut messages, usize::MAX, TrimStrategy::DropOldest);
+        assert_eq!(report.dropped, 0);
+        assert_eq!(messages.len(), 11);
+
+        let mut messages = vec![system("sys"), user(&"x".repeat(4_000))];
+        let report = trim_messages(&mut messages, 10, TrimStrategy::SummarizeOldest);
+        assert_eq!(report.dropped, 0);
+        assert_eq!(messages.len(), 2);
+    }
+
+    #[test]
+    fn prompt_budget_reserves_reply_and_tool_space() {
+        assert_eq!(estimate_tokens("abcdefghi"), 3);
+        assert_eq!(prompt_budget(8_192, Some(1_000), None), 7_192);
+        assert_eq!(prompt_budget(8_192, None, None), 6_144);
+        assert_eq!(prompt_budget(128_000, None, None), 123_904);
+        assert_eq!(prompt_budget(1_000, Some(2_000), None), 0);
+    }
+}
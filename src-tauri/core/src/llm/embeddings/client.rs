@@ -0,0 +1,222 @@
+// Embeddings client for OpenAI-compatible providers
+// Posts a batch of inputs to `{base_url}/embeddings` and returns one float vector per input
+
+use crate::llm::auth::api_key_manager::{ApiKeyManager, ProviderCredentials};
+use crate::llm::error::LlmError;
+use crate::llm::providers::provider::BaseProvider;
+use crate::llm::retry::RetryPolicy;
+use crate::llm::types::ProviderConfig;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize)]
+struct EmbeddingApiRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    /// Always request plain float arrays; base64 output would need decoding
+    encoding_format: &'static str,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EmbeddingApiResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EmbeddingData {
+    #[serde(default)]
+    index: Option<usize>,
+    embedding: Vec<f32>,
+}
+
+pub struct EmbeddingClient {
+    config: ProviderConfig,
+}
+
+impl EmbeddingClient {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self { config }
+    }
+
+    pub async fn create_embeddings(
+        &self,
+        api_keys: &ApiKeyManager,
+        model: &str,
+        input: Vec<String>,
+    ) -> Result<Vec<Vec<f32>>, LlmError> {
+        if input.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let api_key = match api_keys.get_credentials_rotating(&self.config).await? {
+            ProviderCredentials::Token(token) => token,
+            ProviderCredentials::None => {
+                return Err(LlmError::Auth(format!(
+                    "API key not configured for {} embeddings / {} 向量接口未配置 API 密钥",
+                    self.config.name, self.config.name
+                )))
+            }
+        };
+
+        let base = BaseProvider::new(self.config.clone());
+        let base_url = base.resolve_base_url_with_fallback(api_keys).await?;
+        let url = format!("{}/embeddings", base_url.trim_end_matches('/'));
+
+        let client = crate::llm::http_client::shared_client().map_err(LlmError::Other)?;
+        let request = client
+            .post(&url)
+            .timeout(REQUEST_TIMEOUT)
+            .bearer_auth(&api_key)
+            .json(&EmbeddingApiRequest {
+                model,
+                input: &input,
+                encoding_format: "float",
+            });
+
+        let response = RetryPolicy::for_provider(&self.config)
+            .send(request)
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            if status.as_u16() == 429 {
+                api_keys.mark_key_rate_limited(&api_key);
+            }
+            let headers = response.headers().clone();
+            let body = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(LlmError::from_response_parts(
+                status.as_u16(),
+                &headers,
+                format!(
+                    "Embeddings request failed ({}): {} / 向量生成失败",
+                    status, body
+                ),
+            ));
+        }
+
+        let body = response.text().await?;
+        let embeddings = parse_embeddings_response(&body)?;
+        if embeddings.len() != input.len() {
+            return Err(LlmError::InvalidResponse(format!(
+                "Expected {} embeddings, got {} / 返回的向量数量不匹配",
+                input.len(),
+                embeddings.len()
+            )));
+        }
+        Ok(embeddings)
+    }
+}
+
+/// Extract vectors ordered by their `index`, falling back to response order
+fn parse_embeddings_response(body: &str) -> Result<Vec<Vec<f32>>, LlmError> {
+    let payload: EmbeddingApiResponse = serde_json::from_str(body).map_err(|e| {
+        LlmError::InvalidResponse(format!("Failed to parse embeddings response: {}", e))
+    })?;
+    let mut data = payload.data;
+    if data.iter().all(|item| item.index.is_some()) {
+        data.sort_by_key(|item| item.index);
+    }
+    Ok(data.into_iter().map(|item| item.embedding).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::testing::mock_server::start_sequence_server;
+    use crate::llm::types::ProtocolType;
+    use serde_json::json;
+
+    #[test]
+    fn parses_multiple_input_vectors() {
+        let body = json!({
+            "object": "list",
+            "data": [
+                { "object": "embedding", "index": 0, "embedding": [0.1, -0.2, 0.3] },
+                { "object": "embedding", "index": 1, "embedding": [0.4, 0.5, -0.6] }
+            ],
+            "model": "text-embedding-3-small",
+            "usage": { "prompt_tokens": 8, "total_tokens": 8 }
+        })
+        .to_string();
+
+        let embeddings = parse_embeddings_response(&body).expect("parse response");
+
+        assert_eq!(embeddings, vec![vec![0.1, -0.2, 0.3], vec![0.4, 0.5, -0.6]]);
+    }
+
+    #[test]
+    fn orders_vectors_by_index() {
+        let body = json!({
+            "data": [
+                { "index": 1, "embedding": [2.0] },
+                { "index": 0, "embedding": [1.0] }
+            ]
+        })
+        .to_string();
+
+        let embeddings = parse_embeddings_response(&body).expect("parse response");
+
+        assert_eq!(embeddings, vec![vec![1.0], vec![2.0]]);
+    }
+
+    #[test]
+    fn rejects_malformed_response() {
+        let result = parse_embeddings_response(r#"{"error":"nope"}"#);
+        assert!(matches!(result, Err(LlmError::InvalidResponse(_))));
+    }
+
+    #[test]
+    fn request_body_asks_for_float_encoding() {
+        let input = vec!["hello".to_string(), "world".to_string()];
+        let body = serde_json::to_value(EmbeddingApiRequest {
+            model: "text-embedding-3-small",
+            input: &input,
+            encoding_format: "float",
+        })
+        .expect("serialize request");
+
+        assert_eq!(
+            body,
+            json!({
+                "model": "text-embedding-3-small",
+                "input": ["hello", "world"],
+                "encoding_format": "float"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn create_embeddings_returns_vectors_from_provider() {
+        let response = json!({
+            "data": [
+                { "index": 0, "embedding": [0.25, 0.5] },
+                { "index": 1, "embedding": [0.75, 1.0] }
+            ]
+        })
+        .to_string();
+        let (base_url, hits) =
+            start_sequence_server(vec![(200, response)]).expect("start mock server");
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+
+        let config = provider_config("openai", ProtocolType::OpenAiCompatible, &base_url);
+        let client = EmbeddingClient::new(config);
+        let embeddings = client
+            .create_embeddings(
+                &api_keys,
+                "text-embedding-3-small",
+                vec!["first".to_string(), "second".to_string()],
+            )
+            .await
+            .expect("create embeddings");
+
+        assert_eq!(embeddings, vec![vec![0.25, 0.5], vec![0.75, 1.0]]);
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,387 @@
+// Structured error type for provider and image client operations
+// Lets callers branch on the error kind (auth, rate limit, network) instead of matching strings
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::time::Duration;
+
+/// Error returned by providers and image clients
+#[derive(Debug, Clone, PartialEq)]
+pub enum LlmError {
+    /// Missing or rejected credentials
+    Auth(String),
+    /// The provider throttled the request (HTTP 429)
+    RateLimited {
+        retry_after: Option<Duration>,
+        body: String,
+    },
+    /// The request never produced a response (connect, DNS, TLS, timeout)
+    Network(String),
+    /// A response arrived but could not be understood
+    InvalidResponse(String),
+    /// The provider answered with a non-success status
+    /// `body` is the raw response, `message` the provider's `error.message` when it sent JSON
+    ProviderError {
+        status: u16,
+        body: String,
+        message: Option<String>,
+    },
+    /// The provider's moderation refused the prompt before generating anything
+    /// `body` keeps the provider's filter details, such as Azure's `content_filter_result`
+    ContentFiltered {
+        status: u16,
+        body: String,
+        message: Option<String>,
+    },
+    /// The request body is over the provider's `max_request_bytes`, so it was never sent
+    RequestTooLarge { size: usize, limit: usize },
+    /// The stream stayed open but sent nothing, not even a keep-alive, for `idle`
+    StreamStalled { idle: Duration },
+    /// Configuration and request-building failures
+    Other(String),
+    /// The caller aborted the request before it finished
+    Cancelled,
+}
+
+impl LlmError {
+    /// Classify a failed HTTP response by status code
+    pub fn from_status(
+        status: u16,
+        body: impl Into<String>,
+        retry_after: Option<Duration>,
+    ) -> Self {
+        let body = body.into();
+        match status {
+            401 | 403 => Self::Auth(format!(
+                "Authentication failed ({}): {} / 认证失败",
+                status, body
+            )),
+            429 => Self::RateLimited { retry_after, body },
+            400 if is_content_filter_error(&body) => Self::ContentFiltered {
+                status,
+                message: provider_error_message(&body),
+                body,
+            },
+            _ => Self::ProviderError {
+                status,
+                message: provider_error_message(&body),
+                body,
+            },
+        }
+    }
+
+    /// Classify a failed response, reading `Retry-After` from its headers
+    pub fn from_response_parts(status: u16, headers: &HeaderMap, body: impl Into<String>) -> Self {
+        Self::from_status(status, body, retry_after_from_headers(headers))
+    }
+
+    /// HTTP status that caused the error, if any
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::RateLimited { .. } => Some(429),
+            Self::ProviderError { status, .. } | Self::ContentFiltered { status, .. } => {
+                Some(*status)
+            }
+            _ => None,
+        }
+    }
+
+    /// Raw response body the provider sent with a failed status
+    pub fn body(&self) -> Option<&str> {
+        match self {
+            Self::RateLimited { body, .. }
+            | Self::ProviderError { body, .. }
+            | Self::ContentFiltered { body, .. } => Some(body),
+            _ => None,
+        }
+    }
+
+    /// Human-readable reason extracted from the provider's JSON error body
+    pub fn provider_message(&self) -> Option<&str> {
+        match self {
+            Self::ProviderError { message, .. } | Self::ContentFiltered { message, .. } => {
+                message.as_deref()
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether repeating the same request may succeed
+    pub fn is_retriable(&self) -> bool {
+        match self {
+            Self::RateLimited { .. } | Self::Network(_) | Self::StreamStalled { .. } => true,
+            Self::ProviderError { status, .. } => is_retriable_status(*status),
+            Self::Auth(_)
+            | Self::InvalidResponse(_)
+            | Self::ContentFiltered { .. }
+            | Self::RequestTooLarge { .. }
+            | Self::Other(_)
+            | Self::Cancelled => false,
+        }
+    }
+}
+
+/// Pull the reason out of a provider error body
+/// Accepts `{"error": {"message": ..}}`, `{"error": ".."}` and `{"message": ..}`
+pub fn provider_error_message(body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(body.trim()).ok()?;
+    let message = match value.get("error") {
+        Some(serde_json::Value::String(message)) => Some(message.as_str()),
+        Some(error) => error.get("message").and_then(|m| m.as_str()),
+        None => value.get("message").and_then(|m| m.as_str()),
+    }?;
+    let message = message.trim();
+    (!message.is_empty()).then(|| message.to_string())
+}
+
+/// Whether an error body reports a moderation refusal
+/// OpenAI and Azure send `"code": "content_filter"`, Azure adds `ResponsibleAIPolicyViolation`
+fn is_content_filter_error(body: &str) -> bool {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(body.trim()) else {
+        return false;
+    };
+    let Some(error) = value.get("error") else {
+        return false;
+    };
+    let code = |value: &serde_json::Value| value.get("code").and_then(|c| c.as_str());
+    code(error) == Some("content_filter")
+        || error.get("innererror").and_then(code) == Some("ResponsibleAIPolicyViolation")
+}
+
+/// Whether an HTTP status is worth retrying (throttling, timeouts, server errors)
+pub fn is_retriable_status(status: u16) -> bool {
+    status == 408 || status == 429 || status >= 500
+}
+
+/// Parse a `Retry-After` header given either as delay-seconds or an HTTP date
+pub fn retry_after_from_headers(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+    parse_retry_after(value)
+}
+
+pub fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.timestamp_millis() - chrono::Utc::now().timestamp_millis();
+    Some(Duration::from_millis(delay.max(0) as u64))
+}
+
+impl std::fmt::Display for LlmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auth(msg)
+            | Self::Network(msg)
+            | Self::InvalidResponse(msg)
+            | Self::Other(msg) => {
+                write!(f, "{}", msg)
+            }
+            Self::RateLimited { retry_after, body } => match retry_after {
+                Some(delay) => write!(
+                    f,
+                    "Rate limited, retry after {}s: {} / 请求过于频繁，请 {} 秒后重试",
+                    delay.as_secs(),
+                    body,
+                    delay.as_secs()
+                ),
+                None => write!(f, "Rate limited: {} / 请求过于频繁", body),
+            },
+            Self::ProviderError {
+                status,
+                body,
+                message,
+            } => {
+                let detail = message.as_deref().unwrap_or(body);
+                write!(f, "HTTP {}: {} / 服务商返回错误 {}", status, detail, status)
+            }
+            Self::ContentFiltered { body, message, .. } => {
+                let detail = message.as_deref().unwrap_or(body);
+                write!(
+                    f,
+                    "Blocked by the provider's content filter: {} / 内容被服务商安全策略拦截",
+                    detail
+                )
+            }
+            Self::RequestTooLarge { size, limit } => write!(
+                f,
+                "Request body is {} ({} bytes), over the provider's {} limit; send smaller images or less context / 请求体 {} 超过服务商限制 {}",
+                format_size(*size),
+                size,
+                format_size(*limit),
+                format_size(*size),
+                format_size(*limit)
+            ),
+            Self::StreamStalled { idle } => write!(
+                f,
+                "Stream stalled, no data received for {:?} / 流式响应停滞，{:?} 内未收到数据",
+                idle, idle
+            ),
+            Self::Cancelled => write!(f, "Request cancelled / 请求已取消"),
+        }
+    }
+}
+
+fn format_size(bytes: usize) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    format!("{:.1} MB", bytes as f64 / MB)
+}
+
+impl std::error::Error for LlmError {}
+
+impl From<LlmError> for String {
+    fn from(err: LlmError) -> Self {
+        err.to_string()
+    }
+}
+
+impl From<String> for LlmError {
+    fn from(msg: String) -> Self {
+        Self::Other(msg)
+    }
+}
+
+impl From<reqwest::Error> for LlmError {
+    fn from(err: reqwest::Error) -> Self {
+        if let Some(status) = err.status() {
+            return Self::from_status(status.as_u16(), err.to_string(), None);
+        }
+        if err.is_decode() {
+            return Self::InvalidResponse(format!(
+                "Failed to decode response: {} / 响应解析失败",
+                err
+            ));
+        }
+        if err.is_builder() {
+            return Self::Other(format!("Failed to build request: {}", err));
+        }
+        Self::Network(format!("Network error: {} / 网络请求失败", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn classifies_auth_statuses() {
+        for status in [401, 403] {
+            let err = LlmError::from_status(status, "invalid key", None);
+            assert!(
+                matches!(err, LlmError::Auth(_)),
+                "{} should be auth",
+                status
+            );
+            assert!(!err.is_retriable());
+        }
+    }
+
+    #[test]
+    fn classifies_rate_limit_with_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("7"));
+
+        let err = LlmError::from_response_parts(429, &headers, "slow down");
+
+        assert_eq!(
+            err,
+            LlmError::RateLimited {
+                retry_after: Some(Duration::from_secs(7)),
+                body: "slow down".to_string(),
+            }
+        );
+        assert_eq!(err.status(), Some(429));
+        assert!(err.is_retriable());
+    }
+
+    #[test]
+    fn classifies_server_and_client_errors() {
+        let server = LlmError::from_status(503, "overloaded", None);
+        assert_eq!(
+            server,
+            LlmError::ProviderError {
+                status: 503,
+                body: "overloaded".to_string(),
+                message: None,
+            }
+        );
+        assert!(server.is_retriable());
+
+        let timeout = LlmError::from_status(408, "timeout", None);
+        assert!(timeout.is_retriable());
+
+        let bad_request = LlmError::from_status(400, "bad prompt", None);
+        assert_eq!(bad_request.status(), Some(400));
+        assert!(!bad_request.is_retriable());
+    }
+
+    #[test]
+    fn display_keeps_messages_verbatim() {
+        let msg = "API key not configured / 未配置 API 密钥".to_string();
+        assert_eq!(LlmError::Auth(msg.clone()).to_string(), msg);
+        assert_eq!(String::from(LlmError::from(msg.clone())), msg);
+        assert_eq!(
+            LlmError::from_status(500, "boom", None).to_string(),
+            "HTTP 500: boom / 服务商返回错误 500"
+        );
+    }
+
+    #[test]
+    fn keeps_status_body_and_message_from_json_error() {
+        let body = r#"{"error":{"code":"InputTextSensitiveContentDetected","message":"The request failed because the input text may contain sensitive information.","type":"BadRequest"}}"#;
+
+        let err = LlmError::from_status(400, body, None);
+
+        assert_eq!(err.status(), Some(400));
+        assert_eq!(err.body(), Some(body));
+        assert_eq!(
+            err.provider_message(),
+            Some("The request failed because the input text may contain sensitive information.")
+        );
+        assert_eq!(
+            err.to_string(),
+            "HTTP 400: The request failed because the input text may contain sensitive information. / 服务商返回错误 400"
+        );
+    }
+
+    #[test]
+    fn classifies_content_filter_refusals() {
+        let body = r#"{"error":{"message":"The response was filtered due to the prompt triggering Azure OpenAI's content management policy.","type":null,"param":"prompt","code":"content_filter","status":400,"innererror":{"code":"ResponsibleAIPolicyViolation","content_filter_result":{"hate":{"filtered":false,"severity":"safe"},"violence":{"filtered":true,"severity":"medium"}}}}}"#;
+
+        let err = LlmError::from_status(400, body, None);
+
+        assert!(matches!(err, LlmError::ContentFiltered { .. }), "{:?}", err);
+        assert_eq!(err.status(), Some(400));
+        assert_eq!(err.body(), Some(body));
+        assert!(!err.is_retriable());
+        assert!(err.to_string().starts_with("Blocked by"));
+
+        let other = LlmError::from_status(400, r#"{"error":{"code":"invalid_value"}}"#, None);
+        assert!(matches!(other, LlmError::ProviderError { .. }));
+    }
+
+    #[test]
+    fn provider_error_message_handles_common_shapes() {
+        assert_eq!(
+            provider_error_message(r#"{"error":"quota exceeded"}"#).as_deref(),
+            Some("quota exceeded")
+        );
+        assert_eq!(
+            provider_error_message(r#"{"message":"model not found","code":404}"#).as_deref(),
+            Some("model not found")
+        );
+        assert_eq!(provider_error_message(r#"{"error":{"message":""}}"#), None);
+        assert_eq!(provider_error_message("<html>Bad Gateway</html>"), None);
+    }
+
+    #[test]
+    fn parses_retry_after_values() {
+        assert_eq!(parse_retry_after("12"), Some(Duration::from_secs(12)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -0,0 +1,709 @@
+// Shared HTTP client settings for provider requests
+// Applies the configured HTTP/SOCKS proxy, User-Agent and extra TLS root certificates to every
+// client built through `client_builder`
+// Provider requests share one pooled client from `shared_client` and set their own timeouts
+// Middleware installed with `set_middleware` wraps every provider and image request
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+/// Setting key holding the serialized `HttpSettings`
+pub const HTTP_SETTINGS_KEY: &str = "http_settings_json";
+
+/// Hosts that never go through a proxy, so local providers such as Ollama keep working
+const LOCAL_NO_PROXY: &str = "localhost,127.0.0.1,::1";
+
+/// User-Agent sent to providers unless the settings or a provider pick another one
+pub const DEFAULT_USER_AGENT: &str = concat!("talkcody/", env!("CARGO_PKG_VERSION"));
+
+/// Marks a `root_certificates` entry as inline PEM rather than a file path
+const PEM_PREFIX: &str = "-----BEGIN";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_POOL_MAX_IDLE_PER_HOST: usize = 5;
+const DEFAULT_POOL_IDLE_TIMEOUT_SECS: u64 = 90;
+const DEFAULT_TCP_KEEPALIVE_SECS: u64 = 60;
+
+static HTTP_SETTINGS: RwLock<HttpSettings> = RwLock::new(HttpSettings {
+    http_proxy: None,
+    https_proxy: None,
+    no_proxy: None,
+    pool_max_idle_per_host: None,
+    pool_idle_timeout_secs: None,
+    tcp_keepalive_secs: None,
+    root_certificates: Vec::new(),
+    request_id_header: None,
+    user_agent: None,
+    header_denylist: Vec::new(),
+    header_allowlist: Vec::new(),
+    #[cfg(feature = "danger-accept-invalid-certs")]
+    danger_accept_invalid_certs: false,
+});
+
+/// Stack installed with `set_middleware`, outermost first
+static MIDDLEWARE: RwLock<Vec<Arc<dyn HttpMiddleware>>> = RwLock::new(Vec::new());
+
+/// Client shared by provider requests, rebuilt when the effective settings change
+static SHARED_CLIENT: Mutex<Option<(HttpSettings, reqwest::Client)>> = Mutex::new(None);
+
+/// Proxy, connection pool and TLS configuration for outbound requests
+/// Proxy URLs may use `http://`, `https://` or `socks5://`/`socks5h://`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpSettings {
+    #[serde(rename = "httpProxy")]
+    pub http_proxy: Option<String>,
+    #[serde(rename = "httpsProxy")]
+    pub https_proxy: Option<String>,
+    /// Comma-separated hosts, domains or CIDRs that bypass the proxy
+    #[serde(rename = "noProxy")]
+    pub no_proxy: Option<String>,
+    /// Idle connections kept open per host, 0 disables pooling
+    #[serde(rename = "poolMaxIdlePerHost")]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Seconds an idle pooled connection is kept before closing
+    #[serde(rename = "poolIdleTimeoutSecs")]
+    pub pool_idle_timeout_secs: Option<u64>,
+    /// Seconds between TCP keep-alive probes, 0 disables them
+    #[serde(rename = "tcpKeepaliveSecs")]
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Extra trusted roots for internal CAs, each a PEM file path or inline PEM text
+    #[serde(rename = "rootCertificates")]
+    pub root_certificates: Vec<String>,
+    /// Header the client request ID is sent under, `x-request-id` when unset; "" stops sending
+    #[serde(rename = "requestIdHeader")]
+    pub request_id_header: Option<String>,
+    /// User-Agent for provider requests, `talkcody/<version>` when unset
+    /// Providers that must identify as a specific client still send their own
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    /// Headers never sent to providers, such as telemetry; names compare case-insensitively
+    /// Auth and Content-Type headers are kept regardless
+    #[serde(rename = "headerDenylist")]
+    pub header_denylist: Vec<String>,
+    /// When non-empty, provider requests carry only these headers besides auth and Content-Type
+    #[serde(rename = "headerAllowlist")]
+    pub header_allowlist: Vec<String>,
+    /// DANGER: turns off TLS certificate verification for every provider request, so anyone
+    /// on the network path can impersonate a provider and read API keys. Only compiled in
+    /// with the `danger-accept-invalid-certs` feature; prefer `root_certificates`
+    #[cfg(feature = "danger-accept-invalid-certs")]
+    #[serde(rename = "dangerAcceptInvalidCerts")]
+    pub danger_accept_invalid_certs: bool,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+impl HttpSettings {
+    /// Read `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` (either case)
+    pub fn from_env() -> Self {
+        Self::from_lookup(|key| std::env::var(key).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let read = |name: &str| {
+            lookup(name)
+                .or_else(|| lookup(&name.to_lowercase()))
+                .filter(|value| !value.trim().is_empty())
+        };
+        let all_proxy = read("ALL_PROXY");
+        Self {
+            http_proxy: read("HTTP_PROXY").or_else(|| all_proxy.clone()),
+            https_proxy: read("HTTPS_PROXY").or(all_proxy),
+            no_proxy: read("NO_PROXY"),
+            ..Self::default()
+        }
+    }
+
+    /// Fill fields left empty in the explicit settings from `fallback`
+    pub fn or_else(&self, fallback: &HttpSettings) -> HttpSettings {
+        let pick = |explicit: &Option<String>, fallback: &Option<String>| {
+            non_empty(explicit)
+                .or_else(|| non_empty(fallback))
+                .map(str::to_string)
+        };
+        HttpSettings {
+            http_proxy: pick(&self.http_proxy, &fallback.http_proxy),
+            https_proxy: pick(&self.https_proxy, &fallback.https_proxy),
+            no_proxy: pick(&self.no_proxy, &fallback.no_proxy),
+            pool_max_idle_per_host: self
+                .pool_max_idle_per_host
+                .or(fallback.pool_max_idle_per_host),
+            pool_idle_timeout_secs: self
+                .pool_idle_timeout_secs
+                .or(fallback.pool_idle_timeout_secs),
+            tcp_keepalive_secs: self.tcp_keepalive_secs.or(fallback.tcp_keepalive_secs),
+            root_certificates: if self.root_certificates.is_empty() {
+                fallback.root_certificates.clone()
+            } else {
+                self.root_certificates.clone()
+            },
+            request_id_header: self
+                .request_id_header
+                .clone()
+                .or_else(|| fallback.request_id_header.clone()),
+            user_agent: pick(&self.user_agent, &fallback.user_agent),
+            header_denylist: self.header_denylist.clone(),
+            header_allowlist: self.header_allowlist.clone(),
+            #[cfg(feature = "danger-accept-invalid-certs")]
+            danger_accept_invalid_certs: self.danger_accept_invalid_certs,
+        }
+    }
+
+    /// Bypass list passed to reqwest, always including local addresses
+    pub fn no_proxy_list(&self) -> String {
+        match non_empty(&self.no_proxy) {
+            Some(extra) => format!("{},{}", LOCAL_NO_PROXY, extra),
+            None => LOCAL_NO_PROXY.to_string(),
+        }
+    }
+
+    /// Parse `root_certificates`, reading entries that are not inline PEM from disk
+    pub fn load_root_certificates(&self) -> Result<Vec<reqwest::Certificate>, String> {
+        let mut certificates = Vec::new();
+        for entry in self.root_certificates.iter().map(|entry| entry.trim()) {
+            if entry.is_empty() {
+                continue;
+            }
+            let (label, pem) = if entry.starts_with(PEM_PREFIX) {
+                ("inline PEM", entry.as_bytes().to_vec())
+            } else {
+                let pem = std::fs::read(entry).map_err(|e| {
+                    format!(
+                        "Failed to read root certificate '{}': {} / 读取根证书失败",
+                        entry, e
+                    )
+                })?;
+                (entry, pem)
+            };
+            let parsed = reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| {
+                format!("Invalid root certificate in {}: {} / 根证书无效", label, e)
+            })?;
+            if parsed.is_empty() {
+                return Err(format!("No certificate found in {} / 未找到证书", label));
+            }
+            certificates.extend(parsed);
+        }
+        Ok(certificates)
+    }
+
+    /// Configured User-Agent, or the default
+    pub fn user_agent(&self) -> &str {
+        non_empty(&self.user_agent).unwrap_or(DEFAULT_USER_AGENT)
+    }
+
+    /// Add the configured proxies, User-Agent and TLS settings to a client builder
+    pub fn apply(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> Result<reqwest::ClientBuilder, String> {
+        let no_proxy = reqwest::NoProxy::from_string(&self.no_proxy_list());
+        if let Some(url) = non_empty(&self.http_proxy) {
+            let proxy = reqwest::Proxy::http(url)
+                .map_err(|e| format!("Invalid HTTP proxy '{}': {} / HTTP 代理地址无效", url, e))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+        }
+        if let Some(url) = non_empty(&self.https_proxy) {
+            let proxy = reqwest::Proxy::https(url).map_err(|e| {
+                format!("Invalid HTTPS proxy '{}': {} / HTTPS 代理地址无效", url, e)
+            })?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy));
+        }
+        builder = builder.user_agent(self.user_agent());
+        for certificate in self.load_root_certificates()? {
+            builder = builder.add_root_certificate(certificate);
+        }
+        #[cfg(feature = "danger-accept-invalid-certs")]
+        if self.danger_accept_invalid_certs {
+            log::warn!(
+                "[HttpClient] TLS certificate verification is DISABLED for provider requests"
+            );
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder)
+    }
+
+    /// Add the connection pool and keep-alive settings to a client builder
+    pub fn apply_pool(&self, builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        let idle_timeout = self
+            .pool_idle_timeout_secs
+            .unwrap_or(DEFAULT_POOL_IDLE_TIMEOUT_SECS);
+        let keepalive = self
+            .tcp_keepalive_secs
+            .unwrap_or(DEFAULT_TCP_KEEPALIVE_SECS);
+        builder
+            .pool_max_idle_per_host(
+                self.pool_max_idle_per_host
+                    .unwrap_or(DEFAULT_POOL_MAX_IDLE_PER_HOST),
+            )
+            .pool_idle_timeout(Duration::from_secs(idle_timeout))
+            .tcp_keepalive((keepalive > 0).then(|| Duration::from_secs(keepalive)))
+    }
+}
+
+/// Explicitly configured settings, without the environment fallback
+pub fn http_settings() -> HttpSettings {
+    HTTP_SETTINGS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Settings in effect for new clients: explicit values first, then the environment
+pub fn effective_http_settings() -> HttpSettings {
+    http_settings().or_else(&HttpSettings::from_env())
+}
+
+/// Validate and install new settings for clients built from now on
+pub fn set_http_settings(settings: HttpSettings) -> Result<(), String> {
+    settings.apply(reqwest::Client::builder())?;
+    if let Some(name) = non_empty(&settings.request_id_header) {
+        reqwest::header::HeaderName::from_bytes(name.as_bytes()).map_err(|e| {
+            format!(
+                "Invalid request ID header '{}': {} / 请求 ID 头名称无效",
+                name, e
+            )
+        })?;
+    }
+    if let Some(user_agent) = non_empty(&settings.user_agent) {
+        reqwest::header::HeaderValue::from_str(user_agent).map_err(|e| {
+            format!(
+                "Invalid User-Agent '{}': {} / User-Agent 无效",
+                user_agent, e
+            )
+        })?;
+    }
+    *HTTP_SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = settings;
+    Ok(())
+}
+
+/// Load persisted settings at startup
+pub async fn load_http_settings(api_keys: &ApiKeyManager) -> Result<(), String> {
+    if let Some(raw) = api_keys.get_setting(HTTP_SETTINGS_KEY).await? {
+        let settings: HttpSettings = serde_json::from_str(&raw)
+            .map_err(|e| format!("Failed to parse HTTP settings: {}", e))?;
+        set_http_settings(settings)?;
+    }
+    Ok(())
+}
+
+/// Persist and install new settings
+pub async fn save_http_settings(
+    api_keys: &ApiKeyManager,
+    settings: HttpSettings,
+) -> Result<(), String> {
+    set_http_settings(settings.clone())?;
+    let raw = serde_json::to_string(&settings)
+        .map_err(|e| format!("Failed to serialize HTTP settings: {}", e))?;
+    api_keys.set_setting(HTTP_SETTINGS_KEY, &raw).await
+}
+
+/// `reqwest::Client::builder()` with the effective proxy and TLS settings applied
+/// Invalid proxies from the environment are logged and ignored
+pub fn client_builder() -> reqwest::ClientBuilder {
+    client_builder_for(&effective_http_settings())
+}
+
+/// `reqwest::Client::builder()` with the given proxy and TLS settings applied
+pub fn client_builder_for(settings: &HttpSettings) -> reqwest::ClientBuilder {
+    match settings.apply(reqwest::Client::builder()) {
+        Ok(builder) => builder,
+        Err(err) => {
+            log::warn!("[HttpClient] Ignoring HTTP settings: {}", err);
+            reqwest::Client::builder()
+        }
+    }
+}
+
+/// The pooled client shared by provider requests
+/// It has no overall timeout; callers set one per request with `RequestBuilder::timeout`
+pub fn shared_client() -> Result<reqwest::Client, String> {
+    let settings = effective_http_settings();
+    let mut cached = SHARED_CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((cached_settings, client)) = cached.as_ref() {
+        if *cached_settings == settings {
+            return Ok(client.clone());
+        }
+    }
+
+    let client = settings
+        .apply_pool(client_builder_for(&settings))
+        .connect_timeout(CONNECT_TIMEOUT)
+        .tcp_nodelay(true)
+        .gzip(false)
+        .brotli(false)
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+    *cached = Some((settings, client.clone()));
+    Ok(client)
+}
+
+/// Hook around outbound provider requests, for tracing spans, metrics or custom retries
+/// Modelled on `reqwest-middleware`: call `next.run(request)` to send the request on, or
+/// return a response without calling it to short-circuit
+#[async_trait]
+pub trait HttpMiddleware: Send + Sync {
+    async fn handle(
+        &self,
+        request: reqwest::Request,
+        next: Next<'_>,
+    ) -> Result<reqwest::Response, LlmError>;
+}
+
+/// The rest of the middleware stack, ending with the client that sends the request
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    client: &'a reqwest::Client,
+    middleware: &'a [Arc<dyn HttpMiddleware>],
+}
+
+impl Next<'_> {
+    /// Pass `request` to the next middleware, or send it when none are left
+    /// May be called more than once, with `Request::try_clone`, to retry
+    pub async fn run(self, request: reqwest::Request) -> Result<reqwest::Response, LlmError> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => {
+                let next = Next {
+                    client: self.client,
+                    middleware: rest,
+                };
+                first.handle(request, next).await
+            }
+            None => Ok(self.client.execute(request).await?),
+        }
+    }
+}
+
+/// Install the middleware provider and image requests go through, outermost first
+/// An empty list restores plain sends
+pub fn set_middleware(middleware: Vec<Arc<dyn HttpMiddleware>>) {
+    *MIDDLEWARE.write().unwrap_or_else(|e| e.into_inner()) = middleware;
+}
+
+/// `RequestBuilder::send` through the installed middleware
+#[async_trait]
+pub trait SendWithMiddleware {
+    async fn send_with_middleware(self) -> Result<reqwest::Response, LlmError>;
+}
+
+#[async_trait]
+impl SendWithMiddleware for reqwest::RequestBuilder {
+    async fn send_with_middleware(self) -> Result<reqwest::Response, LlmError> {
+        let middleware = MIDDLEWARE.read().unwrap_or_else(|e| e.into_inner()).clone();
+        if middleware.is_empty() {
+            return Ok(self.send().await?);
+        }
+        let (client, request) = self.build_split();
+        let next = Next {
+            client: &client,
+            middleware: &middleware,
+        };
+        next.run(request?).await
+    }
+}
+
+/// Resolve a per-request timeout given in milliseconds
+/// `None` falls back to `default`, `Some(0)` means no timeout
+pub fn resolve_timeout(timeout_ms: Option<u64>, default: Duration) -> Option<Duration> {
+    match timeout_ms {
+        Some(0) => None,
+        Some(ms) => Some(Duration::from_millis(ms)),
+        None => Some(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Answer one request with 200 and report the request line seen by the server
+    fn start_recording_server() -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind server");
+        let addr = listener.local_addr().expect("server address");
+        let server = tiny_http::Server::from_listener(listener, None).expect("start server");
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            if let Ok(mut request) = server.recv() {
+                let mut body = String::new();
+                let _ = request.as_reader().read_to_string(&mut body);
+                let _ = tx.send(request.url().to_string());
+                let _ = request.respond(tiny_http::Response::from_string("ok"));
+            }
+        });
+        (format!("http://{}", addr), rx)
+    }
+
+    #[test]
+    fn resolve_timeout_handles_default_zero_and_custom() {
+        let default = Duration::from_secs(120);
+        assert_eq!(resolve_timeout(None, default), Some(default));
+        assert_eq!(resolve_timeout(Some(0), default), None);
+        assert_eq!(
+            resolve_timeout(Some(1500), default),
+            Some(Duration::from_millis(1500))
+        );
+    }
+
+    #[test]
+    fn reads_proxy_settings_from_environment_lookup() {
+        let env: HashMap<&str, &str> = HashMap::from([
+            ("https_proxy", "http://proxy.corp:8080"),
+            ("ALL_PROXY", "socks5://127.0.0.1:1080"),
+            ("NO_PROXY", "internal.corp"),
+        ]);
+        let settings = HttpSettings::from_lookup(|key| env.get(key).map(|v| v.to_string()));
+
+        assert_eq!(
+            settings,
+            HttpSettings {
+                http_proxy: Some("socks5://127.0.0.1:1080".to_string()),
+                https_proxy: Some("http://proxy.corp:8080".to_string()),
+                no_proxy: Some("internal.corp".to_string()),
+                ..HttpSettings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn explicit_settings_take_precedence_over_environment() {
+        let explicit = HttpSettings {
+            http_proxy: Some("http://explicit:3128".to_string()),
+            https_proxy: Some("  ".to_string()),
+            pool_max_idle_per_host: Some(2),
+            ..HttpSettings::default()
+        };
+        let env = HttpSettings {
+            http_proxy: Some("http://env:3128".to_string()),
+            https_proxy: Some("http://env:3129".to_string()),
+            no_proxy: Some("corp".to_string()),
+            pool_max_idle_per_host: Some(8),
+            tcp_keepalive_secs: Some(30),
+            ..HttpSettings::default()
+        };
+
+        let merged = explicit.or_else(&env);
+
+        assert_eq!(merged.http_proxy.as_deref(), Some("http://explicit:3128"));
+        assert_eq!(merged.https_proxy.as_deref(), Some("http://env:3129"));
+        assert_eq!(merged.no_proxy_list(), "localhost,127.0.0.1,::1,corp");
+        assert_eq!(merged.pool_max_idle_per_host, Some(2));
+        assert_eq!(merged.tcp_keepalive_secs, Some(30));
+    }
+
+    const TEST_CA_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBjTCCATKgAwIBAgITPQrHjYZgIy4ZVCDYfgrQZULWrTAKBggqhkjOPQQDAjAb
+MRkwFwYDVQQDDBBUYWxrQ29keSBUZXN0IENBMCAXDTI2MTAxNDA3MDA0NFoYDzIx
+MjYwOTIwMDcwMDQ0WjAbMRkwFwYDVQQDDBBUYWxrQ29keSBUZXN0IENBMFkwEwYH
+KoZIzj0CAQYIKoZIzj0DAQcDQgAEmfKdFzlw7+2Vu7Tptup02G2xOCLCX6US+WSl
+ZqzkUpTqFlJNNzTanbkpN4yL6/YXdB26Xzu7zO3jLoTb1F/SgaNTMFEwHQYDVR0O
+BBYEFJgiVI3CcIiRp2XlxShyGAsVqQHKMB8GA1UdIwQYMBaAFJgiVI3CcIiRp2Xl
+xShyGAsVqQHKMA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSQAwRgIhAN+F
+8o6BuED0ufOMRC/oK8wg5inxnhGtAOPV0D0OKjjRAiEAsfqlXCU1pBrLnsRtIIxb
+xjCuYQtO7awU1s48h1q3nmI=
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn root_certificates_load_from_inline_pem_and_files() {
+        let dir = tempfile::TempDir::new().expect("temp dir");
+        let path = dir.path().join("corp-ca.pem");
+        std::fs::write(&path, TEST_CA_PEM).expect("write cert");
+        let settings = HttpSettings {
+            root_certificates: vec![TEST_CA_PEM.to_string(), path.to_string_lossy().to_string()],
+            ..HttpSettings::default()
+        };
+
+        assert_eq!(
+            settings
+                .load_root_certificates()
+                .expect("load certificates")
+                .len(),
+            2
+        );
+        let client = settings
+            .apply(reqwest::Client::builder())
+            .expect("apply certificates")
+            .build();
+        assert!(client.is_ok());
+    }
+
+    #[test]
+    fn invalid_root_certificates_are_rejected() {
+        for entry in [
+            "-----BEGIN CERTIFICATE-----\nnot base64\n",
+            "/no/such/ca.pem",
+        ] {
+            let settings = HttpSettings {
+                root_certificates: vec![entry.to_string()],
+                ..HttpSettings::default()
+            };
+            assert!(
+                settings.apply(reqwest::Client::builder()).is_err(),
+                "{}",
+                entry
+            );
+        }
+    }
+
+    #[test]
+    fn apply_accepts_socks5_and_rejects_invalid_urls() {
+        let socks = HttpSettings {
+            https_proxy: Some("socks5h://127.0.0.1:1080".to_string()),
+            ..HttpSettings::default()
+        };
+        assert!(socks.apply(reqwest::Client::builder()).is_ok());
+
+        let invalid = HttpSettings {
+            http_proxy: Some("not a url".to_string()),
+            ..HttpSettings::default()
+        };
+        assert!(invalid.apply(reqwest::Client::builder()).is_err());
+    }
+
+    #[tokio::test]
+    async fn configured_http_proxy_receives_requests() {
+        let (proxy_url, seen) = start_recording_server();
+        let settings = HttpSettings {
+            http_proxy: Some(proxy_url),
+            ..HttpSettings::default()
+        };
+        let client = settings
+            .apply(reqwest::Client::builder())
+            .expect("apply proxy")
+            .build()
+            .expect("build client");
+
+        let response = client
+            .get("http://provider.example/v1/models")
+            .send()
+            .await
+            .expect("send through proxy");
+
+        assert!(response.status().is_success());
+        // A proxy sees the absolute URL in the request line
+        assert_eq!(
+            seen.recv().expect("request line"),
+            "http://provider.example/v1/models"
+        );
+    }
+
+    #[tokio::test]
+    async fn localhost_bypasses_configured_proxy() {
+        let (target_url, seen) = start_recording_server();
+        let settings = HttpSettings {
+            // Nothing listens here; a proxied request would fail to connect
+            http_proxy: Some("http://127.0.0.2:9".to_string()),
+            ..HttpSettings::default()
+        };
+        let client = settings
+            .apply(reqwest::Client::builder())
+            .expect("apply proxy")
+            .build()
+            .expect("build client");
+
+        let local_url = target_url.replace("127.0.0.1", "localhost");
+        let response = client
+            .get(format!("{}/api/tags", local_url))
+            .send()
+            .await
+            .expect("send directly");
+
+        assert!(response.status().is_success());
+        assert_eq!(seen.recv().expect("request line"), "/api/tags");
+    }
+
+    #[tokio::test]
+    async fn shared_client_reuses_pooled_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind server");
+        let addr = listener.local_addr().expect("server address");
+        let server = tiny_http::Server::from_listener(listener, None).expect("start server");
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for _ in 0..2 {
+                if let Ok(request) = server.recv() {
+                    let _ = tx.send(request.remote_addr().map(|peer| peer.port()));
+                    let _ = request.respond(tiny_http::Response::from_string("ok"));
+                }
+            }
+        });
+
+        for _ in 0..2 {
+            let response = shared_client()
+                .expect("shared client")
+                .get(format!("http://{}/ping", addr))
+                .timeout(Duration::from_secs(5))
+                .send()
+                .await
+                .expect("send request");
+            response.text().await.expect("read body");
+        }
+
+        let first = rx.recv().expect("first request");
+        let second = rx.recv().expect("second request");
+        assert!(first.is_some());
+        assert_eq!(first, second);
+    }
+
+    /// Tags requests to one host and counts them, leaving other tests' requests alone
+    struct TagRequests {
+        host: String,
+        seen: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl HttpMiddleware for TagRequests {
+        async fn handle(
+            &self,
+            mut request: reqwest::Request,
+            next: Next<'_>,
+        ) -> Result<reqwest::Response, LlmError> {
+            if request.url().authority() == self.host {
+                self.seen.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                request.headers_mut().insert(
+                    "x-middleware",
+                    reqwest::header::HeaderValue::from_static("seen"),
+                );
+            }
+            next.run(request).await
+        }
+    }
+
+    #[tokio::test]
+    async fn installed_middleware_wraps_provider_requests() {
+        use crate::llm::retry::RetryPolicy;
+        use crate::llm::testing::mock_server::start_capture_server;
+
+        let (base_url, captured) = start_capture_server(200, b"ok".to_vec()).expect("server");
+        let middleware = Arc::new(TagRequests {
+            host: base_url.trim_start_matches("http://").to_string(),
+            seen: Default::default(),
+        });
+        set_middleware(vec![middleware.clone() as Arc<dyn HttpMiddleware>]);
+
+        let request = shared_client()
+            .expect("shared client")
+            .post(format!("{}/v1/chat/completions", base_url))
+            .body("{}");
+        let result = RetryPolicy::default().send(request).await;
+        set_middleware(Vec::new());
+
+        assert_eq!(result.expect("response").status().as_u16(), 200);
+        assert_eq!(middleware.seen.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let sent = captured.recv().expect("captured request");
+        assert_eq!(sent.header("x-middleware"), Some("seen"));
+    }
+}
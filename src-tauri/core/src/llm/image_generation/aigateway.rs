@@ -1,7 +1,10 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::http_client::SendWithMiddleware;
 use crate::llm::image_generation::openai::OpenAiImageClient;
 use crate::llm::image_generation::types::{GeneratedImage, ImageGenerationRequest};
 use crate::llm::providers::provider::BaseProvider;
+use crate::llm::request_id::{RequestIdTracker, RequestIds};
+use crate::llm::request_log;
 use crate::llm::types::ProviderConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -59,11 +62,20 @@ struct ImageUrl {
 
 pub struct AIGatewayImageClient {
     config: ProviderConfig,
+    request_ids: RequestIdTracker,
 }
 
 impl AIGatewayImageClient {
     pub fn new(config: ProviderConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            request_ids: RequestIdTracker::default(),
+        }
+    }
+
+    /// Client and provider IDs of the calls this client made
+    pub fn request_ids(&self) -> RequestIds {
+        self.request_ids.ids()
     }
 
     pub async fn generate(
@@ -150,10 +162,7 @@ impl AIGatewayImageClient {
             }],
         };
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
         let mut headers = HashMap::new();
         headers.insert("Content-Type".to_string(), "application/json".to_string());
@@ -162,6 +171,8 @@ impl AIGatewayImageClient {
             .maybe_set_openai_account_header(&self.config.id, &mut headers)
             .await?;
 
+        request_log::log_request("AiGatewayImageClient", "POST", &url, &headers, Some(&body));
+
         let mut header_map = reqwest::header::HeaderMap::new();
         for (key, value) in headers {
             let header_name = reqwest::header::HeaderName::from_bytes(key.as_bytes())
@@ -171,13 +182,16 @@ impl AIGatewayImageClient {
             header_map.insert(header_name, header_value);
         }
 
-        let response = client
-            .post(&url)
+        let response = self
+            .request_ids
+            .tag(client.post(&url))
+            .timeout(Duration::from_secs(120))
             .headers(header_map)
             .json(&body)
-            .send()
+            .send_with_middleware()
             .await
             .map_err(|e| format!("AI Gateway chat completions request failed: {}", e))?;
+        self.request_ids.record(response.headers());
 
         if !response.status().is_success() {
             let status = response.status();
@@ -185,12 +199,19 @@ impl AIGatewayImageClient {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
+            request_log::log_response("AiGatewayImageClient", &url, status.as_u16(), Some(&body));
             return Err(format!(
                 "AI Gateway image generation failed ({}): {} / AI Gateway 图片生成失败",
                 status, body
             ));
         }
 
+        request_log::log_response(
+            "AiGatewayImageClient",
+            &url,
+            response.status().as_u16(),
+            None,
+        );
         let payload = response
             .json::<ChatCompletionsResponse>()
             .await
@@ -213,6 +234,7 @@ impl AIGatewayImageClient {
                                 url: None,
                                 mime_type,
                                 revised_prompt: None,
+                                expires_at: None,
                             });
                         } else {
                             // It's a regular URL
@@ -221,6 +243,7 @@ impl AIGatewayImageClient {
                                 url: Some(url.clone()),
                                 mime_type: "image/png".to_string(),
                                 revised_prompt: None,
+                                expires_at: None,
                             });
                         }
                     }
@@ -241,23 +264,17 @@ impl AIGatewayImageClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::testing::fixtures::provider_config;
 
     #[test]
     fn identifies_multimodal_image_models() {
         let config = ProviderConfig {
-            id: "aiGateway".to_string(),
-            name: "AI Gateway".to_string(),
-            protocol: crate::llm::types::ProtocolType::OpenAiCompatible,
-            base_url: "https://ai-gateway.vercel.sh".to_string(),
             api_key_name: "AI_GATEWAY_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "aiGateway",
+                crate::llm::types::ProtocolType::OpenAiCompatible,
+                "https://ai-gateway.vercel.sh",
+            )
         };
         let client = AIGatewayImageClient::new(config);
 
@@ -271,19 +288,12 @@ mod tests {
     #[test]
     fn parses_data_url() {
         let config = ProviderConfig {
-            id: "aiGateway".to_string(),
-            name: "AI Gateway".to_string(),
-            protocol: crate::llm::types::ProtocolType::OpenAiCompatible,
-            base_url: "https://ai-gateway.vercel.sh".to_string(),
             api_key_name: "AI_GATEWAY_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "aiGateway",
+                crate::llm::types::ProtocolType::OpenAiCompatible,
+                "https://ai-gateway.vercel.sh",
+            )
         };
         let client = AIGatewayImageClient::new(config);
 
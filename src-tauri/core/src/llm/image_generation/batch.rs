@@ -0,0 +1,167 @@
+// Concurrent batch generation for image providers that return one image per call
+// Failed requests are reported alongside the successful images instead of failing the batch
+
+use crate::llm::error::LlmError;
+use crate::llm::image_generation::types::GeneratedImage;
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use tokio::sync::Semaphore;
+
+/// Requests allowed in flight when the caller does not choose a limit
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 4;
+
+/// A request in the batch that failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchImageError {
+    /// Position of the request in the batch
+    pub index: usize,
+    pub message: String,
+}
+
+/// Images from the successful requests, in request order, plus any failures
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatchImageResult {
+    pub images: Vec<GeneratedImage>,
+    pub errors: Vec<BatchImageError>,
+}
+
+impl BatchImageResult {
+    /// Fail only when no request succeeded, surfacing the first error
+    pub fn into_images(self) -> Result<Vec<GeneratedImage>, String> {
+        self.or_first_error().map(|result| result.images)
+    }
+
+    /// Like `into_images`, keeping the failures next to the images of a partial batch
+    pub fn or_first_error(mut self) -> Result<Self, String> {
+        if self.images.is_empty() && !self.errors.is_empty() {
+            return Err(self.errors.swap_remove(0).message);
+        }
+        Ok(self)
+    }
+}
+
+/// Run `generate(index)` for `count` requests with at most `max_in_flight` running at once
+pub async fn generate_batch<F, Fut>(
+    count: usize,
+    max_in_flight: usize,
+    generate: F,
+) -> BatchImageResult
+where
+    F: Fn(usize) -> Fut,
+    Fut: Future<Output = Result<Vec<GeneratedImage>, LlmError>>,
+{
+    let semaphore = Semaphore::new(max_in_flight.max(1));
+    let semaphore = &semaphore;
+    let generate = &generate;
+    let outcomes = join_all((0..count).map(|index| async move {
+        let _permit = semaphore.acquire().await;
+        generate(index).await
+    }))
+    .await;
+
+    let mut result = BatchImageResult::default();
+    for (index, outcome) in outcomes.into_iter().enumerate() {
+        match outcome {
+            Ok(images) => result.images.extend(images),
+            Err(error) => {
+                log::warn!("[ImageBatch] Request {} failed: {}", index, error);
+                result.errors.push(BatchImageError {
+                    index,
+                    message: error.to_string(),
+                });
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn image(index: usize) -> GeneratedImage {
+        GeneratedImage {
+            b64_json: None,
+            url: Some(format!("https://example.com/{}.png", index)),
+            mime_type: "image/png".to_string(),
+            revised_prompt: None,
+            expires_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_successful_images_when_one_request_fails() {
+        let result = generate_batch(3, 3, |index| async move {
+            if index == 1 {
+                Err(LlmError::ProviderError {
+                    status: 400,
+                    body: "content rejected".to_string(),
+                    message: None,
+                })
+            } else {
+                Ok(vec![image(index)])
+            }
+        })
+        .await;
+
+        let urls: Vec<_> = result.images.iter().filter_map(|i| i.url.clone()).collect();
+        assert_eq!(
+            urls,
+            vec!["https://example.com/0.png", "https://example.com/2.png"]
+        );
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].index, 1);
+        assert!(result.errors[0].message.contains("content rejected"));
+        assert!(result.into_images().is_ok());
+    }
+
+    #[tokio::test]
+    async fn preserves_request_order_when_later_requests_finish_first() {
+        let result = generate_batch(3, 3, |index| async move {
+            tokio::time::sleep(Duration::from_millis((3 - index as u64) * 20)).await;
+            Ok(vec![image(index)])
+        })
+        .await;
+
+        let urls: Vec<_> = result.images.iter().filter_map(|i| i.url.clone()).collect();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/0.png",
+                "https://example.com/1.png",
+                "https://example.com/2.png"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn limits_requests_in_flight() {
+        let running = &AtomicUsize::new(0);
+        let peak = &AtomicUsize::new(0);
+
+        generate_batch(6, 2, move |index| async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok(vec![image(index)])
+        })
+        .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn fails_when_every_request_fails() {
+        let result = generate_batch(2, 2, |_| async {
+            Err(LlmError::Network("connection reset".to_string()))
+        })
+        .await;
+
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(result.into_images().unwrap_err(), "connection reset");
+    }
+}
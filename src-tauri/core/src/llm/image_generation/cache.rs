@@ -0,0 +1,394 @@
+// Disk cache for seeded image requests
+// A fixed seed makes the output reproducible, so the same request can be answered from disk
+// Requests without a seed are never cached since each call returns different images
+// Providers that send validators can have expired entries revalidated instead of regenerated
+
+use crate::llm::image_generation::types::{GeneratedImage, ImageGenerationRequest};
+use reqwest::header::{
+    HeaderMap, CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Setting that turns the cache on
+pub const IMAGE_CACHE_ENABLED_KEY: &str = "image_cache_enabled";
+/// Per-provider setting that sends expired entries back as conditional requests
+/// Only the OpenAI image client sends them; other providers regenerate as before
+pub const REVALIDATE_ENABLED_PREFIX: &str = "image_cache_revalidate";
+/// Directory under the app data dir holding cache entries
+pub const IMAGE_CACHE_DIR: &str = "image_cache";
+pub const DEFAULT_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+pub const DEFAULT_MAX_ENTRIES: usize = 200;
+
+const ENTRY_EXTENSION: &str = "json";
+
+/// `ETag` and `Last-Modified` a provider sent with a result
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheValidators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    /// Validators of a response, or `None` when it sent neither header
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: reqwest::header::HeaderName| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let validators = Self {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        (validators != Self::default()).then_some(validators)
+    }
+
+    /// Make `request` conditional on the stored result still being current
+    /// `no-cache` keeps proxies in between from answering with their own copy
+    pub fn apply(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let mut request = request.header(CACHE_CONTROL, "no-cache");
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
+/// Expired entry that can still be revalidated with the provider
+#[derive(Debug, Clone)]
+pub struct StaleEntry {
+    pub images: Vec<GeneratedImage>,
+    pub validators: CacheValidators,
+}
+
+pub enum CacheLookup {
+    Fresh(Vec<GeneratedImage>),
+    Stale(StaleEntry),
+    Miss,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Unix time in milliseconds the entry was written
+    created_at_ms: u64,
+    images: Vec<GeneratedImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    validators: Option<CacheValidators>,
+}
+
+pub struct ImageCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_entries: usize,
+    revalidate: bool,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl ImageCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            ttl: DEFAULT_TTL,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            revalidate: false,
+        }
+    }
+
+    /// Entries older than `ttl` are treated as misses and removed
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Oldest entries are evicted once more than `max_entries` are stored
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries.max(1);
+        self
+    }
+
+    /// Keep expired entries that have validators so they can be revalidated
+    /// Expired entries then only leave through the `max_entries` limit
+    pub fn with_revalidation(mut self, revalidate: bool) -> Self {
+        self.revalidate = revalidate;
+        self
+    }
+
+    /// Cache key for a request, or `None` when it has no seed and must not be cached
+    /// Covers the whole request, provider options included, except the fields that only
+    /// change how the result is delivered or logged
+    pub fn key(provider_id: &str, model: &str, request: &ImageGenerationRequest) -> Option<String> {
+        request.seed?;
+        // Edits depend on the source image, which is not worth hashing into a key
+        if request.image.is_some() {
+            return None;
+        }
+        let mut request = request.clone();
+        request.model = String::new();
+        request.request_id = None;
+        request.timeout_ms = None;
+        request.response_format = None;
+        request.user_id = None;
+        request.download_urls = false;
+        let material = serde_json::json!([provider_id, model, request]);
+        let digest = Sha256::digest(material.to_string().as_bytes());
+        Some(hex::encode(digest))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", key, ENTRY_EXTENSION))
+    }
+
+    fn is_expired(&self, entry: &CacheEntry) -> bool {
+        now_ms().saturating_sub(entry.created_at_ms) > self.ttl.as_millis() as u64
+    }
+
+    /// Stored images for `key`; unreadable and expired entries count as misses
+    pub async fn get(&self, key: &str) -> Option<Vec<GeneratedImage>> {
+        match self.lookup(key).await {
+            CacheLookup::Fresh(images) => Some(images),
+            CacheLookup::Stale(_) | CacheLookup::Miss => None,
+        }
+    }
+
+    /// Entry for `key`; expired ones are stale when revalidation is on and they have
+    /// validators, and are otherwise removed like unreadable ones
+    pub async fn lookup(&self, key: &str) -> CacheLookup {
+        let path = self.entry_path(key);
+        let Ok(raw) = tokio::fs::read(&path).await else {
+            return CacheLookup::Miss;
+        };
+        match serde_json::from_slice::<CacheEntry>(&raw) {
+            Ok(entry) if !self.is_expired(&entry) => CacheLookup::Fresh(entry.images),
+            Ok(CacheEntry {
+                images,
+                validators: Some(validators),
+                ..
+            }) if self.revalidate => CacheLookup::Stale(StaleEntry { images, validators }),
+            _ => {
+                let _ = tokio::fs::remove_file(&path).await;
+                CacheLookup::Miss
+            }
+        }
+    }
+
+    /// Store images under `key` with the validators they came with, then evict expired and
+    /// excess entries; storing again after a `304` starts the entry's TTL over
+    /// Results holding only URLs are skipped since provider links expire
+    pub async fn put(
+        &self,
+        key: &str,
+        images: &[GeneratedImage],
+        validators: Option<&CacheValidators>,
+    ) -> Result<(), String> {
+        if images.is_empty() || images.iter().any(|image| image.b64_json.is_none()) {
+            return Ok(());
+        }
+        tokio::fs::create_dir_all(&self.dir).await.map_err(|e| {
+            format!(
+                "Failed to create image cache directory: {} / 创建图片缓存目录失败",
+                e
+            )
+        })?;
+        let entry = CacheEntry {
+            created_at_ms: now_ms(),
+            images: images.to_vec(),
+            validators: validators.cloned(),
+        };
+        let raw = serde_json::to_vec(&entry)
+            .map_err(|e| format!("Failed to encode cached images: {}", e))?;
+        tokio::fs::write(self.entry_path(key), raw)
+            .await
+            .map_err(|e| format!("Failed to write image cache: {} / 写入图片缓存失败", e))?;
+        self.evict().await;
+        Ok(())
+    }
+
+    /// Drop entries past their TTL unless they are kept for revalidation, then the oldest ones
+    /// beyond `max_entries`
+    /// Ages come from file modification times so large entries are not read back
+    async fn evict(&self) {
+        let Ok(mut dir) = tokio::fs::read_dir(&self.dir).await else {
+            return;
+        };
+        let mut entries = Vec::new();
+        while let Ok(Some(item)) = dir.next_entry().await {
+            let path = item.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(ENTRY_EXTENSION) {
+                continue;
+            }
+            let modified = item
+                .metadata()
+                .await
+                .ok()
+                .and_then(|metadata| metadata.modified().ok())
+                .unwrap_or(UNIX_EPOCH);
+            entries.push((modified, path));
+        }
+
+        let now = SystemTime::now();
+        entries.sort();
+        let mut kept = Vec::with_capacity(entries.len());
+        for (modified, path) in entries {
+            let expired =
+                !self.revalidate && now.duration_since(modified).is_ok_and(|age| age > self.ttl);
+            if expired {
+                let _ = tokio::fs::remove_file(&path).await;
+            } else {
+                kept.push(path);
+            }
+        }
+        let excess = kept.len().saturating_sub(self.max_entries);
+        for path in kept.into_iter().take(excess) {
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn request(prompt: &str, seed: Option<i64>) -> ImageGenerationRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "seedream",
+            "prompt": prompt,
+            "size": "2048x2048",
+            "seed": seed
+        }))
+        .expect("request")
+    }
+
+    fn image(b64: &str) -> GeneratedImage {
+        GeneratedImage {
+            b64_json: Some(b64.to_string()),
+            url: None,
+            mime_type: "image/png".to_string(),
+            revised_prompt: None,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn key_requires_a_seed() {
+        assert_eq!(
+            ImageCache::key("volcengine", "seedream", &request("a fox", None)),
+            None
+        );
+        let seeded = ImageCache::key("volcengine", "seedream", &request("a fox", Some(7)));
+        assert!(seeded.is_some());
+        assert_ne!(
+            seeded,
+            ImageCache::key("volcengine", "seedream", &request("a fox", Some(8)))
+        );
+    }
+
+    #[test]
+    fn key_covers_provider_options_but_not_delivery_fields() {
+        let plain = request("a fox", Some(7));
+        let mut styled = request("a fox", Some(7));
+        styled.provider_options = Some(serde_json::json!({ "style": "anime" }));
+        let mut delivered = request("a fox", Some(7));
+        delivered.response_format = Some("b64_json".to_string());
+        delivered.user_id = Some("user-1".to_string());
+        delivered.request_id = Some("req-1".to_string());
+
+        let key = |request| ImageCache::key("stability", "sd3", request);
+        assert_ne!(key(&plain), key(&styled));
+        assert_eq!(key(&plain), key(&delivered));
+    }
+
+    #[tokio::test]
+    async fn returns_stored_images_on_hit_and_none_on_miss() {
+        let dir = TempDir::new().expect("temp dir");
+        let cache = ImageCache::new(dir.path().to_path_buf());
+        let key =
+            ImageCache::key("volcengine", "seedream", &request("a fox", Some(7))).expect("key");
+
+        assert!(cache.get(&key).await.is_none());
+
+        cache.put(&key, &[image("abc")], None).await.expect("put");
+        let images = cache.get(&key).await.expect("hit");
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].b64_json.as_deref(), Some("abc"));
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_misses() {
+        let dir = TempDir::new().expect("temp dir");
+        let cache = ImageCache::new(dir.path().to_path_buf()).with_ttl(Duration::ZERO);
+
+        cache
+            .put("stale", &[image("abc")], None)
+            .await
+            .expect("put");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        assert!(cache.get("stale").await.is_none());
+        assert!(!dir.path().join("stale.json").exists());
+    }
+
+    #[tokio::test]
+    async fn expired_entries_with_validators_are_stale_when_revalidating() {
+        let dir = TempDir::new().expect("temp dir");
+        let validators = CacheValidators {
+            etag: Some("\"v1\"".to_string()),
+            last_modified: None,
+        };
+        let cache = ImageCache::new(dir.path().to_path_buf())
+            .with_ttl(Duration::ZERO)
+            .with_revalidation(true);
+
+        cache
+            .put("tagged", &[image("abc")], Some(&validators))
+            .await
+            .expect("put");
+        cache
+            .put("untagged", &[image("def")], None)
+            .await
+            .expect("put");
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        match cache.lookup("tagged").await {
+            CacheLookup::Stale(entry) => {
+                assert_eq!(entry.validators, validators);
+                assert_eq!(entry.images[0].b64_json.as_deref(), Some("abc"));
+            }
+            _ => panic!("Expected a stale entry"),
+        }
+        assert!(matches!(cache.lookup("untagged").await, CacheLookup::Miss));
+        assert!(cache.get("tagged").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn evicts_oldest_entries_beyond_max() {
+        let dir = TempDir::new().expect("temp dir");
+        let cache = ImageCache::new(dir.path().to_path_buf()).with_max_entries(2);
+
+        for key in ["first", "second", "third"] {
+            cache.put(key, &[image(key)], None).await.expect("put");
+            // Keep modification times distinct so the eviction order is deterministic
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        assert!(cache.get("first").await.is_none());
+        assert!(cache.get("second").await.is_some());
+        assert!(cache.get("third").await.is_some());
+    }
+}
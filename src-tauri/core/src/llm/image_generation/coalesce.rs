@@ -0,0 +1,206 @@
+// In-flight request coalescing
+// Identical image requests issued while one is still running share its upstream call
+// Opt-in per provider through the `image_coalesce_enabled_{provider_id}` setting
+
+use crate::llm::image_generation::types::ImageGenerationRequest;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use tokio::sync::watch;
+
+/// Settings prefix that turns coalescing on for a provider
+pub const COALESCE_ENABLED_PREFIX: &str = "image_coalesce_enabled";
+
+/// Key identifying requests that would produce the same upstream call
+/// The request id and client timeout are left out since they differ between callers
+pub fn image_request_key(provider_id: &str, model: &str, request: &ImageGenerationRequest) -> u64 {
+    let mut request = request.clone();
+    request.request_id = None;
+    request.timeout_ms = None;
+
+    let mut hasher = DefaultHasher::new();
+    provider_id.hash(&mut hasher);
+    model.hash(&mut hasher);
+    serde_json::to_string(&request)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Shares the result of one running future with every caller that asks for the same key
+/// An entry lives only while its first caller is still waiting on the upstream call
+pub struct RequestCoalescer<T> {
+    in_flight: Mutex<HashMap<u64, watch::Receiver<Option<T>>>>,
+}
+
+impl<T> Default for RequestCoalescer<T> {
+    fn default() -> Self {
+        Self {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Removes the entry when the leading call finishes or is dropped
+struct InFlightEntry<'a, T> {
+    coalescer: &'a RequestCoalescer<T>,
+    key: u64,
+}
+
+impl<T> Drop for InFlightEntry<'_, T> {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.coalescer.in_flight.lock() {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+impl<T: Clone> RequestCoalescer<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of upstream calls currently running
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.lock().map(|map| map.len()).unwrap_or(0)
+    }
+
+    /// Run `upstream`, or wait for the identical call already in flight and return its result
+    /// A caller whose leader was cancelled falls back to running its own `upstream`
+    pub async fn run<F>(&self, key: u64, upstream: F) -> T
+    where
+        F: Future<Output = T>,
+    {
+        let role = match self.in_flight.lock() {
+            Ok(mut in_flight) => match in_flight.get(&key) {
+                Some(receiver) => Some(Role::Follower(receiver.clone())),
+                None => {
+                    let (sender, receiver) = watch::channel(None);
+                    in_flight.insert(key, receiver);
+                    Some(Role::Leader(sender))
+                }
+            },
+            Err(_) => None,
+        };
+        // A poisoned map only loses sharing, never the request itself
+        let Some(role) = role else {
+            return upstream.await;
+        };
+
+        match role {
+            Role::Leader(sender) => {
+                let entry = InFlightEntry {
+                    coalescer: self,
+                    key,
+                };
+                let value = upstream.await;
+                // Later identical requests start a fresh call rather than reuse this result
+                drop(entry);
+                let _ = sender.send(Some(value.clone()));
+                value
+            }
+            Role::Follower(mut receiver) => {
+                loop {
+                    let current = receiver.borrow_and_update().clone();
+                    if let Some(value) = current {
+                        return value;
+                    }
+                    if receiver.changed().await.is_err() {
+                        break;
+                    }
+                }
+                let current = receiver.borrow().clone();
+                match current {
+                    Some(value) => value,
+                    None => upstream.await,
+                }
+            }
+        }
+    }
+}
+
+enum Role<T> {
+    Leader(watch::Sender<Option<T>>),
+    Follower(watch::Receiver<Option<T>>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    async fn upstream(calls: &AtomicUsize, value: &str) -> Result<String, String> {
+        calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        Ok(value.to_string())
+    }
+
+    fn request(prompt: &str, request_id: Option<&str>) -> ImageGenerationRequest {
+        serde_json::from_value(serde_json::json!({
+            "model": "gpt-image-1",
+            "prompt": prompt,
+            "requestId": request_id
+        }))
+        .expect("request")
+    }
+
+    #[tokio::test]
+    async fn simultaneous_identical_requests_share_one_upstream_call() {
+        let coalescer = RequestCoalescer::new();
+        let calls = AtomicUsize::new(0);
+        let key = image_request_key("openai", "gpt-image-1", &request("a fox", None));
+
+        let (first, second) = tokio::join!(
+            coalescer.run(key, upstream(&calls, "first")),
+            coalescer.run(key, upstream(&calls, "second")),
+        );
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(first, Ok("first".to_string()));
+        assert_eq!(second, Ok("first".to_string()));
+        assert_eq!(coalescer.in_flight(), 0);
+
+        // The entry is gone once the call completes, so the next request goes upstream
+        let third = coalescer.run(key, upstream(&calls, "third")).await;
+        assert_eq!(third, Ok("third".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn different_keys_run_separately() {
+        let coalescer = RequestCoalescer::new();
+        let calls = AtomicUsize::new(0);
+        let fox = image_request_key("openai", "gpt-image-1", &request("a fox", None));
+        let owl = image_request_key("openai", "gpt-image-1", &request("an owl", None));
+
+        let (first, second) = tokio::join!(
+            coalescer.run(fox, upstream(&calls, "fox")),
+            coalescer.run(owl, upstream(&calls, "owl")),
+        );
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(first, Ok("fox".to_string()));
+        assert_eq!(second, Ok("owl".to_string()));
+    }
+
+    #[test]
+    fn key_ignores_request_id_but_not_model_or_provider() {
+        let base = image_request_key("openai", "gpt-image-1", &request("a fox", Some("1")));
+
+        assert_eq!(
+            base,
+            image_request_key("openai", "gpt-image-1", &request("a fox", Some("2")))
+        );
+        assert_ne!(
+            base,
+            image_request_key("openai", "dall-e-3", &request("a fox", Some("1")))
+        );
+        assert_ne!(
+            base,
+            image_request_key("aiGateway", "gpt-image-1", &request("a fox", Some("1")))
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::http_client::SendWithMiddleware;
 use crate::llm::image_generation::types::{GeneratedImage, ImageGenerationRequest};
 use crate::llm::providers::provider::BaseProvider;
+use crate::llm::request_id::{RequestIdTracker, RequestIds};
+use crate::llm::request_log;
 use crate::llm::types::ProviderConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -110,11 +113,20 @@ struct QwenImageErrorResponse {
 
 pub struct DashScopeImageClient {
     config: ProviderConfig,
+    request_ids: RequestIdTracker,
 }
 
 impl DashScopeImageClient {
     pub fn new(config: ProviderConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            request_ids: RequestIdTracker::default(),
+        }
+    }
+
+    /// Client and provider IDs of the calls this client made
+    pub fn request_ids(&self) -> RequestIds {
+        self.request_ids.ids()
     }
 
     pub async fn generate(
@@ -194,15 +206,14 @@ impl DashScopeImageClient {
             prompt_preview
         );
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
         let mut headers = HashMap::new();
         headers.insert("Content-Type".to_string(), "application/json".to_string());
         headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
 
+        request_log::log_request("DashScopeImageClient", "POST", &url, &headers, Some(&body));
+
         let mut header_map = reqwest::header::HeaderMap::new();
         for (key, value) in headers {
             let header_name = reqwest::header::HeaderName::from_bytes(key.as_bytes())
@@ -214,16 +225,19 @@ impl DashScopeImageClient {
 
         log::info!("[DashScopeImageClient] Sending request...");
 
-        let response = client
-            .post(&url)
+        let response = self
+            .request_ids
+            .tag(client.post(&url))
+            .timeout(Duration::from_secs(120))
             .headers(header_map)
             .json(&body)
-            .send()
+            .send_with_middleware()
             .await
             .map_err(|e| {
                 log::error!("[DashScopeImageClient] Request failed: {}", e);
                 format!("Alibaba image request failed: {}", e)
             })?;
+        self.request_ids.record(response.headers());
 
         let status = response.status();
         log::info!("[DashScopeImageClient] Response status: {}", status);
@@ -233,6 +247,12 @@ impl DashScopeImageClient {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
+            request_log::log_response(
+                "DashScopeImageClient",
+                &url,
+                status.as_u16(),
+                Some(&body_text),
+            );
 
             log::error!("[DashScopeImageClient] Error response body: {}", body_text);
 
@@ -250,6 +270,7 @@ impl DashScopeImageClient {
             ));
         }
 
+        request_log::log_response("DashScopeImageClient", &url, status.as_u16(), None);
         let payload = response
             .json::<QwenImageResponse>()
             .await
@@ -287,6 +308,7 @@ impl DashScopeImageClient {
                             url: Some(image.clone()),
                             mime_type: "image/png".to_string(),
                             revised_prompt: None,
+                            expires_at: None,
                         });
                     }
                     QwenImageContentResponse::Text { text } => {
@@ -314,6 +336,7 @@ impl DashScopeImageClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::testing::fixtures::provider_config;
 
     #[test]
     fn parses_qwen_image_response() {
@@ -396,21 +419,11 @@ mod tests {
 
     #[test]
     fn dashscope_image_client_constructs() {
-        let config = ProviderConfig {
-            id: "alibaba".to_string(),
-            name: "Alibaba".to_string(),
-            protocol: crate::llm::types::ProtocolType::OpenAiCompatible,
-            base_url: "https://dashscope.aliyuncs.com/compatible-mode/v1".to_string(),
-            api_key_name: "ALIBABA_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
-        };
+        let config = provider_config(
+            "alibaba",
+            crate::llm::types::ProtocolType::OpenAiCompatible,
+            "https://dashscope.aliyuncs.com/compatible-mode/v1",
+        );
         let _client = DashScopeImageClient::new(config);
     }
 
@@ -0,0 +1,131 @@
+// Eager download of URL-only image results
+// Provider links expire, so callers that set `download_urls` get the bytes inlined as base64
+// while the original URL is kept alongside
+
+use crate::llm::cancellation::cancellable;
+use crate::llm::error::LlmError;
+use crate::llm::http_client::SendWithMiddleware;
+use crate::llm::image_generation::types::GeneratedImage;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Download deadline when the request sets no timeout
+pub const DEFAULT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Mime type from a `Content-Type` header, or `None` when it does not name an image type
+fn image_content_type(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    headers
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.split(';').next().unwrap_or(value).trim().to_string())
+        .filter(|value| value.starts_with("image/"))
+}
+
+/// Fetch `url` and return its bytes as base64 with the mime type the server reported
+pub async fn fetch_base64(
+    client: &reqwest::Client,
+    url: &str,
+    timeout: Option<Duration>,
+) -> Result<(String, Option<String>), String> {
+    let mut request = client.get(url);
+    if let Some(timeout) = timeout {
+        request = request.timeout(timeout);
+    }
+    let response = request
+        .send_with_middleware()
+        .await
+        .map_err(|e| format!("Failed to download image: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!(
+            "Image download failed ({}) / 图片下载失败",
+            response.status()
+        ));
+    }
+    let mime_type = image_content_type(response.headers());
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    Ok((STANDARD.encode(bytes), mime_type))
+}
+
+/// Fill in `b64_json` for every image that only has a URL, keeping the URL
+/// A failed download leaves that image URL-only with a warning rather than failing a
+/// generation that already succeeded
+pub async fn download_url_images(
+    images: &mut [GeneratedImage],
+    timeout: Option<Duration>,
+    cancel: Option<&CancellationToken>,
+) -> Result<(), LlmError> {
+    let client = crate::llm::http_client::shared_client().map_err(LlmError::Other)?;
+    for image in images.iter_mut().filter(|image| image.b64_json.is_none()) {
+        let Some(url) = image.url.clone() else {
+            continue;
+        };
+        match cancellable(cancel, fetch_base64(&client, &url, timeout)).await? {
+            Ok((b64_json, mime_type)) => {
+                image.b64_json = Some(b64_json);
+                if let Some(mime_type) = mime_type {
+                    image.mime_type = mime_type;
+                }
+            }
+            Err(e) => log::warn!("[ImageDownload] Keeping URL-only image {}: {}", url, e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::mock_server::start_header_server;
+
+    fn url_image(url: &str) -> GeneratedImage {
+        GeneratedImage {
+            b64_json: None,
+            url: Some(url.to_string()),
+            mime_type: "image/png".to_string(),
+            revised_prompt: None,
+            expires_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn downloads_url_images_as_base64_with_served_mime_type() {
+        let base_url = start_header_server(
+            200,
+            "jpeg bytes".to_string(),
+            vec![("Content-Type", "image/jpeg; charset=binary")],
+        )
+        .expect("start mock server");
+        let url = format!("{}/image.jpeg", base_url);
+        let mut images = vec![url_image(&url)];
+
+        download_url_images(&mut images, None, None)
+            .await
+            .expect("download");
+
+        assert_eq!(
+            images[0].b64_json.as_deref(),
+            Some(STANDARD.encode("jpeg bytes").as_str())
+        );
+        assert_eq!(images[0].mime_type, "image/jpeg");
+        assert_eq!(images[0].url.as_deref(), Some(url.as_str()));
+    }
+
+    #[tokio::test]
+    async fn failed_download_keeps_the_url() {
+        let base_url =
+            start_header_server(404, "gone".to_string(), vec![]).expect("start mock server");
+        let mut images = vec![url_image(&format!("{}/expired.png", base_url))];
+
+        download_url_images(&mut images, None, None)
+            .await
+            .expect("download");
+
+        assert!(images[0].b64_json.is_none());
+        assert!(images[0].url.is_some());
+        assert_eq!(images[0].mime_type, "image/png");
+    }
+}
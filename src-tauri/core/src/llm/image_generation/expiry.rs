@@ -0,0 +1,117 @@
+// Expiry of signed image URLs
+// Providers return pre-signed storage links that stop working after a while; the signature's
+// query parameters usually say when, so the UI can refresh an image before it breaks
+
+use crate::llm::image_generation::types::GeneratedImage;
+use chrono::{DateTime, NaiveDateTime};
+
+/// A URL this close to its expiry is treated as already expired, leaving time to download it
+pub const EXPIRY_MARGIN_SECS: i64 = 60;
+
+/// Signing date and lifetime parameters of the V4-style signatures (S3, GCS, TOS)
+const DATED_SIGNATURES: &[(&str, &str)] = &[
+    ("x-amz-date", "x-amz-expires"),
+    ("x-goog-date", "x-goog-expires"),
+    ("x-tos-date", "x-tos-expires"),
+];
+
+/// Unix seconds at which a signed `url` expires, or None when its query carries no expiry
+/// Reads V4 signing date plus lifetime, an `Expires` epoch (OSS, S3 v2, CloudFront) and the
+/// `se` end time of Azure SAS links
+pub fn parse_url_expiry(url: &str) -> Option<i64> {
+    let url = url::Url::parse(url).ok()?;
+    let params: Vec<(String, String)> = url
+        .query_pairs()
+        .map(|(key, value)| (key.to_ascii_lowercase(), value.into_owned()))
+        .collect();
+    let param = |name: &str| {
+        params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim())
+    };
+
+    for (date_key, expires_key) in DATED_SIGNATURES {
+        if let (Some(date), Some(lifetime)) = (param(date_key), param(expires_key)) {
+            let signed_at = NaiveDateTime::parse_from_str(date, "%Y%m%dT%H%M%SZ").ok()?;
+            let lifetime: i64 = lifetime.parse().ok()?;
+            return Some(signed_at.and_utc().timestamp() + lifetime);
+        }
+    }
+    if let Some(expires) = param("expires") {
+        return expires.parse().ok();
+    }
+    param("se")
+        .and_then(|end| DateTime::parse_from_rfc3339(end).ok())
+        .map(|end| end.timestamp())
+}
+
+/// Set `expires_at` on URL images that do not have one yet
+pub fn fill_url_expiry(images: &mut [GeneratedImage]) {
+    for image in images.iter_mut().filter(|image| image.expires_at.is_none()) {
+        image.expires_at = image.url.as_deref().and_then(parse_url_expiry);
+    }
+}
+
+/// Whether the image's URL has expired at `now` (Unix seconds), counting the margin
+/// URLs without a known expiry are assumed to still work
+pub fn url_expired(image: &GeneratedImage, now: i64) -> bool {
+    image
+        .expires_at
+        .is_some_and(|expires_at| now + EXPIRY_MARGIN_SECS >= expires_at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_expiry_from_v4_signed_urls() {
+        // 2024-05-01T08:00:00Z plus one hour
+        let s3 = "https://bucket.s3.amazonaws.com/out/1.png?X-Amz-Algorithm=AWS4-HMAC-SHA256\
+                  &X-Amz-Date=20240501T080000Z&X-Amz-Expires=3600&X-Amz-Signature=abc";
+        assert_eq!(parse_url_expiry(s3), Some(1_714_550_400 + 3600));
+
+        let tos = "https://ark.tos-cn-beijing.volces.com/a.jpeg?x-tos-algorithm=TOS4-HMAC-SHA256\
+                   &x-tos-date=20240501T080000Z&x-tos-expires=86400&x-tos-signature=def";
+        assert_eq!(parse_url_expiry(tos), Some(1_714_550_400 + 86400));
+    }
+
+    #[test]
+    fn parses_expiry_from_epoch_and_sas_urls() {
+        let oss = "https://dashscope-result.oss-cn-beijing.aliyuncs.com/1.png\
+                   ?Expires=1714636800&OSSAccessKeyId=key&Signature=sig%3D";
+        assert_eq!(parse_url_expiry(oss), Some(1_714_636_800));
+
+        let sas = "https://oaidalleapiprodscus.blob.core.windows.net/private/img.png\
+                   ?st=2024-05-01T07%3A00%3A00Z&se=2024-05-01T09%3A00%3A00Z&sp=r&sig=xyz";
+        assert_eq!(parse_url_expiry(sas), Some(1_714_550_400 + 3600));
+    }
+
+    #[test]
+    fn unsigned_or_malformed_urls_have_no_expiry() {
+        assert_eq!(parse_url_expiry("https://example.com/image.png"), None);
+        assert_eq!(
+            parse_url_expiry("https://example.com/a.png?X-Amz-Date=yesterday&X-Amz-Expires=60"),
+            None
+        );
+        assert_eq!(parse_url_expiry("not a url"), None);
+    }
+
+    #[test]
+    fn expiry_counts_the_margin() {
+        let image = GeneratedImage {
+            b64_json: None,
+            url: Some("https://example.com/a.png?Expires=1000".to_string()),
+            mime_type: "image/png".to_string(),
+            revised_prompt: None,
+            expires_at: None,
+        };
+        let mut images = [image];
+        fill_url_expiry(&mut images);
+
+        assert_eq!(images[0].expires_at, Some(1000));
+        assert!(!url_expired(&images[0], 1000 - EXPIRY_MARGIN_SECS - 1));
+        assert!(url_expired(&images[0], 1000 - EXPIRY_MARGIN_SECS));
+    }
+}
@@ -1,6 +1,10 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::http_client::SendWithMiddleware;
 use crate::llm::image_generation::types::{GeneratedImage, ImageGenerationRequest};
+use crate::llm::request_id::{RequestIdTracker, RequestIds};
+use crate::llm::request_log;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 
 /// Request format for Vertex AI predict endpoint (used for Imagen models)
@@ -90,17 +94,24 @@ struct GeminiInlineData {
 
 pub struct GoogleImageClient {
     base_url: String,
+    request_ids: RequestIdTracker,
 }
 
 impl GoogleImageClient {
     pub fn new() -> Self {
+        Self::with_base_url("https://generativelanguage.googleapis.com/v1beta".to_string())
+    }
+
+    pub fn with_base_url(base_url: String) -> Self {
         Self {
-            base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
+            base_url,
+            request_ids: RequestIdTracker::default(),
         }
     }
 
-    pub fn with_base_url(base_url: String) -> Self {
-        Self { base_url }
+    /// Client and provider IDs of the calls this client made
+    pub fn request_ids(&self) -> RequestIds {
+        self.request_ids.ids()
     }
 
     pub async fn generate(
@@ -152,18 +163,20 @@ impl GoogleImageClient {
             api_key
         );
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
-        let response = client
-            .post(&url)
+        let headers = HashMap::from([("Content-Type".to_string(), "application/json".to_string())]);
+        request_log::log_request("GoogleImageClient", "POST", &url, &headers, Some(&payload));
+        let response = self
+            .request_ids
+            .tag(client.post(&url))
+            .timeout(Duration::from_secs(120))
             .header("Content-Type", "application/json")
             .json(&payload)
-            .send()
+            .send_with_middleware()
             .await
             .map_err(|e| format!("Google image request failed: {}", e))?;
+        self.request_ids.record(response.headers());
 
         if !response.status().is_success() {
             let status = response.status();
@@ -171,11 +184,13 @@ impl GoogleImageClient {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
+            request_log::log_response("GoogleImageClient", &url, status.as_u16(), Some(&body));
             return Err(format!(
                 "Google image generation failed ({}): {} / Google 图片生成失败",
                 status, body
             ));
         }
+        request_log::log_response("GoogleImageClient", &url, response.status().as_u16(), None);
 
         let response_data = response
             .json::<VertexAiPredictResponse>()
@@ -193,6 +208,7 @@ impl GoogleImageClient {
                         url: None,
                         mime_type,
                         revised_prompt: None,
+                        expires_at: None,
                     });
                 }
             }
@@ -230,18 +246,20 @@ impl GoogleImageClient {
             api_key
         );
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
-        let response = client
-            .post(&url)
+        let headers = HashMap::from([("Content-Type".to_string(), "application/json".to_string())]);
+        request_log::log_request("GoogleImageClient", "POST", &url, &headers, Some(&payload));
+        let response = self
+            .request_ids
+            .tag(client.post(&url))
+            .timeout(Duration::from_secs(120))
             .header("Content-Type", "application/json")
             .json(&payload)
-            .send()
+            .send_with_middleware()
             .await
             .map_err(|e| format!("Google image request failed: {}", e))?;
+        self.request_ids.record(response.headers());
 
         if !response.status().is_success() {
             let status = response.status();
@@ -249,11 +267,13 @@ impl GoogleImageClient {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
+            request_log::log_response("GoogleImageClient", &url, status.as_u16(), Some(&body));
             return Err(format!(
                 "Google image generation failed ({}): {} / Google 图片生成失败",
                 status, body
             ));
         }
+        request_log::log_response("GoogleImageClient", &url, response.status().as_u16(), None);
 
         let response_data = response
             .json::<GeminiGenerateContentResponse>()
@@ -272,6 +292,7 @@ impl GoogleImageClient {
                                     url: None,
                                     mime_type: inline_data.mime_type,
                                     revised_prompt: None,
+                                    expires_at: None,
                                 });
                             }
                         }
@@ -1,10 +1,18 @@
+pub mod batch;
+pub mod cache;
+pub mod coalesce;
+pub mod download;
+pub mod expiry;
 pub mod service;
+pub mod streaming;
 pub mod types;
 
 pub mod aigateway;
 pub mod dashscope;
 pub mod google;
 pub mod openai;
+pub mod replicate;
+pub mod stability;
 pub mod volcengine;
 pub mod zhipu;
 
@@ -1,9 +1,21 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
-use crate::llm::image_generation::types::{GeneratedImage, ImageGenerationRequest};
+use crate::llm::http_client::SendWithMiddleware;
+use crate::llm::image_generation::cache::{CacheValidators, StaleEntry};
+use crate::llm::image_generation::streaming::{
+    emit, progress_percent, ImageEventSender, ImageGenerationEvent, PreviewFrames,
+};
+use crate::llm::image_generation::types::{
+    normalize_revised_prompt, GeneratedImage, ImageGenerationRequest,
+};
+use crate::llm::protocols::sse::{self, SseLineReader};
 use crate::llm::providers::provider::BaseProvider;
+use crate::llm::request_id::{RequestIdTracker, RequestIds};
+use crate::llm::request_log;
 use crate::llm::types::ProviderConfig;
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize)]
@@ -18,11 +30,40 @@ struct OpenAiImageRequest {
     n: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    /// Preview frames sent before each final image when streaming, 0 to 3
+    #[serde(skip_serializing_if = "Option::is_none")]
+    partial_images: Option<u32>,
 }
 
+/// Preview frames requested for streamed `gpt-image-*` generations
+const STREAM_PARTIAL_IMAGES: u32 = 2;
+
+/// Quality values accepted by `gpt-image-1`
+const GPT_IMAGE_QUALITIES: &[&str] = &["low", "medium", "high", "auto"];
+
 #[derive(Debug, Clone, Deserialize)]
 struct OpenAiImageResponse {
     data: Vec<OpenAiImageData>,
+    /// `png`, `jpeg` or `webp`; only returned by `gpt-image-1`
+    #[serde(default)]
+    output_format: Option<String>,
+    /// Token usage; only returned by `gpt-image-1`
+    #[serde(default)]
+    usage: Option<OpenAiImageUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiImageUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+    #[serde(default)]
+    total_tokens: u64,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -34,13 +75,150 @@ struct OpenAiImageData {
     revised_prompt: Option<String>,
 }
 
+/// One `data:` event from a streamed `gpt-image-*` generation
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAiImageStreamEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    #[serde(default)]
+    b64_json: Option<String>,
+    #[serde(default)]
+    partial_image_index: Option<usize>,
+    #[serde(default)]
+    output_format: Option<String>,
+}
+
+/// `gpt-image-*` models always return base64 and reject `response_format`
+fn is_gpt_image_model(model: &str) -> bool {
+    model.starts_with("gpt-image")
+}
+
+/// Map the requested quality onto the values the model accepts
+/// DALL-E style `standard`/`hd` are translated for `gpt-image-1`
+fn resolve_quality(model: &str, quality: Option<String>) -> Result<Option<String>, String> {
+    let Some(quality) = quality.filter(|q| !q.trim().is_empty()) else {
+        return Ok(None);
+    };
+    if !is_gpt_image_model(model) {
+        return Ok(Some(quality));
+    }
+    let quality = match quality.trim().to_lowercase().as_str() {
+        "hd" => "high".to_string(),
+        "standard" => "medium".to_string(),
+        other => other.to_string(),
+    };
+    if GPT_IMAGE_QUALITIES.contains(&quality.as_str()) {
+        Ok(Some(quality))
+    } else {
+        Err(format!(
+            "Quality {} is not supported by {}. Supported values: {} / {} 不支持画质 {}，可用值：{}",
+            quality,
+            model,
+            GPT_IMAGE_QUALITIES.join(", "),
+            model,
+            quality,
+            GPT_IMAGE_QUALITIES.join(", ")
+        ))
+    }
+}
+
+fn mime_type_for_format(output_format: Option<&str>) -> &'static str {
+    match output_format {
+        Some("jpeg") | Some("jpg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        _ => "image/png",
+    }
+}
+
+/// Convert a parsed response into images, using `output_format` for the mime type
+fn images_from_response(payload: OpenAiImageResponse) -> Vec<GeneratedImage> {
+    let mime_type = mime_type_for_format(payload.output_format.as_deref());
+    payload
+        .data
+        .into_iter()
+        .map(|item| GeneratedImage {
+            b64_json: item.b64_json,
+            url: item.url,
+            mime_type: mime_type.to_string(),
+            revised_prompt: normalize_revised_prompt(item.revised_prompt),
+            expires_at: None,
+        })
+        .collect()
+}
+
 pub struct OpenAiImageClient {
     config: ProviderConfig,
+    request_ids: RequestIdTracker,
+    /// Cached result sent as a conditional request, answered from the cache on `304`
+    stale: Option<StaleEntry>,
+    validators: Mutex<Option<CacheValidators>>,
 }
 
 impl OpenAiImageClient {
     pub fn new(config: ProviderConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            request_ids: RequestIdTracker::default(),
+            stale: None,
+            validators: Mutex::new(None),
+        }
+    }
+
+    /// Revalidate `stale` with the provider instead of generating from scratch
+    pub fn revalidating(mut self, stale: Option<StaleEntry>) -> Self {
+        self.stale = stale;
+        self
+    }
+
+    /// Client and provider IDs of the calls this client made
+    pub fn request_ids(&self) -> RequestIds {
+        self.request_ids.ids()
+    }
+
+    /// Validators of the last response, stored with its images in the cache
+    pub fn cache_validators(&self) -> Option<CacheValidators> {
+        self.validators
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Stored images when the provider answered a revalidation with `304 Not Modified`
+    fn not_modified(&self, response: &reqwest::Response) -> Option<Vec<GeneratedImage>> {
+        let stale = self
+            .stale
+            .as_ref()
+            .filter(|_| response.status() == reqwest::StatusCode::NOT_MODIFIED)?;
+        // A 304 may leave its validators out, the stored ones then still hold
+        self.validators
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_or_insert_with(|| stale.validators.clone());
+        log::info!("[OpenAiImage] Cached images are still current, reusing them");
+        Some(stale.images.clone())
+    }
+
+    fn build_body(
+        &self,
+        model: &str,
+        request: ImageGenerationRequest,
+    ) -> Result<OpenAiImageRequest, String> {
+        let gpt_image = is_gpt_image_model(model);
+        Ok(OpenAiImageRequest {
+            model: model.to_string(),
+            prompt: request.prompt,
+            size: request.size,
+            quality: resolve_quality(model, request.quality)?,
+            n: request.n,
+            response_format: if gpt_image {
+                None
+            } else {
+                request.response_format
+            },
+            user: request.user_id.filter(|user| !user.trim().is_empty()),
+            stream: None,
+            partial_images: None,
+        })
     }
 
     pub async fn generate(
@@ -49,6 +227,143 @@ impl OpenAiImageClient {
         model: &str,
         request: ImageGenerationRequest,
     ) -> Result<Vec<GeneratedImage>, String> {
+        let body = self.build_body(model, request)?;
+        let (response, url) = self.send(api_keys, &body).await?;
+
+        request_log::log_response("OpenAiImageClient", &url, response.status().as_u16(), None);
+        if let Some(images) = self.not_modified(&response) {
+            return Ok(images);
+        }
+        let payload = response
+            .json::<OpenAiImageResponse>()
+            .await
+            .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
+
+        if let Some(usage) = payload.usage.as_ref() {
+            log::info!(
+                "[OpenAiImage] {} usage: input={} output={} total={}",
+                model,
+                usage.input_tokens,
+                usage.output_tokens,
+                usage.total_tokens
+            );
+        }
+
+        Ok(images_from_response(payload))
+    }
+
+    /// Like `generate`, reporting preview frames and progress on `events` and ending with a
+    /// `Complete` event; models other than `gpt-image-*` fall back to `generate`
+    pub async fn generate_streaming(
+        &self,
+        api_keys: &ApiKeyManager,
+        model: &str,
+        request: ImageGenerationRequest,
+        events: &ImageEventSender,
+    ) -> Result<Vec<GeneratedImage>, String> {
+        if !is_gpt_image_model(model) {
+            let images = self.generate(api_keys, model, request).await?;
+            emit(
+                events,
+                ImageGenerationEvent::Complete {
+                    images: images.clone(),
+                },
+            );
+            return Ok(images);
+        }
+
+        let total = request.n.unwrap_or(1).max(1) as usize;
+        let mut body = self.build_body(model, request)?;
+        body.stream = Some(true);
+        body.partial_images = Some(STREAM_PARTIAL_IMAGES);
+        let (response, url) = self.send(api_keys, &body).await?;
+        request_log::log_response("OpenAiImageClient", &url, response.status().as_u16(), None);
+        if let Some(images) = self.not_modified(&response) {
+            emit(
+                events,
+                ImageGenerationEvent::Complete {
+                    images: images.clone(),
+                },
+            );
+            return Ok(images);
+        }
+
+        // Images arrive one after another, so previews belong to the first image not yet done;
+        // frames arriving after the last image are late ones and get dropped
+        let mut images = Vec::new();
+        let mut previews = PreviewFrames::default();
+        let mut reader = SseLineReader::new();
+        let mut chunks = response.bytes_stream();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(|e| format!("OpenAI image stream failed: {}", e))?;
+            reader.feed(&chunk);
+            for record in reader.by_ref() {
+                let data = record?.data;
+                if sse::is_done(&data) {
+                    continue;
+                }
+                let event: OpenAiImageStreamEvent = serde_json::from_str(&data)
+                    .map_err(|e| format!("Failed to parse OpenAI image stream event: {}", e))?;
+                let mime_type = mime_type_for_format(event.output_format.as_deref());
+                let index = images.len();
+                match (event.event_type.as_str(), event.b64_json) {
+                    ("image_generation.partial_image", Some(b64_json)) => {
+                        let index = index.min(total - 1);
+                        let frame = event.partial_image_index.unwrap_or_default();
+                        if let Some(preview) = previews.frame(index, frame, &b64_json, mime_type) {
+                            emit(events, preview);
+                        }
+                    }
+                    ("image_generation.completed", Some(b64_json)) => {
+                        previews.deliver(index);
+                        let image = GeneratedImage {
+                            b64_json: Some(b64_json),
+                            url: None,
+                            mime_type: mime_type.to_string(),
+                            revised_prompt: None,
+                            expires_at: None,
+                        };
+                        emit(
+                            events,
+                            ImageGenerationEvent::PartialImage {
+                                index,
+                                image: image.clone(),
+                            },
+                        );
+                        images.push(image);
+                        emit(
+                            events,
+                            ImageGenerationEvent::Progress {
+                                percent: progress_percent(images.len(), total),
+                            },
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if images.is_empty() {
+            return Err(
+                "OpenAI image stream ended without an image / OpenAI 图片流未返回图片".to_string(),
+            );
+        }
+        emit(
+            events,
+            ImageGenerationEvent::Complete {
+                images: images.clone(),
+            },
+        );
+        Ok(images)
+    }
+
+    /// Send a request body and return the successful response with the URL it went to
+    /// With a stale entry the request is conditional and `304 Not Modified` counts as success
+    async fn send(
+        &self,
+        api_keys: &ApiKeyManager,
+        body: &OpenAiImageRequest,
+    ) -> Result<(reqwest::Response, String), String> {
         let credentials = api_keys.get_credentials(&self.config).await?;
         let api_key = match credentials {
             crate::llm::auth::api_key_manager::ProviderCredentials::Token(token) => token,
@@ -64,19 +379,7 @@ impl OpenAiImageClient {
         let base_url = base.resolve_base_url_with_fallback(api_keys).await?;
         let url = format!("{}/images/generations", base_url.trim_end_matches('/'));
 
-        let body = OpenAiImageRequest {
-            model: model.to_string(),
-            prompt: request.prompt,
-            size: request.size,
-            quality: request.quality,
-            n: request.n,
-            response_format: request.response_format,
-        };
-
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
         let mut headers = HashMap::new();
         headers.insert("Content-Type".to_string(), "application/json".to_string());
@@ -85,6 +388,8 @@ impl OpenAiImageClient {
             .maybe_set_openai_account_header(&self.config.id, &mut headers)
             .await?;
 
+        request_log::log_request("OpenAiImageClient", "POST", &url, &headers, Some(body));
+
         let mut header_map = reqwest::header::HeaderMap::new();
         for (key, value) in headers {
             let header_name = reqwest::header::HeaderName::from_bytes(key.as_bytes())
@@ -94,49 +399,159 @@ impl OpenAiImageClient {
             header_map.insert(header_name, header_value);
         }
 
-        let response = client
-            .post(&url)
+        let mut request = self
+            .request_ids
+            .tag(client.post(&url))
+            .timeout(Duration::from_secs(120))
             .headers(header_map)
-            .json(&body)
-            .send()
+            .json(body);
+        if let Some(stale) = &self.stale {
+            request = stale.validators.apply(request);
+        }
+        let response = request
+            .send_with_middleware()
             .await
             .map_err(|e| format!("OpenAI image request failed: {}", e))?;
+        self.request_ids.record(response.headers());
+        *self.validators.lock().unwrap_or_else(|e| e.into_inner()) =
+            CacheValidators::from_headers(response.headers());
 
-        if !response.status().is_success() {
+        let not_modified = response.status() == reqwest::StatusCode::NOT_MODIFIED;
+        if !response.status().is_success() && !(not_modified && self.stale.is_some()) {
             let status = response.status();
             let body = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
+            request_log::log_response("OpenAiImageClient", &url, status.as_u16(), Some(body));
             return Err(format!(
                 "OpenAI image generation failed ({}): {} / OpenAI 图片生成失败",
                 status, body
             ));
         }
-
-        let payload = response
-            .json::<OpenAiImageResponse>()
-            .await
-            .map_err(|e| format!("Failed to parse OpenAI response: {}", e))?;
-
-        let images = payload
-            .data
-            .into_iter()
-            .map(|item| GeneratedImage {
-                b64_json: item.b64_json,
-                url: item.url,
-                mime_type: "image/png".to_string(),
-                revised_prompt: item.revised_prompt,
-            })
-            .collect();
-
-        Ok(images)
+        Ok((response, url))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::types::ProtocolType;
+
+    fn test_client() -> OpenAiImageClient {
+        OpenAiImageClient::new(provider_config(
+            "openai",
+            ProtocolType::OpenAiCompatible,
+            "https://api.openai.com/v1",
+        ))
+    }
+
+    fn image_request(quality: Option<&str>) -> ImageGenerationRequest {
+        ImageGenerationRequest {
+            model: String::new(),
+            prompt: "a lighthouse at dusk".to_string(),
+            size: Some("1024x1024".to_string()),
+            aspect_ratio: None,
+            quality: quality.map(str::to_string),
+            n: Some(1),
+            response_format: Some("url".to_string()),
+            provider_options: None,
+            request_id: None,
+            image: None,
+            mask: None,
+            timeout_ms: None,
+            seed: None,
+            negative_prompt: None,
+            skip_size_validation: false,
+            strict_n: false,
+            download_urls: false,
+            user_id: None,
+            template: None,
+        }
+    }
+
+    #[test]
+    fn parses_gpt_image_response_with_usage() {
+        let json = r#"{
+            "created": 1713833628,
+            "background": "opaque",
+            "data": [{"b64_json": "iVBORw0KGgo="}],
+            "output_format": "webp",
+            "quality": "high",
+            "size": "1024x1024",
+            "usage": {
+                "input_tokens": 50,
+                "input_tokens_details": {"image_tokens": 0, "text_tokens": 50},
+                "output_tokens": 4160,
+                "total_tokens": 4210
+            }
+        }"#;
+        let parsed: OpenAiImageResponse = serde_json::from_str(json).expect("parse response");
+        let usage = parsed.usage.clone().expect("usage block");
+        assert_eq!(usage.input_tokens, 50);
+        assert_eq!(usage.output_tokens, 4160);
+        assert_eq!(usage.total_tokens, 4210);
+
+        let images = images_from_response(parsed);
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].b64_json.as_deref(), Some("iVBORw0KGgo="));
+        assert_eq!(images[0].url, None);
+        assert_eq!(images[0].mime_type, "image/webp");
+    }
+
+    #[test]
+    fn gpt_image_body_omits_response_format_and_maps_quality() {
+        let body = test_client()
+            .build_body("gpt-image-1", image_request(Some("hd")))
+            .expect("build body");
+        let json = serde_json::to_value(&body).expect("serialize body");
+        assert_eq!(json["quality"], "high");
+        assert!(json.get("response_format").is_none());
+
+        for quality in ["low", "medium", "high", "auto"] {
+            let body = test_client()
+                .build_body("gpt-image-1", image_request(Some(quality)))
+                .expect("build body");
+            assert_eq!(body.quality.as_deref(), Some(quality));
+        }
+    }
+
+    #[test]
+    fn gpt_image_rejects_unknown_quality() {
+        let err = test_client()
+            .build_body("gpt-image-1", image_request(Some("ultra")))
+            .expect_err("unsupported quality");
+        assert!(err.contains("low, medium, high, auto"));
+    }
+
+    #[test]
+    fn body_sends_user_only_when_provided() {
+        let client = test_client();
+        let body = serde_json::to_value(
+            client
+                .build_body("dall-e-3", image_request(None))
+                .expect("build body"),
+        )
+        .expect("serialize body");
+        assert!(body.get("user").is_none());
+
+        let mut request = image_request(None);
+        request.user_id = Some("account-42".to_string());
+        let body =
+            serde_json::to_value(client.build_body("dall-e-3", request).expect("build body"))
+                .expect("serialize body");
+        assert_eq!(body["user"], "account-42");
+    }
+
+    #[test]
+    fn dall_e_body_keeps_quality_and_response_format() {
+        let body = test_client()
+            .build_body("dall-e-3", image_request(Some("hd")))
+            .expect("build body");
+        assert_eq!(body.quality.as_deref(), Some("hd"));
+        assert_eq!(body.response_format.as_deref(), Some("url"));
+    }
 
     #[test]
     fn parses_openai_image_response_with_b64() {
@@ -147,6 +562,15 @@ mod tests {
         assert_eq!(parsed.data[0].revised_prompt.as_deref(), Some("hi"));
     }
 
+    #[test]
+    fn images_from_response_drops_empty_revised_prompt() {
+        let json = r#"{"data":[{"b64_json":"abc","revised_prompt":""},{"b64_json":"def"}]}"#;
+        let parsed: OpenAiImageResponse = serde_json::from_str(json).expect("parse response");
+        let images = images_from_response(parsed);
+        assert_eq!(images.len(), 2);
+        assert!(images.iter().all(|image| image.revised_prompt.is_none()));
+    }
+
     #[test]
     fn parses_openai_image_response_with_url() {
         let json = r#"{"data":[{"url":"https://example.com/image.png"}]}"#;
@@ -157,4 +581,178 @@ mod tests {
             Some("https://example.com/image.png")
         );
     }
+
+    fn stale_entry() -> StaleEntry {
+        StaleEntry {
+            images: vec![GeneratedImage {
+                b64_json: Some("c3RvcmVk".to_string()),
+                url: None,
+                mime_type: "image/png".to_string(),
+                revised_prompt: None,
+                expires_at: None,
+            }],
+            validators: CacheValidators {
+                etag: Some("\"v1\"".to_string()),
+                last_modified: Some("Tue, 13 Oct 2026 08:00:00 GMT".to_string()),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn not_modified_returns_the_stored_images() {
+        use crate::llm::testing::mock_server::start_capture_server;
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+        let (base_url, captured) = start_capture_server(304, Vec::new()).expect("mock server");
+        let mut client = test_client().revalidating(Some(stale_entry()));
+        client.config.base_url = base_url;
+
+        let images = client
+            .generate(&api_keys, "dall-e-3", image_request(None))
+            .await
+            .expect("revalidated images");
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].b64_json.as_deref(), Some("c3RvcmVk"));
+        // No validators came back with the 304, so the stored ones are kept
+        assert_eq!(client.cache_validators(), Some(stale_entry().validators));
+        let request = captured.recv().expect("captured request");
+        assert_eq!(request.header("if-none-match"), Some("\"v1\""));
+        assert_eq!(
+            request.header("if-modified-since"),
+            Some("Tue, 13 Oct 2026 08:00:00 GMT")
+        );
+        assert_eq!(request.header("cache-control"), Some("no-cache"));
+    }
+
+    #[tokio::test]
+    async fn modified_results_replace_the_stored_images_and_validators() {
+        use crate::llm::testing::mock_server::start_capture_server_with_headers;
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+        let body = br#"{"data":[{"b64_json":"ZnJlc2g="}]}"#.to_vec();
+        let (base_url, _captured) =
+            start_capture_server_with_headers(200, body, vec![("ETag", "\"v2\"")])
+                .expect("mock server");
+        let mut client = test_client().revalidating(Some(stale_entry()));
+        client.config.base_url = base_url;
+
+        let images = client
+            .generate(&api_keys, "dall-e-3", image_request(None))
+            .await
+            .expect("fresh images");
+
+        assert_eq!(images[0].b64_json.as_deref(), Some("ZnJlc2g="));
+        assert_eq!(
+            client.cache_validators(),
+            Some(CacheValidators {
+                etag: Some("\"v2\"".to_string()),
+                last_modified: None,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn not_modified_without_a_stored_entry_is_an_error() {
+        use crate::llm::testing::mock_server::start_capture_server;
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+        let (base_url, captured) = start_capture_server(304, Vec::new()).expect("mock server");
+        let mut client = test_client();
+        client.config.base_url = base_url;
+
+        let err = client
+            .generate(&api_keys, "dall-e-3", image_request(None))
+            .await
+            .expect_err("unexpected 304");
+
+        assert!(err.contains("304"), "{}", err);
+        let request = captured.recv().expect("captured request");
+        assert_eq!(request.header("if-none-match"), None);
+    }
+
+    #[tokio::test]
+    async fn generate_streaming_sends_previews_then_the_final_image() {
+        use crate::llm::testing::mock_server::start_capture_server;
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+        let frames = [
+            STANDARD.encode("blurry"),
+            STANDARD.encode("sharper"),
+            STANDARD.encode("final"),
+        ];
+        let events = [
+            format!(
+                r#"{{"type":"image_generation.partial_image","b64_json":"{}","partial_image_index":0,"output_format":"webp"}}"#,
+                frames[0]
+            ),
+            r#"{"type":"image_generation.partial_image","b64_json":"%%%","partial_image_index":1}"#
+                .to_string(),
+            format!(
+                r#"{{"type":"image_generation.partial_image","b64_json":"{}","partial_image_index":1,"output_format":"webp"}}"#,
+                frames[1]
+            ),
+            format!(
+                r#"{{"type":"image_generation.completed","b64_json":"{}","output_format":"webp"}}"#,
+                frames[2]
+            ),
+            format!(
+                r#"{{"type":"image_generation.partial_image","b64_json":"{}","partial_image_index":2}}"#,
+                frames[1]
+            ),
+        ];
+        let body: String = events
+            .iter()
+            .map(|event| format!("data: {}\n\n", event))
+            .collect();
+        let (base_url, captured) =
+            start_capture_server(200, body.into_bytes()).expect("start mock server");
+        let mut client = test_client();
+        client.config.base_url = base_url;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let images = client
+            .generate_streaming(&api_keys, "gpt-image-1", image_request(None), &tx)
+            .await
+            .expect("stream image");
+        drop(tx);
+
+        let mut previews = Vec::new();
+        let mut received = Vec::new();
+        while let Some(event) = rx.recv().await {
+            if let ImageGenerationEvent::Preview {
+                index: 0,
+                frame,
+                partial_b64,
+                mime_type,
+            } = &event
+            {
+                assert_eq!(mime_type, "image/webp");
+                previews.push((*frame, STANDARD.decode(partial_b64).expect("decodes")));
+            } else {
+                received.push(event);
+            }
+        }
+        // The malformed frame and the one after the final image are dropped
+        assert_eq!(
+            previews,
+            vec![(0, b"blurry".to_vec()), (1, b"sharper".to_vec())]
+        );
+        assert!(matches!(
+            &received[..],
+            [
+                ImageGenerationEvent::PartialImage { index: 0, .. },
+                ImageGenerationEvent::Progress { percent: 100 },
+                ImageGenerationEvent::Complete { images },
+            ] if images.len() == 1
+        ));
+        assert_eq!(images[0].b64_json.as_deref(), Some(frames[2].as_str()));
+        assert_eq!(images[0].mime_type, "image/webp");
+
+        let request = captured.recv().expect("captured request");
+        let sent: serde_json::Value = serde_json::from_slice(&request.body).expect("json body");
+        assert_eq!(sent["stream"], true);
+        assert_eq!(sent["partial_images"], STREAM_PARTIAL_IMAGES);
+    }
 }
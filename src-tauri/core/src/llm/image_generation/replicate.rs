@@ -0,0 +1,512 @@
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::http_client::resolve_timeout;
+use crate::llm::http_client::SendWithMiddleware;
+use crate::llm::image_generation::download::fetch_base64;
+use crate::llm::image_generation::types::{
+    requested_size, AspectRatioSupport, GeneratedImage, ImageGenerationRequest,
+};
+use crate::llm::providers::provider::BaseProvider;
+use crate::llm::request_id::{RequestIdTracker, RequestIds};
+use crate::llm::request_log;
+use crate::llm::types::ProviderConfig;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long to wait for a prediction to finish when the request sets no timeout
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+/// First delay between status polls, doubled after each poll
+const DEFAULT_POLL_INITIAL: Duration = Duration::from_millis(500);
+/// Upper bound for the delay between status polls
+const DEFAULT_POLL_MAX: Duration = Duration::from_secs(5);
+
+/// Prediction object returned by the create and get endpoints
+#[derive(Debug, Clone, Deserialize)]
+struct Prediction {
+    id: String,
+    status: String,
+    #[serde(default)]
+    output: Option<Value>,
+    #[serde(default)]
+    error: Option<Value>,
+}
+
+/// What to do after reading a prediction's status
+#[derive(Debug, Clone, PartialEq)]
+enum PollStep {
+    /// `starting` or `processing`: poll again
+    Pending,
+    /// `succeeded`: the output image URLs
+    Succeeded(Vec<String>),
+    Failed(String),
+    Canceled,
+}
+
+impl Prediction {
+    fn step(&self) -> PollStep {
+        match self.status.as_str() {
+            "succeeded" => PollStep::Succeeded(output_urls(self.output.as_ref())),
+            "failed" => PollStep::Failed(
+                self.error
+                    .as_ref()
+                    .map(|error| match error {
+                        Value::String(message) => message.clone(),
+                        other => other.to_string(),
+                    })
+                    .filter(|message| !message.trim().is_empty())
+                    .unwrap_or_else(|| "Unknown error".to_string()),
+            ),
+            "canceled" => PollStep::Canceled,
+            _ => PollStep::Pending,
+        }
+    }
+}
+
+/// Image outputs are either a single URL or a list of URLs
+fn output_urls(output: Option<&Value>) -> Vec<String> {
+    match output {
+        Some(Value::String(url)) => vec![url.clone()],
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| item.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Create endpoint for a model reference
+/// `owner/name` runs the model's latest version, `owner/name:version` or a bare
+/// version id pins one
+fn prediction_target(base_url: &str, model: &str) -> (String, Option<String>) {
+    let base_url = base_url.trim_end_matches('/');
+    match model.split_once(':') {
+        Some((_, version)) => (
+            format!("{}/predictions", base_url),
+            Some(version.to_string()),
+        ),
+        None if model.contains('/') => (format!("{}/models/{}/predictions", base_url, model), None),
+        None => (format!("{}/predictions", base_url), Some(model.to_string())),
+    }
+}
+
+/// Model input built from the shared request fields
+fn build_input(request: &ImageGenerationRequest) -> Value {
+    let mut input = Map::new();
+    input.insert("prompt".to_string(), json!(request.prompt));
+    if let Some(size) = requested_size(request, AspectRatioSupport::Native).as_deref() {
+        // Sizes may be given as a ratio such as "16:9" or as `WIDTHxHEIGHT`
+        if size.contains(':') {
+            input.insert("aspect_ratio".to_string(), json!(size));
+        } else if let Some((width, height)) = size.split_once('x') {
+            if let (Ok(width), Ok(height)) =
+                (width.trim().parse::<u32>(), height.trim().parse::<u32>())
+            {
+                input.insert("width".to_string(), json!(width));
+                input.insert("height".to_string(), json!(height));
+            }
+        }
+    }
+    if let Some(n) = request.n {
+        input.insert("num_outputs".to_string(), json!(n.max(1)));
+    }
+    if let Some(seed) = request.seed {
+        input.insert("seed".to_string(), json!(seed));
+    }
+    if let Some(negative_prompt) = &request.negative_prompt {
+        input.insert("negative_prompt".to_string(), json!(negative_prompt));
+    }
+    Value::Object(input)
+}
+
+/// Decode a `data:` URL into (base64 data, mime type)
+fn parse_data_url(url: &str) -> Option<(String, String)> {
+    let (meta, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = meta.split(';').next().filter(|mime| !mime.is_empty());
+    Some((
+        data.to_string(),
+        mime_type.unwrap_or("image/png").to_string(),
+    ))
+}
+
+pub struct ReplicateImageClient {
+    config: ProviderConfig,
+    poll_initial: Duration,
+    poll_max: Duration,
+    request_ids: RequestIdTracker,
+}
+
+impl ReplicateImageClient {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            config,
+            poll_initial: DEFAULT_POLL_INITIAL,
+            poll_max: DEFAULT_POLL_MAX,
+            request_ids: RequestIdTracker::default(),
+        }
+    }
+
+    /// Client and provider IDs of the calls this client made
+    pub fn request_ids(&self) -> RequestIds {
+        self.request_ids.ids()
+    }
+
+    /// Override the status poll backoff (first delay and cap)
+    pub fn with_poll_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.poll_initial = initial;
+        self.poll_max = max.max(initial);
+        self
+    }
+
+    pub async fn generate(
+        &self,
+        api_keys: &ApiKeyManager,
+        model: &str,
+        request: ImageGenerationRequest,
+    ) -> Result<Vec<GeneratedImage>, String> {
+        let credentials = api_keys.get_credentials(&self.config).await?;
+        let api_token = match credentials {
+            crate::llm::auth::api_key_manager::ProviderCredentials::Token(token) => token,
+            crate::llm::auth::api_key_manager::ProviderCredentials::None => {
+                return Err(
+                    "API token not configured for Replicate image generation / Replicate 图片生成未配置 API 令牌"
+                        .to_string(),
+                )
+            }
+        };
+        let auth_header = format!("Token {}", api_token);
+
+        let base = BaseProvider::new(self.config.clone());
+        let base_url = base.resolve_base_url_with_fallback(api_keys).await?;
+        let base_url = base_url.trim_end_matches('/').to_string();
+        let timeout = resolve_timeout(request.timeout_ms, DEFAULT_TIMEOUT);
+
+        let (url, version) = prediction_target(&base_url, model);
+        let mut body = json!({ "input": build_input(&request) });
+        if let Some(version) = version {
+            body["version"] = json!(version);
+        }
+
+        let client = crate::llm::http_client::shared_client()?;
+        let headers = HashMap::from([
+            ("Authorization".to_string(), auth_header.clone()),
+            ("Content-Type".to_string(), "application/json".to_string()),
+        ]);
+        request_log::log_request("ReplicateImageClient", "POST", &url, &headers, Some(&body));
+        let response = self
+            .request_ids
+            .tag(client.post(&url))
+            .header(reqwest::header::AUTHORIZATION, &auth_header)
+            .json(&body)
+            .send_with_middleware()
+            .await
+            .map_err(|e| format!("Replicate prediction request failed: {}", e))?;
+        self.request_ids.record(response.headers());
+        let mut prediction = Self::read_prediction(response, &url, "create").await?;
+
+        let started = Instant::now();
+        let mut delay = self.poll_initial;
+        let poll_url = format!("{}/predictions/{}", base_url, prediction.id);
+        let output = loop {
+            match prediction.step() {
+                PollStep::Succeeded(urls) => break urls,
+                PollStep::Failed(message) => {
+                    return Err(format!(
+                        "Replicate prediction {} failed: {} / Replicate 预测任务失败: {}",
+                        prediction.id, message, message
+                    ))
+                }
+                PollStep::Canceled => {
+                    return Err(format!(
+                        "Replicate prediction {} was canceled / Replicate 预测任务已取消",
+                        prediction.id
+                    ))
+                }
+                PollStep::Pending => {}
+            }
+
+            if let Some(timeout) = timeout {
+                let elapsed = started.elapsed();
+                if elapsed >= timeout {
+                    Self::cancel(&client, &poll_url, &auth_header).await;
+                    return Err(format!(
+                        "Replicate prediction {} did not finish within {}ms / Replicate 预测任务超时",
+                        prediction.id,
+                        timeout.as_millis()
+                    ));
+                }
+                // Never sleep past the deadline
+                delay = delay.min(timeout - elapsed);
+            }
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(self.poll_max);
+
+            request_log::log_request::<()>(
+                "ReplicateImageClient",
+                "GET",
+                &poll_url,
+                &headers,
+                None,
+            );
+            let response = self
+                .request_ids
+                .tag(client.get(&poll_url))
+                .header(reqwest::header::AUTHORIZATION, &auth_header)
+                .send_with_middleware()
+                .await
+                .map_err(|e| format!("Replicate status request failed: {}", e))?;
+            self.request_ids.record(response.headers());
+            prediction = Self::read_prediction(response, &poll_url, "status").await?;
+        };
+
+        if output.is_empty() {
+            return Err(format!(
+                "Replicate prediction {} returned no images / Replicate 预测任务未返回图片",
+                prediction.id
+            ));
+        }
+
+        let mut images = Vec::with_capacity(output.len());
+        for url in output {
+            images.push(Self::download(&client, &url).await?);
+        }
+        Ok(images)
+    }
+
+    async fn read_prediction(
+        response: reqwest::Response,
+        url: &str,
+        stage: &str,
+    ) -> Result<Prediction, String> {
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read Replicate response: {}", e))?;
+        request_log::log_response("ReplicateImageClient", url, status.as_u16(), Some(&text));
+        if !status.is_success() {
+            return Err(format!(
+                "Replicate {} request failed ({}): {} / Replicate 请求失败",
+                stage, status, text
+            ));
+        }
+        serde_json::from_str(&text)
+            .map_err(|e| format!("Failed to parse Replicate prediction: {}", e))
+    }
+
+    /// Best-effort cancel so a timed-out prediction stops consuming credits
+    async fn cancel(client: &reqwest::Client, poll_url: &str, auth_header: &str) {
+        let url = format!("{}/cancel", poll_url);
+        if let Err(e) = client
+            .post(&url)
+            .header(reqwest::header::AUTHORIZATION, auth_header)
+            .timeout(Duration::from_secs(10))
+            .send_with_middleware()
+            .await
+        {
+            log::warn!("Failed to cancel Replicate prediction: {}", e);
+        }
+    }
+
+    /// Output URLs expire after an hour, so the image bytes are fetched right away
+    async fn download(client: &reqwest::Client, url: &str) -> Result<GeneratedImage, String> {
+        if let Some((data, mime_type)) = parse_data_url(url) {
+            return Ok(GeneratedImage {
+                b64_json: Some(data),
+                url: None,
+                mime_type,
+                revised_prompt: None,
+                expires_at: None,
+            });
+        }
+
+        let (b64_json, mime_type) = fetch_base64(client, url, None)
+            .await
+            .map_err(|e| format!("Replicate: {}", e))?;
+        Ok(GeneratedImage {
+            b64_json: Some(b64_json),
+            url: Some(url.to_string()),
+            mime_type: mime_type.unwrap_or_else(|| "image/png".to_string()),
+            revised_prompt: None,
+            expires_at: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::testing::mock_server::{start_capture_server, start_sequence_server};
+    use crate::llm::types::ProtocolType;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use std::sync::atomic::Ordering;
+
+    fn test_client(base_url: &str) -> ReplicateImageClient {
+        ReplicateImageClient::new(provider_config(
+            "replicate",
+            ProtocolType::OpenAiCompatible,
+            base_url,
+        ))
+        .with_poll_backoff(Duration::from_millis(5), Duration::from_millis(20))
+    }
+
+    fn image_request(timeout_ms: Option<u64>) -> ImageGenerationRequest {
+        ImageGenerationRequest {
+            model: String::new(),
+            prompt: "a watercolor fox".to_string(),
+            size: Some("16:9".to_string()),
+            aspect_ratio: None,
+            quality: None,
+            n: Some(1),
+            response_format: None,
+            provider_options: None,
+            request_id: None,
+            image: None,
+            mask: None,
+            timeout_ms,
+            seed: Some(7),
+            negative_prompt: None,
+            skip_size_validation: false,
+            strict_n: false,
+            download_urls: false,
+            user_id: None,
+            template: None,
+        }
+    }
+
+    fn prediction(status: &str, extra: Value) -> (u16, String) {
+        let mut body = json!({ "id": "p1", "status": status });
+        if let (Some(body), Some(extra)) = (body.as_object_mut(), extra.as_object()) {
+            body.extend(extra.clone());
+        }
+        (200, body.to_string())
+    }
+
+    #[test]
+    fn maps_prediction_status_to_poll_step() {
+        let parse = |body: Value| serde_json::from_value::<Prediction>(body).expect("prediction");
+        assert_eq!(
+            parse(json!({"id": "p", "status": "processing"})).step(),
+            PollStep::Pending
+        );
+        assert_eq!(
+            parse(json!({"id": "p", "status": "succeeded", "output": "https://x/a.png"})).step(),
+            PollStep::Succeeded(vec!["https://x/a.png".to_string()])
+        );
+        assert_eq!(
+            parse(json!({"id": "p", "status": "failed", "error": "NSFW"})).step(),
+            PollStep::Failed("NSFW".to_string())
+        );
+        assert_eq!(
+            parse(json!({"id": "p", "status": "canceled"})).step(),
+            PollStep::Canceled
+        );
+    }
+
+    #[test]
+    fn selects_create_endpoint_from_model_reference() {
+        assert_eq!(
+            prediction_target(
+                "https://api.replicate.com/v1/",
+                "black-forest-labs/flux-schnell"
+            ),
+            (
+                "https://api.replicate.com/v1/models/black-forest-labs/flux-schnell/predictions"
+                    .to_string(),
+                None
+            )
+        );
+        assert_eq!(
+            prediction_target("https://api.replicate.com/v1", "stability-ai/sdxl:abc123"),
+            (
+                "https://api.replicate.com/v1/predictions".to_string(),
+                Some("abc123".to_string())
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_polls_until_succeeded_and_downloads_output() {
+        let png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        let (image_base, captured) = start_capture_server(200, png.clone()).expect("image server");
+        let image_url = format!("{}/out-0.png", image_base);
+        let (base_url, hits) = start_sequence_server(vec![
+            (201, json!({"id": "p1", "status": "starting"}).to_string()),
+            prediction("processing", json!({})),
+            prediction("processing", json!({})),
+            prediction("succeeded", json!({ "output": [image_url] })),
+        ])
+        .expect("mock server");
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_replicate", "test-token")]).await;
+        let images = test_client(&base_url)
+            .generate(
+                &api_keys,
+                "black-forest-labs/flux-schnell",
+                image_request(None),
+            )
+            .await
+            .expect("generate");
+
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].mime_type, "image/png");
+        assert_eq!(images[0].url.as_deref(), Some(image_url.as_str()));
+        assert_eq!(
+            images[0].b64_json.as_deref(),
+            Some(STANDARD.encode(png).as_str())
+        );
+        assert_eq!(captured.recv().expect("download").url, "/out-0.png");
+    }
+
+    #[tokio::test]
+    async fn generate_reports_failed_prediction() {
+        let (base_url, _) = start_sequence_server(vec![
+            prediction("starting", json!({})),
+            prediction("failed", json!({ "error": "CUDA out of memory" })),
+        ])
+        .expect("mock server");
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_replicate", "test-token")]).await;
+        let err = test_client(&base_url)
+            .generate(&api_keys, "owner/model", image_request(None))
+            .await
+            .expect_err("failed prediction");
+
+        assert!(err.contains("p1 failed: CUDA out of memory"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn generate_reports_canceled_prediction() {
+        let (base_url, _) = start_sequence_server(vec![
+            prediction("processing", json!({})),
+            prediction("canceled", json!({})),
+        ])
+        .expect("mock server");
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_replicate", "test-token")]).await;
+        let err = test_client(&base_url)
+            .generate(&api_keys, "owner/model", image_request(None))
+            .await
+            .expect_err("canceled prediction");
+
+        assert!(err.contains("was canceled"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn generate_times_out_while_prediction_is_pending() {
+        let responses = std::iter::repeat_with(|| prediction("processing", json!({})))
+            .take(50)
+            .collect();
+        let (base_url, _) = start_sequence_server(responses).expect("mock server");
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_replicate", "test-token")]).await;
+        let err = test_client(&base_url)
+            .generate(&api_keys, "owner/model", image_request(Some(60)))
+            .await
+            .expect_err("timed out prediction");
+
+        assert!(err.contains("did not finish within 60ms"), "{}", err);
+    }
+}
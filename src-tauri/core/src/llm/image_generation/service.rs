@@ -1,30 +1,60 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::http_client::{resolve_timeout, shared_client};
 use crate::llm::image_generation::aigateway::AIGatewayImageClient;
+use crate::llm::image_generation::cache::{
+    CacheLookup, ImageCache, StaleEntry, IMAGE_CACHE_DIR, IMAGE_CACHE_ENABLED_KEY,
+    REVALIDATE_ENABLED_PREFIX,
+};
+use crate::llm::image_generation::coalesce::{
+    image_request_key, RequestCoalescer, COALESCE_ENABLED_PREFIX,
+};
 use crate::llm::image_generation::dashscope::DashScopeImageClient;
+use crate::llm::image_generation::download::{
+    download_url_images, fetch_base64, DEFAULT_DOWNLOAD_TIMEOUT,
+};
+use crate::llm::image_generation::expiry::{fill_url_expiry, url_expired};
 use crate::llm::image_generation::google::GoogleImageClient;
 use crate::llm::image_generation::openai::OpenAiImageClient;
-use crate::llm::image_generation::types::{ImageGenerationRequest, ImageGenerationResponse};
+use crate::llm::image_generation::replicate::ReplicateImageClient;
+use crate::llm::image_generation::stability::StabilityImageClient;
+use crate::llm::image_generation::types::{
+    validate_image_data, GeneratedImage, ImageGenerationRequest, ImageGenerationResponse,
+};
 use crate::llm::image_generation::volcengine::VolcengineImageClient;
 use crate::llm::image_generation::zhipu::ZhipuImageClient;
 use crate::llm::models::model_registry::ModelRegistry;
+use crate::llm::moderation;
+use crate::llm::providers::provider::setting_enabled;
 use crate::llm::providers::provider_registry::ProviderRegistry;
 use crate::llm::types::CustomProvidersConfiguration;
+use crate::llm::types::ImageRefreshRequest;
 use crate::llm::types::ModelsConfiguration;
+use std::sync::OnceLock;
+use tokio_util::sync::CancellationToken;
 
 /// Settings key for image generator model type
 const IMAGE_GENERATOR_MODEL_TYPE_KEY: &str = "model_type_image_generator";
 /// Default image generator model
 const DEFAULT_IMAGE_GENERATOR_MODEL: &str = "gemini-3-pro-image";
 
+/// Image requests currently running for providers with coalescing enabled
+fn in_flight_requests() -> &'static RequestCoalescer<Result<ImageGenerationResponse, String>> {
+    static IN_FLIGHT: OnceLock<RequestCoalescer<Result<ImageGenerationResponse, String>>> =
+        OnceLock::new();
+    IN_FLIGHT.get_or_init(RequestCoalescer::new)
+}
+
 pub struct ImageGenerationService;
 
 impl ImageGenerationService {
+    /// Generate images for `request`; clients that support it abort once `cancel` fires
     pub async fn generate(
         api_keys: &ApiKeyManager,
         registry: &ProviderRegistry,
         custom_providers: &CustomProvidersConfiguration,
         models: &ModelsConfiguration,
-        request: ImageGenerationRequest,
+        mut request: ImageGenerationRequest,
+        cancel: Option<&CancellationToken>,
     ) -> Result<ImageGenerationResponse, String> {
         let api_map = api_keys.load_api_keys().await?;
 
@@ -53,19 +83,169 @@ impl ImageGenerationService {
         let provider_model_name =
             ModelRegistry::resolve_provider_model_name(&model_key, &provider_id, models);
 
+        if let Some(template) = request.template.take() {
+            request.prompt = template.render(&request.prompt)?;
+        }
+        moderation::precheck(api_keys, registry, &provider_id, &request.prompt).await?;
+
+        // Seeded requests are answered from the disk cache when it is enabled; expired entries
+        // are revalidated with the provider when that is enabled for it
+        let cache =
+            Self::image_cache(api_keys, &provider_id, &provider_model_name, &request).await?;
+        let mut stale = None;
+        if let Some((cache, key)) = &cache {
+            match cache.lookup(key).await {
+                CacheLookup::Fresh(images) => {
+                    return Ok(ImageGenerationResponse {
+                        provider: provider_id,
+                        images,
+                        request_id: None,
+                        rate_limit: None,
+                        request_ids: None,
+                        cache_validators: None,
+                        errors: Vec::new(),
+                    });
+                }
+                CacheLookup::Stale(entry) => stale = Some(entry),
+                CacheLookup::Miss => {}
+            }
+        }
+
+        // With coalescing on, identical requests already in flight share that call's result
+        let coalesce_key = setting_enabled(api_keys, COALESCE_ENABLED_PREFIX, &provider_id)
+            .await?
+            .then(|| image_request_key(&provider_id, &provider_model_name, &request));
+        let download_timeout = request
+            .download_urls
+            .then(|| resolve_timeout(request.timeout_ms, DEFAULT_DOWNLOAD_TIMEOUT));
+        let upstream = Self::dispatch(
+            api_keys,
+            registry,
+            provider_id,
+            &provider_model_name,
+            request,
+            stale,
+            cancel,
+        );
+        let mut result = match coalesce_key {
+            Some(key) => in_flight_requests().run(key, upstream).await,
+            None => upstream.await,
+        };
+
+        // Clients that already inlined their images are skipped, only URL-only results download
+        if let (Ok(response), Some(timeout)) = (&mut result, download_timeout) {
+            download_url_images(&mut response.images, timeout, None)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        if let Ok(response) = &mut result {
+            validate_image_data(&mut response.images)?;
+            fill_url_expiry(&mut response.images);
+        }
+
+        // A partial batch is not cached, or its missing images would never be generated again
+        if let (Ok(response), Some((cache, key))) = (&result, &cache) {
+            if response.errors.is_empty() {
+                // A failed write only costs the next request a cache hit
+                let validators = response.cache_validators.as_ref();
+                if let Err(e) = cache.put(key, &response.images, validators).await {
+                    log::warn!("[ImageCache] Failed to store images: {}", e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Renew an image whose URL may have expired, for the UI to call when it next shows it
+    /// A URL that still works is downloaded and inlined; an expired or failing one is generated
+    /// again from the original request, which seeded requests answer from the image cache
+    pub async fn refresh_image_url(
+        api_keys: &ApiKeyManager,
+        registry: &ProviderRegistry,
+        custom_providers: &CustomProvidersConfiguration,
+        models: &ModelsConfiguration,
+        refresh: ImageRefreshRequest,
+    ) -> Result<GeneratedImage, String> {
+        let ImageRefreshRequest { mut image, request } = refresh;
+        if image.b64_json.is_some() {
+            return Ok(image);
+        }
+        let now = chrono::Utc::now().timestamp();
+        if let Some(url) = image.url.clone().filter(|_| !url_expired(&image, now)) {
+            let timeout = resolve_timeout(request.timeout_ms, DEFAULT_DOWNLOAD_TIMEOUT);
+            match fetch_base64(&shared_client()?, &url, timeout).await {
+                Ok((b64_json, mime_type)) => {
+                    image.b64_json = Some(b64_json);
+                    if let Some(mime_type) = mime_type {
+                        image.mime_type = mime_type;
+                    }
+                    validate_image_data(std::slice::from_mut(&mut image))?;
+                    return Ok(image);
+                }
+                Err(e) => log::warn!("[ImageGenerationService] Regenerating {}: {}", url, e),
+            }
+        }
+
+        let request = ImageGenerationRequest {
+            n: Some(1),
+            ..request
+        };
+        Self::generate(api_keys, registry, custom_providers, models, request, None)
+            .await?
+            .images
+            .into_iter()
+            .next()
+            .ok_or_else(|| "Provider returned no image / 服务商未返回图片".to_string())
+    }
+
+    /// Cache and key for a request, or `None` when caching is off or the request has no seed
+    async fn image_cache(
+        api_keys: &ApiKeyManager,
+        provider_id: &str,
+        provider_model_name: &str,
+        request: &ImageGenerationRequest,
+    ) -> Result<Option<(ImageCache, String)>, String> {
+        let enabled = api_keys.get_setting(IMAGE_CACHE_ENABLED_KEY).await?;
+        if enabled.as_deref() != Some("true") {
+            return Ok(None);
+        }
+        let Some(key) = ImageCache::key(provider_id, provider_model_name, request) else {
+            return Ok(None);
+        };
+        let revalidate = setting_enabled(api_keys, REVALIDATE_ENABLED_PREFIX, provider_id).await?;
+        let cache = ImageCache::new(api_keys.app_data_dir().join(IMAGE_CACHE_DIR))
+            .with_revalidation(revalidate);
+        Ok(Some((cache, key)))
+    }
+
+    /// Send the request to the provider's image client
+    /// `stale` is revalidated by clients that support conditional requests
+    async fn dispatch(
+        api_keys: &ApiKeyManager,
+        registry: &ProviderRegistry,
+        provider_id: String,
+        provider_model_name: &str,
+        request: ImageGenerationRequest,
+        stale: Option<StaleEntry>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<ImageGenerationResponse, String> {
         match provider_id.as_str() {
             "openai" => {
                 let provider = registry
                     .provider(&provider_id)
                     .ok_or_else(|| "OpenAI provider not configured".to_string())?;
-                let client = OpenAiImageClient::new(provider.clone());
+                let client = OpenAiImageClient::new(provider.clone()).revalidating(stale);
                 let images = client
-                    .generate(api_keys, &provider_model_name, request)
+                    .generate(api_keys, provider_model_name, request)
                     .await?;
                 Ok(ImageGenerationResponse {
                     provider: provider_id,
                     images,
                     request_id: None,
+                    rate_limit: None,
+                    request_ids: Some(client.request_ids()),
+                    cache_validators: client.cache_validators(),
+                    errors: Vec::new(),
                 })
             }
             "aiGateway" => {
@@ -74,12 +254,16 @@ impl ImageGenerationService {
                     .ok_or_else(|| "aiGateway provider not configured".to_string())?;
                 let client = AIGatewayImageClient::new(provider.clone());
                 let images = client
-                    .generate(api_keys, &provider_model_name, request)
+                    .generate(api_keys, provider_model_name, request)
                     .await?;
                 Ok(ImageGenerationResponse {
                     provider: provider_id,
                     images,
                     request_id: None,
+                    rate_limit: None,
+                    request_ids: Some(client.request_ids()),
+                    cache_validators: None,
+                    errors: Vec::new(),
                 })
             }
             "google" => {
@@ -88,12 +272,16 @@ impl ImageGenerationService {
                     .ok_or_else(|| "Google provider not configured".to_string())?;
                 let client = GoogleImageClient::with_base_url(provider.base_url.clone());
                 let images = client
-                    .generate(api_keys, &provider_model_name, request)
+                    .generate(api_keys, provider_model_name, request)
                     .await?;
                 Ok(ImageGenerationResponse {
                     provider: provider_id,
                     images,
                     request_id: None,
+                    rate_limit: None,
+                    request_ids: Some(client.request_ids()),
+                    cache_validators: None,
+                    errors: Vec::new(),
                 })
             }
             "volcengine" => {
@@ -101,13 +289,18 @@ impl ImageGenerationService {
                     .provider(&provider_id)
                     .ok_or_else(|| "Volcengine provider not configured".to_string())?;
                 let client = VolcengineImageClient::new(provider.clone());
-                let images = client
-                    .generate(api_keys, &provider_model_name, request)
+                // Models returning one image per call fan a larger `n` out into a batch
+                let batch = client
+                    .generate_partial(api_keys, provider_model_name, request, cancel)
                     .await?;
                 Ok(ImageGenerationResponse {
                     provider: provider_id,
-                    images,
+                    images: batch.images,
                     request_id: None,
+                    rate_limit: client.last_rate_limit(),
+                    request_ids: Some(client.request_ids()),
+                    cache_validators: None,
+                    errors: batch.errors,
                 })
             }
             "zhipu" => {
@@ -116,12 +309,16 @@ impl ImageGenerationService {
                     .ok_or_else(|| "Zhipu AI provider not configured".to_string())?;
                 let client = ZhipuImageClient::new(provider.clone());
                 let images = client
-                    .generate(api_keys, &provider_model_name, request)
+                    .generate(api_keys, provider_model_name, request)
                     .await?;
                 Ok(ImageGenerationResponse {
                     provider: provider_id,
                     images,
                     request_id: None,
+                    rate_limit: None,
+                    request_ids: Some(client.request_ids()),
+                    cache_validators: None,
+                    errors: Vec::new(),
                 })
             }
             "alibaba" => {
@@ -130,12 +327,52 @@ impl ImageGenerationService {
                     .ok_or_else(|| "Alibaba provider not configured".to_string())?;
                 let client = DashScopeImageClient::new(provider.clone());
                 let images = client
-                    .generate(api_keys, &provider_model_name, request)
+                    .generate(api_keys, provider_model_name, request)
+                    .await?;
+                Ok(ImageGenerationResponse {
+                    provider: provider_id,
+                    images,
+                    request_id: None,
+                    rate_limit: None,
+                    request_ids: Some(client.request_ids()),
+                    cache_validators: None,
+                    errors: Vec::new(),
+                })
+            }
+            "stability" => {
+                let provider = registry
+                    .provider(&provider_id)
+                    .ok_or_else(|| "Stability provider not configured".to_string())?;
+                let client = StabilityImageClient::new(provider.clone());
+                let images = client
+                    .generate(api_keys, provider_model_name, request)
+                    .await?;
+                Ok(ImageGenerationResponse {
+                    provider: provider_id,
+                    images,
+                    request_id: None,
+                    rate_limit: None,
+                    request_ids: Some(client.request_ids()),
+                    cache_validators: None,
+                    errors: Vec::new(),
+                })
+            }
+            "replicate" => {
+                let provider = registry
+                    .provider(&provider_id)
+                    .ok_or_else(|| "Replicate provider not configured".to_string())?;
+                let client = ReplicateImageClient::new(provider.clone());
+                let images = client
+                    .generate(api_keys, provider_model_name, request)
                     .await?;
                 Ok(ImageGenerationResponse {
                     provider: provider_id,
                     images,
                     request_id: None,
+                    rate_limit: None,
+                    request_ids: Some(client.request_ids()),
+                    cache_validators: None,
+                    errors: Vec::new(),
                 })
             }
             _ => Err(format!(
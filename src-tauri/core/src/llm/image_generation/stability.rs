@@ -0,0 +1,378 @@
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::http_client::SendWithMiddleware;
+use crate::llm::image_generation::types::{
+    requested_size, AspectRatioSupport, GeneratedImage, ImageGenerationRequest,
+};
+use crate::llm::providers::provider::BaseProvider;
+use crate::llm::request_id::{RequestIdTracker, RequestIds};
+use crate::llm::request_log;
+use crate::llm::types::ProviderConfig;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::multipart::Form;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Aspect ratios accepted by the Stable Image API, as (name, width / height)
+const ASPECT_RATIOS: &[(&str, f32)] = &[
+    ("21:9", 21.0 / 9.0),
+    ("16:9", 16.0 / 9.0),
+    ("3:2", 3.0 / 2.0),
+    ("5:4", 5.0 / 4.0),
+    ("1:1", 1.0),
+    ("4:5", 4.0 / 5.0),
+    ("2:3", 2.0 / 3.0),
+    ("9:16", 9.0 / 16.0),
+    ("9:21", 9.0 / 21.0),
+];
+
+/// Multipart fields for Stability's `v2beta/stable-image/generate` endpoints
+#[derive(Debug, Clone, PartialEq)]
+struct StabilityImageFields {
+    prompt: String,
+    aspect_ratio: Option<String>,
+    output_format: String,
+    /// Only sent to the `sd3` endpoint, which serves several models
+    model: Option<String>,
+    negative_prompt: Option<String>,
+    seed: Option<i64>,
+}
+
+impl StabilityImageFields {
+    fn text_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![("prompt", self.prompt.clone())];
+        if let Some(aspect_ratio) = &self.aspect_ratio {
+            fields.push(("aspect_ratio", aspect_ratio.clone()));
+        }
+        fields.push(("output_format", self.output_format.clone()));
+        if let Some(model) = &self.model {
+            fields.push(("model", model.clone()));
+        }
+        if let Some(negative_prompt) = &self.negative_prompt {
+            fields.push(("negative_prompt", negative_prompt.clone()));
+        }
+        if let Some(seed) = self.seed {
+            fields.push(("seed", seed.to_string()));
+        }
+        fields
+    }
+
+    fn into_form(self) -> Form {
+        self.text_fields()
+            .into_iter()
+            .fold(Form::new(), |form, (name, value)| form.text(name, value))
+    }
+}
+
+/// Map a model name to its endpoint: `ultra`, `core` or `sd3`
+fn endpoint_for_model(model: &str) -> &'static str {
+    if model.contains("ultra") {
+        "ultra"
+    } else if model.starts_with("sd3") {
+        "sd3"
+    } else {
+        "core"
+    }
+}
+
+/// Closest supported aspect ratio for a `WIDTHxHEIGHT` size
+fn aspect_ratio_for_size(size: &str) -> Option<String> {
+    let (width, height) = size.split_once('x')?;
+    let width: f32 = width.trim().parse().ok()?;
+    let height: f32 = height.trim().parse().ok()?;
+    if width <= 0.0 || height <= 0.0 {
+        return None;
+    }
+    let ratio = width / height;
+    ASPECT_RATIOS
+        .iter()
+        .min_by(|a, b| {
+            (a.1 - ratio)
+                .abs()
+                .partial_cmp(&(b.1 - ratio).abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|(name, _)| name.to_string())
+}
+
+/// Output format from `response_format`, which for this provider names the image encoding
+fn output_format(response_format: Option<&str>) -> &'static str {
+    match response_format {
+        Some("jpeg") | Some("jpg") => "jpeg",
+        Some("webp") => "webp",
+        _ => "png",
+    }
+}
+
+fn mime_type_for_format(format: &str) -> &'static str {
+    match format {
+        "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        _ => "image/png",
+    }
+}
+
+pub struct StabilityImageClient {
+    config: ProviderConfig,
+    request_ids: RequestIdTracker,
+}
+
+impl StabilityImageClient {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            config,
+            request_ids: RequestIdTracker::default(),
+        }
+    }
+
+    /// Client and provider IDs of the calls this client made
+    pub fn request_ids(&self) -> RequestIds {
+        self.request_ids.ids()
+    }
+
+    fn build_fields(&self, model: &str, request: ImageGenerationRequest) -> StabilityImageFields {
+        let size = requested_size(&request, AspectRatioSupport::Native);
+        let aspect_ratio = size.as_deref().and_then(|size| {
+            // Sizes may already be given as a ratio such as "16:9"
+            if size.contains(':') {
+                Some(size.to_string())
+            } else {
+                aspect_ratio_for_size(size)
+            }
+        });
+        StabilityImageFields {
+            prompt: request.prompt,
+            aspect_ratio,
+            output_format: output_format(request.response_format.as_deref()).to_string(),
+            model: (endpoint_for_model(model) == "sd3").then(|| model.to_string()),
+            negative_prompt: request.negative_prompt,
+            seed: request.seed,
+        }
+    }
+
+    pub async fn generate(
+        &self,
+        api_keys: &ApiKeyManager,
+        model: &str,
+        request: ImageGenerationRequest,
+    ) -> Result<Vec<GeneratedImage>, String> {
+        let credentials = api_keys.get_credentials(&self.config).await?;
+        let api_key = match credentials {
+            crate::llm::auth::api_key_manager::ProviderCredentials::Token(token) => token,
+            crate::llm::auth::api_key_manager::ProviderCredentials::None => {
+                return Err(
+                    "API key not configured for Stability image generation / Stability 图片生成未配置 API 密钥"
+                        .to_string(),
+                )
+            }
+        };
+
+        let base = BaseProvider::new(self.config.clone());
+        let base_url = base.resolve_base_url_with_fallback(api_keys).await?;
+        let url = format!(
+            "{}/v2beta/stable-image/generate/{}",
+            base_url.trim_end_matches('/'),
+            endpoint_for_model(model)
+        );
+
+        // The endpoint returns one image per call
+        let count = request.n.unwrap_or(1).max(1);
+        let fields = self.build_fields(model, request);
+        let mime_type = mime_type_for_format(&fields.output_format);
+
+        let client = crate::llm::http_client::shared_client()?;
+
+        let mut images = Vec::with_capacity(count as usize);
+        let log_headers = HashMap::from([
+            ("Authorization".to_string(), format!("Bearer {}", api_key)),
+            ("Accept".to_string(), "image/*".to_string()),
+        ]);
+        for _ in 0..count {
+            // Multipart bodies are not logged
+            request_log::log_request::<()>(
+                "StabilityImageClient",
+                "POST",
+                &url,
+                &log_headers,
+                None,
+            );
+            // `image/*` asks for raw bytes instead of a JSON envelope
+            let response = self
+                .request_ids
+                .tag(client.post(&url))
+                .timeout(Duration::from_secs(120))
+                .bearer_auth(&api_key)
+                .header(reqwest::header::ACCEPT, "image/*")
+                .multipart(fields.clone().into_form())
+                .send_with_middleware()
+                .await
+                .map_err(|e| format!("Stability image request failed: {}", e))?;
+            self.request_ids.record(response.headers());
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Unknown error".to_string());
+                request_log::log_response(
+                    "StabilityImageClient",
+                    &url,
+                    status.as_u16(),
+                    Some(&body),
+                );
+                return Err(format!(
+                    "Stability image generation failed ({}): {} / Stability 图片生成失败",
+                    status, body
+                ));
+            }
+            request_log::log_response(
+                "StabilityImageClient",
+                &url,
+                response.status().as_u16(),
+                None,
+            );
+
+            let bytes = response
+                .bytes()
+                .await
+                .map_err(|e| format!("Failed to read Stability response: {}", e))?;
+            images.push(GeneratedImage {
+                b64_json: Some(STANDARD.encode(bytes)),
+                url: None,
+                mime_type: mime_type.to_string(),
+                revised_prompt: None,
+                expires_at: None,
+            });
+        }
+
+        Ok(images)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::request_id::DEFAULT_REQUEST_ID_HEADER;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::testing::mock_server::{
+        start_capture_server, start_capture_server_with_headers,
+    };
+    use crate::llm::types::ProtocolType;
+
+    fn test_client(base_url: &str) -> StabilityImageClient {
+        StabilityImageClient::new(provider_config(
+            "stability",
+            ProtocolType::OpenAiCompatible,
+            base_url,
+        ))
+    }
+
+    fn image_request(size: Option<&str>) -> ImageGenerationRequest {
+        ImageGenerationRequest {
+            model: String::new(),
+            prompt: "a watercolor fox".to_string(),
+            size: size.map(str::to_string),
+            aspect_ratio: None,
+            quality: None,
+            n: Some(1),
+            response_format: None,
+            provider_options: None,
+            request_id: None,
+            image: None,
+            mask: None,
+            timeout_ms: None,
+            seed: None,
+            negative_prompt: None,
+            skip_size_validation: false,
+            strict_n: false,
+            download_urls: false,
+            user_id: None,
+            template: None,
+        }
+    }
+
+    #[test]
+    fn maps_sizes_to_nearest_aspect_ratio() {
+        assert_eq!(aspect_ratio_for_size("1024x1024").as_deref(), Some("1:1"));
+        assert_eq!(aspect_ratio_for_size("1920x1080").as_deref(), Some("16:9"));
+        assert_eq!(aspect_ratio_for_size("832x1248").as_deref(), Some("2:3"));
+        assert_eq!(aspect_ratio_for_size("square"), None);
+    }
+
+    #[test]
+    fn aspect_ratio_passes_through_unless_size_is_set() {
+        let client = test_client("http://127.0.0.1:1");
+        let mut request = image_request(None);
+        request.aspect_ratio = Some("21:9".to_string());
+
+        let fields = client.build_fields("stable-image-core", request.clone());
+        assert_eq!(fields.aspect_ratio.as_deref(), Some("21:9"));
+
+        request.size = Some("1024x1024".to_string());
+        let fields = client.build_fields("stable-image-core", request);
+        assert_eq!(fields.aspect_ratio.as_deref(), Some("1:1"));
+    }
+
+    #[test]
+    fn selects_endpoint_from_model() {
+        assert_eq!(endpoint_for_model("stable-image-ultra"), "ultra");
+        assert_eq!(endpoint_for_model("sd3.5-large"), "sd3");
+        assert_eq!(endpoint_for_model("stable-image-core"), "core");
+    }
+
+    #[tokio::test]
+    async fn generate_sends_prompt_and_aspect_ratio_and_encodes_bytes() {
+        let png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        let (base_url, captured) = start_capture_server(200, png.clone()).expect("mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_stability", "test-key")]).await;
+
+        let images = test_client(&base_url)
+            .generate(
+                &api_keys,
+                "stable-image-core",
+                image_request(Some("1920x1080")),
+            )
+            .await
+            .expect("generate");
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].mime_type, "image/png");
+        assert_eq!(
+            images[0].b64_json.as_deref(),
+            Some(STANDARD.encode(png).as_str())
+        );
+
+        let request = captured.recv().expect("captured request");
+        assert_eq!(request.url, "/v2beta/stable-image/generate/core");
+        let body = String::from_utf8_lossy(&request.body);
+        assert!(body.contains("name=\"prompt\"\r\n\r\na watercolor fox"));
+        assert!(body.contains("name=\"aspect_ratio\"\r\n\r\n16:9"));
+        assert!(body.contains("name=\"output_format\"\r\n\r\npng"));
+        assert!(!body.contains("name=\"model\""));
+    }
+
+    #[tokio::test]
+    async fn generate_sends_client_request_id_and_keeps_the_provider_id() {
+        let (base_url, captured) = start_capture_server_with_headers(
+            200,
+            b"png".to_vec(),
+            vec![("x-request-id", "stab_req_42")],
+        )
+        .expect("mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_stability", "test-key")]).await;
+        let client = test_client(&base_url);
+
+        client
+            .generate(&api_keys, "stable-image-core", image_request(None))
+            .await
+            .expect("generate");
+
+        let ids = client.request_ids();
+        let request = captured.recv().expect("captured request");
+        assert_eq!(
+            request.header(DEFAULT_REQUEST_ID_HEADER),
+            Some(ids.client.as_str())
+        );
+        assert_eq!(ids.provider.as_deref(), Some("stab_req_42"));
+    }
+}
@@ -0,0 +1,134 @@
+// Progress events for image generation
+// Providers that stream partial results report them as they arrive; the rest send one Complete event
+
+use crate::llm::image_generation::types::GeneratedImage;
+use base64::{
+    engine::general_purpose::{STANDARD, STANDARD_NO_PAD},
+    Engine,
+};
+use serde::Serialize;
+use std::collections::HashSet;
+use tokio::sync::mpsc;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ImageGenerationEvent {
+    /// Share of the requested images that have finished, 0 to 100
+    Progress { percent: u8 },
+    /// Low-resolution frame of image `index`, replaced by the next frame or the final image
+    /// Every frame is a complete base64 image that decodes on its own
+    Preview {
+        index: usize,
+        frame: usize,
+        #[serde(rename = "partialB64")]
+        partial_b64: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
+    /// One image finished before the rest of the request
+    PartialImage { index: usize, image: GeneratedImage },
+    /// Every image the request produced
+    Complete { images: Vec<GeneratedImage> },
+}
+
+pub type ImageEventSender = mpsc::UnboundedSender<ImageGenerationEvent>;
+
+/// Send an event, ignoring a receiver that has already gone away
+pub(crate) fn emit(events: &ImageEventSender, event: ImageGenerationEvent) {
+    let _ = events.send(event);
+}
+
+pub(crate) fn progress_percent(finished: usize, total: usize) -> u8 {
+    if total == 0 {
+        return 100;
+    }
+    (finished.min(total) * 100 / total) as u8
+}
+
+/// Preview frames of a streamed generation
+/// Frames that are not valid base64 are dropped, as is anything arriving for an image that
+/// has already been delivered, so a preview never replaces a final image
+#[derive(Debug, Default)]
+pub(crate) struct PreviewFrames {
+    delivered: HashSet<usize>,
+}
+
+impl PreviewFrames {
+    /// Preview event for a frame of image `index`, or None when it should not be shown
+    pub(crate) fn frame(
+        &self,
+        index: usize,
+        frame: usize,
+        data: &str,
+        mime_type: &str,
+    ) -> Option<ImageGenerationEvent> {
+        if self.delivered.contains(&index) {
+            return None;
+        }
+        let Some(partial_b64) = well_formed_base64(data) else {
+            log::warn!(
+                "[ImageStream] Dropping preview frame {} of image {}: invalid base64",
+                frame,
+                index
+            );
+            return None;
+        };
+        Some(ImageGenerationEvent::Preview {
+            index,
+            frame,
+            partial_b64,
+            mime_type: mime_type.to_string(),
+        })
+    }
+
+    /// Record that the final image `index` was delivered
+    pub(crate) fn deliver(&mut self, index: usize) {
+        self.delivered.insert(index);
+    }
+}
+
+/// `data` as padded standard base64 without whitespace, or None when it does not decode
+/// Providers sometimes wrap lines or leave off the padding
+fn well_formed_base64(data: &str) -> Option<String> {
+    let compact: String = data.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    if compact.is_empty() {
+        return None;
+    }
+    let bytes = STANDARD
+        .decode(&compact)
+        .or_else(|_| STANDARD_NO_PAD.decode(&compact))
+        .ok()?;
+    Some(STANDARD.encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preview_frames_are_normalized_and_stop_after_delivery() {
+        let mut previews = PreviewFrames::default();
+        let frame = STANDARD_NO_PAD.encode("low-res frame");
+
+        let wrapped = format!("{}\n{}", &frame[..8], &frame[8..]);
+        let Some(ImageGenerationEvent::Preview { partial_b64, .. }) =
+            previews.frame(0, 0, &wrapped, "image/png")
+        else {
+            panic!("expected a preview");
+        };
+        assert_eq!(STANDARD.decode(partial_b64).unwrap(), b"low-res frame");
+
+        assert!(previews.frame(0, 1, "not base64!", "image/png").is_none());
+
+        previews.deliver(0);
+        assert!(previews.frame(0, 2, &frame, "image/png").is_none());
+        assert!(previews.frame(1, 0, &frame, "image/png").is_some());
+    }
+
+    #[test]
+    fn progress_is_capped_at_one_hundred() {
+        assert_eq!(progress_percent(1, 4), 25);
+        assert_eq!(progress_percent(5, 4), 100);
+        assert_eq!(progress_percent(0, 0), 100);
+    }
+}
@@ -4,9 +4,9 @@ use crate::llm::image_generation::openai::OpenAiImageClient;
 use crate::llm::image_generation::service::ImageGenerationService;
 use crate::llm::image_generation::types::GeneratedImage;
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::testing::fixtures::provider_config;
 use crate::llm::types::{
-    AuthType, CustomProvidersConfiguration, ModelConfig, ModelsConfiguration, ProtocolType,
-    ProviderConfig,
+    CustomProvidersConfiguration, ModelConfig, ModelsConfiguration, ProtocolType, ProviderConfig,
 };
 use serde_json::json;
 use std::collections::HashMap;
@@ -56,19 +56,12 @@ fn openai_image_response_parses_url() {
 #[test]
 fn openai_image_client_constructs() {
     let config = ProviderConfig {
-        id: "openai".to_string(),
-        name: "OpenAI".to_string(),
-        protocol: ProtocolType::OpenAiCompatible,
-        base_url: "https://api.openai.com/v1".to_string(),
-        api_key_name: "OPENAI_API_KEY".to_string(),
         supports_oauth: true,
-        supports_coding_plan: false,
-        supports_international: false,
-        coding_plan_base_url: None,
-        international_base_url: None,
-        headers: None,
-        extra_body: None,
-        auth_type: AuthType::Bearer,
+        ..provider_config(
+            "openai",
+            ProtocolType::OpenAiCompatible,
+            "https://api.openai.com/v1",
+        )
     };
     let _client = OpenAiImageClient::new(config);
     let _image: GeneratedImage = GeneratedImage {
@@ -76,6 +69,7 @@ fn openai_image_client_constructs() {
         url: None,
         mime_type: "image/png".to_string(),
         revised_prompt: None,
+        expires_at: None,
     };
 }
 
@@ -100,36 +94,16 @@ async fn setup_test_context() -> (
 
     // Setup provider registry with image generation providers
     let providers = vec![
-        ProviderConfig {
-            id: "openai".to_string(),
-            name: "OpenAI".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.openai.com/v1".to_string(),
-            api_key_name: "OPENAI_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: AuthType::Bearer,
-        },
-        ProviderConfig {
-            id: "google".to_string(),
-            name: "Google".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://generativelanguage.googleapis.com/v1beta".to_string(),
-            api_key_name: "GOOGLE_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: AuthType::Bearer,
-        },
+        provider_config(
+            "openai",
+            ProtocolType::OpenAiCompatible,
+            "https://api.openai.com/v1",
+        ),
+        provider_config(
+            "google",
+            ProtocolType::OpenAiCompatible,
+            "https://generativelanguage.googleapis.com/v1beta",
+        ),
     ];
     let registry = ProviderRegistry::new(providers);
 
@@ -365,21 +339,11 @@ async fn resolve_image_generator_model_finds_volcengine_model() {
     let api_keys = ApiKeyManager::new(db, dir.path().join("app-data"));
 
     // Setup provider registry with volcengine
-    let providers = vec![ProviderConfig {
-        id: "volcengine".to_string(),
-        name: "Volcengine".to_string(),
-        protocol: ProtocolType::OpenAiCompatible,
-        base_url: "https://ark.cn-beijing.volces.com/api/v3".to_string(),
-        api_key_name: "VOLCENGINE_API_KEY".to_string(),
-        supports_oauth: false,
-        supports_coding_plan: false,
-        supports_international: false,
-        coding_plan_base_url: None,
-        international_base_url: None,
-        headers: None,
-        extra_body: None,
-        auth_type: AuthType::Bearer,
-    }];
+    let providers = vec![provider_config(
+        "volcengine",
+        ProtocolType::OpenAiCompatible,
+        "https://ark.cn-beijing.volces.com/api/v3",
+    )];
     let registry = ProviderRegistry::new(providers);
 
     // Setup models config with volcengine model
@@ -450,19 +414,12 @@ async fn resolve_image_generator_model_finds_alibaba_model() {
 
     // Setup provider registry with alibaba
     let providers = vec![ProviderConfig {
-        id: "alibaba".to_string(),
-        name: "Alibaba".to_string(),
-        protocol: ProtocolType::OpenAiCompatible,
-        base_url: "https://dashscope.aliyuncs.com/compatible-mode/v1".to_string(),
         api_key_name: "DASHSCOPE_API_KEY".to_string(),
-        supports_oauth: false,
-        supports_coding_plan: false,
-        supports_international: false,
-        coding_plan_base_url: None,
-        international_base_url: None,
-        headers: None,
-        extra_body: None,
-        auth_type: AuthType::Bearer,
+        ..provider_config(
+            "alibaba",
+            ProtocolType::OpenAiCompatible,
+            "https://dashscope.aliyuncs.com/compatible-mode/v1",
+        )
     }];
     let registry = ProviderRegistry::new(providers);
 
@@ -533,21 +490,11 @@ async fn resolve_image_generator_model_finds_zhipu_image_model() {
     let api_keys = ApiKeyManager::new(db, dir.path().join("app-data"));
 
     // Setup provider registry with zhipu
-    let providers = vec![ProviderConfig {
-        id: "zhipu".to_string(),
-        name: "Zhipu AI".to_string(),
-        protocol: ProtocolType::OpenAiCompatible,
-        base_url: "https://open.bigmodel.cn/api/paas/v4".to_string(),
-        api_key_name: "ZHIPU_API_KEY".to_string(),
-        supports_oauth: false,
-        supports_coding_plan: false,
-        supports_international: false,
-        coding_plan_base_url: None,
-        international_base_url: None,
-        headers: None,
-        extra_body: None,
-        auth_type: AuthType::Bearer,
-    }];
+    let providers = vec![provider_config(
+        "zhipu",
+        ProtocolType::OpenAiCompatible,
+        "https://open.bigmodel.cn/api/paas/v4",
+    )];
     let registry = ProviderRegistry::new(providers);
 
     // Setup models config with zhipu image model
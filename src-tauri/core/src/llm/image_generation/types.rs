@@ -1 +1,298 @@
 pub use crate::llm::types::{GeneratedImage, ImageGenerationRequest, ImageGenerationResponse};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// Revised prompt as reported by a provider, with blank values treated as absent
+/// Every client maps its own field through this so `GeneratedImage::revised_prompt`
+/// is either a real prompt or `None`
+pub fn normalize_revised_prompt(revised_prompt: Option<String>) -> Option<String> {
+    revised_prompt
+        .map(|prompt| prompt.trim().to_string())
+        .filter(|prompt| !prompt.is_empty())
+}
+
+/// How a provider accepts the requested image shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AspectRatioSupport {
+    /// Ratios such as `16:9` are sent as-is
+    Native,
+    /// Only pixel sizes are accepted; a ratio maps to the closest of these `WIDTHxHEIGHT` sizes
+    Sizes(&'static [&'static str]),
+}
+
+/// Size to send for a request, filling in from `aspect_ratio` when `size` is unset
+pub fn requested_size(
+    request: &ImageGenerationRequest,
+    support: AspectRatioSupport,
+) -> Option<String> {
+    if let Some(size) = &request.size {
+        return Some(size.clone());
+    }
+    let ratio = request.aspect_ratio.as_deref()?.trim();
+    match support {
+        AspectRatioSupport::Native => Some(ratio.to_string()),
+        AspectRatioSupport::Sizes(sizes) => {
+            let size = nearest_size_for_aspect_ratio(ratio, sizes);
+            if size.is_none() {
+                log::warn!("Ignoring unparseable aspect ratio '{}'", ratio);
+            }
+            size.map(str::to_string)
+        }
+    }
+}
+
+/// Width over height for a `W:H` ratio
+pub fn parse_aspect_ratio(ratio: &str) -> Option<f32> {
+    let (width, height) = ratio.split_once(':')?;
+    let width: f32 = width.trim().parse().ok()?;
+    let height: f32 = height.trim().parse().ok()?;
+    (width > 0.0 && height > 0.0).then_some(width / height)
+}
+
+/// The `WIDTHxHEIGHT` size whose shape is closest to `ratio`
+pub fn nearest_size_for_aspect_ratio(ratio: &str, sizes: &[&'static str]) -> Option<&'static str> {
+    let target = parse_aspect_ratio(ratio)?;
+    sizes
+        .iter()
+        .filter_map(|size| {
+            let (width, height) = size.split_once('x')?;
+            let width: f32 = width.parse().ok()?;
+            let height: f32 = height.parse().ok()?;
+            Some((*size, (width / height - target).abs()))
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(size, _)| size)
+}
+
+/// Image format recognised from its magic bytes: PNG, JPEG, GIF or WebP
+pub fn sniff_image_mime(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if bytes.starts_with(b"GIF8") {
+        Some("image/gif")
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// `sniff_image_mime` on base64 data, decoding only the header the signatures need
+pub fn sniff_base64_image_mime(data: &str) -> Option<&'static str> {
+    // 16 base64 characters decode to the 12 bytes the longest signature needs
+    let data = data.trim();
+    let bytes = STANDARD.decode(data.get(..16).unwrap_or(data)).ok()?;
+    sniff_image_mime(&bytes)
+}
+
+/// Mime type of a generated image from its data's magic bytes, else its URL's extension,
+/// falling back to PNG when neither tells
+pub fn detect_image_mime(b64_json: Option<&str>, url: Option<&str>) -> &'static str {
+    b64_json
+        .and_then(sniff_base64_image_mime)
+        .or_else(|| url.and_then(mime_from_url))
+        .unwrap_or("image/png")
+}
+
+fn mime_from_url(url: &str) -> Option<&'static str> {
+    let path = url.split(['?', '#']).next()?;
+    let (_, extension) = path.rsplit('/').next()?.rsplit_once('.')?;
+    match extension.to_ascii_lowercase().as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Check that every `b64_json` decodes, so truncated data fails here rather than in the UI
+/// `mime_type` is corrected from the decoded bytes; unrecognised formats keep the declared one
+pub fn validate_image_data(images: &mut [GeneratedImage]) -> Result<(), String> {
+    for (index, image) in images.iter_mut().enumerate() {
+        let Some(data) = image.b64_json.as_deref() else {
+            continue;
+        };
+        let bytes = STANDARD.decode(data.trim()).map_err(|e| {
+            format!(
+                "Image {} has invalid base64 data: {} / 第 {} 张图片的 base64 数据无效: {}",
+                index + 1,
+                e,
+                index + 1,
+                e
+            )
+        })?;
+        if bytes.is_empty() {
+            return Err(format!(
+                "Image {} has no data / 第 {} 张图片没有数据",
+                index + 1,
+                index + 1
+            ));
+        }
+        if let Some(mime_type) = sniff_image_mime(&bytes) {
+            image.mime_type = mime_type.to_string();
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZES: &[&str] = &[
+        "2048x2048",
+        "2560x1440",
+        "1440x2560",
+        "2304x1728",
+        "3024x1296",
+    ];
+
+    #[test]
+    fn normalize_revised_prompt_maps_missing_and_blank_to_none() {
+        assert_eq!(normalize_revised_prompt(None), None);
+        assert_eq!(normalize_revised_prompt(Some(String::new())), None);
+        assert_eq!(normalize_revised_prompt(Some("  \n".to_string())), None);
+        assert_eq!(
+            normalize_revised_prompt(Some(" a red fox ".to_string())),
+            Some("a red fox".to_string())
+        );
+    }
+
+    #[test]
+    fn maps_aspect_ratios_to_nearest_size() {
+        for (ratio, size) in [
+            ("1:1", "2048x2048"),
+            ("16:9", "2560x1440"),
+            ("9:16", "1440x2560"),
+            ("4:3", "2304x1728"),
+            ("3:2", "2304x1728"),
+            ("21:9", "3024x1296"),
+            (" 2 : 1 ", "2560x1440"),
+        ] {
+            assert_eq!(
+                nearest_size_for_aspect_ratio(ratio, SIZES),
+                Some(size),
+                "{}",
+                ratio
+            );
+        }
+        assert_eq!(nearest_size_for_aspect_ratio("wide", SIZES), None);
+        assert_eq!(nearest_size_for_aspect_ratio("16:0", SIZES), None);
+    }
+
+    #[test]
+    fn requested_size_prefers_size_over_aspect_ratio() {
+        let mut request: ImageGenerationRequest = serde_json::from_value(serde_json::json!({
+            "model": "m",
+            "prompt": "p",
+            "size": "1024x1024",
+            "aspectRatio": "16:9"
+        }))
+        .expect("request");
+        let sizes = AspectRatioSupport::Sizes(SIZES);
+
+        assert_eq!(
+            requested_size(&request, sizes).as_deref(),
+            Some("1024x1024")
+        );
+
+        request.size = None;
+        assert_eq!(
+            requested_size(&request, sizes).as_deref(),
+            Some("2560x1440")
+        );
+        assert_eq!(
+            requested_size(&request, AspectRatioSupport::Native).as_deref(),
+            Some("16:9")
+        );
+    }
+
+    fn image(b64_json: &str, mime_type: &str) -> GeneratedImage {
+        GeneratedImage {
+            b64_json: Some(b64_json.to_string()),
+            url: None,
+            mime_type: mime_type.to_string(),
+            revised_prompt: None,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn validate_image_data_takes_mime_type_from_magic_bytes() {
+        let png = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0];
+        let jpeg = [0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F'];
+        let mut images = vec![
+            image(&STANDARD.encode(png), "image/webp"),
+            image(&STANDARD.encode(jpeg), "image/png"),
+            image(&STANDARD.encode("not an image"), "image/webp"),
+        ];
+
+        validate_image_data(&mut images).expect("valid base64");
+
+        assert_eq!(images[0].mime_type, "image/png");
+        assert_eq!(images[1].mime_type, "image/jpeg");
+        assert_eq!(images[2].mime_type, "image/webp");
+    }
+
+    #[test]
+    fn validate_image_data_rejects_invalid_base64() {
+        let truncated = STANDARD.encode([0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A]);
+        let mut images = vec![
+            image(&STANDARD.encode([0xFF, 0xD8, 0xFF]), "image/png"),
+            image(&truncated[..truncated.len() - 3], "image/png"),
+        ];
+
+        let err = validate_image_data(&mut images).expect_err("truncated data");
+        assert!(err.contains("Image 2 has invalid base64"), "{}", err);
+
+        let err = validate_image_data(&mut [image("", "image/png")]).expect_err("empty data");
+        assert!(err.contains("no data"), "{}", err);
+        assert!(validate_image_data(&mut [image("iVBO%%%", "image/png")]).is_err());
+    }
+
+    #[test]
+    fn detect_image_mime_reads_the_data_then_the_url() {
+        let jpeg = STANDARD.encode([
+            0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F', 0, 1,
+        ]);
+
+        assert_eq!(detect_image_mime(Some(&jpeg), None), "image/jpeg");
+        assert_eq!(
+            detect_image_mime(Some(&jpeg), Some("https://cdn.example.com/a.png")),
+            "image/jpeg"
+        );
+        assert_eq!(
+            detect_image_mime(None, Some("https://cdn.example.com/a.JPEG?sig=x.png")),
+            "image/jpeg"
+        );
+        assert_eq!(
+            detect_image_mime(None, Some("https://cdn.example.com/out.webp")),
+            "image/webp"
+        );
+        assert_eq!(
+            detect_image_mime(None, Some("https://cdn.example.com/v1/image")),
+            "image/png"
+        );
+        assert_eq!(detect_image_mime(Some("not base64!"), None), "image/png");
+    }
+
+    #[test]
+    fn sniff_base64_image_mime_recognises_each_signature() {
+        let png = STANDARD.encode([0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+        assert_eq!(sniff_base64_image_mime(&png), Some("image/png"));
+        assert_eq!(
+            sniff_base64_image_mime("/9j/4AAQSkZJRgABAQ"),
+            Some("image/jpeg")
+        );
+        assert_eq!(
+            sniff_base64_image_mime("R0lGODlhAQABAIAAAP"),
+            Some("image/gif")
+        );
+        assert_eq!(
+            sniff_base64_image_mime("UklGRiQAAABXRUJQVlA4"),
+            Some("image/webp")
+        );
+        assert_eq!(sniff_base64_image_mime("AAAA"), None);
+    }
+}
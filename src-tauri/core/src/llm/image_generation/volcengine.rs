@@ -1,15 +1,78 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
-use crate::llm::image_generation::types::{GeneratedImage, ImageGenerationRequest};
+use crate::llm::cancellation::cancellable;
+use crate::llm::error::LlmError;
+use crate::llm::http_client::resolve_timeout;
+use crate::llm::image_generation::batch::{
+    generate_batch, BatchImageResult, DEFAULT_MAX_IN_FLIGHT,
+};
+use crate::llm::image_generation::download::download_url_images;
+use crate::llm::image_generation::streaming::{
+    emit, progress_percent, ImageEventSender, ImageGenerationEvent,
+};
+use crate::llm::image_generation::types::{
+    detect_image_mime, normalize_revised_prompt, requested_size, sniff_image_mime,
+    AspectRatioSupport, GeneratedImage, ImageGenerationRequest,
+};
+use crate::llm::protocols::sse::{self, SseLineReader};
 use crate::llm::providers::provider::BaseProvider;
+use crate::llm::rate_limit::RateLimitInfo;
+use crate::llm::request_id::{RequestIdTracker, RequestIds};
+use crate::llm::request_log;
+use crate::llm::retry::RetryPolicy;
 use crate::llm::types::ProviderConfig;
+use futures_util::StreamExt;
+use reqwest::multipart::{Form, Part};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Mutex;
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 /// Minimum pixel count required by Volcengine Seedream model (3,686,400 pixels)
 /// This equals dimensions like 2560x1440, 1920x1920, etc.
 const MIN_PIXEL_COUNT: u32 = 3_686_400;
 
+/// Sizes accepted by Seedream 3.0 text-to-image models
+const SEEDREAM_3_SIZES: &[&str] = &[
+    "1024x1024",
+    "864x1152",
+    "1152x864",
+    "1280x720",
+    "720x1280",
+    "832x1248",
+    "1248x832",
+    "1512x648",
+];
+
+/// Recommended sizes for models without a fixed list, one per common aspect ratio
+/// Each meets `MIN_PIXEL_COUNT`, so an `aspect_ratio` maps straight to a valid size
+const ASPECT_RATIO_SIZES: &[&str] = &[
+    "2048x2048",
+    "2304x1728",
+    "1728x2304",
+    "2560x1440",
+    "1440x2560",
+    "2496x1664",
+    "1664x2496",
+    "3024x1296",
+];
+
+/// Most images one request may ask for, matched by model name fragment
+/// Models not listed return a single image per call
+const MAX_IMAGES_PER_REQUEST: &[(&str, u32)] = &[("seedream-4", 15)];
+
+/// How a request's `n` fits the model's per-request limit
+#[derive(Debug, PartialEq)]
+enum ImageCount {
+    /// Send as one request, with `n` clamped when it exceeded the limit
+    Single(Option<u32>),
+    /// The model returns one image per call, so send this many single-image requests
+    FanOut(u32),
+}
+
+/// Client timeout used when the request does not specify one
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
 /// Request format for Volcengine/ByteDance Seedream image generation
 /// Follows OpenAI-compatible format
 #[derive(Debug, Clone, Serialize)]
@@ -24,6 +87,88 @@ struct VolcengineImageRequest {
     n: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    negative_prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+}
+
+/// Request body, chosen by whether a source image was supplied
+#[derive(Debug)]
+enum VolcengineImageBody {
+    /// Text-to-image, sent as JSON to `/images/generations`
+    Generation(VolcengineImageRequest),
+    /// Image-to-image or inpainting, sent as multipart form data to `/images/edits`
+    Edit {
+        fields: VolcengineImageRequest,
+        image: Vec<u8>,
+        mask: Option<Vec<u8>>,
+    },
+}
+
+impl VolcengineImageBody {
+    fn endpoint(&self) -> &'static str {
+        match self {
+            Self::Generation(_) => "images/generations",
+            Self::Edit { .. } => "images/edits",
+        }
+    }
+}
+
+/// Set the request deadline, leaving none when `timeout` is `None`
+fn with_timeout(
+    request: reqwest::RequestBuilder,
+    timeout: Option<Duration>,
+) -> reqwest::RequestBuilder {
+    match timeout {
+        Some(timeout) => request.timeout(timeout),
+        None => request,
+    }
+}
+
+/// Build the multipart form for an edit request
+fn build_edit_form(
+    fields: VolcengineImageRequest,
+    image: Vec<u8>,
+    mask: Option<Vec<u8>>,
+) -> Result<Form, LlmError> {
+    let mut form = Form::new()
+        .text("model", fields.model)
+        .text("prompt", fields.prompt)
+        .part("image", image_part(image, "image")?);
+    if let Some(mask) = mask {
+        form = form.part("mask", image_part(mask, "mask")?);
+    }
+    if let Some(size) = fields.size {
+        form = form.text("size", size);
+    }
+    if let Some(quality) = fields.quality {
+        form = form.text("quality", quality);
+    }
+    if let Some(n) = fields.n {
+        form = form.text("n", n.to_string());
+    }
+    if let Some(response_format) = fields.response_format {
+        form = form.text("response_format", response_format);
+    }
+    if let Some(seed) = fields.seed {
+        form = form.text("seed", seed.to_string());
+    }
+    if let Some(negative_prompt) = fields.negative_prompt {
+        form = form.text("negative_prompt", negative_prompt);
+    }
+    Ok(form)
+}
+
+fn image_part(bytes: Vec<u8>, name: &str) -> Result<Part, LlmError> {
+    let mime = sniff_image_mime(&bytes).unwrap_or("image/png");
+    let extension = mime.trim_start_matches("image/");
+    Part::bytes(bytes)
+        .file_name(format!("{}.{}", name, extension))
+        .mime_str(mime)
+        .map_err(|e| LlmError::Other(format!("Invalid image mime type {}: {}", mime, e)))
 }
 
 /// Response format from Volcengine image generation API
@@ -41,13 +186,58 @@ struct VolcengineImageData {
     revised_prompt: Option<String>,
 }
 
+/// One `data:` event from a streamed generation
+#[derive(Debug, Clone, Deserialize)]
+struct VolcengineStreamEvent {
+    #[serde(rename = "type")]
+    event_type: String,
+    #[serde(default)]
+    image_index: Option<usize>,
+    #[serde(default)]
+    b64_json: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    error: Option<VolcengineStreamError>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct VolcengineStreamError {
+    message: String,
+}
+
 pub struct VolcengineImageClient {
     config: ProviderConfig,
+    /// Quotas from the most recent response that reported them
+    rate_limit: Mutex<Option<RateLimitInfo>>,
+    request_ids: RequestIdTracker,
 }
 
 impl VolcengineImageClient {
     pub fn new(config: ProviderConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            rate_limit: Mutex::new(None),
+            request_ids: RequestIdTracker::default(),
+        }
+    }
+
+    /// Client and provider IDs of the calls this client made
+    pub fn request_ids(&self) -> RequestIds {
+        self.request_ids.ids()
+    }
+
+    /// Rate-limit headers seen on the last response, if Volcengine sent any
+    pub fn last_rate_limit(&self) -> Option<RateLimitInfo> {
+        self.rate_limit.lock().ok().and_then(|info| info.clone())
+    }
+
+    fn record_rate_limit(&self, headers: &reqwest::header::HeaderMap) {
+        if let Some(info) = RateLimitInfo::from_headers(headers) {
+            if let Ok(mut slot) = self.rate_limit.lock() {
+                *slot = Some(info);
+            }
+        }
     }
 
     /// Validates and converts the requested size to a valid Volcengine size.
@@ -94,100 +284,436 @@ impl VolcengineImageClient {
         Some(best_match.to_string())
     }
 
+    /// Sizes the model is known to accept, or `None` when any size meeting the pixel minimum works
+    fn supported_sizes(model: &str) -> Option<&'static [&'static str]> {
+        if model.contains("seedream-3") {
+            Some(SEEDREAM_3_SIZES)
+        } else {
+            None
+        }
+    }
+
+    /// Sizes an `aspect_ratio` is matched against
+    fn aspect_ratio_sizes(model: &str) -> &'static [&'static str] {
+        Self::supported_sizes(model).unwrap_or(ASPECT_RATIO_SIZES)
+    }
+
+    fn max_images_per_request(model: &str) -> u32 {
+        MAX_IMAGES_PER_REQUEST
+            .iter()
+            .find(|(fragment, _)| model.contains(fragment))
+            .map_or(1, |(_, max)| *max)
+    }
+
+    /// Fit `n` to the model's limit, rejecting it instead when `strict` is set
+    fn fit_image_count(model: &str, n: Option<u32>, strict: bool) -> Result<ImageCount, LlmError> {
+        let max = Self::max_images_per_request(model);
+        let Some(requested) = n.filter(|requested| *requested > max) else {
+            return Ok(ImageCount::Single(n));
+        };
+        if strict {
+            return Err(LlmError::Other(format!(
+                "{} returns at most {} image(s) per request, got n = {} / {} 每次最多生成 {} 张图片，当前 n = {}",
+                model, max, requested, model, max, requested
+            )));
+        }
+        if max == 1 {
+            Ok(ImageCount::FanOut(requested))
+        } else {
+            log::warn!("Clamping n from {} to {} for {}", requested, max, model);
+            Ok(ImageCount::Single(Some(max)))
+        }
+    }
+
+    /// Resolve the size to send, rejecting sizes outside the model's allow-list
+    fn resolve_size(
+        &self,
+        model: &str,
+        requested_size: Option<String>,
+        skip_validation: bool,
+    ) -> Result<Option<String>, LlmError> {
+        let Some(sizes) = Self::supported_sizes(model) else {
+            // Convert size to meet Volcengine's minimum pixel requirement
+            return Ok(self.validate_and_convert_size(requested_size));
+        };
+        match requested_size {
+            Some(size) if !skip_validation && !sizes.contains(&size.as_str()) => {
+                Err(LlmError::Other(format!(
+                    "Size {} is not supported by {}. Supported sizes: {} / {} 不支持尺寸 {}，可用尺寸：{}",
+                    size,
+                    model,
+                    sizes.join(", "),
+                    model,
+                    size,
+                    sizes.join(", ")
+                )))
+            }
+            size => Ok(size),
+        }
+    }
+
+    /// Build the request body, switching to the edits endpoint when a source image is supplied
+    fn build_body(
+        &self,
+        model: &str,
+        request: ImageGenerationRequest,
+    ) -> Result<VolcengineImageBody, LlmError> {
+        let size = requested_size(
+            &request,
+            AspectRatioSupport::Sizes(Self::aspect_ratio_sizes(model)),
+        );
+        let validated_size = self.resolve_size(model, size, request.skip_size_validation)?;
+
+        let fields = VolcengineImageRequest {
+            model: model.to_string(),
+            prompt: request.prompt,
+            size: validated_size,
+            quality: request.quality,
+            n: request.n,
+            response_format: request.response_format,
+            seed: request.seed,
+            negative_prompt: request.negative_prompt,
+            stream: None,
+        };
+
+        match (request.image, request.mask) {
+            (Some(image), mask) => Ok(VolcengineImageBody::Edit {
+                fields,
+                image,
+                mask,
+            }),
+            (None, Some(_)) => Err(LlmError::Other(
+                "A mask requires a source image for Volcengine image editing / Volcengine 图片编辑的蒙版需要同时提供原图"
+                    .to_string(),
+            )),
+            (None, None) => Ok(VolcengineImageBody::Generation(fields)),
+        }
+    }
+
+    /// Generate images, clamping `n` to the model's limit or fanning it out into single-image
+    /// requests when the model returns one image per call
+    /// With `download_urls` set, URL-only results are fetched and returned as base64 too
     pub async fn generate(
         &self,
         api_keys: &ApiKeyManager,
         model: &str,
         request: ImageGenerationRequest,
-    ) -> Result<Vec<GeneratedImage>, String> {
-        let credentials = api_keys.get_credentials(&self.config).await?;
+    ) -> Result<Vec<GeneratedImage>, LlmError> {
+        self.generate_partial(api_keys, model, request, None)
+            .await
+            .map(|result| result.images)
+    }
+
+    /// Like `generate`, keeping the failed requests of a fanned-out batch alongside its images
+    /// and aborting every request once `cancel` fires; fails only when no request succeeded
+    pub async fn generate_partial(
+        &self,
+        api_keys: &ApiKeyManager,
+        model: &str,
+        mut request: ImageGenerationRequest,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<BatchImageResult, LlmError> {
+        match Self::fit_image_count(model, request.n, request.strict_n)? {
+            ImageCount::Single(n) => {
+                request.n = n;
+                let images = self
+                    .generate_with_cancel(api_keys, model, request, cancel)
+                    .await?;
+                Ok(BatchImageResult {
+                    images,
+                    errors: Vec::new(),
+                })
+            }
+            ImageCount::FanOut(_) => self
+                .generate_batch(api_keys, model, request, DEFAULT_MAX_IN_FLIGHT, cancel)
+                .await
+                .or_first_error()
+                .map_err(LlmError::Other),
+        }
+    }
+
+    /// Generate `request.n` images as single-image requests, at most `max_in_flight` at a time
+    pub async fn generate_batch(
+        &self,
+        api_keys: &ApiKeyManager,
+        model: &str,
+        request: ImageGenerationRequest,
+        max_in_flight: usize,
+        cancel: Option<&CancellationToken>,
+    ) -> BatchImageResult {
+        let count = request.n.unwrap_or(1).max(1) as usize;
+        let request = &request;
+        generate_batch(count, max_in_flight, move |_| {
+            let mut single = request.clone();
+            single.n = Some(1);
+            self.generate_with_cancel(api_keys, model, single, cancel)
+        })
+        .await
+    }
+
+    /// Same as `generate`, aborting the HTTP request with `LlmError::Cancelled` once `cancel` fires
+    pub async fn generate_with_cancel(
+        &self,
+        api_keys: &ApiKeyManager,
+        model: &str,
+        request: ImageGenerationRequest,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<Vec<GeneratedImage>, LlmError> {
+        let timeout = resolve_timeout(request.timeout_ms, DEFAULT_TIMEOUT);
+        let download_urls = request.download_urls;
+        let body = self.build_body(model, request)?;
+        let response = self.send(api_keys, body, timeout, cancel).await?;
+
+        let payload = cancellable(cancel, response.json::<VolcengineImageResponse>())
+            .await?
+            .map_err(|e| {
+                LlmError::InvalidResponse(format!("Failed to parse Volcengine response: {}", e))
+            })?;
+
+        let mut images: Vec<GeneratedImage> = payload
+            .data
+            .into_iter()
+            .map(|item| GeneratedImage {
+                mime_type: detect_image_mime(item.b64_json.as_deref(), item.url.as_deref())
+                    .to_string(),
+                b64_json: item.b64_json,
+                url: item.url,
+                revised_prompt: normalize_revised_prompt(item.revised_prompt),
+                expires_at: None,
+            })
+            .collect();
+
+        if download_urls {
+            download_url_images(&mut images, timeout, cancel).await?;
+        }
+        Ok(images)
+    }
+
+    /// Seedream 4.0 can stream each image as it finishes
+    fn supports_streaming(model: &str) -> bool {
+        model.contains("seedream-4")
+    }
+
+    /// Like `generate`, reporting progress on `events` and ending with a `Complete` event
+    /// Models or edit requests without streaming support fall back to `generate`
+    pub async fn generate_streaming(
+        &self,
+        api_keys: &ApiKeyManager,
+        model: &str,
+        mut request: ImageGenerationRequest,
+        events: &ImageEventSender,
+    ) -> Result<Vec<GeneratedImage>, LlmError> {
+        if !Self::supports_streaming(model) || request.image.is_some() {
+            let images = self.generate(api_keys, model, request).await?;
+            emit(
+                events,
+                ImageGenerationEvent::Complete {
+                    images: images.clone(),
+                },
+            );
+            return Ok(images);
+        }
+
+        // Streaming models take several images per request, so `n` only ever needs clamping
+        if let ImageCount::Single(n) = Self::fit_image_count(model, request.n, request.strict_n)? {
+            request.n = n;
+        }
+        let total = request.n.unwrap_or(1).max(1) as usize;
+        let timeout = resolve_timeout(request.timeout_ms, DEFAULT_TIMEOUT);
+        let mut body = self.build_body(model, request)?;
+        if let VolcengineImageBody::Generation(fields) = &mut body {
+            fields.stream = Some(true);
+        }
+        let response = self.send(api_keys, body, timeout, None).await?;
+
+        let mut images = Vec::new();
+        let mut failures = Vec::new();
+        let mut reader = SseLineReader::new();
+        let mut chunks = response.bytes_stream();
+        'stream: while let Some(chunk) = chunks.next().await {
+            reader.feed(&chunk?);
+            for record in reader.by_ref() {
+                let data = record.map_err(LlmError::InvalidResponse)?.data;
+                if sse::is_done(&data) {
+                    break 'stream;
+                }
+                let event: VolcengineStreamEvent = serde_json::from_str(&data).map_err(|e| {
+                    LlmError::InvalidResponse(format!(
+                        "Failed to parse Volcengine stream event: {}",
+                        e
+                    ))
+                })?;
+                match event.event_type.as_str() {
+                    "image_generation.partial_succeeded" => {
+                        let mime_type =
+                            detect_image_mime(event.b64_json.as_deref(), event.url.as_deref());
+                        let image = GeneratedImage {
+                            b64_json: event.b64_json,
+                            url: event.url,
+                            mime_type: mime_type.to_string(),
+                            revised_prompt: None,
+                            expires_at: None,
+                        };
+                        emit(
+                            events,
+                            ImageGenerationEvent::PartialImage {
+                                index: event.image_index.unwrap_or(images.len()),
+                                image: image.clone(),
+                            },
+                        );
+                        images.push(image);
+                    }
+                    "image_generation.partial_failed" => {
+                        let message = event
+                            .error
+                            .map(|error| error.message)
+                            .unwrap_or_else(|| "Unknown error".to_string());
+                        log::warn!("[Volcengine] Streamed image failed: {}", message);
+                        failures.push(message);
+                    }
+                    "image_generation.completed" => break 'stream,
+                    _ => continue,
+                }
+                emit(
+                    events,
+                    ImageGenerationEvent::Progress {
+                        percent: progress_percent(images.len() + failures.len(), total),
+                    },
+                );
+            }
+        }
+
+        if images.is_empty() {
+            let reason = failures
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| "no images returned".to_string());
+            return Err(LlmError::InvalidResponse(format!(
+                "Volcengine image generation failed: {} / Volcengine 图片生成失败",
+                reason
+            )));
+        }
+        emit(
+            events,
+            ImageGenerationEvent::Complete {
+                images: images.clone(),
+            },
+        );
+        Ok(images)
+    }
+
+    /// Send a request body and return the successful response
+    async fn send(
+        &self,
+        api_keys: &ApiKeyManager,
+        body: VolcengineImageBody,
+        timeout: Option<Duration>,
+        cancel: Option<&CancellationToken>,
+    ) -> Result<reqwest::Response, LlmError> {
+        let credentials = api_keys.get_credentials_rotating(&self.config).await?;
         let api_key = match credentials {
             crate::llm::auth::api_key_manager::ProviderCredentials::Token(token) => token,
             crate::llm::auth::api_key_manager::ProviderCredentials::None => {
-                return Err(
+                return Err(LlmError::Auth(
                     "API key not configured for Volcengine image generation / Volcengine 图片生成未配置 API 密钥"
                         .to_string(),
-                )
+                ))
             }
         };
 
         let base = BaseProvider::new(self.config.clone());
         let base_url = base.resolve_base_url_with_fallback(api_keys).await?;
-        let url = format!("{}/images/generations", base_url.trim_end_matches('/'));
-
-        // Validate and convert size to meet Volcengine's minimum pixel requirement
-        let validated_size = self.validate_and_convert_size(request.size);
-
-        let body = VolcengineImageRequest {
-            model: model.to_string(),
-            prompt: request.prompt,
-            size: validated_size,
-            quality: request.quality,
-            n: request.n,
-            response_format: request.response_format,
-        };
+        let url = format!("{}/{}", base_url.trim_end_matches('/'), body.endpoint());
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client().map_err(LlmError::Other)?;
 
         let mut headers = HashMap::new();
-        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        if matches!(body, VolcengineImageBody::Generation(_)) {
+            headers.insert("Content-Type".to_string(), "application/json".to_string());
+        }
         headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
+        // Edit requests are multipart, so only their text fields are logged
+        let log_body = match &body {
+            VolcengineImageBody::Generation(fields) | VolcengineImageBody::Edit { fields, .. } => {
+                fields
+            }
+        };
+        request_log::log_request(
+            "VolcengineImageClient",
+            "POST",
+            &url,
+            &headers,
+            Some(log_body),
+        );
 
         let mut header_map = reqwest::header::HeaderMap::new();
         for (key, value) in headers {
             let header_name = reqwest::header::HeaderName::from_bytes(key.as_bytes())
-                .map_err(|e| format!("Invalid header name {}: {}", key, e))?;
+                .map_err(|e| LlmError::Other(format!("Invalid header name {}: {}", key, e)))?;
             let header_value = reqwest::header::HeaderValue::from_str(&value)
-                .map_err(|e| format!("Invalid header value for {}: {}", key, e))?;
+                .map_err(|e| LlmError::Other(format!("Invalid header value for {}: {}", key, e)))?;
             header_map.insert(header_name, header_value);
         }
 
-        let response = client
-            .post(&url)
-            .headers(header_map)
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| format!("Volcengine image request failed: {}", e))?;
+        let request = self.request_ids.tag(client.post(&url)).headers(header_map);
+        let request = with_timeout(request, timeout);
+        // Multipart bodies cannot be cloned, so edit requests are sent without retries
+        let request = match body {
+            VolcengineImageBody::Generation(fields) => request.json(&fields),
+            VolcengineImageBody::Edit {
+                fields,
+                image,
+                mask,
+            } => request.multipart(build_edit_form(fields, image, mask)?),
+        };
+        let response = cancellable(
+            cancel,
+            RetryPolicy::for_provider(&self.config).send(request),
+        )
+        .await?
+        .map_err(|e| match e {
+            LlmError::Network(msg) => {
+                LlmError::Network(format!("Volcengine image request failed: {}", msg))
+            }
+            other => other,
+        })?;
+        self.record_rate_limit(response.headers());
+        self.request_ids.record(response.headers());
 
         if !response.status().is_success() {
             let status = response.status();
+            if status.as_u16() == 429 {
+                api_keys.mark_key_rate_limited(&api_key);
+            }
+            let response_headers = response.headers().clone();
             let body = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(format!(
-                "Volcengine image generation failed ({}): {} / Volcengine 图片生成失败",
-                status, body
+            request_log::log_response("VolcengineImageClient", &url, status.as_u16(), Some(&body));
+            // Keep the raw body so callers can read the provider's error code and message
+            return Err(LlmError::from_response_parts(
+                status.as_u16(),
+                &response_headers,
+                body,
             ));
         }
 
-        let payload = response
-            .json::<VolcengineImageResponse>()
-            .await
-            .map_err(|e| format!("Failed to parse Volcengine response: {}", e))?;
-
-        let images = payload
-            .data
-            .into_iter()
-            .map(|item| GeneratedImage {
-                b64_json: item.b64_json,
-                url: item.url,
-                mime_type: "image/png".to_string(),
-                revised_prompt: item.revised_prompt,
-            })
-            .collect();
-
-        Ok(images)
+        request_log::log_response(
+            "VolcengineImageClient",
+            &url,
+            response.status().as_u16(),
+            None,
+        );
+        Ok(response)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::types::ProtocolType;
 
     #[test]
     fn parses_volcengine_image_response_with_b64() {
@@ -214,41 +740,21 @@ mod tests {
 
     #[test]
     fn volcengine_image_client_constructs() {
-        let config = ProviderConfig {
-            id: "volcengine".to_string(),
-            name: "Volcengine".to_string(),
-            protocol: crate::llm::types::ProtocolType::OpenAiCompatible,
-            base_url: "https://ark.cn-beijing.volces.com/api/v3".to_string(),
-            api_key_name: "VOLCENGINE_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
-        };
+        let config = provider_config(
+            "volcengine",
+            ProtocolType::OpenAiCompatible,
+            "https://ark.cn-beijing.volces.com/api/v3",
+        );
         let _client = VolcengineImageClient::new(config);
     }
 
     #[test]
     fn test_validate_size_meets_minimum() {
-        let config = ProviderConfig {
-            id: "volcengine".to_string(),
-            name: "Volcengine".to_string(),
-            protocol: crate::llm::types::ProtocolType::OpenAiCompatible,
-            base_url: "https://ark.cn-beijing.volces.com/api/v3".to_string(),
-            api_key_name: "VOLCENGINE_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
-        };
+        let config = provider_config(
+            "volcengine",
+            ProtocolType::OpenAiCompatible,
+            "https://ark.cn-beijing.volces.com/api/v3",
+        );
         let client = VolcengineImageClient::new(config);
 
         // 2560x1440 (3,686,400 pixels) - exactly at minimum
@@ -262,21 +768,11 @@ mod tests {
 
     #[test]
     fn test_validate_size_converts_small_sizes() {
-        let config = ProviderConfig {
-            id: "volcengine".to_string(),
-            name: "Volcengine".to_string(),
-            protocol: crate::llm::types::ProtocolType::OpenAiCompatible,
-            base_url: "https://ark.cn-beijing.volces.com/api/v3".to_string(),
-            api_key_name: "VOLCENGINE_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
-        };
+        let config = provider_config(
+            "volcengine",
+            ProtocolType::OpenAiCompatible,
+            "https://ark.cn-beijing.volces.com/api/v3",
+        );
         let client = VolcengineImageClient::new(config);
 
         // 1024x1024 (1,048,576 pixels) - too small, should convert to square
@@ -294,21 +790,11 @@ mod tests {
 
     #[test]
     fn test_validate_size_handles_invalid_input() {
-        let config = ProviderConfig {
-            id: "volcengine".to_string(),
-            name: "Volcengine".to_string(),
-            protocol: crate::llm::types::ProtocolType::OpenAiCompatible,
-            base_url: "https://ark.cn-beijing.volces.com/api/v3".to_string(),
-            api_key_name: "VOLCENGINE_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
-        };
+        let config = provider_config(
+            "volcengine",
+            ProtocolType::OpenAiCompatible,
+            "https://ark.cn-beijing.volces.com/api/v3",
+        );
         let client = VolcengineImageClient::new(config);
 
         // Invalid format like "2K"
@@ -326,21 +812,11 @@ mod tests {
 
     #[test]
     fn test_validate_size_preserves_aspect_ratio() {
-        let config = ProviderConfig {
-            id: "volcengine".to_string(),
-            name: "Volcengine".to_string(),
-            protocol: crate::llm::types::ProtocolType::OpenAiCompatible,
-            base_url: "https://ark.cn-beijing.volces.com/api/v3".to_string(),
-            api_key_name: "VOLCENGINE_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
-        };
+        let config = provider_config(
+            "volcengine",
+            ProtocolType::OpenAiCompatible,
+            "https://ark.cn-beijing.volces.com/api/v3",
+        );
         let client = VolcengineImageClient::new(config);
 
         // Wide landscape (21:9 approx)
@@ -355,4 +831,633 @@ mod tests {
         let result = client.validate_and_convert_size(Some("1024x1024".to_string()));
         assert_eq!(result, Some("1920x1920".to_string()));
     }
+
+    #[tokio::test]
+    async fn generate_retries_rate_limited_requests() {
+        use crate::llm::retry::RetryPolicy;
+        use crate::llm::testing::mock_server::start_sequence_server;
+        use std::sync::atomic::Ordering;
+
+        let success = r#"{"data":[{"url":"https://example.com/image.png"}]}"#.to_string();
+        let (base_url, hits) = start_sequence_server(vec![
+            (429, "busy".to_string()),
+            (429, "busy".to_string()),
+            (200, success),
+        ])
+        .expect("start mock server");
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+
+        let config = ProviderConfig {
+            retry_policy: Some(RetryPolicy {
+                max_retries: 3,
+                base_delay_ms: 1,
+                max_delay_ms: 10,
+            }),
+            ..provider_config("volcengine", ProtocolType::OpenAiCompatible, &base_url)
+        };
+        let client = VolcengineImageClient::new(config);
+        let request = ImageGenerationRequest {
+            model: "seedream".to_string(),
+            prompt: "a lighthouse".to_string(),
+            size: None,
+            aspect_ratio: None,
+            quality: None,
+            n: None,
+            response_format: None,
+            provider_options: None,
+            request_id: None,
+            image: None,
+            mask: None,
+            timeout_ms: None,
+            seed: None,
+            negative_prompt: None,
+            skip_size_validation: false,
+            strict_n: false,
+            download_urls: false,
+            user_id: None,
+            template: None,
+        };
+
+        let images = client
+            .generate(&api_keys, "seedream", request)
+            .await
+            .expect("generate after retries");
+
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+        assert_eq!(images.len(), 1);
+        assert_eq!(
+            images[0].url.as_deref(),
+            Some("https://example.com/image.png")
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_batch_returns_partial_results_when_one_request_fails() {
+        use crate::llm::testing::mock_server::start_sequence_server;
+
+        let ok = |url: &str| format!(r#"{{"data":[{{"url":"{}"}}]}}"#, url);
+        let (base_url, hits) = start_sequence_server(vec![
+            (200, ok("https://example.com/1.png")),
+            (
+                400,
+                r#"{"error":{"message":"prompt rejected"}}"#.to_string(),
+            ),
+            (200, ok("https://example.com/3.png")),
+        ])
+        .expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+
+        let mut client = test_client();
+        client.config.base_url = base_url;
+        let mut request = edit_request(None, None);
+        request.n = Some(3);
+
+        // One request in flight keeps the mock responses in request order
+        let result = client
+            .generate_batch(&api_keys, "seedream", request, 1, None)
+            .await;
+
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 3);
+        let urls: Vec<_> = result.images.iter().filter_map(|i| i.url.clone()).collect();
+        assert_eq!(
+            urls,
+            vec!["https://example.com/1.png", "https://example.com/3.png"]
+        );
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].index, 1);
+        assert!(result.errors[0].message.contains("prompt rejected"));
+    }
+
+    #[tokio::test]
+    async fn generate_exposes_status_and_provider_message_on_failure() {
+        use crate::llm::testing::mock_server::start_sequence_server;
+
+        let body = r#"{"error":{"code":"OutputImageSensitiveContentDetected","message":"The generated image may contain sensitive content."}}"#;
+        let (base_url, _hits) =
+            start_sequence_server(vec![(400, body.to_string())]).expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+
+        let mut client = test_client();
+        client.config.base_url = base_url;
+
+        let err = client
+            .generate(&api_keys, "seedream", edit_request(None, None))
+            .await
+            .expect_err("rejected prompt");
+
+        assert_eq!(err.status(), Some(400));
+        assert_eq!(err.body(), Some(body));
+        assert_eq!(
+            err.provider_message(),
+            Some("The generated image may contain sensitive content.")
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_records_rate_limit_headers() {
+        use crate::llm::testing::mock_server::start_header_server;
+
+        let base_url = start_header_server(
+            200,
+            r#"{"data":[{"url":"https://example.com/a.png"}]}"#.to_string(),
+            vec![
+                ("x-ratelimit-limit-requests", "500"),
+                ("x-ratelimit-remaining-requests", "7"),
+                ("x-ratelimit-reset-requests", "30s"),
+            ],
+        )
+        .expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+
+        let mut client = test_client();
+        client.config.base_url = base_url;
+        assert_eq!(client.last_rate_limit(), None);
+
+        client
+            .generate(&api_keys, "seedream", edit_request(None, None))
+            .await
+            .expect("generate");
+
+        let info = client.last_rate_limit().expect("rate limit info");
+        assert_eq!(info.limit_requests, Some(500));
+        assert_eq!(info.remaining_requests, Some(7));
+        assert_eq!(info.reset_requests.as_deref(), Some("30s"));
+        assert_eq!(info.remaining_tokens, None);
+    }
+
+    #[test]
+    fn fit_image_count_clamps_or_fans_out_by_model() {
+        let fit = |model, n| VolcengineImageClient::fit_image_count(model, n, false).unwrap();
+
+        assert_eq!(
+            fit("doubao-seedream-4-0-250828", Some(20)),
+            ImageCount::Single(Some(15))
+        );
+        assert_eq!(
+            fit("doubao-seedream-4-0-250828", Some(4)),
+            ImageCount::Single(Some(4))
+        );
+        assert_eq!(
+            fit("doubao-seedream-3-0-t2i-250415", Some(3)),
+            ImageCount::FanOut(3)
+        );
+        assert_eq!(
+            fit("doubao-seedream-3-0-t2i-250415", None),
+            ImageCount::Single(None)
+        );
+    }
+
+    #[test]
+    fn fit_image_count_rejects_excess_n_when_strict() {
+        let err =
+            VolcengineImageClient::fit_image_count("doubao-seedream-4-0-250828", Some(20), true)
+                .expect_err("n above limit");
+
+        assert!(err.to_string().contains("at most 15 image(s) per request"));
+        assert!(VolcengineImageClient::fit_image_count("seedream", Some(1), true).is_ok());
+    }
+
+    #[tokio::test]
+    async fn generate_fans_out_for_single_image_models() {
+        use crate::llm::testing::mock_server::start_sequence_server;
+
+        let ok = |url: &str| format!(r#"{{"data":[{{"url":"{}"}}]}}"#, url);
+        let (base_url, hits) = start_sequence_server(vec![
+            (200, ok("https://example.com/1.png")),
+            (200, ok("https://example.com/2.png")),
+        ])
+        .expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+
+        let mut client = test_client();
+        client.config.base_url = base_url;
+        let mut request = edit_request(None, None);
+        request.n = Some(2);
+
+        let images = client
+            .generate(&api_keys, "seedream", request)
+            .await
+            .expect("images");
+
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(images.len(), 2);
+    }
+
+    fn edit_request(image: Option<Vec<u8>>, mask: Option<Vec<u8>>) -> ImageGenerationRequest {
+        ImageGenerationRequest {
+            model: "seedream".to_string(),
+            prompt: "make it night".to_string(),
+            size: Some("2560x1440".to_string()),
+            aspect_ratio: None,
+            quality: None,
+            n: Some(1),
+            response_format: Some("url".to_string()),
+            provider_options: None,
+            request_id: None,
+            image,
+            mask,
+            timeout_ms: None,
+            seed: None,
+            negative_prompt: None,
+            skip_size_validation: false,
+            strict_n: false,
+            download_urls: false,
+            user_id: None,
+            template: None,
+        }
+    }
+
+    fn test_client() -> VolcengineImageClient {
+        VolcengineImageClient::new(provider_config(
+            "volcengine",
+            ProtocolType::OpenAiCompatible,
+            "https://ark.cn-beijing.volces.com/api/v3",
+        ))
+    }
+
+    #[test]
+    fn text_only_request_uses_generations_json_body() {
+        let body = test_client()
+            .build_body("seedream", edit_request(None, None))
+            .expect("build body");
+
+        assert_eq!(body.endpoint(), "images/generations");
+        let VolcengineImageBody::Generation(fields) = body else {
+            panic!("expected a generation body");
+        };
+        let json = serde_json::to_value(&fields).expect("serialize body");
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "model": "seedream",
+                "prompt": "make it night",
+                "size": "2560x1440",
+                "n": 1,
+                "response_format": "url"
+            })
+        );
+    }
+
+    #[test]
+    fn seed_and_negative_prompt_are_serialized_only_when_set() {
+        let mut request = edit_request(None, None);
+        request.seed = Some(42);
+        request.negative_prompt = Some("blurry, text".to_string());
+
+        let VolcengineImageBody::Generation(fields) = test_client()
+            .build_body("seedream", request)
+            .expect("build body")
+        else {
+            panic!("expected a generation body");
+        };
+        let json = serde_json::to_value(&fields).expect("serialize body");
+        assert_eq!(json["seed"], 42);
+        assert_eq!(json["negative_prompt"], "blurry, text");
+
+        let VolcengineImageBody::Generation(fields) = test_client()
+            .build_body("seedream", edit_request(None, None))
+            .expect("build body")
+        else {
+            panic!("expected a generation body");
+        };
+        let json = serde_json::to_value(&fields).expect("serialize body");
+        assert!(json.get("seed").is_none());
+        assert!(json.get("negative_prompt").is_none());
+    }
+
+    #[test]
+    fn source_image_switches_to_edits_multipart_body() {
+        let png = vec![0x89, b'P', b'N', b'G'];
+        let mask = vec![0xFF, 0xD8, 0xFF, 0xE0];
+        let body = test_client()
+            .build_body(
+                "seedream",
+                edit_request(Some(png.clone()), Some(mask.clone())),
+            )
+            .expect("build body");
+
+        assert_eq!(body.endpoint(), "images/edits");
+        match body {
+            VolcengineImageBody::Edit {
+                fields,
+                image,
+                mask: edit_mask,
+            } => {
+                assert_eq!(fields.prompt, "make it night");
+                assert_eq!(image, png);
+                assert_eq!(edit_mask, Some(mask.clone()));
+                assert!(build_edit_form(fields, image, edit_mask).is_ok());
+            }
+            VolcengineImageBody::Generation(_) => panic!("expected an edit body"),
+        }
+        assert_eq!(sniff_image_mime(&mask), Some("image/jpeg"));
+    }
+
+    #[test]
+    fn mask_without_source_image_is_rejected() {
+        let result = test_client().build_body("seedream", edit_request(None, Some(vec![1, 2, 3])));
+        assert!(matches!(result, Err(LlmError::Other(_))));
+    }
+
+    #[test]
+    fn seedream_3_accepts_listed_size() {
+        let mut request = edit_request(None, None);
+        request.size = Some("1152x864".to_string());
+
+        let body = test_client()
+            .build_body("doubao-seedream-3-0-t2i-250415", request)
+            .expect("build body");
+
+        let VolcengineImageBody::Generation(fields) = body else {
+            panic!("expected a generation body");
+        };
+        // Listed sizes are sent unchanged rather than scaled up to the pixel minimum
+        assert_eq!(fields.size.as_deref(), Some("1152x864"));
+    }
+
+    #[test]
+    fn aspect_ratio_maps_to_nearest_supported_size() {
+        let client = test_client();
+        let cases = [
+            ("seedream", "1:1", "2048x2048"),
+            ("seedream", "16:9", "2560x1440"),
+            ("seedream", "9:16", "1440x2560"),
+            ("seedream", "4:3", "2304x1728"),
+            ("seedream", "2:3", "1664x2496"),
+            ("seedream", "21:9", "3024x1296"),
+            ("doubao-seedream-3-0-t2i-250415", "16:9", "1280x720"),
+            ("doubao-seedream-3-0-t2i-250415", "3:4", "864x1152"),
+        ];
+
+        for (model, ratio, expected) in cases {
+            let mut request = edit_request(None, None);
+            request.size = None;
+            request.aspect_ratio = Some(ratio.to_string());
+
+            let body = client.build_body(model, request).expect("build body");
+
+            let VolcengineImageBody::Generation(fields) = body else {
+                panic!("expected a generation body");
+            };
+            assert_eq!(
+                fields.size.as_deref(),
+                Some(expected),
+                "{} {}",
+                model,
+                ratio
+            );
+        }
+    }
+
+    #[test]
+    fn seedream_3_rejects_unlisted_size_unless_skipped() {
+        let client = test_client();
+        let model = "doubao-seedream-3-0-t2i-250415";
+        let mut request = edit_request(None, None);
+        request.size = Some("1000x1000".to_string());
+
+        let err = client
+            .build_body(model, request.clone())
+            .expect_err("unsupported size");
+        let message = err.to_string();
+        assert!(message.contains("1000x1000"));
+        assert!(message.contains("1024x1024, 864x1152"));
+        assert!(message.contains("不支持尺寸"));
+
+        request.skip_size_validation = true;
+        let body = client
+            .build_body(model, request)
+            .expect("validation skipped");
+        let VolcengineImageBody::Generation(fields) = body else {
+            panic!("expected a generation body");
+        };
+        assert_eq!(fields.size.as_deref(), Some("1000x1000"));
+    }
+
+    #[tokio::test]
+    async fn requested_timeout_applies_to_shared_client() {
+        // Connections land in the listener backlog but never get a response
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind listener");
+        let addr = listener.local_addr().expect("listener address");
+
+        let client = crate::llm::http_client::shared_client().expect("client");
+        let started = std::time::Instant::now();
+        let request = client.get(format!("http://{}", addr));
+        let err = with_timeout(request, resolve_timeout(Some(100), DEFAULT_TIMEOUT))
+            .send()
+            .await
+            .expect_err("request should time out");
+
+        assert!(err.is_timeout());
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn generate_partial_cancels_every_request_of_a_batch() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind listener");
+        let addr = listener.local_addr().expect("listener address");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+
+        let mut client = test_client();
+        client.config.base_url = format!("http://{}", addr);
+        let mut request = edit_request(None, None);
+        request.n = Some(3);
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            canceller.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let err = client
+            .generate_partial(&api_keys, "seedream", request, Some(&token))
+            .await
+            .expect_err("cancelled batch");
+
+        assert!(err.to_string().contains("cancelled"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn generate_with_cancel_aborts_pending_request() {
+        // Connections land in the listener backlog but never get a response
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind listener");
+        let addr = listener.local_addr().expect("listener address");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+
+        let mut client = test_client();
+        client.config.base_url = format!("http://{}", addr);
+        let token = CancellationToken::new();
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            canceller.cancel();
+        });
+
+        let started = std::time::Instant::now();
+        let result = client
+            .generate_with_cancel(
+                &api_keys,
+                "seedream",
+                edit_request(None, None),
+                Some(&token),
+            )
+            .await;
+
+        assert!(matches!(result, Err(LlmError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn generate_downloads_url_results_only_when_asked() {
+        use crate::llm::testing::mock_server::{start_capture_server, start_header_server};
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+        let image_url = start_header_server(
+            200,
+            "webp bytes".to_string(),
+            vec![("Content-Type", "image/webp")],
+        )
+        .expect("start image server");
+        let body = format!(r#"{{"data":[{{"url":"{}/0.webp"}}]}}"#, image_url);
+
+        let (base_url, _) =
+            start_capture_server(200, body.clone().into_bytes()).expect("start mock server");
+        let mut client = test_client();
+        client.config.base_url = base_url;
+        let images = client
+            .generate(&api_keys, "seedream", edit_request(None, None))
+            .await
+            .expect("generate");
+        assert!(images[0].b64_json.is_none());
+
+        let (base_url, _) =
+            start_capture_server(200, body.into_bytes()).expect("start mock server");
+        client.config.base_url = base_url;
+        let mut request = edit_request(None, None);
+        request.download_urls = true;
+        let images = client
+            .generate(&api_keys, "seedream", request)
+            .await
+            .expect("generate");
+
+        assert_eq!(
+            images[0].b64_json.as_deref(),
+            Some(STANDARD.encode("webp bytes").as_str())
+        );
+        assert_eq!(images[0].mime_type, "image/webp");
+        assert_eq!(
+            images[0].url.as_deref(),
+            Some(format!("{}/0.webp", image_url).as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_reports_the_format_the_provider_returned() {
+        use crate::llm::testing::mock_server::start_capture_server;
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+        let jpeg = STANDARD.encode([
+            0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F', 0, 1,
+        ]);
+        let body = format!(
+            r#"{{"data":[{{"b64_json":"{}"}},{{"url":"https://cdn.example.com/1.webp?x=1"}}]}}"#,
+            jpeg
+        );
+        let (base_url, _) =
+            start_capture_server(200, body.into_bytes()).expect("start mock server");
+        let mut client = test_client();
+        client.config.base_url = base_url;
+
+        let images = client
+            .generate(&api_keys, "seedream", edit_request(None, None))
+            .await
+            .expect("generate");
+
+        assert_eq!(images[0].mime_type, "image/jpeg");
+        assert_eq!(images[1].mime_type, "image/webp");
+    }
+
+    #[tokio::test]
+    async fn generate_streaming_reports_each_image_then_completes() {
+        use crate::llm::testing::mock_server::start_capture_server;
+
+        let events = [
+            r#"{"type":"image_generation.partial_succeeded","image_index":0,"url":"https://example.com/0.png"}"#,
+            r#"{"type":"image_generation.partial_succeeded","image_index":1,"url":"https://example.com/1.png"}"#,
+            r#"{"type":"image_generation.completed","usage":{"generated_images":2}}"#,
+            "[DONE]",
+        ];
+        let body: String = events
+            .iter()
+            .map(|event| format!("data: {}\n\n", event))
+            .collect();
+        let (base_url, captured) =
+            start_capture_server(200, body.into_bytes()).expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+
+        let mut client = test_client();
+        client.config.base_url = base_url;
+        let mut request = edit_request(None, None);
+        request.n = Some(2);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let images = client
+            .generate_streaming(&api_keys, "doubao-seedream-4-0-250828", request, &tx)
+            .await
+            .expect("stream images");
+        drop(tx);
+
+        assert_eq!(images.len(), 2);
+        let mut received = Vec::new();
+        while let Some(event) = rx.recv().await {
+            received.push(event);
+        }
+        assert!(matches!(
+            &received[..],
+            [
+                ImageGenerationEvent::PartialImage { index: 0, .. },
+                ImageGenerationEvent::Progress { percent: 50 },
+                ImageGenerationEvent::PartialImage { index: 1, .. },
+                ImageGenerationEvent::Progress { percent: 100 },
+                ImageGenerationEvent::Complete { images },
+            ] if images.len() == 2
+        ));
+        let request = captured.recv().expect("captured request");
+        let sent: serde_json::Value = serde_json::from_slice(&request.body).expect("json body");
+        assert_eq!(sent["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn generate_streaming_falls_back_to_blocking_request() {
+        use crate::llm::testing::mock_server::start_capture_server;
+
+        let body = br#"{"data":[{"url":"https://example.com/image.png"}]}"#.to_vec();
+        let (base_url, captured) = start_capture_server(200, body).expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_volcengine", "test-key")]).await;
+
+        let mut client = test_client();
+        client.config.base_url = base_url;
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let images = client
+            .generate_streaming(&api_keys, "seedream", edit_request(None, None), &tx)
+            .await
+            .expect("generate");
+        drop(tx);
+
+        assert_eq!(images.len(), 1);
+        assert!(matches!(
+            rx.recv().await,
+            Some(ImageGenerationEvent::Complete { images }) if images.len() == 1
+        ));
+        assert!(rx.recv().await.is_none());
+        let request = captured.recv().expect("captured request");
+        let sent: serde_json::Value = serde_json::from_slice(&request.body).expect("json body");
+        assert!(sent.get("stream").is_none());
+    }
 }
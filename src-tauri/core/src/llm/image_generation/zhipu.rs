@@ -1,6 +1,11 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
-use crate::llm::image_generation::types::{GeneratedImage, ImageGenerationRequest};
+use crate::llm::http_client::SendWithMiddleware;
+use crate::llm::image_generation::types::{
+    normalize_revised_prompt, GeneratedImage, ImageGenerationRequest,
+};
 use crate::llm::providers::provider::BaseProvider;
+use crate::llm::request_id::{RequestIdTracker, RequestIds};
+use crate::llm::request_log;
 use crate::llm::types::ProviderConfig;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -39,11 +44,20 @@ struct ZhipuImageData {
 
 pub struct ZhipuImageClient {
     config: ProviderConfig,
+    request_ids: RequestIdTracker,
 }
 
 impl ZhipuImageClient {
     pub fn new(config: ProviderConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            request_ids: RequestIdTracker::default(),
+        }
+    }
+
+    /// Client and provider IDs of the calls this client made
+    pub fn request_ids(&self) -> RequestIds {
+        self.request_ids.ids()
     }
 
     pub async fn generate(
@@ -76,15 +90,14 @@ impl ZhipuImageClient {
             response_format: request.response_format,
         };
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
         let mut headers = HashMap::new();
         headers.insert("Content-Type".to_string(), "application/json".to_string());
         headers.insert("Authorization".to_string(), format!("Bearer {}", api_key));
 
+        request_log::log_request("ZhipuImageClient", "POST", &url, &headers, Some(&body));
+
         let mut header_map = reqwest::header::HeaderMap::new();
         for (key, value) in headers {
             let header_name = reqwest::header::HeaderName::from_bytes(key.as_bytes())
@@ -94,13 +107,16 @@ impl ZhipuImageClient {
             header_map.insert(header_name, header_value);
         }
 
-        let response = client
-            .post(&url)
+        let response = self
+            .request_ids
+            .tag(client.post(&url))
+            .timeout(Duration::from_secs(120))
             .headers(header_map)
             .json(&body)
-            .send()
+            .send_with_middleware()
             .await
             .map_err(|e| format!("Zhipu AI image request failed: {}", e))?;
+        self.request_ids.record(response.headers());
 
         if !response.status().is_success() {
             let status = response.status();
@@ -108,12 +124,14 @@ impl ZhipuImageClient {
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
+            request_log::log_response("ZhipuImageClient", &url, status.as_u16(), Some(&body));
             return Err(format!(
                 "Zhipu AI image generation failed ({}): {} / 智谱 AI 图片生成失败",
                 status, body
             ));
         }
 
+        request_log::log_response("ZhipuImageClient", &url, response.status().as_u16(), None);
         let payload = response
             .json::<ZhipuImageResponse>()
             .await
@@ -126,7 +144,8 @@ impl ZhipuImageClient {
                 b64_json: item.b64_json,
                 url: item.url,
                 mime_type: "image/png".to_string(),
-                revised_prompt: item.revised_prompt,
+                revised_prompt: normalize_revised_prompt(item.revised_prompt),
+                expires_at: None,
             })
             .collect();
 
@@ -137,6 +156,7 @@ impl ZhipuImageClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::testing::fixtures::provider_config;
 
     #[test]
     fn parses_zhipu_image_response_with_b64() {
@@ -174,21 +194,11 @@ mod tests {
 
     #[test]
     fn zhipu_image_client_constructs() {
-        let config = ProviderConfig {
-            id: "zhipu".to_string(),
-            name: "Zhipu AI".to_string(),
-            protocol: crate::llm::types::ProtocolType::OpenAiCompatible,
-            base_url: "https://open.bigmodel.cn/api/paas/v4".to_string(),
-            api_key_name: "ZHIPU_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
-        };
+        let config = provider_config(
+            "zhipu",
+            crate::llm::types::ProtocolType::OpenAiCompatible,
+            "https://open.bigmodel.cn/api/paas/v4",
+        );
         let _client = ZhipuImageClient::new(config);
     }
 }
@@ -0,0 +1,210 @@
+// Image input check
+// Requests carrying images are checked against the model before they are sent, so a model
+// without vision fails with a clear message instead of a provider error
+
+use crate::llm::types::{ContentPart, Message, MessageContent, ModelsConfiguration};
+use serde::{Deserialize, Serialize};
+
+/// Vision-capable models named in the error message
+const MAX_SUGGESTIONS: usize = 3;
+
+/// What happens when images go to a model that cannot read them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageInputCheck {
+    /// Log a warning and send the request anyway
+    Warn,
+    /// Fail the request before it is sent
+    #[default]
+    Error,
+}
+
+/// Whether any message carries an image part
+pub fn has_image_content(messages: &[Message]) -> bool {
+    messages.iter().any(|message| {
+        let parts = match message {
+            Message::System { .. } => return false,
+            Message::User { content, .. } | Message::Assistant { content, .. } => match content {
+                MessageContent::Text(_) => return false,
+                MessageContent::Parts(parts) => parts,
+            },
+            Message::Tool { content, .. } => content,
+        };
+        parts
+            .iter()
+            .any(|part| matches!(part, ContentPart::Image { .. }))
+    })
+}
+
+/// Check that `model_key` can read the images in `messages`
+/// The model's `imageInput` flag decides; models missing from the configuration are judged by
+/// the provider's `supports_vision` capability
+pub fn check(
+    messages: &[Message],
+    model_key: &str,
+    models: &ModelsConfiguration,
+    supports_vision: bool,
+    policy: ImageInputCheck,
+) -> Result<(), String> {
+    if !has_image_content(messages) {
+        return Ok(());
+    }
+    let accepts_images = models
+        .models
+        .get(model_key)
+        .map_or(supports_vision, |model| model.image_input);
+    if accepts_images {
+        return Ok(());
+    }
+
+    let mut suggestions: Vec<&str> = models
+        .models
+        .iter()
+        .filter(|(_, model)| model.image_input)
+        .map(|(key, _)| key.as_str())
+        .collect();
+    suggestions.sort_unstable();
+    suggestions.truncate(MAX_SUGGESTIONS);
+    let hint = if suggestions.is_empty() {
+        "choose a vision-capable model".to_string()
+    } else {
+        format!(
+            "choose a vision-capable model such as {}",
+            suggestions.join(", ")
+        )
+    };
+    let message = format!(
+        "Model '{}' does not accept image input, {} / 当前模型不支持图片输入，请选择支持视觉的模型",
+        model_key, hint
+    );
+    match policy {
+        ImageInputCheck::Warn => {
+            log::warn!("{}", message);
+            Ok(())
+        }
+        ImageInputCheck::Error => Err(message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::types::ModelConfig;
+    use std::collections::HashMap;
+
+    fn model(name: &str, image_input: bool) -> ModelConfig {
+        ModelConfig {
+            name: name.to_string(),
+            image_input,
+            image_output: false,
+            audio_input: false,
+            video_input: false,
+            interleaved: false,
+            providers: vec!["openai".to_string()],
+            provider_mappings: None,
+            pricing: None,
+            context_length: None,
+        }
+    }
+
+    fn models() -> ModelsConfiguration {
+        ModelsConfiguration {
+            version: "1".to_string(),
+            models: HashMap::from([
+                ("gpt-4o".to_string(), model("GPT-4o", true)),
+                ("o3-mini".to_string(), model("o3-mini", false)),
+            ]),
+        }
+    }
+
+    fn with_image() -> Vec<Message> {
+        vec![Message::User {
+            content: MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: "What is in this screenshot?".to_string(),
+                },
+                ContentPart::Image {
+                    image: "iVBORw0KGgo=".to_string(),
+                    mime_type: Some("image/png".to_string()),
+                },
+            ]),
+            provider_options: None,
+            cache: false,
+        }]
+    }
+
+    #[test]
+    fn rejects_images_for_a_model_without_vision() {
+        let err = check(
+            &with_image(),
+            "o3-mini",
+            &models(),
+            true,
+            ImageInputCheck::Error,
+        )
+        .expect_err("non-vision model");
+
+        assert!(
+            err.contains("'o3-mini' does not accept image input"),
+            "{}",
+            err
+        );
+        assert!(err.contains("such as gpt-4o"), "{}", err);
+        assert!(check(
+            &with_image(),
+            "gpt-4o",
+            &models(),
+            false,
+            ImageInputCheck::Error
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn warn_policy_and_text_only_requests_pass() {
+        let text_only = vec![Message::User {
+            content: MessageContent::Text("Hello".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+
+        assert!(check(
+            &with_image(),
+            "o3-mini",
+            &models(),
+            true,
+            ImageInputCheck::Warn
+        )
+        .is_ok());
+        assert!(check(
+            &text_only,
+            "o3-mini",
+            &models(),
+            false,
+            ImageInputCheck::Error
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn unlisted_models_fall_back_to_the_provider_capability() {
+        let models = models();
+
+        assert!(check(
+            &with_image(),
+            "local-llava",
+            &models,
+            true,
+            ImageInputCheck::Error
+        )
+        .is_ok());
+        assert!(check(
+            &with_image(),
+            "local-llama",
+            &models,
+            false,
+            ImageInputCheck::Error
+        )
+        .is_err());
+    }
+}
@@ -1,12 +1,28 @@
 pub mod ai_services;
+pub mod audio;
 pub mod auth;
+pub mod cancellation;
 pub mod commands;
+pub mod context_window;
+pub mod embeddings;
+pub mod error;
+pub mod http_client;
 pub mod image_generation;
+pub mod image_input;
 pub mod models;
+pub mod moderation;
+pub mod pricing;
 pub mod protocols;
 pub mod providers;
+pub mod rate_limit;
+pub mod request_id;
+pub mod request_log;
+pub mod retry;
+pub mod stream_metrics;
 pub mod streaming;
+pub mod template;
 pub mod testing;
+pub mod tokenize;
 pub mod tracing;
 pub mod transcription;
 pub mod types;
@@ -292,6 +292,7 @@ mod tests {
     use super::*;
     use crate::database::Database;
     use crate::llm::providers::provider_registry::ProviderRegistry;
+    use crate::llm::testing::fixtures;
     use crate::llm::types::{CustomProviderConfig, CustomProviderType, ModelConfig, ModelPricing};
     use crate::llm::types::{ProtocolType, ProviderConfig};
     use std::collections::HashMap;
@@ -324,19 +325,8 @@ mod tests {
 
     fn provider_config(id: &str, auth_type: crate::llm::types::AuthType) -> ProviderConfig {
         ProviderConfig {
-            id: id.to_string(),
-            name: id.to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://example.com".to_string(),
-            api_key_name: "TEST_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
             auth_type,
+            ..fixtures::provider_config(id, ProtocolType::OpenAiCompatible, "https://example.com")
         }
     }
 
@@ -0,0 +1,322 @@
+// Content moderation pre-check
+// Prompts can be screened by a moderator before they reach a chat or image provider
+// Screening is opt-in per provider through the `moderation_enabled_{provider_id}` setting
+
+use crate::llm::auth::api_key_manager::{ApiKeyManager, ProviderCredentials};
+use crate::llm::error::LlmError;
+use crate::llm::http_client::SendWithMiddleware;
+use crate::llm::providers::provider::{setting_enabled, BaseProvider};
+use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::request_log;
+use crate::llm::types::{ContentPart, Message, MessageContent};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Settings prefix that turns moderation on for a provider
+pub const MODERATION_ENABLED_PREFIX: &str = "moderation_enabled";
+/// Provider whose credentials and base URL back the default moderator
+const MODERATION_PROVIDER_ID: &str = "openai";
+const DEFAULT_MODERATION_MODEL: &str = "omni-moderation-latest";
+const MODERATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Verdict for a single input
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ModerationResult {
+    pub flagged: bool,
+    /// Categories the moderator flagged, sorted by name
+    pub categories: Vec<String>,
+}
+
+/// Screens text before it is sent to a provider
+#[async_trait]
+pub trait Moderator: Send + Sync {
+    async fn moderate(&self, input: &str) -> Result<ModerationResult, LlmError>;
+}
+
+/// Moderator backed by OpenAI's `/moderations` endpoint
+pub struct OpenAiModerator {
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationEntry {
+    flagged: bool,
+    #[serde(default)]
+    categories: HashMap<String, bool>,
+}
+
+impl OpenAiModerator {
+    pub fn new(base_url: impl Into<String>, api_key: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            api_key: api_key.into(),
+            model: DEFAULT_MODERATION_MODEL.to_string(),
+        }
+    }
+
+    pub fn with_model(mut self, model: impl Into<String>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Build from the configured OpenAI provider, honouring a custom `base_url_openai`
+    pub async fn from_settings(
+        api_keys: &ApiKeyManager,
+        registry: &ProviderRegistry,
+    ) -> Result<Self, LlmError> {
+        let config = registry.provider(MODERATION_PROVIDER_ID).ok_or_else(|| {
+            LlmError::Other(
+                "Content moderation requires the OpenAI provider / 内容审核需要配置 OpenAI 供应商"
+                    .to_string(),
+            )
+        })?;
+        let api_key = match api_keys.get_credentials(config).await {
+            Ok(ProviderCredentials::Token(key)) => key,
+            _ => {
+                return Err(LlmError::Auth(
+                    "API key not configured for OpenAI content moderation / 内容审核未配置 OpenAI API 密钥"
+                        .to_string(),
+                ))
+            }
+        };
+        let base_url = BaseProvider::new(config.clone())
+            .resolve_base_url_with_fallback(api_keys)
+            .await?;
+        Ok(Self::new(base_url, api_key))
+    }
+}
+
+#[async_trait]
+impl Moderator for OpenAiModerator {
+    async fn moderate(&self, input: &str) -> Result<ModerationResult, LlmError> {
+        let url = format!("{}/moderations", self.base_url.trim_end_matches('/'));
+        let body = serde_json::json!({ "model": self.model, "input": input });
+        let headers = HashMap::from([(
+            "Authorization".to_string(),
+            format!("Bearer {}", self.api_key),
+        )]);
+        request_log::log_request("OpenAiModerator", "POST", &url, &headers, Some(&body));
+
+        let client = crate::llm::http_client::shared_client()?;
+        let response = client
+            .post(&url)
+            .bearer_auth(&self.api_key)
+            .timeout(MODERATION_TIMEOUT)
+            .json(&body)
+            .send_with_middleware()
+            .await?;
+
+        let status = response.status().as_u16();
+        let response_headers = response.headers().clone();
+        let text = response.text().await?;
+        request_log::log_response("OpenAiModerator", &url, status, Some(&text));
+        if status >= 400 {
+            return Err(LlmError::from_response_parts(
+                status,
+                &response_headers,
+                text,
+            ));
+        }
+
+        let payload: ModerationResponse = serde_json::from_str(&text).map_err(|e| {
+            LlmError::InvalidResponse(format!("Failed to parse moderation response: {}", e))
+        })?;
+        let mut result = ModerationResult::default();
+        for entry in payload.results {
+            result.flagged |= entry.flagged;
+            result.categories.extend(
+                entry
+                    .categories
+                    .into_iter()
+                    .filter_map(|(name, hit)| hit.then_some(name)),
+            );
+        }
+        result.categories.sort();
+        result.categories.dedup();
+        Ok(result)
+    }
+}
+
+/// Reject `input` when the moderator flags it
+pub async fn ensure_allowed(moderator: &dyn Moderator, input: &str) -> Result<(), LlmError> {
+    if input.trim().is_empty() {
+        return Ok(());
+    }
+    let result = moderator.moderate(input).await?;
+    if !result.flagged {
+        return Ok(());
+    }
+    let categories = if result.categories.is_empty() {
+        "unspecified".to_string()
+    } else {
+        result.categories.join(", ")
+    };
+    Err(LlmError::Other(format!(
+        "Prompt blocked by content moderation ({}) / 提示词未通过内容审核 ({})",
+        categories, categories
+    )))
+}
+
+/// Run the pre-check for `provider_id` when moderation is enabled for it
+pub async fn precheck(
+    api_keys: &ApiKeyManager,
+    registry: &ProviderRegistry,
+    provider_id: &str,
+    input: &str,
+) -> Result<(), LlmError> {
+    if !setting_enabled(api_keys, MODERATION_ENABLED_PREFIX, provider_id).await? {
+        return Ok(());
+    }
+    let moderator = OpenAiModerator::from_settings(api_keys, registry).await?;
+    ensure_allowed(&moderator, input).await
+}
+
+/// Text of the most recent user turn, which is what a chat pre-check screens
+pub fn latest_user_text(messages: &[Message]) -> String {
+    let Some(content) = messages.iter().rev().find_map(|message| match message {
+        Message::User { content, .. } => Some(content),
+        _ => None,
+    }) else {
+        return String::new();
+    };
+    match content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Parts(parts) => parts
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::fixtures::api_key_manager_with;
+    use crate::llm::testing::mock_server::{start_capture_server, start_sequence_server};
+
+    struct StaticModerator(ModerationResult);
+
+    #[async_trait]
+    impl Moderator for StaticModerator {
+        async fn moderate(&self, _input: &str) -> Result<ModerationResult, LlmError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    const FLAGGED_RESPONSE: &str = r#"{"id":"modr-1","model":"omni-moderation-latest","results":[{"flagged":true,"categories":{"violence":true,"harassment":false,"self-harm":true}}]}"#;
+    const CLEAN_RESPONSE: &str = r#"{"id":"modr-2","model":"omni-moderation-latest","results":[{"flagged":false,"categories":{"violence":false}}]}"#;
+
+    #[tokio::test]
+    async fn ensure_allowed_blocks_flagged_prompt() {
+        let moderator = StaticModerator(ModerationResult {
+            flagged: true,
+            categories: vec!["violence".to_string()],
+        });
+
+        let err = ensure_allowed(&moderator, "something violent")
+            .await
+            .expect_err("flagged prompt");
+
+        assert!(err
+            .to_string()
+            .contains("blocked by content moderation (violence)"));
+    }
+
+    #[tokio::test]
+    async fn ensure_allowed_passes_clean_prompt() {
+        let moderator = StaticModerator(ModerationResult::default());
+        assert!(ensure_allowed(&moderator, "a quiet lake").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn openai_moderator_parses_flagged_categories() {
+        let (base_url, captured) =
+            start_capture_server(200, FLAGGED_RESPONSE.as_bytes().to_vec()).expect("server");
+
+        let result = OpenAiModerator::new(base_url, "test-key")
+            .moderate("hello")
+            .await
+            .expect("moderate");
+
+        assert!(result.flagged);
+        assert_eq!(result.categories, vec!["self-harm", "violence"]);
+        let request = captured.recv().expect("captured request");
+        assert_eq!(request.url, "/moderations");
+        let body: serde_json::Value = serde_json::from_slice(&request.body).expect("json body");
+        assert_eq!(body["input"], "hello");
+        assert_eq!(body["model"], DEFAULT_MODERATION_MODEL);
+    }
+
+    #[tokio::test]
+    async fn precheck_only_runs_for_opted_in_providers() {
+        let (base_url, hits) = start_sequence_server(vec![
+            (200, FLAGGED_RESPONSE.to_string()),
+            (200, CLEAN_RESPONSE.to_string()),
+        ])
+        .expect("server");
+        let (_dir, api_keys) = api_key_manager_with(&[
+            ("api_key_openai", "test-key"),
+            ("base_url_openai", &base_url),
+        ])
+        .await;
+        let registry = ProviderRegistry::default();
+
+        // Not opted in: the moderator is never called
+        precheck(&api_keys, &registry, "volcengine", "anything")
+            .await
+            .expect("moderation disabled");
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        api_keys
+            .set_setting("moderation_enabled_volcengine", "true")
+            .await
+            .expect("enable moderation");
+        let err = precheck(&api_keys, &registry, "volcengine", "something violent")
+            .await
+            .expect_err("flagged prompt");
+        assert!(err.to_string().contains("content moderation"));
+
+        precheck(&api_keys, &registry, "volcengine", "a quiet lake")
+            .await
+            .expect("clean prompt");
+        assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn latest_user_text_reads_last_user_turn() {
+        let messages = vec![
+            Message::User {
+                content: MessageContent::Text("first".to_string()),
+                provider_options: None,
+                cache: false,
+            },
+            Message::User {
+                content: MessageContent::Parts(vec![
+                    ContentPart::Text {
+                        text: "draw".to_string(),
+                    },
+                    ContentPart::Text {
+                        text: "a cat".to_string(),
+                    },
+                ]),
+                provider_options: None,
+                cache: false,
+            },
+        ];
+        assert_eq!(latest_user_text(&messages), "draw\na cat");
+        assert_eq!(latest_user_text(&[]), "");
+    }
+}
@@ -0,0 +1,280 @@
+// Per-token price table and cost estimates for reported token usage
+// Prices come from the bundled model configs; user overrides win since providers reprice often
+
+use crate::llm::ai_services::types::TokenUsage;
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::types::{ModelConfig, ModelPricing};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const PRICE_OVERRIDES_SETTINGS_KEY: &str = "price_overrides_json";
+
+/// Dollar cost per token for one model on one provider
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    #[serde(rename = "input")]
+    pub input_per_token: f64,
+    #[serde(rename = "output")]
+    pub output_per_token: f64,
+    /// Falls back to the input price when unset
+    #[serde(default, rename = "cachedInput")]
+    pub cached_input_per_token: Option<f64>,
+    /// Falls back to the input price when unset
+    #[serde(default, rename = "cacheCreation")]
+    pub cache_creation_per_token: Option<f64>,
+}
+
+impl ModelPrice {
+    pub fn new(input_per_token: f64, output_per_token: f64) -> Self {
+        Self {
+            input_per_token,
+            output_per_token,
+            cached_input_per_token: None,
+            cache_creation_per_token: None,
+        }
+    }
+
+    /// Parse the string rates of a model config, `None` if input or output is not a number
+    fn from_pricing(pricing: &ModelPricing) -> Option<Self> {
+        let rate = |value: &str| value.trim().parse::<f64>().ok().filter(|v| v.is_finite());
+        Some(Self {
+            input_per_token: rate(&pricing.input)?,
+            output_per_token: rate(&pricing.output)?,
+            cached_input_per_token: pricing.cached_input.as_deref().and_then(rate),
+            cache_creation_per_token: pricing.cache_creation.as_deref().and_then(rate),
+        })
+    }
+
+    pub fn cost(&self, usage: &TokenUsage) -> CostEstimate {
+        let cached = usage.cached_input_tokens.unwrap_or(0);
+        let cache_creation = usage.cache_creation_input_tokens.unwrap_or(0);
+        let uncached = usage
+            .input_tokens
+            .saturating_sub(cached)
+            .saturating_sub(cache_creation);
+
+        let input_cost = f64::from(uncached) * self.input_per_token
+            + f64::from(cached) * self.cached_input_per_token.unwrap_or(self.input_per_token)
+            + f64::from(cache_creation)
+                * self
+                    .cache_creation_per_token
+                    .unwrap_or(self.input_per_token);
+        let output_cost = f64::from(usage.output_tokens) * self.output_per_token;
+
+        CostEstimate {
+            input_cost,
+            output_cost,
+            total_cost: input_cost + output_cost,
+        }
+    }
+}
+
+/// User price overrides keyed by provider id, then model id
+pub type PriceOverrides = HashMap<String, HashMap<String, ModelPrice>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct CostEstimate {
+    /// Includes cached and cache-creation input tokens at their own rates
+    #[serde(rename = "inputCost")]
+    pub input_cost: f64,
+    #[serde(rename = "outputCost")]
+    pub output_cost: f64,
+    #[serde(rename = "totalCost")]
+    pub total_cost: f64,
+}
+
+/// Prices keyed by `(provider_id, model)`
+#[derive(Debug, Clone, Default)]
+pub struct PriceTable {
+    prices: HashMap<(String, String), ModelPrice>,
+}
+
+impl PriceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a table from model configs, listing each priced model under every provider serving it
+    pub fn from_model_configs(models: &HashMap<String, ModelConfig>) -> Self {
+        let mut table = Self::new();
+        for (model_id, config) in models {
+            let Some(price) = config.pricing.as_ref().and_then(ModelPrice::from_pricing) else {
+                continue;
+            };
+            for provider_id in &config.providers {
+                table.insert(provider_id, model_id, price);
+            }
+        }
+        table
+    }
+
+    pub fn insert(&mut self, provider_id: &str, model: &str, price: ModelPrice) {
+        self.prices
+            .insert((provider_id.to_string(), model.to_string()), price);
+    }
+
+    pub fn apply_overrides(&mut self, overrides: &PriceOverrides) {
+        for (provider_id, models) in overrides {
+            for (model, price) in models {
+                self.insert(provider_id, model, *price);
+            }
+        }
+    }
+
+    /// Price for a model, accepting the `model@provider` form used in model identifiers
+    pub fn price(&self, provider_id: &str, model: &str) -> Option<&ModelPrice> {
+        let lookup = |model: &str| {
+            self.prices
+                .get(&(provider_id.to_string(), model.to_string()))
+        };
+        lookup(model).or_else(|| lookup(model.split('@').next()?))
+    }
+
+    /// Cost of the given usage, or `None` when the model has no known price
+    pub fn estimate_cost(
+        &self,
+        usage: &TokenUsage,
+        provider_id: &str,
+        model: &str,
+    ) -> Option<CostEstimate> {
+        self.price(provider_id, model)
+            .map(|price| price.cost(usage))
+    }
+}
+
+pub async fn load_price_overrides(api_keys: &ApiKeyManager) -> Result<PriceOverrides, String> {
+    match api_keys.get_setting(PRICE_OVERRIDES_SETTINGS_KEY).await? {
+        Some(raw) if !raw.trim().is_empty() => serde_json::from_str(&raw)
+            .map_err(|e| format!("Failed to parse price overrides: {}", e)),
+        _ => Ok(PriceOverrides::new()),
+    }
+}
+
+pub async fn save_price_overrides(
+    api_keys: &ApiKeyManager,
+    overrides: &PriceOverrides,
+) -> Result<(), String> {
+    let raw = serde_json::to_string(overrides)
+        .map_err(|e| format!("Failed to serialize price overrides: {}", e))?;
+    api_keys
+        .set_setting(PRICE_OVERRIDES_SETTINGS_KEY, &raw)
+        .await
+}
+
+/// Price table from the current models config with the user's overrides applied
+pub async fn load_price_table(api_keys: &ApiKeyManager) -> Result<PriceTable, String> {
+    let models = api_keys.load_models_config().await?;
+    let mut table = PriceTable::from_model_configs(&models.models);
+    table.apply_overrides(&load_price_overrides(api_keys).await?);
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input: u32, output: u32, cached: Option<u32>) -> TokenUsage {
+        TokenUsage {
+            input_tokens: input,
+            output_tokens: output,
+            cached_input_tokens: cached,
+            cache_creation_input_tokens: None,
+            estimated: false,
+        }
+    }
+
+    fn sample_table() -> PriceTable {
+        let mut table = PriceTable::new();
+        table.insert("openai", "gpt-4o", ModelPrice::new(0.0000025, 0.00001));
+        table.insert(
+            "anthropic",
+            "claude-sonnet-4",
+            ModelPrice {
+                cached_input_per_token: Some(0.0000003),
+                ..ModelPrice::new(0.000003, 0.000015)
+            },
+        );
+        table
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn estimate_cost_breaks_down_input_and_output() {
+        let table = sample_table();
+
+        let cost = table
+            .estimate_cost(&usage(1_000_000, 200_000, None), "openai", "gpt-4o")
+            .expect("priced");
+
+        assert_close(cost.input_cost, 2.5);
+        assert_close(cost.output_cost, 2.0);
+        assert_close(cost.total_cost, 4.5);
+    }
+
+    #[test]
+    fn cached_input_tokens_use_the_cached_rate() {
+        let table = sample_table();
+
+        let cost = table
+            .estimate_cost(
+                &usage(10_000, 1_000, Some(8_000)),
+                "anthropic",
+                "claude-sonnet-4@anthropic",
+            )
+            .expect("priced");
+
+        assert_close(cost.input_cost, 2_000.0 * 0.000003 + 8_000.0 * 0.0000003);
+        assert_close(cost.output_cost, 0.015);
+        assert!(table
+            .estimate_cost(&usage(1, 1, None), "openai", "claude-sonnet-4")
+            .is_none());
+    }
+
+    #[test]
+    fn overrides_replace_config_prices() {
+        let mut models = HashMap::new();
+        models.insert(
+            "gpt-4o".to_string(),
+            ModelConfig {
+                name: "GPT-4o".to_string(),
+                image_input: true,
+                image_output: false,
+                audio_input: false,
+                video_input: false,
+                interleaved: false,
+                providers: vec!["openai".to_string(), "openRouter".to_string()],
+                provider_mappings: None,
+                pricing: Some(ModelPricing {
+                    input: "0.0000025".to_string(),
+                    output: "0.00001".to_string(),
+                    cached_input: None,
+                    cache_creation: None,
+                }),
+                context_length: None,
+            },
+        );
+        let mut table = PriceTable::from_model_configs(&models);
+        let overrides: PriceOverrides = serde_json::from_str(
+            r#"{"openRouter": {"gpt-4o": {"input": 0.000005, "output": 0.00002}}}"#,
+        )
+        .expect("overrides");
+        table.apply_overrides(&overrides);
+
+        assert_eq!(
+            table.price("openai", "gpt-4o"),
+            Some(&ModelPrice::new(0.0000025, 0.00001))
+        );
+        assert_eq!(
+            table.price("openRouter", "gpt-4o"),
+            Some(&ModelPrice::new(0.000005, 0.00002))
+        );
+    }
+}
@@ -0,0 +1,1119 @@
+// Anthropic Messages protocol
+// Targets /v1/messages natively: system prompt split out of messages, x-api-key auth,
+// and the content_block_* / message_* SSE event family
+
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    prompt_cache::{self, MAX_CACHE_BREAKPOINTS},
+    request_builder::{merge_extra_body, ProtocolRequestBuilder, RequestBuildContext},
+    stream_parser::{self, ProtocolStreamParser, StreamParseContext, StreamParseState},
+    ToolCallAccum,
+};
+use crate::llm::types::{
+    BuiltinTool, ContentPart, ImageSource, Message, MessageContent, ReasoningEffort, StreamEvent,
+    ToolDefinition,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Value sent in the `anthropic-version` header
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// `image` content block; http(s) URLs are passed on for Anthropic to fetch
+pub(crate) fn image_block(image: &str, mime_type: Option<&str>) -> Value {
+    match ImageSource::of(image, mime_type) {
+        ImageSource::Base64 { mime_type, data } => json!({
+            "type": "image",
+            "source": { "type": "base64", "media_type": mime_type, "data": data }
+        }),
+        ImageSource::Url(url) => json!({
+            "type": "image",
+            "source": { "type": "url", "url": url }
+        }),
+    }
+}
+
+/// Anthropic requires max_tokens on every request
+const DEFAULT_MAX_TOKENS: i32 = 4096;
+/// Versioned type of the server-side web search tool
+const WEB_SEARCH_TOOL_TYPE: &str = "web_search_20250305";
+/// Smallest `thinking.budget_tokens` the Messages API accepts
+const MIN_THINKING_BUDGET: i64 = 1024;
+
+pub struct AnthropicProtocol;
+
+impl AnthropicProtocol {
+    /// Collect all system messages into the top-level `system` field.
+    /// A single prompt is sent as a plain string, multiple or cached prompts as text blocks.
+    fn build_system(&self, messages: &[Message]) -> Option<Value> {
+        let prompts: Vec<(&str, bool)> = messages
+            .iter()
+            .filter_map(|msg| match msg {
+                Message::System { content, .. } if !content.trim().is_empty() => {
+                    Some((content.as_str(), msg.is_cache_breakpoint()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        match prompts.as_slice() {
+            [] => None,
+            [(single, false)] => Some(json!(single)),
+            many => Some(Value::Array(
+                many.iter()
+                    .map(|(text, cache)| {
+                        let mut block = json!({ "type": "text", "text": text });
+                        if *cache {
+                            block["cache_control"] = prompt_cache::cache_control();
+                        }
+                        block
+                    })
+                    .collect(),
+            )),
+        }
+    }
+
+    fn build_messages(&self, messages: &[Message]) -> Vec<Value> {
+        let mut result = Vec::new();
+        for msg in messages {
+            let start = result.len();
+            match msg {
+                Message::System { .. } => {}
+                Message::User { content, .. } => {
+                    let blocks = self.convert_content(content);
+                    if !blocks.is_empty() {
+                        result.push(json!({ "role": "user", "content": blocks }));
+                    }
+                }
+                Message::Assistant { content, .. } => {
+                    let blocks = self.convert_content(content);
+                    if !blocks.is_empty() {
+                        result.push(json!({ "role": "assistant", "content": blocks }));
+                    }
+                }
+                Message::Tool { content, .. } => {
+                    let tool_results: Vec<Value> = content
+                        .iter()
+                        .filter_map(|part| match part {
+                            ContentPart::ToolResult {
+                                tool_call_id,
+                                output,
+                                ..
+                            } => Some(json!({
+                                "type": "tool_result",
+                                "tool_use_id": tool_call_id,
+                                "content": self.tool_output_to_string(output)
+                            })),
+                            _ => None,
+                        })
+                        .collect();
+                    if !tool_results.is_empty() {
+                        result.push(json!({ "role": "user", "content": tool_results }));
+                    }
+                }
+            }
+            if msg.is_cache_breakpoint() && result.len() > start {
+                if let Some(content) = result.last_mut().and_then(|m| m.get_mut("content")) {
+                    prompt_cache::mark_content(content);
+                }
+            }
+        }
+        result
+    }
+
+    fn convert_content(&self, content: &MessageContent) -> Vec<Value> {
+        match content {
+            MessageContent::Text(text) => {
+                if text.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![json!({ "type": "text", "text": text })]
+                }
+            }
+            MessageContent::Parts(parts) => {
+                let mut mapped = Vec::new();
+                for part in parts {
+                    match part {
+                        ContentPart::Text { text } => {
+                            // Anthropic rejects empty text blocks
+                            if !text.is_empty() {
+                                mapped.push(json!({ "type": "text", "text": text }));
+                            }
+                        }
+                        ContentPart::Image { image, mime_type } => {
+                            mapped.push(image_block(image, mime_type.as_deref()));
+                        }
+                        ContentPart::ToolCall {
+                            tool_call_id,
+                            tool_name,
+                            input,
+                            ..
+                        } => {
+                            mapped.push(json!({
+                                "type": "tool_use",
+                                "id": tool_call_id,
+                                "name": tool_name,
+                                "input": input
+                            }));
+                        }
+                        ContentPart::Reasoning {
+                            text,
+                            provider_options,
+                        } => {
+                            // Thinking blocks can only be replayed together with their signature
+                            let signature = provider_options
+                                .as_ref()
+                                .and_then(|opts| opts.get("anthropic"))
+                                .and_then(|v| v.get("signature"));
+                            if let Some(signature) = signature {
+                                mapped.push(json!({
+                                    "type": "thinking",
+                                    "thinking": text,
+                                    "signature": signature
+                                }));
+                            }
+                        }
+                        ContentPart::ToolResult { .. } => {}
+                        ContentPart::Video { .. } => {
+                            // Anthropic Messages API doesn't support video input, skip
+                        }
+                    }
+                }
+                mapped
+            }
+        }
+    }
+
+    fn tool_output_to_string(&self, output: &Value) -> String {
+        if let Some(value) = output.get("value").and_then(|v| v.as_str()) {
+            return value.to_string();
+        }
+        output.to_string()
+    }
+
+    fn build_tools(&self, tools: Option<&[ToolDefinition]>) -> Option<Vec<Value>> {
+        let tools = tools?;
+        Some(
+            tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "name": tool.name,
+                        "description": tool.description,
+                        "input_schema": tool.parameters
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Server tools for the Messages API; shared with the legacy Claude protocol
+    pub(crate) fn append_builtin_tools(
+        body: &mut Value,
+        builtin_tools: &[BuiltinTool],
+    ) -> Result<(), String> {
+        for tool in builtin_tools {
+            // Code execution is still behind a beta header
+            if *tool == BuiltinTool::CodeInterpreter {
+                return Err(
+                    "Code execution is not enabled for Anthropic / Anthropic 未启用代码执行"
+                        .to_string(),
+                );
+            }
+            let server_tool = json!({ "type": WEB_SEARCH_TOOL_TYPE, "name": "web_search" });
+            match body["tools"].as_array_mut() {
+                Some(tools) => tools.push(server_tool),
+                None => body["tools"] = json!([server_tool]),
+            }
+        }
+        Ok(())
+    }
+
+    /// Extended thinking for `effort`; shared with the legacy Claude protocol
+    /// Skipped for models without extended thinking and when providerOptions already set
+    /// `thinking`. The budget stays below `max_tokens` as the API requires
+    pub(crate) fn apply_thinking(body: &mut Value, model: &str, effort: Option<ReasoningEffort>) {
+        let Some(effort) = effort else {
+            return;
+        };
+        if body.get("thinking").is_some() || !supports_thinking(model) {
+            return;
+        }
+        let max_tokens = body["max_tokens"]
+            .as_i64()
+            .unwrap_or(i64::from(DEFAULT_MAX_TOKENS));
+        let budget = i64::from(effort.budget_tokens()).min(max_tokens - 1);
+        if budget < MIN_THINKING_BUDGET {
+            log::warn!(
+                "[Anthropic] max_tokens {} leaves no room for a thinking budget, thinking disabled",
+                max_tokens
+            );
+            return;
+        }
+        body["thinking"] = json!({ "type": "enabled", "budget_tokens": budget });
+    }
+
+    fn resolve_event_type(event_type: Option<&str>, payload: &Value) -> String {
+        event_type
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty() && *value != "message")
+            .map(|value| value.to_string())
+            .or_else(|| {
+                payload
+                    .get("type")
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string())
+            })
+            .unwrap_or_else(|| "message".to_string())
+    }
+
+    fn block_index(payload: &Value) -> Option<usize> {
+        payload
+            .get("index")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize)
+    }
+
+    fn thinking_id(state: &StreamParseState) -> String {
+        state
+            .current_thinking_id
+            .clone()
+            .unwrap_or_else(|| "thinking".to_string())
+    }
+
+    fn handle_block_start(
+        &self,
+        payload: &Value,
+        state: &mut StreamParseState,
+    ) -> Option<StreamEvent> {
+        let index = Self::block_index(payload).unwrap_or(0);
+        let block = payload.get("content_block")?;
+        let block_type = block.get("type").and_then(|v| v.as_str())?;
+        state
+            .content_block_types
+            .insert(index, block_type.to_string());
+
+        match block_type {
+            "text" => {
+                if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
+                    if !text.is_empty() {
+                        state.pending_events.push(StreamEvent::TextDelta {
+                            text: text.to_string(),
+                        });
+                    }
+                }
+                if !state.text_started {
+                    state.text_started = true;
+                    return Some(StreamEvent::TextStart);
+                }
+                if !state.pending_events.is_empty() {
+                    return Some(state.pending_events.remove(0));
+                }
+                None
+            }
+            "thinking" => {
+                let id = format!("thinking_{}", index);
+                state.content_block_ids.insert(index, id.clone());
+                state.current_thinking_id = Some(id.clone());
+                state.reasoning_started = true;
+                Some(StreamEvent::ReasoningStart {
+                    id,
+                    provider_metadata: None,
+                })
+            }
+            "tool_use" => {
+                let id = block
+                    .get("id")
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| format!("tool_{}", index));
+                let name = block
+                    .get("name")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                // Some gateways send the complete input up front instead of json deltas
+                let arguments = match block.get("input") {
+                    Some(input) if input.as_object().is_some_and(|obj| !obj.is_empty()) => {
+                        input.to_string()
+                    }
+                    _ => String::new(),
+                };
+                state.content_block_ids.insert(index, id.clone());
+                state.tool_calls.insert(
+                    id.clone(),
+                    ToolCallAccum {
+                        tool_call_id: id.clone(),
+                        tool_name: name,
+                        arguments,
+                        thought_signature: None,
+                    },
+                );
+                state.tool_call_order.push(id);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn handle_block_delta(
+        &self,
+        payload: &Value,
+        state: &mut StreamParseState,
+    ) -> Option<StreamEvent> {
+        let delta = payload.get("delta")?;
+        let delta_type = delta.get("type").and_then(|v| v.as_str()).unwrap_or("");
+        match delta_type {
+            "text_delta" => {
+                let text = delta.get("text").and_then(|v| v.as_str())?;
+                let event = StreamEvent::TextDelta {
+                    text: text.to_string(),
+                };
+                if !state.text_started {
+                    state.text_started = true;
+                    state.pending_events.push(event);
+                    return Some(StreamEvent::TextStart);
+                }
+                Some(event)
+            }
+            "thinking_delta" => {
+                let text = delta
+                    .get("thinking")
+                    .or_else(|| delta.get("text"))
+                    .and_then(|v| v.as_str())?;
+                Some(StreamEvent::ReasoningDelta {
+                    id: Self::thinking_id(state),
+                    text: text.to_string(),
+                    provider_metadata: None,
+                })
+            }
+            "signature_delta" => {
+                let signature = delta.get("signature")?;
+                Some(StreamEvent::ReasoningDelta {
+                    id: Self::thinking_id(state),
+                    text: String::new(),
+                    provider_metadata: Some(json!({
+                        "anthropic": { "signature": signature }
+                    })),
+                })
+            }
+            "input_json_delta" => {
+                let tool_id = Self::block_index(payload)
+                    .and_then(|index| state.content_block_ids.get(&index).cloned())?;
+                let chunk = delta.get("partial_json").and_then(|v| v.as_str())?;
+                if let Some(acc) = state.tool_calls.get_mut(&tool_id) {
+                    acc.arguments.push_str(chunk);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn handle_block_stop(
+        &self,
+        payload: &Value,
+        state: &mut StreamParseState,
+    ) -> Option<StreamEvent> {
+        let index = Self::block_index(payload)?;
+        let block_type = state.content_block_types.get(&index).cloned()?;
+        let block_id = state.content_block_ids.get(&index).cloned();
+
+        match block_type.as_str() {
+            "thinking" => {
+                state.reasoning_started = false;
+                state.current_thinking_id = None;
+                Some(StreamEvent::ReasoningEnd {
+                    id: block_id.unwrap_or_else(|| "thinking".to_string()),
+                })
+            }
+            "tool_use" => {
+                let tool_id = block_id?;
+                if state.emitted_tool_calls.contains(&tool_id) {
+                    return None;
+                }
+                let acc = state.tool_calls.get(&tool_id)?;
+                let input = if acc.arguments.trim().is_empty() {
+                    json!({})
+                } else {
+                    serde_json::from_str(&acc.arguments)
+                        .unwrap_or_else(|_| Value::String(acc.arguments.clone()))
+                };
+                let event = StreamEvent::ToolCall {
+                    tool_call_id: acc.tool_call_id.clone(),
+                    tool_name: acc.tool_name.clone(),
+                    input,
+                    provider_metadata: None,
+                };
+                state.emitted_tool_calls.insert(tool_id);
+                Some(event)
+            }
+            _ => None,
+        }
+    }
+
+    fn handle_message_start(
+        &self,
+        payload: &Value,
+        state: &mut StreamParseState,
+    ) -> Option<StreamEvent> {
+        let message = payload.get("message")?;
+        if let Some(usage) = message.get("usage") {
+            let read = |key: &str| usage.get(key).and_then(|v| v.as_i64()).map(|v| v as i32);
+            state.input_tokens = read("input_tokens");
+            state.cached_input_tokens = read("cache_read_input_tokens");
+            state.cache_creation_input_tokens = read("cache_creation_input_tokens");
+        }
+        stream_parser::start_event(state, message.get("model").and_then(|v| v.as_str()), None)
+    }
+
+    fn handle_message_delta(
+        &self,
+        payload: &Value,
+        state: &mut StreamParseState,
+    ) -> Option<StreamEvent> {
+        if let Some(stop_reason) = payload
+            .get("delta")
+            .and_then(|v| v.get("stop_reason"))
+            .and_then(|v| v.as_str())
+        {
+            state.finish_reason = Some(stop_reason.to_string());
+        }
+
+        let usage = payload.get("usage")?;
+        let read = |key: &str| usage.get(key).and_then(|v| v.as_i64()).map(|v| v as i32);
+        // message_delta usage is cumulative; fall back to message_start for input counts
+        let input_tokens = read("input_tokens").or(state.input_tokens).unwrap_or(0);
+        let output_tokens = read("output_tokens").unwrap_or(0);
+        Some(StreamEvent::Usage {
+            input_tokens,
+            output_tokens,
+            total_tokens: Some(input_tokens + output_tokens),
+            cached_input_tokens: read("cache_read_input_tokens").or(state.cached_input_tokens),
+            cache_creation_input_tokens: read("cache_creation_input_tokens")
+                .or(state.cache_creation_input_tokens),
+            timing: None,
+            estimated: false,
+        })
+    }
+}
+
+/// Whether `model` has extended thinking: Claude 3.7 Sonnet and the Claude 4 generations
+/// Matches from `claude-` on, so vendor prefixes such as `anthropic/` or Bedrock's
+/// `anthropic.` are ignored
+fn supports_thinking(model: &str) -> bool {
+    let model = model.to_ascii_lowercase();
+    let Some(start) = model.find("claude-") else {
+        return false;
+    };
+    let name = &model[start + "claude-".len()..];
+    if name.starts_with("3-7-") {
+        return true;
+    }
+    ["sonnet-", "opus-", "haiku-"].iter().any(|family| {
+        name.strip_prefix(family)
+            .and_then(|rest| rest.split(['-', '.']).next())
+            .and_then(|major| major.parse::<u32>().ok())
+            .is_some_and(|major| major >= 4)
+    })
+}
+
+impl ProtocolRequestBuilder for AnthropicProtocol {
+    fn build_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        let mut body = json!({
+            "model": ctx.model,
+            "messages": self.build_messages(ctx.messages),
+            "stream": ctx.stream,
+            "max_tokens": ctx.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)
+        });
+
+        if let Some(system) = self.build_system(ctx.messages) {
+            body["system"] = system;
+        }
+        if let Some(tools) = self.build_tools(ctx.tools) {
+            body["tools"] = Value::Array(tools);
+        }
+        Self::append_builtin_tools(&mut body, ctx.builtin_tools)?;
+        if let Some(temperature) = ctx.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(top_p) = ctx.top_p {
+            body["top_p"] = json!(top_p);
+        }
+        if let Some(top_k) = ctx.top_k {
+            body["top_k"] = json!(top_k);
+        }
+
+        if let Some(anthropic) = ctx.provider_options.and_then(|opts| opts.get("anthropic")) {
+            if let Some(thinking) = anthropic.get("thinking") {
+                body["thinking"] = thinking.clone();
+            }
+        }
+        Self::apply_thinking(&mut body, ctx.model, ctx.reasoning_effort);
+
+        merge_extra_body(&mut body, ctx.extra_body);
+        prompt_cache::limit_breakpoints(&mut body, MAX_CACHE_BREAKPOINTS);
+
+        Ok(body)
+    }
+}
+
+impl ProtocolStreamParser for AnthropicProtocol {
+    fn parse_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        // Messages streams end with message_stop; a proxy's [DONE] sentinel carries nothing
+        if self.is_done_event(ctx.data) {
+            return Ok(None);
+        }
+        let Some(payload) = stream_parser::parse_json_data(state, ctx.data)? else {
+            return Ok(None);
+        };
+        let event_type = Self::resolve_event_type(ctx.event_type, &payload);
+
+        let event = match event_type.as_str() {
+            "message_start" => self.handle_message_start(&payload, state),
+            "content_block_start" => self.handle_block_start(&payload, state),
+            "content_block_delta" => self.handle_block_delta(&payload, state),
+            "content_block_stop" => self.handle_block_stop(&payload, state),
+            "message_delta" => self.handle_message_delta(&payload, state),
+            "message_stop" => Some(StreamEvent::Done {
+                finish_reason: state.finish_reason.clone(),
+            }),
+            "error" => {
+                let error = payload.get("error");
+                let kind = error
+                    .and_then(|e| e.get("type"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("error");
+                let message = error
+                    .and_then(|e| e.get("message"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("Unknown error");
+                return Err(format!("Anthropic stream error ({}): {}", kind, message));
+            }
+            // ping and unknown events are keep-alives
+            _ => None,
+        };
+
+        Ok(event)
+    }
+}
+
+impl ProtocolHeaderBuilder for AnthropicProtocol {
+    fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        headers.insert(
+            "anthropic-version".to_string(),
+            ANTHROPIC_VERSION.to_string(),
+        );
+        if let Some(token) = ctx.oauth_token {
+            headers.insert("Authorization".to_string(), format!("Bearer {}", token));
+        } else if let Some(key) = ctx.api_key {
+            headers.insert("x-api-key".to_string(), key.to_string());
+        }
+        if let Some(extra) = ctx.extra_headers {
+            for (k, v) in extra {
+                headers.insert(k.to_string(), v.to_string());
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Recorded from a claude-sonnet-4-5 turn that thinks, answers, then calls a tool
+    const TOOL_USE_TRANSCRIPT: &str = r#"event: message_start
+data: {"type":"message_start","message":{"id":"msg_01","type":"message","role":"assistant","model":"claude-sonnet-4-5","content":[],"stop_reason":null,"usage":{"input_tokens":25,"cache_read_input_tokens":10,"cache_creation_input_tokens":0,"output_tokens":1}}}
+
+event: content_block_start
+data: {"type":"content_block_start","index":0,"content_block":{"type":"thinking","thinking":""}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"thinking_delta","thinking":"Checking the file."}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":0,"delta":{"type":"signature_delta","signature":"sig-1"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":0}
+
+event: content_block_start
+data: {"type":"content_block_start","index":1,"content_block":{"type":"text","text":""}}
+
+event: ping
+data: {"type":"ping"}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"text_delta","text":"Hello"}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":1,"delta":{"type":"text_delta","text":" world"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":1}
+
+event: content_block_start
+data: {"type":"content_block_start","index":2,"content_block":{"type":"tool_use","id":"toolu_01","name":"readFile","input":{}}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":2,"delta":{"type":"input_json_delta","partial_json":"{\"path\":"}}
+
+event: content_block_delta
+data: {"type":"content_block_delta","index":2,"delta":{"type":"input_json_delta","partial_json":"\"/tmp/a.rs\"}"}}
+
+event: content_block_stop
+data: {"type":"content_block_stop","index":2}
+
+event: message_delta
+data: {"type":"message_delta","delta":{"stop_reason":"tool_use","stop_sequence":null},"usage":{"output_tokens":42}}
+
+event: message_stop
+data: {"type":"message_stop"}
+"#;
+
+    fn parse_transcript(transcript: &str) -> Vec<StreamEvent> {
+        let protocol = AnthropicProtocol;
+        let mut state = StreamParseState::default();
+        let mut events = Vec::new();
+
+        for block in transcript.split("\n\n") {
+            let mut event_type = None;
+            let mut data = None;
+            for line in block.lines() {
+                if let Some(rest) = line.strip_prefix("event:") {
+                    event_type = Some(rest.trim());
+                } else if let Some(rest) = line.strip_prefix("data:") {
+                    data = Some(rest.trim());
+                }
+            }
+            let Some(data) = data else {
+                continue;
+            };
+
+            let ctx = StreamParseContext { event_type, data };
+            if let Some(event) = protocol
+                .parse_stream_event(ctx, &mut state)
+                .expect("parse event")
+            {
+                events.push(event);
+            }
+            events.append(&mut state.pending_events);
+        }
+
+        events
+    }
+
+    fn user_text(text: &str) -> Message {
+        Message::User {
+            content: MessageContent::Text(text.to_string()),
+            provider_options: None,
+            cache: false,
+        }
+    }
+
+    #[test]
+    fn image_blocks_unwrap_data_urls_and_pass_http_urls_on() {
+        assert_eq!(
+            image_block("data:image/webp;base64,UklGRh4AAABXRUJQ", None),
+            json!({
+                "type": "image",
+                "source": {
+                    "type": "base64",
+                    "media_type": "image/webp",
+                    "data": "UklGRh4AAABXRUJQ"
+                }
+            })
+        );
+        assert_eq!(
+            image_block("/9j/4AAQSkZJRg==", None)["source"]["media_type"],
+            "image/jpeg"
+        );
+        assert_eq!(
+            image_block("https://example.com/cat.png", None),
+            json!({
+                "type": "image",
+                "source": { "type": "url", "url": "https://example.com/cat.png" }
+            })
+        );
+    }
+
+    #[test]
+    fn parses_recorded_transcript_into_stream_events() {
+        let events = parse_transcript(TOOL_USE_TRANSCRIPT);
+        let actual = serde_json::to_value(&events).expect("serialize events");
+
+        assert_eq!(
+            actual,
+            json!([
+                { "type": "start", "model": "claude-sonnet-4-5" },
+                { "type": "reasoning-start", "id": "thinking_0", "provider_metadata": null },
+                {
+                    "type": "reasoning-delta",
+                    "id": "thinking_0",
+                    "text": "Checking the file.",
+                    "provider_metadata": null
+                },
+                {
+                    "type": "reasoning-delta",
+                    "id": "thinking_0",
+                    "text": "",
+                    "provider_metadata": { "anthropic": { "signature": "sig-1" } }
+                },
+                { "type": "reasoning-end", "id": "thinking_0" },
+                { "type": "text-start" },
+                { "type": "text-delta", "text": "Hello" },
+                { "type": "text-delta", "text": " world" },
+                {
+                    "type": "tool-call",
+                    "toolCallId": "toolu_01",
+                    "toolName": "readFile",
+                    "input": { "path": "/tmp/a.rs" },
+                    "provider_metadata": null
+                },
+                {
+                    "type": "usage",
+                    "input_tokens": 25,
+                    "output_tokens": 42,
+                    "total_tokens": 67,
+                    "cached_input_tokens": 10,
+                    "cache_creation_input_tokens": 0
+                },
+                { "type": "done", "finish_reason": "tool_use" }
+            ])
+        );
+    }
+
+    #[test]
+    fn stream_error_event_returns_error() {
+        let protocol = AnthropicProtocol;
+        let mut state = StreamParseState::default();
+        let data = json!({
+            "type": "error",
+            "error": { "type": "overloaded_error", "message": "Overloaded" }
+        })
+        .to_string();
+
+        let result = protocol.parse_stream_event(
+            StreamParseContext {
+                event_type: Some("error"),
+                data: &data,
+            },
+            &mut state,
+        );
+
+        let err = result.expect_err("error event");
+        assert!(err.contains("overloaded_error"));
+        assert!(err.contains("Overloaded"));
+    }
+
+    #[test]
+    fn build_request_splits_system_from_messages() {
+        let protocol = AnthropicProtocol;
+        let messages = vec![
+            Message::System {
+                content: "You are helpful.".to_string(),
+                provider_options: None,
+                cache: false,
+            },
+            user_text("hi"),
+            Message::Assistant {
+                content: MessageContent::Parts(vec![ContentPart::ToolCall {
+                    tool_call_id: "toolu_01".to_string(),
+                    tool_name: "readFile".to_string(),
+                    input: json!({ "path": "/tmp/a.rs" }),
+                    provider_metadata: None,
+                }]),
+                provider_options: None,
+                cache: false,
+            },
+            Message::Tool {
+                content: vec![ContentPart::ToolResult {
+                    tool_call_id: "toolu_01".to_string(),
+                    tool_name: "readFile".to_string(),
+                    output: json!({ "type": "text", "value": "fn main() {}" }),
+                }],
+                provider_options: None,
+                cache: false,
+            },
+        ];
+
+        let body = protocol
+            .build_request(RequestBuildContext {
+                model: "claude-sonnet-4-5",
+                messages: &messages,
+                tools: None,
+                temperature: None,
+                max_tokens: None,
+                top_p: None,
+                top_k: Some(5),
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: None,
+                response_format: None,
+                user_id: None,
+                provider_options: None,
+                extra_body: None,
+                stream: true,
+                logprobs: false,
+                top_logprobs: None,
+                builtin_tools: &[],
+                reasoning_effort: None,
+            })
+            .expect("build request");
+
+        assert_eq!(body["system"], json!("You are helpful."));
+        assert_eq!(body["max_tokens"], json!(DEFAULT_MAX_TOKENS));
+        assert_eq!(body["top_k"], json!(5));
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "user", "content": [{ "type": "text", "text": "hi" }] },
+                {
+                    "role": "assistant",
+                    "content": [{
+                        "type": "tool_use",
+                        "id": "toolu_01",
+                        "name": "readFile",
+                        "input": { "path": "/tmp/a.rs" }
+                    }]
+                },
+                {
+                    "role": "user",
+                    "content": [{
+                        "type": "tool_result",
+                        "tool_use_id": "toolu_01",
+                        "content": "fn main() {}"
+                    }]
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn build_request_sends_multiple_system_prompts_as_blocks() {
+        let protocol = AnthropicProtocol;
+        let messages = vec![
+            Message::System {
+                content: "first".to_string(),
+                provider_options: None,
+                cache: false,
+            },
+            Message::System {
+                content: "second".to_string(),
+                provider_options: None,
+                cache: false,
+            },
+            user_text("hi"),
+        ];
+
+        let body = protocol
+            .build_request(RequestBuildContext {
+                model: "claude-sonnet-4-5",
+                messages: &messages,
+                tools: None,
+                temperature: None,
+                max_tokens: Some(256),
+                top_p: None,
+                top_k: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: None,
+                response_format: None,
+                user_id: None,
+                provider_options: None,
+                extra_body: None,
+                stream: true,
+                logprobs: false,
+                top_logprobs: None,
+                builtin_tools: &[],
+                reasoning_effort: None,
+            })
+            .expect("build request");
+
+        assert_eq!(
+            body["system"],
+            json!([
+                { "type": "text", "text": "first" },
+                { "type": "text", "text": "second" }
+            ])
+        );
+        assert_eq!(body["max_tokens"], json!(256));
+    }
+
+    #[test]
+    fn builtin_web_search_is_added_as_a_server_tool() {
+        let mut body = json!({ "tools": [{ "name": "readFile", "input_schema": {} }] });
+
+        AnthropicProtocol::append_builtin_tools(&mut body, &[BuiltinTool::WebSearch])
+            .expect("web search");
+
+        assert_eq!(
+            body["tools"][1],
+            json!({ "type": "web_search_20250305", "name": "web_search" })
+        );
+        let code = [BuiltinTool::CodeInterpreter];
+        assert!(AnthropicProtocol::append_builtin_tools(&mut body, &code).is_err());
+    }
+
+    #[test]
+    fn reasoning_effort_sets_the_thinking_budget() {
+        let thinking = |model, max_tokens, effort| {
+            let mut body = json!({ "model": model, "max_tokens": max_tokens });
+            AnthropicProtocol::apply_thinking(&mut body, model, Some(effort));
+            body.get("thinking").cloned()
+        };
+
+        for (effort, budget) in [
+            (ReasoningEffort::Low, 1024),
+            (ReasoningEffort::Medium, 8192),
+            (ReasoningEffort::High, 24576),
+        ] {
+            assert_eq!(
+                thinking("claude-sonnet-4-5", 32000, effort),
+                Some(json!({ "type": "enabled", "budget_tokens": budget })),
+                "{:?}",
+                effort
+            );
+        }
+        // Clamped below max_tokens, dropped when no valid budget is left
+        assert_eq!(
+            thinking("anthropic/claude-opus-4-1", 4096, ReasoningEffort::High),
+            Some(json!({ "type": "enabled", "budget_tokens": 4095 }))
+        );
+        assert_eq!(
+            thinking("claude-3-7-sonnet-latest", 1024, ReasoningEffort::Low),
+            None
+        );
+        for model in [
+            "claude-3-5-sonnet-20241022",
+            "claude-3-haiku-20240307",
+            "gpt-4o",
+        ] {
+            assert_eq!(
+                thinking(model, 32000, ReasoningEffort::High),
+                None,
+                "{}",
+                model
+            );
+        }
+    }
+
+    #[test]
+    fn provider_thinking_options_win_over_reasoning_effort() {
+        let protocol = AnthropicProtocol;
+        let messages = vec![user_text("hi")];
+        let options = json!({ "anthropic": { "thinking": { "type": "disabled" } } });
+
+        let body = protocol
+            .build_request(RequestBuildContext {
+                model: "claude-sonnet-4-5",
+                messages: &messages,
+                tools: None,
+                temperature: None,
+                max_tokens: Some(16000),
+                top_p: None,
+                top_k: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: None,
+                response_format: None,
+                user_id: None,
+                provider_options: Some(&options),
+                extra_body: None,
+                stream: true,
+                logprobs: false,
+                top_logprobs: None,
+                builtin_tools: &[],
+                reasoning_effort: Some(ReasoningEffort::Medium),
+            })
+            .expect("build request");
+
+        assert_eq!(body["thinking"], json!({ "type": "disabled" }));
+    }
+
+    #[test]
+    fn build_request_marks_cached_messages_with_cache_control() {
+        let protocol = AnthropicProtocol;
+        let messages = vec![
+            Message::System {
+                content: "long system prompt".to_string(),
+                provider_options: None,
+                cache: true,
+            },
+            user_text("first turn"),
+            Message::User {
+                content: MessageContent::Text("latest turn".to_string()),
+                // The frontend's existing cache hint counts as a flag too
+                provider_options: Some(json!({
+                    "anthropic": { "cacheControl": { "type": "ephemeral" } }
+                })),
+                cache: false,
+            },
+        ];
+
+        let body = protocol
+            .build_request(RequestBuildContext {
+                model: "claude-sonnet-4-5",
+                messages: &messages,
+                tools: None,
+                temperature: None,
+                max_tokens: None,
+                top_p: None,
+                top_k: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: None,
+                response_format: None,
+                user_id: None,
+                provider_options: None,
+                extra_body: None,
+                stream: true,
+                logprobs: false,
+                top_logprobs: None,
+                builtin_tools: &[],
+                reasoning_effort: None,
+            })
+            .expect("build request");
+
+        assert_eq!(
+            body["system"],
+            json!([{
+                "type": "text",
+                "text": "long system prompt",
+                "cache_control": { "type": "ephemeral" }
+            }])
+        );
+        assert!(body["messages"][0]["content"][0]
+            .get("cache_control")
+            .is_none());
+        assert_eq!(
+            body["messages"][1]["content"][0]["cache_control"],
+            json!({ "type": "ephemeral" })
+        );
+    }
+
+    #[test]
+    fn build_headers_uses_x_api_key_and_version() {
+        let protocol = AnthropicProtocol;
+        let headers = protocol.build_base_headers(HeaderBuildContext {
+            api_key: Some("sk-ant"),
+            oauth_token: None,
+            extra_headers: None,
+        });
+
+        assert_eq!(headers.get("x-api-key"), Some(&"sk-ant".to_string()));
+        assert_eq!(
+            headers.get("anthropic-version"),
+            Some(&ANTHROPIC_VERSION.to_string())
+        );
+        assert!(!headers.contains_key("Authorization"));
+    }
+}
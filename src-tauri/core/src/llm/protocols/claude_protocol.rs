@@ -1,3 +1,6 @@
+use crate::llm::protocols::anthropic_protocol::image_block;
+use crate::llm::protocols::prompt_cache::{self, MAX_CACHE_BREAKPOINTS};
+use crate::llm::protocols::request_builder::merge_extra_body;
 use crate::llm::protocols::{LlmProtocol, ProtocolStreamState, ToolCallAccum};
 use crate::llm::types::{ContentPart, Message, MessageContent, StreamEvent, ToolDefinition};
 use serde_json::{json, Value};
@@ -10,6 +13,7 @@ impl ClaudeProtocol {
     fn build_messages(&self, messages: &[Message]) -> Vec<Value> {
         let mut result = Vec::new();
         for msg in messages {
+            let start = result.len();
             match msg {
                 Message::System { .. } => {}
                 Message::User { content, .. } => {
@@ -49,6 +53,11 @@ impl ClaudeProtocol {
                     }
                 }
             }
+            if msg.is_cache_breakpoint() && result.len() > start {
+                if let Some(content) = result.last_mut().and_then(|m| m.get_mut("content")) {
+                    prompt_cache::mark_content(content);
+                }
+            }
         }
         result
     }
@@ -64,15 +73,8 @@ impl ClaudeProtocol {
                         ContentPart::Text { text } => {
                             mapped.push(json!({ "type": "text", "text": text }));
                         }
-                        ContentPart::Image { image } => {
-                            mapped.push(json!({
-                                "type": "image",
-                                "source": {
-                                    "type": "base64",
-                                    "media_type": "image/png",
-                                    "data": image
-                                }
-                            }));
+                        ContentPart::Image { image, mime_type } => {
+                            mapped.push(image_block(image, mime_type.as_deref()));
                         }
                         ContentPart::ToolCall {
                             tool_call_id,
@@ -159,7 +161,11 @@ impl LlmProtocol for ClaudeProtocol {
         let mut system = None;
         for msg in messages {
             if let Message::System { content, .. } = msg {
-                system = Some(content.clone());
+                let mut prompt = json!(content);
+                if msg.is_cache_breakpoint() {
+                    prompt_cache::mark_content(&mut prompt);
+                }
+                system = Some(prompt);
                 break;
             }
         }
@@ -172,7 +178,7 @@ impl LlmProtocol for ClaudeProtocol {
         });
 
         if let Some(system) = system {
-            body["system"] = json!(system);
+            body["system"] = system;
         }
         if let Some(tools) = self.build_tools(tools) {
             body["tools"] = Value::Array(tools);
@@ -192,15 +198,8 @@ impl LlmProtocol for ClaudeProtocol {
             }
         }
 
-        if let Some(extra) = extra_body {
-            if let Some(obj) = body.as_object_mut() {
-                if let Some(extra_obj) = extra.as_object() {
-                    for (k, v) in extra_obj {
-                        obj.insert(k.to_string(), v.clone());
-                    }
-                }
-            }
-        }
+        merge_extra_body(&mut body, extra_body);
+        prompt_cache::limit_breakpoints(&mut body, MAX_CACHE_BREAKPOINTS);
 
         Ok(body)
     }
@@ -400,6 +399,8 @@ impl LlmProtocol for ClaudeProtocol {
                         total_tokens: None,
                         cached_input_tokens: None,
                         cache_creation_input_tokens: None,
+                        timing: None,
+                        estimated: false,
                     }));
                 }
             }
@@ -570,10 +571,12 @@ mod tests {
             Message::System {
                 content: "system".to_string(),
                 provider_options: None,
+                cache: false,
             },
             Message::User {
                 content: MessageContent::Text("hi".to_string()),
                 provider_options: None,
+                cache: false,
             },
         ];
 
@@ -612,6 +615,41 @@ mod tests {
         assert_eq!(body.get("max_output_tokens"), Some(&json!(128)));
     }
 
+    #[test]
+    fn build_request_marks_cached_system_and_tool_results() {
+        let protocol = ClaudeProtocol;
+        let messages = vec![
+            Message::System {
+                content: "system".to_string(),
+                provider_options: None,
+                cache: true,
+            },
+            Message::Tool {
+                content: vec![ContentPart::ToolResult {
+                    tool_call_id: "toolu_1".to_string(),
+                    tool_name: "readFile".to_string(),
+                    output: json!({ "type": "text", "value": "fn main() {}" }),
+                }],
+                provider_options: None,
+                cache: true,
+            },
+        ];
+
+        let body = LlmProtocol::build_request(
+            &protocol, "claude-3", &messages, None, None, None, None, None, None, None,
+        )
+        .expect("build request");
+
+        assert_eq!(
+            body["system"],
+            json!([{ "type": "text", "text": "system", "cache_control": { "type": "ephemeral" } }])
+        );
+        assert_eq!(
+            body["messages"][0]["content"][0]["cache_control"],
+            json!({ "type": "ephemeral" })
+        );
+    }
+
     #[test]
     fn parse_stream_emits_reasoning_signature_delta() {
         let protocol = ClaudeProtocol;
@@ -0,0 +1,728 @@
+// Cohere v2 chat protocol
+// Targets /v2/chat, which streams typed SSE events such as content-delta and message-end
+
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    request_builder::{merge_extra_body, ProtocolRequestBuilder, RequestBuildContext},
+    stream_parser::{parse_json_data, ProtocolStreamParser, StreamParseContext, StreamParseState},
+    ToolCallAccum,
+};
+use crate::llm::types::{
+    image_url, ContentPart, Message, MessageContent, ResponseFormat, StreamEvent, ToolDefinition,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Reasoning id for thinking content blocks, which carry no id of their own
+const THINKING_ID: &str = "thinking";
+/// Reasoning id for the tool plan Cohere streams before its tool calls
+const TOOL_PLAN_ID: &str = "tool_plan";
+/// `providerOptions.cohere` keys copied into the request body as-is
+const PASSTHROUGH_OPTIONS: &[&str] = &["safety_mode", "thinking", "seed", "citation_options"];
+
+pub struct CohereProtocol;
+
+impl CohereProtocol {
+    fn build_messages(&self, messages: &[Message]) -> Vec<Value> {
+        let mut result = Vec::new();
+        for msg in messages {
+            match msg {
+                Message::System { content, .. } => {
+                    result.push(json!({ "role": "system", "content": content }));
+                }
+                Message::User { content, .. } => {
+                    result.push(json!({
+                        "role": "user",
+                        "content": self.convert_user_content(content)
+                    }));
+                }
+                Message::Assistant { content, .. } => {
+                    if let Some(message) = self.convert_assistant_message(content) {
+                        result.push(message);
+                    }
+                }
+                Message::Tool { content, .. } => {
+                    for part in content {
+                        if let ContentPart::ToolResult {
+                            tool_call_id,
+                            output,
+                            ..
+                        } = part
+                        {
+                            result.push(json!({
+                                "role": "tool",
+                                "tool_call_id": tool_call_id,
+                                "content": self.tool_output_to_string(output)
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn convert_user_content(&self, content: &MessageContent) -> Value {
+        let parts = match content {
+            MessageContent::Text(text) => return json!(text),
+            MessageContent::Parts(parts) => parts,
+        };
+
+        let mapped: Vec<Value> = parts
+            .iter()
+            .filter_map(|part| match part {
+                ContentPart::Text { text } if !text.is_empty() => {
+                    Some(json!({ "type": "text", "text": text }))
+                }
+                ContentPart::Image { image, mime_type } => {
+                    let url = image_url(image, mime_type.as_deref());
+                    Some(json!({ "type": "image_url", "image_url": { "url": url } }))
+                }
+                // Cohere has no video input; tool parts travel on their own messages
+                _ => None,
+            })
+            .collect();
+        Value::Array(mapped)
+    }
+
+    fn convert_assistant_message(&self, content: &MessageContent) -> Option<Value> {
+        let parts = match content {
+            MessageContent::Text(text) if text.is_empty() => return None,
+            MessageContent::Text(text) => {
+                return Some(json!({ "role": "assistant", "content": text }))
+            }
+            MessageContent::Parts(parts) => parts,
+        };
+
+        let mut text = String::new();
+        let mut tool_calls = Vec::new();
+        for part in parts {
+            match part {
+                ContentPart::Text { text: value } => text.push_str(value),
+                ContentPart::ToolCall {
+                    tool_call_id,
+                    tool_name,
+                    input,
+                    ..
+                } => {
+                    tool_calls.push(json!({
+                        "id": tool_call_id,
+                        "type": "function",
+                        "function": { "name": tool_name, "arguments": input.to_string() }
+                    }));
+                }
+                // Thinking and tool plans are not replayed
+                _ => {}
+            }
+        }
+
+        if text.is_empty() && tool_calls.is_empty() {
+            return None;
+        }
+        let mut message = json!({ "role": "assistant" });
+        if !text.is_empty() {
+            message["content"] = json!(text);
+        }
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = Value::Array(tool_calls);
+        }
+        Some(message)
+    }
+
+    fn tool_output_to_string(&self, output: &Value) -> String {
+        if let Some(value) = output.get("value").and_then(|v| v.as_str()) {
+            return value.to_string();
+        }
+        output.to_string()
+    }
+
+    fn build_tools(&self, tools: Option<&[ToolDefinition]>) -> Option<Vec<Value>> {
+        let tools = tools.filter(|tools| !tools.is_empty())?;
+        Some(
+            tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": tool.name,
+                            "description": tool.description,
+                            "parameters": tool.parameters
+                        }
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    pub(crate) fn map_finish_reason(reason: &str) -> String {
+        match reason {
+            "COMPLETE" | "STOP_SEQUENCE" => "stop".to_string(),
+            "MAX_TOKENS" => "length".to_string(),
+            "TOOL_CALL" => "tool_calls".to_string(),
+            other => other.to_ascii_lowercase(),
+        }
+    }
+
+    fn open_reasoning(state: &mut StreamParseState, id: &str, out: &mut Vec<StreamEvent>) {
+        if state.reasoning_id.as_deref() != Some(id) {
+            Self::close_reasoning(state, out);
+        }
+        if !state.reasoning_started {
+            state.reasoning_started = true;
+            state.reasoning_id = Some(id.to_string());
+            out.push(StreamEvent::ReasoningStart {
+                id: id.to_string(),
+                provider_metadata: None,
+            });
+        }
+    }
+
+    fn close_reasoning(state: &mut StreamParseState, out: &mut Vec<StreamEvent>) {
+        if state.reasoning_started {
+            state.reasoning_started = false;
+            let id = state
+                .reasoning_id
+                .take()
+                .unwrap_or_else(|| THINKING_ID.to_string());
+            out.push(StreamEvent::ReasoningEnd { id });
+        }
+    }
+
+    fn usage_event(usage: &Value) -> Option<StreamEvent> {
+        // `tokens` counts what the model saw; `billed_units` is the fallback
+        let counts = usage.get("tokens").or_else(|| usage.get("billed_units"))?;
+        let read = |key: &str| counts.get(key).and_then(|v| v.as_f64()).map(|v| v as i32);
+        let input_tokens = read("input_tokens").unwrap_or(0);
+        let output_tokens = read("output_tokens").unwrap_or(0);
+        Some(StreamEvent::Usage {
+            input_tokens,
+            output_tokens,
+            total_tokens: Some(input_tokens + output_tokens),
+            cached_input_tokens: usage
+                .get("cached_tokens")
+                .and_then(|v| v.as_f64())
+                .map(|v| v as i32),
+            cache_creation_input_tokens: None,
+            timing: None,
+            estimated: false,
+        })
+    }
+
+    fn collect_events(
+        &self,
+        event_type: &str,
+        payload: &Value,
+        state: &mut StreamParseState,
+        out: &mut Vec<StreamEvent>,
+    ) -> Result<(), String> {
+        let index = payload.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+        let message = payload.get("delta").and_then(|d| d.get("message"));
+
+        match event_type {
+            "content-start" | "content-delta" => {
+                let Some(content) = message.and_then(|m| m.get("content")) else {
+                    return Ok(());
+                };
+                if event_type == "content-start" {
+                    let block_type = content
+                        .get("type")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("text");
+                    state
+                        .content_block_types
+                        .insert(index as usize, block_type.to_string());
+                }
+                if let Some(thinking) = content.get("thinking").and_then(|v| v.as_str()) {
+                    if !thinking.is_empty() {
+                        Self::open_reasoning(state, THINKING_ID, out);
+                        out.push(StreamEvent::ReasoningDelta {
+                            id: THINKING_ID.to_string(),
+                            text: thinking.to_string(),
+                            provider_metadata: None,
+                        });
+                    }
+                }
+                if let Some(text) = content.get("text").and_then(|v| v.as_str()) {
+                    if !text.is_empty() {
+                        Self::close_reasoning(state, out);
+                        if !state.text_started {
+                            state.text_started = true;
+                            out.push(StreamEvent::TextStart);
+                        }
+                        out.push(StreamEvent::TextDelta {
+                            text: text.to_string(),
+                        });
+                    }
+                }
+            }
+            "content-end" => {
+                let block_type = state.content_block_types.remove(&(index as usize));
+                if block_type.as_deref() == Some("thinking") {
+                    Self::close_reasoning(state, out);
+                }
+            }
+            "tool-plan-delta" => {
+                if let Some(plan) = message
+                    .and_then(|m| m.get("tool_plan"))
+                    .and_then(|v| v.as_str())
+                    .filter(|plan| !plan.is_empty())
+                {
+                    Self::open_reasoning(state, TOOL_PLAN_ID, out);
+                    out.push(StreamEvent::ReasoningDelta {
+                        id: TOOL_PLAN_ID.to_string(),
+                        text: plan.to_string(),
+                        provider_metadata: None,
+                    });
+                }
+            }
+            "tool-call-start" => {
+                Self::close_reasoning(state, out);
+                let call = message.and_then(|m| m.get("tool_calls"));
+                let id = call
+                    .and_then(|c| c.get("id"))
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("call_{}", state.tool_call_order.len()));
+                let function = call.and_then(|c| c.get("function"));
+                let name = function
+                    .and_then(|f| f.get("name"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let arguments = function
+                    .and_then(|f| f.get("arguments"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+
+                state.tool_call_index_map.insert(index, id.clone());
+                state.tool_call_order.push(id.clone());
+                state.tool_calls.insert(
+                    id.clone(),
+                    ToolCallAccum {
+                        tool_call_id: id.clone(),
+                        tool_name: name.clone(),
+                        arguments: arguments.clone(),
+                        thought_signature: None,
+                    },
+                );
+                out.push(StreamEvent::ToolCallDelta {
+                    index: index as u32,
+                    id: Some(id),
+                    name: Some(name),
+                    arguments_chunk: arguments,
+                });
+            }
+            "tool-call-delta" => {
+                let chunk = message
+                    .and_then(|m| m.get("tool_calls"))
+                    .and_then(|c| c.get("function"))
+                    .and_then(|f| f.get("arguments"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default();
+                let accum = state
+                    .tool_call_index_map
+                    .get(&index)
+                    .and_then(|id| state.tool_calls.get_mut(id));
+                if let Some(accum) = accum {
+                    accum.arguments.push_str(chunk);
+                    out.push(StreamEvent::ToolCallDelta {
+                        index: index as u32,
+                        id: None,
+                        name: None,
+                        arguments_chunk: chunk.to_string(),
+                    });
+                }
+            }
+            "tool-call-end" => {
+                let Some(id) = state.tool_call_index_map.get(&index).cloned() else {
+                    return Ok(());
+                };
+                if let Some(accum) = state.tool_calls.get(&id) {
+                    if state.emitted_tool_calls.insert(id.clone()) {
+                        let input = if accum.arguments.trim().is_empty() {
+                            json!({})
+                        } else {
+                            serde_json::from_str(&accum.arguments)
+                                .map_err(|e| format!("Invalid Cohere tool call arguments: {}", e))?
+                        };
+                        out.push(StreamEvent::ToolCall {
+                            tool_call_id: id,
+                            tool_name: accum.tool_name.clone(),
+                            input,
+                            provider_metadata: None,
+                        });
+                    }
+                }
+            }
+            "citation-start" => {
+                let sources = message
+                    .and_then(|m| m.get("citations"))
+                    .and_then(|c| c.get("sources"))
+                    .and_then(|v| v.as_array());
+                for source in sources.into_iter().flatten() {
+                    let document = source.get("document");
+                    let Some(url) = document.and_then(|d| d.get("url")).and_then(|v| v.as_str())
+                    else {
+                        continue;
+                    };
+                    out.push(StreamEvent::Citation {
+                        url: url.to_string(),
+                        title: document
+                            .and_then(|d| d.get("title"))
+                            .and_then(|v| v.as_str())
+                            .map(str::to_string),
+                    });
+                }
+            }
+            "message-end" => {
+                Self::close_reasoning(state, out);
+                let delta = payload.get("delta");
+                let reason = delta
+                    .and_then(|d| d.get("finish_reason"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("COMPLETE");
+                if reason == "ERROR" {
+                    let message = delta
+                        .and_then(|d| d.get("error"))
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("Unknown error");
+                    out.push(StreamEvent::Error {
+                        message: format!("Cohere stopped the response: {}", message),
+                    });
+                }
+                let mut finish_reason = Self::map_finish_reason(reason);
+                if finish_reason == "stop" && !state.tool_call_order.is_empty() {
+                    finish_reason = "tool_calls".to_string();
+                }
+                state.finish_reason = Some(finish_reason);
+                if let Some(usage) = delta.and_then(|d| d.get("usage")) {
+                    out.extend(Self::usage_event(usage));
+                }
+                out.push(StreamEvent::Done {
+                    finish_reason: state.finish_reason.clone(),
+                });
+            }
+            // message-start, citation-end and unknown events carry nothing to surface
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+impl ProtocolRequestBuilder for CohereProtocol {
+    fn build_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        let mut body = json!({
+            "model": ctx.model,
+            "messages": self.build_messages(ctx.messages),
+            "stream": ctx.stream
+        });
+
+        if let Some(tools) = self.build_tools(ctx.tools) {
+            body["tools"] = Value::Array(tools);
+        }
+        if let Some(temperature) = ctx.temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let Some(max_tokens) = ctx.max_tokens {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if let Some(top_p) = ctx.top_p {
+            body["p"] = json!(top_p);
+        }
+        if let Some(top_k) = ctx.top_k {
+            body["k"] = json!(top_k);
+        }
+        if let Some(frequency_penalty) = ctx.frequency_penalty {
+            body["frequency_penalty"] = json!(frequency_penalty);
+        }
+        if let Some(presence_penalty) = ctx.presence_penalty {
+            body["presence_penalty"] = json!(presence_penalty);
+        }
+        if let Some(stop) = ctx.stop.filter(|stop| !stop.is_empty()) {
+            body["stop_sequences"] = json!(stop);
+        }
+        match ctx.response_format {
+            Some(ResponseFormat::JsonObject) => {
+                body["response_format"] = json!({ "type": "json_object" });
+            }
+            Some(ResponseFormat::JsonSchema { schema, .. }) => {
+                body["response_format"] = json!({ "type": "json_object", "json_schema": schema });
+            }
+            None => {}
+        }
+
+        if let Some(cohere) = ctx.provider_options.and_then(|opts| opts.get("cohere")) {
+            for key in PASSTHROUGH_OPTIONS {
+                if let Some(value) = cohere.get(*key) {
+                    body[*key] = value.clone();
+                }
+            }
+        }
+
+        merge_extra_body(&mut body, ctx.extra_body);
+
+        Ok(body)
+    }
+}
+
+impl ProtocolStreamParser for CohereProtocol {
+    fn parse_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        let Some(payload) = parse_json_data(state, ctx.data)? else {
+            return Ok(None);
+        };
+        let event_type = payload
+            .get("type")
+            .and_then(|v| v.as_str())
+            .or(ctx.event_type)
+            .unwrap_or_default()
+            .to_string();
+
+        let mut events = Vec::new();
+        self.collect_events(&event_type, &payload, state, &mut events)?;
+        if events.is_empty() {
+            return Ok(None);
+        }
+
+        let first = events.remove(0);
+        state.pending_events.extend(events);
+        Ok(Some(first))
+    }
+}
+
+impl ProtocolHeaderBuilder for CohereProtocol {
+    fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        if let Some(token) = ctx.api_key.or(ctx.oauth_token) {
+            headers.insert("Authorization".to_string(), format!("Bearer {}", token));
+        }
+        if let Some(extra) = ctx.extra_headers {
+            for (k, v) in extra {
+                headers.insert(k.to_string(), v.to_string());
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::protocols::sse::SseLineReader;
+
+    /// Split an SSE transcript into records and run each through the parser
+    fn parse_transcript(body: &str) -> Vec<StreamEvent> {
+        let protocol = CohereProtocol;
+        let mut state = StreamParseState::default();
+        let mut reader = SseLineReader::new();
+        reader.feed(body.as_bytes());
+        let mut events = Vec::new();
+
+        for record in reader {
+            let record = record.expect("utf8 record");
+            if let Some(event) = protocol
+                .parse_stream_event(
+                    StreamParseContext {
+                        event_type: record.event.as_deref(),
+                        data: &record.data,
+                    },
+                    &mut state,
+                )
+                .expect("parse frame")
+            {
+                events.push(event);
+            }
+            events.append(&mut state.pending_events);
+        }
+
+        events
+    }
+
+    const TEXT_TRANSCRIPT: &str = r#"event: message-start
+data: {"id":"m1","type":"message-start","delta":{"message":{"role":"assistant","content":[],"tool_plan":"","tool_calls":[],"citations":[]}}}
+
+event: content-start
+data: {"type":"content-start","index":0,"delta":{"message":{"content":{"type":"text","text":""}}}}
+
+event: content-delta
+data: {"type":"content-delta","index":0,"delta":{"message":{"content":{"text":"Hel"}}}}
+
+event: content-delta
+data: {"type":"content-delta","index":0,"delta":{"message":{"content":{"text":"lo"}}}}
+
+event: content-end
+data: {"type":"content-end","index":0}
+
+event: message-end
+data: {"type":"message-end","delta":{"finish_reason":"COMPLETE","usage":{"billed_units":{"input_tokens":5,"output_tokens":2},"tokens":{"input_tokens":71,"output_tokens":2}}}}
+
+"#;
+
+    const TOOL_CALL_TRANSCRIPT: &str = r#"event: tool-plan-delta
+data: {"type":"tool-plan-delta","delta":{"message":{"tool_plan":"I will read the file."}}}
+
+event: tool-call-start
+data: {"type":"tool-call-start","index":0,"delta":{"message":{"tool_calls":{"id":"readFile_abc","type":"function","function":{"name":"readFile","arguments":""}}}}}
+
+event: tool-call-delta
+data: {"type":"tool-call-delta","index":0,"delta":{"message":{"tool_calls":{"function":{"arguments":"{\"path\":"}}}}}
+
+event: tool-call-delta
+data: {"type":"tool-call-delta","index":0,"delta":{"message":{"tool_calls":{"function":{"arguments":" \"a.rs\"}"}}}}}
+
+event: tool-call-end
+data: {"type":"tool-call-end","index":0}
+
+event: message-end
+data: {"type":"message-end","delta":{"finish_reason":"TOOL_CALL"}}
+
+"#;
+
+    #[test]
+    fn parses_text_stream_transcript() {
+        let events =
+            serde_json::to_value(parse_transcript(TEXT_TRANSCRIPT)).expect("serialize events");
+
+        assert_eq!(
+            events,
+            json!([
+                { "type": "text-start" },
+                { "type": "text-delta", "text": "Hel" },
+                { "type": "text-delta", "text": "lo" },
+                {
+                    "type": "usage",
+                    "input_tokens": 71,
+                    "output_tokens": 2,
+                    "total_tokens": 73,
+                    "cached_input_tokens": null,
+                    "cache_creation_input_tokens": null
+                },
+                { "type": "done", "finish_reason": "stop" }
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_tool_plan_and_streamed_tool_call() {
+        let events = parse_transcript(TOOL_CALL_TRANSCRIPT);
+
+        assert!(matches!(
+            &events[0],
+            StreamEvent::ReasoningStart { id, .. } if id == TOOL_PLAN_ID
+        ));
+        assert!(matches!(
+            &events[1],
+            StreamEvent::ReasoningDelta { text, .. } if text == "I will read the file."
+        ));
+        assert!(matches!(&events[2], StreamEvent::ReasoningEnd { id } if id == TOOL_PLAN_ID));
+        let tool_call = events
+            .iter()
+            .find_map(|event| match event {
+                StreamEvent::ToolCall {
+                    tool_call_id,
+                    tool_name,
+                    input,
+                    ..
+                } => Some((tool_call_id.as_str(), tool_name.as_str(), input.clone())),
+                _ => None,
+            })
+            .expect("tool call");
+        assert_eq!(
+            tool_call,
+            ("readFile_abc", "readFile", json!({ "path": "a.rs" }))
+        );
+        assert!(matches!(
+            events.last(),
+            Some(StreamEvent::Done { finish_reason: Some(reason) }) if reason == "tool_calls"
+        ));
+    }
+
+    #[test]
+    fn build_request_maps_messages_into_cohere_shape() {
+        let messages = vec![
+            Message::System {
+                content: "Be brief.".to_string(),
+                provider_options: None,
+                cache: false,
+            },
+            Message::User {
+                content: MessageContent::Text("Read a.rs".to_string()),
+                provider_options: None,
+                cache: false,
+            },
+            Message::Assistant {
+                content: MessageContent::Parts(vec![ContentPart::ToolCall {
+                    tool_call_id: "call_1".to_string(),
+                    tool_name: "readFile".to_string(),
+                    input: json!({ "path": "a.rs" }),
+                    provider_metadata: None,
+                }]),
+                provider_options: None,
+                cache: false,
+            },
+            Message::Tool {
+                content: vec![ContentPart::ToolResult {
+                    tool_call_id: "call_1".to_string(),
+                    tool_name: "readFile".to_string(),
+                    output: json!({ "type": "text", "value": "fn main() {}" }),
+                }],
+                provider_options: None,
+                cache: false,
+            },
+        ];
+        let stop = vec!["END".to_string()];
+
+        let body = CohereProtocol
+            .build_request(RequestBuildContext {
+                model: "command-a-03-2025",
+                messages: &messages,
+                tools: None,
+                temperature: Some(0.3),
+                max_tokens: None,
+                top_p: Some(0.9),
+                top_k: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: Some(stop.as_slice()),
+                response_format: Some(&ResponseFormat::JsonObject),
+                user_id: None,
+                provider_options: Some(&json!({ "cohere": { "safety_mode": "STRICT" } })),
+                extra_body: None,
+                stream: true,
+                logprobs: false,
+                top_logprobs: None,
+                builtin_tools: &[],
+                reasoning_effort: None,
+            })
+            .expect("build request");
+
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "system", "content": "Be brief." },
+                { "role": "user", "content": "Read a.rs" },
+                {
+                    "role": "assistant",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": { "name": "readFile", "arguments": "{\"path\":\"a.rs\"}" }
+                    }]
+                },
+                { "role": "tool", "tool_call_id": "call_1", "content": "fn main() {}" }
+            ])
+        );
+        assert_eq!(body["p"], json!(0.9));
+        assert_eq!(body["stop_sequences"], json!(["END"]));
+        assert_eq!(body["response_format"], json!({ "type": "json_object" }));
+        assert_eq!(body["safety_mode"], json!("STRICT"));
+        assert_eq!(body["stream"], json!(true));
+    }
+}
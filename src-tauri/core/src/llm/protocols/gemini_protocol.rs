@@ -0,0 +1,847 @@
+// Google Gemini generateContent protocol
+// Native :streamGenerateContent requests with ?key= auth, streamed as a JSON array
+
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    request_builder::{merge_extra_body, ProtocolRequestBuilder, RequestBuildContext},
+    stream_parser::{
+        self, ProtocolStreamParser, StreamFormat, StreamParseContext, StreamParseState,
+    },
+};
+use crate::llm::types::{
+    BuiltinTool, ContentPart, ImageSource, Message, MessageContent, StreamEvent, ToolDefinition,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Mime type sent for image URLs whose type is neither given nor guessable from the path
+const DEFAULT_URL_IMAGE_MIME: &str = "image/jpeg";
+
+/// Image part; http(s) URLs become `fileData` for Gemini to fetch
+fn image_part(image: &str, mime_type: Option<&str>) -> Value {
+    match ImageSource::of(image, mime_type) {
+        ImageSource::Base64 { mime_type, data } => json!({
+            "inlineData": { "mimeType": mime_type, "data": data }
+        }),
+        ImageSource::Url(url) => {
+            let path = url.split(['?', '#']).next().unwrap_or(url);
+            let mime_type = mime_type
+                .filter(|m| !m.trim().is_empty())
+                .map(str::to_string)
+                .or_else(|| {
+                    mime_guess::from_path(path)
+                        .first()
+                        .filter(|guess| guess.type_() == mime::IMAGE)
+                        .map(|guess| guess.essence_str().to_string())
+                })
+                .unwrap_or_else(|| DEFAULT_URL_IMAGE_MIME.to_string());
+            json!({ "fileData": { "mimeType": mime_type, "fileUri": url } })
+        }
+    }
+}
+
+/// Reasoning id used for Gemini thought parts, which carry no id of their own
+const THOUGHT_ID: &str = "thought";
+
+pub struct GeminiProtocol;
+
+/// Whether `model` takes a `thinkingConfig`: Gemini 2.5 and later
+fn supports_thinking(model: &str) -> bool {
+    let model = model.trim_start_matches("models/").to_ascii_lowercase();
+    let Some(version) = model.strip_prefix("gemini-") else {
+        return false;
+    };
+    let mut numbers = version
+        .split('-')
+        .next()
+        .unwrap_or_default()
+        .split('.')
+        .map(|part| part.parse::<u32>().ok());
+    match (numbers.next().flatten(), numbers.next().flatten()) {
+        (Some(major), minor) => (major, minor.unwrap_or(0)) >= (2, 5),
+        (None, _) => false,
+    }
+}
+
+impl GeminiProtocol {
+    /// Endpoint path relative to the versioned base URL (e.g. `.../v1beta`)
+    pub fn endpoint_path(model: &str, stream: bool) -> String {
+        let method = if stream {
+            "streamGenerateContent"
+        } else {
+            "generateContent"
+        };
+        format!("models/{}:{}", model.trim_start_matches("models/"), method)
+    }
+
+    /// Append the `key` query parameter Gemini uses for API key auth
+    pub fn with_api_key(url: &str, api_key: &str) -> String {
+        let separator = if url.contains('?') { '&' } else { '?' };
+        format!("{}{}key={}", url, separator, api_key)
+    }
+
+    fn build_system_instruction(&self, messages: &[Message]) -> Option<Value> {
+        let parts: Vec<Value> = messages
+            .iter()
+            .filter_map(|msg| match msg {
+                Message::System { content, .. } if !content.trim().is_empty() => {
+                    Some(json!({ "text": content }))
+                }
+                _ => None,
+            })
+            .collect();
+        if parts.is_empty() {
+            None
+        } else {
+            Some(json!({ "parts": parts }))
+        }
+    }
+
+    fn build_contents(&self, messages: &[Message]) -> Vec<Value> {
+        let mut contents = Vec::new();
+        for msg in messages {
+            let (role, parts) = match msg {
+                Message::System { .. } => continue,
+                Message::User { content, .. } => ("user", self.convert_content(content)),
+                Message::Assistant { content, .. } => ("model", self.convert_content(content)),
+                Message::Tool { content, .. } => (
+                    "user",
+                    content
+                        .iter()
+                        .filter_map(|part| match part {
+                            ContentPart::ToolResult {
+                                tool_name, output, ..
+                            } => Some(json!({
+                                "functionResponse": {
+                                    "name": tool_name,
+                                    "response": { "content": self.tool_output_value(output) }
+                                }
+                            })),
+                            _ => None,
+                        })
+                        .collect(),
+                ),
+            };
+            if !parts.is_empty() {
+                contents.push(json!({ "role": role, "parts": parts }));
+            }
+        }
+        contents
+    }
+
+    fn convert_content(&self, content: &MessageContent) -> Vec<Value> {
+        match content {
+            MessageContent::Text(text) => {
+                if text.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![json!({ "text": text })]
+                }
+            }
+            MessageContent::Parts(parts) => {
+                let mut mapped = Vec::new();
+                for part in parts {
+                    match part {
+                        ContentPart::Text { text } => {
+                            if !text.is_empty() {
+                                mapped.push(json!({ "text": text }));
+                            }
+                        }
+                        ContentPart::Image { image, mime_type } => {
+                            mapped.push(image_part(image, mime_type.as_deref()));
+                        }
+                        ContentPart::Video { video, mime_type } => {
+                            mapped.push(json!({
+                                "inlineData": {
+                                    "mimeType": mime_type.as_deref().unwrap_or("video/mp4"),
+                                    "data": video
+                                }
+                            }));
+                        }
+                        ContentPart::ToolCall {
+                            tool_name,
+                            input,
+                            provider_metadata,
+                            ..
+                        } => {
+                            let mut call = json!({
+                                "functionCall": { "name": tool_name, "args": input }
+                            });
+                            // Gemini 3 rejects replayed function calls without their signature
+                            if let Some(signature) = provider_metadata
+                                .as_ref()
+                                .and_then(|m| m.get("google"))
+                                .and_then(|g| g.get("thoughtSignature"))
+                            {
+                                call["thoughtSignature"] = signature.clone();
+                            }
+                            mapped.push(call);
+                        }
+                        // Thought summaries are not replayed to Gemini
+                        ContentPart::Reasoning { .. } | ContentPart::ToolResult { .. } => {}
+                    }
+                }
+                mapped
+            }
+        }
+    }
+
+    fn tool_output_value(&self, output: &Value) -> Value {
+        output
+            .get("value")
+            .cloned()
+            .unwrap_or_else(|| output.clone())
+    }
+
+    fn build_tools(&self, tools: Option<&[ToolDefinition]>) -> Option<Value> {
+        let tools = tools?;
+        if tools.is_empty() {
+            return None;
+        }
+        let declarations: Vec<Value> = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters
+                })
+            })
+            .collect();
+        Some(json!([{ "functionDeclarations": declarations }]))
+    }
+
+    pub(crate) fn map_finish_reason(reason: &str) -> String {
+        match reason {
+            "STOP" => "stop".to_string(),
+            "MAX_TOKENS" => "length".to_string(),
+            "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII"
+            | "IMAGE_SAFETY" => "content_filter".to_string(),
+            other => other.to_ascii_lowercase(),
+        }
+    }
+
+    fn close_reasoning(state: &mut StreamParseState, out: &mut Vec<StreamEvent>) {
+        if state.reasoning_started {
+            state.reasoning_started = false;
+            let id = state
+                .reasoning_id
+                .take()
+                .unwrap_or_else(|| THOUGHT_ID.to_string());
+            out.push(StreamEvent::ReasoningEnd { id });
+        }
+    }
+
+    fn usage_event(usage: &Value) -> StreamEvent {
+        let read = |key: &str| usage.get(key).and_then(|v| v.as_i64()).map(|v| v as i32);
+        let input_tokens = read("promptTokenCount").unwrap_or(0);
+        // Thought tokens are billed as output
+        let output_tokens =
+            read("candidatesTokenCount").unwrap_or(0) + read("thoughtsTokenCount").unwrap_or(0);
+        StreamEvent::Usage {
+            input_tokens,
+            output_tokens,
+            total_tokens: read("totalTokenCount").or(Some(input_tokens + output_tokens)),
+            cached_input_tokens: read("cachedContentTokenCount"),
+            cache_creation_input_tokens: None,
+            timing: None,
+            estimated: false,
+        }
+    }
+
+    fn collect_events(
+        &self,
+        payload: &Value,
+        state: &mut StreamParseState,
+        out: &mut Vec<StreamEvent>,
+    ) -> Result<(), String> {
+        if let Some(elements) = payload.as_array() {
+            for element in elements {
+                self.collect_events(element, state, out)?;
+            }
+            return Ok(());
+        }
+
+        if let Some(error) = payload.get("error") {
+            let status = error
+                .get("status")
+                .and_then(|v| v.as_str())
+                .unwrap_or("error");
+            let message = error
+                .get("message")
+                .and_then(|v| v.as_str())
+                .unwrap_or("Unknown error");
+            return Err(format!("Gemini stream error ({}): {}", status, message));
+        }
+
+        // Every chunk names the served model; only the first produces a Start
+        let model_version = payload.get("modelVersion").and_then(|v| v.as_str());
+        out.extend(stream_parser::start_event(state, model_version, None));
+
+        // The whole prompt was rejected, no candidates follow
+        if let Some(reason) = payload
+            .get("promptFeedback")
+            .and_then(|f| f.get("blockReason"))
+            .and_then(|v| v.as_str())
+        {
+            state.finish_reason = Some("content_filter".to_string());
+            out.push(StreamEvent::Error {
+                message: format!("Gemini blocked the prompt: {}", reason),
+            });
+            if let Some(usage) = payload.get("usageMetadata") {
+                out.push(Self::usage_event(usage));
+            }
+            out.push(StreamEvent::Done {
+                finish_reason: state.finish_reason.clone(),
+            });
+            return Ok(());
+        }
+
+        // Only the first candidate is streamed; extra candidates are ignored
+        let Some(candidate) = payload
+            .get("candidates")
+            .and_then(|v| v.as_array())
+            .and_then(|candidates| {
+                candidates
+                    .iter()
+                    .find(|c| c.get("index").and_then(|v| v.as_u64()).unwrap_or(0) == 0)
+            })
+        else {
+            return Ok(());
+        };
+
+        let parts = candidate
+            .get("content")
+            .and_then(|c| c.get("parts"))
+            .and_then(|v| v.as_array());
+        for part in parts.into_iter().flatten() {
+            if let Some(call) = part.get("functionCall") {
+                Self::close_reasoning(state, out);
+                let id = call
+                    .get("id")
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| format!("call_{}", state.tool_call_order.len()));
+                state.tool_call_order.push(id.clone());
+                let provider_metadata = part
+                    .get("thoughtSignature")
+                    .map(|sig| json!({ "google": { "thoughtSignature": sig } }));
+                out.push(StreamEvent::ToolCall {
+                    tool_call_id: id,
+                    tool_name: call
+                        .get("name")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    input: call.get("args").cloned().unwrap_or_else(|| json!({})),
+                    provider_metadata,
+                });
+                continue;
+            }
+
+            let Some(text) = part.get("text").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let is_thought = part
+                .get("thought")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+
+            if is_thought {
+                if !state.reasoning_started {
+                    state.reasoning_started = true;
+                    state.reasoning_id = Some(THOUGHT_ID.to_string());
+                    out.push(StreamEvent::ReasoningStart {
+                        id: THOUGHT_ID.to_string(),
+                        provider_metadata: None,
+                    });
+                }
+                out.push(StreamEvent::ReasoningDelta {
+                    id: THOUGHT_ID.to_string(),
+                    text: text.to_string(),
+                    provider_metadata: None,
+                });
+                continue;
+            }
+
+            Self::close_reasoning(state, out);
+            if text.is_empty() {
+                continue;
+            }
+            if !state.text_started {
+                state.text_started = true;
+                out.push(StreamEvent::TextStart);
+            }
+            out.push(StreamEvent::TextDelta {
+                text: text.to_string(),
+            });
+        }
+
+        if let Some(reason) = candidate.get("finishReason").and_then(|v| v.as_str()) {
+            Self::close_reasoning(state, out);
+            let mut finish_reason = Self::map_finish_reason(reason);
+            if finish_reason == "stop" && !state.tool_call_order.is_empty() {
+                finish_reason = "tool_calls".to_string();
+            }
+            if finish_reason == "content_filter" {
+                out.push(StreamEvent::Error {
+                    message: format!("Gemini stopped the response: {}", reason),
+                });
+            }
+            state.finish_reason = Some(finish_reason);
+            if let Some(usage) = payload.get("usageMetadata") {
+                out.push(Self::usage_event(usage));
+            }
+            out.push(StreamEvent::Done {
+                finish_reason: state.finish_reason.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl ProtocolRequestBuilder for GeminiProtocol {
+    fn build_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        let mut body = json!({
+            "contents": self.build_contents(ctx.messages)
+        });
+
+        if let Some(system) = self.build_system_instruction(ctx.messages) {
+            body["systemInstruction"] = system;
+        }
+        if let Some(tools) = self.build_tools(ctx.tools) {
+            body["tools"] = tools;
+        }
+        if !ctx.builtin_tools.is_empty() {
+            let grounding = ctx.builtin_tools.iter().map(|tool| match tool {
+                BuiltinTool::WebSearch => json!({ "google_search": {} }),
+                BuiltinTool::CodeInterpreter => json!({ "code_execution": {} }),
+            });
+            match body["tools"].as_array_mut() {
+                Some(tools) => tools.extend(grounding),
+                None => body["tools"] = Value::Array(grounding.collect()),
+            }
+        }
+
+        let mut generation_config = serde_json::Map::new();
+        if let Some(temperature) = ctx.temperature {
+            generation_config.insert("temperature".to_string(), json!(temperature));
+        }
+        if let Some(top_p) = ctx.top_p {
+            generation_config.insert("topP".to_string(), json!(top_p));
+        }
+        if let Some(top_k) = ctx.top_k {
+            generation_config.insert("topK".to_string(), json!(top_k));
+        }
+        if let Some(max_tokens) = ctx.max_tokens {
+            generation_config.insert("maxOutputTokens".to_string(), json!(max_tokens));
+        }
+
+        // providerOptions.google.thinkingConfig replaces the effort budget
+        if let Some(effort) = ctx
+            .reasoning_effort
+            .filter(|_| supports_thinking(ctx.model))
+        {
+            generation_config.insert(
+                "thinkingConfig".to_string(),
+                json!({ "thinkingBudget": effort.budget_tokens() }),
+            );
+        }
+        if let Some(google) = ctx.provider_options.and_then(|opts| opts.get("google")) {
+            if let Some(thinking) = google.get("thinkingConfig") {
+                generation_config.insert("thinkingConfig".to_string(), thinking.clone());
+            }
+            if let Some(safety) = google.get("safetySettings") {
+                body["safetySettings"] = safety.clone();
+            }
+        }
+
+        if !generation_config.is_empty() {
+            body["generationConfig"] = Value::Object(generation_config);
+        }
+
+        merge_extra_body(&mut body, ctx.extra_body);
+
+        Ok(body)
+    }
+}
+
+impl ProtocolStreamParser for GeminiProtocol {
+    fn parse_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        let payload: Value = serde_json::from_str(ctx.data).map_err(|e| e.to_string())?;
+
+        let mut events = Vec::new();
+        self.collect_events(&payload, state, &mut events)?;
+        if events.is_empty() {
+            return Ok(None);
+        }
+
+        let first = events.remove(0);
+        state.pending_events.extend(events);
+        Ok(Some(first))
+    }
+
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::JsonArray
+    }
+}
+
+impl ProtocolHeaderBuilder for GeminiProtocol {
+    fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        // API keys travel in the ?key= query parameter, only OAuth tokens use a header
+        if ctx.api_key.is_none() {
+            if let Some(token) = ctx.oauth_token {
+                headers.insert("Authorization".to_string(), format!("Bearer {}", token));
+            }
+        }
+        if let Some(extra) = ctx.extra_headers {
+            for (k, v) in extra {
+                headers.insert(k.to_string(), v.to_string());
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::protocols::stream_parser::take_json_array_element;
+    use crate::llm::types::ReasoningEffort;
+    use serde_json::json;
+
+    /// Feed a streamed JSON array body through the framing and parser in small chunks
+    fn parse_body(body: &str) -> Vec<StreamEvent> {
+        let protocol = GeminiProtocol;
+        let mut state = StreamParseState::default();
+        let mut buffer = Vec::new();
+        let mut events = Vec::new();
+
+        for chunk in body.as_bytes().chunks(7) {
+            buffer.extend_from_slice(chunk);
+            while let Some(element) = take_json_array_element(&mut buffer) {
+                let data = String::from_utf8(element).expect("utf8 element");
+                if let Some(event) = protocol
+                    .parse_stream_event(
+                        StreamParseContext {
+                            event_type: None,
+                            data: &data,
+                        },
+                        &mut state,
+                    )
+                    .expect("parse element")
+                {
+                    events.push(event);
+                }
+                events.append(&mut state.pending_events);
+            }
+        }
+
+        events
+    }
+
+    fn context<'a>(
+        messages: &'a [Message],
+        provider_options: Option<&'a Value>,
+    ) -> RequestBuildContext<'a> {
+        RequestBuildContext {
+            model: "gemini-2.5-pro",
+            messages,
+            tools: None,
+            temperature: Some(0.2),
+            max_tokens: Some(1024),
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            provider_options,
+            extra_body: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            builtin_tools: &[],
+            reasoning_effort: None,
+        }
+    }
+
+    fn build(messages: &[Message], provider_options: Option<&Value>) -> Value {
+        GeminiProtocol
+            .build_request(context(messages, provider_options))
+            .expect("build request")
+    }
+
+    #[test]
+    fn image_parts_unwrap_data_urls_and_send_http_urls_as_file_data() {
+        assert_eq!(
+            image_part("data:image/gif;base64,R0lGODlh", Some("image/png")),
+            json!({ "inlineData": { "mimeType": "image/gif", "data": "R0lGODlh" } })
+        );
+        assert_eq!(
+            image_part("https://example.com/diagram.webp?v=2", None),
+            json!({
+                "fileData": {
+                    "mimeType": "image/webp",
+                    "fileUri": "https://example.com/diagram.webp?v=2"
+                }
+            })
+        );
+        assert_eq!(
+            image_part("https://example.com/render", None)["fileData"]["mimeType"],
+            DEFAULT_URL_IMAGE_MIME
+        );
+    }
+
+    #[test]
+    fn parses_multi_candidate_stream_using_first_candidate() {
+        let body = r#"[{"candidates": [{"content": {"parts": [{"text": "Hello"}],"role": "model"},"index": 0},{"content": {"parts": [{"text": "Bonjour"}],"role": "model"},"index": 1}]}
+,
+{"candidates": [{"content": {"parts": [{"text": " {world}"}],"role": "model"},"finishReason": "STOP","index": 0},{"content": {"parts": [{"text": " monde"}],"role": "model"},"finishReason": "STOP","index": 1}],"usageMetadata": {"promptTokenCount": 12,"candidatesTokenCount": 6,"totalTokenCount": 18}}
+]"#;
+
+        let events = serde_json::to_value(parse_body(body)).expect("serialize events");
+
+        assert_eq!(
+            events,
+            json!([
+                { "type": "text-start" },
+                { "type": "text-delta", "text": "Hello" },
+                { "type": "text-delta", "text": " {world}" },
+                {
+                    "type": "usage",
+                    "input_tokens": 12,
+                    "output_tokens": 6,
+                    "total_tokens": 18,
+                    "cached_input_tokens": null,
+                    "cache_creation_input_tokens": null
+                },
+                { "type": "done", "finish_reason": "stop" }
+            ])
+        );
+    }
+
+    #[test]
+    fn starts_with_the_served_model_version() {
+        let body = r#"[{"candidates": [{"content": {"parts": [{"text": "Hi"}],"role": "model"},"index": 0}],"modelVersion": "gemini-2.5-flash-preview-05-20"},
+{"candidates": [{"content": {"parts": [{"text": "!"}],"role": "model"},"finishReason": "STOP","index": 0}],"modelVersion": "gemini-2.5-flash-preview-05-20"}
+]"#;
+
+        let events = serde_json::to_value(parse_body(body)).expect("serialize events");
+
+        assert_eq!(
+            events,
+            json!([
+                { "type": "start", "model": "gemini-2.5-flash-preview-05-20" },
+                { "type": "text-start" },
+                { "type": "text-delta", "text": "Hi" },
+                { "type": "text-delta", "text": "!" },
+                { "type": "done", "finish_reason": "stop" }
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_safety_blocked_prompt() {
+        let body = r#"[{"promptFeedback": {"blockReason": "SAFETY","safetyRatings": [{"category": "HARM_CATEGORY_DANGEROUS_CONTENT","probability": "HIGH"}]},"usageMetadata": {"promptTokenCount": 9,"totalTokenCount": 9}}]"#;
+
+        let events = serde_json::to_value(parse_body(body)).expect("serialize events");
+
+        assert_eq!(
+            events,
+            json!([
+                { "type": "error", "message": "Gemini blocked the prompt: SAFETY" },
+                {
+                    "type": "usage",
+                    "input_tokens": 9,
+                    "output_tokens": 0,
+                    "total_tokens": 9,
+                    "cached_input_tokens": null,
+                    "cache_creation_input_tokens": null
+                },
+                { "type": "done", "finish_reason": "content_filter" }
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_safety_stopped_candidate() {
+        let body = r#"[{"candidates": [{"content": {"parts": [{"text": "Sure"}],"role": "model"},"index": 0}]},
+{"candidates": [{"finishReason": "SAFETY","index": 0,"safetyRatings": [{"category": "HARM_CATEGORY_HARASSMENT","probability": "MEDIUM"}]}]}]"#;
+
+        let events = serde_json::to_value(parse_body(body)).expect("serialize events");
+
+        assert_eq!(
+            events,
+            json!([
+                { "type": "text-start" },
+                { "type": "text-delta", "text": "Sure" },
+                { "type": "error", "message": "Gemini stopped the response: SAFETY" },
+                { "type": "done", "finish_reason": "content_filter" }
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_thoughts_and_function_calls() {
+        let body = r#"[{"candidates": [{"content": {"parts": [{"text": "Need the file","thought": true}],"role": "model"},"index": 0}]},
+{"candidates": [{"content": {"parts": [{"functionCall": {"name": "readFile","args": {"path": "a.rs"}},"thoughtSignature": "sig"}],"role": "model"},"finishReason": "STOP","index": 0}]}]"#;
+
+        let events = serde_json::to_value(parse_body(body)).expect("serialize events");
+
+        assert_eq!(
+            events,
+            json!([
+                { "type": "reasoning-start", "id": "thought", "provider_metadata": null },
+                {
+                    "type": "reasoning-delta",
+                    "id": "thought",
+                    "text": "Need the file",
+                    "provider_metadata": null
+                },
+                { "type": "reasoning-end", "id": "thought" },
+                {
+                    "type": "tool-call",
+                    "toolCallId": "call_0",
+                    "toolName": "readFile",
+                    "input": { "path": "a.rs" },
+                    "provider_metadata": { "google": { "thoughtSignature": "sig" } }
+                },
+                { "type": "done", "finish_reason": "tool_calls" }
+            ])
+        );
+    }
+
+    #[test]
+    fn build_request_maps_roles_and_system_instruction() {
+        let messages = vec![
+            Message::System {
+                content: "Be brief.".to_string(),
+                provider_options: None,
+                cache: false,
+            },
+            Message::User {
+                content: MessageContent::Text("hi".to_string()),
+                provider_options: None,
+                cache: false,
+            },
+            Message::Assistant {
+                content: MessageContent::Text("hello".to_string()),
+                provider_options: None,
+                cache: false,
+            },
+        ];
+        let options = json!({ "google": { "thinkingConfig": { "thinkingBudget": 512 } } });
+
+        let body = build(&messages, Some(&options));
+
+        assert_eq!(
+            body["systemInstruction"],
+            json!({ "parts": [{ "text": "Be brief." }] })
+        );
+        assert_eq!(
+            body["contents"],
+            json!([
+                { "role": "user", "parts": [{ "text": "hi" }] },
+                { "role": "model", "parts": [{ "text": "hello" }] }
+            ])
+        );
+        assert_eq!(body["generationConfig"]["maxOutputTokens"], json!(1024));
+        assert_eq!(
+            body["generationConfig"]["thinkingConfig"],
+            json!({ "thinkingBudget": 512 })
+        );
+        assert!(body.get("model").is_none());
+    }
+
+    #[test]
+    fn reasoning_effort_sets_the_thinking_budget() {
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let thinking = |model, effort| {
+            let body = GeminiProtocol
+                .build_request(RequestBuildContext {
+                    model,
+                    reasoning_effort: Some(effort),
+                    ..context(&messages, None)
+                })
+                .expect("build request");
+            body["generationConfig"].get("thinkingConfig").cloned()
+        };
+
+        for (effort, budget) in [
+            (ReasoningEffort::Low, 1024),
+            (ReasoningEffort::Medium, 8192),
+            (ReasoningEffort::High, 24576),
+        ] {
+            assert_eq!(
+                thinking("gemini-2.5-flash", effort),
+                Some(json!({ "thinkingBudget": budget })),
+                "{:?}",
+                effort
+            );
+        }
+        assert!(thinking("models/gemini-3-pro-preview", ReasoningEffort::Low).is_some());
+        for model in ["gemini-2.0-flash", "gemini-1.5-pro", "gemma-3-27b-it"] {
+            assert_eq!(thinking(model, ReasoningEffort::High), None, "{}", model);
+        }
+    }
+
+    #[test]
+    fn builtin_tools_become_grounding_tools() {
+        let messages = vec![Message::User {
+            content: MessageContent::Text("latest rust release?".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+
+        let body = GeminiProtocol
+            .build_request(RequestBuildContext {
+                builtin_tools: &[BuiltinTool::WebSearch, BuiltinTool::CodeInterpreter],
+                ..context(&messages, None)
+            })
+            .expect("build request");
+
+        assert_eq!(
+            body["tools"],
+            json!([{ "google_search": {} }, { "code_execution": {} }])
+        );
+    }
+
+    #[test]
+    fn endpoint_path_and_api_key_query() {
+        assert_eq!(
+            GeminiProtocol::endpoint_path("gemini-2.5-pro", true),
+            "models/gemini-2.5-pro:streamGenerateContent"
+        );
+        assert_eq!(
+            GeminiProtocol::endpoint_path("models/gemini-2.5-pro", false),
+            "models/gemini-2.5-pro:generateContent"
+        );
+        assert_eq!(
+            GeminiProtocol::with_api_key(
+                "https://example.com/v1beta/models/x:generateContent",
+                "k"
+            ),
+            "https://example.com/v1beta/models/x:generateContent?key=k"
+        );
+
+        let headers = GeminiProtocol.build_base_headers(HeaderBuildContext {
+            api_key: Some("k"),
+            oauth_token: None,
+            extra_headers: None,
+        });
+        assert!(!headers.contains_key("Authorization"));
+    }
+}
@@ -4,7 +4,10 @@ use std::collections::{HashMap, HashSet};
 
 // Re-export new modular traits
 pub mod header_builder;
+pub mod multipart;
 pub mod request_builder;
+pub mod response_parser;
+pub mod sse;
 pub mod stream_parser;
 
 pub use header_builder::ProtocolHeaderBuilder;
@@ -63,6 +66,7 @@ pub struct ProtocolStreamState {
     pub current_thinking_id: Option<String>,
     pub pending_events: Vec<StreamEvent>,
     pub text_started: bool,
+    pub stream_started: bool,
     pub content_block_types: HashMap<usize, String>,
     pub content_block_ids: HashMap<usize, String>,
     pub reasoning_started: bool,
@@ -92,6 +96,11 @@ pub struct ToolCallAccum {
     pub thought_signature: Option<String>,
 }
 
+pub mod anthropic_protocol;
 pub mod claude_protocol;
+pub mod cohere_protocol;
+pub mod gemini_protocol;
+pub mod ollama_protocol;
 pub mod openai_protocol;
 pub mod openai_responses_protocol;
+pub mod prompt_cache;
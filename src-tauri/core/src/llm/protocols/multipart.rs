@@ -0,0 +1,182 @@
+// Multipart upload of vision images
+// Providers that take image files next to the JSON payload get the raw bytes instead of base64
+// data URLs, which are a third larger and count against the request size limit
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+
+/// Form field holding the JSON request body
+pub const PAYLOAD_FIELD: &str = "payload";
+/// Scheme of the URL that replaces an extracted image, followed by the name of its form field
+pub const ATTACHMENT_SCHEME: &str = "attachment://";
+
+/// Image moved out of the JSON body into its own form field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageAttachment {
+    pub field: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// Encoded multipart body with the `Content-Type` naming its boundary
+#[derive(Debug, Clone)]
+pub struct MultipartBody {
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Move every base64 `image_url` in `body` into an attachment, leaving
+/// `attachment://<field>` as its URL
+/// http(s) URLs and data that does not decode stay inline
+pub fn extract_image_attachments(body: &mut Value) -> Vec<ImageAttachment> {
+    let mut attachments = Vec::new();
+    collect_images(body, &mut attachments);
+    attachments
+}
+
+fn collect_images(value: &mut Value, attachments: &mut Vec<ImageAttachment>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                if key == "image_url" {
+                    if let Some(url) = child.get_mut("url") {
+                        extract_data_url(url, attachments);
+                        continue;
+                    }
+                }
+                collect_images(child, attachments);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_images(item, attachments);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn extract_data_url(url: &mut Value, attachments: &mut Vec<ImageAttachment>) {
+    let Some((mime_type, data)) = url.as_str().and_then(parse_data_url) else {
+        return;
+    };
+    let field = format!("image_{}", attachments.len());
+    *url = Value::String(format!("{}{}", ATTACHMENT_SCHEME, field));
+    attachments.push(ImageAttachment {
+        field,
+        mime_type,
+        data,
+    });
+}
+
+/// Mime type and bytes of a `data:image/...;base64,` URL
+fn parse_data_url(url: &str) -> Option<(String, Vec<u8>)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+    if !mime_type.starts_with("image/") {
+        return None;
+    }
+    let data = STANDARD.decode(data.trim()).ok()?;
+    Some((mime_type.to_string(), data))
+}
+
+/// `multipart/form-data` body with `payload` as the JSON field followed by one file field per
+/// attachment
+pub fn encode_multipart(payload: &Value, attachments: &[ImageAttachment]) -> MultipartBody {
+    let boundary = format!("----talkcody-{:016x}", rand::random::<u64>());
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\
+             Content-Type: application/json\r\n\r\n",
+            boundary, PAYLOAD_FIELD
+        )
+        .as_bytes(),
+    );
+    bytes.extend_from_slice(payload.to_string().as_bytes());
+    bytes.extend_from_slice(b"\r\n");
+    for attachment in attachments {
+        let extension = attachment.mime_type.strip_prefix("image/").unwrap_or("bin");
+        bytes.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}.{}\"\r\n\
+                 Content-Type: {}\r\n\r\n",
+                boundary, attachment.field, attachment.field, extension, attachment.mime_type
+            )
+            .as_bytes(),
+        );
+        bytes.extend_from_slice(&attachment.data);
+        bytes.extend_from_slice(b"\r\n");
+    }
+    bytes.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    MultipartBody {
+        content_type: format!("multipart/form-data; boundary={}", boundary),
+        bytes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn moves_base64_images_into_attachments() {
+        let data_url = format!(
+            "data:image/png;base64,{}",
+            STANDARD.encode(b"\x89PNG bytes")
+        );
+        let mut body = json!({
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "What is this?" },
+                    { "type": "image_url", "image_url": { "url": data_url } },
+                    { "type": "image_url", "image_url": { "url": "https://example.com/cat.jpg" } }
+                ]
+            }]
+        });
+
+        let attachments = extract_image_attachments(&mut body);
+
+        assert_eq!(
+            attachments,
+            vec![ImageAttachment {
+                field: "image_0".to_string(),
+                mime_type: "image/png".to_string(),
+                data: b"\x89PNG bytes".to_vec(),
+            }]
+        );
+        let content = &body["messages"][0]["content"];
+        assert_eq!(content[1]["image_url"]["url"], "attachment://image_0");
+        assert_eq!(
+            content[2]["image_url"]["url"],
+            "https://example.com/cat.jpg"
+        );
+    }
+
+    #[test]
+    fn encodes_payload_and_image_fields() {
+        let attachments = [ImageAttachment {
+            field: "image_0".to_string(),
+            mime_type: "image/jpeg".to_string(),
+            data: b"jpeg".to_vec(),
+        }];
+
+        let body = encode_multipart(&json!({ "model": "m" }), &attachments);
+
+        let boundary = body
+            .content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .expect("boundary");
+        let text = String::from_utf8(body.bytes).expect("utf-8");
+        assert!(text.starts_with(&format!("--{}\r\n", boundary)), "{}", text);
+        assert!(text.contains(
+            "name=\"payload\"\r\nContent-Type: application/json\r\n\r\n{\"model\":\"m\"}\r\n"
+        ));
+        assert!(
+            text.contains("filename=\"image_0.jpeg\"\r\nContent-Type: image/jpeg\r\n\r\njpeg\r\n")
+        );
+        assert!(text.ends_with(&format!("--{}--\r\n", boundary)));
+    }
+}
@@ -0,0 +1,536 @@
+// Ollama native chat protocol
+// Targets /api/chat, which streams newline-delimited JSON instead of SSE
+
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    request_builder::{merge_extra_body, ProtocolRequestBuilder, RequestBuildContext},
+    stream_parser::{ProtocolStreamParser, StreamFormat, StreamParseContext, StreamParseState},
+};
+use crate::llm::types::{ContentPart, Message, MessageContent, StreamEvent, ToolDefinition};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Reasoning id used for Ollama thinking output, which carries no id of its own
+const THINKING_ID: &str = "thinking";
+
+pub struct OllamaProtocol;
+
+impl OllamaProtocol {
+    fn build_messages(&self, messages: &[Message]) -> Vec<Value> {
+        let mut result = Vec::new();
+        for msg in messages {
+            match msg {
+                Message::System { content, .. } => {
+                    result.push(json!({ "role": "system", "content": content }));
+                }
+                Message::User { content, .. } => {
+                    result.push(self.convert_message("user", content));
+                }
+                Message::Assistant { content, .. } => {
+                    result.push(self.convert_message("assistant", content));
+                }
+                Message::Tool { content, .. } => {
+                    for part in content {
+                        if let ContentPart::ToolResult {
+                            tool_name, output, ..
+                        } = part
+                        {
+                            result.push(json!({
+                                "role": "tool",
+                                "tool_name": tool_name,
+                                "content": self.tool_output_to_string(output)
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn convert_message(&self, role: &str, content: &MessageContent) -> Value {
+        let parts = match content {
+            MessageContent::Text(text) => return json!({ "role": role, "content": text }),
+            MessageContent::Parts(parts) => parts,
+        };
+
+        let mut text = String::new();
+        let mut images = Vec::new();
+        let mut tool_calls = Vec::new();
+        for part in parts {
+            match part {
+                ContentPart::Text { text: value } => text.push_str(value),
+                ContentPart::Image { image, .. } => images.push(json!(image)),
+                ContentPart::ToolCall {
+                    tool_name, input, ..
+                } => {
+                    tool_calls.push(json!({
+                        "function": { "name": tool_name, "arguments": input }
+                    }));
+                }
+                // Ollama has no video input and doesn't replay thinking
+                ContentPart::Video { .. }
+                | ContentPart::Reasoning { .. }
+                | ContentPart::ToolResult { .. } => {}
+            }
+        }
+
+        let mut message = json!({ "role": role, "content": text });
+        if !images.is_empty() {
+            message["images"] = Value::Array(images);
+        }
+        if !tool_calls.is_empty() {
+            message["tool_calls"] = Value::Array(tool_calls);
+        }
+        message
+    }
+
+    fn tool_output_to_string(&self, output: &Value) -> String {
+        if let Some(value) = output.get("value").and_then(|v| v.as_str()) {
+            return value.to_string();
+        }
+        output.to_string()
+    }
+
+    fn build_tools(&self, tools: Option<&[ToolDefinition]>) -> Option<Vec<Value>> {
+        let tools = tools?;
+        Some(
+            tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "type": "function",
+                        "function": {
+                            "name": tool.name,
+                            "description": tool.description,
+                            "parameters": tool.parameters
+                        }
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn close_reasoning(state: &mut StreamParseState, out: &mut Vec<StreamEvent>) {
+        if state.reasoning_started {
+            state.reasoning_started = false;
+            out.push(StreamEvent::ReasoningEnd {
+                id: THINKING_ID.to_string(),
+            });
+        }
+    }
+
+    fn collect_events(&self, payload: &Value, state: &mut StreamParseState) -> Vec<StreamEvent> {
+        let mut out = Vec::new();
+
+        if let Some(message) = payload.get("message") {
+            if let Some(thinking) = message.get("thinking").and_then(|v| v.as_str()) {
+                if !thinking.is_empty() {
+                    if !state.reasoning_started {
+                        state.reasoning_started = true;
+                        out.push(StreamEvent::ReasoningStart {
+                            id: THINKING_ID.to_string(),
+                            provider_metadata: None,
+                        });
+                    }
+                    out.push(StreamEvent::ReasoningDelta {
+                        id: THINKING_ID.to_string(),
+                        text: thinking.to_string(),
+                        provider_metadata: None,
+                    });
+                }
+            }
+
+            if let Some(content) = message.get("content").and_then(|v| v.as_str()) {
+                if !content.is_empty() {
+                    Self::close_reasoning(state, &mut out);
+                    if !state.text_started {
+                        state.text_started = true;
+                        out.push(StreamEvent::TextStart);
+                    }
+                    out.push(StreamEvent::TextDelta {
+                        text: content.to_string(),
+                    });
+                }
+            }
+
+            // Ollama sends each tool call complete in a single line
+            if let Some(calls) = message.get("tool_calls").and_then(|v| v.as_array()) {
+                Self::close_reasoning(state, &mut out);
+                for call in calls {
+                    let Some(function) = call.get("function") else {
+                        continue;
+                    };
+                    let id = call
+                        .get("id")
+                        .and_then(|v| v.as_str())
+                        .map(|v| v.to_string())
+                        .unwrap_or_else(|| format!("call_{}", state.tool_call_order.len()));
+                    state.tool_call_order.push(id.clone());
+                    let input = match function.get("arguments") {
+                        // Some models return arguments as an encoded string
+                        Some(Value::String(raw)) => {
+                            serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.clone()))
+                        }
+                        Some(args) => args.clone(),
+                        None => json!({}),
+                    };
+                    out.push(StreamEvent::ToolCall {
+                        tool_call_id: id,
+                        tool_name: function
+                            .get("name")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or_default()
+                            .to_string(),
+                        input,
+                        provider_metadata: None,
+                    });
+                }
+            }
+        }
+
+        if payload.get("done").and_then(|v| v.as_bool()) == Some(true) {
+            Self::close_reasoning(state, &mut out);
+            let mut finish_reason = payload
+                .get("done_reason")
+                .and_then(|v| v.as_str())
+                .unwrap_or("stop")
+                .to_string();
+            if finish_reason == "stop" && !state.tool_call_order.is_empty() {
+                finish_reason = "tool_calls".to_string();
+            }
+            state.finish_reason = Some(finish_reason);
+
+            let read = |key: &str| payload.get(key).and_then(|v| v.as_i64()).map(|v| v as i32);
+            if let (Some(input_tokens), Some(output_tokens)) =
+                (read("prompt_eval_count"), read("eval_count"))
+            {
+                out.push(StreamEvent::Usage {
+                    input_tokens,
+                    output_tokens,
+                    total_tokens: Some(input_tokens + output_tokens),
+                    cached_input_tokens: None,
+                    cache_creation_input_tokens: None,
+                    timing: None,
+                    estimated: false,
+                });
+            }
+            out.push(StreamEvent::Done {
+                finish_reason: state.finish_reason.clone(),
+            });
+        }
+
+        out
+    }
+}
+
+impl ProtocolRequestBuilder for OllamaProtocol {
+    fn build_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        let mut body = json!({
+            "model": ctx.model,
+            "messages": self.build_messages(ctx.messages),
+            "stream": ctx.stream
+        });
+
+        if let Some(tools) = self.build_tools(ctx.tools) {
+            body["tools"] = Value::Array(tools);
+        }
+
+        let mut options = serde_json::Map::new();
+        if let Some(temperature) = ctx.temperature {
+            options.insert("temperature".to_string(), json!(temperature));
+        }
+        if let Some(top_p) = ctx.top_p {
+            options.insert("top_p".to_string(), json!(top_p));
+        }
+        if let Some(top_k) = ctx.top_k {
+            options.insert("top_k".to_string(), json!(top_k));
+        }
+        if let Some(max_tokens) = ctx.max_tokens {
+            options.insert("num_predict".to_string(), json!(max_tokens));
+        }
+
+        if let Some(ollama) = ctx.provider_options.and_then(|opts| opts.get("ollama")) {
+            if let Some(think) = ollama.get("think") {
+                body["think"] = think.clone();
+            }
+            if let Some(keep_alive) = ollama.get("keep_alive") {
+                body["keep_alive"] = keep_alive.clone();
+            }
+            if let Some(extra) = ollama.get("options").and_then(|v| v.as_object()) {
+                for (k, v) in extra {
+                    options.insert(k.to_string(), v.clone());
+                }
+            }
+        }
+
+        if !options.is_empty() {
+            body["options"] = Value::Object(options);
+        }
+
+        merge_extra_body(&mut body, ctx.extra_body);
+
+        Ok(body)
+    }
+}
+
+impl ProtocolStreamParser for OllamaProtocol {
+    fn parse_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        let payload: Value = serde_json::from_str(ctx.data).map_err(|e| e.to_string())?;
+
+        // Failures after the 200 arrive as an `{"error": "..."}` line, e.g. the model crashing
+        if let Some(error) = payload.get("error").and_then(|v| v.as_str()) {
+            return Ok(Some(StreamEvent::Error {
+                message: format!("Ollama stream error: {}", error),
+            }));
+        }
+
+        let mut events = self.collect_events(&payload, state);
+        if events.is_empty() {
+            return Ok(None);
+        }
+
+        let first = events.remove(0);
+        state.pending_events.extend(events);
+        Ok(Some(first))
+    }
+
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Ndjson
+    }
+}
+
+impl ProtocolHeaderBuilder for OllamaProtocol {
+    fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        // Local server, never send credentials
+        let mut headers = HashMap::new();
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        if let Some(extra) = ctx.extra_headers {
+            for (k, v) in extra {
+                headers.insert(k.to_string(), v.to_string());
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::protocols::stream_parser::take_ndjson_line;
+    use serde_json::json;
+
+    fn parse_body(body: &str) -> Vec<StreamEvent> {
+        let protocol = OllamaProtocol;
+        let mut state = StreamParseState::default();
+        let mut buffer = body.as_bytes().to_vec();
+        let mut events = Vec::new();
+
+        while let Some(line) = take_ndjson_line(&mut buffer) {
+            let data = String::from_utf8(line).expect("utf8 line");
+            if data.trim().is_empty() {
+                continue;
+            }
+            if let Some(event) = protocol
+                .parse_stream_event(
+                    StreamParseContext {
+                        event_type: None,
+                        data: &data,
+                    },
+                    &mut state,
+                )
+                .expect("parse line")
+            {
+                events.push(event);
+            }
+            events.append(&mut state.pending_events);
+        }
+
+        events
+    }
+
+    #[test]
+    fn parses_ndjson_chat_stream() {
+        let body = concat!(
+            r#"{"model":"llama3.2","created_at":"2025-01-01T00:00:00Z","message":{"role":"assistant","content":"Hel"},"done":false}"#,
+            "\n",
+            r#"{"model":"llama3.2","created_at":"2025-01-01T00:00:01Z","message":{"role":"assistant","content":"lo"},"done":false}"#,
+            "\r\n\n",
+            r#"{"model":"llama3.2","created_at":"2025-01-01T00:00:02Z","message":{"role":"assistant","content":""},"done":true,"done_reason":"stop","prompt_eval_count":26,"eval_count":2}"#,
+            "\n"
+        );
+
+        let events = serde_json::to_value(parse_body(body)).expect("serialize events");
+
+        assert_eq!(
+            events,
+            json!([
+                { "type": "text-start" },
+                { "type": "text-delta", "text": "Hel" },
+                { "type": "text-delta", "text": "lo" },
+                {
+                    "type": "usage",
+                    "input_tokens": 26,
+                    "output_tokens": 2,
+                    "total_tokens": 28,
+                    "cached_input_tokens": null,
+                    "cache_creation_input_tokens": null
+                },
+                { "type": "done", "finish_reason": "stop" }
+            ])
+        );
+    }
+
+    #[test]
+    fn mid_stream_error_line_becomes_error_event() {
+        let body = concat!(
+            r#"{"message":{"role":"assistant","content":"Hel"},"done":false}"#,
+            "\n",
+            r#"{"error":"model runner has unexpectedly stopped"}"#,
+            "\n"
+        );
+
+        let events = parse_body(body);
+
+        assert!(matches!(
+            events.as_slice(),
+            [
+                StreamEvent::TextStart,
+                StreamEvent::TextDelta { .. },
+                StreamEvent::Error { message },
+            ] if message == "Ollama stream error: model runner has unexpectedly stopped"
+        ));
+    }
+
+    #[test]
+    fn parses_thinking_and_tool_calls() {
+        let body = concat!(
+            r#"{"message":{"role":"assistant","content":"","thinking":"Let me look"},"done":false}"#,
+            "\n",
+            r#"{"message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"readFile","arguments":{"path":"a.rs"}}}]},"done":false}"#,
+            "\n",
+            r#"{"message":{"role":"assistant","content":""},"done":true,"done_reason":"stop"}"#,
+            "\n"
+        );
+
+        let events = serde_json::to_value(parse_body(body)).expect("serialize events");
+
+        assert_eq!(
+            events,
+            json!([
+                { "type": "reasoning-start", "id": "thinking", "provider_metadata": null },
+                {
+                    "type": "reasoning-delta",
+                    "id": "thinking",
+                    "text": "Let me look",
+                    "provider_metadata": null
+                },
+                { "type": "reasoning-end", "id": "thinking" },
+                {
+                    "type": "tool-call",
+                    "toolCallId": "call_0",
+                    "toolName": "readFile",
+                    "input": { "path": "a.rs" },
+                    "provider_metadata": null
+                },
+                { "type": "done", "finish_reason": "tool_calls" }
+            ])
+        );
+    }
+
+    #[test]
+    fn error_line_returns_error() {
+        let protocol = OllamaProtocol;
+        let mut state = StreamParseState::default();
+
+        let result = protocol.parse_stream_event(
+            StreamParseContext {
+                event_type: None,
+                data: r#"{"error":"model 'llama9' not found"}"#,
+            },
+            &mut state,
+        );
+
+        assert!(result.unwrap_err().contains("model 'llama9' not found"));
+    }
+
+    #[test]
+    fn build_request_uses_native_chat_format() {
+        let messages = vec![
+            Message::System {
+                content: "Be brief.".to_string(),
+                provider_options: None,
+                cache: false,
+            },
+            Message::User {
+                content: MessageContent::Parts(vec![
+                    ContentPart::Text {
+                        text: "What is this?".to_string(),
+                    },
+                    ContentPart::Image {
+                        image: "aGVsbG8=".to_string(),
+                        mime_type: None,
+                    },
+                ]),
+                provider_options: None,
+                cache: false,
+            },
+        ];
+
+        let body = OllamaProtocol
+            .build_request(RequestBuildContext {
+                model: "llama3.2",
+                messages: &messages,
+                tools: None,
+                temperature: Some(0.5),
+                max_tokens: Some(128),
+                top_p: None,
+                top_k: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: None,
+                response_format: None,
+                user_id: None,
+                provider_options: None,
+                extra_body: None,
+                stream: true,
+                logprobs: false,
+                top_logprobs: None,
+                builtin_tools: &[],
+                reasoning_effort: None,
+            })
+            .expect("build request");
+
+        assert_eq!(body["stream"], json!(true));
+        assert_eq!(
+            body["messages"],
+            json!([
+                { "role": "system", "content": "Be brief." },
+                { "role": "user", "content": "What is this?", "images": ["aGVsbG8="] }
+            ])
+        );
+        assert_eq!(
+            body["options"],
+            json!({ "temperature": 0.5, "num_predict": 128 })
+        );
+    }
+
+    #[test]
+    fn headers_never_include_authorization() {
+        let headers = OllamaProtocol.build_base_headers(HeaderBuildContext {
+            api_key: Some("ignored"),
+            oauth_token: Some("ignored"),
+            extra_headers: None,
+        });
+
+        assert!(!headers.contains_key("Authorization"));
+        assert_eq!(
+            headers.get("Content-Type"),
+            Some(&"application/json".to_string())
+        );
+    }
+}
@@ -1,20 +1,97 @@
 use crate::llm::protocols::{
     header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
-    request_builder::{ProtocolRequestBuilder, RequestBuildContext},
+    prompt_cache::{self, MAX_CACHE_BREAKPOINTS},
+    request_builder::{merge_extra_body, ProtocolRequestBuilder, RequestBuildContext},
     stream_parser::{self, ProtocolStreamParser, StreamParseContext, StreamParseState},
     LlmProtocol, ProtocolStreamState, ToolCallAccum,
 };
-use crate::llm::types::{ContentPart, Message, MessageContent, StreamEvent, ToolDefinition};
+use crate::llm::types::{
+    check_tool_results, image_url, BuiltinTool, ContentPart, Message, MessageContent,
+    ResponseFormat, StreamEvent, TokenLogProb, ToolDefinition, UsageTiming,
+};
+use serde::Serialize;
 use serde_json::{json, Value};
 use std::collections::HashMap;
 
+/// Accepted range for `temperature` on OpenAI-compatible APIs
+const TEMPERATURE_RANGE: std::ops::RangeInclusive<f32> = 0.0..=2.0;
+
+/// Most alternatives OpenAI reports per token
+const MAX_TOP_LOGPROBS: u8 = 20;
+
+/// Reasoning model families; they take `max_completion_tokens` instead of `max_tokens` and
+/// accept `reasoning_effort`
+const REASONING_FAMILIES: &[&str] = &["o1", "o3", "o4", "gpt-5"];
+
 pub struct OpenAiProtocol;
 
+/// Sampling controls copied into the request body; unset ones keep the provider defaults
+#[derive(Serialize)]
+struct SamplingParams<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    frequency_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    presence_penalty: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<&'a [String]>,
+}
+
+impl<'a> SamplingParams<'a> {
+    fn from_context(ctx: &RequestBuildContext<'a>) -> Result<Self, String> {
+        if let Some(temperature) = ctx.temperature {
+            if !TEMPERATURE_RANGE.contains(&temperature) {
+                return Err(format!(
+                    "temperature must be between 0 and 2, got {} / temperature 必须在 0 到 2 之间，当前为 {}",
+                    temperature, temperature
+                ));
+            }
+        }
+        // Same cap, under the name the model family accepts
+        let (max_tokens, max_completion_tokens) = if is_reasoning_model(ctx.model) {
+            (None, ctx.max_tokens)
+        } else {
+            (ctx.max_tokens, None)
+        };
+        Ok(Self {
+            temperature: ctx.temperature,
+            max_tokens,
+            max_completion_tokens,
+            top_p: ctx.top_p,
+            frequency_penalty: ctx.frequency_penalty,
+            presence_penalty: ctx.presence_penalty,
+            stop: ctx.stop.filter(|stop| !stop.is_empty()),
+        })
+    }
+}
+
+/// Whether `model` belongs to one of the reasoning families (o-series and GPT-5)
+/// Matches the id after any vendor prefix such as `openai/`, ignoring case
+fn is_reasoning_model(model: &str) -> bool {
+    let id = model
+        .rsplit('/')
+        .next()
+        .unwrap_or(model)
+        .to_ascii_lowercase();
+    REASONING_FAMILIES.iter().any(|family| {
+        id.strip_prefix(family)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(['-', '.']))
+    })
+}
+
 impl OpenAiProtocol {
     fn build_messages(&self, messages: &[Message]) -> Vec<Value> {
         let mut result = Vec::new();
 
         for msg in messages {
+            let start = result.len();
             match msg {
                 Message::System { content, .. } => {
                     result.push(json!({ "role": "system", "content": content }));
@@ -28,6 +105,7 @@ impl OpenAiProtocol {
                 Message::Assistant {
                     content,
                     provider_options,
+                    ..
                 } => {
                     result.push(self.build_assistant_message(content, provider_options.as_ref()));
                 }
@@ -52,6 +130,12 @@ impl OpenAiProtocol {
                     }
                 }
             }
+            // Caching gateways read Anthropic-style `cache_control` on content parts
+            if msg.is_cache_breakpoint() && result.len() > start {
+                if let Some(content) = result.last_mut().and_then(|m| m.get_mut("content")) {
+                    prompt_cache::mark_content(content);
+                }
+            }
         }
 
         result
@@ -67,10 +151,10 @@ impl OpenAiProtocol {
                         ContentPart::Text { text } => {
                             mapped.push(json!({ "type": "text", "text": text }));
                         }
-                        ContentPart::Image { image } => {
+                        ContentPart::Image { image, mime_type } => {
                             mapped.push(json!({
                                 "type": "image_url",
-                                "image_url": { "url": format!("data:image/png;base64,{}", image) }
+                                "image_url": { "url": image_url(image, mime_type.as_deref()) }
                             }));
                         }
                         ContentPart::Video { video, mime_type } => {
@@ -140,11 +224,11 @@ impl OpenAiProtocol {
                                 rich_parts.push(json!({ "type": "text", "text": text }));
                             }
                         }
-                        ContentPart::Image { image } => {
+                        ContentPart::Image { image, mime_type } => {
                             has_image = true;
                             rich_parts.push(json!({
                                 "type": "image_url",
-                                "image_url": { "url": format!("data:image/png;base64,{}", image) }
+                                "image_url": { "url": image_url(image, mime_type.as_deref()) }
                             }));
                         }
                         ContentPart::Video { video, mime_type } => {
@@ -226,6 +310,29 @@ impl OpenAiProtocol {
         message
     }
 
+    fn build_response_format(&self, format: &ResponseFormat) -> Result<Value, String> {
+        match format {
+            ResponseFormat::JsonObject => Ok(json!({ "type": "json_object" })),
+            ResponseFormat::JsonSchema {
+                name,
+                schema,
+                strict,
+            } => {
+                if !schema.is_object() {
+                    return Err(format!(
+                        "Response schema '{}' must be a JSON object / 响应格式 schema '{}' 必须是 JSON 对象",
+                        name, name
+                    ));
+                }
+                let mut json_schema = json!({ "name": name, "schema": schema });
+                if let Some(strict) = strict {
+                    json_schema["strict"] = json!(strict);
+                }
+                Ok(json!({ "type": "json_schema", "json_schema": json_schema }))
+            }
+        }
+    }
+
     fn tool_output_to_string(&self, output: &Value) -> String {
         if let Some(value) = output.get("value").and_then(|v| v.as_str()) {
             return value.to_string();
@@ -349,6 +456,28 @@ impl OpenAiProtocol {
             } else if !state.tool_call_order.contains(&key) {
                 state.tool_call_order.push(key.clone());
             }
+
+            // Surface each fragment as it arrives; the assembled call still follows as ToolCall
+            let arguments_chunk = match args_value {
+                Some(Value::String(chunk)) => chunk.clone(),
+                Some(Value::Null) | None => String::new(),
+                Some(other) => other.to_string(),
+            };
+            if !arguments_chunk.is_empty() || !tool_call_id.is_empty() || !name.is_empty() {
+                let position = index.map(|value| value as usize).unwrap_or_else(|| {
+                    state
+                        .tool_call_order
+                        .iter()
+                        .position(|slot| *slot == key)
+                        .unwrap_or_default()
+                });
+                state.pending_events.push(StreamEvent::ToolCallDelta {
+                    index: position as u32,
+                    id: (!tool_call_id.is_empty()).then(|| tool_call_id.clone()),
+                    name: (!name.is_empty()).then(|| name.to_string()),
+                    arguments_chunk,
+                });
+            }
         }
     }
 
@@ -399,29 +528,71 @@ impl OpenAiProtocol {
 
 impl ProtocolRequestBuilder for OpenAiProtocol {
     fn build_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        let sampling = SamplingParams::from_context(&ctx)?;
+        check_tool_results(ctx.messages)?;
         let mut body = json!({
             "model": ctx.model,
             "messages": self.build_messages(ctx.messages),
-            "stream": true,
-            "stream_options": { "include_usage": true }
+            "stream": ctx.stream
         });
+        if ctx.stream {
+            body["stream_options"] = json!({ "include_usage": true });
+        }
 
         if let Some(tools) = self.build_tools(ctx.tools) {
+            if !tools.is_empty() {
+                // "auto" unless overridden with providerOptions.openai.toolChoice
+                body["tool_choice"] = ctx
+                    .provider_options
+                    .and_then(|options| options.get("openai"))
+                    .and_then(|openai| openai.get("toolChoice"))
+                    .cloned()
+                    .unwrap_or_else(|| json!("auto"));
+            }
             body["tools"] = Value::Array(tools);
         }
-        if let Some(temperature) = ctx.temperature {
-            body["temperature"] = json!(temperature);
-        }
-        if let Some(max_tokens) = ctx.max_tokens {
-            body["max_tokens"] = json!(max_tokens);
-        }
-        if let Some(top_p) = ctx.top_p {
-            body["top_p"] = json!(top_p);
+        if let (Some(obj), Value::Object(params)) = (
+            body.as_object_mut(),
+            serde_json::to_value(&sampling)
+                .map_err(|e| format!("Failed to serialize sampling parameters: {}", e))?,
+        ) {
+            obj.extend(params);
         }
         if let Some(top_k) = ctx.top_k {
             body["top_k"] = json!(top_k);
         }
+        if let Some(format) = ctx.response_format {
+            body["response_format"] = self.build_response_format(format)?;
+        }
+        if let Some(user) = ctx.user_id.filter(|user| !user.trim().is_empty()) {
+            body["user"] = json!(user);
+        }
+        if ctx.logprobs {
+            body["logprobs"] = json!(true);
+            if let Some(top_logprobs) = ctx.top_logprobs {
+                body["top_logprobs"] = json!(top_logprobs.min(MAX_TOP_LOGPROBS));
+            }
+        }
+        for tool in ctx.builtin_tools {
+            match tool {
+                // Answered by the search-enabled chat models
+                BuiltinTool::WebSearch => body["web_search_options"] = json!({}),
+                BuiltinTool::CodeInterpreter => {
+                    return Err(
+                        "Code interpreter needs the Responses API / 代码解释器需要 Responses API"
+                            .to_string(),
+                    )
+                }
+            }
+        }
 
+        // Other models reject the parameter; providerOptions.openai.reasoningEffort still wins
+        if let Some(effort) = ctx
+            .reasoning_effort
+            .filter(|_| is_reasoning_model(ctx.model))
+        {
+            body["reasoning_effort"] = json!(effort.as_str());
+        }
         if let Some(options) = ctx.provider_options {
             if let Some(openai_opts) = options.get("openai") {
                 if let Some(reasoning) = openai_opts.get("reasoningEffort") {
@@ -441,15 +612,8 @@ impl ProtocolRequestBuilder for OpenAiProtocol {
             body["reasoning"] = Value::Null;
         }
 
-        if let Some(extra) = ctx.extra_body {
-            if let Some(obj) = body.as_object_mut() {
-                if let Some(extra_obj) = extra.as_object() {
-                    for (k, v) in extra_obj {
-                        obj.insert(k.to_string(), v.clone());
-                    }
-                }
-            }
-        }
+        merge_extra_body(&mut body, ctx.extra_body);
+        prompt_cache::limit_breakpoints(&mut body, MAX_CACHE_BREAKPOINTS);
 
         if body.get("reasoning") == Some(&Value::Null) {
             body.as_object_mut().map(|obj| obj.remove("reasoning"));
@@ -485,9 +649,20 @@ impl ProtocolStreamParser for OpenAiProtocol {
             }));
         }
 
-        let payload: Value = serde_json::from_str(ctx.data).map_err(|e| e.to_string())?;
+        let Some(payload) = stream_parser::parse_json_data(state, ctx.data)? else {
+            return Ok(None);
+        };
+
+        if let Some(start) = stream_parser::start_event(
+            state,
+            payload.get("model").and_then(|v| v.as_str()),
+            payload.get("system_fingerprint").and_then(|v| v.as_str()),
+        ) {
+            state.pending_events.push(start);
+        }
 
-        // Only emit Usage event when there's meaningful usage data
+        // Final chunk carries usage when `stream_options.include_usage` is set; providers
+        // that never send it (or send `"usage": null`) simply produce no Usage event
         if let Some(usage) = payload.get("usage") {
             let input_tokens = usage
                 .get("prompt_tokens")
@@ -498,6 +673,17 @@ impl ProtocolStreamParser for OpenAiProtocol {
                 .and_then(|v| v.as_i64())
                 .unwrap_or(0);
             let total_tokens = usage.get("total_tokens").and_then(|v| v.as_i64());
+            let details = usage.get("prompt_tokens_details");
+            // DeepSeek reports cache hits at the top level instead of in the details
+            let cached_input_tokens = details
+                .and_then(|details| details.get("cached_tokens"))
+                .or_else(|| usage.get("prompt_cache_hit_tokens"))
+                .and_then(|v| v.as_i64())
+                .map(|v| v as i32);
+            let cache_creation_input_tokens = details
+                .and_then(|details| details.get("cache_write_tokens"))
+                .and_then(|v| v.as_i64())
+                .map(|v| v as i32);
 
             let has_meaningful_data =
                 input_tokens > 0 || output_tokens > 0 || total_tokens.is_some_and(|v| v > 0);
@@ -507,16 +693,26 @@ impl ProtocolStreamParser for OpenAiProtocol {
                     input_tokens: input_tokens as i32,
                     output_tokens: output_tokens as i32,
                     total_tokens: total_tokens.map(|v| v as i32),
-                    cached_input_tokens: None,
-                    cache_creation_input_tokens: None,
+                    cached_input_tokens,
+                    cache_creation_input_tokens,
+                    timing: UsageTiming::from_usage(usage),
+                    estimated: false,
                 });
             }
         }
 
+        let had_finish_reason = state.finish_reason.is_some();
+        let mut content_filter_results = None;
         let choices = payload.get("choices").and_then(|v| v.as_array());
         if let Some(choice) = choices.and_then(|arr| arr.first()) {
             if let Some(finish_reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
                 state.finish_reason = Some(finish_reason.to_string());
+                if finish_reason == "content_filter" {
+                    content_filter_results = choice
+                        .get("content_filter_results")
+                        .filter(|results| !results.is_null())
+                        .cloned();
+                }
             }
             if let Some(delta) = choice.get("delta") {
                 // Handle reasoning_content (DeepSeek-style) and reasoning (OpenRouter/MiniMax-style)
@@ -564,6 +760,10 @@ impl ProtocolStreamParser for OpenAiProtocol {
                 // Handle tool calls (may come without text content)
                 self.parse_tool_delta(delta, state);
             }
+            // Only sent when the request asked for logprobs
+            if let Some(tokens) = choice.get("logprobs").and_then(TokenLogProb::from_openai) {
+                state.pending_events.push(StreamEvent::LogProbs { tokens });
+            }
         }
 
         if state.finish_reason.as_deref() == Some("tool_calls") {
@@ -580,6 +780,16 @@ impl ProtocolStreamParser for OpenAiProtocol {
             state.reasoning_started = false;
         }
 
+        // `Stop` follows the final chunk's content so truncation is told apart from completion
+        if !had_finish_reason {
+            if let Some(reason) = state.finish_reason.clone() {
+                state.pending_events.push(StreamEvent::Stop {
+                    reason,
+                    content_filter_results,
+                });
+            }
+        }
+
         if let Some(event) = state.pending_events.first().cloned() {
             state.pending_events.remove(0);
             return Ok(Some(event));
@@ -638,8 +848,18 @@ impl LlmProtocol for OpenAiProtocol {
             max_tokens,
             top_p,
             top_k,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
             provider_options,
             extra_body,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            builtin_tools: &[],
+            reasoning_effort: None,
         };
         ProtocolRequestBuilder::build_request(self, ctx)
     }
@@ -654,6 +874,7 @@ impl LlmProtocol for OpenAiProtocol {
         let mut new_state = stream_parser::StreamParseState {
             finish_reason: state.finish_reason.clone(),
             text_started: state.text_started,
+            stream_started: state.stream_started,
             reasoning_started: state.reasoning_started,
             reasoning_id: state.reasoning_id.clone(),
             pending_events: std::mem::take(&mut state.pending_events),
@@ -666,6 +887,11 @@ impl LlmProtocol for OpenAiProtocol {
             current_thinking_id: state.current_thinking_id.clone(),
             openai_reasoning: std::mem::take(&mut state.openai_reasoning),
             openai_store: state.openai_store,
+            input_tokens: None,
+            cached_input_tokens: None,
+            cache_creation_input_tokens: None,
+            partial_data: String::new(),
+            tolerant_json: state.tolerant_json,
         };
 
         let result = ProtocolStreamParser::parse_stream_event(self, ctx, &mut new_state);
@@ -673,6 +899,7 @@ impl LlmProtocol for OpenAiProtocol {
         // Sync state back
         state.finish_reason = new_state.finish_reason;
         state.text_started = new_state.text_started;
+        state.stream_started = new_state.stream_started;
         state.reasoning_started = new_state.reasoning_started;
         state.reasoning_id = new_state.reasoning_id;
         state.pending_events = new_state.pending_events;
@@ -708,6 +935,7 @@ impl LlmProtocol for OpenAiProtocol {
 mod tests {
     use super::*;
     use crate::llm::protocols::ProtocolStreamState;
+    use crate::llm::types::ReasoningEffort;
     use serde_json::json;
     use std::collections::HashMap;
 
@@ -1092,6 +1320,7 @@ mod tests {
             provider_options: Some(json!({
                 "openaiCompatible": { "reasoning_content": "" }
             })),
+            cache: false,
         }];
 
         let built = protocol.build_messages(&messages);
@@ -1110,6 +1339,7 @@ mod tests {
                 provider_metadata: None,
             }]),
             provider_options: None,
+            cache: false,
         }];
 
         let built = protocol.build_messages(&messages);
@@ -1123,6 +1353,7 @@ mod tests {
         let messages = vec![Message::User {
             content: MessageContent::Text("hi".to_string()),
             provider_options: None,
+            cache: false,
         }];
 
         let body = LlmProtocol::build_request(
@@ -1148,12 +1379,1072 @@ mod tests {
         assert_eq!(body.get("max_tokens"), Some(&json!(120)));
     }
 
+    fn tool_turn(result_call_id: &str) -> Vec<Message> {
+        vec![
+            Message::User {
+                content: MessageContent::Text("Weather in Paris?".to_string()),
+                provider_options: None,
+                cache: false,
+            },
+            Message::Assistant {
+                content: MessageContent::Parts(vec![
+                    ContentPart::Text {
+                        text: "Checking.".to_string(),
+                    },
+                    ContentPart::ToolCall {
+                        tool_call_id: "call_1".to_string(),
+                        tool_name: "getWeather".to_string(),
+                        input: json!({ "city": "Paris" }),
+                        provider_metadata: None,
+                    },
+                ]),
+                provider_options: None,
+                cache: false,
+            },
+            Message::Tool {
+                content: vec![ContentPart::ToolResult {
+                    tool_call_id: result_call_id.to_string(),
+                    tool_name: "getWeather".to_string(),
+                    output: json!({ "type": "text", "value": "18°C, cloudy" }),
+                }],
+                provider_options: None,
+                cache: false,
+            },
+        ]
+    }
+
+    #[test]
+    fn build_request_sends_tool_results_after_the_tool_call() {
+        let protocol = OpenAiProtocol;
+        let messages = tool_turn("call_1");
+
+        let body = ProtocolRequestBuilder::build_request(&protocol, sampling_context(&messages))
+            .expect("build request");
+
+        let sent = body["messages"].as_array().expect("messages");
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[1]["role"], "assistant");
+        assert_eq!(sent[1]["content"], "Checking.");
+        assert_eq!(
+            sent[1]["tool_calls"],
+            json!([{
+                "id": "call_1",
+                "type": "function",
+                "function": { "name": "getWeather", "arguments": "{\"city\":\"Paris\"}" }
+            }])
+        );
+        assert_eq!(sent[2]["role"], "tool");
+        assert_eq!(sent[2]["tool_call_id"], "call_1");
+        assert_eq!(sent[2]["content"], "18°C, cloudy");
+    }
+
+    #[test]
+    fn build_request_rejects_tool_results_without_a_prior_call() {
+        let protocol = OpenAiProtocol;
+        let messages = tool_turn("call_9");
+
+        let err = ProtocolRequestBuilder::build_request(&protocol, sampling_context(&messages))
+            .expect_err("orphan tool result");
+
+        assert!(err.contains("'call_9'"), "{}", err);
+    }
+
+    fn sampling_context<'a>(messages: &'a [Message]) -> RequestBuildContext<'a> {
+        RequestBuildContext {
+            model: "gpt-4o",
+            messages,
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            provider_options: None,
+            extra_body: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            builtin_tools: &[],
+            reasoning_effort: None,
+        }
+    }
+
+    #[test]
+    fn build_request_serializes_only_provided_sampling_params() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let stop = Vec::new();
+        let ctx = RequestBuildContext {
+            temperature: Some(0.5),
+            presence_penalty: Some(0.25),
+            stop: Some(&stop),
+            ..sampling_context(&messages)
+        };
+
+        let body = ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request");
+
+        assert_eq!(body.get("temperature"), Some(&json!(0.5)));
+        assert_eq!(body.get("presence_penalty"), Some(&json!(0.25)));
+        for absent in ["max_tokens", "top_p", "frequency_penalty", "stop"] {
+            assert!(body.get(absent).is_none(), "{} should be omitted", absent);
+        }
+    }
+
+    #[test]
+    fn build_request_serializes_all_sampling_params() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let stop = vec!["</answer>".to_string(), "\n\n".to_string()];
+        let ctx = RequestBuildContext {
+            temperature: Some(0.0),
+            max_tokens: Some(256),
+            top_p: Some(0.5),
+            frequency_penalty: Some(-1.0),
+            presence_penalty: Some(1.5),
+            stop: Some(&stop),
+            ..sampling_context(&messages)
+        };
+
+        let body = ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request");
+
+        assert_eq!(body.get("temperature"), Some(&json!(0.0)));
+        assert_eq!(body.get("max_tokens"), Some(&json!(256)));
+        assert_eq!(body.get("top_p"), Some(&json!(0.5)));
+        assert_eq!(body.get("frequency_penalty"), Some(&json!(-1.0)));
+        assert_eq!(body.get("presence_penalty"), Some(&json!(1.5)));
+        assert_eq!(body.get("stop"), Some(&json!(["</answer>", "\n\n"])));
+    }
+
+    #[test]
+    fn build_request_names_the_output_cap_by_model_family() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let build = |model| {
+            let ctx = RequestBuildContext {
+                model,
+                max_tokens: Some(512),
+                ..sampling_context(&messages)
+            };
+            ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request")
+        };
+
+        for model in [
+            "o1",
+            "o3-mini",
+            "o4-mini-2025-04-16",
+            "gpt-5",
+            "gpt-5.1",
+            "openai/o3",
+        ] {
+            let body = build(model);
+            assert_eq!(
+                body.get("max_completion_tokens"),
+                Some(&json!(512)),
+                "{}",
+                model
+            );
+            assert!(body.get("max_tokens").is_none(), "{}", model);
+        }
+        for model in ["gpt-4o", "gpt-4.1-mini", "deepseek-chat", "o3de-chat"] {
+            let body = build(model);
+            assert_eq!(body.get("max_tokens"), Some(&json!(512)), "{}", model);
+            assert!(body.get("max_completion_tokens").is_none(), "{}", model);
+        }
+    }
+
+    #[test]
+    fn build_request_sends_reasoning_effort_to_reasoning_models_only() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let build = |model, provider_options| {
+            let ctx = RequestBuildContext {
+                model,
+                provider_options,
+                reasoning_effort: Some(ReasoningEffort::High),
+                ..sampling_context(&messages)
+            };
+            ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request")
+        };
+
+        for effort in [
+            ReasoningEffort::Low,
+            ReasoningEffort::Medium,
+            ReasoningEffort::High,
+        ] {
+            let ctx = RequestBuildContext {
+                model: "o3-mini",
+                reasoning_effort: Some(effort),
+                ..sampling_context(&messages)
+            };
+            let body =
+                ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request");
+            assert_eq!(body.get("reasoning_effort"), Some(&json!(effort.as_str())));
+        }
+        assert!(build("gpt-4o", None).get("reasoning_effort").is_none());
+        let options = json!({ "openai": { "reasoningEffort": "minimal" } });
+        assert_eq!(
+            build("gpt-5", Some(&options)).get("reasoning_effort"),
+            Some(&json!("minimal"))
+        );
+    }
+
+    #[test]
+    fn build_request_sends_user_only_when_provided() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+
+        let body = ProtocolRequestBuilder::build_request(&protocol, sampling_context(&messages))
+            .expect("build request");
+        assert!(body.get("user").is_none());
+
+        let ctx = RequestBuildContext {
+            user_id: Some("account-42"),
+            ..sampling_context(&messages)
+        };
+        let body = ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request");
+        assert_eq!(body.get("user"), Some(&json!("account-42")));
+    }
+
+    #[test]
+    fn build_request_asks_for_logprobs_only_when_requested() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+
+        let body = ProtocolRequestBuilder::build_request(&protocol, sampling_context(&messages))
+            .expect("build request");
+        assert!(body.get("logprobs").is_none());
+        assert!(body.get("top_logprobs").is_none());
+
+        let ctx = RequestBuildContext {
+            logprobs: true,
+            top_logprobs: Some(50),
+            ..sampling_context(&messages)
+        };
+        let body = ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request");
+        assert_eq!(body["logprobs"], json!(true));
+        assert_eq!(body["top_logprobs"], json!(20));
+    }
+
+    #[test]
+    fn build_request_maps_builtin_web_search_to_search_options() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+
+        let ctx = RequestBuildContext {
+            builtin_tools: &[BuiltinTool::WebSearch],
+            ..sampling_context(&messages)
+        };
+        let body = ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request");
+        assert_eq!(body["web_search_options"], json!({}));
+
+        let ctx = RequestBuildContext {
+            builtin_tools: &[BuiltinTool::CodeInterpreter],
+            ..sampling_context(&messages)
+        };
+        let err = ProtocolRequestBuilder::build_request(&protocol, ctx).expect_err("no sandbox");
+        assert!(err.contains("Responses API"), "{}", err);
+    }
+
+    #[test]
+    fn build_request_rejects_out_of_range_temperature() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let ctx = RequestBuildContext {
+            temperature: Some(2.5),
+            ..sampling_context(&messages)
+        };
+
+        let err = ProtocolRequestBuilder::build_request(&protocol, ctx).expect_err("invalid");
+
+        assert!(
+            err.contains("temperature must be between 0 and 2"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn build_request_serializes_json_schema_response_format() {
+        let protocol = OpenAiProtocol;
+        let messages = Vec::new();
+        let format = ResponseFormat::JsonSchema {
+            name: "commit_message".to_string(),
+            schema: json!({
+                "type": "object",
+                "properties": { "subject": { "type": "string" } },
+                "required": ["subject"]
+            }),
+            strict: Some(true),
+        };
+        let ctx = RequestBuildContext {
+            response_format: Some(&format),
+            user_id: None,
+            ..sampling_context(&messages)
+        };
+
+        let body = ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request");
+
+        assert_eq!(
+            body["response_format"],
+            json!({
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "commit_message",
+                    "schema": {
+                        "type": "object",
+                        "properties": { "subject": { "type": "string" } },
+                        "required": ["subject"]
+                    },
+                    "strict": true
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn build_request_serializes_json_object_response_format() {
+        let protocol = OpenAiProtocol;
+        let messages = Vec::new();
+        let ctx = RequestBuildContext {
+            response_format: Some(&ResponseFormat::JsonObject),
+            user_id: None,
+            ..sampling_context(&messages)
+        };
+
+        let body = ProtocolRequestBuilder::build_request(&protocol, ctx).expect("build request");
+
+        assert_eq!(body["response_format"], json!({ "type": "json_object" }));
+        assert!(
+            ProtocolRequestBuilder::build_request(&protocol, sampling_context(&messages))
+                .expect("build request")
+                .get("response_format")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn build_request_rejects_non_object_schema() {
+        let protocol = OpenAiProtocol;
+        let messages = Vec::new();
+        let format = ResponseFormat::JsonSchema {
+            name: "answer".to_string(),
+            schema: json!("string"),
+            strict: None,
+        };
+        let ctx = RequestBuildContext {
+            response_format: Some(&format),
+            user_id: None,
+            ..sampling_context(&messages)
+        };
+
+        let err = ProtocolRequestBuilder::build_request(&protocol, ctx).expect_err("invalid");
+
+        assert!(err.contains("must be a JSON object"), "{}", err);
+    }
+
+    fn build_user_messages(content: MessageContent) -> Vec<Value> {
+        let messages = vec![Message::User {
+            content,
+            provider_options: None,
+            cache: false,
+        }];
+        let body = LlmProtocol::build_request(
+            &OpenAiProtocol,
+            "gpt-4o",
+            &messages,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .expect("build request");
+        body["messages"].as_array().expect("messages").clone()
+    }
+
+    #[test]
+    fn build_request_keeps_text_only_message_as_string() {
+        let messages = build_user_messages(MessageContent::Text("describe this".to_string()));
+
+        assert_eq!(
+            messages,
+            vec![json!({ "role": "user", "content": "describe this" })]
+        );
+    }
+
+    #[test]
+    fn build_request_emits_content_parts_for_text_and_images() {
+        let messages = build_user_messages(MessageContent::Parts(vec![
+            ContentPart::Text {
+                text: "what is in these?".to_string(),
+            },
+            ContentPart::Image {
+                image: "/9j/4AAQSkZJRg==".to_string(),
+                mime_type: None,
+            },
+            ContentPart::Image {
+                image: "UklGRh4AAABXRUJQ".to_string(),
+                mime_type: Some("image/webp".to_string()),
+            },
+            ContentPart::Image {
+                image: "https://example.com/cat.png".to_string(),
+                mime_type: None,
+            },
+        ]));
+
+        assert_eq!(
+            messages,
+            vec![json!({
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "what is in these?" },
+                    {
+                        "type": "image_url",
+                        "image_url": { "url": "data:image/jpeg;base64,/9j/4AAQSkZJRg==" }
+                    },
+                    {
+                        "type": "image_url",
+                        "image_url": { "url": "data:image/webp;base64,UklGRh4AAABXRUJQ" }
+                    },
+                    {
+                        "type": "image_url",
+                        "image_url": { "url": "https://example.com/cat.png" }
+                    }
+                ]
+            })]
+        );
+    }
+
+    fn collect_stream_events(protocol: &OpenAiProtocol, chunks: &[String]) -> Vec<StreamEvent> {
+        let mut state = stream_parser::StreamParseState::default();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            let ctx = StreamParseContext {
+                event_type: None,
+                data: chunk,
+            };
+            if let Some(event) = ProtocolStreamParser::parse_stream_event(protocol, ctx, &mut state)
+                .expect("parse chunk")
+            {
+                events.push(event);
+            }
+            events.append(&mut state.pending_events);
+        }
+        events
+    }
+
+    fn event_labels(events: &[StreamEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                StreamEvent::ReasoningStart { .. } => "reasoning-start".to_string(),
+                StreamEvent::ReasoningDelta { text, .. } => format!("reasoning:{}", text),
+                StreamEvent::ReasoningEnd { .. } => "reasoning-end".to_string(),
+                StreamEvent::TextStart => "text-start".to_string(),
+                StreamEvent::TextDelta { text } => format!("text:{}", text),
+                StreamEvent::Start { model, .. } => format!("start:{}", model),
+                StreamEvent::Stop { reason, .. } => format!("stop:{}", reason),
+                StreamEvent::Done { finish_reason } => {
+                    format!("done:{}", finish_reason.as_deref().unwrap_or(""))
+                }
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_stream_starts_with_served_model_and_fingerprint() {
+        let protocol = OpenAiProtocol;
+        let chunk = |content: &str| {
+            json!({
+                "model": "gpt-4o-2024-08-06",
+                "system_fingerprint": "fp_f33667828e",
+                "choices": [{"delta": {"content": content}}]
+            })
+            .to_string()
+        };
+        let chunks = vec![chunk("Hi"), chunk("!"), "[DONE]".to_string()];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        let starts: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::Start {
+                    model,
+                    system_fingerprint,
+                } => Some((model.as_str(), system_fingerprint.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(starts, vec![("gpt-4o-2024-08-06", Some("fp_f33667828e"))]);
+        assert!(matches!(events.first(), Some(StreamEvent::Start { .. })));
+    }
+
+    #[test]
+    fn parse_stream_separates_reasoning_from_content_in_mixed_transcript() {
+        let protocol = OpenAiProtocol;
+        let chunks = vec![
+            json!({"choices": [{"delta": {"role": "assistant", "content": ""}}]}).to_string(),
+            json!({"choices": [{"delta": {"reasoning_content": "The user wants"}}]}).to_string(),
+            json!({"choices": [{"delta": {"reasoning_content": " a greeting."}}]}).to_string(),
+            json!({"choices": [{"delta": {"content": "Hello"}}]}).to_string(),
+            json!({"choices": [{"delta": {"content": " there!"}}]}).to_string(),
+            json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}).to_string(),
+            "[DONE]".to_string(),
+        ];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        assert_eq!(
+            event_labels(&events),
+            vec![
+                "reasoning-start",
+                "reasoning:The user wants",
+                "reasoning: a greeting.",
+                "text-start",
+                "text:Hello",
+                "text: there!",
+                // The reasoning block stays open until the choice finishes
+                "reasoning-end",
+                "stop:stop",
+                "done:stop",
+            ]
+        );
+        let ids: Vec<&str> = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::ReasoningStart { id, .. }
+                | StreamEvent::ReasoningDelta { id, .. }
+                | StreamEvent::ReasoningEnd { id } => Some(id.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert!(ids.windows(2).all(|pair| pair[0] == pair[1]));
+    }
+
+    #[test]
+    fn parse_stream_without_reasoning_emits_only_text_events() {
+        let protocol = OpenAiProtocol;
+        let chunks = vec![
+            json!({"choices": [{"delta": {"content": "Hi"}}]}).to_string(),
+            json!({"choices": [{"delta": {}, "finish_reason": "stop"}]}).to_string(),
+            "[DONE]".to_string(),
+        ];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        assert_eq!(
+            event_labels(&events),
+            vec!["text-start", "text:Hi", "stop:stop", "done:stop"]
+        );
+    }
+
+    #[test]
+    fn parse_stream_brackets_response_with_start_and_stop() {
+        let protocol = OpenAiProtocol;
+        let chunks = vec![
+            json!({"model": "gpt-4o", "choices": [{"delta": {"content": "Hi"}}]}).to_string(),
+            json!({"model": "gpt-4o", "choices": [{"delta": {"content": "!"}}]}).to_string(),
+            json!({"model": "gpt-4o", "choices": [{"delta": {}, "finish_reason": "length"}]})
+                .to_string(),
+            "[DONE]".to_string(),
+        ];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        assert_eq!(
+            event_labels(&events),
+            vec![
+                "start:gpt-4o",
+                "text-start",
+                "text:Hi",
+                "text:!",
+                "stop:length",
+                "done:length",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_stream_maps_finish_reason_to_stop_event() {
+        let protocol = OpenAiProtocol;
+        for reason in ["stop", "length", "content_filter", "tool_calls"] {
+            let chunks = vec![
+                json!({"choices": [{"delta": {"content": "Hi"}}]}).to_string(),
+                json!({"choices": [{"delta": {}, "finish_reason": reason}]}).to_string(),
+                // Some servers repeat the finish reason on a trailing usage chunk
+                json!({"choices": [{"delta": {}, "finish_reason": reason}]}).to_string(),
+                "[DONE]".to_string(),
+            ];
+
+            let events = collect_stream_events(&protocol, &chunks);
+
+            let stops: Vec<&str> = events
+                .iter()
+                .filter_map(|event| match event {
+                    StreamEvent::Stop { reason, .. } => Some(reason.as_str()),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(stops, vec![reason], "finish_reason {}", reason);
+        }
+    }
+
+    #[test]
+    fn parse_stream_reports_content_filter_results_with_the_stop() {
+        let protocol = OpenAiProtocol;
+        // Azure OpenAI cutting a reply short after its moderation flagged the output
+        let filter_results = json!({
+            "hate": { "filtered": false, "severity": "safe" },
+            "violence": { "filtered": true, "severity": "medium" }
+        });
+        let chunks = vec![
+            json!({"model": "gpt-4o", "choices": [{"delta": {"content": "Once upon"}}]})
+                .to_string(),
+            json!({"choices": [{
+                "delta": {},
+                "finish_reason": "content_filter",
+                "content_filter_results": filter_results
+            }]})
+            .to_string(),
+            "[DONE]".to_string(),
+        ];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        assert_eq!(
+            event_labels(&events),
+            vec![
+                "start:gpt-4o",
+                "text-start",
+                "text:Once upon",
+                "stop:content_filter",
+                "done:content_filter",
+            ]
+        );
+        let stop = events
+            .iter()
+            .find(|event| matches!(event, StreamEvent::Stop { .. }))
+            .expect("stop event");
+        assert_eq!(
+            serde_json::to_value(stop).expect("serialize"),
+            json!({
+                "type": "stop",
+                "reason": "content_filter",
+                "content_filter_results": filter_results
+            })
+        );
+    }
+
+    #[test]
+    fn parse_stream_emits_logprobs_after_the_text_delta() {
+        let protocol = OpenAiProtocol;
+        let chunks = vec![
+            json!({
+                "model": "gpt-4o-mini",
+                "choices": [{
+                    "index": 0,
+                    "delta": {"content": "Yes"},
+                    "logprobs": {
+                        "content": [{
+                            "token": "Yes",
+                            "logprob": -0.0012,
+                            "bytes": [89, 101, 115],
+                            "top_logprobs": [
+                                {"token": "Yes", "logprob": -0.0012, "bytes": [89, 101, 115]},
+                                {"token": "No", "logprob": -6.75, "bytes": [78, 111]}
+                            ]
+                        }],
+                        "refusal": null
+                    }
+                }]
+            })
+            .to_string(),
+            // Chunks without logprobs, or with `"logprobs": null`, add nothing
+            json!({"choices": [{"delta": {"content": "!"}, "logprobs": null}]}).to_string(),
+            "[DONE]".to_string(),
+        ];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        let logprobs: Vec<&Vec<TokenLogProb>> = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::LogProbs { tokens } => Some(tokens),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(logprobs.len(), 1);
+        let token = &logprobs[0][0];
+        assert_eq!(token.token, "Yes");
+        assert_eq!(token.logprob, -0.0012);
+        let alternatives: Vec<&str> = token
+            .top_logprobs
+            .iter()
+            .map(|t| t.token.as_str())
+            .collect();
+        assert_eq!(alternatives, vec!["Yes", "No"]);
+
+        let delta_at = events
+            .iter()
+            .position(|event| matches!(event, StreamEvent::TextDelta { text } if text == "Yes"));
+        let logprobs_at = events
+            .iter()
+            .position(|event| matches!(event, StreamEvent::LogProbs { .. }));
+        assert!(delta_at.is_some() && delta_at < logprobs_at);
+    }
+
+    fn read_file_tool() -> ToolDefinition {
+        ToolDefinition {
+            tool_type: "function".to_string(),
+            name: "readFile".to_string(),
+            description: Some("Read a file".to_string()),
+            parameters: json!({
+                "type": "object",
+                "properties": { "path": { "type": "string" } },
+                "required": ["path"]
+            }),
+            strict: false,
+        }
+    }
+
+    #[test]
+    fn build_request_serializes_tools_with_tool_choice() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("open main.rs".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let tools = vec![read_file_tool()];
+
+        let body = LlmProtocol::build_request(
+            &protocol,
+            "gpt-4o",
+            &messages,
+            Some(&tools),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .expect("build request");
+
+        assert_eq!(body.get("tool_choice"), Some(&json!("auto")));
+        assert_eq!(
+            body.get("tools"),
+            Some(&json!([{
+                "type": "function",
+                "function": {
+                    "name": "readFile",
+                    "description": "Read a file",
+                    "parameters": {
+                        "type": "object",
+                        "properties": { "path": { "type": "string" } },
+                        "required": ["path"]
+                    }
+                }
+            }]))
+        );
+
+        let forced = json!({ "openai": { "toolChoice": { "type": "function", "function": { "name": "readFile" } } } });
+        let body = LlmProtocol::build_request(
+            &protocol,
+            "gpt-4o",
+            &messages,
+            Some(&tools),
+            None,
+            None,
+            None,
+            None,
+            Some(&forced),
+            None,
+        )
+        .expect("build request");
+        assert_eq!(
+            body.get("tool_choice"),
+            Some(&json!({ "type": "function", "function": { "name": "readFile" } }))
+        );
+
+        let body = LlmProtocol::build_request(
+            &protocol, "gpt-4o", &messages, None, None, None, None, None, None, None,
+        )
+        .expect("build request");
+        assert!(body.get("tools").is_none());
+        assert!(body.get("tool_choice").is_none());
+    }
+
+    #[test]
+    fn parse_stream_emits_tool_call_deltas_for_two_chunk_call() {
+        let protocol = OpenAiProtocol;
+        let chunks = vec![
+            json!({"choices": [{"delta": {"tool_calls": [{
+                "index": 0,
+                "id": "call_1",
+                "type": "function",
+                "function": { "name": "readFile", "arguments": "{\"path\":" }
+            }]}}]})
+            .to_string(),
+            json!({"choices": [{"delta": {"tool_calls": [{
+                "index": 0,
+                "function": { "arguments": "\"src/main.rs\"}" }
+            }]}}]})
+            .to_string(),
+            json!({"choices": [{"delta": {}, "finish_reason": "tool_calls"}]}).to_string(),
+            "[DONE]".to_string(),
+        ];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        let deltas: Vec<(u32, Option<&str>, Option<&str>, &str)> = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::ToolCallDelta {
+                    index,
+                    id,
+                    name,
+                    arguments_chunk,
+                } => Some((
+                    *index,
+                    id.as_deref(),
+                    name.as_deref(),
+                    arguments_chunk.as_str(),
+                )),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            deltas,
+            vec![
+                (0, Some("call_1"), Some("readFile"), "{\"path\":"),
+                (0, None, None, "\"src/main.rs\"}"),
+            ]
+        );
+
+        // Concatenating the fragments by index reproduces the final arguments
+        let reassembled: String = deltas.iter().map(|delta| delta.3).collect();
+        assert_eq!(
+            serde_json::from_str::<Value>(&reassembled).expect("complete JSON"),
+            json!({ "path": "src/main.rs" })
+        );
+
+        let tool_calls: Vec<&StreamEvent> = events
+            .iter()
+            .filter(|event| matches!(event, StreamEvent::ToolCall { .. }))
+            .collect();
+        assert_eq!(tool_calls.len(), 1);
+        match tool_calls[0] {
+            StreamEvent::ToolCall {
+                tool_call_id,
+                tool_name,
+                input,
+                ..
+            } => {
+                assert_eq!(tool_call_id, "call_1");
+                assert_eq!(tool_name, "readFile");
+                assert_eq!(input, &json!({ "path": "src/main.rs" }));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn build_request_requests_usage_in_stream() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+
+        let body = LlmProtocol::build_request(
+            &protocol, "gpt-4o", &messages, None, None, None, None, None, None, None,
+        )
+        .expect("build request");
+
+        assert_eq!(
+            body.get("stream_options"),
+            Some(&json!({ "include_usage": true }))
+        );
+    }
+
+    #[test]
+    fn parse_stream_emits_usage_from_final_chunk() {
+        let protocol = OpenAiProtocol;
+        let chunks = vec![
+            json!({ "choices": [{ "index": 0, "delta": { "role": "assistant", "content": "Hi" } }], "usage": null })
+                .to_string(),
+            json!({ "choices": [{ "index": 0, "delta": {}, "finish_reason": "stop" }], "usage": null })
+                .to_string(),
+            json!({
+                "choices": [],
+                "usage": {
+                    "prompt_tokens": 12,
+                    "completion_tokens": 3,
+                    "total_tokens": 15,
+                    "prompt_tokens_details": { "cached_tokens": 8 }
+                }
+            })
+            .to_string(),
+            "[DONE]".to_string(),
+        ];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        assert_eq!(
+            serde_json::to_value(&events).expect("serialize events"),
+            json!([
+                { "type": "text-start" },
+                { "type": "text-delta", "text": "Hi" },
+                { "type": "stop", "reason": "stop" },
+                {
+                    "type": "usage",
+                    "input_tokens": 12,
+                    "output_tokens": 3,
+                    "total_tokens": 15,
+                    "cached_input_tokens": 8,
+                    "cache_creation_input_tokens": null
+                },
+                { "type": "done", "finish_reason": "stop" }
+            ])
+        );
+    }
+
+    #[test]
+    fn build_request_marks_cached_messages_for_caching_gateways() {
+        let protocol = OpenAiProtocol;
+        let messages = vec![
+            Message::System {
+                content: "long system prompt".to_string(),
+                provider_options: None,
+                cache: true,
+            },
+            Message::User {
+                content: MessageContent::Text("hi".to_string()),
+                provider_options: None,
+                cache: false,
+            },
+        ];
+
+        let body = LlmProtocol::build_request(
+            &protocol,
+            "anthropic/claude-sonnet-4.5",
+            &messages,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+        .expect("build request");
+
+        assert_eq!(
+            body["messages"][0]["content"],
+            json!([{
+                "type": "text",
+                "text": "long system prompt",
+                "cache_control": { "type": "ephemeral" }
+            }])
+        );
+        assert_eq!(body["messages"][1]["content"], json!("hi"));
+    }
+
+    #[test]
+    fn parse_stream_reads_deepseek_cache_hit_tokens() {
+        let protocol = OpenAiProtocol;
+        let chunks = vec![json!({
+            "choices": [],
+            "usage": {
+                "prompt_tokens": 100,
+                "completion_tokens": 5,
+                "total_tokens": 105,
+                "prompt_cache_hit_tokens": 96,
+                "prompt_cache_miss_tokens": 4
+            }
+        })
+        .to_string()];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        assert!(matches!(
+            events.as_slice(),
+            [StreamEvent::Usage {
+                cached_input_tokens: Some(96),
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn parse_stream_without_usage_still_finishes() {
+        let protocol = OpenAiProtocol;
+        let chunks = vec![
+            json!({ "choices": [{ "delta": { "content": "Hi" } }] }).to_string(),
+            json!({ "choices": [{ "delta": {}, "finish_reason": "stop" }] }).to_string(),
+            "[DONE]".to_string(),
+        ];
+
+        let events = collect_stream_events(&protocol, &chunks);
+
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, StreamEvent::Usage { .. })));
+        assert!(matches!(
+            events.last(),
+            Some(StreamEvent::Done { finish_reason }) if finish_reason.as_deref() == Some("stop")
+        ));
+    }
+
     #[test]
     fn build_request_includes_openrouter_reasoning_when_only_openrouter_is_set() {
         let protocol = OpenAiProtocol;
         let messages = vec![Message::User {
             content: MessageContent::Text("hi".to_string()),
             provider_options: None,
+            cache: false,
         }];
 
         let body = LlmProtocol::build_request(
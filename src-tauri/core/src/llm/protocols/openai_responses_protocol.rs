@@ -1,10 +1,15 @@
-use crate::llm::protocols::stream_parser::StreamParseState;
+use crate::llm::protocols::stream_parser::{parse_json_data, StreamParseState};
 use crate::llm::protocols::{
-    self, request_builder::RequestBuildContext, stream_parser::StreamParseContext, LlmProtocol,
-    OpenAiReasoningPartStatus, ProtocolRequestBuilder, ProtocolStreamParser, ProtocolStreamState,
-    ToolCallAccum,
+    self,
+    request_builder::{merge_extra_body, RequestBuildContext},
+    sse,
+    stream_parser::StreamParseContext,
+    LlmProtocol, OpenAiReasoningPartStatus, ProtocolRequestBuilder, ProtocolStreamParser,
+    ProtocolStreamState, ToolCallAccum,
+};
+use crate::llm::types::{
+    image_url, BuiltinTool, ContentPart, Message, MessageContent, StreamEvent, ToolDefinition,
 };
-use crate::llm::types::{ContentPart, Message, MessageContent, StreamEvent, ToolDefinition};
 use serde_json::{json, Value};
 
 pub struct OpenAiResponsesProtocol;
@@ -53,10 +58,10 @@ impl OpenAiResponsesProtocol {
                                 mapped.push(json!({ "type": "input_text", "text": text }));
                             }
                         }
-                        ContentPart::Image { image } => {
+                        ContentPart::Image { image, mime_type } => {
                             mapped.push(json!({
                                 "type": "input_image",
-                                "image_url": format!("data:image/png;base64,{}", image)
+                                "image_url": image_url(image, mime_type.as_deref())
                             }));
                         }
                         _ => {}
@@ -83,10 +88,10 @@ impl OpenAiResponsesProtocol {
                             pending_parts.push(json!({ "type": "output_text", "text": text }));
                         }
                     }
-                    ContentPart::Image { image } => {
+                    ContentPart::Image { image, mime_type } => {
                         pending_parts.push(json!({
                             "type": "input_image",
-                            "image_url": format!("data:image/png;base64,{}", image)
+                            "image_url": image_url(image, mime_type.as_deref())
                         }));
                     }
                     ContentPart::ToolCall {
@@ -212,6 +217,18 @@ impl ProtocolRequestBuilder for OpenAiResponsesProtocol {
             }
             body["tools"] = Value::Array(mapped_tools);
         }
+        if !ctx.builtin_tools.is_empty() {
+            let hosted = ctx.builtin_tools.iter().map(|tool| match tool {
+                BuiltinTool::WebSearch => json!({ "type": "web_search" }),
+                BuiltinTool::CodeInterpreter => {
+                    json!({ "type": "code_interpreter", "container": { "type": "auto" } })
+                }
+            });
+            match body["tools"].as_array_mut() {
+                Some(tools) => tools.extend(hosted),
+                None => body["tools"] = Value::Array(hosted.collect()),
+            }
+        }
         if let Some(temperature) = ctx.temperature {
             body["temperature"] = json!(temperature);
         }
@@ -221,6 +238,9 @@ impl ProtocolRequestBuilder for OpenAiResponsesProtocol {
         if let Some(top_k) = ctx.top_k {
             body["top_k"] = json!(top_k);
         }
+        if let Some(effort) = ctx.reasoning_effort {
+            body["reasoning"]["effort"] = json!(effort.as_str());
+        }
         if let Some(provider_options) = ctx.provider_options {
             if let Some(openai_opts) = provider_options.get("openai") {
                 if let Some(reasoning_effort) = openai_opts.get("reasoningEffort") {
@@ -241,13 +261,7 @@ impl ProtocolRequestBuilder for OpenAiResponsesProtocol {
                 }
             }
         }
-        if let Some(extra_body) = ctx.extra_body {
-            if let Some(obj) = extra_body.as_object() {
-                for (k, v) in obj {
-                    body[k] = v.clone();
-                }
-            }
-        }
+        merge_extra_body(&mut body, ctx.extra_body);
 
         Ok(body)
     }
@@ -285,6 +299,15 @@ pub(crate) fn parse_openai_oauth_event(
     data: &str,
     state: &mut StreamParseState,
 ) -> Result<Option<StreamEvent>, String> {
+    // Some proxies end Responses streams with the chat completions sentinel
+    if sse::is_done(data) {
+        return Ok(None);
+    }
+    let Some(payload) = parse_json_data(state, data)? else {
+        return Ok(None);
+    };
+    let data = payload.to_string();
+
     let mut legacy_state = ProtocolStreamState {
         finish_reason: state.finish_reason.clone(),
         tool_calls: std::mem::take(&mut state.tool_calls),
@@ -294,6 +317,7 @@ pub(crate) fn parse_openai_oauth_event(
         current_thinking_id: state.current_thinking_id.clone(),
         pending_events: std::mem::take(&mut state.pending_events),
         text_started: state.text_started,
+        stream_started: state.stream_started,
         content_block_types: std::mem::take(&mut state.content_block_types),
         content_block_ids: std::mem::take(&mut state.content_block_ids),
         reasoning_started: state.reasoning_started,
@@ -302,10 +326,11 @@ pub(crate) fn parse_openai_oauth_event(
         openai_store: state.openai_store,
     };
 
-    let result = parse_openai_oauth_event_legacy(event_type, data, &mut legacy_state);
+    let result = parse_openai_oauth_event_legacy(event_type, &data, &mut legacy_state);
 
     state.finish_reason = legacy_state.finish_reason;
     state.text_started = legacy_state.text_started;
+    state.stream_started = legacy_state.stream_started;
     state.reasoning_started = legacy_state.reasoning_started;
     state.reasoning_id = legacy_state.reasoning_id;
     state.pending_events = legacy_state.pending_events;
@@ -372,6 +397,8 @@ pub(crate) fn parse_openai_oauth_event_legacy(
                 total_tokens: total_tokens.map(|v| v as i32),
                 cached_input_tokens: None,
                 cache_creation_input_tokens: None,
+                timing: None,
+                estimated: false,
             });
         }
 
@@ -912,6 +939,8 @@ pub(crate) fn parse_openai_oauth_event_legacy(
                         total_tokens: total_tokens.map(|v| v as i32),
                         cached_input_tokens: None,
                         cache_creation_input_tokens: None,
+                        timing: None,
+                        estimated: false,
                     });
                 }
                 // Only emit text from response.completed if no text was streamed
@@ -1182,8 +1211,18 @@ impl LlmProtocol for OpenAiResponsesProtocol {
             max_tokens,
             top_p,
             top_k,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
             provider_options,
             extra_body,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            builtin_tools: &[],
+            reasoning_effort: None,
         };
         ProtocolRequestBuilder::build_request(self, ctx)
     }
@@ -1198,6 +1237,7 @@ impl LlmProtocol for OpenAiResponsesProtocol {
         let mut new_state = protocols::stream_parser::StreamParseState {
             finish_reason: state.finish_reason.clone(),
             text_started: state.text_started,
+            stream_started: state.stream_started,
             reasoning_started: state.reasoning_started,
             reasoning_id: state.reasoning_id.clone(),
             pending_events: std::mem::take(&mut state.pending_events),
@@ -1210,12 +1250,18 @@ impl LlmProtocol for OpenAiResponsesProtocol {
             current_thinking_id: state.current_thinking_id.clone(),
             openai_reasoning: std::mem::take(&mut state.openai_reasoning),
             openai_store: state.openai_store,
+            input_tokens: None,
+            cached_input_tokens: None,
+            cache_creation_input_tokens: None,
+            partial_data: String::new(),
+            tolerant_json: state.tolerant_json,
         };
 
         let result = ProtocolStreamParser::parse_stream_event(self, ctx, &mut new_state);
 
         state.finish_reason = new_state.finish_reason;
         state.text_started = new_state.text_started;
+        state.stream_started = new_state.stream_started;
         state.reasoning_started = new_state.reasoning_started;
         state.reasoning_id = new_state.reasoning_id;
         state.pending_events = new_state.pending_events;
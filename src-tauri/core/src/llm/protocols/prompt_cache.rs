@@ -0,0 +1,109 @@
+// Prompt caching directives
+// A message flagged as a cache breakpoint gets `cache_control` on its last content block,
+// the Anthropic shape that OpenRouter and other caching gateways accept as well
+
+use serde_json::{json, Map, Value};
+
+/// Anthropic rejects requests with more than four cache breakpoints
+pub const MAX_CACHE_BREAKPOINTS: usize = 4;
+
+pub fn cache_control() -> Value {
+    json!({ "type": "ephemeral" })
+}
+
+/// Put `cache_control` on the last block of `content`; plain text becomes a text block first
+pub fn mark_content(content: &mut Value) {
+    if let Some(text) = content.as_str() {
+        *content = json!([{ "type": "text", "text": text }]);
+    }
+    if let Some(block) = content
+        .as_array_mut()
+        .and_then(|blocks| blocks.last_mut())
+        .and_then(|block| block.as_object_mut())
+    {
+        block.insert("cache_control".to_string(), cache_control());
+    }
+}
+
+/// Keep only the last `max` breakpoints across `system` and `messages`, in prompt order
+/// A later breakpoint still covers the prefix an earlier one would have cached
+pub fn limit_breakpoints(body: &mut Value, max: usize) {
+    let Some(body) = body.as_object_mut() else {
+        return;
+    };
+    let mut system = Vec::new();
+    let mut messages = Vec::new();
+    for (key, value) in body.iter_mut() {
+        match key.as_str() {
+            "system" => system.extend(marked_blocks(value)),
+            "messages" => {
+                for message in value.as_array_mut().into_iter().flatten() {
+                    if let Some(content) = message.get_mut("content") {
+                        messages.extend(marked_blocks(content));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let marked: Vec<_> = system.into_iter().chain(messages).collect();
+    let excess = marked.len().saturating_sub(max);
+    for block in marked.into_iter().take(excess) {
+        block.remove("cache_control");
+    }
+}
+
+fn marked_blocks(content: &mut Value) -> impl Iterator<Item = &mut Map<String, Value>> {
+    content
+        .as_array_mut()
+        .into_iter()
+        .flatten()
+        .filter_map(|block| block.as_object_mut())
+        .filter(|block| block.contains_key("cache_control"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mark_content_wraps_plain_text() {
+        let mut content = json!("You are a careful reviewer");
+
+        mark_content(&mut content);
+
+        assert_eq!(
+            content,
+            json!([{
+                "type": "text",
+                "text": "You are a careful reviewer",
+                "cache_control": { "type": "ephemeral" }
+            }])
+        );
+    }
+
+    #[test]
+    fn limit_breakpoints_drops_the_earliest() {
+        let block =
+            |text: &str| json!({ "type": "text", "text": text, "cache_control": cache_control() });
+        let mut body = json!({
+            "system": [block("system")],
+            "messages": [
+                { "role": "user", "content": [block("one")] },
+                { "role": "assistant", "content": [block("two")] },
+                { "role": "user", "content": [block("three"), block("four")] }
+            ]
+        });
+
+        limit_breakpoints(&mut body, MAX_CACHE_BREAKPOINTS);
+
+        assert!(body["system"][0].get("cache_control").is_none());
+        assert!(body["messages"][0]["content"][0]
+            .get("cache_control")
+            .is_some());
+        assert!(body["messages"][2]["content"][1]
+            .get("cache_control")
+            .is_some());
+    }
+}
@@ -1,6 +1,6 @@
 // Protocol-level request building trait
 // Handles conversion from internal message types to provider-specific API format
-use crate::llm::types::{Message, ToolDefinition};
+use crate::llm::types::{BuiltinTool, Message, ReasoningEffort, ResponseFormat, ToolDefinition};
 use serde_json::Value;
 
 /// Context for building a request
@@ -13,8 +13,81 @@ pub struct RequestBuildContext<'a> {
     pub max_tokens: Option<i32>,
     pub top_p: Option<f32>,
     pub top_k: Option<i32>,
+    pub frequency_penalty: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub stop: Option<&'a [String]>,
+    pub response_format: Option<&'a ResponseFormat>,
+    /// Sent as `user` by protocols that accept an end-user identifier
+    pub user_id: Option<&'a str>,
     pub provider_options: Option<&'a Value>,
     pub extra_body: Option<&'a Value>,
+    /// False asks for a single JSON response instead of an event stream
+    pub stream: bool,
+    /// Ask for per-token log probabilities; only set when the provider reports them
+    pub logprobs: bool,
+    pub top_logprobs: Option<u8>,
+    /// Provider-side tools, already checked against the provider's capabilities
+    pub builtin_tools: &'a [BuiltinTool],
+    /// Sent only to models of a reasoning family, in the form the protocol takes
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+/// Key inside `extra_body` whose entries replace fields the builder already set
+pub const EXTRA_BODY_OVERRIDE_KEY: &str = "$override";
+
+/// Deep-merge a provider's `extra_body` into a built request body
+/// Config values only fill gaps: objects merge key by key, and a field the builder
+/// already set wins. Entries under `"$override"` replace builder fields explicitly
+pub fn merge_extra_body(body: &mut Value, extra_body: Option<&Value>) {
+    let Some(extra) = extra_body.and_then(Value::as_object) else {
+        return;
+    };
+    for (key, value) in extra {
+        if key != EXTRA_BODY_OVERRIDE_KEY {
+            fill_missing(body, key, value);
+        }
+    }
+    if let Some(overrides) = extra.get(EXTRA_BODY_OVERRIDE_KEY) {
+        override_fields(body, overrides);
+    }
+}
+
+fn fill_missing(target: &mut Value, key: &str, value: &Value) {
+    let Some(obj) = target.as_object_mut() else {
+        return;
+    };
+    match obj.get_mut(key) {
+        None | Some(Value::Null) => {
+            obj.insert(key.to_string(), value.clone());
+        }
+        Some(existing) if existing.is_object() && value.is_object() => {
+            for (nested_key, nested_value) in value.as_object().into_iter().flatten() {
+                fill_missing(existing, nested_key, nested_value);
+            }
+        }
+        Some(_) => {
+            log::debug!(
+                "extra_body field '{}' ignored, the request already sets it",
+                key
+            );
+        }
+    }
+}
+
+fn override_fields(target: &mut Value, overrides: &Value) {
+    let (Some(obj), Some(overrides)) = (target.as_object_mut(), overrides.as_object()) else {
+        return;
+    };
+    for (key, value) in overrides {
+        match obj.get_mut(key) {
+            Some(existing) if existing.is_object() && value.is_object() => {
+                override_fields(existing, value);
+            }
+            _ => {
+                obj.insert(key.to_string(), value.clone());
+            }
+        }
+    }
 }
 
 /// Trait for building protocol-specific requests
@@ -37,3 +110,66 @@ pub trait ProtocolMessageConverter: Send + Sync {
     /// Build system message (if supported by protocol)
     fn build_system_message(&self, content: &str) -> Option<Value>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_extra_body_fills_gaps_without_replacing_builder_fields() {
+        let mut body = json!({
+            "model": "qwen3",
+            "stream": true,
+            "reasoning": { "effort": "low" },
+            "tool_choice": null
+        });
+        let extra = json!({
+            "enable_thinking": true,
+            "stream": false,
+            "reasoning": { "effort": "high", "enabled": true },
+            "tool_choice": "none",
+            "vendor": { "region": "eu" }
+        });
+
+        merge_extra_body(&mut body, Some(&extra));
+
+        assert_eq!(
+            body,
+            json!({
+                "model": "qwen3",
+                "stream": true,
+                "reasoning": { "effort": "low", "enabled": true },
+                "tool_choice": "none",
+                "enable_thinking": true,
+                "vendor": { "region": "eu" }
+            })
+        );
+    }
+
+    #[test]
+    fn merge_extra_body_applies_explicit_overrides() {
+        let mut body = json!({
+            "model": "qwen3",
+            "stream_options": { "include_usage": true },
+            "temperature": 0.7
+        });
+        let extra = json!({
+            "$override": {
+                "temperature": 0.0,
+                "stream_options": { "include_usage": false }
+            }
+        });
+
+        merge_extra_body(&mut body, Some(&extra));
+
+        assert_eq!(body["temperature"], json!(0.0));
+        assert_eq!(body["stream_options"], json!({ "include_usage": false }));
+        assert!(body.get("$override").is_none());
+
+        let before = body.clone();
+        merge_extra_body(&mut body, None);
+        merge_extra_body(&mut body, Some(&json!(["not", "an", "object"])));
+        assert_eq!(body, before);
+    }
+}
@@ -0,0 +1,227 @@
+// Protocol-level parsing of non-streaming responses
+// Reads the single JSON body a provider returns when `stream` is off
+use crate::llm::ai_services::types::TokenUsage;
+use crate::llm::protocols::{cohere_protocol::CohereProtocol, gemini_protocol::GeminiProtocol};
+use crate::llm::types::ProtocolType;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Full reply of a non-streaming completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionResult {
+    pub text: String,
+    pub usage: Option<TokenUsage>,
+    #[serde(rename = "finishReason")]
+    pub finish_reason: Option<String>,
+}
+
+/// Parse a completion body in the wire format of `protocol`
+pub fn parse_completion(protocol: ProtocolType, body: &Value) -> Result<CompletionResult, String> {
+    if let Some(error) = body.get("error").filter(|error| !error.is_null()) {
+        let message = error
+            .get("message")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| error.to_string());
+        return Err(format!("Completion failed: {}", message));
+    }
+    match protocol {
+        ProtocolType::OpenAiCompatible => parse_openai(body),
+        ProtocolType::Claude | ProtocolType::Anthropic => Ok(parse_anthropic(body)),
+        ProtocolType::Gemini => Ok(parse_gemini(body)),
+        ProtocolType::Cohere => Ok(parse_cohere(body)),
+    }
+}
+
+fn parse_openai(body: &Value) -> Result<CompletionResult, String> {
+    let choice = body
+        .get("choices")
+        .and_then(|choices| choices.get(0))
+        .ok_or_else(|| "Completion response has no choices / 响应中没有结果".to_string())?;
+    let content = choice.get("message").and_then(|m| m.get("content"));
+    // Content is a plain string, or a list of parts from some compatible servers
+    let text = match content {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Array(parts)) => text_parts(parts, |_| true),
+        _ => String::new(),
+    };
+    let usage = body.get("usage").map(|usage| TokenUsage {
+        input_tokens: token_count(usage, "prompt_tokens"),
+        output_tokens: token_count(usage, "completion_tokens"),
+        cached_input_tokens: usage
+            .get("prompt_tokens_details")
+            .and_then(|details| optional_count(details, "cached_tokens")),
+        cache_creation_input_tokens: None,
+        estimated: false,
+    });
+    Ok(CompletionResult {
+        text,
+        usage,
+        finish_reason: string_field(choice, "finish_reason"),
+    })
+}
+
+fn parse_anthropic(body: &Value) -> CompletionResult {
+    let text = body
+        .get("content")
+        .and_then(Value::as_array)
+        .map(|blocks| text_parts(blocks, |block| block["type"] == "text"))
+        .unwrap_or_default();
+    let usage = body.get("usage").map(|usage| TokenUsage {
+        input_tokens: token_count(usage, "input_tokens"),
+        output_tokens: token_count(usage, "output_tokens"),
+        cached_input_tokens: optional_count(usage, "cache_read_input_tokens"),
+        cache_creation_input_tokens: optional_count(usage, "cache_creation_input_tokens"),
+        estimated: false,
+    });
+    CompletionResult {
+        text,
+        usage,
+        finish_reason: string_field(body, "stop_reason"),
+    }
+}
+
+fn parse_gemini(body: &Value) -> CompletionResult {
+    // Only the first candidate is read, as when streaming
+    let candidate = body.get("candidates").and_then(|c| c.get(0));
+    let text = candidate
+        .and_then(|c| c.get("content"))
+        .and_then(|content| content.get("parts"))
+        .and_then(Value::as_array)
+        .map(|parts| text_parts(parts, |part| part["thought"] != true))
+        .unwrap_or_default();
+    let usage = body.get("usageMetadata").map(|usage| TokenUsage {
+        input_tokens: token_count(usage, "promptTokenCount"),
+        output_tokens: token_count(usage, "candidatesTokenCount"),
+        cached_input_tokens: optional_count(usage, "cachedContentTokenCount"),
+        cache_creation_input_tokens: None,
+        estimated: false,
+    });
+    CompletionResult {
+        text,
+        usage,
+        finish_reason: candidate
+            .and_then(|c| c.get("finishReason"))
+            .and_then(Value::as_str)
+            .map(GeminiProtocol::map_finish_reason),
+    }
+}
+
+fn parse_cohere(body: &Value) -> CompletionResult {
+    let text = body
+        .get("message")
+        .and_then(|message| message.get("content"))
+        .and_then(Value::as_array)
+        .map(|parts| text_parts(parts, |part| part["type"] == "text"))
+        .unwrap_or_default();
+    let usage = body
+        .get("usage")
+        .and_then(|usage| usage.get("tokens").or_else(|| usage.get("billed_units")))
+        .map(|tokens| TokenUsage {
+            input_tokens: token_count(tokens, "input_tokens"),
+            output_tokens: token_count(tokens, "output_tokens"),
+            cached_input_tokens: None,
+            cache_creation_input_tokens: None,
+            estimated: false,
+        });
+    CompletionResult {
+        text,
+        usage,
+        finish_reason: body
+            .get("finish_reason")
+            .and_then(Value::as_str)
+            .map(CohereProtocol::map_finish_reason),
+    }
+}
+
+/// Concatenated `text` of the parts `keep` accepts
+fn text_parts(parts: &[Value], keep: impl Fn(&Value) -> bool) -> String {
+    parts
+        .iter()
+        .filter(|part| keep(part))
+        .filter_map(|part| part.get("text").and_then(Value::as_str))
+        .collect()
+}
+
+fn token_count(usage: &Value, key: &str) -> u32 {
+    optional_count(usage, key).unwrap_or(0)
+}
+
+fn optional_count(usage: &Value, key: &str) -> Option<u32> {
+    usage
+        .get(key)
+        .and_then(Value::as_u64)
+        .map(|tokens| tokens as u32)
+}
+
+fn string_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_openai_chat_completion() {
+        let body = json!({
+            "id": "chatcmpl-9x",
+            "object": "chat.completion",
+            "model": "gpt-4o-mini",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": "Hello there!" },
+                "finish_reason": "stop"
+            }],
+            "usage": {
+                "prompt_tokens": 12,
+                "completion_tokens": 4,
+                "total_tokens": 16,
+                "prompt_tokens_details": { "cached_tokens": 8 }
+            }
+        });
+
+        let result = parse_completion(ProtocolType::OpenAiCompatible, &body).expect("parse");
+
+        assert_eq!(result.text, "Hello there!");
+        assert_eq!(result.finish_reason.as_deref(), Some("stop"));
+        let usage = result.usage.expect("usage");
+        assert_eq!((usage.input_tokens, usage.output_tokens), (12, 4));
+        assert_eq!(usage.cached_input_tokens, Some(8));
+    }
+
+    #[test]
+    fn openai_completion_without_choices_is_an_error() {
+        let err = parse_completion(ProtocolType::OpenAiCompatible, &json!({ "choices": [] }))
+            .expect_err("no choices");
+        assert!(err.contains("no choices"), "{}", err);
+
+        let err = parse_completion(
+            ProtocolType::OpenAiCompatible,
+            &json!({ "error": { "message": "model not found" } }),
+        )
+        .expect_err("error body");
+        assert!(err.contains("model not found"), "{}", err);
+    }
+
+    #[test]
+    fn parses_anthropic_message_text_blocks() {
+        let body = json!({
+            "type": "message",
+            "content": [
+                { "type": "thinking", "thinking": "hmm" },
+                { "type": "text", "text": "Hi" },
+                { "type": "text", "text": " there" }
+            ],
+            "stop_reason": "end_turn",
+            "usage": { "input_tokens": 20, "output_tokens": 3, "cache_read_input_tokens": 5 }
+        });
+
+        let result = parse_completion(ProtocolType::Anthropic, &body).expect("parse");
+
+        assert_eq!(result.text, "Hi there");
+        assert_eq!(result.finish_reason.as_deref(), Some("end_turn"));
+        assert_eq!(result.usage.expect("usage").cached_input_tokens, Some(5));
+    }
+}
@@ -0,0 +1,155 @@
+// Server-sent event framing
+// Splits a streaming body into `event`/`data` records; what the data means is left to the
+// protocol parsers
+use crate::llm::protocols::stream_parser::take_sse_frame;
+
+/// Data payload OpenAI-style streams send after their last chunk
+pub const DONE_SENTINEL: &str = "[DONE]";
+
+/// Whether a `data:` payload is the `[DONE]` sentinel
+pub fn is_done(data: &str) -> bool {
+    data.trim() == DONE_SENTINEL
+}
+
+/// One event of an SSE stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseRecord {
+    /// Name from the `event:` line, for protocols that dispatch on it (Anthropic, Cohere)
+    pub event: Option<String>,
+    /// The event's `data:` lines joined with `\n`
+    pub data: String,
+}
+
+impl SseRecord {
+    /// Parse one event block, with lines ending in `\n` or `\r\n`
+    /// Returns None for blocks without data, such as comments and keep-alives
+    pub fn parse(block: &str) -> Option<Self> {
+        let mut event = None;
+        let mut data_lines = Vec::new();
+        for line in block.lines() {
+            if let Some(rest) = line.strip_prefix("event:") {
+                event = Some(rest.trim().to_string());
+            } else if let Some(rest) = line.strip_prefix("data:") {
+                // Only the single optional space after the colon is dropped, per the SSE spec
+                data_lines.push(rest.strip_prefix(' ').unwrap_or(rest));
+            } else if line == "data" {
+                data_lines.push("");
+            }
+        }
+        if data_lines.is_empty() {
+            return None;
+        }
+        Some(Self {
+            event,
+            data: data_lines.join("\n"),
+        })
+    }
+
+    pub fn is_done(&self) -> bool {
+        is_done(&self.data)
+    }
+}
+
+/// Buffers the chunks of a streaming body and yields each record once its blank line arrives
+/// Call `feed` with every chunk, then iterate to drain the records it completed
+#[derive(Debug, Default)]
+pub struct SseLineReader {
+    buffer: Vec<u8>,
+}
+
+impl SseLineReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Next complete record, or None until more bytes arrive
+    pub fn next_record(&mut self) -> Result<Option<SseRecord>, String> {
+        while let Some(block) = take_sse_frame(&mut self.buffer) {
+            if let Some(record) = parse_block(block)? {
+                return Ok(Some(record));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Record left in the buffer when the body ended without a closing blank line
+    pub fn finish(&mut self) -> Result<Option<SseRecord>, String> {
+        parse_block(std::mem::take(&mut self.buffer))
+    }
+}
+
+impl Iterator for SseLineReader {
+    type Item = Result<SseRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+fn parse_block(block: Vec<u8>) -> Result<Option<SseRecord>, String> {
+    let block =
+        String::from_utf8(block).map_err(|e| format!("Invalid UTF-8 in SSE event: {}", e))?;
+    Ok(SseRecord::parse(&block))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(event: Option<&str>, data: &str) -> SseRecord {
+        SseRecord {
+            event: event.map(str::to_string),
+            data: data.to_string(),
+        }
+    }
+
+    #[test]
+    fn joins_multi_line_data_fields() {
+        let mut reader = SseLineReader::new();
+        reader.feed(b"event: message\ndata: {\"a\":\ndata:  1}\ndata\n: keep-alive\n\n");
+
+        let records: Vec<SseRecord> = reader.by_ref().collect::<Result<_, _>>().expect("parse");
+
+        assert_eq!(records, vec![record(Some("message"), "{\"a\":\n 1}\n")]);
+    }
+
+    #[test]
+    fn handles_mixed_line_endings_across_chunks() {
+        let mut reader = SseLineReader::new();
+        let mut records = Vec::new();
+        for chunk in [
+            "data: one\r\n\r",
+            "\ndata: two\n\n: ping\r\n\r\nevent: done\r\ndata: [DO",
+            "NE]\r\n\ndata: tail",
+        ] {
+            reader.feed(chunk.as_bytes());
+            records.extend(reader.by_ref().map(|record| record.expect("record")));
+        }
+
+        assert_eq!(
+            records,
+            vec![
+                record(None, "one"),
+                record(None, "two"),
+                record(Some("done"), "[DONE]"),
+            ]
+        );
+        assert!(records[2].is_done());
+        assert_eq!(reader.finish(), Ok(Some(record(None, "tail"))));
+        assert_eq!(reader.finish(), Ok(None));
+    }
+
+    #[test]
+    fn rejects_blocks_that_are_not_utf8() {
+        let mut reader = SseLineReader::new();
+        reader.feed(b"data: \xff\xfe\n\n");
+
+        let err = reader.next_record().expect_err("invalid utf-8");
+
+        assert!(err.contains("Invalid UTF-8"), "{}", err);
+    }
+}
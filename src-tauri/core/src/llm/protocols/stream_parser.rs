@@ -1,6 +1,11 @@
 // Protocol-level stream parsing trait
 // Handles conversion from SSE stream data to internal StreamEvent types
+use crate::llm::protocols::sse::SseRecord;
 use crate::llm::types::StreamEvent;
+use serde_json::Value;
+
+/// Upper bound on a payload held back while waiting for the rest of its JSON
+const MAX_PARTIAL_DATA_LEN: usize = 1 << 20;
 
 /// State maintained during stream parsing
 #[derive(Default)]
@@ -10,7 +15,14 @@ pub struct StreamParseState {
     pub reasoning_started: bool,
     pub reasoning_id: Option<String>,
     pub pending_events: Vec<StreamEvent>,
+    // Set once the `Start` lifecycle event has gone out
+    pub stream_started: bool,
     // Tool call accumulation state
+    // Streams split one call across chunks: the first carries `index`, `id` and `name`, later
+    // ones only `index` and an `arguments` fragment. Fragments are appended to the entry keyed
+    // by the call id (resolved through `tool_call_index_map` when only `index` is present),
+    // `tool_call_order` keeps the index order, and `emitted_tool_calls` prevents emitting a
+    // `ToolCall` twice once the arguments parse as complete JSON.
     pub tool_calls: std::collections::HashMap<String, super::ToolCallAccum>,
     pub tool_call_order: Vec<String>,
     pub emitted_tool_calls: std::collections::HashSet<String>,
@@ -22,6 +34,14 @@ pub struct StreamParseState {
     // OpenAI Responses reasoning summary tracking
     pub openai_reasoning: std::collections::HashMap<String, super::OpenAiReasoningState>,
     pub openai_store: Option<bool>,
+    // Anthropic Messages usage reported in message_start, merged into the final usage event
+    pub input_tokens: Option<i32>,
+    pub cached_input_tokens: Option<i32>,
+    pub cache_creation_input_tokens: Option<i32>,
+    // `data:` payload cut off mid-JSON, completed by the next frame
+    pub partial_data: String,
+    // Provider opted into `tolerant_stream_json`: repair or drop malformed payloads
+    pub tolerant_json: bool,
 }
 
 impl StreamParseState {
@@ -31,6 +51,268 @@ impl StreamParseState {
     }
 }
 
+/// Wire framing of a streaming response body
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StreamFormat {
+    /// Server-sent events separated by blank lines
+    #[default]
+    Sse,
+    /// One JSON array whose elements arrive incrementally (Gemini streamGenerateContent)
+    JsonArray,
+    /// Newline-delimited JSON objects (Ollama /api/chat)
+    Ndjson,
+}
+
+/// Find the first blank line in an SSE body, returns (index, delimiter_length)
+/// Handles \n\n and \r\n\r\n, including streams that mix the two
+pub fn find_sse_delimiter(buf: &[u8]) -> Option<(usize, usize)> {
+    buf.windows(2)
+        .enumerate()
+        .find_map(|(pos, pair)| match pair {
+            b"\n\n" if pos > 0 && buf[pos - 1] == b'\r' => Some((pos - 1, 3)),
+            b"\n\n" => Some((pos, 2)),
+            b"\n\r" if buf.get(pos + 2) == Some(&b'\n') => {
+                // `pos` sits on the \n ending the last line, which may itself follow a \r
+                if pos > 0 && buf[pos - 1] == b'\r' {
+                    Some((pos - 1, 4))
+                } else {
+                    Some((pos, 3))
+                }
+            }
+            _ => None,
+        })
+}
+
+/// Take the next complete SSE event block out of the buffer, leaving partial lines in place
+pub fn take_sse_frame(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let (idx, delimiter_len) = find_sse_delimiter(buf)?;
+    let frame = buf[..idx].to_vec();
+    buf.drain(..idx + delimiter_len);
+    Some(frame)
+}
+
+/// Parse a `data:` payload as JSON, joining payloads a provider split across frames
+/// Returns None for keep-alive payloads and for fragments still waiting on the rest
+/// In tolerant mode trailing commas are trimmed and payloads that still fail are dropped
+pub fn parse_json_data(state: &mut StreamParseState, data: &str) -> Result<Option<Value>, String> {
+    let data = data.trim();
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    let pending = std::mem::take(&mut state.partial_data);
+    if pending.is_empty() {
+        return match serde_json::from_str(data) {
+            Ok(payload) => Ok(Some(payload)),
+            Err(e) if e.is_eof() => hold_partial_data(state, data.to_string()),
+            Err(e) if state.tolerant_json => Ok(repair_json(data, &e)),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+
+    // A payload that parses on its own starts a new event, so the held fragment never completed
+    if let Ok(payload) = serde_json::from_str(data) {
+        log::warn!("Dropping incomplete stream payload: {}", pending);
+        return Ok(Some(payload));
+    }
+
+    let joined = pending + data;
+    match serde_json::from_str(&joined) {
+        Ok(payload) => Ok(Some(payload)),
+        Err(e) if e.is_eof() => hold_partial_data(state, joined),
+        Err(e) if state.tolerant_json => Ok(repair_json(&joined, &e)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// `Start` for the model a chunk says is answering, once per stream
+/// Returns None after the first call that named a model
+pub fn start_event(
+    state: &mut StreamParseState,
+    model: Option<&str>,
+    system_fingerprint: Option<&str>,
+) -> Option<StreamEvent> {
+    if state.stream_started {
+        return None;
+    }
+    let model = model.filter(|model| !model.is_empty())?;
+    state.stream_started = true;
+    Some(StreamEvent::Start {
+        model: model.to_string(),
+        system_fingerprint: system_fingerprint
+            .filter(|fingerprint| !fingerprint.is_empty())
+            .map(str::to_string),
+    })
+}
+
+/// Retry a payload with its trailing commas removed, dropping it with a warning if that fails
+fn repair_json(data: &str, error: &serde_json::Error) -> Option<Value> {
+    match serde_json::from_str(&trim_trailing_commas(data)) {
+        Ok(payload) => Some(payload),
+        Err(_) => {
+            log::warn!("Dropping malformed stream payload ({}): {}", error, data);
+            None
+        }
+    }
+}
+
+/// Remove commas that directly precede a closing `}` or `]`, or end the payload
+fn trim_trailing_commas(data: &str) -> String {
+    let mut output = String::with_capacity(data.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = data.trim_end().chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let rest = chars.clone().find(|next| !next.is_whitespace());
+            if matches!(rest, None | Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// Split a `data:` payload holding several JSON objects back to back, as in
+/// `{...}{...}` or `{...}\ndata: {...}`, into one payload per object
+/// Anything after the last complete object, such as a fragment or `[DONE]`, is kept as the
+/// final payload
+pub fn split_json_payloads(data: &str) -> Vec<String> {
+    let mut payloads = Vec::new();
+    let mut rest = data.trim();
+    while !rest.is_empty() {
+        rest = rest.strip_prefix("data:").map_or(rest, str::trim_start);
+        let Some(end) = rest
+            .starts_with('{')
+            .then(|| json_object_end(rest.as_bytes()))
+            .flatten()
+        else {
+            payloads.push(rest.to_string());
+            break;
+        };
+        payloads.push(rest[..end].to_string());
+        rest = rest[end..].trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+    payloads
+}
+
+fn hold_partial_data(state: &mut StreamParseState, data: String) -> Result<Option<Value>, String> {
+    if data.len() > MAX_PARTIAL_DATA_LEN {
+        return Err(format!(
+            "Incomplete stream payload exceeded {} bytes",
+            MAX_PARTIAL_DATA_LEN
+        ));
+    }
+    state.partial_data = data;
+    Ok(None)
+}
+
+/// Take the next complete line out of a newline-delimited JSON body
+pub fn take_ndjson_line(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let pos = buf.iter().position(|b| *b == b'\n')?;
+    let mut line: Vec<u8> = buf.drain(..=pos).collect();
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Some(line)
+}
+
+/// Take the next complete element out of a streamed JSON array body such as
+/// `[{...},\r\n{...}]`, leaving any partial element in the buffer
+pub fn take_json_array_element(buf: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let Some(start) = buf.iter().position(|b| *b == b'{') else {
+        // Only separators ('[', ',', ']' and whitespace) are buffered
+        buf.clear();
+        return None;
+    };
+
+    let end = start + json_object_end(&buf[start..])?;
+    let element = buf[start..end].to_vec();
+    buf.drain(..end);
+    Some(element)
+}
+
+/// Length of the JSON object that `bytes` starts with, or None while it is incomplete
+fn json_object_end(bytes: &[u8]) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (offset, byte) in bytes.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if *byte == b'\\' {
+                escaped = true;
+            } else if *byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'{' => depth += 1,
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(offset + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Take the next complete frame out of the buffer for the given stream format
+pub fn take_frame(format: StreamFormat, buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+    match format {
+        StreamFormat::Sse => take_sse_frame(buffer),
+        StreamFormat::JsonArray => take_json_array_element(buffer),
+        StreamFormat::Ndjson => take_ndjson_line(buffer),
+    }
+}
+
+/// Read a frame taken by `take_frame`; JSON framings carry the payload as `data`
+/// Returns None for frames without content, such as SSE comments and blank lines
+pub fn parse_frame(format: StreamFormat, raw: &str) -> Option<SseRecord> {
+    match format {
+        StreamFormat::Sse => SseRecord::parse(raw),
+        StreamFormat::JsonArray | StreamFormat::Ndjson => {
+            let data = raw.trim();
+            (!data.is_empty()).then(|| SseRecord {
+                event: None,
+                data: data.to_string(),
+            })
+        }
+    }
+}
+
+/// Tolerant providers may pack several JSON objects into one frame; give each its own event
+pub fn split_frame(frame: SseRecord, tolerant: bool) -> Vec<SseRecord> {
+    if !tolerant {
+        return vec![frame];
+    }
+    split_json_payloads(&frame.data)
+        .into_iter()
+        .map(|data| SseRecord {
+            event: frame.event.clone(),
+            data,
+        })
+        .collect()
+}
+
 /// Context for parsing a stream event
 #[derive(Debug, Clone)]
 pub struct StreamParseContext<'a> {
@@ -51,6 +333,112 @@ pub trait ProtocolStreamParser: Send + Sync {
 
     /// Check if this is a done/sentinel event
     fn is_done_event(&self, data: &str) -> bool {
-        data.trim() == "[DONE]"
+        super::sse::is_done(data)
+    }
+
+    /// Framing of the response body, SSE unless the protocol streams another format
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Sse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn find_sse_delimiter_picks_the_first_blank_line() {
+        assert_eq!(
+            find_sse_delimiter(b"data: a\n\ndata: b\r\n\r\n"),
+            Some((7, 2))
+        );
+        assert_eq!(
+            find_sse_delimiter(b"data: a\r\n\r\ndata: b\n\n"),
+            Some((7, 4))
+        );
+        assert_eq!(find_sse_delimiter(b"data: a\r\n\n"), Some((7, 3)));
+        assert_eq!(find_sse_delimiter(b"data: a\n\r\n"), Some((7, 3)));
+        assert_eq!(find_sse_delimiter(b"data: a\r\n"), None);
+    }
+
+    #[test]
+    fn take_sse_frame_leaves_partial_lines_buffered() {
+        let mut buf = b"data: {\"a\":1}\n\ndata: {\"b\"".to_vec();
+
+        assert_eq!(take_sse_frame(&mut buf), Some(b"data: {\"a\":1}".to_vec()));
+        assert_eq!(take_sse_frame(&mut buf), None);
+        assert_eq!(buf, b"data: {\"b\"".to_vec());
+    }
+
+    #[test]
+    fn parse_json_data_joins_payloads_split_across_frames() {
+        let mut state = StreamParseState::default();
+
+        assert_eq!(
+            parse_json_data(&mut state, "{\"choices\":[{\"delta\":"),
+            Ok(None)
+        );
+        assert_eq!(parse_json_data(&mut state, "  "), Ok(None));
+        let payload = parse_json_data(&mut state, "{\"content\":\"hi\"}}]}")
+            .expect("parse")
+            .expect("payload");
+
+        assert_eq!(
+            payload,
+            json!({ "choices": [{ "delta": { "content": "hi" } }] })
+        );
+        assert!(state.partial_data.is_empty());
+    }
+
+    #[test]
+    fn parse_json_data_drops_a_fragment_that_never_completes() {
+        let mut state = StreamParseState::default();
+
+        assert_eq!(parse_json_data(&mut state, "{\"a\":"), Ok(None));
+        let payload = parse_json_data(&mut state, "{\"b\":2}")
+            .expect("parse")
+            .expect("payload");
+
+        assert_eq!(payload, json!({ "b": 2 }));
+        assert!(state.partial_data.is_empty());
+        assert!(parse_json_data(&mut state, "not json").is_err());
+    }
+
+    #[test]
+    fn split_json_payloads_separates_concatenated_objects() {
+        assert_eq!(
+            split_json_payloads(r#"{"a":"}{"}{"b":2},"#),
+            vec![r#"{"a":"}{"}"#, r#"{"b":2}"#]
+        );
+        assert_eq!(
+            split_json_payloads("{\"a\":1}\ndata: {\"b\":2}\ndata: [DONE]"),
+            vec![r#"{"a":1}"#, r#"{"b":2}"#, "[DONE]"]
+        );
+        assert_eq!(
+            split_json_payloads(r#"{"a":1}{"b":"#),
+            vec![r#"{"a":1}"#, r#"{"b":"#]
+        );
+    }
+
+    #[test]
+    fn tolerant_mode_trims_trailing_commas_and_drops_garbage() {
+        let mut strict = StreamParseState::default();
+        let mut tolerant = StreamParseState {
+            tolerant_json: true,
+            ..StreamParseState::default()
+        };
+        let chunk = r#"{"choices":[{"delta":{"content":"a, b,"},},],}"#;
+
+        assert!(parse_json_data(&mut strict, chunk).is_err());
+        assert_eq!(
+            parse_json_data(&mut tolerant, chunk),
+            Ok(Some(
+                json!({ "choices": [{ "delta": { "content": "a, b," } }] })
+            ))
+        );
+        assert_eq!(parse_json_data(&mut tolerant, r#"{"a":1}}"#), Ok(None));
+        assert_eq!(parse_json_data(&mut tolerant, "not json"), Ok(None));
+        assert!(tolerant.partial_data.is_empty());
     }
 }
@@ -0,0 +1,310 @@
+// Azure OpenAI Provider Implementation
+// Requests go to /openai/deployments/{deployment}/chat/completions?api-version=... on the
+// resource endpoint, with the model name used as the deployment name
+// Authenticates with an `api-key` header instead of `Authorization: Bearer`
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    openai_protocol::OpenAiProtocol,
+    request_builder::ProtocolRequestBuilder,
+    stream_parser::ProtocolStreamParser,
+};
+use crate::llm::providers::provider::{
+    probe_completion, BaseProvider, HealthStatus, Provider, ProviderCapabilities, ProviderContext,
+    ProviderCredentials as Creds,
+};
+use crate::llm::types::{ModelInfo, ProtocolType, ProviderConfig};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Instant;
+use url::form_urlencoded;
+
+/// Latest GA data-plane API version, used when neither settings nor the URL name one
+pub const DEFAULT_AZURE_API_VERSION: &str = "2024-10-21";
+
+pub struct AzureOpenAiProvider {
+    base: BaseProvider,
+    protocol: OpenAiProtocol,
+}
+
+impl AzureOpenAiProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            base: BaseProvider::new(config),
+            protocol: OpenAiProtocol,
+        }
+    }
+
+    /// Reduce a pasted endpoint such as `https://res.openai.azure.com/openai/deployments/...`
+    /// to the resource URL
+    fn resource_url(base_url: &str) -> &str {
+        let without_query = base_url.split('?').next().unwrap_or_default().trim();
+        let trimmed = without_query.trim_end_matches('/');
+        // Only look for `/openai` in the path, resource names may start with "openai" too
+        let host_start = trimmed.find("://").map_or(0, |pos| pos + 3);
+        let path_start = trimmed[host_start..]
+            .find('/')
+            .map_or(trimmed.len(), |pos| host_start + pos);
+        match trimmed[path_start..].find("/openai") {
+            Some(pos) => &trimmed[..path_start + pos],
+            None => trimmed,
+        }
+    }
+
+    /// `api-version` query parameter carried by a pasted endpoint URL
+    fn api_version_from_url(base_url: &str) -> Option<String> {
+        let (_, query) = base_url.split_once('?')?;
+        form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == "api-version")
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    }
+
+    async fn resolve_api_version(&self, ctx: &ProviderContext<'_>) -> String {
+        let setting_key = format!("api_version_{}", self.base.config.id);
+        if let Ok(Some(version)) = ctx.api_key_manager.get_setting(&setting_key).await {
+            let version = version.trim();
+            if !version.is_empty() {
+                return version.to_string();
+            }
+        }
+
+        let base_url = self
+            .base
+            .resolve_base_url_with_fallback(ctx.api_key_manager)
+            .await
+            .unwrap_or_default();
+        Self::api_version_from_url(&base_url)
+            .unwrap_or_else(|| DEFAULT_AZURE_API_VERSION.to_string())
+    }
+
+    fn deployment_path(deployment: &str, api_version: &str) -> String {
+        let encode =
+            |value: &str| -> String { form_urlencoded::byte_serialize(value.as_bytes()).collect() };
+        format!(
+            "openai/deployments/{}/chat/completions?api-version={}",
+            encode(deployment.trim()),
+            encode(api_version)
+        )
+    }
+}
+
+#[async_trait]
+impl Provider for AzureOpenAiProvider {
+    fn id(&self) -> &str {
+        &self.base.config.id
+    }
+
+    fn name(&self) -> &str {
+        &self.base.config.name
+    }
+
+    fn protocol_type(&self) -> ProtocolType {
+        self.base.config.protocol
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.base.config
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_vision: true,
+            supports_structured_output: true,
+            supports_logprobs: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        let base_url = self
+            .base
+            .resolve_base_url_with_fallback(ctx.api_key_manager)
+            .await?;
+        let resource_url = Self::resource_url(&base_url);
+        if resource_url.is_empty() {
+            return Err(LlmError::Other(
+                "Azure OpenAI needs a resource endpoint such as https://my-resource.openai.azure.com / 请设置 Azure OpenAI 资源地址"
+                    .to_string(),
+            ));
+        }
+        Ok(resource_url.to_string())
+    }
+
+    async fn resolve_endpoint_path(&self, ctx: &ProviderContext<'_>) -> String {
+        let api_version = self.resolve_api_version(ctx).await;
+        Self::deployment_path(ctx.model, &api_version)
+    }
+
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        self.base.api_key_credentials(api_key_manager).await
+    }
+
+    async fn list_models(&self, _ctx: &ProviderContext<'_>) -> Result<Vec<ModelInfo>, LlmError> {
+        // Deployments are managed per resource in the Azure portal, not listed by the data plane
+        Err(LlmError::Other(
+            "Azure OpenAI does not support model discovery, enter the deployment name as the model"
+                .to_string(),
+        ))
+    }
+
+    async fn health_check(&self, ctx: &ProviderContext<'_>) -> Result<HealthStatus, String> {
+        // Nothing to list, so the check needs a deployment to send a completion to
+        if ctx.model.trim().is_empty() {
+            return Err(
+                "Choose a deployment to check Azure OpenAI / 请先选择 Azure OpenAI 部署名称"
+                    .to_string(),
+            );
+        }
+        let started = Instant::now();
+        HealthStatus::from_probe(probe_completion(self, ctx).await, started)
+    }
+
+    fn build_protocol_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        // Entra ID tokens still go out as Bearer; API keys use Azure's own header
+        let api_key = ctx.api_key.filter(|_| ctx.oauth_token.is_none());
+        let mut headers = self.protocol.build_base_headers(HeaderBuildContext {
+            api_key: None,
+            ..ctx
+        });
+        if let Some(key) = api_key {
+            headers.insert("api-key".to_string(), key.to_string());
+        }
+        headers
+    }
+
+    fn build_protocol_request(
+        &self,
+        ctx: crate::llm::protocols::request_builder::RequestBuildContext,
+    ) -> Result<Value, String> {
+        self.protocol.build_request(ctx)
+    }
+
+    fn parse_protocol_stream_event(
+        &self,
+        ctx: crate::llm::protocols::stream_parser::StreamParseContext,
+        state: &mut crate::llm::protocols::stream_parser::StreamParseState,
+    ) -> Result<Option<crate::llm::types::StreamEvent>, String> {
+        self.protocol.parse_stream_event(ctx, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::types::AuthType;
+
+    fn create_test_config(base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            api_key_name: "AZURE_OPENAI_API_KEY".to_string(),
+            auth_type: AuthType::ApiKey,
+            ..provider_config("azure", ProtocolType::OpenAiCompatible, base_url)
+        }
+    }
+
+    fn context<'a>(config: &'a ProviderConfig, api_keys: &'a ApiKeyManager) -> ProviderContext<'a> {
+        ProviderContext {
+            provider_config: config,
+            api_key_manager: api_keys,
+            model: "gpt-4o-prod",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn builds_deployment_url_with_default_api_version() {
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_azure", "azure-key")]).await;
+        let config = create_test_config("https://openai-prod.openai.azure.com/");
+        let provider = AzureOpenAiProvider::new(config.clone());
+        let ctx = context(&config, &api_keys);
+
+        let request = provider
+            .build_complete_request(&ctx)
+            .await
+            .expect("request");
+
+        assert_eq!(
+            request.url,
+            format!(
+                "https://openai-prod.openai.azure.com/openai/deployments/gpt-4o-prod/chat/completions?api-version={}",
+                DEFAULT_AZURE_API_VERSION
+            )
+        );
+        assert_eq!(
+            request.headers.get("api-key").map(String::as_str),
+            Some("azure-key")
+        );
+        assert!(!request.headers.contains_key("Authorization"));
+    }
+
+    #[tokio::test]
+    async fn api_version_comes_from_settings_then_pasted_url() {
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_azure", "azure-key")]).await;
+        let config = create_test_config(
+            "https://my-resource.openai.azure.com/openai/deployments/old/chat/completions?api-version=2024-06-01",
+        );
+        let provider = AzureOpenAiProvider::new(config.clone());
+        let ctx = context(&config, &api_keys);
+
+        let request = provider
+            .build_complete_request(&ctx)
+            .await
+            .expect("request");
+        assert_eq!(
+            request.url,
+            "https://my-resource.openai.azure.com/openai/deployments/gpt-4o-prod/chat/completions?api-version=2024-06-01"
+        );
+
+        api_keys
+            .set_setting("api_version_azure", "2025-01-01-preview")
+            .await
+            .expect("set api version");
+        let request = provider
+            .build_complete_request(&ctx)
+            .await
+            .expect("request");
+        assert!(request.url.ends_with("?api-version=2025-01-01-preview"));
+    }
+
+    #[tokio::test]
+    async fn missing_resource_endpoint_is_an_error() {
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_azure", "azure-key")]).await;
+        let config = create_test_config("");
+        let provider = AzureOpenAiProvider::new(config.clone());
+        let ctx = context(&config, &api_keys);
+
+        let err = provider
+            .resolve_base_url(&ctx)
+            .await
+            .expect_err("empty endpoint");
+
+        assert!(err.to_string().contains("resource endpoint"));
+    }
+}
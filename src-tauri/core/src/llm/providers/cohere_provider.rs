@@ -0,0 +1,168 @@
+// Cohere Provider Implementation
+// Native v2 /chat endpoint with Bearer auth, streamed as typed SSE events
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use crate::llm::protocols::{
+    cohere_protocol::CohereProtocol,
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    request_builder::{ProtocolRequestBuilder, RequestBuildContext},
+    stream_parser::{ProtocolStreamParser, StreamParseContext, StreamParseState},
+};
+use crate::llm::providers::provider::{
+    BaseProvider, Provider, ProviderCapabilities, ProviderContext, ProviderCredentials as Creds,
+};
+use crate::llm::types::{ProtocolType, ProviderConfig, StreamEvent};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub const DEFAULT_COHERE_BASE_URL: &str = "https://api.cohere.com/v2";
+
+pub struct CohereProvider {
+    base: BaseProvider,
+    protocol: CohereProtocol,
+}
+
+impl CohereProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            base: BaseProvider::new(config),
+            protocol: CohereProtocol,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for CohereProvider {
+    fn id(&self) -> &str {
+        &self.base.config.id
+    }
+
+    fn name(&self) -> &str {
+        &self.base.config.name
+    }
+
+    fn protocol_type(&self) -> ProtocolType {
+        self.base.config.protocol
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.base.config
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // Command A Vision takes images; `/v2/embed` serves the embed models
+        ProviderCapabilities {
+            supports_vision: true,
+            supports_embeddings: true,
+            supports_structured_output: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        let base_url = self
+            .base
+            .resolve_base_url_with_fallback(ctx.api_key_manager)
+            .await?;
+        let base_url = base_url.trim().trim_end_matches('/');
+        if base_url.is_empty() {
+            return Ok(DEFAULT_COHERE_BASE_URL.to_string());
+        }
+        Ok(base_url.to_string())
+    }
+
+    async fn resolve_endpoint_path(&self, _ctx: &ProviderContext<'_>) -> String {
+        "chat".to_string()
+    }
+
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        self.base.api_key_credentials(api_key_manager).await
+    }
+
+    fn build_protocol_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        self.protocol.build_base_headers(ctx)
+    }
+
+    fn build_protocol_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        self.protocol.build_request(ctx)
+    }
+
+    fn parse_protocol_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        self.protocol.parse_stream_event(ctx, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::types::{Message, MessageContent};
+
+    fn cohere_config() -> ProviderConfig {
+        provider_config("cohere", ProtocolType::Cohere, DEFAULT_COHERE_BASE_URL)
+    }
+
+    #[tokio::test]
+    async fn build_complete_request_targets_v2_chat_with_bearer_token() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("api_key_cohere", "co-test")
+            .await
+            .expect("set api key");
+        let config = cohere_config();
+        let provider = CohereProvider::new(config.clone());
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "command-a-03-2025",
+            messages: &messages,
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+
+        let request = provider
+            .build_complete_request(&ctx)
+            .await
+            .expect("build request");
+
+        assert_eq!(request.url, "https://api.cohere.com/v2/chat");
+        assert_eq!(
+            request.headers.get("Authorization").map(String::as_str),
+            Some("Bearer co-test")
+        );
+        assert_eq!(request.body["model"], "command-a-03-2025");
+        assert_eq!(request.body["messages"][0]["content"], "hi");
+    }
+}
@@ -0,0 +1,255 @@
+// DeepSeek Coding Plan Provider Implementation
+// Uses the coding plan endpoint when `use_coding_plan_deepseek_coding` is on, with a TalkCody
+// User-Agent header
+// Streamed `reasoning_content` deltas are parsed by the OpenAI protocol into reasoning events
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    openai_protocol::OpenAiProtocol,
+    request_builder::ProtocolRequestBuilder,
+    stream_parser::ProtocolStreamParser,
+};
+use crate::llm::providers::provider::{
+    BaseProvider, Provider, ProviderCapabilities, ProviderContext, ProviderCredentials as Creds,
+};
+use crate::llm::types::{ProtocolType, ProviderConfig};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+const DEEPSEEK_CODING_USER_AGENT: &str = concat!("TalkCody/", env!("CARGO_PKG_VERSION"));
+
+pub struct DeepSeekCodingProvider {
+    base: BaseProvider,
+    protocol: OpenAiProtocol,
+}
+
+impl DeepSeekCodingProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            base: BaseProvider::new(config),
+            protocol: OpenAiProtocol,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for DeepSeekCodingProvider {
+    fn id(&self) -> &str {
+        &self.base.config.id
+    }
+
+    fn name(&self) -> &str {
+        &self.base.config.name
+    }
+
+    fn protocol_type(&self) -> ProtocolType {
+        self.base.config.protocol
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.base.config
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // The coding plan endpoint only serves text chat
+        ProviderCapabilities::chat()
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        // Use standard endpoint resolution, so the coding plan URL follows its setting
+        self.base
+            .resolve_base_url_with_fallback(ctx.api_key_manager)
+            .await
+            .map_err(LlmError::from)
+    }
+
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        self.base.api_key_credentials(api_key_manager).await
+    }
+
+    async fn add_provider_headers(
+        &self,
+        _ctx: &ProviderContext<'_>,
+        headers: &mut HashMap<String, String>,
+    ) -> Result<(), LlmError> {
+        headers.insert(
+            "User-Agent".to_string(),
+            DEEPSEEK_CODING_USER_AGENT.to_string(),
+        );
+        Ok(())
+    }
+
+    fn build_protocol_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        self.protocol.build_base_headers(ctx)
+    }
+
+    fn build_protocol_request(
+        &self,
+        ctx: crate::llm::protocols::request_builder::RequestBuildContext,
+    ) -> Result<Value, String> {
+        self.protocol.build_request(ctx)
+    }
+
+    fn parse_protocol_stream_event(
+        &self,
+        ctx: crate::llm::protocols::stream_parser::StreamParseContext,
+        state: &mut crate::llm::protocols::stream_parser::StreamParseState,
+    ) -> Result<Option<crate::llm::types::StreamEvent>, String> {
+        self.protocol.parse_stream_event(ctx, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::protocols::stream_parser::{StreamParseContext, StreamParseState};
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::types::StreamEvent;
+    use tempfile::TempDir;
+
+    fn create_test_config() -> ProviderConfig {
+        ProviderConfig {
+            supports_coding_plan: true,
+            coding_plan_base_url: Some("https://api.deepseek.com/beta".to_string()),
+            ..provider_config(
+                "deepseek_coding",
+                ProtocolType::OpenAiCompatible,
+                "https://api.deepseek.com/v1",
+            )
+        }
+    }
+
+    async fn setup_test_context() -> (TempDir, ApiKeyManager, DeepSeekCodingProvider) {
+        let (dir, api_keys) = api_key_manager_with(&[]).await;
+        let provider = DeepSeekCodingProvider::new(create_test_config());
+
+        (dir, api_keys, provider)
+    }
+
+    fn test_context<'a>(
+        config: &'a ProviderConfig,
+        api_keys: &'a ApiKeyManager,
+    ) -> ProviderContext<'a> {
+        ProviderContext {
+            provider_config: config,
+            api_key_manager: api_keys,
+            model: "deepseek-chat",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn build_headers_include_coding_user_agent() {
+        let (_dir, api_keys, provider) = setup_test_context().await;
+        let config = create_test_config();
+        let ctx = test_context(&config, &api_keys);
+
+        let headers = provider
+            .build_headers(&ctx, &Creds::ApiKey("test-key".to_string()))
+            .await
+            .expect("headers");
+
+        assert_eq!(
+            headers.get("User-Agent").map(String::as_str),
+            Some(DEEPSEEK_CODING_USER_AGENT)
+        );
+        assert!(DEEPSEEK_CODING_USER_AGENT.starts_with("TalkCody/"));
+        assert_eq!(
+            headers.get("Authorization").map(String::as_str),
+            Some("Bearer test-key")
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_base_url_uses_standard_url_by_default() {
+        let (_dir, api_keys, provider) = setup_test_context().await;
+        let config = create_test_config();
+        let ctx = test_context(&config, &api_keys);
+
+        let base_url = provider.resolve_base_url(&ctx).await.expect("base url");
+
+        assert_eq!(base_url, "https://api.deepseek.com/v1");
+    }
+
+    #[tokio::test]
+    async fn resolve_base_url_uses_coding_plan_url_when_enabled() {
+        let (_dir, api_keys, provider) = setup_test_context().await;
+        api_keys
+            .set_setting("use_coding_plan_deepseek_coding", "true")
+            .await
+            .expect("enable coding plan");
+        let config = create_test_config();
+        let ctx = test_context(&config, &api_keys);
+
+        let base_url = provider.resolve_base_url(&ctx).await.expect("base url");
+
+        assert_eq!(base_url, "https://api.deepseek.com/beta");
+    }
+
+    #[tokio::test]
+    async fn resolve_base_url_prefers_custom_setting() {
+        let (_dir, api_keys, provider) = setup_test_context().await;
+        api_keys
+            .set_setting("base_url_deepseek_coding", "https://proxy.example/v1")
+            .await
+            .expect("set base url");
+        let config = create_test_config();
+        let ctx = test_context(&config, &api_keys);
+
+        let base_url = provider.resolve_base_url(&ctx).await.expect("base url");
+
+        assert_eq!(base_url, "https://proxy.example/v1");
+    }
+
+    #[test]
+    fn parse_stream_emits_reasoning_from_reasoning_content() {
+        let provider = DeepSeekCodingProvider::new(create_test_config());
+        let mut state = StreamParseState::default();
+        let data = r#"{"choices":[{"delta":{"reasoning_content":"Check the tests first"}}]}"#;
+
+        let event = provider
+            .parse_protocol_stream_event(
+                StreamParseContext {
+                    event_type: None,
+                    data,
+                },
+                &mut state,
+            )
+            .expect("parse")
+            .expect("event");
+
+        assert!(matches!(event, StreamEvent::ReasoningStart { .. }));
+        match state.pending_events.first() {
+            Some(StreamEvent::ReasoningDelta { text, .. }) => {
+                assert_eq!(text, "Check the tests first")
+            }
+            other => panic!("Expected ReasoningDelta, got {:?}", other),
+        }
+    }
+}
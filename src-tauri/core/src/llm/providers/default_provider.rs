@@ -3,12 +3,15 @@
 // Uses standard protocol implementations without overrides
 
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
 use crate::llm::protocols::{
-    claude_protocol::ClaudeProtocol, header_builder::HeaderBuildContext,
-    openai_protocol::OpenAiProtocol,
+    anthropic_protocol::AnthropicProtocol, claude_protocol::ClaudeProtocol,
+    cohere_protocol::CohereProtocol, gemini_protocol::GeminiProtocol,
+    header_builder::HeaderBuildContext, openai_protocol::OpenAiProtocol,
+    stream_parser::StreamFormat,
 };
 use crate::llm::providers::provider::{
-    BaseProvider, Provider, ProviderContext, ProviderCredentials as Creds,
+    BaseProvider, Provider, ProviderCapabilities, ProviderContext, ProviderCredentials as Creds,
 };
 use crate::llm::types::ProtocolType;
 use crate::llm::types::ProviderConfig;
@@ -34,6 +37,9 @@ trait ProtocolImpl: Send + Sync {
         ctx: crate::llm::protocols::stream_parser::StreamParseContext,
         state: &mut crate::llm::protocols::stream_parser::StreamParseState,
     ) -> Result<Option<crate::llm::types::StreamEvent>, String>;
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Sse
+    }
 }
 
 struct OpenAiProtocolWrapper(OpenAiProtocol);
@@ -59,6 +65,79 @@ impl ProtocolImpl for OpenAiProtocolWrapper {
     }
 }
 
+struct AnthropicProtocolWrapper(AnthropicProtocol);
+impl ProtocolImpl for AnthropicProtocolWrapper {
+    fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        use crate::llm::protocols::ProtocolHeaderBuilder;
+        ProtocolHeaderBuilder::build_base_headers(&self.0, ctx)
+    }
+    fn build_request(
+        &self,
+        ctx: crate::llm::protocols::request_builder::RequestBuildContext,
+    ) -> Result<Value, String> {
+        use crate::llm::protocols::ProtocolRequestBuilder;
+        ProtocolRequestBuilder::build_request(&self.0, ctx)
+    }
+    fn parse_stream_event(
+        &self,
+        ctx: crate::llm::protocols::stream_parser::StreamParseContext,
+        state: &mut crate::llm::protocols::stream_parser::StreamParseState,
+    ) -> Result<Option<crate::llm::types::StreamEvent>, String> {
+        use crate::llm::protocols::ProtocolStreamParser;
+        ProtocolStreamParser::parse_stream_event(&self.0, ctx, state)
+    }
+}
+
+struct GeminiProtocolWrapper(GeminiProtocol);
+impl ProtocolImpl for GeminiProtocolWrapper {
+    fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        use crate::llm::protocols::ProtocolHeaderBuilder;
+        ProtocolHeaderBuilder::build_base_headers(&self.0, ctx)
+    }
+    fn build_request(
+        &self,
+        ctx: crate::llm::protocols::request_builder::RequestBuildContext,
+    ) -> Result<Value, String> {
+        use crate::llm::protocols::ProtocolRequestBuilder;
+        ProtocolRequestBuilder::build_request(&self.0, ctx)
+    }
+    fn parse_stream_event(
+        &self,
+        ctx: crate::llm::protocols::stream_parser::StreamParseContext,
+        state: &mut crate::llm::protocols::stream_parser::StreamParseState,
+    ) -> Result<Option<crate::llm::types::StreamEvent>, String> {
+        use crate::llm::protocols::ProtocolStreamParser;
+        ProtocolStreamParser::parse_stream_event(&self.0, ctx, state)
+    }
+    fn stream_format(&self) -> StreamFormat {
+        use crate::llm::protocols::ProtocolStreamParser;
+        ProtocolStreamParser::stream_format(&self.0)
+    }
+}
+
+struct CohereProtocolWrapper(CohereProtocol);
+impl ProtocolImpl for CohereProtocolWrapper {
+    fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        use crate::llm::protocols::ProtocolHeaderBuilder;
+        ProtocolHeaderBuilder::build_base_headers(&self.0, ctx)
+    }
+    fn build_request(
+        &self,
+        ctx: crate::llm::protocols::request_builder::RequestBuildContext,
+    ) -> Result<Value, String> {
+        use crate::llm::protocols::ProtocolRequestBuilder;
+        ProtocolRequestBuilder::build_request(&self.0, ctx)
+    }
+    fn parse_stream_event(
+        &self,
+        ctx: crate::llm::protocols::stream_parser::StreamParseContext,
+        state: &mut crate::llm::protocols::stream_parser::StreamParseState,
+    ) -> Result<Option<crate::llm::types::StreamEvent>, String> {
+        use crate::llm::protocols::ProtocolStreamParser;
+        ProtocolStreamParser::parse_stream_event(&self.0, ctx, state)
+    }
+}
+
 struct ClaudeProtocolWrapper(ClaudeProtocol);
 impl ProtocolImpl for ClaudeProtocolWrapper {
     fn build_base_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
@@ -84,7 +163,7 @@ impl ProtocolImpl for ClaudeProtocolWrapper {
     ) -> Result<Value, String> {
         use crate::llm::protocols::LlmProtocol;
 
-        self.0.build_request(
+        let mut body = self.0.build_request(
             ctx.model,
             ctx.messages,
             ctx.tools,
@@ -94,7 +173,10 @@ impl ProtocolImpl for ClaudeProtocolWrapper {
             ctx.top_k,
             ctx.provider_options,
             ctx.extra_body,
-        )
+        )?;
+        AnthropicProtocol::append_builtin_tools(&mut body, ctx.builtin_tools)?;
+        AnthropicProtocol::apply_thinking(&mut body, ctx.model, ctx.reasoning_effort);
+        Ok(body)
     }
     fn parse_stream_event(
         &self,
@@ -112,6 +194,7 @@ impl ProtocolImpl for ClaudeProtocolWrapper {
             current_thinking_id: state.current_thinking_id.clone(),
             pending_events: std::mem::take(&mut state.pending_events),
             text_started: state.text_started,
+            stream_started: state.stream_started,
             content_block_types: std::mem::take(&mut state.content_block_types),
             content_block_ids: std::mem::take(&mut state.content_block_ids),
             reasoning_started: state.reasoning_started,
@@ -132,6 +215,7 @@ impl ProtocolImpl for ClaudeProtocolWrapper {
         state.current_thinking_id = legacy.current_thinking_id;
         state.pending_events = legacy.pending_events;
         state.text_started = legacy.text_started;
+        state.stream_started = legacy.stream_started;
         state.content_block_types = legacy.content_block_types;
         state.content_block_ids = legacy.content_block_ids;
         state.reasoning_started = legacy.reasoning_started;
@@ -148,6 +232,9 @@ impl DefaultProvider {
         let protocol: Box<dyn ProtocolImpl> = match config.protocol {
             ProtocolType::OpenAiCompatible => Box::new(OpenAiProtocolWrapper(OpenAiProtocol)),
             ProtocolType::Claude => Box::new(ClaudeProtocolWrapper(ClaudeProtocol)),
+            ProtocolType::Anthropic => Box::new(AnthropicProtocolWrapper(AnthropicProtocol)),
+            ProtocolType::Gemini => Box::new(GeminiProtocolWrapper(GeminiProtocol)),
+            ProtocolType::Cohere => Box::new(CohereProtocolWrapper(CohereProtocol)),
         };
 
         Self {
@@ -175,16 +262,56 @@ impl Provider for DefaultProvider {
         &self.base.config
     }
 
-    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, String> {
+    fn capabilities(&self) -> ProviderCapabilities {
+        let id = self.base.config.id.as_str();
+        let native_gemini = self.base.config.protocol == ProtocolType::Gemini;
+        match id {
+            // Search, speech and image-only services have no chat endpoint
+            "tavily" | "serper" | "elevenlabs" => ProviderCapabilities::default(),
+            "stability" | "replicate" => ProviderCapabilities {
+                supports_images: true,
+                ..ProviderCapabilities::default()
+            },
+            _ => ProviderCapabilities {
+                supports_vision: matches!(
+                    id,
+                    "anthropic"
+                        | "google"
+                        | "openRouter"
+                        | "aiGateway"
+                        | "zhipu"
+                        | "alibaba"
+                        | "volcengine"
+                ),
+                supports_images: matches!(
+                    id,
+                    "google" | "aiGateway" | "zhipu" | "alibaba" | "volcengine"
+                ),
+                supports_embeddings: matches!(id, "google" | "zhipu" | "alibaba" | "volcengine"),
+                supports_structured_output: id == "openRouter",
+                supports_logprobs: matches!(id, "openRouter" | "deepseek"),
+                // Messages API server tools, Gemini grounding and code execution
+                supports_web_search: id == "anthropic" || native_gemini,
+                supports_code_interpreter: native_gemini,
+                supports_multipart_images: self.base.config.multipart_images,
+                ..ProviderCapabilities::chat()
+            },
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
         self.base
             .resolve_base_url_with_fallback(ctx.api_key_manager)
             .await
+            .map_err(LlmError::from)
     }
 
-    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, String> {
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
         use crate::llm::auth::api_key_manager::ProviderCredentials as AkmCreds;
 
-        let creds = api_key_manager.get_credentials(&self.base.config).await?;
+        let creds = api_key_manager
+            .get_credentials_rotating(&self.base.config)
+            .await?;
         match creds {
             AkmCreds::None => Ok(Creds::None),
             AkmCreds::Token(token) => match self.base.config.auth_type {
@@ -215,6 +342,10 @@ impl Provider for DefaultProvider {
     ) -> Result<Option<crate::llm::types::StreamEvent>, String> {
         ProtocolImpl::parse_stream_event(&*self.protocol, ctx, state)
     }
+
+    fn stream_format(&self) -> StreamFormat {
+        self.protocol.stream_format()
+    }
 }
 
 #[cfg(test)]
@@ -222,25 +353,16 @@ mod tests {
     use super::*;
     use crate::database::Database;
     use crate::llm::auth::api_key_manager::ApiKeyManager;
+    use crate::llm::testing::fixtures::provider_config;
     use crate::llm::types::{AuthType, ProtocolType, ProviderConfig};
     use std::sync::Arc;
     use tempfile::TempDir;
 
     fn create_test_config(auth_type: AuthType) -> ProviderConfig {
         ProviderConfig {
-            id: "talkcody".to_string(),
-            name: "TalkCody".to_string(),
-            protocol: ProtocolType::Claude,
-            base_url: "https://api.talkcody.com".to_string(),
             api_key_name: "TALKCODY_ENABLED".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
             auth_type,
+            ..provider_config("talkcody", ProtocolType::Claude, "https://api.talkcody.com")
         }
     }
 
@@ -320,6 +442,6 @@ mod tests {
         // Verify that we get an error about authentication being required
         assert!(result.is_err());
         let error_msg = result.unwrap_err();
-        assert!(error_msg.contains("Authentication required"));
+        assert!(error_msg.to_string().contains("Authentication required"));
     }
 }
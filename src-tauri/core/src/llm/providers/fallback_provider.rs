@@ -0,0 +1,339 @@
+// Fallback provider chain
+// Sends one request to an ordered list of providers, moving on to the next only when the
+// current one fails in a way another provider could recover from (network, 429, 5xx) and the
+// request's retry budget, if any, has attempts left
+
+use crate::llm::error::LlmError;
+use crate::llm::providers::provider::{Provider, ProviderContext, DRY_RUN_UNSUPPORTED};
+use crate::llm::request_id::RequestIds;
+use crate::llm::retry::{RetryBudget, RetryPolicy};
+
+/// Successful response together with the provider that produced it
+/// Callers parse the stream with `provider`, since the chain may mix protocols
+pub struct FallbackResponse<'a> {
+    pub provider: &'a dyn Provider,
+    pub response: reqwest::Response,
+    /// Client and provider IDs of the call that succeeded
+    pub request_ids: RequestIds,
+    /// Providers tried before `provider`, with the error each one failed with
+    pub failures: Vec<(String, LlmError)>,
+}
+
+impl FallbackResponse<'_> {
+    pub fn provider_id(&self) -> &str {
+        self.provider.id()
+    }
+}
+
+pub struct FallbackProvider {
+    providers: Vec<Box<dyn Provider>>,
+}
+
+impl FallbackProvider {
+    /// Providers are tried in the order given
+    pub fn new(providers: Vec<Box<dyn Provider>>) -> Self {
+        Self { providers }
+    }
+
+    pub fn providers(&self) -> &[Box<dyn Provider>] {
+        &self.providers
+    }
+
+    /// Send the request described by `ctx` to each provider in turn until one succeeds
+    /// Every provider gets the same messages and parameters with its own config; each one
+    /// retries under its own policy before the chain moves on. Non-retriable errors such as
+    /// auth failures are returned right away, as are the last provider's errors and the error
+    /// that used up `ctx.retry_budget`
+    pub async fn send(
+        &self,
+        ctx: &ProviderContext<'_>,
+        client: &reqwest::Client,
+    ) -> Result<FallbackResponse<'_>, LlmError> {
+        if ctx.dry_run {
+            return Err(LlmError::Other(DRY_RUN_UNSUPPORTED.to_string()));
+        }
+        let mut failures = Vec::new();
+        for (index, provider) in self.providers.iter().enumerate() {
+            if ctx.cancel_token.is_some_and(|token| token.is_cancelled()) {
+                return Err(LlmError::Cancelled);
+            }
+            let provider_ctx = ProviderContext {
+                provider_config: provider.config(),
+                ..ctx.clone()
+            };
+            let err = match Self::send_one(provider.as_ref(), &provider_ctx, client).await {
+                Ok((response, request_ids)) => {
+                    return Ok(FallbackResponse {
+                        provider: provider.as_ref(),
+                        response,
+                        request_ids,
+                        failures,
+                    })
+                }
+                Err(err) => err,
+            };
+
+            let is_last = index + 1 == self.providers.len();
+            let budget_spent = ctx.retry_budget.is_some_and(RetryBudget::is_exhausted);
+            if is_last || !err.is_retriable() || budget_spent {
+                return Err(err);
+            }
+            log::warn!(
+                "[Fallback] Provider {} failed: {}, trying {}",
+                provider.id(),
+                err,
+                self.providers[index + 1].id()
+            );
+            failures.push((provider.id().to_string(), err));
+        }
+        Err(LlmError::Other(
+            "No providers configured for fallback / 未配置备用服务商".to_string(),
+        ))
+    }
+
+    async fn send_one(
+        provider: &dyn Provider,
+        ctx: &ProviderContext<'_>,
+        client: &reqwest::Client,
+    ) -> Result<(reqwest::Response, RequestIds), LlmError> {
+        let built_request = provider.build_complete_request(ctx).await?;
+        let mut req_builder = client.post(&built_request.url);
+        for (key, value) in &built_request.headers {
+            req_builder = req_builder.header(key, value);
+        }
+        req_builder = built_request.with_body(req_builder);
+
+        let response = RetryPolicy::for_provider(provider.config())
+            .for_idempotency(built_request.idempotent)
+            .send_within(req_builder, ctx.retry_budget)
+            .await?;
+        let mut request_ids = built_request.request_ids;
+        request_ids.record(response.headers());
+        let status = response.status().as_u16();
+        if status < 400 {
+            return Ok((response, request_ids));
+        }
+        if status == 429 {
+            if let Some(api_key) = &built_request.api_key {
+                ctx.api_key_manager.mark_key_rate_limited(api_key);
+            }
+        }
+        log::warn!(
+            "[Fallback] {} answered {} for {:?}",
+            provider.id(),
+            status,
+            request_ids
+        );
+        let headers = response.headers().clone();
+        let body = response.text().await.unwrap_or_default();
+        Err(LlmError::from_response_parts(status, &headers, body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::auth::api_key_manager::ApiKeyManager;
+    use crate::llm::providers::groq_provider::{groq_preset, GroqProvider};
+    use crate::llm::testing::fixtures::api_key_manager_with;
+    use crate::llm::testing::mock_server::start_sequence_server;
+    use crate::llm::types::ProviderConfig;
+    use std::sync::atomic::Ordering;
+
+    const CHAIN_KEYS: &[(&str, &str)] = &[
+        ("api_key_primary", "test-key"),
+        ("api_key_backup", "test-key"),
+    ];
+
+    /// OpenAI-compatible provider pointed at a mock server, without retries of its own
+    fn provider(id: &str, base_url: &str) -> Box<dyn Provider> {
+        retrying_provider(id, base_url, 0)
+    }
+
+    fn retrying_provider(id: &str, base_url: &str, max_retries: u32) -> Box<dyn Provider> {
+        Box::new(GroqProvider::new(ProviderConfig {
+            id: id.to_string(),
+            base_url: base_url.to_string(),
+            retry_policy: Some(RetryPolicy {
+                max_retries,
+                base_delay_ms: 1,
+                max_delay_ms: 1,
+            }),
+            ..groq_preset()
+        }))
+    }
+
+    fn context<'a>(config: &'a ProviderConfig, api_keys: &'a ApiKeyManager) -> ProviderContext<'a> {
+        ProviderContext {
+            provider_config: config,
+            api_key_manager: api_keys,
+            model: "llama-3.3-70b-versatile",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_next_provider_on_503() {
+        let (_dir, api_keys) = api_key_manager_with(CHAIN_KEYS).await;
+        let (primary_url, primary_hits) =
+            start_sequence_server(vec![(503, "overloaded".to_string())])
+                .expect("start mock server");
+        let (backup_url, backup_hits) =
+            start_sequence_server(vec![(200, "data: [DONE]\n\n".to_string())])
+                .expect("start mock server");
+        let chain = FallbackProvider::new(vec![
+            provider("primary", &primary_url),
+            provider("backup", &backup_url),
+        ]);
+        let config = groq_preset();
+
+        let result = chain
+            .send(&context(&config, &api_keys), &reqwest::Client::new())
+            .await
+            .expect("fallback response");
+
+        assert_eq!(result.provider_id(), "backup");
+        assert_eq!(result.response.status().as_u16(), 200);
+        assert_eq!(result.failures.len(), 1);
+        assert_eq!(result.failures[0].0, "primary");
+        assert_eq!(result.failures[0].1.status(), Some(503));
+        assert_eq!(primary_hits.load(Ordering::SeqCst), 1);
+        assert_eq!(backup_hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_and_fallbacks_share_one_budget() {
+        let (_dir, api_keys) = api_key_manager_with(CHAIN_KEYS).await;
+        let (primary_url, primary_hits) =
+            start_sequence_server(vec![(503, "overloaded".to_string()); 3])
+                .expect("start mock server");
+        let (backup_url, backup_hits) =
+            start_sequence_server(vec![(200, "data: [DONE]\n\n".to_string())])
+                .expect("start mock server");
+        let chain = FallbackProvider::new(vec![
+            retrying_provider("primary", &primary_url, 2),
+            provider("backup", &backup_url),
+        ]);
+        let config = groq_preset();
+        let budget = RetryBudget::new(2);
+        let ctx = ProviderContext {
+            retry_budget: Some(&budget),
+            ..context(&config, &api_keys)
+        };
+
+        let err = chain
+            .send(&ctx, &reqwest::Client::new())
+            .await
+            .err()
+            .expect("budget exhausted");
+
+        // Two attempts on the primary use up the budget before its last retry and the backup
+        assert_eq!(err.status(), Some(503));
+        assert_eq!(primary_hits.load(Ordering::SeqCst), 2);
+        assert_eq!(backup_hits.load(Ordering::SeqCst), 0);
+        assert!(budget.is_exhausted());
+    }
+
+    #[tokio::test]
+    async fn budget_with_room_left_still_fails_over() {
+        let (_dir, api_keys) = api_key_manager_with(CHAIN_KEYS).await;
+        let (primary_url, primary_hits) =
+            start_sequence_server(vec![(503, "overloaded".to_string()); 3])
+                .expect("start mock server");
+        let (backup_url, backup_hits) =
+            start_sequence_server(vec![(200, "data: [DONE]\n\n".to_string())])
+                .expect("start mock server");
+        let chain = FallbackProvider::new(vec![
+            retrying_provider("primary", &primary_url, 2),
+            provider("backup", &backup_url),
+        ]);
+        let config = groq_preset();
+        let budget = RetryBudget::new(5);
+        let ctx = ProviderContext {
+            retry_budget: Some(&budget),
+            ..context(&config, &api_keys)
+        };
+
+        let result = chain
+            .send(&ctx, &reqwest::Client::new())
+            .await
+            .expect("fallback response");
+
+        assert_eq!(result.provider_id(), "backup");
+        assert_eq!(primary_hits.load(Ordering::SeqCst), 3);
+        assert_eq!(backup_hits.load(Ordering::SeqCst), 1);
+        assert_eq!(budget.remaining(), 1);
+    }
+
+    #[tokio::test]
+    async fn auth_errors_do_not_fail_over() {
+        let (_dir, api_keys) = api_key_manager_with(CHAIN_KEYS).await;
+        let (primary_url, _) = start_sequence_server(vec![(
+            401,
+            r#"{"error":{"message":"bad key"}}"#.to_string(),
+        )])
+        .expect("start mock server");
+        let (backup_url, backup_hits) =
+            start_sequence_server(vec![(200, "data: [DONE]\n\n".to_string())])
+                .expect("start mock server");
+        let chain = FallbackProvider::new(vec![
+            provider("primary", &primary_url),
+            provider("backup", &backup_url),
+        ]);
+        let config = groq_preset();
+
+        let err = chain
+            .send(&context(&config, &api_keys), &reqwest::Client::new())
+            .await
+            .err()
+            .expect("auth error");
+
+        assert!(matches!(err, LlmError::Auth(_)));
+        assert_eq!(backup_hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn returns_last_error_when_every_provider_fails() {
+        let (_dir, api_keys) = api_key_manager_with(CHAIN_KEYS).await;
+        let (primary_url, _) = start_sequence_server(vec![(502, "bad gateway".to_string())])
+            .expect("start mock server");
+        let (backup_url, _) =
+            start_sequence_server(vec![(429, "slow down".to_string())]).expect("start mock server");
+        let chain = FallbackProvider::new(vec![
+            provider("primary", &primary_url),
+            provider("backup", &backup_url),
+        ]);
+        let config = groq_preset();
+
+        let err = chain
+            .send(&context(&config, &api_keys), &reqwest::Client::new())
+            .await
+            .err()
+            .expect("rate limited");
+
+        assert!(matches!(err, LlmError::RateLimited { .. }));
+    }
+}
@@ -2,6 +2,7 @@
 // Handles special headers required by GitHub Copilot API
 
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
 use crate::llm::protocols::{
     header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
     openai_protocol::OpenAiProtocol,
@@ -9,7 +10,7 @@ use crate::llm::protocols::{
     stream_parser::ProtocolStreamParser,
 };
 use crate::llm::providers::provider::{
-    BaseProvider, Provider, ProviderContext, ProviderCredentials as Creds,
+    BaseProvider, Provider, ProviderCapabilities, ProviderContext, ProviderCredentials as Creds,
 };
 use crate::llm::types::ProtocolType;
 use crate::llm::types::ProviderConfig;
@@ -49,7 +50,14 @@ impl Provider for GithubCopilotProvider {
         &self.base.config
     }
 
-    async fn resolve_base_url(&self, _ctx: &ProviderContext<'_>) -> Result<String, String> {
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_vision: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, _ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
         // GitHub Copilot uses a fixed base URL
         Ok("https://api.githubcopilot.com".to_string())
     }
@@ -59,7 +67,7 @@ impl Provider for GithubCopilotProvider {
         "chat/completions".to_string()
     }
 
-    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, String> {
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
         let creds = api_key_manager.get_credentials(&self.base.config).await?;
         match creds {
             crate::llm::auth::api_key_manager::ProviderCredentials::Token(token) => {
@@ -73,7 +81,7 @@ impl Provider for GithubCopilotProvider {
         &self,
         _ctx: &ProviderContext<'_>,
         headers: &mut HashMap<String, String>,
-    ) -> Result<(), String> {
+    ) -> Result<(), LlmError> {
         // GitHub Copilot requires special headers
         headers.insert(
             "User-Agent".to_string(),
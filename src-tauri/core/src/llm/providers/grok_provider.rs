@@ -0,0 +1,307 @@
+// xAI Grok Provider Implementation
+// OpenAI-compatible chat with optional live search via `search_parameters`
+// Search sources come back as `citations` on the stream and surface as Citation events
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    openai_protocol::OpenAiProtocol,
+    request_builder::{ProtocolRequestBuilder, RequestBuildContext},
+    sse,
+    stream_parser::{self, ProtocolStreamParser, StreamParseContext, StreamParseState},
+};
+use crate::llm::providers::provider::{
+    BaseProvider, Provider, ProviderCapabilities, ProviderContext, ProviderCredentials as Creds,
+};
+use crate::llm::types::{BuiltinTool, ProtocolType, ProviderConfig, StreamEvent};
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+pub const DEFAULT_XAI_BASE_URL: &str = "https://api.x.ai/v1";
+
+pub struct GrokProvider {
+    base: BaseProvider,
+    protocol: OpenAiProtocol,
+}
+
+impl GrokProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            base: BaseProvider::new(config),
+            protocol: OpenAiProtocol,
+        }
+    }
+
+    /// `providerOptions.xai.searchParameters`, set when the user turns on live search
+    fn search_parameters(provider_options: Option<&Value>) -> Option<&Value> {
+        provider_options?
+            .get("xai")?
+            .get("searchParameters")
+            .filter(|params| !params.is_null())
+    }
+
+    /// Citations carried by a chunk, either as plain URLs or `{url, title}` objects
+    fn citations(payload: &Value) -> Vec<StreamEvent> {
+        let Some(citations) = payload.get("citations").and_then(|v| v.as_array()) else {
+            return Vec::new();
+        };
+        citations
+            .iter()
+            .filter_map(|citation| {
+                let (url, title) = match citation {
+                    Value::String(url) => (url.as_str(), None),
+                    _ => (
+                        citation.get("url")?.as_str()?,
+                        citation.get("title").and_then(|v| v.as_str()),
+                    ),
+                };
+                Some(StreamEvent::Citation {
+                    url: url.to_string(),
+                    title: title.map(str::to_string),
+                })
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Provider for GrokProvider {
+    fn id(&self) -> &str {
+        &self.base.config.id
+    }
+
+    fn name(&self) -> &str {
+        &self.base.config.name
+    }
+
+    fn protocol_type(&self) -> ProtocolType {
+        self.base.config.protocol
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.base.config
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_vision: true,
+            supports_structured_output: true,
+            // Live search, sent as `search_parameters`
+            supports_web_search: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        let base_url = self
+            .base
+            .resolve_base_url_with_fallback(ctx.api_key_manager)
+            .await?;
+        let base_url = base_url.trim().trim_end_matches('/');
+        if base_url.is_empty() {
+            return Ok(DEFAULT_XAI_BASE_URL.to_string());
+        }
+        Ok(base_url.to_string())
+    }
+
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        self.base.api_key_credentials(api_key_manager).await
+    }
+
+    fn build_protocol_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        self.protocol.build_base_headers(ctx)
+    }
+
+    fn build_protocol_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        // Explicit search parameters win over the generic web search flag
+        let search_parameters = Self::search_parameters(ctx.provider_options)
+            .cloned()
+            .or_else(|| {
+                ctx.builtin_tools
+                    .contains(&BuiltinTool::WebSearch)
+                    .then(|| json!({ "mode": "auto" }))
+            });
+        let mut body = self.protocol.build_request(RequestBuildContext {
+            builtin_tools: &[],
+            ..ctx
+        })?;
+        if let Some(search_parameters) = search_parameters {
+            body["search_parameters"] = search_parameters;
+        }
+        Ok(body)
+    }
+
+    fn parse_protocol_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        if sse::is_done(ctx.data) {
+            return self.protocol.parse_stream_event(ctx, state);
+        }
+
+        let Some(payload) = stream_parser::parse_json_data(state, ctx.data)? else {
+            return Ok(None);
+        };
+        let data = payload.to_string();
+        let event = self.protocol.parse_stream_event(
+            StreamParseContext {
+                event_type: ctx.event_type,
+                data: &data,
+            },
+            state,
+        )?;
+        // Queued after the chunk's own events, so sources follow the text they back
+        state.pending_events.extend(Self::citations(&payload));
+        match event {
+            Some(event) => Ok(Some(event)),
+            None if !state.pending_events.is_empty() => Ok(Some(state.pending_events.remove(0))),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::fixtures::provider_config;
+    use serde_json::json;
+
+    fn grok_config() -> ProviderConfig {
+        provider_config("xai", ProtocolType::OpenAiCompatible, DEFAULT_XAI_BASE_URL)
+    }
+
+    fn request_context(provider_options: Option<&Value>) -> RequestBuildContext<'_> {
+        RequestBuildContext {
+            model: "grok-4",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            provider_options,
+            extra_body: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            builtin_tools: &[],
+            reasoning_effort: None,
+        }
+    }
+
+    fn parse_all(provider: &GrokProvider, frames: &[&str]) -> Vec<StreamEvent> {
+        let mut state = StreamParseState::default();
+        let mut events = Vec::new();
+        for data in frames {
+            let ctx = StreamParseContext {
+                event_type: None,
+                data,
+            };
+            let event = provider
+                .parse_protocol_stream_event(ctx, &mut state)
+                .expect("parse");
+            events.extend(event);
+            events.append(&mut state.pending_events);
+        }
+        events
+    }
+
+    #[test]
+    fn build_request_passes_search_parameters_only_when_enabled() {
+        let provider = GrokProvider::new(grok_config());
+        let options = json!({
+            "xai": { "searchParameters": { "mode": "auto", "return_citations": true } }
+        });
+
+        let body = provider
+            .build_protocol_request(request_context(Some(&options)))
+            .expect("request");
+        assert_eq!(
+            body["search_parameters"],
+            json!({ "mode": "auto", "return_citations": true })
+        );
+
+        let body = provider
+            .build_protocol_request(request_context(None))
+            .expect("request");
+        assert!(body.get("search_parameters").is_none());
+    }
+
+    #[test]
+    fn builtin_web_search_turns_on_live_search() {
+        let provider = GrokProvider::new(grok_config());
+
+        let body = provider
+            .build_protocol_request(RequestBuildContext {
+                builtin_tools: &[BuiltinTool::WebSearch],
+                ..request_context(None)
+            })
+            .expect("request");
+
+        assert_eq!(body["search_parameters"], json!({ "mode": "auto" }));
+        assert!(body.get("web_search_options").is_none());
+    }
+
+    #[test]
+    fn parse_stream_surfaces_citations_after_text() {
+        let provider = GrokProvider::new(grok_config());
+
+        let events = parse_all(
+            &provider,
+            &[
+                r#"{"choices":[{"index":0,"delta":{"content":"Rust 1.90 is out."}}]}"#,
+                r#"{"choices":[{"index":0,"delta":{},"finish_reason":"stop"}],"citations":["https://blog.rust-lang.org/",{"url":"https://x.com/rustlang/status/1","title":"Rust on X"}]}"#,
+                "[DONE]",
+            ],
+        );
+
+        let citations: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::Citation { url, title } => Some((url.as_str(), title.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            citations,
+            vec![
+                ("https://blog.rust-lang.org/", None),
+                ("https://x.com/rustlang/status/1", Some("Rust on X")),
+            ]
+        );
+        let first_citation = events
+            .iter()
+            .position(|event| matches!(event, StreamEvent::Citation { .. }));
+        let text = events
+            .iter()
+            .position(|event| matches!(event, StreamEvent::TextDelta { .. }));
+        assert!(text.is_some() && text < first_citation);
+        assert!(matches!(events.last(), Some(StreamEvent::Done { .. })));
+    }
+
+    #[test]
+    fn parse_stream_reads_citations_from_fragmented_chunk() {
+        let provider = GrokProvider::new(grok_config());
+
+        let events = parse_all(
+            &provider,
+            &[
+                r#"{"choices":[{"index":0,"delta":{}}],"citations":["#,
+                r#""https://docs.x.ai/"]}"#,
+            ],
+        );
+
+        assert!(matches!(
+            events.as_slice(),
+            [StreamEvent::Citation { url, title: None }] if url == "https://docs.x.ai/"
+        ));
+    }
+}
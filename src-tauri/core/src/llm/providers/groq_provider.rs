@@ -0,0 +1,335 @@
+// Groq Provider Implementation
+// OpenAI-compatible chat on Groq's LPU inference; usage arrives under `x_groq` on the final chunk
+// together with server timings, and quotas come back in the standard `x-ratelimit-*` headers
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    openai_protocol::OpenAiProtocol,
+    request_builder::{ProtocolRequestBuilder, RequestBuildContext},
+    sse,
+    stream_parser::{self, ProtocolStreamParser, StreamParseContext, StreamParseState},
+};
+use crate::llm::providers::provider::{
+    BaseProvider, Provider, ProviderCapabilities, ProviderContext, ProviderCredentials as Creds,
+};
+use crate::llm::retry::RetryPolicy;
+use crate::llm::types::{AuthType, ProtocolType, ProviderConfig, StreamEvent};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub const DEFAULT_GROQ_BASE_URL: &str = "https://api.groq.com/openai/v1";
+
+/// Fields the OpenAI protocol may send that Groq rejects with a 400
+const UNSUPPORTED_FIELDS: &[&str] = &["top_k"];
+
+/// Groq answers in well under a second and its per-minute quotas refill quickly, so throttled
+/// requests are retried sooner and more often than the default policy would
+const GROQ_RETRY_POLICY: RetryPolicy = RetryPolicy {
+    max_retries: 4,
+    base_delay_ms: 250,
+    max_delay_ms: 10_000,
+};
+
+/// Ready-made configuration for the hosted Groq API
+pub fn groq_preset() -> ProviderConfig {
+    ProviderConfig {
+        id: "groq".to_string(),
+        name: "Groq".to_string(),
+        protocol: ProtocolType::OpenAiCompatible,
+        base_url: DEFAULT_GROQ_BASE_URL.to_string(),
+        api_key_name: "GROQ_API_KEY".to_string(),
+        supports_oauth: false,
+        supports_coding_plan: false,
+        supports_international: false,
+        coding_plan_base_url: None,
+        international_base_url: None,
+        headers: None,
+        extra_body: None,
+        auth_type: AuthType::Bearer,
+        retry_policy: Some(GROQ_RETRY_POLICY),
+        model_aliases: Default::default(),
+        default_model: None,
+        tolerant_stream_json: false,
+        max_request_bytes: None,
+        multipart_images: false,
+        organization: None,
+        project: None,
+    }
+}
+
+pub struct GroqProvider {
+    base: BaseProvider,
+    protocol: OpenAiProtocol,
+}
+
+impl GroqProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            base: BaseProvider::new(config),
+            protocol: OpenAiProtocol,
+        }
+    }
+
+    /// Move `x_groq.usage` to the top level so the OpenAI parser reports it
+    fn lift_usage(payload: &mut Value) {
+        if payload.get("usage").is_some_and(|usage| !usage.is_null()) {
+            return;
+        }
+        let usage = payload
+            .get_mut("x_groq")
+            .and_then(|x_groq| x_groq.get_mut("usage"))
+            .map(Value::take);
+        if let Some(usage) = usage.filter(|usage| !usage.is_null()) {
+            payload["usage"] = usage;
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for GroqProvider {
+    fn id(&self) -> &str {
+        &self.base.config.id
+    }
+
+    fn name(&self) -> &str {
+        &self.base.config.name
+    }
+
+    fn protocol_type(&self) -> ProtocolType {
+        self.base.config.protocol
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.base.config
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // Llama 4 models take images; json_schema is served on the larger models
+        ProviderCapabilities {
+            supports_vision: true,
+            supports_structured_output: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        let base_url = self
+            .base
+            .resolve_base_url_with_fallback(ctx.api_key_manager)
+            .await?;
+        let base_url = base_url.trim().trim_end_matches('/');
+        if base_url.is_empty() {
+            return Ok(DEFAULT_GROQ_BASE_URL.to_string());
+        }
+        Ok(base_url.to_string())
+    }
+
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        self.base.api_key_credentials(api_key_manager).await
+    }
+
+    fn build_protocol_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        self.protocol.build_base_headers(ctx)
+    }
+
+    fn build_protocol_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        let mut body = self.protocol.build_request(ctx)?;
+        if let Some(obj) = body.as_object_mut() {
+            for field in UNSUPPORTED_FIELDS {
+                obj.remove(*field);
+            }
+        }
+        Ok(body)
+    }
+
+    fn parse_protocol_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        if sse::is_done(ctx.data) {
+            return self.protocol.parse_stream_event(ctx, state);
+        }
+        let Some(mut payload) = stream_parser::parse_json_data(state, ctx.data)? else {
+            return Ok(None);
+        };
+        Self::lift_usage(&mut payload);
+        let data = payload.to_string();
+        self.protocol.parse_stream_event(
+            StreamParseContext {
+                event_type: ctx.event_type,
+                data: &data,
+            },
+            state,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::rate_limit::RateLimitInfo;
+    use crate::llm::testing::fixtures::api_key_manager_with;
+    use crate::llm::types::UsageTiming;
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+    fn parse_all(provider: &GroqProvider, frames: &[&str]) -> Vec<StreamEvent> {
+        let mut state = StreamParseState::default();
+        let mut events = Vec::new();
+        for data in frames {
+            let ctx = StreamParseContext {
+                event_type: None,
+                data,
+            };
+            let event = provider
+                .parse_protocol_stream_event(ctx, &mut state)
+                .expect("parse");
+            events.extend(event);
+            events.append(&mut state.pending_events);
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn preset_resolves_groq_base_url() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let config = groq_preset();
+        let provider = GroqProvider::new(config.clone());
+        let ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "llama-3.3-70b-versatile",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+
+        assert_eq!(
+            provider.resolve_base_url(&ctx).await.expect("base url"),
+            "https://api.groq.com/openai/v1"
+        );
+        assert_eq!(RetryPolicy::for_provider(&config), GROQ_RETRY_POLICY);
+    }
+
+    #[test]
+    fn parse_stream_surfaces_x_groq_usage_timing() {
+        let provider = GroqProvider::new(groq_preset());
+
+        let events = parse_all(
+            &provider,
+            &[
+                r#"{"id":"chatcmpl-1","model":"llama-3.3-70b-versatile","choices":[{"index":0,"delta":{"content":"Hi"}}]}"#,
+                r#"{"id":"chatcmpl-1","model":"llama-3.3-70b-versatile","choices":[{"index":0,"delta":{},"finish_reason":"stop"}],"x_groq":{"id":"req_01","usage":{"queue_time":0.021,"prompt_tokens":18,"prompt_time":0.0035,"completion_tokens":2,"completion_time":0.0041,"total_tokens":20,"total_time":0.0076}}}"#,
+                "[DONE]",
+            ],
+        );
+
+        let usage = events.iter().find_map(|event| match event {
+            StreamEvent::Usage {
+                input_tokens,
+                output_tokens,
+                timing,
+                ..
+            } => Some((*input_tokens, *output_tokens, *timing)),
+            _ => None,
+        });
+        assert_eq!(
+            usage,
+            Some((
+                18,
+                2,
+                Some(UsageTiming {
+                    queue_time: Some(0.021),
+                    prompt_time: Some(0.0035),
+                    completion_time: Some(0.0041),
+                    total_time: Some(0.0076),
+                })
+            ))
+        );
+        assert!(matches!(events.last(), Some(StreamEvent::Done { .. })));
+    }
+
+    #[test]
+    fn groq_rate_limit_headers_parse() {
+        // Groq limits requests per day and tokens per minute, with fractional reset times
+        let headers: HeaderMap = [
+            ("x-ratelimit-limit-requests", "14400"),
+            ("x-ratelimit-remaining-requests", "14370"),
+            ("x-ratelimit-reset-requests", "2m59.56s"),
+            ("x-ratelimit-limit-tokens", "18000"),
+            ("x-ratelimit-remaining-tokens", "17997"),
+            ("x-ratelimit-reset-tokens", "7.66s"),
+        ]
+        .into_iter()
+        .map(|(name, value)| {
+            (
+                HeaderName::from_static(name),
+                HeaderValue::from_static(value),
+            )
+        })
+        .collect();
+
+        let info = RateLimitInfo::from_headers(&headers).expect("rate limit info");
+
+        assert_eq!(info.remaining_requests, Some(14370));
+        assert_eq!(info.reset_requests.as_deref(), Some("2m59.56s"));
+        assert_eq!(info.reset_tokens.as_deref(), Some("7.66s"));
+        assert!(!info.is_near_limit(0.1));
+    }
+
+    #[test]
+    fn build_request_drops_unsupported_top_k() {
+        let provider = GroqProvider::new(groq_preset());
+
+        let body = provider
+            .build_protocol_request(RequestBuildContext {
+                model: "llama-3.3-70b-versatile",
+                messages: &[],
+                tools: None,
+                temperature: Some(0.5),
+                max_tokens: None,
+                top_p: None,
+                top_k: Some(40),
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: None,
+                response_format: None,
+                user_id: None,
+                provider_options: None,
+                extra_body: None,
+                stream: true,
+                logprobs: false,
+                top_logprobs: None,
+                builtin_tools: &[],
+                reasoning_effort: None,
+            })
+            .expect("request");
+
+        assert!(body.get("top_k").is_none());
+        assert_eq!(body["temperature"], serde_json::json!(0.5));
+    }
+}
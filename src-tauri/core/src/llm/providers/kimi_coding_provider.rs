@@ -2,6 +2,7 @@
 // Uses the coding plan endpoint with special KimiCLI User-Agent header
 
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
 use crate::llm::protocols::{
     header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
     openai_protocol::OpenAiProtocol,
@@ -9,12 +10,20 @@ use crate::llm::protocols::{
     stream_parser::ProtocolStreamParser,
 };
 use crate::llm::providers::provider::{
-    BaseProvider, Provider, ProviderContext, ProviderCredentials as Creds,
+    probe_completion, BaseProvider, HealthStatus, Provider, ProviderCapabilities, ProviderContext,
+    ProviderCredentials as Creds,
 };
-use crate::llm::types::{ProtocolType, ProviderConfig};
+use crate::llm::types::{ModelInfo, ProtocolType, ProviderConfig};
 use async_trait::async_trait;
 use serde_json::Value;
 use std::collections::HashMap;
+use std::time::Instant;
+
+/// Client the coding plan endpoint accepts; pinned because Kimi rejects unknown agents
+pub const KIMI_CODING_USER_AGENT: &str = "KimiCLI/1.3";
+
+/// Model probed by the health check when the caller does not name one
+const HEALTH_CHECK_MODEL: &str = "kimi-k2.5";
 
 pub struct KimiCodingProvider {
     base: BaseProvider,
@@ -48,41 +57,55 @@ impl Provider for KimiCodingProvider {
         &self.base.config
     }
 
-    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, String> {
+    fn capabilities(&self) -> ProviderCapabilities {
+        // The coding plan endpoint only serves text chat
+        ProviderCapabilities::chat()
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
         // Use standard endpoint resolution
         self.base
             .resolve_base_url_with_fallback(ctx.api_key_manager)
             .await
+            .map_err(LlmError::from)
     }
 
-    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, String> {
-        // Try api_key_{provider_id} format first (standard storage format)
-        let key_value = api_key_manager
-            .get_setting(&format!("api_key_{}", self.base.config.id))
-            .await?;
-
-        // Fall back to api_key_name for backward compatibility
-        let key_value = match key_value {
-            Some(key) if !key.is_empty() => key,
-            _ => api_key_manager
-                .get_setting(&self.base.config.api_key_name)
-                .await?
-                .ok_or_else(|| format!("API key '{}' not found", self.base.config.api_key_name))?,
-        };
-
-        Ok(Creds::ApiKey(key_value))
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        self.base.api_key_credentials(api_key_manager).await
     }
 
     async fn add_provider_headers(
         &self,
         _ctx: &ProviderContext<'_>,
         headers: &mut HashMap<String, String>,
-    ) -> Result<(), String> {
-        // Add KimiCLI User-Agent for coding plan endpoint
-        headers.insert("User-Agent".to_string(), "KimiCLI/1.3".to_string());
+    ) -> Result<(), LlmError> {
+        // The coding plan endpoint only serves the Kimi CLI, so this replaces the default agent
+        headers.insert("User-Agent".to_string(), KIMI_CODING_USER_AGENT.to_string());
         Ok(())
     }
 
+    async fn list_models(&self, _ctx: &ProviderContext<'_>) -> Result<Vec<ModelInfo>, LlmError> {
+        // The coding plan endpoint serves a fixed model and has no /models route
+        Err(LlmError::Other(
+            "Kimi Coding Plan does not support model discovery".to_string(),
+        ))
+    }
+
+    async fn health_check(&self, ctx: &ProviderContext<'_>) -> Result<HealthStatus, String> {
+        // Without a /models route, probe the coding plan chat endpoint itself
+        let model = if ctx.model.is_empty() {
+            HEALTH_CHECK_MODEL
+        } else {
+            ctx.model
+        };
+        let probe_ctx = ProviderContext {
+            model,
+            ..ctx.clone()
+        };
+        let started = Instant::now();
+        HealthStatus::from_probe(probe_completion(self, &probe_ctx).await, started)
+    }
+
     fn build_protocol_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
         self.protocol.build_base_headers(ctx)
     }
@@ -108,24 +131,18 @@ mod tests {
     use super::*;
     use crate::database::Database;
     use crate::llm::auth::api_key_manager::ApiKeyManager;
+    use crate::llm::testing::fixtures::provider_config;
     use std::sync::Arc;
     use tempfile::TempDir;
 
     fn create_test_config() -> ProviderConfig {
         ProviderConfig {
-            id: "kimi_coding".to_string(),
-            name: "Kimi Coding Plan".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.moonshot.cn/kimi-cli".to_string(),
-            api_key_name: "KIMI_CODING_API_KEY".to_string(),
-            supports_oauth: false,
             supports_coding_plan: true,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "kimi_coding",
+                ProtocolType::OpenAiCompatible,
+                "https://api.moonshot.cn/kimi-cli",
+            )
         }
     }
 
@@ -215,6 +232,94 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn resolve_base_url_honors_coding_plan_setting() {
+        let (_dir, api_keys, _) = setup_test_context().await;
+        api_keys
+            .set_setting("use_coding_plan_kimi_coding", "true")
+            .await
+            .expect("set coding plan");
+        let config = ProviderConfig {
+            coding_plan_base_url: Some("https://api.kimi.com/coding/v1".to_string()),
+            ..create_test_config()
+        };
+        let provider = KimiCodingProvider::new(config.clone());
+        let ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+
+        let base_url = provider.resolve_base_url(&ctx).await.expect("base url");
+
+        assert_eq!(base_url, "https://api.kimi.com/coding/v1");
+    }
+
+    #[tokio::test]
+    async fn list_models_is_not_supported() {
+        let (_dir, api_keys, provider) = setup_test_context().await;
+        let config = create_test_config();
+        let ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+
+        let error = provider.list_models(&ctx).await.unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("does not support model discovery"));
+    }
+
     #[tokio::test]
     async fn get_credentials_returns_error_when_no_key_found() {
         let (_dir, api_keys, provider) = setup_test_context().await;
@@ -224,6 +329,114 @@ mod tests {
 
         assert!(result.is_err());
         let error_msg = result.unwrap_err();
-        assert!(error_msg.contains("API key 'KIMI_CODING_API_KEY' not found"));
+        assert!(error_msg
+            .to_string()
+            .contains("API key 'KIMI_CODING_API_KEY' not found"));
+    }
+
+    fn health_check_context<'a>(
+        config: &'a ProviderConfig,
+        api_keys: &'a ApiKeyManager,
+    ) -> ProviderContext<'a> {
+        ProviderContext {
+            provider_config: config,
+            api_key_manager: api_keys,
+            model: "",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        }
+    }
+
+    async fn health_check_against(base_url: String) -> HealthStatus {
+        let (_dir, api_keys, _) = setup_test_context().await;
+        api_keys
+            .set_setting("api_key_kimi_coding", "test-kimi-coding-key")
+            .await
+            .expect("set api key");
+        let config = ProviderConfig {
+            base_url,
+            ..create_test_config()
+        };
+        let provider = KimiCodingProvider::new(config.clone());
+
+        provider
+            .health_check(&health_check_context(&config, &api_keys))
+            .await
+            .expect("health check")
+    }
+
+    #[tokio::test]
+    async fn health_check_probes_coding_plan_chat_endpoint() {
+        use crate::llm::testing::mock_server::start_capture_server;
+
+        let (base_url, requests) =
+            start_capture_server(200, b"data: [DONE]\n\n".to_vec()).expect("start mock server");
+
+        let status = health_check_against(format!("{}/coding/v1", base_url)).await;
+
+        assert!(matches!(status, HealthStatus::Ok { .. }), "{:?}", status);
+        let request = requests.recv().expect("captured request");
+        assert_eq!(request.url, "/coding/v1/chat/completions");
+        let body: Value = serde_json::from_slice(&request.body).expect("json body");
+        assert_eq!(body["model"], HEALTH_CHECK_MODEL);
+        assert_eq!(body["max_tokens"], 1);
+    }
+
+    #[tokio::test]
+    async fn health_check_reports_rejected_key() {
+        use crate::llm::testing::mock_server::start_sequence_server;
+
+        let (base_url, _hits) = start_sequence_server(vec![(
+            401,
+            r#"{"error":{"message":"Invalid API key"}}"#.to_string(),
+        )])
+        .expect("start mock server");
+
+        let status = health_check_against(base_url).await;
+
+        match status {
+            HealthStatus::AuthFailed { message, .. } => {
+                assert!(message.contains("Invalid API key"))
+            }
+            other => panic!("Expected AuthFailed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn health_check_reports_refused_connection() {
+        // Bind then drop a listener so the port is known to be closed
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .expect("free port");
+
+        let status = health_check_against(format!("http://{}", addr)).await;
+
+        assert!(
+            matches!(status, HealthStatus::Unreachable { .. }),
+            "{:?}",
+            status
+        );
     }
 }
@@ -0,0 +1,246 @@
+// LM Studio Provider Implementation
+// Talks to a local LM Studio server through its OpenAI-compatible /v1 API
+// No credentials unless the server was started with an API token
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    openai_protocol::OpenAiProtocol,
+    request_builder::ProtocolRequestBuilder,
+    stream_parser::ProtocolStreamParser,
+};
+use crate::llm::providers::provider::{
+    BaseProvider, Provider, ProviderCapabilities, ProviderContext, ProviderCredentials as Creds,
+};
+use crate::llm::types::{AuthType, ProtocolType, ProviderConfig};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Default address of a local LM Studio server
+pub const DEFAULT_LMSTUDIO_BASE_URL: &str = "http://localhost:1234/v1";
+
+/// Value stored under the API key setting when the provider is only switched on
+const ENABLED_MARKER: &str = "enabled";
+
+/// Ready-made configuration for a local LM Studio server
+pub fn lmstudio_preset() -> ProviderConfig {
+    ProviderConfig {
+        id: "lmstudio".to_string(),
+        name: "LM Studio".to_string(),
+        protocol: ProtocolType::OpenAiCompatible,
+        base_url: DEFAULT_LMSTUDIO_BASE_URL.to_string(),
+        api_key_name: "LMSTUDIO_ENABLED".to_string(),
+        supports_oauth: false,
+        supports_coding_plan: false,
+        supports_international: false,
+        coding_plan_base_url: None,
+        international_base_url: None,
+        headers: None,
+        extra_body: None,
+        auth_type: AuthType::None,
+        retry_policy: None,
+        model_aliases: Default::default(),
+        default_model: None,
+        tolerant_stream_json: false,
+        max_request_bytes: None,
+        multipart_images: false,
+        organization: None,
+        project: None,
+    }
+}
+
+pub struct LmStudioProvider {
+    base: BaseProvider,
+    protocol: OpenAiProtocol,
+}
+
+impl LmStudioProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            base: BaseProvider::new(config),
+            protocol: OpenAiProtocol,
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for LmStudioProvider {
+    fn id(&self) -> &str {
+        &self.base.config.id
+    }
+
+    fn name(&self) -> &str {
+        &self.base.config.name
+    }
+
+    fn protocol_type(&self) -> ProtocolType {
+        self.base.config.protocol
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.base.config
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // Vision models and `/v1/embeddings` are served for whatever models are loaded
+        ProviderCapabilities {
+            supports_vision: true,
+            supports_embeddings: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        let base_url = self
+            .base
+            .resolve_base_url_with_fallback(ctx.api_key_manager)
+            .await?;
+        let base_url = base_url.trim().trim_end_matches('/');
+        if base_url.is_empty() {
+            return Ok(DEFAULT_LMSTUDIO_BASE_URL.to_string());
+        }
+        Ok(base_url.to_string())
+    }
+
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        // The setting holds the enabled marker unless the user entered a server token
+        let key_value = api_key_manager
+            .get_setting(&format!("api_key_{}", self.base.config.id))
+            .await?;
+        match key_value.as_deref().map(str::trim) {
+            Some(key) if !key.is_empty() && key != ENABLED_MARKER => {
+                Ok(Creds::ApiKey(key.to_string()))
+            }
+            _ => Ok(Creds::None),
+        }
+    }
+
+    fn build_protocol_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        self.protocol.build_base_headers(ctx)
+    }
+
+    fn build_protocol_request(
+        &self,
+        ctx: crate::llm::protocols::request_builder::RequestBuildContext,
+    ) -> Result<Value, String> {
+        self.protocol.build_request(ctx)
+    }
+
+    fn parse_protocol_stream_event(
+        &self,
+        ctx: crate::llm::protocols::stream_parser::StreamParseContext,
+        state: &mut crate::llm::protocols::stream_parser::StreamParseState,
+    ) -> Result<Option<crate::llm::types::StreamEvent>, String> {
+        self.protocol.parse_stream_event(ctx, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::protocols::stream_parser::{StreamParseContext, StreamParseState};
+    use crate::llm::testing::fixtures::api_key_manager_with;
+    use crate::llm::types::StreamEvent;
+
+    fn context<'a>(config: &'a ProviderConfig, api_keys: &'a ApiKeyManager) -> ProviderContext<'a> {
+        ProviderContext {
+            provider_config: config,
+            api_key_manager: api_keys,
+            model: "qwen2.5-coder-7b-instruct",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn preset_resolves_to_default_localhost_url() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let config = lmstudio_preset();
+        let provider = LmStudioProvider::new(config.clone());
+        let ctx = context(&config, &api_keys);
+
+        let base_url = provider.resolve_base_url(&ctx).await.expect("base url");
+
+        assert_eq!(base_url, "http://localhost:1234/v1");
+    }
+
+    #[tokio::test]
+    async fn get_credentials_uses_a_key_only_when_one_is_set() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let provider = LmStudioProvider::new(lmstudio_preset());
+
+        let creds = provider.get_credentials(&api_keys).await.expect("creds");
+        assert!(matches!(creds, Creds::None));
+
+        api_keys
+            .set_setting("api_key_lmstudio", ENABLED_MARKER)
+            .await
+            .expect("set enabled");
+        let creds = provider.get_credentials(&api_keys).await.expect("creds");
+        assert!(matches!(creds, Creds::None));
+
+        api_keys
+            .set_setting("api_key_lmstudio", "lm-token")
+            .await
+            .expect("set key");
+        let creds = provider.get_credentials(&api_keys).await.expect("creds");
+        assert!(matches!(creds, Creds::ApiKey(ref key) if key == "lm-token"));
+    }
+
+    #[test]
+    fn parse_stream_finishes_without_usage() {
+        let provider = LmStudioProvider::new(lmstudio_preset());
+        let mut state = StreamParseState::default();
+        let mut events = Vec::new();
+
+        for data in [
+            r#"{"choices":[{"index":0,"delta":{"content":"ok"}}]}"#,
+            r#"{"choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}"#,
+            "[DONE]",
+        ] {
+            let ctx = StreamParseContext {
+                event_type: None,
+                data,
+            };
+            let event = provider
+                .parse_protocol_stream_event(ctx, &mut state)
+                .expect("parse");
+            events.extend(event);
+            events.append(&mut state.pending_events);
+        }
+
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, StreamEvent::Usage { .. })));
+        match events.last() {
+            Some(StreamEvent::Done { finish_reason }) => {
+                assert_eq!(finish_reason.as_deref(), Some("stop"))
+            }
+            other => panic!("Expected Done, got {:?}", other),
+        }
+    }
+}
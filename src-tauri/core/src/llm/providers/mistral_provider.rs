@@ -0,0 +1,227 @@
+// Mistral Provider Implementation
+// OpenAI-compatible chat completions with Mistral's `safe_prompt` guardrail toggle
+// The API rejects unknown fields, so OpenAI-only ones are stripped from the body
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    openai_protocol::OpenAiProtocol,
+    request_builder::{ProtocolRequestBuilder, RequestBuildContext},
+    stream_parser::{ProtocolStreamParser, StreamParseContext, StreamParseState},
+};
+use crate::llm::providers::provider::{
+    BaseProvider, Provider, ProviderCapabilities, ProviderContext, ProviderCredentials as Creds,
+};
+use crate::llm::types::{ProtocolType, ProviderConfig, StreamEvent};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+pub const DEFAULT_MISTRAL_BASE_URL: &str = "https://api.mistral.ai/v1";
+
+/// Fields the OpenAI protocol sends that Mistral answers with a 422
+/// Usage arrives on the final chunk without `stream_options`
+const UNSUPPORTED_FIELDS: &[&str] = &["stream_options", "top_k", "user"];
+
+pub struct MistralProvider {
+    base: BaseProvider,
+    protocol: OpenAiProtocol,
+}
+
+impl MistralProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            base: BaseProvider::new(config),
+            protocol: OpenAiProtocol,
+        }
+    }
+
+    /// `providerOptions.mistral.safePrompt`, which prepends Mistral's safety system prompt
+    fn safe_prompt(provider_options: Option<&Value>) -> Option<bool> {
+        provider_options?
+            .get("mistral")?
+            .get("safePrompt")?
+            .as_bool()
+    }
+}
+
+#[async_trait]
+impl Provider for MistralProvider {
+    fn id(&self) -> &str {
+        &self.base.config.id
+    }
+
+    fn name(&self) -> &str {
+        &self.base.config.name
+    }
+
+    fn protocol_type(&self) -> ProtocolType {
+        self.base.config.protocol
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.base.config
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_vision: true,
+            supports_structured_output: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        let base_url = self
+            .base
+            .resolve_base_url_with_fallback(ctx.api_key_manager)
+            .await?;
+        let base_url = base_url.trim().trim_end_matches('/');
+        if base_url.is_empty() {
+            return Ok(DEFAULT_MISTRAL_BASE_URL.to_string());
+        }
+        Ok(base_url.to_string())
+    }
+
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        self.base.api_key_credentials(api_key_manager).await
+    }
+
+    fn build_protocol_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        self.protocol.build_base_headers(ctx)
+    }
+
+    fn build_protocol_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        let safe_prompt = Self::safe_prompt(ctx.provider_options);
+        let mut body = self.protocol.build_request(ctx)?;
+        if let Some(obj) = body.as_object_mut() {
+            for field in UNSUPPORTED_FIELDS {
+                obj.remove(*field);
+            }
+            if let Some(safe_prompt) = safe_prompt {
+                obj.insert("safe_prompt".to_string(), Value::Bool(safe_prompt));
+            }
+        }
+        Ok(body)
+    }
+
+    fn parse_protocol_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        self.protocol.parse_stream_event(ctx, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::fixtures::provider_config;
+    use crate::llm::types::ResponseFormat;
+    use serde_json::json;
+
+    fn mistral_config() -> ProviderConfig {
+        provider_config(
+            "mistral",
+            ProtocolType::OpenAiCompatible,
+            DEFAULT_MISTRAL_BASE_URL,
+        )
+    }
+
+    fn request_context<'a>(
+        provider_options: Option<&'a Value>,
+        response_format: Option<&'a ResponseFormat>,
+    ) -> RequestBuildContext<'a> {
+        RequestBuildContext {
+            model: "mistral-large-latest",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: Some(40),
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format,
+            user_id: None,
+            provider_options,
+            extra_body: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            builtin_tools: &[],
+            reasoning_effort: None,
+        }
+    }
+
+    #[test]
+    fn build_request_serializes_safe_prompt_and_drops_openai_fields() {
+        let provider = MistralProvider::new(mistral_config());
+        let options = json!({ "mistral": { "safePrompt": true } });
+
+        let body = provider
+            .build_protocol_request(request_context(Some(&options), None))
+            .expect("request");
+
+        assert_eq!(body["safe_prompt"], json!(true));
+        assert!(body.get("stream_options").is_none());
+        assert!(body.get("top_k").is_none());
+
+        let body = provider
+            .build_protocol_request(request_context(None, None))
+            .expect("request");
+        assert!(body.get("safe_prompt").is_none());
+    }
+
+    #[test]
+    fn build_request_serializes_json_mode() {
+        let provider = MistralProvider::new(mistral_config());
+
+        let body = provider
+            .build_protocol_request(request_context(None, Some(&ResponseFormat::JsonObject)))
+            .expect("request");
+
+        assert_eq!(body["response_format"], json!({ "type": "json_object" }));
+    }
+
+    #[test]
+    fn parse_stream_ends_on_done_sentinel() {
+        let provider = MistralProvider::new(mistral_config());
+        let mut state = StreamParseState::default();
+        let mut events = Vec::new();
+
+        for data in [
+            r#"{"id":"cmpl-1","model":"mistral-large-latest","choices":[{"index":0,"delta":{"role":"assistant","content":"{}"},"finish_reason":null}]}"#,
+            r#"{"id":"cmpl-1","model":"mistral-large-latest","choices":[{"index":0,"delta":{"content":""},"finish_reason":"stop"}],"usage":{"prompt_tokens":12,"total_tokens":14,"completion_tokens":2}}"#,
+            "[DONE]",
+        ] {
+            let ctx = StreamParseContext {
+                event_type: None,
+                data,
+            };
+            let event = provider
+                .parse_protocol_stream_event(ctx, &mut state)
+                .expect("parse");
+            events.extend(event);
+            events.append(&mut state.pending_events);
+        }
+
+        assert!(events.iter().any(|event| matches!(
+            event,
+            StreamEvent::Usage {
+                input_tokens: 12,
+                output_tokens: 2,
+                ..
+            }
+        )));
+        match events.last() {
+            Some(StreamEvent::Done { finish_reason }) => {
+                assert_eq!(finish_reason.as_deref(), Some("stop"))
+            }
+            other => panic!("Expected Done, got {:?}", other),
+        }
+    }
+}
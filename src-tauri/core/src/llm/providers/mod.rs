@@ -3,17 +3,35 @@ pub mod provider_configs;
 pub mod provider_registry;
 
 // New provider implementations
+pub mod azure_openai_provider;
+pub mod cohere_provider;
+pub mod deepseek_coding_provider;
 pub mod default_provider;
+pub mod fallback_provider;
 pub mod github_copilot_provider;
+pub mod grok_provider;
+pub mod groq_provider;
 pub mod kimi_coding_provider;
+pub mod lmstudio_provider;
+pub mod mistral_provider;
 pub mod moonshot_provider;
+pub mod ollama_provider;
 pub mod openai_provider;
 
 // Re-export key types
+pub use azure_openai_provider::AzureOpenAiProvider;
+pub use cohere_provider::CohereProvider;
+pub use deepseek_coding_provider::DeepSeekCodingProvider;
 pub use default_provider::DefaultProvider;
+pub use fallback_provider::{FallbackProvider, FallbackResponse};
 pub use github_copilot_provider::GithubCopilotProvider;
+pub use grok_provider::GrokProvider;
+pub use groq_provider::GroqProvider;
 pub use kimi_coding_provider::KimiCodingProvider;
+pub use lmstudio_provider::LmStudioProvider;
+pub use mistral_provider::MistralProvider;
 pub use moonshot_provider::MoonshotProvider;
+pub use ollama_provider::OllamaProvider;
 pub use openai_provider::OpenAiProvider;
 #[allow(unused_imports)]
 pub use provider::{Provider, ProviderCredentials};
@@ -2,6 +2,7 @@
 // Supports video input on the standard /v1 endpoint
 
 use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
 use crate::llm::protocols::{
     header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
     openai_protocol::OpenAiProtocol,
@@ -9,7 +10,7 @@ use crate::llm::protocols::{
     stream_parser::ProtocolStreamParser,
 };
 use crate::llm::providers::provider::{
-    BaseProvider, Provider, ProviderContext, ProviderCredentials as Creds,
+    BaseProvider, Provider, ProviderCapabilities, ProviderContext, ProviderCredentials as Creds,
 };
 use crate::llm::types::{ProtocolType, ProviderConfig};
 use async_trait::async_trait;
@@ -48,36 +49,30 @@ impl Provider for MoonshotProvider {
         &self.base.config
     }
 
-    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, String> {
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_vision: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
         // Use standard endpoint resolution
         self.base
             .resolve_base_url_with_fallback(ctx.api_key_manager)
             .await
+            .map_err(LlmError::from)
     }
 
-    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, String> {
-        // Try api_key_{provider_id} format first (standard storage format)
-        let key_value = api_key_manager
-            .get_setting(&format!("api_key_{}", self.base.config.id))
-            .await?;
-
-        // Fall back to api_key_name for backward compatibility
-        let key_value = match key_value {
-            Some(key) if !key.is_empty() => key,
-            _ => api_key_manager
-                .get_setting(&self.base.config.api_key_name)
-                .await?
-                .ok_or_else(|| format!("API key '{}' not found", self.base.config.api_key_name))?,
-        };
-
-        Ok(Creds::ApiKey(key_value))
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        self.base.api_key_credentials(api_key_manager).await
     }
 
     async fn add_provider_headers(
         &self,
         _ctx: &ProviderContext<'_>,
         _headers: &mut HashMap<String, String>,
-    ) -> Result<(), String> {
+    ) -> Result<(), LlmError> {
         // No special headers needed for Moonshot
         Ok(())
     }
@@ -107,24 +102,21 @@ mod tests {
     use super::*;
     use crate::database::Database;
     use crate::llm::auth::api_key_manager::ApiKeyManager;
+    use crate::llm::testing::fixtures::provider_config;
     use std::sync::Arc;
     use tempfile::TempDir;
 
     fn create_test_config() -> ProviderConfig {
         ProviderConfig {
-            id: "moonshot".to_string(),
-            name: "Moonshot".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.moonshot.cn/v1".to_string(),
-            api_key_name: "MOONSHOT_API_KEY".to_string(),
-            supports_oauth: false,
             supports_coding_plan: true,
             supports_international: true,
             coding_plan_base_url: Some("https://api.moonshot.cn/kimi-cli".to_string()),
             international_base_url: Some("https://api.moonshot.cn/international".to_string()),
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "moonshot",
+                ProtocolType::OpenAiCompatible,
+                "https://api.moonshot.cn/v1",
+            )
         }
     }
 
@@ -223,6 +215,8 @@ mod tests {
 
         assert!(result.is_err());
         let error_msg = result.unwrap_err();
-        assert!(error_msg.contains("API key 'MOONSHOT_API_KEY' not found"));
+        assert!(error_msg
+            .to_string()
+            .contains("API key 'MOONSHOT_API_KEY' not found"));
     }
 }
@@ -0,0 +1,272 @@
+// Ollama Provider Implementation
+// Talks to a local Ollama server through its native /api/chat endpoint, no credentials
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use crate::llm::protocols::{
+    header_builder::{HeaderBuildContext, ProtocolHeaderBuilder},
+    ollama_protocol::OllamaProtocol,
+    request_builder::ProtocolRequestBuilder,
+    stream_parser::{ProtocolStreamParser, StreamFormat},
+};
+use crate::llm::providers::provider::{
+    fetch_discovery_body, BaseProvider, Provider, ProviderCapabilities, ProviderContext,
+    ProviderCredentials as Creds,
+};
+use crate::llm::types::{ModelInfo, ProtocolType, ProviderConfig};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Default address of a local Ollama server
+pub const DEFAULT_OLLAMA_BASE_URL: &str = "http://localhost:11434";
+
+pub struct OllamaProvider {
+    base: BaseProvider,
+    protocol: OllamaProtocol,
+}
+
+impl OllamaProvider {
+    pub fn new(config: ProviderConfig) -> Self {
+        Self {
+            base: BaseProvider::new(config),
+            protocol: OllamaProtocol,
+        }
+    }
+
+    /// Strip the OpenAI-compatible `/v1` suffix older settings may still carry
+    fn normalize_base_url(base_url: &str) -> String {
+        let trimmed = base_url.trim().trim_end_matches('/');
+        if trimmed.is_empty() {
+            return DEFAULT_OLLAMA_BASE_URL.to_string();
+        }
+        trimmed.trim_end_matches("/v1").to_string()
+    }
+}
+
+/// Parse the `/api/tags` response listing locally pulled models
+pub fn parse_ollama_tags(body: &str) -> Result<Vec<ModelInfo>, LlmError> {
+    let payload: Value = serde_json::from_str(body).map_err(|e| {
+        LlmError::InvalidResponse(format!("Failed to parse Ollama tags response: {}", e))
+    })?;
+    let models = payload
+        .get("models")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| {
+            LlmError::InvalidResponse(
+                "Ollama tags response is missing the 'models' array".to_string(),
+            )
+        })?;
+
+    Ok(models
+        .iter()
+        .filter_map(|entry| {
+            let id = entry
+                .get("model")
+                .or_else(|| entry.get("name"))
+                .and_then(|v| v.as_str())?;
+            let created = entry
+                .get("modified_at")
+                .and_then(|v| v.as_str())
+                .and_then(|v| chrono::DateTime::parse_from_rfc3339(v).ok())
+                .map(|dt| dt.timestamp())
+                .unwrap_or(0);
+            Some(ModelInfo {
+                id: id.to_string(),
+                created,
+                owned_by: None,
+                context_window: None,
+            })
+        })
+        .collect())
+}
+
+#[async_trait]
+impl Provider for OllamaProvider {
+    fn id(&self) -> &str {
+        &self.base.config.id
+    }
+
+    fn name(&self) -> &str {
+        &self.base.config.name
+    }
+
+    fn protocol_type(&self) -> ProtocolType {
+        self.base.config.protocol
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.base.config
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        // Local models such as llava accept images; `/v1/embeddings` serves embedding models
+        ProviderCapabilities {
+            supports_vision: true,
+            supports_embeddings: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        let base_url = self
+            .base
+            .resolve_base_url_with_fallback(ctx.api_key_manager)
+            .await?;
+        Ok(Self::normalize_base_url(&base_url))
+    }
+
+    async fn resolve_endpoint_path(&self, _ctx: &ProviderContext<'_>) -> String {
+        "api/chat".to_string()
+    }
+
+    async fn get_credentials(&self, _api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
+        Ok(Creds::None)
+    }
+
+    async fn list_models(&self, ctx: &ProviderContext<'_>) -> Result<Vec<ModelInfo>, LlmError> {
+        let base_url = self.effective_base_url(ctx).await?;
+        let headers = self.build_headers(ctx, &Creds::None).await?;
+        let body = fetch_discovery_body(&format!("{}/api/tags", base_url), &headers).await?;
+        parse_ollama_tags(&body)
+    }
+
+    fn build_protocol_headers(&self, ctx: HeaderBuildContext) -> HashMap<String, String> {
+        self.protocol.build_base_headers(ctx)
+    }
+
+    fn build_protocol_request(
+        &self,
+        ctx: crate::llm::protocols::request_builder::RequestBuildContext,
+    ) -> Result<Value, String> {
+        self.protocol.build_request(ctx)
+    }
+
+    fn parse_protocol_stream_event(
+        &self,
+        ctx: crate::llm::protocols::stream_parser::StreamParseContext,
+        state: &mut crate::llm::protocols::stream_parser::StreamParseState,
+    ) -> Result<Option<crate::llm::types::StreamEvent>, String> {
+        self.protocol.parse_stream_event(ctx, state)
+    }
+
+    fn stream_format(&self) -> StreamFormat {
+        self.protocol.stream_format()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::types::{Message, MessageContent};
+
+    fn create_test_config(base_url: &str) -> ProviderConfig {
+        ProviderConfig {
+            api_key_name: "OLLAMA_ENABLED".to_string(),
+            auth_type: crate::llm::types::AuthType::None,
+            ..provider_config("ollama", ProtocolType::OpenAiCompatible, base_url)
+        }
+    }
+
+    fn context<'a>(
+        config: &'a ProviderConfig,
+        api_keys: &'a ApiKeyManager,
+        messages: &'a [Message],
+    ) -> ProviderContext<'a> {
+        ProviderContext {
+            provider_config: config,
+            api_key_manager: api_keys,
+            model: "llama3.2",
+            messages,
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn get_credentials_returns_none() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let provider = OllamaProvider::new(create_test_config(DEFAULT_OLLAMA_BASE_URL));
+
+        let creds = provider
+            .get_credentials(&api_keys)
+            .await
+            .expect("get credentials");
+
+        assert!(matches!(creds, Creds::None));
+    }
+
+    #[tokio::test]
+    async fn resolve_base_url_defaults_to_localhost_when_empty() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let config = create_test_config("");
+        let provider = OllamaProvider::new(config.clone());
+        let ctx = context(&config, &api_keys, &[]);
+
+        let base_url = provider.resolve_base_url(&ctx).await.expect("base url");
+
+        assert_eq!(base_url, DEFAULT_OLLAMA_BASE_URL);
+    }
+
+    #[test]
+    fn parse_ollama_tags_reads_local_models() {
+        let body = r#"{"models":[{"name":"llama3.2:latest","model":"llama3.2:latest","modified_at":"2024-10-01T12:00:00Z","size":2019393189}]}"#;
+
+        let models = parse_ollama_tags(body).expect("parse tags");
+
+        assert_eq!(
+            models,
+            vec![ModelInfo {
+                id: "llama3.2:latest".to_string(),
+                created: 1727784000,
+                owned_by: None,
+                context_window: None,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn build_complete_request_targets_api_chat_without_auth() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let config = create_test_config("http://127.0.0.1:11434/v1");
+        let provider = OllamaProvider::new(config.clone());
+        let messages = vec![Message::User {
+            content: MessageContent::Text("hi".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let ctx = context(&config, &api_keys, &messages);
+
+        let request = provider
+            .build_complete_request(&ctx)
+            .await
+            .expect("build request");
+
+        assert_eq!(request.url, "http://127.0.0.1:11434/api/chat");
+        assert!(!request.headers.contains_key("Authorization"));
+        assert_eq!(request.body["model"], "llama3.2");
+        assert_eq!(provider.stream_format(), StreamFormat::Ndjson);
+    }
+}
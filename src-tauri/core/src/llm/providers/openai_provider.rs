@@ -2,6 +2,7 @@
 // Handles both standard OpenAI API and OAuth (Codex) modes
 
 use crate::llm::auth::api_key_manager::{ApiKeyManager, ProviderCredentials};
+use crate::llm::error::LlmError;
 use crate::llm::protocols::header_builder::HeaderBuildContext;
 use crate::llm::protocols::openai_protocol::OpenAiProtocol;
 use crate::llm::protocols::openai_responses_protocol::OpenAiResponsesProtocol;
@@ -11,7 +12,8 @@ use crate::llm::protocols::stream_parser::{
 };
 use crate::llm::protocols::ProtocolHeaderBuilder;
 use crate::llm::providers::provider::{
-    BaseProvider, Provider, ProviderContext, ProviderCredentials as Creds,
+    fit_builtin_tools, BaseProvider, Provider, ProviderCapabilities, ProviderContext,
+    ProviderCredentials as Creds,
 };
 use crate::llm::types::ProtocolType;
 use crate::llm::types::{ProviderConfig, StreamEvent};
@@ -74,8 +76,18 @@ impl OpenAiProvider {
             max_tokens: ctx.max_tokens,
             top_p: ctx.top_p,
             top_k: ctx.top_k,
+            frequency_penalty: ctx.frequency_penalty,
+            presence_penalty: ctx.presence_penalty,
+            stop: ctx.stop,
+            response_format: ctx.response_format,
+            user_id: ctx.user_id,
             provider_options: ctx.provider_options,
             extra_body: ctx.provider_config.extra_body.as_ref(),
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            builtin_tools: &[],
+            reasoning_effort: ctx.reasoning_effort,
         };
         self.responses_protocol.build_request(request_ctx)
     }
@@ -99,7 +111,20 @@ impl Provider for OpenAiProvider {
         &self.base.config
     }
 
-    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, String> {
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            supports_vision: true,
+            supports_images: true,
+            supports_embeddings: true,
+            supports_structured_output: true,
+            supports_logprobs: true,
+            supports_web_search: true,
+            supports_code_interpreter: true,
+            ..ProviderCapabilities::chat()
+        }
+    }
+
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
         // If using OAuth, use ChatGPT backend API
         if self.is_oauth_mode(ctx.api_key_manager).await {
             return Ok("https://chatgpt.com/backend-api".to_string());
@@ -109,6 +134,7 @@ impl Provider for OpenAiProvider {
         self.base
             .resolve_base_url_with_fallback(ctx.api_key_manager)
             .await
+            .map_err(LlmError::from)
     }
 
     async fn resolve_endpoint_path(&self, ctx: &ProviderContext<'_>) -> String {
@@ -121,7 +147,7 @@ impl Provider for OpenAiProvider {
         }
     }
 
-    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, String> {
+    async fn get_credentials(&self, api_key_manager: &ApiKeyManager) -> Result<Creds, LlmError> {
         if self.is_oauth_mode(api_key_manager).await {
             // Get OAuth token
             let creds = api_key_manager.get_credentials(&self.base.config).await?;
@@ -136,8 +162,10 @@ impl Provider for OpenAiProvider {
                 _ => Ok(Creds::None),
             }
         } else {
-            // Standard API key
-            let creds = api_key_manager.get_credentials(&self.base.config).await?;
+            // Standard API key, rotating through a comma-separated key pool
+            let creds = api_key_manager
+                .get_credentials_rotating(&self.base.config)
+                .await?;
             match creds {
                 ProviderCredentials::Token(token) => Ok(Creds::ApiKey(token)),
                 ProviderCredentials::None => Ok(Creds::None),
@@ -149,7 +177,7 @@ impl Provider for OpenAiProvider {
         &self,
         ctx: &ProviderContext<'_>,
         headers: &mut HashMap<String, String>,
-    ) -> Result<(), String> {
+    ) -> Result<(), LlmError> {
         if self.is_oauth_mode(ctx.api_key_manager).await {
             headers.insert(
                 "OpenAI-Beta".to_string(),
@@ -171,7 +199,8 @@ impl Provider for OpenAiProvider {
         Ok(())
     }
 
-    async fn build_request(&self, ctx: &ProviderContext<'_>) -> Result<Value, String> {
+    async fn build_request(&self, ctx: &ProviderContext<'_>) -> Result<Value, LlmError> {
+        let builtin_tools = fit_builtin_tools(self, ctx.builtin_tools)?;
         if self.is_oauth_mode(ctx.api_key_manager).await || Self::is_responses_model(ctx.model) {
             let request_ctx = RequestBuildContext {
                 model: ctx.model,
@@ -181,10 +210,21 @@ impl Provider for OpenAiProvider {
                 max_tokens: ctx.max_tokens,
                 top_p: ctx.top_p,
                 top_k: ctx.top_k,
+                frequency_penalty: ctx.frequency_penalty,
+                presence_penalty: ctx.presence_penalty,
+                stop: ctx.stop,
+                response_format: ctx.response_format,
+                user_id: ctx.user_id,
                 provider_options: ctx.provider_options,
                 extra_body: ctx.provider_config.extra_body.as_ref(),
+                // Codex only answers as a stream, and Responses replies are only parsed as one
+                stream: true,
+                logprobs: false,
+                top_logprobs: None,
+                builtin_tools,
+                reasoning_effort: ctx.reasoning_effort,
             };
-            self.responses_protocol.build_request(request_ctx)
+            Ok(self.responses_protocol.build_request(request_ctx)?)
         } else {
             // Use standard protocol request building
             let request_ctx = RequestBuildContext {
@@ -195,10 +235,20 @@ impl Provider for OpenAiProvider {
                 max_tokens: ctx.max_tokens,
                 top_p: ctx.top_p,
                 top_k: ctx.top_k,
+                frequency_penalty: ctx.frequency_penalty,
+                presence_penalty: ctx.presence_penalty,
+                stop: ctx.stop,
+                response_format: ctx.response_format,
+                user_id: ctx.user_id,
                 provider_options: ctx.provider_options,
                 extra_body: ctx.provider_config.extra_body.as_ref(),
+                stream: ctx.stream,
+                logprobs: ctx.logprobs,
+                top_logprobs: ctx.top_logprobs,
+                builtin_tools,
+                reasoning_effort: ctx.reasoning_effort,
             };
-            self.protocol.build_request(request_ctx)
+            Ok(self.protocol.build_request(request_ctx)?)
         }
     }
 
@@ -239,10 +289,12 @@ mod tests {
     use super::*;
     use crate::database::Database;
     use crate::llm::auth::api_key_manager::ApiKeyManager;
+    use crate::llm::error::LlmError;
     use crate::llm::protocols::openai_responses_protocol::{
         parse_openai_oauth_event_legacy, parse_openai_oauth_function_call_done,
     };
     use crate::llm::protocols::{ProtocolStreamState, ToolCallAccum};
+    use crate::llm::testing::fixtures::provider_config;
     use crate::llm::types::{ContentPart, Message, MessageContent, StreamTextRequest};
     use serde_json::json;
     use std::sync::Arc;
@@ -256,19 +308,12 @@ mod tests {
         db.connect().await.expect("db connect");
         let api_keys = ApiKeyManager::new(db, std::path::PathBuf::from("/tmp"));
         let provider = OpenAiProvider::new(ProviderConfig {
-            id: "openai".to_string(),
-            name: "OpenAI".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.openai.com/v1".to_string(),
-            api_key_name: "OPENAI_API_KEY".to_string(),
             supports_oauth: true,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "openai",
+                ProtocolType::OpenAiCompatible,
+                "https://api.openai.com/v1",
+            )
         });
 
         let request = StreamTextRequest {
@@ -277,6 +322,7 @@ mod tests {
                 Message::User {
                     content: MessageContent::Text("hi".to_string()),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::Assistant {
                     content: MessageContent::Parts(vec![
@@ -291,6 +337,7 @@ mod tests {
                         },
                     ]),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::Tool {
                     content: vec![ContentPart::ToolResult {
@@ -299,6 +346,7 @@ mod tests {
                         output: json!({ "type": "text", "value": "ok" }),
                     }],
                     provider_options: None,
+                    cache: false,
                 },
             ],
             tools: None,
@@ -307,9 +355,28 @@ mod tests {
             max_tokens: None,
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            template: None,
+            trim_strategy: None,
+            logprobs: None,
+            top_logprobs: None,
             provider_options: None,
             request_id: None,
             trace_context: None,
+            timeout_ms: None,
+            dry_run: None,
+            builtin_tools: None,
+            strip_headers: None,
+            max_attempts: None,
+            image_input_check: None,
+            reasoning_effort: None,
+            stall_timeout_ms: None,
+            idempotent: None,
         };
 
         let ctx = ProviderContext {
@@ -322,8 +389,24 @@ mod tests {
             max_tokens: request.max_tokens,
             top_p: request.top_p,
             top_k: request.top_k,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.as_deref(),
+            response_format: request.response_format.as_ref(),
+            user_id: request.user_id.as_deref(),
+            base_url_override: request.base_url_override.as_deref(),
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
         };
 
         let body = provider.build_oauth_request(&ctx).expect("request body");
@@ -544,19 +627,12 @@ mod tests {
         db.connect().await.expect("db connect");
         let api_keys = ApiKeyManager::new(db, std::path::PathBuf::from("/tmp"));
         let provider = OpenAiProvider::new(ProviderConfig {
-            id: "openai".to_string(),
-            name: "OpenAI".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.openai.com/v1".to_string(),
-            api_key_name: "OPENAI_API_KEY".to_string(),
             supports_oauth: true,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "openai",
+                ProtocolType::OpenAiCompatible,
+                "https://api.openai.com/v1",
+            )
         });
 
         let request = StreamTextRequest {
@@ -565,20 +641,24 @@ mod tests {
                 Message::System {
                     content: "You are a helpful assistant.".to_string(),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::User {
                     content: MessageContent::Text("Hello!".to_string()),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::Assistant {
                     content: MessageContent::Text("Hi there! How can I help you?".to_string()),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::User {
                     content: MessageContent::Parts(vec![ContentPart::Text {
                         text: "What's the weather?".to_string(),
                     }]),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::Assistant {
                     content: MessageContent::Parts(vec![
@@ -591,6 +671,7 @@ mod tests {
                         },
                     ]),
                     provider_options: None,
+                    cache: false,
                 },
             ],
             tools: None,
@@ -599,9 +680,28 @@ mod tests {
             max_tokens: None,
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            template: None,
+            trim_strategy: None,
+            logprobs: None,
+            top_logprobs: None,
             provider_options: None,
             request_id: None,
             trace_context: None,
+            timeout_ms: None,
+            dry_run: None,
+            builtin_tools: None,
+            strip_headers: None,
+            max_attempts: None,
+            image_input_check: None,
+            reasoning_effort: None,
+            stall_timeout_ms: None,
+            idempotent: None,
         };
 
         let ctx = ProviderContext {
@@ -614,8 +714,24 @@ mod tests {
             max_tokens: request.max_tokens,
             top_p: request.top_p,
             top_k: request.top_k,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.as_deref(),
+            response_format: request.response_format.as_ref(),
+            user_id: request.user_id.as_deref(),
+            base_url_override: request.base_url_override.as_deref(),
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
         };
 
         let body = provider.build_oauth_request(&ctx).expect("request body");
@@ -1,17 +1,32 @@
 // Provider trait and base implementation
 // Providers encapsulate provider-specific business logic and configuration
 
-use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::auth::api_key_manager::{ApiKeyManager, ProviderCredentials as PooledCredentials};
+use crate::llm::cancellation::cancellable;
+use crate::llm::error::LlmError;
+use crate::llm::http_client::{self, SendWithMiddleware};
 use crate::llm::protocols::{
+    gemini_protocol::GeminiProtocol,
     header_builder::HeaderBuildContext,
+    multipart::{encode_multipart, extract_image_attachments, ImageAttachment},
     request_builder::RequestBuildContext,
-    stream_parser::{StreamParseContext, StreamParseState},
+    response_parser::{self, CompletionResult},
+    stream_parser::{StreamFormat, StreamParseContext, StreamParseState},
 };
+use crate::llm::request_id::{self, RequestIds};
+use crate::llm::request_log;
+use crate::llm::retry::{RetryBudget, RetryPolicy, RotateOnThrottle};
+use crate::llm::tokenize;
 use crate::llm::types::ProtocolType;
-use crate::llm::types::{Message, ProviderConfig, StreamEvent, ToolDefinition, TraceContext};
+use crate::llm::types::{
+    BuiltinTool, Message, MessageContent, ModelInfo, ProviderConfig, ReasoningEffort,
+    ResponseFormat, StreamEvent, ToolDefinition, TraceContext,
+};
 use async_trait::async_trait;
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 
 /// Context for provider operations
 #[derive(Clone)]
@@ -25,9 +40,102 @@ pub struct ProviderContext<'a> {
     pub max_tokens: Option<i32>,
     pub top_p: Option<f32>,
     pub top_k: Option<i32>,
+    pub frequency_penalty: Option<f32>,
+    pub presence_penalty: Option<f32>,
+    pub stop: Option<&'a [String]>,
+    pub response_format: Option<&'a ResponseFormat>,
+    /// End-user identifier forwarded for provider-side abuse monitoring
+    pub user_id: Option<&'a str>,
+    /// Base URL for this call only; skips `resolve_base_url` when set
+    pub base_url_override: Option<&'a str>,
     pub provider_options: Option<&'a Value>,
     #[allow(dead_code)]
     pub trace_context: Option<&'a TraceContext>,
+    /// Fires when the caller aborts the request
+    pub cancel_token: Option<&'a CancellationToken>,
+    /// Whether the response is streamed; `complete` turns it off
+    pub stream: bool,
+    /// Requested per-token log probabilities, dropped for providers that do not report them
+    pub logprobs: bool,
+    pub top_logprobs: Option<u8>,
+    /// Build the request but do not send it; the runners hand back a `DryRunRequest` instead
+    pub dry_run: bool,
+    pub builtin_tools: &'a [BuiltinTool],
+    /// Header names removed before sending, in addition to the global denylist
+    pub strip_headers: &'a [String],
+    /// Attempts left for this logical request across retries, replays and fallback providers
+    pub retry_budget: Option<&'a RetryBudget>,
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Copied into `BuiltRequest::idempotent`; false sends the request exactly once
+    pub idempotent: bool,
+}
+
+/// Re-signs a throttled request with the next key of the provider's pool
+/// The throttled key is cooled down first; when the pool has no other usable key the
+/// retry policy backs off and resends the same request
+pub struct CredentialRotation<'a, 'b, F> {
+    provider: &'a dyn Provider,
+    ctx: &'a ProviderContext<'b>,
+    built: BuiltRequest,
+    prepare: F,
+}
+
+impl<'a, 'b, F> CredentialRotation<'a, 'b, F>
+where
+    F: Fn(&BuiltRequest) -> reqwest::RequestBuilder + Send + Sync,
+{
+    /// `prepare` turns a built request into the HTTP request that is sent
+    pub fn new(
+        provider: &'a dyn Provider,
+        ctx: &'a ProviderContext<'b>,
+        built: BuiltRequest,
+        prepare: F,
+    ) -> Self {
+        Self {
+            provider,
+            ctx,
+            built,
+            prepare,
+        }
+    }
+
+    /// The request as last built, carrying the key that was sent most recently
+    pub fn built(&self) -> &BuiltRequest {
+        &self.built
+    }
+
+    pub fn request(&self) -> reqwest::RequestBuilder {
+        (self.prepare)(&self.built)
+    }
+}
+
+#[async_trait]
+impl<F> RotateOnThrottle for CredentialRotation<'_, '_, F>
+where
+    F: Fn(&BuiltRequest) -> reqwest::RequestBuilder + Send + Sync,
+{
+    async fn rotate(&mut self) -> Option<reqwest::RequestBuilder> {
+        let throttled = self.built.api_key.clone()?;
+        let api_keys = self.ctx.api_key_manager;
+        api_keys.mark_key_rate_limited(&throttled);
+
+        let rebuilt = match self.provider.build_complete_request(self.ctx).await {
+            Ok(rebuilt) => rebuilt,
+            Err(err) => {
+                log::warn!("[Retry] Failed to rebuild throttled request: {}", err);
+                return None;
+            }
+        };
+        let rotated = rebuilt
+            .api_key
+            .as_deref()
+            .is_some_and(|key| key != throttled && !api_keys.is_key_rate_limited(key));
+        if !rotated {
+            return None;
+        }
+        self.built = rebuilt;
+        Some(self.request())
+    }
 }
 
 /// Credentials for authentication
@@ -49,8 +157,301 @@ pub struct BuiltRequest {
     pub url: String,
     pub headers: HashMap<String, String>,
     pub body: Value,
+    /// API key sent with the request, reported back to the key pool on 429
+    pub api_key: Option<String>,
+    /// Whether sending the request twice is harmless; retries and stream replays are skipped
+    /// when it is not
+    pub idempotent: bool,
+    /// Client ID sent in the request headers; runners fill in the provider's from the response
+    pub request_ids: RequestIds,
+    /// Images taken out of `body` to send as multipart fields; empty for a plain JSON body
+    pub attachments: Vec<ImageAttachment>,
+}
+
+impl BuiltRequest {
+    /// Set `builder`'s body: `body` as JSON, or a multipart form when it has attachments
+    pub fn with_body(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if self.attachments.is_empty() {
+            return builder.json(&self.body);
+        }
+        // Encoded up front rather than as a streamed form so retries can resend it
+        let form = encode_multipart(&self.body, &self.attachments);
+        builder
+            .header(reqwest::header::CONTENT_TYPE, form.content_type)
+            .body(form.bytes)
+    }
+
+    /// POST of this request to its URL with its headers and body
+    pub fn post(&self, client: &reqwest::Client) -> reqwest::RequestBuilder {
+        let mut builder = client.post(&self.url);
+        for (key, value) in &self.headers {
+            builder = builder.header(key, value);
+        }
+        self.with_body(builder)
+    }
+
+    /// Streaming POST of this request to `url`, which differs from `self.url` only when
+    /// recording fixtures against a test server
+    pub fn stream_request(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        timeout: Option<Duration>,
+    ) -> reqwest::RequestBuilder {
+        let mut builder = client.post(url);
+        for (key, value) in &self.headers {
+            builder = builder.header(key, value);
+        }
+        builder = self.with_body(builder.header("Accept", "text/event-stream"));
+        match timeout {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        }
+    }
+
+    /// The request as a dry run reports it, with credentials redacted
+    pub fn dry_run(&self) -> DryRunRequest {
+        let redactor = request_log::redactor();
+        DryRunRequest {
+            url: redactor.redact_url(&self.url),
+            headers: redactor.redact_headers(&self.headers),
+            body: redactor.redact_json(&self.body),
+        }
+    }
+}
+
+/// Request that would have been sent, returned in place of a response when `dry_run` is set
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DryRunRequest {
+    pub url: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Value,
+}
+
+/// Features a provider supports, used by the UI to decide what to offer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ProviderCapabilities {
+    #[serde(rename = "supportsStreaming")]
+    pub supports_streaming: bool,
+    #[serde(rename = "supportsTools")]
+    pub supports_tools: bool,
+    #[serde(rename = "supportsVision")]
+    pub supports_vision: bool,
+    #[serde(rename = "supportsImages")]
+    pub supports_images: bool,
+    #[serde(rename = "supportsEmbeddings")]
+    pub supports_embeddings: bool,
+    /// Enforces a JSON schema on the response; JSON object mode is assumed otherwise
+    #[serde(rename = "supportsStructuredOutput")]
+    pub supports_structured_output: bool,
+    /// Reports per-token log probabilities when asked
+    #[serde(rename = "supportsLogprobs")]
+    pub supports_logprobs: bool,
+    /// Searches the web on its side when `BuiltinTool::WebSearch` is requested
+    #[serde(rename = "supportsWebSearch")]
+    pub supports_web_search: bool,
+    /// Runs model-written code on its side when `BuiltinTool::CodeInterpreter` is requested
+    #[serde(rename = "supportsCodeInterpreter")]
+    pub supports_code_interpreter: bool,
+    /// Takes vision images as multipart file fields next to the JSON payload
+    #[serde(default, rename = "supportsMultipartImages")]
+    pub supports_multipart_images: bool,
+}
+
+impl ProviderCapabilities {
+    /// Streaming chat with tool calls and nothing else
+    pub const fn chat() -> Self {
+        Self {
+            supports_streaming: true,
+            supports_tools: true,
+            supports_vision: false,
+            supports_images: false,
+            supports_embeddings: false,
+            supports_structured_output: false,
+            supports_logprobs: false,
+            supports_web_search: false,
+            supports_code_interpreter: false,
+            supports_multipart_images: false,
+        }
+    }
+}
+
+/// Outcome of a provider health check, with the round trip of the probe request
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "status")]
+pub enum HealthStatus {
+    #[serde(rename = "ok")]
+    Ok {
+        #[serde(rename = "latencyMs")]
+        latency_ms: u64,
+    },
+    #[serde(rename = "authFailed")]
+    AuthFailed {
+        #[serde(rename = "latencyMs")]
+        latency_ms: u64,
+        message: String,
+    },
+    #[serde(rename = "unreachable")]
+    Unreachable {
+        #[serde(rename = "latencyMs")]
+        latency_ms: u64,
+        message: String,
+    },
 }
 
+impl HealthStatus {
+    /// Classify the result of a probe request sent at `started`
+    /// Errors that mean the check itself could not run are returned as `Err`
+    pub fn from_probe(result: Result<(), LlmError>, started: Instant) -> Result<Self, String> {
+        let latency_ms = started.elapsed().as_millis() as u64;
+        match result {
+            // A throttled request still proves the key was accepted
+            Ok(()) | Err(LlmError::RateLimited { .. }) => Ok(Self::Ok { latency_ms }),
+            Err(err @ LlmError::Auth(_)) => Ok(Self::AuthFailed {
+                latency_ms,
+                message: err.to_string(),
+            }),
+            Err(LlmError::Other(message)) => Err(message),
+            Err(err) => Ok(Self::Unreachable {
+                latency_ms,
+                message: err.to_string(),
+            }),
+        }
+    }
+}
+
+/// Fallback for providers that cannot enforce a schema
+static JSON_OBJECT_FORMAT: ResponseFormat = ResponseFormat::JsonObject;
+
+/// Fit a requested response format to what the provider can enforce
+/// Schemas downgrade to JSON object mode without structured output support; protocols other
+/// than OpenAI-compatible have no response format field, so asking for one is an error
+pub(crate) fn fit_response_format<'a, P: Provider + ?Sized>(
+    provider: &P,
+    format: Option<&'a ResponseFormat>,
+) -> Result<Option<&'a ResponseFormat>, LlmError> {
+    match format {
+        None => Ok(None),
+        Some(_) if provider.protocol_type() != ProtocolType::OpenAiCompatible => {
+            Err(LlmError::Other(format!(
+                "Provider '{}' does not support JSON response formats / 该服务商不支持 JSON 输出格式",
+                provider.id()
+            )))
+        }
+        Some(ResponseFormat::JsonSchema { name, .. })
+            if !provider.capabilities().supports_structured_output =>
+        {
+            log::warn!(
+                "Provider '{}' cannot enforce schema '{}', falling back to JSON object mode",
+                provider.id(),
+                name
+            );
+            Ok(Some(&JSON_OBJECT_FORMAT))
+        }
+        Some(format) => Ok(Some(format)),
+    }
+}
+
+/// Whether to ask for logprobs; providers that do not report them get the request without
+pub(crate) fn fit_logprobs<P: Provider + ?Sized>(provider: &P, requested: bool) -> bool {
+    if requested && !provider.capabilities().supports_logprobs {
+        log::warn!(
+            "Provider '{}' does not report logprobs, sending the request without them",
+            provider.id()
+        );
+        return false;
+    }
+    requested
+}
+
+/// Check requested provider-side tools against the provider's capabilities
+pub(crate) fn fit_builtin_tools<'a, P: Provider + ?Sized>(
+    provider: &P,
+    tools: &'a [BuiltinTool],
+) -> Result<&'a [BuiltinTool], LlmError> {
+    let capabilities = provider.capabilities();
+    let unsupported = tools.iter().find(|tool| match tool {
+        BuiltinTool::WebSearch => !capabilities.supports_web_search,
+        BuiltinTool::CodeInterpreter => !capabilities.supports_code_interpreter,
+    });
+    match unsupported {
+        Some(tool) => Err(LlmError::Other(format!(
+            "Provider '{}' does not offer built-in {} / 该服务商不支持内置工具: {}",
+            provider.id(),
+            tool.label(),
+            tool.label()
+        ))),
+        None => Ok(tools),
+    }
+}
+
+/// Request body limit for providers without `max_request_bytes`, matching the strictest of
+/// the big APIs (Anthropic's 32 MB)
+pub const DEFAULT_MAX_REQUEST_BYTES: usize = 32 * 1024 * 1024;
+
+/// Reject a body over the provider's size limit before it is sent
+/// Oversized bodies, usually base64 images, otherwise upload for a while and end in a 413
+pub(crate) fn check_request_size(
+    config: &ProviderConfig,
+    body: &Value,
+    attachments: &[ImageAttachment],
+) -> Result<(), LlmError> {
+    let limit = config
+        .max_request_bytes
+        .unwrap_or(DEFAULT_MAX_REQUEST_BYTES);
+    let attached: usize = attachments.iter().map(|image| image.data.len()).sum();
+    let size = serialized_len(body) + attached;
+    if size > limit {
+        log::warn!(
+            "[Provider {}] Request body of {} bytes is over the {}-byte limit",
+            config.id,
+            size,
+            limit
+        );
+        return Err(LlmError::RequestTooLarge { size, limit });
+    }
+    Ok(())
+}
+
+/// Length of `body` as JSON, counted without building the string
+fn serialized_len(body: &Value) -> usize {
+    struct Counter(usize);
+
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    // Writing a `Value` into a sink that never fails cannot fail
+    let _ = serde_json::to_writer(&mut counter, body);
+    counter.0
+}
+
+/// Returned by send paths that have no way to hand back a `DryRunRequest`
+pub(crate) const DRY_RUN_UNSUPPORTED: &str =
+    "Dry run is only supported for streamed requests / 仅流式请求支持试运行";
+
+/// Upper bound for a health check probe, so a hung endpoint reports as unreachable
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+/// Upper bound for a token count request, after which the local estimate is shown instead
+const COUNT_TOKENS_TIMEOUT: Duration = Duration::from_secs(10);
+/// Request fields Anthropic's `/messages/count_tokens` accepts
+const ANTHROPIC_COUNT_FIELDS: &[&str] = &[
+    "model",
+    "messages",
+    "system",
+    "tools",
+    "tool_choice",
+    "thinking",
+];
+
 /// Trait for provider-specific logic
 /// Each provider can override specific behaviors while inheriting defaults from the base protocol
 #[async_trait]
@@ -70,15 +471,25 @@ pub trait Provider: Send + Sync {
 
     /// Resolve the base URL for the request
     /// Provider can override this to select between different endpoints (coding plan, international, etc.)
-    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, String>;
+    async fn resolve_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError>;
+
+    /// Base URL actually used for a call: the per-request override, else `resolve_base_url`
+    async fn effective_base_url(&self, ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        match ctx.base_url_override.map(str::trim) {
+            Some(url) if !url.is_empty() => Ok(url.trim_end_matches('/').to_string()),
+            _ => self.resolve_base_url(ctx).await,
+        }
+    }
 
     /// Resolve the endpoint path
     /// Provider can override this for special endpoints (e.g., OpenAI OAuth uses 'codex/responses')
-    async fn resolve_endpoint_path(&self, _ctx: &ProviderContext<'_>) -> String {
+    async fn resolve_endpoint_path(&self, ctx: &ProviderContext<'_>) -> String {
         // Default to protocol's standard endpoint
         match self.protocol_type() {
             ProtocolType::OpenAiCompatible => "chat/completions".to_string(),
-            ProtocolType::Claude => "messages".to_string(),
+            ProtocolType::Claude | ProtocolType::Anthropic => "messages".to_string(),
+            ProtocolType::Gemini => GeminiProtocol::endpoint_path(ctx.model, ctx.stream),
+            ProtocolType::Cohere => "chat".to_string(),
         }
     }
 
@@ -86,7 +497,7 @@ pub trait Provider: Send + Sync {
     async fn get_credentials(
         &self,
         api_key_manager: &ApiKeyManager,
-    ) -> Result<ProviderCredentials, String>;
+    ) -> Result<ProviderCredentials, LlmError>;
 
     /// Build headers for the request
     /// Provider can override this to add special headers (e.g., GitHub Copilot, Moonshot coding plan)
@@ -94,7 +505,7 @@ pub trait Provider: Send + Sync {
         &self,
         ctx: &ProviderContext<'_>,
         credentials: &ProviderCredentials,
-    ) -> Result<HashMap<String, String>, String> {
+    ) -> Result<HashMap<String, String>, LlmError> {
         let (api_key, oauth_token) = match credentials {
             ProviderCredentials::None => (None, None),
             ProviderCredentials::Token(token) => (Some(token.as_str()), Some(token.as_str())),
@@ -105,15 +516,37 @@ pub trait Provider: Send + Sync {
         let header_ctx = HeaderBuildContext {
             api_key,
             oauth_token,
-            extra_headers: ctx.provider_config.headers.as_ref(),
+            extra_headers: None,
         };
 
         // Start with protocol base headers
         let mut headers = self.build_protocol_headers(header_ctx);
 
-        // Add provider-specific headers
+        // Static headers from the provider config fill in around the protocol headers
+        if let Some(config_headers) = ctx.provider_config.headers.as_ref() {
+            let base_url = if config_headers
+                .values()
+                .any(|value| value.contains(BASE_URL_PLACEHOLDER))
+            {
+                Some(self.effective_base_url(ctx).await?)
+            } else {
+                None
+            };
+            merge_config_headers(
+                &mut headers,
+                config_headers,
+                oauth_token.or(api_key),
+                base_url.as_deref(),
+            );
+        }
+
+        set_openai_account_headers(&mut headers, ctx.provider_config);
+        set_user_agent(&mut headers);
+
+        // Add provider-specific headers, which win over config headers
         self.add_provider_headers(ctx, &mut headers).await?;
 
+        HeaderPolicy::current(ctx.strip_headers).apply(&mut headers);
         Ok(headers)
     }
 
@@ -126,14 +559,14 @@ pub trait Provider: Send + Sync {
         &self,
         _ctx: &ProviderContext<'_>,
         _headers: &mut HashMap<String, String>,
-    ) -> Result<(), String> {
+    ) -> Result<(), LlmError> {
         // Default: no additional headers
         Ok(())
     }
 
     /// Build the request body
     /// Provider can override this for special request formats (e.g., OpenAI OAuth/Codex)
-    async fn build_request(&self, ctx: &ProviderContext<'_>) -> Result<Value, String> {
+    async fn build_request(&self, ctx: &ProviderContext<'_>) -> Result<Value, LlmError> {
         // Google OpenAI-compatible endpoint rejects top_k.
         let drop_top_k = ctx.provider_config.id.eq_ignore_ascii_case("google")
             || ctx
@@ -141,6 +574,9 @@ pub trait Provider: Send + Sync {
                 .base_url
                 .contains("generativelanguage.googleapis.com");
         let top_k = if drop_top_k { None } else { ctx.top_k };
+        let response_format = fit_response_format(self, ctx.response_format)?;
+        let logprobs = fit_logprobs(self, ctx.logprobs);
+        let builtin_tools = fit_builtin_tools(self, ctx.builtin_tools)?;
         let request_ctx = RequestBuildContext {
             model: ctx.model,
             messages: ctx.messages,
@@ -149,11 +585,21 @@ pub trait Provider: Send + Sync {
             max_tokens: ctx.max_tokens,
             top_p: ctx.top_p,
             top_k,
+            frequency_penalty: ctx.frequency_penalty,
+            presence_penalty: ctx.presence_penalty,
+            stop: ctx.stop,
+            response_format,
+            user_id: ctx.user_id,
             provider_options: ctx.provider_options,
             extra_body: ctx.provider_config.extra_body.as_ref(),
+            stream: ctx.stream,
+            logprobs,
+            top_logprobs: ctx.top_logprobs.filter(|_| logprobs),
+            builtin_tools,
+            reasoning_effort: ctx.reasoning_effort,
         };
 
-        self.build_protocol_request(request_ctx)
+        Ok(self.build_protocol_request(request_ctx)?)
     }
 
     /// Build protocol request (delegates to protocol)
@@ -190,30 +636,256 @@ pub trait Provider: Send + Sync {
         self.parse_stream_event(event_type, data, state)
     }
 
+    /// Framing of the streaming response body
+    fn stream_format(&self) -> StreamFormat {
+        StreamFormat::Sse
+    }
+
+    /// Parse the single JSON body of a non-streaming response
+    fn parse_completion_response(&self, body: &Value) -> Result<CompletionResult, String> {
+        response_parser::parse_completion(self.protocol_type(), body)
+    }
+
+    /// Send the request with `stream` off and wait for the whole reply
+    /// For scripts and background jobs that have no use for incremental events
+    async fn complete(&self, ctx: &ProviderContext<'_>) -> Result<CompletionResult, String> {
+        if ctx.dry_run {
+            return Err(DRY_RUN_UNSUPPORTED.to_string());
+        }
+        let ctx = &ProviderContext {
+            stream: false,
+            ..ctx.clone()
+        };
+        let request = self.build_complete_request(ctx).await?;
+        let client = http_client::shared_client()?;
+        let policy = RetryPolicy::for_provider(self.config()).for_idempotency(request.idempotent);
+        let response = cancellable(
+            ctx.cancel_token,
+            policy.send_within(request.post(&client), ctx.retry_budget),
+        )
+        .await??;
+
+        let status = response.status().as_u16();
+        if status >= 400 {
+            if status == 429 {
+                if let Some(api_key) = &request.api_key {
+                    ctx.api_key_manager.mark_key_rate_limited(api_key);
+                }
+            }
+            let headers = response.headers().clone();
+            let text = response.text().await.unwrap_or_default();
+            log::warn!(
+                "[Provider {}] Completion failed with {} for {:?}",
+                self.id(),
+                status,
+                request.request_ids
+            );
+            return Err(LlmError::from_response_parts(status, &headers, text).into());
+        }
+        let body: Value = cancellable(ctx.cancel_token, response.json())
+            .await?
+            .map_err(|e| format!("Failed to parse completion response: {}", e))?;
+        let mut result = self.parse_completion_response(&body)?;
+        if result.usage.is_none() {
+            let usage = tokenize::estimate_usage(self.protocol_type(), ctx.messages, &result.text);
+            result.usage = Some(usage);
+        }
+        Ok(result)
+    }
+
+    /// List the models the provider exposes
+    /// Default queries the OpenAI-compatible `/models` endpoint; override for other discovery APIs
+    async fn list_models(&self, ctx: &ProviderContext<'_>) -> Result<Vec<ModelInfo>, LlmError> {
+        if self.protocol_type() != ProtocolType::OpenAiCompatible {
+            return Err(LlmError::Other(format!(
+                "Provider '{}' does not support model discovery",
+                self.id()
+            )));
+        }
+
+        let base_url = self.effective_base_url(ctx).await?;
+        let url = format!(
+            "{}/models",
+            normalize_provider_base_url(&base_url, ctx.provider_config)
+        );
+        let credentials = self.get_credentials(ctx.api_key_manager).await?;
+        let headers = self.build_headers(ctx, &credentials).await?;
+        let body = fetch_discovery_body(&url, &headers).await?;
+        parse_models_response(&body)
+    }
+
+    /// Prompt tokens `ctx.messages` would cost on `ctx.model`, counted before anything is sent
+    /// Uses the provider's counting endpoint where it has one (Anthropic, Gemini) and the local
+    /// estimate otherwise, or when that endpoint fails
+    async fn count_tokens(&self, ctx: &ProviderContext<'_>) -> Result<usize, String> {
+        if !ctx.dry_run {
+            match count_tokens_remote(self, ctx).await {
+                Ok(Some(count)) => return Ok(count),
+                Ok(None) => {}
+                Err(e) => log::warn!(
+                    "[Provider {}] Token count request failed, estimating locally: {}",
+                    self.id(),
+                    e
+                ),
+            }
+        }
+        let family = tokenize::TokenizerFamily::from(self.protocol_type());
+        let count =
+            tokenize::count_message_tokens(tokenize::estimator().as_ref(), family, ctx.messages);
+        Ok(count as usize)
+    }
+
+    /// Verify the configured credentials with a minimal request
+    /// Default lists models on OpenAI-compatible providers, otherwise sends a one-token completion
+    async fn health_check(&self, ctx: &ProviderContext<'_>) -> Result<HealthStatus, String> {
+        let started = Instant::now();
+        let result = if self.protocol_type() == ProtocolType::OpenAiCompatible {
+            self.list_models(ctx).await.map(|_| ())
+        } else {
+            probe_completion(self, ctx).await
+        };
+        HealthStatus::from_probe(result, started)
+    }
+
     /// Check if this provider uses OAuth
     fn uses_oauth(&self) -> bool {
         self.config().supports_oauth
     }
 
+    /// Features the provider supports; defaults to streaming chat with tools
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities::chat()
+    }
+
     /// Build the complete request
     async fn build_complete_request(
         &self,
         ctx: &ProviderContext<'_>,
-    ) -> Result<BuiltRequest, String> {
-        let base_url = self.resolve_base_url(ctx).await?;
+    ) -> Result<BuiltRequest, LlmError> {
+        let ctx = &ProviderContext {
+            model: BaseProvider::resolve_model(ctx.provider_config, ctx.model),
+            ..ctx.clone()
+        };
+        let base_url = self.effective_base_url(ctx).await?;
         let endpoint_path = self.resolve_endpoint_path(ctx).await;
         let normalized_base_url = normalize_provider_base_url(&base_url, ctx.provider_config);
         let credentials = self.get_credentials(ctx.api_key_manager).await?;
-        let headers = self.build_headers(ctx, &credentials).await?;
-        let body = self.build_request(ctx).await?;
+        let mut headers = self.build_headers(ctx, &credentials).await?;
+        let mut body = self.build_request(ctx).await?;
+        // Capable providers get images as raw bytes, everyone else keeps base64 data URLs
+        let attachments = if self.capabilities().supports_multipart_images {
+            extract_image_attachments(&mut body)
+        } else {
+            Vec::new()
+        };
+        check_request_size(ctx.provider_config, &body, &attachments)?;
 
-        let url = format!(
+        // A request ID set through the provider's config headers is kept as the client ID
+        let mut request_ids = RequestIds::generate();
+        let request_id_header = request_id::header_name()
+            .filter(|name| HeaderPolicy::current(ctx.strip_headers).allows(name));
+        if let Some(name) = request_id_header {
+            match headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(&name))
+            {
+                Some((_, value)) => request_ids.client = value.clone(),
+                None => {
+                    headers.insert(name, request_ids.client.clone());
+                }
+            }
+        }
+
+        let mut url = format!(
             "{}/{}",
             normalized_base_url.trim_end_matches('/'),
             endpoint_path
         );
 
-        Ok(BuiltRequest { url, headers, body })
+        // Gemini takes API keys as a query parameter rather than a header
+        if self.protocol_type() == ProtocolType::Gemini {
+            if let ProviderCredentials::ApiKey(key) | ProviderCredentials::Token(key) = &credentials
+            {
+                url = GeminiProtocol::with_api_key(&url, key);
+            }
+        }
+
+        let api_key = match &credentials {
+            ProviderCredentials::ApiKey(key) | ProviderCredentials::Token(key) => Some(key.clone()),
+            ProviderCredentials::None | ProviderCredentials::OAuth { .. } => None,
+        };
+
+        Ok(BuiltRequest {
+            url,
+            headers,
+            body,
+            api_key,
+            // Completions have no side effects until output is delivered, unless the caller says so
+            idempotent: ctx.idempotent,
+            request_ids,
+            attachments,
+        })
+    }
+}
+
+/// Placeholders a `ProviderConfig::headers` value may reference
+const API_KEY_PLACEHOLDER: &str = "{{apiKey}}";
+const BASE_URL_PLACEHOLDER: &str = "{{baseUrl}}";
+
+const USER_AGENT_HEADER: &str = "User-Agent";
+
+/// `OpenAI-Organization` and `OpenAI-Project` for the configured account, replacing any spelling
+/// of them in the config headers; blank values send nothing
+fn set_openai_account_headers(headers: &mut HashMap<String, String>, config: &ProviderConfig) {
+    for (name, value) in [
+        ("OpenAI-Organization", &config.organization),
+        ("OpenAI-Project", &config.project),
+    ] {
+        let Some(value) = value.as_deref().map(str::trim).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        headers.retain(|existing, _| !existing.eq_ignore_ascii_case(name));
+        headers.insert(name.to_string(), value.to_string());
+    }
+}
+
+/// Add config headers that the protocol did not already set (names compare case-insensitively)
+/// `{{apiKey}}` and `{{baseUrl}}` in a value are replaced; a header whose placeholder
+/// has no value is skipped rather than sent half-filled
+fn merge_config_headers(
+    headers: &mut HashMap<String, String>,
+    config_headers: &HashMap<String, String>,
+    api_key: Option<&str>,
+    base_url: Option<&str>,
+) {
+    for (name, value) in config_headers {
+        if headers
+            .keys()
+            .any(|existing| existing.eq_ignore_ascii_case(name))
+        {
+            continue;
+        }
+        let mut value = value.clone();
+        for (placeholder, replacement) in [
+            (API_KEY_PLACEHOLDER, api_key),
+            (BASE_URL_PLACEHOLDER, base_url),
+        ] {
+            if value.contains(placeholder) {
+                let Some(replacement) = replacement else {
+                    log::warn!(
+                        "Skipping header {}: {} is not available for this request",
+                        name,
+                        placeholder
+                    );
+                    value.clear();
+                    break;
+                };
+                value = value.replace(placeholder, replacement);
+            }
+        }
+        if !value.is_empty() {
+            headers.insert(name.clone(), value);
+        }
     }
 }
 
@@ -246,6 +918,279 @@ fn normalize_provider_base_url(base_url: &str, provider_config: &ProviderConfig)
     format!("{}/v1", without_endpoint.trim_end_matches('/'))
 }
 
+/// Headers a request cannot authenticate or be parsed without, never removed by a policy
+const PROTECTED_HEADERS: &[&str] = &[
+    "authorization",
+    "x-api-key",
+    "api-key",
+    "x-goog-api-key",
+    "content-type",
+];
+
+/// Outgoing headers allowed by the global lists in `HttpSettings` and a request's own strips
+struct HeaderPolicy<'a> {
+    denylist: Vec<String>,
+    allowlist: Vec<String>,
+    strip_headers: &'a [String],
+}
+
+impl<'a> HeaderPolicy<'a> {
+    fn current(strip_headers: &'a [String]) -> Self {
+        let settings = http_client::http_settings();
+        Self {
+            denylist: settings.header_denylist,
+            allowlist: settings.header_allowlist,
+            strip_headers,
+        }
+    }
+
+    fn allows(&self, name: &str) -> bool {
+        let listed = |list: &[String]| {
+            list.iter()
+                .any(|entry| entry.trim().eq_ignore_ascii_case(name))
+        };
+        if PROTECTED_HEADERS
+            .iter()
+            .any(|protected| protected.eq_ignore_ascii_case(name))
+        {
+            return true;
+        }
+        if listed(&self.denylist) || listed(self.strip_headers) {
+            return false;
+        }
+        self.allowlist.is_empty() || listed(&self.allowlist)
+    }
+
+    fn apply(&self, headers: &mut HashMap<String, String>) {
+        headers.retain(|name, _| {
+            let allowed = self.allows(name);
+            if !allowed {
+                log::debug!("Removing header {} from the provider request", name);
+            }
+            allowed
+        });
+    }
+}
+
+/// Keep a User-Agent set through the config headers, otherwise send the global one
+/// The header is stored under its canonical name so `add_provider_headers` replaces it
+fn set_user_agent(headers: &mut HashMap<String, String>) {
+    let configured = headers
+        .keys()
+        .find(|name| name.eq_ignore_ascii_case(USER_AGENT_HEADER))
+        .cloned();
+    let user_agent = configured
+        .and_then(|name| headers.remove(&name))
+        .unwrap_or_else(|| http_client::http_settings().user_agent().to_string());
+    headers.insert(USER_AGENT_HEADER.to_string(), user_agent);
+}
+
+/// GET a model discovery endpoint and return the response body
+pub(crate) async fn fetch_discovery_body(
+    url: &str,
+    headers: &HashMap<String, String>,
+) -> Result<String, LlmError> {
+    let client = crate::llm::http_client::shared_client().map_err(LlmError::Other)?;
+
+    let mut request = client.get(url).timeout(Duration::from_secs(30));
+    for (key, value) in headers {
+        request = request.header(key, value);
+    }
+
+    let response = request.send_with_middleware().await?;
+    let status = response.status().as_u16();
+    let response_headers = response.headers().clone();
+    let text = response.text().await?;
+    if status >= 400 {
+        return Err(LlmError::from_response_parts(
+            status,
+            &response_headers,
+            text,
+        ));
+    }
+    Ok(text)
+}
+
+/// Send a one-token completion for `ctx.model` and discard the reply
+pub(crate) async fn probe_completion<P: Provider + ?Sized>(
+    provider: &P,
+    ctx: &ProviderContext<'_>,
+) -> Result<(), LlmError> {
+    let messages = [Message::User {
+        content: MessageContent::Text("ping".to_string()),
+        provider_options: None,
+        cache: false,
+    }];
+    let probe_ctx = ProviderContext {
+        messages: &messages,
+        tools: None,
+        max_tokens: Some(1),
+        ..ctx.clone()
+    };
+    let request = provider.build_complete_request(&probe_ctx).await?;
+
+    let client = crate::llm::http_client::shared_client().map_err(LlmError::Other)?;
+    let builder = request.post(&client).timeout(HEALTH_CHECK_TIMEOUT);
+    let response = builder.send_with_middleware().await?;
+    let status = response.status().as_u16();
+    if status >= 400 {
+        let response_headers = response.headers().clone();
+        let text = response.text().await.unwrap_or_default();
+        return Err(LlmError::from_response_parts(
+            status,
+            &response_headers,
+            text,
+        ));
+    }
+    Ok(())
+}
+
+/// Count `ctx.messages` with the provider's endpoint, or `None` when its protocol has none
+pub(crate) async fn count_tokens_remote<P: Provider + ?Sized>(
+    provider: &P,
+    ctx: &ProviderContext<'_>,
+) -> Result<Option<usize>, LlmError> {
+    let protocol = provider.protocol_type();
+    if !matches!(
+        protocol,
+        ProtocolType::Claude | ProtocolType::Anthropic | ProtocolType::Gemini
+    ) {
+        return Ok(None);
+    }
+    let ctx = &ProviderContext {
+        stream: false,
+        ..ctx.clone()
+    };
+    let request = provider.build_complete_request(ctx).await?;
+    let Some((url, body)) = count_tokens_request(protocol, &request, ctx) else {
+        return Ok(None);
+    };
+    // Same headers as the completion; counting has no side effects, so it is always retried
+    let request = BuiltRequest {
+        url,
+        body,
+        idempotent: true,
+        attachments: Vec::new(),
+        ..request
+    };
+
+    let client = crate::llm::http_client::shared_client().map_err(LlmError::Other)?;
+    let builder = request.post(&client).timeout(COUNT_TOKENS_TIMEOUT);
+    let policy = RetryPolicy::for_provider(provider.config());
+    let response = cancellable(
+        ctx.cancel_token,
+        policy.send_within(builder, ctx.retry_budget),
+    )
+    .await??;
+    let status = response.status().as_u16();
+    let response_headers = response.headers().clone();
+    let text = response.text().await.unwrap_or_default();
+    if status >= 400 {
+        if status == 429 {
+            if let Some(api_key) = &request.api_key {
+                ctx.api_key_manager.mark_key_rate_limited(api_key);
+            }
+        }
+        return Err(LlmError::from_response_parts(
+            status,
+            &response_headers,
+            text,
+        ));
+    }
+    let payload: Value = serde_json::from_str(&text).map_err(|e| {
+        LlmError::InvalidResponse(format!("Failed to parse token count response: {}", e))
+    })?;
+    let key = if protocol == ProtocolType::Gemini {
+        "totalTokens"
+    } else {
+        "input_tokens"
+    };
+    payload
+        .get(key)
+        .and_then(Value::as_u64)
+        .map(|count| Some(count as usize))
+        .ok_or_else(|| LlmError::InvalidResponse(format!("Token count response has no '{}'", key)))
+}
+
+/// URL and body of the counting request matching a built completion request
+/// `None` when the completion URL does not have the protocol's standard shape
+fn count_tokens_request(
+    protocol: ProtocolType,
+    request: &BuiltRequest,
+    ctx: &ProviderContext<'_>,
+) -> Option<(String, Value)> {
+    match protocol {
+        ProtocolType::Claude | ProtocolType::Anthropic => {
+            let (path, query) = split_query(&request.url);
+            if !path.ends_with("/messages") {
+                return None;
+            }
+            let mut body = serde_json::Map::new();
+            for field in ANTHROPIC_COUNT_FIELDS {
+                if let Some(value) = request.body.get(*field) {
+                    body.insert(field.to_string(), value.clone());
+                }
+            }
+            Some((
+                format!("{}/count_tokens{}", path, query),
+                Value::Object(body),
+            ))
+        }
+        ProtocolType::Gemini => {
+            let (path, query) = split_query(&request.url);
+            let base = path.strip_suffix(":generateContent")?;
+            // countTokens takes the whole generate request, which must name its model
+            let model = BaseProvider::resolve_model(ctx.provider_config, ctx.model);
+            let mut generate_request = request.body.clone();
+            generate_request["model"] =
+                Value::String(format!("models/{}", model.trim_start_matches("models/")));
+            Some((
+                format!("{}:countTokens{}", base, query),
+                serde_json::json!({ "generateContentRequest": generate_request }),
+            ))
+        }
+        ProtocolType::OpenAiCompatible | ProtocolType::Cohere => None,
+    }
+}
+
+/// `url` split before its query string, which stays with the second half
+fn split_query(url: &str) -> (&str, &str) {
+    url.split_at(url.find('?').unwrap_or(url.len()))
+}
+
+/// Parse an OpenAI-style `/models` response (`{"data": [{"id": ..., "created": ...}]}`)
+pub fn parse_models_response(body: &str) -> Result<Vec<ModelInfo>, LlmError> {
+    let payload: Value = serde_json::from_str(body).map_err(|e| {
+        LlmError::InvalidResponse(format!("Failed to parse model list response: {}", e))
+    })?;
+    let data = payload
+        .get("data")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| {
+            LlmError::InvalidResponse("Model list response is missing the 'data' array".to_string())
+        })?;
+
+    Ok(data
+        .iter()
+        .filter_map(|entry| {
+            let id = entry.get("id").and_then(|v| v.as_str())?;
+            Some(ModelInfo {
+                id: id.to_string(),
+                created: entry.get("created").and_then(|v| v.as_i64()).unwrap_or(0),
+                owned_by: entry
+                    .get("owned_by")
+                    .and_then(|v| v.as_str())
+                    .map(|v| v.to_string()),
+                // Groq reports `context_window`, OpenRouter and others `context_length`
+                context_window: ["context_window", "context_length"]
+                    .iter()
+                    .find_map(|key| entry.get(*key).and_then(|v| v.as_u64()))
+                    .and_then(|tokens| u32::try_from(tokens).ok()),
+            })
+        })
+        .collect())
+}
+
 fn is_custom_provider_id(provider_id: &str) -> bool {
     provider_id.starts_with("openai-compatible-") || provider_id.starts_with("anthropic-")
 }
@@ -257,22 +1202,84 @@ fn has_v1_segment(base_url: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+
+    #[test]
+    fn fit_response_format_downgrades_or_rejects_by_capability() {
+        use crate::llm::providers::DefaultProvider;
+
+        let schema = ResponseFormat::JsonSchema {
+            name: "answer".to_string(),
+            schema: serde_json::json!({ "type": "object" }),
+            strict: None,
+        };
+        let deepseek = DefaultProvider::new(custom_provider_config(
+            "deepseek",
+            ProtocolType::OpenAiCompatible,
+        ));
+        let open_router = DefaultProvider::new(custom_provider_config(
+            "openRouter",
+            ProtocolType::OpenAiCompatible,
+        ));
+        let anthropic =
+            DefaultProvider::new(custom_provider_config("anthropic", ProtocolType::Anthropic));
+
+        assert_eq!(
+            fit_response_format(&deepseek, Some(&schema)),
+            Ok(Some(&ResponseFormat::JsonObject))
+        );
+        assert_eq!(
+            fit_response_format(&open_router, Some(&schema)),
+            Ok(Some(&schema))
+        );
+        assert_eq!(fit_response_format(&anthropic, None), Ok(None));
+        let err = fit_response_format(&anthropic, Some(&ResponseFormat::JsonObject))
+            .expect_err("unsupported protocol");
+        assert!(err
+            .to_string()
+            .contains("does not support JSON response formats"));
+    }
+
+    #[test]
+    fn fit_logprobs_drops_the_request_on_providers_without_them() {
+        use crate::llm::providers::DefaultProvider;
+
+        let open_router = DefaultProvider::new(custom_provider_config(
+            "openRouter",
+            ProtocolType::OpenAiCompatible,
+        ));
+        let zhipu = DefaultProvider::new(custom_provider_config(
+            "zhipu",
+            ProtocolType::OpenAiCompatible,
+        ));
+
+        assert!(fit_logprobs(&open_router, true));
+        assert!(!fit_logprobs(&zhipu, true));
+        assert!(!fit_logprobs(&open_router, false));
+    }
+
+    #[test]
+    fn fit_builtin_tools_rejects_tools_the_provider_lacks() {
+        use crate::llm::providers::DefaultProvider;
+
+        let deepseek = DefaultProvider::new(custom_provider_config(
+            "deepseek",
+            ProtocolType::OpenAiCompatible,
+        ));
+        let anthropic =
+            DefaultProvider::new(custom_provider_config("anthropic", ProtocolType::Anthropic));
+
+        assert_eq!(fit_builtin_tools(&deepseek, &[]), Ok(&[][..]));
+        let err = fit_builtin_tools(&deepseek, &[BuiltinTool::WebSearch]).expect_err("no search");
+        assert!(err.to_string().contains("built-in web search"), "{}", err);
+        assert!(fit_builtin_tools(&anthropic, &[BuiltinTool::WebSearch]).is_ok());
+        assert!(fit_builtin_tools(&anthropic, &[BuiltinTool::CodeInterpreter]).is_err());
+    }
 
     fn custom_provider_config(id: &str, protocol: ProtocolType) -> ProviderConfig {
         ProviderConfig {
-            id: id.to_string(),
-            name: "Custom".to_string(),
-            protocol,
-            base_url: "https://api.example.com/v1".to_string(),
             api_key_name: "custom_test".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(id, protocol, "https://api.example.com/v1")
         }
     }
 
@@ -316,6 +1323,45 @@ mod tests {
         assert_eq!(normalized, "https://api.example.com/v1");
     }
 
+    #[test]
+    fn parse_models_response_reads_data_array() {
+        let body = r#"{
+            "object": "list",
+            "data": [
+                {"id": "gpt-4o", "object": "model", "created": 1715367049, "owned_by": "system"},
+                {"id": "local-model", "object": "model", "context_window": 131072},
+                {"object": "model", "created": 1}
+            ]
+        }"#;
+
+        let models = parse_models_response(body).expect("parse models");
+
+        assert_eq!(
+            models,
+            vec![
+                ModelInfo {
+                    id: "gpt-4o".to_string(),
+                    created: 1715367049,
+                    owned_by: Some("system".to_string()),
+                    context_window: None,
+                },
+                ModelInfo {
+                    id: "local-model".to_string(),
+                    created: 0,
+                    owned_by: None,
+                    context_window: Some(131072),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_models_response_rejects_missing_data() {
+        let err = parse_models_response(r#"{"error": "nope"}"#).unwrap_err();
+        assert!(matches!(err, LlmError::InvalidResponse(_)));
+        assert!(err.to_string().contains("'data'"));
+    }
+
     #[test]
     fn normalize_non_custom_provider_base_url_is_unchanged() {
         let mut config = custom_provider_config("openai", ProtocolType::OpenAiCompatible);
@@ -323,6 +1369,985 @@ mod tests {
         let normalized = normalize_provider_base_url("https://api.openai.com/v1", &config);
         assert_eq!(normalized, "https://api.openai.com/v1");
     }
+
+    #[tokio::test]
+    async fn build_headers_merges_config_headers_under_protocol_and_provider_headers() {
+        use crate::llm::providers::KimiCodingProvider;
+
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let config = ProviderConfig {
+            headers: Some(HashMap::from([
+                ("authorization".to_string(), "Bearer wrong".to_string()),
+                ("User-Agent".to_string(), "config-agent".to_string()),
+                ("X-Team".to_string(), "core".to_string()),
+                ("X-Upstream-Key".to_string(), "key={{apiKey}}".to_string()),
+                ("X-Upstream".to_string(), "{{baseUrl}}".to_string()),
+            ])),
+            ..custom_provider_config("kimi_coding", ProtocolType::OpenAiCompatible)
+        };
+        let provider = KimiCodingProvider::new(config.clone());
+        let ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "kimi-k2.5",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+        let base_url = provider.resolve_base_url(&ctx).await.expect("base url");
+
+        let headers = provider
+            .build_headers(&ctx, &ProviderCredentials::ApiKey("sk-test".to_string()))
+            .await
+            .expect("headers");
+
+        let header = |name: &str| headers.get(name).map(String::as_str);
+        assert_eq!(header("Authorization"), Some("Bearer sk-test"));
+        assert!(!headers.contains_key("authorization"));
+        assert_eq!(header("User-Agent"), Some("KimiCLI/1.3"));
+        assert_eq!(header("X-Team"), Some("core"));
+        assert_eq!(header("X-Upstream-Key"), Some("key=sk-test"));
+        assert_eq!(header("X-Upstream"), Some(base_url.as_str()));
+    }
+
+    #[tokio::test]
+    async fn build_headers_strips_requested_headers_but_keeps_auth() {
+        use crate::llm::providers::DefaultProvider;
+
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let config = ProviderConfig {
+            headers: Some(HashMap::from([
+                ("X-Telemetry-Id".to_string(), "device-7".to_string()),
+                ("X-Team".to_string(), "core".to_string()),
+            ])),
+            ..custom_provider_config("gateway", ProtocolType::OpenAiCompatible)
+        };
+        let provider = DefaultProvider::new(config.clone());
+        let strip_headers = ["x-telemetry-id".to_string(), "Authorization".to_string()];
+        let ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "model",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &strip_headers,
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+
+        let headers = provider
+            .build_headers(&ctx, &ProviderCredentials::ApiKey("sk-test".to_string()))
+            .await
+            .expect("headers");
+
+        assert!(!headers.contains_key("X-Telemetry-Id"));
+        assert_eq!(headers.get("X-Team").map(String::as_str), Some("core"));
+        assert_eq!(
+            headers.get("Authorization").map(String::as_str),
+            Some("Bearer sk-test")
+        );
+    }
+
+    #[tokio::test]
+    async fn build_headers_sends_openai_account_headers_only_when_configured() {
+        use crate::llm::providers::DefaultProvider;
+
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let unset = custom_provider_config("gateway", ProtocolType::OpenAiCompatible);
+        let configured = ProviderConfig {
+            organization: Some("org-billing".to_string()),
+            project: Some(" proj_web ".to_string()),
+            headers: Some(HashMap::from([(
+                "openai-organization".to_string(),
+                "org-stale".to_string(),
+            )])),
+            ..unset.clone()
+        };
+
+        let mut sent = Vec::new();
+        for config in [&unset, &configured] {
+            let ctx = ProviderContext {
+                provider_config: config,
+                api_key_manager: &api_keys,
+                model: "model",
+                messages: &[],
+                tools: None,
+                temperature: None,
+                max_tokens: None,
+                top_p: None,
+                top_k: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: None,
+                response_format: None,
+                user_id: None,
+                base_url_override: None,
+                provider_options: None,
+                trace_context: None,
+                cancel_token: None,
+                stream: true,
+                logprobs: false,
+                top_logprobs: None,
+                dry_run: false,
+                builtin_tools: &[],
+                strip_headers: &[],
+                retry_budget: None,
+                reasoning_effort: None,
+                idempotent: true,
+            };
+            let headers = DefaultProvider::new(config.clone())
+                .build_headers(&ctx, &ProviderCredentials::ApiKey("sk-test".to_string()))
+                .await
+                .expect("headers");
+            sent.push(headers);
+        }
+
+        let account_headers = |headers: &HashMap<String, String>| {
+            let mut names: Vec<String> = headers
+                .keys()
+                .filter(|name| name.to_ascii_lowercase().starts_with("openai-"))
+                .cloned()
+                .collect();
+            names.sort();
+            names
+        };
+        assert!(account_headers(&sent[0]).is_empty(), "{:?}", sent[0]);
+        assert_eq!(
+            account_headers(&sent[1]),
+            vec!["OpenAI-Organization", "OpenAI-Project"]
+        );
+        assert_eq!(sent[1]["OpenAI-Organization"], "org-billing");
+        assert_eq!(sent[1]["OpenAI-Project"], "proj_web");
+    }
+
+    #[test]
+    fn header_allowlist_keeps_only_listed_and_protected_headers() {
+        let policy = HeaderPolicy {
+            denylist: vec!["X-Trace".to_string()],
+            allowlist: vec![" x-team ".to_string(), "x-trace".to_string()],
+            strip_headers: &[],
+        };
+        let mut headers = HashMap::from([
+            ("Authorization".to_string(), "Bearer sk-test".to_string()),
+            ("Content-Type".to_string(), "application/json".to_string()),
+            ("X-Team".to_string(), "core".to_string()),
+            ("X-Trace".to_string(), "abc".to_string()),
+            ("User-Agent".to_string(), "talkcody".to_string()),
+        ]);
+
+        policy.apply(&mut headers);
+
+        let mut names: Vec<_> = headers.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        assert_eq!(names, ["Authorization", "Content-Type", "X-Team"]);
+    }
+
+    /// Every User-Agent value `provider` sends, whatever the header's case
+    async fn user_agents(provider: &dyn Provider, api_keys: &ApiKeyManager) -> Vec<String> {
+        let ctx = ProviderContext {
+            provider_config: provider.config(),
+            api_key_manager: api_keys,
+            model: "model",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+        let headers = provider
+            .build_headers(&ctx, &ProviderCredentials::ApiKey("sk-test".to_string()))
+            .await
+            .expect("headers");
+        headers
+            .into_iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(USER_AGENT_HEADER))
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn build_headers_send_default_user_agent_unless_overridden() {
+        use crate::llm::providers::{DefaultProvider, KimiCodingProvider};
+
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let config_agent = || {
+            Some(HashMap::from([(
+                "user-agent".to_string(),
+                "gateway-client/2.0".to_string(),
+            )]))
+        };
+
+        let provider = DefaultProvider::new(custom_provider_config(
+            "gateway",
+            ProtocolType::OpenAiCompatible,
+        ));
+        assert!(http_client::DEFAULT_USER_AGENT.starts_with("talkcody/"));
+        assert_eq!(
+            user_agents(&provider, &api_keys).await,
+            vec![http_client::DEFAULT_USER_AGENT]
+        );
+
+        let provider = DefaultProvider::new(ProviderConfig {
+            headers: config_agent(),
+            ..custom_provider_config("gateway", ProtocolType::OpenAiCompatible)
+        });
+        assert_eq!(
+            user_agents(&provider, &api_keys).await,
+            vec!["gateway-client/2.0"]
+        );
+
+        // Kimi's coding endpoint only accepts its CLI, whatever the config says
+        let provider = KimiCodingProvider::new(ProviderConfig {
+            headers: config_agent(),
+            ..custom_provider_config("kimi_coding", ProtocolType::OpenAiCompatible)
+        });
+        assert_eq!(user_agents(&provider, &api_keys).await, vec!["KimiCLI/1.3"]);
+    }
+
+    #[test]
+    fn merge_config_headers_skips_unresolved_placeholders() {
+        let mut headers = HashMap::new();
+        let config_headers =
+            HashMap::from([("X-Upstream-Key".to_string(), "{{apiKey}}".to_string())]);
+
+        merge_config_headers(&mut headers, &config_headers, None, None);
+
+        assert!(headers.is_empty());
+    }
+
+    fn multi_endpoint_config() -> ProviderConfig {
+        ProviderConfig {
+            supports_coding_plan: true,
+            supports_international: true,
+            coding_plan_base_url: Some("https://coding.example.com/v1".to_string()),
+            international_base_url: Some("https://intl.example.com/v1".to_string()),
+            ..custom_provider_config("multi", ProtocolType::OpenAiCompatible)
+        }
+    }
+
+    #[tokio::test]
+    async fn resolve_base_url_uses_coding_plan_url_when_enabled() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("use_coding_plan_multi", "true")
+            .await
+            .expect("set coding plan");
+        api_keys
+            .set_setting("use_international_multi", "true")
+            .await
+            .expect("set international");
+
+        let base_url = BaseProvider::new(multi_endpoint_config())
+            .resolve_base_url_with_fallback(&api_keys)
+            .await
+            .expect("base url");
+
+        assert_eq!(base_url, "https://coding.example.com/v1");
+    }
+
+    #[tokio::test]
+    async fn resolve_base_url_uses_international_url_when_enabled() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("use_coding_plan_multi", "false")
+            .await
+            .expect("set coding plan");
+        api_keys
+            .set_setting("use_international_multi", "true")
+            .await
+            .expect("set international");
+
+        let base_url = BaseProvider::new(multi_endpoint_config())
+            .resolve_base_url_with_fallback(&api_keys)
+            .await
+            .expect("base url");
+
+        assert_eq!(base_url, "https://intl.example.com/v1");
+    }
+
+    #[tokio::test]
+    async fn base_url_override_takes_precedence_over_config_resolution() {
+        use crate::llm::providers::DefaultProvider;
+
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("base_url_multi", "https://stored.example.com/v1")
+            .await
+            .expect("set base url");
+        api_keys
+            .set_setting("api_key_multi", "sk-test")
+            .await
+            .expect("set api key");
+        let config = multi_endpoint_config();
+        let provider = DefaultProvider::new(config.clone());
+        let mut ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "gpt-4o",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: Some(" https://mirror.example.com/v1/ "),
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+
+        let request = provider
+            .build_complete_request(&ctx)
+            .await
+            .expect("request");
+        assert_eq!(
+            request.url,
+            "https://mirror.example.com/v1/chat/completions"
+        );
+
+        // A blank override falls back to the stored configuration
+        ctx.base_url_override = Some("  ");
+        let base_url = provider.effective_base_url(&ctx).await.expect("base url");
+        assert_eq!(base_url, "https://stored.example.com/v1");
+    }
+
+    #[test]
+    fn resolve_model_maps_aliases_and_falls_back_to_default() {
+        let mut config = custom_provider_config("aliased", ProtocolType::OpenAiCompatible);
+        config.model_aliases = HashMap::from([
+            ("llama".to_string(), "llama-3.3-70b-versatile".to_string()),
+            ("fast".to_string(), "gpt-4o-mini".to_string()),
+        ]);
+
+        assert_eq!(
+            BaseProvider::resolve_model(&config, "llama"),
+            "llama-3.3-70b-versatile"
+        );
+        assert_eq!(BaseProvider::resolve_model(&config, "gpt-4o"), "gpt-4o");
+        // Without a default an empty model is passed through for the provider to reject
+        assert_eq!(BaseProvider::resolve_model(&config, ""), "");
+
+        config.default_model = Some("fast".to_string());
+        assert_eq!(BaseProvider::resolve_model(&config, " "), "gpt-4o-mini");
+        assert_eq!(BaseProvider::resolve_model(&config, "gpt-4o"), "gpt-4o");
+    }
+
+    #[tokio::test]
+    async fn build_complete_request_sends_resolved_model() {
+        use crate::llm::providers::DefaultProvider;
+
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("api_key_aliased", "sk-test")
+            .await
+            .expect("set api key");
+        let mut config = custom_provider_config("aliased", ProtocolType::OpenAiCompatible);
+        config.model_aliases = HashMap::from([("fast".to_string(), "gpt-4o-mini".to_string())]);
+        config.default_model = Some("gpt-4o".to_string());
+        let provider = DefaultProvider::new(config.clone());
+        let mut ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "fast",
+            messages: &[],
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+
+        let request = provider
+            .build_complete_request(&ctx)
+            .await
+            .expect("request");
+        assert_eq!(request.body["model"], "gpt-4o-mini");
+
+        ctx.model = "";
+        let request = provider
+            .build_complete_request(&ctx)
+            .await
+            .expect("request");
+        assert_eq!(request.body["model"], "gpt-4o");
+    }
+
+    #[tokio::test]
+    async fn complete_turns_streaming_off_and_returns_the_reply() {
+        use crate::llm::providers::DefaultProvider;
+        use crate::llm::testing::mock_server::start_capture_server;
+
+        let reply = serde_json::json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": "Four." },
+                "finish_reason": "stop"
+            }],
+            "usage": { "prompt_tokens": 9, "completion_tokens": 2, "total_tokens": 11 }
+        });
+        let (base_url, captured) =
+            start_capture_server(200, reply.to_string().into_bytes()).expect("server");
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("api_key_blocking", "sk-test")
+            .await
+            .expect("set api key");
+        let mut config = custom_provider_config("blocking", ProtocolType::OpenAiCompatible);
+        config.base_url = format!("{}/v1", base_url);
+        let provider = DefaultProvider::new(config.clone());
+        let messages = [Message::User {
+            content: MessageContent::Text("2 + 2?".to_string()),
+            provider_options: None,
+            cache: false,
+        }];
+        let ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "gpt-4o-mini",
+            messages: &messages,
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+
+        let result = provider.complete(&ctx).await.expect("completion");
+
+        assert_eq!(result.text, "Four.");
+        assert_eq!(result.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(result.usage.map(|usage| usage.output_tokens), Some(2));
+        let sent = captured.recv().expect("captured request");
+        let body: Value = serde_json::from_slice(&sent.body).expect("json body");
+        assert_eq!(body["stream"], false);
+        assert!(body.get("stream_options").is_none());
+        assert!(sent.url.ends_with("/chat/completions"), "{}", sent.url);
+    }
+
+    #[tokio::test]
+    async fn multipart_images_are_used_only_by_capable_providers() {
+        use crate::llm::providers::DefaultProvider;
+        use crate::llm::testing::mock_server::start_capture_server;
+        use crate::llm::types::ContentPart;
+
+        let reply = serde_json::json!({
+            "choices": [{ "message": { "content": "A cat." }, "finish_reason": "stop" }]
+        });
+        let (base_url, captured) =
+            start_capture_server(200, reply.to_string().into_bytes()).expect("server");
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("api_key_vision", "sk-test")
+            .await
+            .expect("set api key");
+        let base64_config = ProviderConfig {
+            base_url: format!("{}/v1", base_url),
+            ..custom_provider_config("vision", ProtocolType::OpenAiCompatible)
+        };
+        let multipart_config = ProviderConfig {
+            multipart_images: true,
+            ..base64_config.clone()
+        };
+        let messages = [Message::User {
+            content: MessageContent::Parts(vec![
+                ContentPart::Text {
+                    text: "What is this?".to_string(),
+                },
+                ContentPart::Image {
+                    image: "iVBORw0KGgo=".to_string(),
+                    mime_type: Some("image/png".to_string()),
+                },
+            ]),
+            provider_options: None,
+            cache: false,
+        }];
+
+        let mut requests = Vec::new();
+        for config in [&base64_config, &multipart_config] {
+            let ctx = ProviderContext {
+                provider_config: config,
+                api_key_manager: &api_keys,
+                model: "gpt-4o-mini",
+                messages: &messages,
+                tools: None,
+                temperature: None,
+                max_tokens: None,
+                top_p: None,
+                top_k: None,
+                frequency_penalty: None,
+                presence_penalty: None,
+                stop: None,
+                response_format: None,
+                user_id: None,
+                base_url_override: None,
+                provider_options: None,
+                trace_context: None,
+                cancel_token: None,
+                stream: false,
+                logprobs: false,
+                top_logprobs: None,
+                dry_run: false,
+                builtin_tools: &[],
+                strip_headers: &[],
+                retry_budget: None,
+                reasoning_effort: None,
+                idempotent: true,
+            };
+            let provider = DefaultProvider::new(config.clone());
+            let request = provider
+                .build_complete_request(&ctx)
+                .await
+                .expect("request");
+            requests.push(request);
+            if config.multipart_images {
+                provider.complete(&ctx).await.expect("completion");
+            }
+        }
+
+        let image_url = |request: &BuiltRequest| {
+            request.body["messages"][0]["content"][1]["image_url"]["url"].clone()
+        };
+        assert!(requests[0].attachments.is_empty());
+        assert_eq!(
+            image_url(&requests[0]),
+            "data:image/png;base64,iVBORw0KGgo="
+        );
+        assert_eq!(requests[1].attachments.len(), 1);
+        assert_eq!(image_url(&requests[1]), "attachment://image_0");
+
+        let sent = captured.recv().expect("captured request");
+        let content_type = sent.content_type.unwrap_or_default();
+        assert!(content_type.starts_with("multipart/form-data; boundary="));
+        let png_magic = b"\x89PNG\r\n\x1a\n";
+        assert!(sent.body.windows(png_magic.len()).any(|w| w == png_magic));
+    }
+
+    fn count_ctx<'a>(
+        config: &'a ProviderConfig,
+        api_keys: &'a ApiKeyManager,
+        messages: &'a [Message],
+    ) -> ProviderContext<'a> {
+        ProviderContext {
+            provider_config: config,
+            api_key_manager: api_keys,
+            model: "claude-sonnet-4-5",
+            messages,
+            tools: None,
+            temperature: None,
+            max_tokens: Some(1024),
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        }
+    }
+
+    fn count_messages() -> Vec<Message> {
+        vec![
+            Message::System {
+                content: "You are terse.".to_string(),
+                provider_options: None,
+                cache: false,
+            },
+            Message::User {
+                content: MessageContent::Text("How many tokens is this?".to_string()),
+                provider_options: None,
+                cache: false,
+            },
+        ]
+    }
+
+    #[tokio::test]
+    async fn count_tokens_uses_the_anthropic_counting_endpoint() {
+        use crate::llm::providers::DefaultProvider;
+        use crate::llm::testing::mock_server::start_capture_server;
+
+        let (base_url, captured) =
+            start_capture_server(200, br#"{"input_tokens":42}"#.to_vec()).expect("server");
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("api_key_counted", "sk-ant-test")
+            .await
+            .expect("set api key");
+        let config = ProviderConfig {
+            base_url: format!("{}/v1", base_url),
+            ..custom_provider_config("counted", ProtocolType::Anthropic)
+        };
+        let messages = count_messages();
+
+        let count = DefaultProvider::new(config.clone())
+            .count_tokens(&count_ctx(&config, &api_keys, &messages))
+            .await
+            .expect("count");
+
+        assert_eq!(count, 42);
+        let sent = captured.recv().expect("captured request");
+        assert!(
+            sent.url.ends_with("/v1/messages/count_tokens"),
+            "{}",
+            sent.url
+        );
+        let body: Value = serde_json::from_slice(&sent.body).expect("json body");
+        assert_eq!(body["model"], "claude-sonnet-4-5");
+        assert!(body.get("system").is_some());
+        assert!(body.get("max_tokens").is_none());
+        assert!(body.get("stream").is_none());
+    }
+
+    #[tokio::test]
+    async fn anthropic_count_is_retried_with_the_completion_headers() {
+        use crate::llm::providers::DefaultProvider;
+        use crate::llm::testing::mock_server::start_capture_sequence_server;
+
+        let (base_url, captured) = start_capture_sequence_server(vec![
+            (503, "overloaded".to_string()),
+            (200, r#"{"input_tokens":7}"#.to_string()),
+        ])
+        .expect("server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_counted", "sk-ant-test")]).await;
+        let config = ProviderConfig {
+            base_url: format!("{}/v1", base_url),
+            retry_policy: Some(RetryPolicy {
+                max_retries: 1,
+                base_delay_ms: 1,
+                max_delay_ms: 1,
+            }),
+            ..custom_provider_config("counted", ProtocolType::Anthropic)
+        };
+        let messages = count_messages();
+
+        let count = DefaultProvider::new(config.clone())
+            .count_tokens(&count_ctx(&config, &api_keys, &messages))
+            .await
+            .expect("count");
+
+        assert_eq!(count, 7);
+        for _ in 0..2 {
+            let sent = captured.recv().expect("captured request");
+            assert!(
+                sent.url.ends_with("/v1/messages/count_tokens"),
+                "{}",
+                sent.url
+            );
+            assert_eq!(sent.header("x-api-key"), Some("sk-ant-test"));
+            assert!(sent.header("anthropic-version").is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn count_tokens_estimates_locally_without_a_counting_endpoint() {
+        use crate::llm::providers::DefaultProvider;
+        use crate::llm::testing::mock_server::start_sequence_server;
+        use std::sync::atomic::Ordering;
+
+        let (base_url, hits) =
+            start_sequence_server(vec![(404, "not found".to_string())]).expect("server");
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("api_key_estimated", "sk-test")
+            .await
+            .expect("set api key");
+        let openai_config = ProviderConfig {
+            base_url: format!("{}/v1", base_url),
+            ..custom_provider_config("estimated", ProtocolType::OpenAiCompatible)
+        };
+        let anthropic_config = ProviderConfig {
+            protocol: ProtocolType::Anthropic,
+            ..openai_config.clone()
+        };
+        let messages = count_messages();
+        let estimate = |family| {
+            tokenize::count_message_tokens(tokenize::estimator().as_ref(), family, &messages)
+                as usize
+        };
+
+        let openai_count = DefaultProvider::new(openai_config.clone())
+            .count_tokens(&count_ctx(&openai_config, &api_keys, &messages))
+            .await
+            .expect("count");
+        assert_eq!(openai_count, estimate(tokenize::TokenizerFamily::OpenAi));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        // An endpoint that turns the count request down falls back to the estimate as well
+        let anthropic_count = DefaultProvider::new(anthropic_config.clone())
+            .count_tokens(&count_ctx(&anthropic_config, &api_keys, &messages))
+            .await
+            .expect("count");
+        assert_eq!(anthropic_count, estimate(tokenize::TokenizerFamily::Claude));
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn oversized_request_is_rejected_before_sending() {
+        use crate::llm::providers::DefaultProvider;
+        use crate::llm::testing::mock_server::start_sequence_server;
+        use std::sync::atomic::Ordering;
+
+        let (base_url, hits) =
+            start_sequence_server(vec![(200, "{}".to_string())]).expect("server");
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("api_key_small", "sk-test")
+            .await
+            .expect("set api key");
+        let mut config = custom_provider_config("small", ProtocolType::OpenAiCompatible);
+        config.base_url = format!("{}/v1", base_url);
+        config.max_request_bytes = Some(1024);
+        let provider = DefaultProvider::new(config.clone());
+        let messages = [Message::User {
+            content: MessageContent::Text("x".repeat(4096)),
+            provider_options: None,
+            cache: false,
+        }];
+        let ctx = ProviderContext {
+            provider_config: &config,
+            api_key_manager: &api_keys,
+            model: "gpt-4o-mini",
+            messages: &messages,
+            tools: None,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        };
+
+        let err = provider
+            .build_complete_request(&ctx)
+            .await
+            .err()
+            .expect("too large");
+        let LlmError::RequestTooLarge { size, limit } = err else {
+            panic!("expected RequestTooLarge, got {:?}", err);
+        };
+        assert!(size > 4096, "{}", size);
+        assert_eq!(limit, 1024);
+
+        let err = provider.complete(&ctx).await.expect_err("too large");
+        assert!(err.contains("over the provider's"), "{}", err);
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn request_size_counts_the_serialized_body() {
+        let body = serde_json::json!({ "model": "gpt-4o", "messages": [{ "content": "hé" }] });
+        assert_eq!(serialized_len(&body), body.to_string().len());
+
+        let config = custom_provider_config("default", ProtocolType::OpenAiCompatible);
+        assert!(check_request_size(&config, &body, &[]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn resolve_base_url_falls_back_when_specialized_url_is_missing() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("use_coding_plan_multi", "true")
+            .await
+            .expect("set coding plan");
+        let config = ProviderConfig {
+            coding_plan_base_url: None,
+            ..multi_endpoint_config()
+        };
+
+        let base_url = BaseProvider::new(config)
+            .resolve_base_url_with_fallback(&api_keys)
+            .await
+            .expect("base url");
+
+        assert_eq!(base_url, "https://api.example.com/v1");
+    }
+
+    #[tokio::test]
+    async fn api_key_credentials_rotate_through_the_pool() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        api_keys
+            .set_setting("api_key_custom", "key-a, key-b")
+            .await
+            .expect("set key pool");
+        let config = custom_provider_config("custom", ProtocolType::OpenAiCompatible);
+        let base = BaseProvider::new(config);
+
+        let mut keys = Vec::new();
+        for _ in 0..2 {
+            let creds = base.api_key_credentials(&api_keys).await;
+            match creds.expect("credentials") {
+                ProviderCredentials::ApiKey(key) => keys.push(key),
+                other => panic!("expected ApiKey, got {:?}", other),
+            }
+        }
+        keys.sort();
+        assert_eq!(keys, vec!["key-a", "key-b"]);
+    }
+
+    #[tokio::test]
+    async fn api_key_credentials_fall_back_to_the_legacy_setting() {
+        let (_dir, api_keys) = api_key_manager_with(&[]).await;
+        let config = custom_provider_config("custom", ProtocolType::OpenAiCompatible);
+        let base = BaseProvider::new(config);
+
+        let err = base
+            .api_key_credentials(&api_keys)
+            .await
+            .expect_err("no key");
+        assert!(
+            err.to_string().contains("API key 'custom_test' not found"),
+            "{}",
+            err
+        );
+
+        api_keys
+            .set_setting("custom_test", "legacy-key")
+            .await
+            .expect("set legacy key");
+        let creds = base.api_key_credentials(&api_keys).await;
+        match creds.expect("credentials") {
+            ProviderCredentials::ApiKey(key) => assert_eq!(key, "legacy-key"),
+            other => panic!("expected ApiKey, got {:?}", other),
+        }
+    }
 }
 
 /// Base provider implementation with common logic
@@ -335,7 +2360,49 @@ impl BaseProvider {
         Self { config }
     }
 
+    /// Model ID to send for `model`: the provider's `default_model` when none is given, then
+    /// the `model_aliases` target if the name is an alias. Aliases are not chained
+    pub fn resolve_model<'a>(config: &'a ProviderConfig, model: &'a str) -> &'a str {
+        let model = match model.trim() {
+            "" => config.default_model.as_deref().unwrap_or(model),
+            trimmed => trimmed,
+        };
+        config
+            .model_aliases
+            .get(model)
+            .map(String::as_str)
+            .unwrap_or(model)
+    }
+
+    /// API key credentials rotating through the `api_key_{id}` pool, falling back to the
+    /// legacy `api_key_name` setting for keys stored before the pool format
+    pub async fn api_key_credentials(
+        &self,
+        api_key_manager: &ApiKeyManager,
+    ) -> Result<ProviderCredentials, LlmError> {
+        match api_key_manager.get_credentials_rotating(&self.config).await {
+            Ok(PooledCredentials::Token(key)) => return Ok(ProviderCredentials::ApiKey(key)),
+            Ok(PooledCredentials::None) => return Ok(ProviderCredentials::None),
+            Err(_) => {}
+        }
+
+        let legacy_key = api_key_manager
+            .get_setting(&self.config.api_key_name)
+            .await?
+            .unwrap_or_default();
+        if legacy_key.trim().is_empty() {
+            return Err(LlmError::Auth(format!(
+                "API key '{}' not found",
+                self.config.api_key_name
+            )));
+        }
+        Ok(ProviderCredentials::ApiKey(legacy_key))
+    }
+
     /// Helper to resolve base URL with common logic (coding plan, international, custom)
+    /// Order: custom `base_url_{id}`, then the coding plan URL when `use_coding_plan_{id}`
+    /// is on, then the international URL when `use_international_{id}` is on, then `base_url`
+    /// A mode whose URL is not configured is skipped
     pub async fn resolve_base_url_with_fallback(
         &self,
         api_key_manager: &ApiKeyManager,
@@ -343,32 +2410,27 @@ impl BaseProvider {
         // Check for custom base URL setting
         let setting_key = format!("base_url_{}", self.config.id);
         if let Some(base_url) = api_key_manager.get_setting(&setting_key).await? {
+            let base_url = base_url.trim();
             if !base_url.is_empty() {
-                return Ok(base_url);
+                return Ok(base_url.to_string());
             }
         }
 
         // Check for coding plan
-        if self.config.supports_coding_plan {
-            let coding_plan_key = format!("use_coding_plan_{}", self.config.id);
-            if let Some(use_coding) = api_key_manager.get_setting(&coding_plan_key).await? {
-                if use_coding == "true" {
-                    if let Some(url) = &self.config.coding_plan_base_url {
-                        return Ok(url.clone());
-                    }
-                }
+        if let Some(url) = &self.config.coding_plan_base_url {
+            if self.config.supports_coding_plan
+                && setting_enabled(api_key_manager, "use_coding_plan", &self.config.id).await?
+            {
+                return Ok(url.clone());
             }
         }
 
         // Check for international
-        if self.config.supports_international {
-            let international_key = format!("use_international_{}", self.config.id);
-            if let Some(use_intl) = api_key_manager.get_setting(&international_key).await? {
-                if use_intl == "true" {
-                    if let Some(url) = &self.config.international_base_url {
-                        return Ok(url.clone());
-                    }
-                }
+        if let Some(url) = &self.config.international_base_url {
+            if self.config.supports_international
+                && setting_enabled(api_key_manager, "use_international", &self.config.id).await?
+            {
+                return Ok(url.clone());
             }
         }
 
@@ -376,3 +2438,15 @@ impl BaseProvider {
         Ok(self.config.base_url.clone())
     }
 }
+
+/// Read a per-provider boolean setting such as `use_coding_plan_{id}`
+pub(crate) async fn setting_enabled(
+    api_key_manager: &ApiKeyManager,
+    prefix: &str,
+    provider_id: &str,
+) -> Result<bool, String> {
+    let value = api_key_manager
+        .get_setting(&format!("{}_{}", prefix, provider_id))
+        .await?;
+    Ok(value.is_some_and(|value| value.trim().eq_ignore_ascii_case("true")))
+}
@@ -1,5 +1,15 @@
+use crate::llm::providers::cohere_provider::DEFAULT_COHERE_BASE_URL;
+use crate::llm::providers::grok_provider::DEFAULT_XAI_BASE_URL;
+use crate::llm::providers::groq_provider::groq_preset;
+use crate::llm::providers::lmstudio_provider::lmstudio_preset;
+use crate::llm::providers::mistral_provider::DEFAULT_MISTRAL_BASE_URL;
 use crate::llm::types::{AuthType, ProtocolType, ProviderConfig};
 
+/// Ready-made configurations for local OpenAI-compatible servers
+pub fn provider_presets() -> Vec<ProviderConfig> {
+    vec![lmstudio_preset()]
+}
+
 pub fn builtin_providers() -> Vec<ProviderConfig> {
     vec![
         ProviderConfig {
@@ -16,6 +26,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::TalkCodyJwt,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "openai".to_string(),
@@ -31,6 +49,38 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
+        },
+        ProviderConfig {
+            id: "azure".to_string(),
+            name: "Azure OpenAI".to_string(),
+            protocol: ProtocolType::OpenAiCompatible,
+            // Each resource has its own endpoint, set through base_url_azure
+            base_url: String::new(),
+            api_key_name: "AZURE_OPENAI_API_KEY".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: false,
+            supports_international: false,
+            coding_plan_base_url: None,
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::ApiKey,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "github_copilot".to_string(),
@@ -64,6 +114,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             ),
             extra_body: None,
             auth_type: AuthType::OAuthBearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "openRouter".to_string(),
@@ -91,6 +149,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
                 "reasoning": { "enabled": true }
             })),
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "aiGateway".to_string(),
@@ -116,6 +182,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             ),
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "deepseek".to_string(),
@@ -131,6 +205,106 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
+        },
+        ProviderConfig {
+            id: "xai".to_string(),
+            name: "xAI Grok".to_string(),
+            protocol: ProtocolType::OpenAiCompatible,
+            base_url: DEFAULT_XAI_BASE_URL.to_string(),
+            api_key_name: "XAI_API_KEY".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: false,
+            supports_international: false,
+            coding_plan_base_url: None,
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
+        },
+        ProviderConfig {
+            id: "mistral".to_string(),
+            name: "Mistral".to_string(),
+            protocol: ProtocolType::OpenAiCompatible,
+            base_url: DEFAULT_MISTRAL_BASE_URL.to_string(),
+            api_key_name: "MISTRAL_API_KEY".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: false,
+            supports_international: false,
+            coding_plan_base_url: None,
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
+        },
+        ProviderConfig {
+            id: "cohere".to_string(),
+            name: "Cohere".to_string(),
+            protocol: ProtocolType::Cohere,
+            base_url: DEFAULT_COHERE_BASE_URL.to_string(),
+            api_key_name: "COHERE_API_KEY".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: false,
+            supports_international: false,
+            coding_plan_base_url: None,
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
+        },
+        ProviderConfig {
+            id: "deepseek_coding".to_string(),
+            name: "DeepSeek Coding Plan".to_string(),
+            protocol: ProtocolType::OpenAiCompatible,
+            base_url: "https://api.deepseek.com/v1".to_string(),
+            api_key_name: "DEEPSEEK_CODING_API_KEY".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: true,
+            supports_international: false,
+            coding_plan_base_url: Some("https://api.deepseek.com/beta".to_string()),
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "zhipu".to_string(),
@@ -146,6 +320,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "zai".to_string(),
@@ -161,6 +343,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "MiniMax".to_string(),
@@ -176,6 +366,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::ApiKey,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "moonshot".to_string(),
@@ -191,6 +389,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "kimi_coding".to_string(),
@@ -206,27 +412,21 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
-        ProviderConfig {
-            id: "groq".to_string(),
-            name: "Groq".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.groq.com/openai/v1".to_string(),
-            api_key_name: "GROQ_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: AuthType::Bearer,
-        },
+        groq_preset(),
         ProviderConfig {
             id: "ollama".to_string(),
             name: "Ollama".to_string(),
             protocol: ProtocolType::OpenAiCompatible,
-            base_url: "http://127.0.0.1:11434/v1".to_string(),
+            base_url: "http://127.0.0.1:11434".to_string(),
             api_key_name: "OLLAMA_ENABLED".to_string(),
             supports_oauth: false,
             supports_coding_plan: false,
@@ -236,22 +436,16 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::None,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
-        ProviderConfig {
-            id: "lmstudio".to_string(),
-            name: "LM Studio".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "http://127.0.0.1:1234/v1".to_string(),
-            api_key_name: "LMSTUDIO_ENABLED".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: AuthType::None,
-        },
+        lmstudio_preset(),
         ProviderConfig {
             id: "anthropic".to_string(),
             name: "Anthropic".to_string(),
@@ -266,6 +460,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::OAuthBearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "google".to_string(),
@@ -281,6 +483,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "volcengine".to_string(),
@@ -296,6 +506,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "alibaba".to_string(),
@@ -311,6 +529,60 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
+        },
+        ProviderConfig {
+            id: "replicate".to_string(),
+            name: "Replicate".to_string(),
+            protocol: ProtocolType::OpenAiCompatible,
+            base_url: "https://api.replicate.com/v1".to_string(),
+            api_key_name: "REPLICATE_API_TOKEN".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: false,
+            supports_international: false,
+            coding_plan_base_url: None,
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
+        },
+        ProviderConfig {
+            id: "stability".to_string(),
+            name: "Stability AI".to_string(),
+            protocol: ProtocolType::OpenAiCompatible,
+            base_url: "https://api.stability.ai".to_string(),
+            api_key_name: "STABILITY_API_KEY".to_string(),
+            supports_oauth: false,
+            supports_coding_plan: false,
+            supports_international: false,
+            coding_plan_base_url: None,
+            international_base_url: None,
+            headers: None,
+            extra_body: None,
+            auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "tavily".to_string(),
@@ -326,6 +598,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "serper".to_string(),
@@ -341,6 +621,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
         ProviderConfig {
             id: "elevenlabs".to_string(),
@@ -356,6 +644,14 @@ pub fn builtin_providers() -> Vec<ProviderConfig> {
             headers: None,
             extra_body: None,
             auth_type: AuthType::Bearer,
+            retry_policy: None,
+            model_aliases: Default::default(),
+            default_model: None,
+            tolerant_stream_json: false,
+            max_request_bytes: None,
+            multipart_images: false,
+            organization: None,
+            project: None,
         },
     ]
 }
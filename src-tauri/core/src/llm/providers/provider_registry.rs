@@ -1,14 +1,82 @@
+use crate::llm::error::LlmError;
 use crate::llm::protocols::{claude_protocol::ClaudeProtocol, openai_protocol::OpenAiProtocol};
+use crate::llm::providers::provider::ProviderCapabilities;
 use crate::llm::providers::{
-    DefaultProvider, GithubCopilotProvider, KimiCodingProvider, MoonshotProvider, OpenAiProvider,
-    Provider,
+    AzureOpenAiProvider, CohereProvider, DeepSeekCodingProvider, DefaultProvider,
+    GithubCopilotProvider, GrokProvider, GroqProvider, KimiCodingProvider, LmStudioProvider,
+    MistralProvider, MoonshotProvider, OllamaProvider, OpenAiProvider, Provider,
 };
 use crate::llm::types::ProtocolType;
 use crate::llm::types::ProviderConfig;
 use std::collections::HashMap;
 
+/// Builds a provider instance from its configuration
+pub type ProviderConstructor = fn(ProviderConfig) -> Box<dyn Provider>;
+
+/// Provider constructors, looked up by provider ID first and by protocol otherwise
+#[derive(Debug, Clone, Default)]
+pub struct ProviderConstructors {
+    by_id: HashMap<String, ProviderConstructor>,
+    by_protocol: HashMap<ProtocolType, ProviderConstructor>,
+}
+
+impl ProviderConstructors {
+    /// Providers with their own logic by ID, and the default provider for every protocol
+    pub fn builtin() -> Self {
+        let mut constructors = Self::default();
+        constructors.register_id("openai", |config| Box::new(OpenAiProvider::new(config)));
+        constructors.register_id("azure", |config| Box::new(AzureOpenAiProvider::new(config)));
+        constructors.register_id("github_copilot", |config| {
+            Box::new(GithubCopilotProvider::new(config))
+        });
+        constructors.register_id("moonshot", |config| Box::new(MoonshotProvider::new(config)));
+        constructors.register_id("kimi_coding", |config| {
+            Box::new(KimiCodingProvider::new(config))
+        });
+        constructors.register_id("deepseek_coding", |config| {
+            Box::new(DeepSeekCodingProvider::new(config))
+        });
+        constructors.register_id("ollama", |config| Box::new(OllamaProvider::new(config)));
+        constructors.register_id("lmstudio", |config| Box::new(LmStudioProvider::new(config)));
+        constructors.register_id("xai", |config| Box::new(GrokProvider::new(config)));
+        constructors.register_id("groq", |config| Box::new(GroqProvider::new(config)));
+        constructors.register_id("mistral", |config| Box::new(MistralProvider::new(config)));
+        constructors.register_id("cohere", |config| Box::new(CohereProvider::new(config)));
+
+        for protocol in [
+            ProtocolType::OpenAiCompatible,
+            ProtocolType::Claude,
+            ProtocolType::Anthropic,
+            ProtocolType::Gemini,
+            ProtocolType::Cohere,
+        ] {
+            constructors
+                .register_protocol(protocol, |config| Box::new(DefaultProvider::new(config)));
+        }
+        constructors
+    }
+
+    /// Use `constructor` for the provider with this ID, replacing any earlier one
+    pub fn register_id(&mut self, id: impl Into<String>, constructor: ProviderConstructor) {
+        self.by_id.insert(id.into(), constructor);
+    }
+
+    /// Use `constructor` for providers of `protocol` that have no constructor of their own
+    pub fn register_protocol(&mut self, protocol: ProtocolType, constructor: ProviderConstructor) {
+        self.by_protocol.insert(protocol, constructor);
+    }
+
+    fn get(&self, config: &ProviderConfig) -> Option<ProviderConstructor> {
+        self.by_id
+            .get(&config.id)
+            .or_else(|| self.by_protocol.get(&config.protocol))
+            .copied()
+    }
+}
+
 pub struct ProviderRegistry {
     providers: HashMap<String, ProviderConfig>,
+    constructors: ProviderConstructors,
     // Protocol implementations (kept for backward compatibility during migration)
     #[allow(dead_code)]
     openai_protocol: OpenAiProtocol,
@@ -20,6 +88,7 @@ impl std::fmt::Debug for ProviderRegistry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ProviderRegistry")
             .field("providers", &self.providers)
+            .field("constructors", &self.constructors)
             .finish_non_exhaustive()
     }
 }
@@ -28,6 +97,7 @@ impl Clone for ProviderRegistry {
     fn clone(&self) -> Self {
         Self {
             providers: self.providers.clone(),
+            constructors: self.constructors.clone(),
             openai_protocol: OpenAiProtocol,
             claude_protocol: ClaudeProtocol,
         }
@@ -43,6 +113,14 @@ impl Default for ProviderRegistry {
 
 impl ProviderRegistry {
     pub fn new(builtin_providers: Vec<ProviderConfig>) -> Self {
+        Self::with_constructors(builtin_providers, ProviderConstructors::builtin())
+    }
+
+    /// Registry that builds providers with `constructors` instead of the built-in set
+    pub fn with_constructors(
+        builtin_providers: Vec<ProviderConfig>,
+        constructors: ProviderConstructors,
+    ) -> Self {
         let mut providers = HashMap::new();
         for provider in builtin_providers {
             providers.insert(provider.id.clone(), provider);
@@ -50,6 +128,7 @@ impl ProviderRegistry {
 
         Self {
             providers,
+            constructors,
             openai_protocol: OpenAiProtocol,
             claude_protocol: ClaudeProtocol,
         }
@@ -67,22 +146,51 @@ impl ProviderRegistry {
         self.providers.values().cloned().collect()
     }
 
+    /// Use `constructor` to build the provider with this ID
+    pub fn register_constructor(
+        &mut self,
+        id: impl Into<String>,
+        constructor: ProviderConstructor,
+    ) {
+        self.constructors.register_id(id, constructor);
+    }
+
+    /// Use `constructor` for providers of `protocol` that have no constructor of their own
+    pub fn register_protocol_constructor(
+        &mut self,
+        protocol: ProtocolType,
+        constructor: ProviderConstructor,
+    ) {
+        self.constructors.register_protocol(protocol, constructor);
+    }
+
+    /// Build a provider for `config` with the constructor registered for its ID or protocol
+    pub fn build_provider(&self, config: ProviderConfig) -> Result<Box<dyn Provider>, LlmError> {
+        let constructor = self.constructors.get(&config).ok_or_else(|| {
+            LlmError::Other(format!(
+                "No provider registered for '{}' with protocol {:?} / 未注册该服务商协议",
+                config.id, config.protocol
+            ))
+        })?;
+        Ok(constructor(config))
+    }
+
     /// Create a provider instance for the given provider ID
-    /// This is the new way to get a provider with its specific logic
     pub fn create_provider(&self, id: &str) -> Option<Box<dyn Provider>> {
         let config = self.providers.get(id)?;
+        match self.build_provider(config.clone()) {
+            Ok(provider) => Some(provider),
+            Err(e) => {
+                log::warn!("[ProviderRegistry] {}", e);
+                None
+            }
+        }
+    }
 
-        // Create the appropriate provider based on ID
-        let provider: Box<dyn Provider> = match id {
-            "openai" => Box::new(OpenAiProvider::new(config.clone())),
-            "github_copilot" => Box::new(GithubCopilotProvider::new(config.clone())),
-            "moonshot" => Box::new(MoonshotProvider::new(config.clone())),
-            "kimi_coding" => Box::new(KimiCodingProvider::new(config.clone())),
-            // Use DefaultProvider for all other providers
-            _ => Box::new(DefaultProvider::new(config.clone())),
-        };
-
-        Some(provider)
+    /// Capabilities of a registered provider
+    pub fn capabilities(&self, id: &str) -> Option<ProviderCapabilities> {
+        self.create_provider(id)
+            .map(|provider| provider.capabilities())
     }
 
     /// Legacy method - kept for backward compatibility
@@ -92,7 +200,12 @@ impl ProviderRegistry {
             ProtocolType::OpenAiCompatible => {
                 Some(LegacyProtocolAdapter::new(&self.openai_protocol))
             }
-            ProtocolType::Claude => Some(LegacyProtocolAdapter::new(&self.claude_protocol)),
+            // Anthropic shares the Messages wire format with the legacy Claude protocol
+            ProtocolType::Claude | ProtocolType::Anthropic => {
+                Some(LegacyProtocolAdapter::new(&self.claude_protocol))
+            }
+            // Gemini and Cohere were added after the migration and have no legacy implementation
+            ProtocolType::Gemini | ProtocolType::Cohere => None,
         }
     }
 }
@@ -171,24 +284,11 @@ impl<'a> LegacyProtocolAdapter<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::llm::types::{AuthType, ProviderConfig};
+    use crate::llm::testing::fixtures;
+    use crate::llm::types::ProviderConfig;
 
     fn provider_config(id: &str) -> ProviderConfig {
-        ProviderConfig {
-            id: id.to_string(),
-            name: id.to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://example.com".to_string(),
-            api_key_name: "TEST_API_KEY".to_string(),
-            supports_oauth: false,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: AuthType::Bearer,
-        }
+        fixtures::provider_config(id, ProtocolType::OpenAiCompatible, "https://example.com")
     }
 
     #[test]
@@ -196,6 +296,7 @@ mod tests {
         let registry = ProviderRegistry::new(Vec::new());
         assert!(registry.protocol(ProtocolType::OpenAiCompatible).is_some());
         assert!(registry.protocol(ProtocolType::Claude).is_some());
+        assert!(registry.protocol(ProtocolType::Anthropic).is_some());
     }
 
     #[test]
@@ -220,4 +321,112 @@ mod tests {
         assert!(copilot.is_some());
         assert_eq!(copilot.unwrap().id(), "github_copilot");
     }
+
+    #[test]
+    fn build_provider_uses_id_then_protocol_constructor() {
+        let registry = ProviderRegistry::new(Vec::new());
+
+        let kimi = registry
+            .build_provider(provider_config("kimi_coding"))
+            .expect("kimi provider");
+        assert_eq!(kimi.id(), "kimi_coding");
+        assert!(!kimi.capabilities().supports_images);
+
+        let custom = registry
+            .build_provider(provider_config("my_gateway"))
+            .expect("openai-compatible provider");
+        assert_eq!(custom.id(), "my_gateway");
+        assert_eq!(custom.protocol_type(), ProtocolType::OpenAiCompatible);
+    }
+
+    #[test]
+    fn build_provider_without_constructor_is_an_error() {
+        let registry = ProviderRegistry::with_constructors(Vec::new(), Default::default());
+
+        let err = registry
+            .build_provider(provider_config("my_gateway"))
+            .err()
+            .expect("no constructor");
+
+        assert!(matches!(err, LlmError::Other(_)));
+        assert!(err.to_string().contains("my_gateway"));
+        assert!(err.to_string().contains("OpenAiCompatible"));
+    }
+
+    #[test]
+    fn registered_constructor_replaces_builtin() {
+        let mut registry = ProviderRegistry::new(vec![provider_config("openai")]);
+        registry.register_constructor("openai", |config| {
+            Box::new(DefaultProvider::new(ProviderConfig {
+                name: "Fake OpenAI".to_string(),
+                ..config
+            }))
+        });
+
+        let provider = registry.create_provider("openai").expect("provider");
+
+        assert_eq!(provider.name(), "Fake OpenAI");
+    }
+
+    #[test]
+    fn kimi_coding_reports_tools_and_streaming_but_not_images() {
+        let mut registry = ProviderRegistry::new(Vec::new());
+        registry.register_provider(provider_config("kimi_coding"));
+
+        let capabilities = registry.capabilities("kimi_coding").expect("capabilities");
+        assert!(capabilities.supports_streaming);
+        assert!(capabilities.supports_tools);
+        assert!(!capabilities.supports_images);
+        assert!(!capabilities.supports_embeddings);
+    }
+
+    #[test]
+    fn capabilities_differ_per_provider() {
+        let mut registry = ProviderRegistry::new(Vec::new());
+        for id in ["openai", "volcengine", "tavily"] {
+            registry.register_provider(provider_config(id));
+        }
+
+        let openai = registry.capabilities("openai").expect("openai");
+        assert_eq!(
+            openai,
+            ProviderCapabilities {
+                supports_streaming: true,
+                supports_tools: true,
+                supports_vision: true,
+                supports_images: true,
+                supports_embeddings: true,
+                supports_structured_output: true,
+                supports_logprobs: true,
+                supports_web_search: true,
+                supports_code_interpreter: true,
+                supports_multipart_images: false,
+            }
+        );
+
+        let volcengine = registry.capabilities("volcengine").expect("volcengine");
+        assert!(volcengine.supports_images);
+        assert!(volcengine.supports_streaming);
+
+        let tavily = registry.capabilities("tavily").expect("tavily");
+        assert_eq!(tavily, ProviderCapabilities::default());
+
+        assert!(registry.capabilities("missing").is_none());
+    }
+
+    #[test]
+    fn capabilities_serialize_in_camel_case() {
+        let json = serde_json::to_value(ProviderCapabilities::chat()).expect("serialize");
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "supportsStreaming": true,
+                "supportsTools": true,
+                "supportsVision": false,
+                "supportsImages": false,
+                "supportsEmbeddings": false,
+                "supportsStructuredOutput": false
+            })
+        );
+    }
 }
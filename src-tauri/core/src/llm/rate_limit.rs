@@ -0,0 +1,168 @@
+// Rate-limit headers reported by providers
+// Parsed after each response so the UI can warn before a limit is hit
+
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+/// Header names per field, checked in order: OpenAI style, Anthropic style, then the
+/// generic single-bucket form some gateways send
+const LIMIT_REQUESTS: &[&str] = &[
+    "x-ratelimit-limit-requests",
+    "anthropic-ratelimit-requests-limit",
+    "x-ratelimit-limit",
+];
+const REMAINING_REQUESTS: &[&str] = &[
+    "x-ratelimit-remaining-requests",
+    "anthropic-ratelimit-requests-remaining",
+    "x-ratelimit-remaining",
+];
+const RESET_REQUESTS: &[&str] = &[
+    "x-ratelimit-reset-requests",
+    "anthropic-ratelimit-requests-reset",
+    "x-ratelimit-reset",
+];
+const LIMIT_TOKENS: &[&str] = &[
+    "x-ratelimit-limit-tokens",
+    "anthropic-ratelimit-tokens-limit",
+];
+const REMAINING_TOKENS: &[&str] = &[
+    "x-ratelimit-remaining-tokens",
+    "anthropic-ratelimit-tokens-remaining",
+];
+const RESET_TOKENS: &[&str] = &[
+    "x-ratelimit-reset-tokens",
+    "anthropic-ratelimit-tokens-reset",
+];
+
+/// Request and token quotas from the last response
+/// Reset values are kept as sent, e.g. `6m0s`, seconds or an RFC 3339 timestamp
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitInfo {
+    pub limit_requests: Option<u64>,
+    pub remaining_requests: Option<u64>,
+    pub reset_requests: Option<String>,
+    pub limit_tokens: Option<u64>,
+    pub remaining_tokens: Option<u64>,
+    pub reset_tokens: Option<String>,
+}
+
+impl RateLimitInfo {
+    /// Parse the known rate-limit headers; `None` when the provider sent none of them
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let info = Self {
+            limit_requests: number(headers, LIMIT_REQUESTS),
+            remaining_requests: number(headers, REMAINING_REQUESTS),
+            reset_requests: text(headers, RESET_REQUESTS),
+            limit_tokens: number(headers, LIMIT_TOKENS),
+            remaining_tokens: number(headers, REMAINING_TOKENS),
+            reset_tokens: text(headers, RESET_TOKENS),
+        };
+        (info != Self::default()).then_some(info)
+    }
+
+    /// Whether either quota is down to `fraction` (0.0-1.0) of its limit or less
+    pub fn is_near_limit(&self, fraction: f64) -> bool {
+        let near = |remaining: Option<u64>, limit: Option<u64>| match (remaining, limit) {
+            (Some(remaining), Some(limit)) if limit > 0 => {
+                remaining as f64 <= limit as f64 * fraction
+            }
+            (Some(remaining), _) => remaining == 0,
+            _ => false,
+        };
+        near(self.remaining_requests, self.limit_requests)
+            || near(self.remaining_tokens, self.limit_tokens)
+    }
+}
+
+fn text(headers: &HeaderMap, names: &[&str]) -> Option<String> {
+    names.iter().find_map(|name| {
+        headers
+            .get(*name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    })
+}
+
+fn number(headers: &HeaderMap, names: &[&str]) -> Option<u64> {
+    names.iter().find_map(|name| {
+        headers
+            .get(*name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderName, HeaderValue};
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    HeaderName::from_static(*name),
+                    HeaderValue::from_static(*value),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_openai_style_headers() {
+        let info = RateLimitInfo::from_headers(&headers(&[
+            ("x-ratelimit-limit-requests", "500"),
+            ("x-ratelimit-remaining-requests", "499"),
+            ("x-ratelimit-reset-requests", "120ms"),
+            ("x-ratelimit-limit-tokens", "30000"),
+            ("x-ratelimit-remaining-tokens", "1200"),
+            ("x-ratelimit-reset-tokens", "6m0s"),
+        ]))
+        .expect("rate limit info");
+
+        assert_eq!(
+            info,
+            RateLimitInfo {
+                limit_requests: Some(500),
+                remaining_requests: Some(499),
+                reset_requests: Some("120ms".to_string()),
+                limit_tokens: Some(30000),
+                remaining_tokens: Some(1200),
+                reset_tokens: Some("6m0s".to_string()),
+            }
+        );
+        assert!(info.is_near_limit(0.05));
+        assert!(!info.is_near_limit(0.01));
+    }
+
+    #[test]
+    fn parses_anthropic_and_generic_headers() {
+        let info = RateLimitInfo::from_headers(&headers(&[
+            ("anthropic-ratelimit-requests-limit", "50"),
+            ("anthropic-ratelimit-requests-remaining", "0"),
+            ("anthropic-ratelimit-requests-reset", "2026-10-14T12:00:30Z"),
+            ("anthropic-ratelimit-tokens-remaining", "not-a-number"),
+        ]))
+        .expect("rate limit info");
+        assert_eq!(info.remaining_requests, Some(0));
+        assert_eq!(info.reset_requests.as_deref(), Some("2026-10-14T12:00:30Z"));
+        assert_eq!(info.remaining_tokens, None);
+        assert!(info.is_near_limit(0.0));
+
+        let generic = RateLimitInfo::from_headers(&headers(&[("x-ratelimit-remaining", "12")]))
+            .expect("generic info");
+        assert_eq!(generic.remaining_requests, Some(12));
+        assert!(!generic.is_near_limit(0.1));
+    }
+
+    #[test]
+    fn returns_none_without_rate_limit_headers() {
+        assert_eq!(
+            RateLimitInfo::from_headers(&headers(&[("content-type", "application/json")])),
+            None
+        );
+    }
+}
@@ -0,0 +1,124 @@
+// Request ID correlation
+// Every provider call carries a client-generated ID, sent as `x-request-id` unless configured
+// otherwise, and keeps the ID the provider reports back so both sides' logs can be matched
+
+use crate::llm::http_client;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+pub const DEFAULT_REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Response headers providers put their own request ID in, checked in order
+/// (OpenAI-compatible APIs, Anthropic, Volcengine, AWS-hosted gateways)
+const PROVIDER_REQUEST_ID_HEADERS: &[&str] = &[
+    "x-request-id",
+    "request-id",
+    "x-tt-logid",
+    "x-amzn-requestid",
+];
+
+/// IDs identifying one provider call on the client and provider side
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestIds {
+    #[serde(rename = "clientRequestId")]
+    pub client: String,
+    #[serde(rename = "providerRequestId")]
+    pub provider: Option<String>,
+}
+
+impl RequestIds {
+    /// Fresh client ID; the provider ID is filled in from the response
+    pub fn generate() -> Self {
+        Self {
+            client: uuid::Uuid::new_v4().to_string(),
+            provider: None,
+        }
+    }
+
+    /// Send the client ID under the configured header, unless sending is turned off
+    pub fn tag(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match header_name() {
+            Some(name) => request.header(name, &self.client),
+            None => request,
+        }
+    }
+
+    /// Keep the provider's ID from the response headers
+    pub fn record(&mut self, headers: &HeaderMap) {
+        if let Some(id) = provider_request_id(headers, &self.client) {
+            self.provider = Some(id);
+        }
+    }
+}
+
+/// Header the client ID goes out under, or None when `requestIdHeader` is set to ""
+pub fn header_name() -> Option<String> {
+    match http_client::http_settings().request_id_header {
+        None => Some(DEFAULT_REQUEST_ID_HEADER.to_string()),
+        Some(name) => Some(name.trim().to_string()).filter(|name| !name.is_empty()),
+    }
+}
+
+/// First provider request ID in `headers`; a header that only echoes our own ID is skipped
+pub fn provider_request_id(headers: &HeaderMap, client_id: &str) -> Option<String> {
+    PROVIDER_REQUEST_ID_HEADERS.iter().find_map(|name| {
+        headers
+            .get(*name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty() && *value != client_id)
+            .map(str::to_string)
+    })
+}
+
+/// Request IDs shared by every call an image client makes for one generation
+/// Retries and follow-up polls reuse the client ID; the provider ID is the latest one seen
+#[derive(Debug)]
+pub struct RequestIdTracker(Mutex<RequestIds>);
+
+impl Default for RequestIdTracker {
+    fn default() -> Self {
+        Self(Mutex::new(RequestIds::generate()))
+    }
+}
+
+impl RequestIdTracker {
+    pub fn ids(&self) -> RequestIds {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn tag(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        self.ids().tag(request)
+    }
+
+    pub fn record(&self, headers: &HeaderMap) {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(headers);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn provider_id_skips_an_echo_of_the_client_id() {
+        let mut ids = RequestIds::generate();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-request-id",
+            HeaderValue::from_str(&ids.client).expect("header value"),
+        );
+
+        ids.record(&headers);
+        assert_eq!(ids.provider, None);
+
+        headers.insert("request-id", HeaderValue::from_static("req_011CPx"));
+        ids.record(&headers);
+        assert_eq!(ids.provider.as_deref(), Some("req_011CPx"));
+    }
+}
@@ -0,0 +1,430 @@
+// Opt-in logging of provider requests and responses for debugging
+// Credentials are redacted from headers, query strings and JSON bodies before anything is logged
+
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+/// Setting key holding the serialized `RequestLogSettings`
+pub const REQUEST_LOG_SETTINGS_KEY: &str = "request_log_settings_json";
+
+const REDACTED: &str = "[REDACTED]";
+const DEFAULT_MAX_BODY_CHARS: usize = 4096;
+
+static REQUEST_LOG_SETTINGS: RwLock<RequestLogSettings> = RwLock::new(RequestLogSettings {
+    enabled: false,
+    level: RequestLogLevel::Debug,
+    max_body_chars: DEFAULT_MAX_BODY_CHARS,
+});
+
+/// Rules added at runtime on top of `Redactor::default()`
+static EXTRA_RULES: RwLock<Vec<RedactionRule>> = RwLock::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestLogLevel {
+    Error,
+    Warn,
+    Info,
+    #[default]
+    Debug,
+    Trace,
+}
+
+impl From<RequestLogLevel> for log::Level {
+    fn from(level: RequestLogLevel) -> Self {
+        match level {
+            RequestLogLevel::Error => log::Level::Error,
+            RequestLogLevel::Warn => log::Level::Warn,
+            RequestLogLevel::Info => log::Level::Info,
+            RequestLogLevel::Debug => log::Level::Debug,
+            RequestLogLevel::Trace => log::Level::Trace,
+        }
+    }
+}
+
+/// Whether and how provider traffic is logged; off by default
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestLogSettings {
+    pub enabled: bool,
+    pub level: RequestLogLevel,
+    /// Longer bodies are truncated, 0 logs them in full
+    #[serde(rename = "maxBodyChars")]
+    pub max_body_chars: usize,
+}
+
+impl Default for RequestLogSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            level: RequestLogLevel::Debug,
+            max_body_chars: DEFAULT_MAX_BODY_CHARS,
+        }
+    }
+}
+
+/// Something to hide from logged traffic; names match case-insensitively
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedactionRule {
+    Header(String),
+    QueryParam(String),
+    JsonField(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    rules: Vec<RedactionRule>,
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        let headers = [
+            "authorization",
+            "proxy-authorization",
+            "x-api-key",
+            "api-key",
+            "x-goog-api-key",
+        ];
+        let query_params = ["key", "api_key", "access_token"];
+        let json_fields = ["api_key", "apiKey", "access_token", "refresh_token"];
+        let rules = headers
+            .into_iter()
+            .map(|name| RedactionRule::Header(name.to_string()))
+            .chain(
+                query_params
+                    .into_iter()
+                    .map(|name| RedactionRule::QueryParam(name.to_string())),
+            )
+            .chain(
+                json_fields
+                    .into_iter()
+                    .map(|name| RedactionRule::JsonField(name.to_string())),
+            )
+            .collect();
+        Self { rules }
+    }
+}
+
+impl Redactor {
+    pub fn with_rule(mut self, rule: RedactionRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    fn hides_header(&self, name: &str) -> bool {
+        self.rules.iter().any(|rule| match rule {
+            RedactionRule::Header(header) => header.eq_ignore_ascii_case(name),
+            _ => false,
+        })
+    }
+
+    fn hides_query_param(&self, name: &str) -> bool {
+        self.rules.iter().any(|rule| match rule {
+            RedactionRule::QueryParam(param) => param.eq_ignore_ascii_case(name),
+            _ => false,
+        })
+    }
+
+    fn hides_json_field(&self, name: &str) -> bool {
+        self.rules.iter().any(|rule| match rule {
+            RedactionRule::JsonField(field) => field.eq_ignore_ascii_case(name),
+            _ => false,
+        })
+    }
+
+    /// Headers sorted by lowercase name, with secret values replaced
+    pub fn redact_headers(&self, headers: &HashMap<String, String>) -> BTreeMap<String, String> {
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let value = if self.hides_header(name) {
+                    REDACTED.to_string()
+                } else {
+                    value.clone()
+                };
+                (name.to_lowercase(), value)
+            })
+            .collect()
+    }
+
+    /// Replace the values of secret query parameters, keeping the rest of the URL as-is
+    pub fn redact_url(&self, url: &str) -> String {
+        let (without_fragment, fragment) = match url.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment)),
+            None => (url, None),
+        };
+        let Some((path, query)) = without_fragment.split_once('?') else {
+            return url.to_string();
+        };
+
+        let query = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if self.hides_query_param(name) => {
+                    format!("{}={}", name, REDACTED)
+                }
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        match fragment {
+            Some(fragment) => format!("{}?{}#{}", path, query, fragment),
+            None => format!("{}?{}", path, query),
+        }
+    }
+
+    /// Copy of `body` with secret fields replaced at any depth
+    pub fn redact_json(&self, body: &Value) -> Value {
+        match body {
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(key, value)| {
+                        let value = if self.hides_json_field(key) {
+                            Value::String(REDACTED.to_string())
+                        } else {
+                            self.redact_json(value)
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect(),
+            ),
+            Value::Array(items) => {
+                Value::Array(items.iter().map(|item| self.redact_json(item)).collect())
+            }
+            other => other.clone(),
+        }
+    }
+
+    /// Redacted, truncated text of a response body; JSON bodies are redacted field by field
+    pub fn redact_body(&self, body: &str, max_chars: usize) -> String {
+        let body = match serde_json::from_str::<Value>(body) {
+            Ok(json) if json.is_object() || json.is_array() => self.redact_json(&json).to_string(),
+            _ => body.to_string(),
+        };
+        truncate_chars(body, max_chars)
+    }
+
+    pub fn format_request(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &HashMap<String, String>,
+        body: Option<&Value>,
+        max_body_chars: usize,
+    ) -> String {
+        let mut line = format!(
+            "{} {} headers={:?}",
+            method,
+            self.redact_url(url),
+            self.redact_headers(headers)
+        );
+        if let Some(body) = body {
+            let body = truncate_chars(self.redact_json(body).to_string(), max_body_chars);
+            line.push_str(&format!(" body={}", body));
+        }
+        line
+    }
+
+    pub fn format_response(
+        &self,
+        url: &str,
+        status: u16,
+        body: Option<&str>,
+        max_body_chars: usize,
+    ) -> String {
+        let mut line = format!("{} {}", status, self.redact_url(url));
+        if let Some(body) = body {
+            line.push_str(&format!(" body={}", self.redact_body(body, max_body_chars)));
+        }
+        line
+    }
+}
+
+fn truncate_chars(text: String, max_chars: usize) -> String {
+    if max_chars == 0 {
+        return text;
+    }
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!(
+            "{}... ({} more chars)",
+            &text[..cut],
+            text[cut..].chars().count()
+        ),
+        None => text,
+    }
+}
+
+/// Hide another header, query parameter or JSON field in all logged traffic
+pub fn add_redaction_rule(rule: RedactionRule) {
+    let mut rules = EXTRA_RULES.write().unwrap_or_else(|e| e.into_inner());
+    if !rules.contains(&rule) {
+        rules.push(rule);
+    }
+}
+
+/// The default rules plus any added with `add_redaction_rule`
+pub fn redactor() -> Redactor {
+    let extra = EXTRA_RULES.read().unwrap_or_else(|e| e.into_inner());
+    extra
+        .iter()
+        .cloned()
+        .fold(Redactor::default(), Redactor::with_rule)
+}
+
+pub fn request_log_settings() -> RequestLogSettings {
+    REQUEST_LOG_SETTINGS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+pub fn set_request_log_settings(settings: RequestLogSettings) {
+    *REQUEST_LOG_SETTINGS
+        .write()
+        .unwrap_or_else(|e| e.into_inner()) = settings;
+}
+
+/// Load persisted settings at startup
+pub async fn load_request_log_settings(api_keys: &ApiKeyManager) -> Result<(), String> {
+    if let Some(raw) = api_keys.get_setting(REQUEST_LOG_SETTINGS_KEY).await? {
+        let settings: RequestLogSettings = serde_json::from_str(&raw)
+            .map_err(|e| format!("Failed to parse request log settings: {}", e))?;
+        set_request_log_settings(settings);
+    }
+    Ok(())
+}
+
+/// Persist and install new settings
+pub async fn save_request_log_settings(
+    api_keys: &ApiKeyManager,
+    settings: RequestLogSettings,
+) -> Result<(), String> {
+    set_request_log_settings(settings.clone());
+    let raw = serde_json::to_string(&settings)
+        .map_err(|e| format!("Failed to serialize request log settings: {}", e))?;
+    api_keys.set_setting(REQUEST_LOG_SETTINGS_KEY, &raw).await
+}
+
+/// Settings and level to log with, or `None` when nothing would be written
+fn active_settings() -> Option<(RequestLogSettings, log::Level)> {
+    let settings = request_log_settings();
+    let level = log::Level::from(settings.level);
+    (settings.enabled && log::log_enabled!(level)).then_some((settings, level))
+}
+
+/// Log an outgoing request when request logging is enabled
+/// Pass `None` for bodies that are not JSON, such as multipart forms
+pub fn log_request<T: Serialize + ?Sized>(
+    tag: &str,
+    method: &str,
+    url: &str,
+    headers: &HashMap<String, String>,
+    body: Option<&T>,
+) {
+    if let Some((settings, level)) = active_settings() {
+        let body = body.and_then(|body| serde_json::to_value(body).ok());
+        let line =
+            redactor().format_request(method, url, headers, body.as_ref(), settings.max_body_chars);
+        log::log!(level, "[{}] -> {}", tag, line);
+    }
+}
+
+/// Log a response status, and its body when it has been read, when request logging is enabled
+pub fn log_response(tag: &str, url: &str, status: u16, body: Option<&str>) {
+    if let Some((settings, level)) = active_settings() {
+        let line = redactor().format_response(url, status, body, settings.max_body_chars);
+        log::log!(level, "[{}] <- {}", tag, line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SECRET: &str = "sk-live-1234567890";
+
+    #[test]
+    fn logged_request_never_contains_the_raw_key() {
+        let headers = HashMap::from([
+            ("Authorization".to_string(), format!("Bearer {}", SECRET)),
+            ("x-api-key".to_string(), SECRET.to_string()),
+            ("Content-Type".to_string(), "application/json".to_string()),
+        ]);
+        let body = json!({
+            "model": "gpt-4o",
+            "messages": [{"role": "user", "content": "hi"}],
+            "auth": {"api_key": SECRET}
+        });
+
+        let line = Redactor::default().format_request(
+            "POST",
+            &format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/gemini:generate?alt=sse&key={}",
+                SECRET
+            ),
+            &headers,
+            Some(&body),
+            0,
+        );
+
+        assert!(!line.contains(SECRET), "leaked key in {}", line);
+        assert!(line.contains("alt=sse&key=[REDACTED]"));
+        assert!(line.contains("\"authorization\": \"[REDACTED]\""));
+        assert!(line.contains("\"content-type\": \"application/json\""));
+        assert!(line.contains("\"content\":\"hi\""));
+    }
+
+    #[test]
+    fn logged_response_redacts_json_bodies_and_truncates() {
+        let redactor = Redactor::default();
+
+        let line = redactor.format_response(
+            "https://api.example.com/token",
+            200,
+            Some(&format!(
+                r#"{{"access_token":"{}","expires_in":3600}}"#,
+                SECRET
+            )),
+            0,
+        );
+        assert!(!line.contains(SECRET), "leaked key in {}", line);
+        assert!(line.contains("\"expires_in\":3600"));
+
+        assert_eq!(
+            redactor.redact_body("abcdefghij", 4),
+            "abcd... (6 more chars)"
+        );
+    }
+
+    #[test]
+    fn custom_rules_extend_the_defaults() {
+        let redactor =
+            Redactor::default().with_rule(RedactionRule::Header("X-Custom-Token".to_string()));
+        let headers = HashMap::from([("x-custom-token".to_string(), SECRET.to_string())]);
+
+        let redacted = redactor.redact_headers(&headers);
+
+        assert_eq!(redacted["x-custom-token"], REDACTED);
+        assert_eq!(
+            Redactor::default().redact_headers(&headers)["x-custom-token"],
+            SECRET
+        );
+    }
+
+    #[test]
+    fn urls_without_secret_params_are_unchanged() {
+        let redactor = Redactor::default();
+        assert_eq!(
+            redactor.redact_url("https://api.example.com/v1/models?limit=5#top"),
+            "https://api.example.com/v1/models?limit=5#top"
+        );
+        assert_eq!(
+            redactor.redact_url("https://api.example.com/v1?KEY=abc#top"),
+            "https://api.example.com/v1?KEY=[REDACTED]#top"
+        );
+    }
+}
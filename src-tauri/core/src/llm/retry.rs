@@ -0,0 +1,463 @@
+// Retry policy for provider HTTP requests
+// Retries throttled (429) and transient (408/5xx, network) failures with exponential backoff,
+// optionally capped by a budget shared with the fallback chain
+
+use crate::llm::error::{is_retriable_status, retry_after_from_headers, LlmError};
+use crate::llm::http_client::SendWithMiddleware;
+use crate::llm::types::ProviderConfig;
+use async_trait::async_trait;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_BASE_DELAY_MS: u64 = 500;
+const DEFAULT_MAX_DELAY_MS: u64 = 30_000;
+
+/// Backoff settings, configurable per provider through `ProviderConfig::retry_policy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    #[serde(rename = "maxRetries")]
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on every following one
+    #[serde(rename = "baseDelayMs")]
+    pub base_delay_ms: u64,
+    /// Upper bound for a single wait, including server-provided `Retry-After`
+    #[serde(rename = "maxDelayMs")]
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay_ms: DEFAULT_BASE_DELAY_MS,
+            max_delay_ms: DEFAULT_MAX_DELAY_MS,
+        }
+    }
+}
+
+/// Cap on the HTTP attempts one logical request may make, across retries, stream replays and
+/// fallback providers; clones share the same count
+#[derive(Debug, Clone)]
+pub struct RetryBudget {
+    remaining: Arc<AtomicU32>,
+}
+
+impl RetryBudget {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            remaining: Arc::new(AtomicU32::new(max_attempts)),
+        }
+    }
+
+    /// Take one attempt from the budget, or return false when none are left
+    pub fn try_spend(&self) -> bool {
+        self.remaining
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.remaining.load(Ordering::SeqCst)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining() == 0
+    }
+}
+
+/// Spend an attempt from `budget`; requests without a budget are only bound by their policy
+fn spend(budget: Option<&RetryBudget>) -> bool {
+    budget.is_none_or(RetryBudget::try_spend)
+}
+
+impl RetryPolicy {
+    /// Policy configured for a provider, falling back to the defaults
+    pub fn for_provider(config: &ProviderConfig) -> Self {
+        config.retry_policy.unwrap_or_default()
+    }
+
+    /// This policy for an idempotent request, or one that sends exactly once otherwise
+    pub fn for_idempotency(self, idempotent: bool) -> Self {
+        if idempotent {
+            self
+        } else {
+            Self {
+                max_retries: 0,
+                ..self
+            }
+        }
+    }
+
+    /// Wait before retry number `retry` (0-based)
+    /// A `Retry-After` hint wins over the computed backoff; both are capped at `max_delay_ms`
+    pub fn backoff_delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        let max_delay = Duration::from_millis(self.max_delay_ms);
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(max_delay);
+        }
+
+        let exponential = self
+            .base_delay_ms
+            .saturating_mul(1u64 << retry.min(32))
+            .min(self.max_delay_ms);
+        // Equal jitter: keep half of the delay, randomize the other half
+        let half = exponential / 2;
+        let jitter = if half > 0 {
+            rand::thread_rng().gen_range(0..=half)
+        } else {
+            0
+        };
+        Duration::from_millis(exponential - half + jitter)
+    }
+
+    /// Send a request through the installed middleware, retrying retriable statuses and
+    /// network errors
+    /// The last response is returned as-is once retries are exhausted so callers keep
+    /// their own error reporting for non-success statuses
+    pub async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, LlmError> {
+        self.send_within(request, None).await
+    }
+
+    /// `send`, with every attempt also taken from `budget`
+    /// Once the budget runs out the last response or error is returned as if retries were
+    /// exhausted; a budget that is already empty fails before sending anything
+    pub async fn send_within(
+        &self,
+        request: reqwest::RequestBuilder,
+        budget: Option<&RetryBudget>,
+    ) -> Result<reqwest::Response, LlmError> {
+        self.send_inner(request, budget, None).await
+    }
+
+    /// `send_within`, letting `rotation` swap in a request signed with another key after
+    /// each 429; a swapped request is retried without waiting for the backoff
+    pub async fn send_rotating_within(
+        &self,
+        request: reqwest::RequestBuilder,
+        budget: Option<&RetryBudget>,
+        rotation: &mut dyn RotateOnThrottle,
+    ) -> Result<reqwest::Response, LlmError> {
+        self.send_inner(request, budget, Some(rotation)).await
+    }
+
+    async fn send_inner(
+        &self,
+        mut request: reqwest::RequestBuilder,
+        budget: Option<&RetryBudget>,
+        mut rotation: Option<&mut dyn RotateOnThrottle>,
+    ) -> Result<reqwest::Response, LlmError> {
+        if !spend(budget) {
+            return Err(LlmError::Other(
+                "Retry budget exhausted / 重试次数已用尽".to_string(),
+            ));
+        }
+        let mut retry = 0;
+        loop {
+            let attempt = match request.try_clone() {
+                Some(attempt) => attempt,
+                // Streaming bodies cannot be replayed, send once
+                None => return request.send_with_middleware().await,
+            };
+
+            let delay = match attempt.send_with_middleware().await {
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    if retry >= self.max_retries || !is_retriable_status(status) || !spend(budget) {
+                        return Ok(response);
+                    }
+                    let rotated = match rotation.as_mut() {
+                        Some(rotation) if status == 429 => rotation.rotate().await,
+                        _ => None,
+                    };
+                    if let Some(rotated) = rotated {
+                        log::warn!(
+                            "[Retry] {} returned HTTP 429, retrying {}/{} with another key",
+                            response.url(),
+                            retry + 1,
+                            self.max_retries
+                        );
+                        request = rotated;
+                        retry += 1;
+                        continue;
+                    }
+                    let delay =
+                        self.backoff_delay(retry, retry_after_from_headers(response.headers()));
+                    log::warn!(
+                        "[Retry] {} returned HTTP {}, retrying {}/{} after {}ms",
+                        response.url(),
+                        status,
+                        retry + 1,
+                        self.max_retries,
+                        delay.as_millis()
+                    );
+                    delay
+                }
+                Err(err) => {
+                    if retry >= self.max_retries || !err.is_retriable() || !spend(budget) {
+                        return Err(err);
+                    }
+                    let delay = self.backoff_delay(retry, None);
+                    log::warn!(
+                        "[Retry] Request failed: {}, retrying {}/{} after {}ms",
+                        err,
+                        retry + 1,
+                        self.max_retries,
+                        delay.as_millis()
+                    );
+                    delay
+                }
+            };
+
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+/// Hook for `RetryPolicy::send_rotating_within`, run on every 429 that is about to be retried
+#[async_trait]
+pub trait RotateOnThrottle: Send {
+    /// The request signed with another credential, or `None` to back off and resend the
+    /// throttled one
+    async fn rotate(&mut self) -> Option<reqwest::RequestBuilder>;
+}
+
+/// Decides whether a streamed request that failed part-way may be sent again
+/// Only allowed until the first event reaches the consumer, since a replay would repeat
+/// output it already has; metadata such as request IDs and rate limits is sent again with
+/// every attempt and does not count as delivered
+#[derive(Debug, Clone)]
+pub struct StreamReplay {
+    policy: RetryPolicy,
+    budget: Option<RetryBudget>,
+    retries: u32,
+    delivered: bool,
+}
+
+impl StreamReplay {
+    /// Pass a policy built with `for_idempotency` so non-idempotent requests never replay
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy,
+            budget: None,
+            retries: 0,
+            delivered: false,
+        }
+    }
+
+    /// Stop replaying once `budget` is used up; the resent request itself spends from it
+    pub fn with_budget(self, budget: Option<&RetryBudget>) -> Self {
+        Self {
+            budget: budget.cloned(),
+            ..self
+        }
+    }
+
+    /// Record that an event was handed to the consumer
+    pub fn mark_delivered(&mut self) {
+        self.delivered = true;
+    }
+
+    /// Wait before resending after `err`, or `None` when the error has to be reported
+    pub fn next_delay(&mut self, err: &LlmError) -> Option<Duration> {
+        if self.delivered
+            || !err.is_retriable()
+            || self.retries >= self.policy.max_retries
+            || self.budget.as_ref().is_some_and(RetryBudget::is_exhausted)
+        {
+            return None;
+        }
+        let retry_after = match err {
+            LlmError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        };
+        let delay = self.policy.backoff_delay(self.retries, retry_after);
+        self.retries += 1;
+        Some(delay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::testing::mock_server::start_sequence_server;
+    use std::sync::atomic::Ordering;
+
+    fn responses(statuses: &[u16]) -> Vec<(u16, String)> {
+        statuses
+            .iter()
+            .map(|status| (*status, format!("status {}", status)))
+            .collect()
+    }
+
+    fn fast_policy(max_retries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_retries,
+            base_delay_ms: 1,
+            max_delay_ms: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limited_requests_until_success() {
+        let (base_url, hits) =
+            start_sequence_server(responses(&[429, 429, 200])).expect("start mock server");
+        let client = reqwest::Client::new();
+
+        let response = fast_policy(3)
+            .send(
+                client
+                    .post(format!("{}/images/generations", base_url))
+                    .body("{}"),
+            )
+            .await
+            .expect("send request");
+
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn returns_last_response_when_retries_are_exhausted() {
+        let (base_url, hits) =
+            start_sequence_server(responses(&[429, 429, 200])).expect("start mock server");
+        let client = reqwest::Client::new();
+
+        let response = fast_policy(1)
+            .send(client.get(&base_url))
+            .await
+            .expect("send request");
+
+        assert_eq!(response.status().as_u16(), 429);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let (base_url, hits) =
+            start_sequence_server(responses(&[400, 200])).expect("start mock server");
+        let client = reqwest::Client::new();
+
+        let response = fast_policy(3)
+            .send(client.get(&base_url))
+            .await
+            .expect("send request");
+
+        assert_eq!(response.status().as_u16(), 400);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_requests_are_sent_once() {
+        let (base_url, hits) =
+            start_sequence_server(responses(&[503, 200])).expect("start mock server");
+        let client = reqwest::Client::new();
+
+        let response = fast_policy(3)
+            .for_idempotency(false)
+            .send(client.post(&base_url).body("{}"))
+            .await
+            .expect("send request");
+
+        assert_eq!(response.status().as_u16(), 503);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn budget_caps_retries_and_replays_together() {
+        let (base_url, hits) =
+            start_sequence_server(responses(&[503, 503, 503, 200])).expect("start mock server");
+        let client = reqwest::Client::new();
+        let budget = RetryBudget::new(3);
+
+        let response = fast_policy(5)
+            .send_within(client.get(&base_url), Some(&budget))
+            .await
+            .expect("send request");
+        assert_eq!(response.status().as_u16(), 503);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        let mut replay = StreamReplay::new(fast_policy(5)).with_budget(Some(&budget));
+        assert!(replay
+            .next_delay(&LlmError::Network("connection reset".to_string()))
+            .is_none());
+        let err = fast_policy(5)
+            .send_within(client.get(&base_url), Some(&budget))
+            .await
+            .expect_err("budget exhausted");
+        assert!(
+            err.to_string().contains("Retry budget exhausted"),
+            "{}",
+            err
+        );
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn stream_replay_stops_once_an_event_was_delivered() {
+        let dropped = LlmError::Network("connection reset".to_string());
+
+        let mut replay = StreamReplay::new(fast_policy(3));
+        assert!(replay.next_delay(&dropped).is_some());
+        assert!(replay
+            .next_delay(&LlmError::Auth("bad key".to_string()))
+            .is_none());
+        replay.mark_delivered();
+        assert!(replay.next_delay(&dropped).is_none());
+
+        let mut replay = StreamReplay::new(fast_policy(3).for_idempotency(false));
+        assert!(replay.next_delay(&dropped).is_none());
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_within_jitter_bounds() {
+        let policy = RetryPolicy::default();
+        for retry in 0..4 {
+            let full = 500u64 << retry;
+            let delay = policy.backoff_delay(retry, None).as_millis() as u64;
+            assert!(
+                (full / 2..=full).contains(&delay),
+                "retry {} delay {}",
+                retry,
+                delay
+            );
+        }
+        assert!(policy.backoff_delay(20, None) <= Duration::from_millis(DEFAULT_MAX_DELAY_MS));
+    }
+
+    #[test]
+    fn backoff_honors_retry_after_up_to_max_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(
+            policy.backoff_delay(0, Some(Duration::from_secs(2))),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            policy.backoff_delay(0, Some(Duration::from_secs(120))),
+            Duration::from_millis(DEFAULT_MAX_DELAY_MS)
+        );
+    }
+
+    #[test]
+    fn deserializes_partial_config_with_defaults() {
+        let policy: RetryPolicy = serde_json::from_str(r#"{"maxRetries": 5}"#).expect("parse");
+        assert_eq!(
+            policy,
+            RetryPolicy {
+                max_retries: 5,
+                ..RetryPolicy::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,126 @@
+// Streaming metrics
+// Time to first token, total duration and output rate of one streamed response, sent as the
+// stream's final event whether it finished, failed or was cancelled
+
+use crate::llm::types::StreamEvent;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StreamMetrics {
+    /// From request start to the first text, reasoning or tool-call delta; None if none arrived
+    #[serde(rename = "timeToFirstTokenMs")]
+    pub time_to_first_token_ms: Option<u64>,
+    #[serde(rename = "durationMs")]
+    pub duration_ms: u64,
+    /// Non-empty deltas received, each counted as one token
+    #[serde(rename = "deltaCount")]
+    pub delta_count: u32,
+    /// Deltas per second between the first delta and the end of the stream
+    #[serde(rename = "tokensPerSecond")]
+    pub tokens_per_second: Option<f64>,
+    pub cancelled: bool,
+}
+
+/// Watches the events of one stream, started when the request is made
+#[derive(Debug)]
+pub struct StreamMetricsRecorder {
+    started: Instant,
+    first_token: Option<Duration>,
+    delta_count: u32,
+}
+
+impl StreamMetricsRecorder {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            first_token: None,
+            delta_count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, event: &StreamEvent) {
+        let is_token = match event {
+            StreamEvent::TextDelta { text } | StreamEvent::ReasoningDelta { text, .. } => {
+                !text.is_empty()
+            }
+            StreamEvent::ToolCallDelta {
+                arguments_chunk, ..
+            } => !arguments_chunk.is_empty(),
+            _ => false,
+        };
+        if !is_token {
+            return;
+        }
+        let started = self.started;
+        self.first_token.get_or_insert_with(|| started.elapsed());
+        self.delta_count += 1;
+    }
+
+    pub fn finish(&self, cancelled: bool) -> StreamMetrics {
+        let duration = self.started.elapsed();
+        let tokens_per_second = self
+            .first_token
+            .map(|first| duration.saturating_sub(first).as_secs_f64())
+            .filter(|secs| *secs > 0.0)
+            .map(|secs| f64::from(self.delta_count) / secs);
+        StreamMetrics {
+            time_to_first_token_ms: self.first_token.map(|first| first.as_millis() as u64),
+            duration_ms: duration.as_millis() as u64,
+            delta_count: self.delta_count,
+            tokens_per_second,
+            cancelled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+
+    fn text(text: &str) -> StreamEvent {
+        StreamEvent::TextDelta {
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn time_to_first_token_runs_from_start_to_first_text_delta() {
+        let mut recorder = StreamMetricsRecorder::start();
+        sleep(Duration::from_millis(20));
+        // Events carrying no output do not end the wait for the first token
+        recorder.observe(&StreamEvent::Start {
+            model: "gpt-4o".to_string(),
+            system_fingerprint: None,
+        });
+        recorder.observe(&text(""));
+        sleep(Duration::from_millis(20));
+        recorder.observe(&text("Hel"));
+        sleep(Duration::from_millis(20));
+        recorder.observe(&text("lo"));
+
+        let metrics = recorder.finish(false);
+
+        let ttft = metrics.time_to_first_token_ms.expect("ttft");
+        assert!(ttft >= 40, "{}", ttft);
+        assert!(ttft < metrics.duration_ms);
+        assert_eq!(metrics.delta_count, 2);
+        assert!(metrics.tokens_per_second.is_some_and(|rate| rate > 0.0));
+    }
+
+    #[test]
+    fn stream_without_output_has_no_rate() {
+        let mut recorder = StreamMetricsRecorder::start();
+        recorder.observe(&StreamEvent::Done {
+            finish_reason: None,
+        });
+
+        let metrics = recorder.finish(true);
+
+        assert_eq!(metrics.time_to_first_token_ms, None);
+        assert_eq!(metrics.tokens_per_second, None);
+        assert_eq!(metrics.delta_count, 0);
+        assert!(metrics.cancelled);
+    }
+}
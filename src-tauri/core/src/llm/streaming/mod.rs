@@ -1 +1,2 @@
+pub mod stall;
 pub mod stream_handler;
@@ -0,0 +1,56 @@
+// Stalled stream detection
+// A provider can open the stream and then go quiet; the wait for each chunk is capped apart
+// from the overall request timeout, and any bytes, keep-alive comments included, restart it
+
+use crate::llm::error::LlmError;
+use crate::llm::types::StreamTextRequest;
+use futures_util::{Stream, StreamExt};
+use std::time::Duration;
+
+/// Stall window for requests that do not set `stallTimeoutMs`
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Stall window of `request`; None when the check is disabled with `stallTimeoutMs: 0`
+pub fn stall_timeout(request: &StreamTextRequest) -> Option<Duration> {
+    match request.stall_timeout_ms {
+        Some(0) => None,
+        Some(ms) => Some(Duration::from_millis(ms)),
+        None => Some(DEFAULT_STALL_TIMEOUT),
+    }
+}
+
+/// Next item of `stream`, or `LlmError::StreamStalled` once `window` passes without one
+pub async fn next_chunk<S>(
+    stream: &mut S,
+    window: Option<Duration>,
+) -> Result<Option<S::Item>, LlmError>
+where
+    S: Stream + Unpin,
+{
+    let Some(window) = window else {
+        return Ok(stream.next().await);
+    };
+    tokio::time::timeout(window, stream.next())
+        .await
+        .map_err(|_| LlmError::StreamStalled { idle: window })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+
+    #[tokio::test]
+    async fn a_quiet_stream_stalls_after_the_window() {
+        let window = Duration::from_millis(50);
+        let mut quiet = stream::pending::<Vec<u8>>();
+
+        let result = next_chunk(&mut quiet, Some(window)).await;
+
+        assert_eq!(result, Err(LlmError::StreamStalled { idle: window }));
+        let mut chunks = stream::iter([b": keep-alive\n\n".to_vec()]);
+        let chunk = next_chunk(&mut chunks, Some(window)).await;
+        assert_eq!(chunk, Ok(Some(b": keep-alive\n\n".to_vec())));
+        assert_eq!(next_chunk(&mut chunks, None).await, Ok(None));
+    }
+}
@@ -1,23 +1,37 @@
 use crate::llm::auth::api_key_manager::ApiKeyManager;
-use crate::llm::protocols::stream_parser::StreamParseState;
-use crate::llm::providers::provider::ProviderContext;
+use crate::llm::cancellation::cancellable;
+use crate::llm::context_window;
+use crate::llm::error::LlmError;
+use crate::llm::http_client;
+use crate::llm::image_input;
+use crate::llm::moderation;
+use crate::llm::protocols::stream_parser::{
+    parse_frame, split_frame, take_frame, StreamParseState,
+};
+use crate::llm::providers::provider::{BuiltRequest, CredentialRotation, ProviderContext};
 use crate::llm::providers::provider_registry::ProviderRegistry;
+use crate::llm::rate_limit::RateLimitInfo;
+use crate::llm::request_log;
+use crate::llm::retry::{RetryBudget, RetryPolicy};
+use crate::llm::stream_metrics::StreamMetricsRecorder;
+use crate::llm::streaming::stall;
 use crate::llm::testing::fixtures::FixtureInput;
 use crate::llm::testing::{Recorder, RecordingContext, TestConfig, TestMode};
+use crate::llm::tokenize::UsageFallback;
 use crate::llm::tracing::types::{float_attr, int_attr};
 use crate::llm::tracing::TraceWriter;
 use crate::llm::types::{StreamEvent, StreamTextRequest};
-use futures_util::StreamExt;
 use serde_json;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::{Arc, OnceLock};
+use std::sync::Arc;
 use std::time::Duration;
 use tauri::{Emitter, Manager};
-use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
 
 static REQUEST_COUNTER: AtomicU32 = AtomicU32::new(1000);
-static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+/// Overall cap for a streaming request that does not set its own timeout
+const DEFAULT_STREAM_REQUEST_TIMEOUT: Duration = Duration::from_secs(3000);
 
 /// Token usage info: (input_tokens, output_tokens, total_tokens, cached_input_tokens, cache_creation_input_tokens)
 type TokenUsageInfo = (i32, i32, Option<i32>, Option<i32>, Option<i32>);
@@ -37,6 +51,7 @@ impl StreamHandler {
         window: tauri::Window,
         request: StreamTextRequest,
         request_id: String,
+        cancel_token: Option<CancellationToken>,
     ) -> Result<String, String> {
         // Use provided request_id if non-zero, otherwise generate one
         let request_id = if request_id != "0" {
@@ -46,13 +61,43 @@ impl StreamHandler {
         };
         let event_name = format!("llm-stream-{}", request_id);
 
+        // Measured from here so time to first token includes model resolution and the request
+        let mut metrics = StreamMetricsRecorder::start();
+        let result = self
+            .stream_events(
+                &window,
+                request,
+                request_id.clone(),
+                &event_name,
+                cancel_token.as_ref(),
+                &mut metrics,
+            )
+            .await;
+        let cancelled = cancel_token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled);
+        let metrics = metrics.finish(cancelled);
+        log::info!("[LLM Stream {}] Stream metrics: {:?}", request_id, metrics);
+        let _ = window.emit(&event_name, &StreamEvent::Metrics(metrics));
+        result
+    }
+
+    async fn stream_events(
+        &self,
+        window: &tauri::Window,
+        mut request: StreamTextRequest,
+        request_id: String,
+        event_name: &str,
+        cancel_token: Option<&CancellationToken>,
+        metrics: &mut StreamMetricsRecorder,
+    ) -> Result<String, String> {
         log::info!(
             "[LLM Stream {}] Starting stream completion for model: {}",
             request_id,
             request.model
         );
 
-        let (model_key, provider_id, provider_model_name) =
+        let (model_key, provider_id, provider_model_name, context_window) =
             self.resolve_model_info(&request.model).await?;
         log::info!(
             "[LLM Stream {}] Resolved model: {}, provider: {}",
@@ -71,7 +116,29 @@ impl StreamHandler {
             provider_config.name,
             provider_config.protocol
         );
+        if let Some(template) = request.template.take() {
+            template.render_messages(&mut request.messages)?;
+        }
+        context_window::fit_request(&mut request, context_window);
+        // Loading the model list is only worth it when there are images to check
+        if image_input::has_image_content(&request.messages) {
+            let models = self.api_keys.load_models_config().await?;
+            image_input::check(
+                &request.messages,
+                &model_key,
+                &models,
+                provider.capabilities().supports_vision,
+                request.image_input_check.unwrap_or_default(),
+            )?;
+        }
+        // A dry run makes no network calls, moderation included
+        let dry_run = request.dry_run.unwrap_or(false);
+        if !dry_run {
+            let prompt = moderation::latest_user_text(&request.messages);
+            moderation::precheck(&self.api_keys, &self.registry, &provider_id, &prompt).await?;
+        }
 
+        let retry_budget = request.max_attempts.map(RetryBudget::new);
         let provider_ctx = ProviderContext {
             provider_config,
             api_key_manager: &self.api_keys,
@@ -82,8 +149,24 @@ impl StreamHandler {
             max_tokens: request.max_tokens,
             top_p: request.top_p,
             top_k: request.top_k,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.as_deref(),
+            response_format: request.response_format.as_ref(),
+            user_id: request.user_id.as_deref(),
+            base_url_override: request.base_url_override.as_deref(),
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            cancel_token,
+            stream: true,
+            logprobs: request.logprobs.unwrap_or(false),
+            top_logprobs: request.top_logprobs,
+            dry_run,
+            builtin_tools: request.builtin_tools.as_deref().unwrap_or_default(),
+            strip_headers: request.strip_headers.as_deref().unwrap_or_default(),
+            retry_budget: retry_budget.as_ref(),
+            reasoning_effort: request.reasoning_effort,
+            idempotent: request.idempotent.unwrap_or(true),
         };
 
         let built_request = provider.build_complete_request(&provider_ctx).await?;
@@ -92,6 +175,17 @@ impl StreamHandler {
             request_id,
             built_request.url
         );
+        if provider_ctx.dry_run {
+            log::info!("[LLM Stream {}] Dry run, request not sent", request_id);
+            let _ = window.emit(event_name, &StreamEvent::DryRun(built_request.dry_run()));
+            let _ = window.emit(
+                event_name,
+                &StreamEvent::Done {
+                    finish_reason: None,
+                },
+            );
+            return Ok(request_id);
+        }
 
         // Initialize tracing span if trace_context is provided
         let mut trace_span_id: Option<String> = None;
@@ -261,106 +355,74 @@ impl StreamHandler {
             });
         }
 
-        let client = HTTP_CLIENT.get_or_init(|| {
-            reqwest::Client::builder()
-                .connect_timeout(Duration::from_secs(10))
-                .timeout(Duration::from_secs(3000)) // Add overall request timeout
-                .gzip(false)
-                .brotli(false)
-                .tcp_nodelay(true)
-                .pool_max_idle_per_host(5)
-                .build()
-                .expect("Failed to build HTTP client")
-        });
+        let client = http_client::shared_client()?;
         log::debug!("[LLM Stream {}] HTTP client ready", request_id);
 
-        let mut req_builder = client.post(&url);
-        for (key, value) in headers {
-            req_builder = req_builder.header(&key, &value);
-        }
-        req_builder = req_builder
-            .header("Accept", "text/event-stream")
-            .json(&body);
+        let log_tag = format!("LLM Stream {}", request_id);
+        request_log::log_request(&log_tag, "POST", &url, &headers, Some(&body));
+        // The shared client has no overall timeout, so every stream gets one here
+        // unless the caller opts out with `timeout_ms: 0`
+        let request_timeout =
+            http_client::resolve_timeout(request.timeout_ms, DEFAULT_STREAM_REQUEST_TIMEOUT);
+        let policy =
+            RetryPolicy::for_provider(provider_config).for_idempotency(built_request.idempotent);
+        // Requests rebuilt with another key keep going to the recording server, if any
+        let url_override = (url != built_request.url).then(|| url.clone());
+        let mut rotation = CredentialRotation::new(
+            provider.as_ref(),
+            &provider_ctx,
+            built_request,
+            |built: &BuiltRequest| {
+                let target = url_override.as_deref().unwrap_or(&built.url);
+                built.stream_request(&client, target, request_timeout)
+            },
+        );
 
         // log::info!("[LLM Stream {}] Sending HTTP request...", request_id);
 
-        // Retry configuration: exponential backoff with max 3 retries
-        const MAX_RETRIES: u32 = 3;
-        const BASE_DELAY_MS: u64 = 1000;
-
-        let mut response = None;
-        let mut last_error: Option<String> = None;
-
-        for attempt in 0..=MAX_RETRIES {
-            if attempt > 0 {
-                let delay_ms = BASE_DELAY_MS * (1 << (attempt - 1)); // Exponential backoff: 1s, 2s, 4s
-                log::info!(
-                    "[LLM Stream {}] Retrying request (attempt {}/{}), waiting {}ms",
-                    request_id,
-                    attempt,
-                    MAX_RETRIES,
-                    delay_ms
-                );
-                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-            }
-
-            match req_builder.try_clone() {
-                Some(builder) => match builder.send().await {
-                    Ok(resp) => {
-                        response = Some(resp);
-                        break;
-                    }
-                    Err(e) => {
-                        let err_msg = format!("{}", e);
-                        log::warn!(
-                            "[LLM Stream {}] Request attempt {}/{} failed: {}",
-                            request_id,
-                            attempt + 1,
-                            MAX_RETRIES + 1,
-                            err_msg
-                        );
-                        last_error = Some(err_msg);
-                    }
-                },
-                None => {
-                    // Request body cannot be cloned, try without cloning
-                    match req_builder.send().await {
-                        Ok(resp) => {
-                            response = Some(resp);
-                            break;
-                        }
-                        Err(e) => {
-                            let err_msg = format!("{}", e);
-                            log::warn!(
-                                "[LLM Stream {}] Request attempt {}/{} failed: {}",
-                                request_id,
-                                attempt + 1,
-                                MAX_RETRIES + 1,
-                                err_msg
-                            );
-                            last_error = Some(err_msg);
-                            // Cannot retry without cloning
-                            break;
-                        }
-                    }
-                }
+        // Retries throttled and transient failures before the stream starts, moving on to
+        // the next pooled key after a 429
+        let attempt = rotation.request();
+        let send_result = cancellable(
+            provider_ctx.cancel_token,
+            policy.send_rotating_within(attempt, provider_ctx.retry_budget, &mut rotation),
+        )
+        .await;
+        let built_request = rotation.built();
+        let response = match send_result {
+            Ok(result) => result.map_err(|err| {
+                log::error!("[LLM Stream {}] Request failed: {}", request_id, err);
+                format!("Request failed: {}", err)
+            })?,
+            Err(cancelled) => {
+                return Err(self.finish_cancelled(
+                    window,
+                    event_name,
+                    &request_id,
+                    &trace_span_id,
+                    cancelled,
+                ));
             }
-        }
+        };
 
-        let response = response.ok_or_else(|| {
-            let err = last_error.unwrap_or_else(|| "Request failed after all retries".to_string());
-            log::error!("[LLM Stream {}] Request failed: {}", request_id, err);
-            format!("Request failed: {}", err)
-        })?;
+        let mut request_ids = built_request.request_ids.clone();
+        request_ids.record(response.headers());
 
         let status = response.status().as_u16();
         if status >= 400 {
+            if status == 429 {
+                if let Some(api_key) = &built_request.api_key {
+                    self.api_keys.mark_key_rate_limited(api_key);
+                }
+            }
             let response_headers = response.headers().clone();
             let text = response.text().await.unwrap_or_default();
+            request_log::log_response(&log_tag, &url, status, Some(&text));
             log::error!(
-                "[LLM Stream {}] HTTP error {}: {}",
+                "[LLM Stream {}] HTTP error {} ({:?}): {}",
                 request_id,
                 status,
+                request_ids,
                 text
             );
             if let Some(recorder) = recorder.as_mut() {
@@ -379,27 +441,55 @@ impl StreamHandler {
                     })),
                 );
             }
+            let message =
+                LlmError::from_response_parts(status, &response_headers, text).to_string();
             let error_event = StreamEvent::Error {
-                message: format!("HTTP {}: {}", status, text),
+                message: message.clone(),
             };
-            let _ = window.emit(&event_name, &error_event);
-            return Err(format!("HTTP error {}", status));
+            let _ = window.emit(event_name, &error_event);
+            return Err(message);
         }
 
         let response_headers = response.headers().clone();
+        let _ = window.emit(event_name, &StreamEvent::RequestIds(request_ids));
+        if let Some(info) = RateLimitInfo::from_headers(&response_headers) {
+            let _ = window.emit(event_name, &StreamEvent::RateLimit(info));
+        }
         let mut stream = response.bytes_stream();
+        let stream_format = provider.stream_format();
         let mut buffer: Vec<u8> = Vec::new();
-        let mut state = StreamParseState::default();
+        let mut state = StreamParseState {
+            tolerant_json: provider.config().tolerant_stream_json,
+            ..StreamParseState::default()
+        };
         let mut chunk_count = 0;
         let mut response_text = String::new();
-        let stream_timeout = Duration::from_secs(300); // Timeout between chunks
+        let mut usage_fallback = UsageFallback::new(provider.protocol_type(), &request.messages);
+        let stall_window = stall::stall_timeout(&request);
         const STREAM_MAX_RETRIES: u32 = 3;
         const STREAM_BASE_DELAY_MS: u64 = 1000;
         let mut stream_error_retries: u32 = 0;
 
         'stream_loop: loop {
-            // Use timeout to prevent hanging on stream.next().await
-            let chunk_result = timeout(stream_timeout, stream.next()).await;
+            // A provider that keeps the stream open without sending anything fails as stalled
+            let chunk_result = match cancellable(
+                provider_ctx.cancel_token,
+                stall::next_chunk(&mut stream, stall_window),
+            )
+            .await
+            {
+                Ok(chunk_result) => chunk_result,
+                Err(cancelled) => {
+                    // Returning drops the body stream and its connection; the recorder is left unfinished
+                    return Err(self.finish_cancelled(
+                        window,
+                        event_name,
+                        &request_id,
+                        &trace_span_id,
+                        cancelled,
+                    ));
+                }
+            };
 
             let chunk = match chunk_result {
                 Ok(Some(result)) => result,
@@ -411,12 +501,8 @@ impl StreamHandler {
                     );
                     break;
                 }
-                Err(_) => {
-                    log::error!(
-                        "[LLM Stream {}] Stream timeout - no data received for {} seconds",
-                        request_id,
-                        stream_timeout.as_secs()
-                    );
+                Err(stalled) => {
+                    log::error!("[LLM Stream {}] {}", request_id, stalled);
                     // Record error in tracing span
                     if let Some(ref span_id) = trace_span_id {
                         let trace_writer = window.app_handle().state::<Arc<TraceWriter>>();
@@ -425,22 +511,16 @@ impl StreamHandler {
                             crate::llm::tracing::types::attributes::ERROR_TYPE.to_string(),
                             Some(serde_json::json!({
                                 "error_type": "stream_timeout",
-                                "timeout_seconds": stream_timeout.as_secs(),
-                                "message": format!("Stream timeout - no data received for {} seconds", stream_timeout.as_secs()),
+                                "timeout_ms": stall_window.map(|idle| idle.as_millis() as u64),
+                                "message": stalled.to_string(),
                             })),
                         );
                     }
                     let error_event = StreamEvent::Error {
-                        message: format!(
-                            "Stream timeout - no data received for {} seconds",
-                            stream_timeout.as_secs()
-                        ),
+                        message: stalled.to_string(),
                     };
-                    let _ = window.emit(&event_name, &error_event);
-                    return Err(format!(
-                        "Stream timeout - no data received for {} seconds",
-                        stream_timeout.as_secs()
-                    ));
+                    let _ = window.emit(event_name, &error_event);
+                    return Err(stalled.to_string());
                 }
             };
 
@@ -489,7 +569,7 @@ impl StreamHandler {
                     let error_event = StreamEvent::Error {
                         message: format!("Stream error: {}", err_msg),
                     };
-                    let _ = window.emit(&event_name, &error_event);
+                    let _ = window.emit(event_name, &error_event);
                     return Err(format!("Stream error: {}", err_msg));
                 }
             };
@@ -503,11 +583,8 @@ impl StreamHandler {
 
             buffer.extend_from_slice(&bytes);
 
-            // Process SSE events from buffer, handling both \n\n and \r\n\r\n delimiters
-            while let Some((idx, delimiter_len)) = Self::find_sse_delimiter(&buffer) {
-                let event_bytes = buffer[..idx].to_vec();
-                buffer.drain(..idx + delimiter_len);
-
+            // Process complete events from buffer (SSE blocks or JSON array elements)
+            while let Some(event_bytes) = take_frame(stream_format, &mut buffer) {
                 let event_str = match String::from_utf8(event_bytes) {
                     Ok(s) => s,
                     Err(e) => {
@@ -531,12 +608,22 @@ impl StreamHandler {
                         let error_event = StreamEvent::Error {
                             message: format!("Invalid UTF-8 in SSE event: {}", e),
                         };
-                        let _ = window.emit(&event_name, &error_event);
+                        let _ = window.emit(event_name, &error_event);
                         return Err(format!("Invalid UTF-8 in SSE event: {}", e));
                     }
                 };
 
-                if let Some(parsed) = Self::parse_sse_event(&event_str) {
+                let frames = parse_frame(stream_format, &event_str)
+                    .map(|frame| split_frame(frame, state.tolerant_json))
+                    .unwrap_or_default();
+                if frames.is_empty() {
+                    log::debug!(
+                        "[LLM Stream {}] No SSE event parsed from: {}",
+                        request_id,
+                        event_str
+                    );
+                }
+                for parsed in frames {
                     if let Some(recorder) = recorder.as_mut() {
                         recorder.record_sse_event(parsed.event.as_deref(), &parsed.data);
                     }
@@ -558,6 +645,7 @@ impl StreamHandler {
                                     total_tokens,
                                     cached_input_tokens,
                                     cache_creation_input_tokens,
+                                    ..
                                 } => {
                                     trace_usage = Some((
                                         *input_tokens,
@@ -577,7 +665,11 @@ impl StreamHandler {
                                 recorder.record_expected_event(&event);
                             }
                             Self::append_text_delta(&mut response_text, &event);
-                            self.emit_stream_event(&window, &event_name, &request_id, &event);
+                            if let Some(usage) = usage_fallback.before(&event) {
+                                self.emit_stream_event(window, event_name, &request_id, &usage);
+                            }
+                            metrics.observe(&event);
+                            self.emit_stream_event(window, event_name, &request_id, &event);
 
                             if !trace_ttft_emitted {
                                 if let (Some(ref span_id), Some(client_start_ms)) =
@@ -605,9 +697,18 @@ impl StreamHandler {
                                         recorder.record_expected_event(&pending);
                                     }
                                     Self::append_text_delta(&mut response_text, &pending);
+                                    if let Some(usage) = usage_fallback.before(&pending) {
+                                        self.emit_stream_event(
+                                            window,
+                                            event_name,
+                                            &request_id,
+                                            &usage,
+                                        );
+                                    }
+                                    metrics.observe(&pending);
                                     self.emit_stream_event(
-                                        &window,
-                                        &event_name,
+                                        window,
+                                        event_name,
                                         &request_id,
                                         &pending,
                                     );
@@ -634,9 +735,18 @@ impl StreamHandler {
                                         recorder.record_expected_event(&pending);
                                     }
                                     Self::append_text_delta(&mut response_text, &pending);
+                                    if let Some(usage) = usage_fallback.before(&pending) {
+                                        self.emit_stream_event(
+                                            window,
+                                            event_name,
+                                            &request_id,
+                                            &usage,
+                                        );
+                                    }
+                                    metrics.observe(&pending);
                                     self.emit_stream_event(
-                                        &window,
-                                        &event_name,
+                                        window,
+                                        event_name,
                                         &request_id,
                                         &pending,
                                     );
@@ -662,7 +772,7 @@ impl StreamHandler {
                                 );
                             }
                             let _ = window.emit(
-                                &event_name,
+                                event_name,
                                 &StreamEvent::Error {
                                     message: err.clone(),
                                 },
@@ -670,12 +780,6 @@ impl StreamHandler {
                             return Err(err);
                         }
                     }
-                } else {
-                    log::debug!(
-                        "[LLM Stream {}] No SSE event parsed from: {}",
-                        request_id,
-                        event_str
-                    );
                 }
             }
         }
@@ -763,14 +867,17 @@ impl StreamHandler {
         }
 
         if !done_emitted {
-            let _ = window.emit(
-                &event_name,
-                &StreamEvent::Done {
-                    finish_reason: state.finish_reason.clone(),
-                },
-            );
+            let done = StreamEvent::Done {
+                finish_reason: state.finish_reason.clone(),
+            };
+            if let Some(usage) = usage_fallback.before(&done) {
+                let _ = window.emit(event_name, &usage);
+            }
+            let _ = window.emit(event_name, &done);
         }
 
+        // The streamed text stands in for the raw SSE body
+        request_log::log_response(&log_tag, &url, status, Some(&response_text));
         log::info!(
             "[LLM Stream {}] Stream completion finished successfully",
             request_id
@@ -781,7 +888,7 @@ impl StreamHandler {
     async fn resolve_model_info(
         &self,
         model_identifier: &str,
-    ) -> Result<(String, String, String), String> {
+    ) -> Result<(String, String, String, Option<u32>), String> {
         let models = self.api_keys.load_models_config().await?;
         let api_keys = self.api_keys.load_api_keys().await?;
         let custom_providers = self.api_keys.load_custom_providers().await?;
@@ -802,42 +909,12 @@ impl StreamHandler {
                 &models,
             );
 
-        Ok((model_key, provider_id, provider_model_name))
-    }
-
-    /// Find SSE delimiter in buffer, returns (index, delimiter_length)
-    /// Handles both \n\n and \r\n\r\n delimiters
-    fn find_sse_delimiter(buf: &[u8]) -> Option<(usize, usize)> {
-        // First check for \r\n\r\n (4 bytes)
-        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
-            return Some((pos, 4));
-        }
-        // Then check for \n\n (2 bytes)
-        if let Some(pos) = buf.windows(2).position(|w| w == b"\n\n") {
-            return Some((pos, 2));
-        }
-        None
-    }
+        let context_window = models
+            .models
+            .get(&model_key)
+            .and_then(|model| model.context_length);
 
-    fn parse_sse_event(raw: &str) -> Option<SseEvent> {
-        let mut event: Option<String> = None;
-        let mut data_lines = Vec::new();
-        for line in raw.lines() {
-            if let Some(rest) = line.strip_prefix("event:") {
-                event = Some(rest.trim().to_string());
-            } else if let Some(rest) = line.strip_prefix("data:") {
-                // Preserve payload exactly, only removing single optional leading space per SSE spec
-                let data = rest.strip_prefix(' ').unwrap_or(rest);
-                data_lines.push(data.to_string());
-            }
-        }
-        if data_lines.is_empty() {
-            return None;
-        }
-        Some(SseEvent {
-            event,
-            data: data_lines.join("\n"),
-        })
+        Ok((model_key, provider_id, provider_model_name, context_window))
     }
 
     fn is_decode_response_body_error(error: &str) -> bool {
@@ -862,6 +939,36 @@ impl StreamHandler {
         let _ = window.emit(event_name, event);
     }
 
+    /// Close out a cancelled stream: record it on the trace span and tell the UI it is done
+    fn finish_cancelled(
+        &self,
+        window: &tauri::Window,
+        event_name: &str,
+        request_id: &str,
+        trace_span_id: &Option<String>,
+        error: LlmError,
+    ) -> String {
+        log::info!("[LLM Stream {}] Stream cancelled", request_id);
+        if let Some(span_id) = trace_span_id {
+            let trace_writer = window.app_handle().state::<Arc<TraceWriter>>();
+            trace_writer.add_event(
+                span_id.clone(),
+                "gen_ai.finish_reason".to_string(),
+                Some(serde_json::json!({"finish_reason": "cancelled"})),
+            );
+            trace_writer.end_span(span_id.clone(), chrono::Utc::now().timestamp_millis());
+        }
+        self.emit_stream_event(
+            window,
+            event_name,
+            request_id,
+            &StreamEvent::Done {
+                finish_reason: Some("cancelled".to_string()),
+            },
+        );
+        error.to_string()
+    }
+
     fn build_response_payload(
         finish_reason: Option<&str>,
         ttft_ms: Option<i64>,
@@ -917,11 +1024,6 @@ impl StreamHandler {
     }
 }
 
-struct SseEvent {
-    event: Option<String>,
-    data: String,
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -932,10 +1034,12 @@ mod tests {
         OpenAiResponsesProtocol,
     };
     use crate::llm::protocols::request_builder::{ProtocolRequestBuilder, RequestBuildContext};
+    use crate::llm::protocols::stream_parser::StreamFormat;
     use crate::llm::protocols::{ProtocolStreamState, ToolCallAccum};
     use crate::llm::providers::provider::Provider;
     use crate::llm::providers::provider_configs::builtin_providers;
     use crate::llm::providers::OpenAiProvider;
+    use crate::llm::testing::fixtures::provider_config;
     use crate::llm::types::{
         ContentPart, Message, MessageContent, ProtocolType, ProviderConfig, StreamTextRequest,
     };
@@ -996,14 +1100,31 @@ mod tests {
                     mime_type: Some("video/mp4".to_string()),
                 }]),
                 provider_options: None,
+                cache: false,
             }],
             tools: None,
             temperature: None,
             max_tokens: None,
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
             provider_options: None,
             trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
         };
 
         let base_url = provider
@@ -1022,19 +1143,12 @@ mod tests {
         let api_keys = ApiKeyManager::new(db, std::path::PathBuf::from("/tmp"));
 
         let provider = OpenAiProvider::new(ProviderConfig {
-            id: "openai".to_string(),
-            name: "OpenAI".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.openai.com/v1".to_string(),
-            api_key_name: "OPENAI_API_KEY".to_string(),
             supports_oauth: true,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "openai",
+                ProtocolType::OpenAiCompatible,
+                "https://api.openai.com/v1",
+            )
         });
 
         let request = StreamTextRequest {
@@ -1042,6 +1156,7 @@ mod tests {
             messages: vec![Message::User {
                 content: MessageContent::Text("hi".to_string()),
                 provider_options: None,
+                cache: false,
             }],
             tools: None,
             stream: Some(true),
@@ -1049,9 +1164,28 @@ mod tests {
             max_tokens: None,
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            template: None,
+            trim_strategy: None,
+            logprobs: None,
+            top_logprobs: None,
             provider_options: None,
             request_id: None,
             trace_context: None,
+            timeout_ms: None,
+            dry_run: None,
+            builtin_tools: None,
+            strip_headers: None,
+            max_attempts: None,
+            image_input_check: None,
+            reasoning_effort: None,
+            stall_timeout_ms: None,
+            idempotent: None,
         };
 
         let ctx = ProviderContext {
@@ -1064,8 +1198,24 @@ mod tests {
             max_tokens: request.max_tokens,
             top_p: request.top_p,
             top_k: request.top_k,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.as_deref(),
+            response_format: request.response_format.as_ref(),
+            user_id: request.user_id.as_deref(),
+            base_url_override: request.base_url_override.as_deref(),
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
         };
 
         let endpoint = provider.resolve_endpoint_path(&ctx).await;
@@ -1089,19 +1239,12 @@ mod tests {
         let api_keys = ApiKeyManager::new(db, std::path::PathBuf::from("/tmp"));
 
         let provider = OpenAiProvider::new(ProviderConfig {
-            id: "openai".to_string(),
-            name: "OpenAI".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.openai.com/v1".to_string(),
-            api_key_name: "OPENAI_API_KEY".to_string(),
             supports_oauth: true,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "openai",
+                ProtocolType::OpenAiCompatible,
+                "https://api.openai.com/v1",
+            )
         });
 
         let request = StreamTextRequest {
@@ -1109,6 +1252,7 @@ mod tests {
             messages: vec![Message::User {
                 content: MessageContent::Text("hi".to_string()),
                 provider_options: None,
+                cache: false,
             }],
             tools: None,
             stream: Some(true),
@@ -1116,9 +1260,28 @@ mod tests {
             max_tokens: None,
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            template: None,
+            trim_strategy: None,
+            logprobs: None,
+            top_logprobs: None,
             provider_options: None,
             request_id: None,
             trace_context: None,
+            timeout_ms: None,
+            dry_run: None,
+            builtin_tools: None,
+            strip_headers: None,
+            max_attempts: None,
+            image_input_check: None,
+            reasoning_effort: None,
+            stall_timeout_ms: None,
+            idempotent: None,
         };
 
         let ctx = ProviderContext {
@@ -1131,8 +1294,24 @@ mod tests {
             max_tokens: request.max_tokens,
             top_p: request.top_p,
             top_k: request.top_k,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.as_deref(),
+            response_format: request.response_format.as_ref(),
+            user_id: request.user_id.as_deref(),
+            base_url_override: request.base_url_override.as_deref(),
             provider_options: request.provider_options.as_ref(),
             trace_context: request.trace_context.as_ref(),
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
         };
 
         let endpoint = provider.resolve_endpoint_path(&ctx).await;
@@ -1155,19 +1334,12 @@ mod tests {
         db.connect().await.expect("db connect");
         let _api_keys = ApiKeyManager::new(db, std::path::PathBuf::from("/tmp"));
         let provider = OpenAiProvider::new(ProviderConfig {
-            id: "openai".to_string(),
-            name: "OpenAI".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.openai.com/v1".to_string(),
-            api_key_name: "OPENAI_API_KEY".to_string(),
             supports_oauth: true,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "openai",
+                ProtocolType::OpenAiCompatible,
+                "https://api.openai.com/v1",
+            )
         });
 
         let request = StreamTextRequest {
@@ -1176,6 +1348,7 @@ mod tests {
                 Message::User {
                     content: MessageContent::Text("hi".to_string()),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::Assistant {
                     content: MessageContent::Parts(vec![
@@ -1190,6 +1363,7 @@ mod tests {
                         },
                     ]),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::Tool {
                     content: vec![ContentPart::ToolResult {
@@ -1198,6 +1372,7 @@ mod tests {
                         output: json!({ "type": "text", "value": "ok" }),
                     }],
                     provider_options: None,
+                    cache: false,
                 },
             ],
             tools: None,
@@ -1206,9 +1381,28 @@ mod tests {
             max_tokens: None,
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            template: None,
+            trim_strategy: None,
+            logprobs: None,
+            top_logprobs: None,
             provider_options: None,
             request_id: None,
             trace_context: None,
+            timeout_ms: None,
+            dry_run: None,
+            builtin_tools: None,
+            strip_headers: None,
+            max_attempts: None,
+            image_input_check: None,
+            reasoning_effort: None,
+            stall_timeout_ms: None,
+            idempotent: None,
         };
 
         let request_ctx = RequestBuildContext {
@@ -1219,8 +1413,18 @@ mod tests {
             max_tokens: request.max_tokens,
             top_p: request.top_p,
             top_k: request.top_k,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.as_deref(),
+            response_format: request.response_format.as_ref(),
+            user_id: request.user_id.as_deref(),
             provider_options: request.provider_options.as_ref(),
             extra_body: provider.config().extra_body.as_ref(),
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            builtin_tools: &[],
+            reasoning_effort: None,
         };
         let body = OpenAiResponsesProtocol
             .build_request(request_ctx)
@@ -1407,10 +1611,64 @@ mod tests {
     #[test]
     fn find_sse_delimiter_prefers_crlf() {
         let data = b"event: ping\r\n\r\n";
-        let delimiter = StreamHandler::find_sse_delimiter(data);
+        let delimiter = crate::llm::protocols::stream_parser::find_sse_delimiter(data);
         assert_eq!(delimiter, Some((11, 4)));
     }
 
+    #[test]
+    fn fragmented_sse_chunks_reassemble_into_events() {
+        use crate::llm::protocols::openai_protocol::OpenAiProtocol;
+        use crate::llm::protocols::stream_parser::{ProtocolStreamParser, StreamParseContext};
+
+        let chunks: [&[u8]; 8] = [
+            b": keep-alive\n\n",
+            b"data: {\"choices\":[{\"delta\":{\"con",
+            b"tent\":\"Hel\"}}]}\r\n",
+            b"\r\n\n\n",
+            b"data: {\"choices\":[{\"delta\":{\"content\":\"lo\"},",
+            b"\"finish_reason\":\"stop\"}]}\n\n",
+            b"data: [DO",
+            b"NE]\n\n",
+        ];
+        let protocol = OpenAiProtocol;
+        let mut state = StreamParseState::default();
+        let mut buffer = Vec::new();
+        let mut events = Vec::new();
+
+        for chunk in chunks {
+            buffer.extend_from_slice(chunk);
+            while let Some(frame) = take_frame(StreamFormat::Sse, &mut buffer) {
+                let raw = String::from_utf8(frame).expect("utf8");
+                let Some(parsed) = parse_frame(StreamFormat::Sse, &raw) else {
+                    continue;
+                };
+                let ctx = StreamParseContext {
+                    event_type: parsed.event.as_deref(),
+                    data: &parsed.data,
+                };
+                let event = protocol.parse_stream_event(ctx, &mut state).expect("parse");
+                events.extend(event);
+                events.append(&mut state.pending_events);
+            }
+        }
+
+        let text: String = events
+            .iter()
+            .filter_map(|event| match event {
+                StreamEvent::TextDelta { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "Hello");
+        assert!(buffer.is_empty());
+        match events.last() {
+            Some(StreamEvent::Done { finish_reason }) => {
+                assert_eq!(finish_reason.as_deref(), Some("stop"))
+            }
+            other => panic!("Expected Done, got {:?}", other),
+        }
+    }
+
     #[test]
     fn build_response_payload_includes_response_text() {
         let payload = StreamHandler::build_response_payload(
@@ -1436,7 +1694,7 @@ mod tests {
     #[test]
     fn parse_sse_event_preserves_data_lines() {
         let raw = "event: message\ndata: first\ndata: second\n";
-        let event = StreamHandler::parse_sse_event(raw).expect("parsed");
+        let event = parse_frame(StreamFormat::Sse, raw).expect("parsed");
         assert_eq!(event.event.as_deref(), Some("message"));
         assert_eq!(event.data, "first\nsecond");
     }
@@ -1479,8 +1737,24 @@ mod tests {
             max_tokens: None,
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
             provider_options: None,
             trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
         };
 
         let base_url = provider
@@ -1702,19 +1976,12 @@ mod tests {
         db.connect().await.expect("db connect");
         let _api_keys = ApiKeyManager::new(db, std::path::PathBuf::from("/tmp"));
         let provider = OpenAiProvider::new(ProviderConfig {
-            id: "openai".to_string(),
-            name: "OpenAI".to_string(),
-            protocol: ProtocolType::OpenAiCompatible,
-            base_url: "https://api.openai.com/v1".to_string(),
-            api_key_name: "OPENAI_API_KEY".to_string(),
             supports_oauth: true,
-            supports_coding_plan: false,
-            supports_international: false,
-            coding_plan_base_url: None,
-            international_base_url: None,
-            headers: None,
-            extra_body: None,
-            auth_type: crate::llm::types::AuthType::Bearer,
+            ..provider_config(
+                "openai",
+                ProtocolType::OpenAiCompatible,
+                "https://api.openai.com/v1",
+            )
         });
 
         let request = StreamTextRequest {
@@ -1723,20 +1990,24 @@ mod tests {
                 Message::System {
                     content: "You are a helpful assistant.".to_string(),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::User {
                     content: MessageContent::Text("Hello!".to_string()),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::Assistant {
                     content: MessageContent::Text("Hi there! How can I help you?".to_string()),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::User {
                     content: MessageContent::Parts(vec![ContentPart::Text {
                         text: "What's the weather?".to_string(),
                     }]),
                     provider_options: None,
+                    cache: false,
                 },
                 Message::Assistant {
                     content: MessageContent::Parts(vec![
@@ -1749,6 +2020,7 @@ mod tests {
                         },
                     ]),
                     provider_options: None,
+                    cache: false,
                 },
             ],
             tools: None,
@@ -1757,9 +2029,28 @@ mod tests {
             max_tokens: None,
             top_p: None,
             top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            template: None,
+            trim_strategy: None,
+            logprobs: None,
+            top_logprobs: None,
             provider_options: None,
             request_id: None,
             trace_context: None,
+            timeout_ms: None,
+            dry_run: None,
+            builtin_tools: None,
+            strip_headers: None,
+            max_attempts: None,
+            image_input_check: None,
+            reasoning_effort: None,
+            stall_timeout_ms: None,
+            idempotent: None,
         };
 
         let request_ctx = RequestBuildContext {
@@ -1770,8 +2061,18 @@ mod tests {
             max_tokens: request.max_tokens,
             top_p: request.top_p,
             top_k: request.top_k,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            stop: request.stop.as_deref(),
+            response_format: request.response_format.as_ref(),
+            user_id: request.user_id.as_deref(),
             provider_options: request.provider_options.as_ref(),
             extra_body: provider.config().extra_body.as_ref(),
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            builtin_tools: &[],
+            reasoning_effort: None,
         };
         let body = OpenAiResponsesProtocol
             .build_request(request_ctx)
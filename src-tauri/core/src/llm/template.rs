@@ -0,0 +1,211 @@
+// Prompt templates
+// `{{name}}` placeholders in chat messages and image prompts are filled in before the request
+// is built; `\{{` writes a literal `{{`
+
+use crate::llm::types::{ContentPart, Message, MessageContent};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// What to do with a placeholder that has no value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingVariable {
+    /// Fail the render
+    #[default]
+    Error,
+    /// Leave the `{{name}}` placeholder in the output
+    Keep,
+}
+
+/// Variables sent alongside a request to fill its prompt placeholders
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub vars: HashMap<String, String>,
+    /// Leave unknown placeholders as-is instead of rejecting the request
+    #[serde(default, rename = "keepMissing")]
+    pub keep_missing: bool,
+}
+
+impl PromptTemplate {
+    fn missing(&self) -> MissingVariable {
+        if self.keep_missing {
+            MissingVariable::Keep
+        } else {
+            MissingVariable::Error
+        }
+    }
+
+    pub fn render(&self, template: &str) -> Result<String, String> {
+        render_with(template, &self.vars, self.missing())
+    }
+
+    /// Render the text of system, user and assistant messages; tool traffic is left untouched
+    pub fn render_messages(&self, messages: &mut [Message]) -> Result<(), String> {
+        for message in messages {
+            match message {
+                Message::System { content, .. } => *content = self.render(content)?,
+                Message::User { content, .. } | Message::Assistant { content, .. } => {
+                    self.render_content(content)?
+                }
+                Message::Tool { .. } => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn render_content(&self, content: &mut MessageContent) -> Result<(), String> {
+        match content {
+            MessageContent::Text(text) => *text = self.render(text)?,
+            MessageContent::Parts(parts) => {
+                for part in parts {
+                    if let ContentPart::Text { text } = part {
+                        *text = self.render(text)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Substitute `{{name}}` placeholders, failing on any variable not in `vars`
+pub fn render(template: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    render_with(template, vars, MissingVariable::Error)
+}
+
+pub fn render_with(
+    template: &str,
+    vars: &HashMap<String, String>,
+    missing: MissingVariable,
+) -> Result<String, String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find(OPEN) {
+        if rest[..start].ends_with('\\') {
+            output.push_str(&rest[..start - 1]);
+            output.push_str(OPEN);
+            rest = &rest[start + OPEN.len()..];
+            continue;
+        }
+        output.push_str(&rest[..start]);
+
+        let after_open = &rest[start + OPEN.len()..];
+        let Some(end) = after_open.find(CLOSE) else {
+            // An unterminated `{{` is plain text
+            output.push_str(&rest[start..]);
+            return Ok(output);
+        };
+        let placeholder = &rest[start..start + OPEN.len() + end + CLOSE.len()];
+        let name = after_open[..end].trim();
+
+        match vars.get(name) {
+            Some(value) => output.push_str(value),
+            None if missing == MissingVariable::Keep => output.push_str(placeholder),
+            None => {
+                return Err(format!(
+                    "Missing template variable '{}' / 模板变量缺失: {}",
+                    name, name
+                ))
+            }
+        }
+        rest = &after_open[end + CLOSE.len()..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn substitutes_placeholders() {
+        let rendered = render(
+            "Review {{ file }} in {{lang}}, then {{lang}} again",
+            &vars(&[("file", "main.rs"), ("lang", "Rust")]),
+        )
+        .expect("render");
+
+        assert_eq!(rendered, "Review main.rs in Rust, then Rust again");
+    }
+
+    #[test]
+    fn missing_variable_errors_or_is_kept() {
+        let vars = vars(&[("style", "watercolor")]);
+
+        let err = render("A {{style}} {{subject}}", &vars).expect_err("missing variable");
+        assert!(err.contains("subject"));
+
+        let kept =
+            render_with("A {{style}} {{subject}}", &vars, MissingVariable::Keep).expect("render");
+        assert_eq!(kept, "A watercolor {{subject}}");
+    }
+
+    #[test]
+    fn escaped_and_unterminated_braces_stay_literal() {
+        let vars = vars(&[("name", "x")]);
+
+        assert_eq!(
+            render(r"\{{name}} is {{name}}", &vars).expect("render"),
+            "{{name}} is x"
+        );
+        assert_eq!(render("open {{name", &vars).expect("render"), "open {{name");
+    }
+
+    #[test]
+    fn render_messages_skips_tool_traffic() {
+        let template = PromptTemplate {
+            vars: vars(&[("topic", "borrowing")]),
+            keep_missing: false,
+        };
+        let mut messages = vec![
+            Message::System {
+                content: "Explain {{topic}}".to_string(),
+                provider_options: None,
+                cache: false,
+            },
+            Message::User {
+                content: MessageContent::Parts(vec![ContentPart::Text {
+                    text: "More on {{topic}}".to_string(),
+                }]),
+                provider_options: None,
+                cache: false,
+            },
+            Message::Tool {
+                content: vec![ContentPart::ToolResult {
+                    tool_call_id: "call_1".to_string(),
+                    tool_name: "grep".to_string(),
+                    output: serde_json::json!("{{unknown}}"),
+                }],
+                provider_options: None,
+                cache: false,
+            },
+        ];
+
+        template.render_messages(&mut messages).expect("render");
+
+        let Message::System { content, .. } = &messages[0] else {
+            panic!("expected system message");
+        };
+        assert_eq!(content, "Explain borrowing");
+        let Message::User {
+            content: MessageContent::Parts(parts),
+            ..
+        } = &messages[1]
+        else {
+            panic!("expected user parts");
+        };
+        assert!(matches!(&parts[0], ContentPart::Text { text } if text == "More on borrowing"));
+    }
+}
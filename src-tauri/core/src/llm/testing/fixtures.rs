@@ -1,3 +1,9 @@
+#[cfg(test)]
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+#[cfg(test)]
+use crate::llm::protocols::sse::{SseLineReader, SseRecord};
+#[cfg(any(test, feature = "testing"))]
+use crate::llm::types::{AuthType, ProtocolType, ProviderConfig};
 use crate::llm::types::{Message, StreamEvent, ToolDefinition};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -64,6 +70,62 @@ pub struct RecordedSseEvent {
     pub data: String,
 }
 
+/// Bearer-auth provider config with every optional feature off
+/// The legacy key setting is `{ID}_API_KEY`; override other fields with struct update syntax
+#[cfg(any(test, feature = "testing"))]
+pub fn provider_config(id: &str, protocol: ProtocolType, base_url: &str) -> ProviderConfig {
+    ProviderConfig {
+        id: id.to_string(),
+        name: id.to_string(),
+        protocol,
+        base_url: base_url.to_string(),
+        api_key_name: format!("{}_API_KEY", id.to_uppercase()),
+        supports_oauth: false,
+        supports_coding_plan: false,
+        supports_international: false,
+        coding_plan_base_url: None,
+        international_base_url: None,
+        headers: None,
+        extra_body: None,
+        auth_type: AuthType::Bearer,
+        retry_policy: None,
+        model_aliases: Default::default(),
+        default_model: None,
+        tolerant_stream_json: false,
+        max_request_bytes: None,
+        multipart_images: false,
+        organization: None,
+        project: None,
+    }
+}
+
+/// `ApiKeyManager` over a fresh settings table seeded with `settings`
+/// The returned directory holds the database and must outlive the manager
+#[cfg(test)]
+pub async fn api_key_manager_with(settings: &[(&str, &str)]) -> (tempfile::TempDir, ApiKeyManager) {
+    let dir = tempfile::TempDir::new().expect("temp dir");
+    let db_path = dir.path().join("test.db");
+    let db = std::sync::Arc::new(crate::database::Database::new(
+        db_path.to_string_lossy().to_string(),
+    ));
+    db.connect().await.expect("db connect");
+    db.execute(
+        "CREATE TABLE IF NOT EXISTS settings (key TEXT PRIMARY KEY, value TEXT, updated_at INTEGER)",
+        vec![],
+    )
+    .await
+    .expect("create settings");
+
+    let api_keys = ApiKeyManager::new(db, dir.path().to_path_buf());
+    for (key, value) in settings {
+        api_keys
+            .set_setting(key, value)
+            .await
+            .expect("seed setting");
+    }
+    (dir, api_keys)
+}
+
 #[allow(dead_code)]
 #[cfg(test)]
 pub fn fixture_file_name(fixture: &ProviderFixture) -> String {
@@ -124,30 +186,17 @@ pub fn build_sse_body(events: &[RecordedSseEvent]) -> String {
 
 #[cfg(test)]
 pub fn parse_sse_body(body: &str) -> Vec<RecordedSseEvent> {
-    let mut events = Vec::new();
-    for raw in body.split("\n\n") {
-        if raw.trim().is_empty() {
-            continue;
-        }
-        let mut event_name = None;
-        let mut data_lines = Vec::new();
-        for line in raw.lines() {
-            if let Some(rest) = line.strip_prefix("event:") {
-                event_name = Some(rest.trim().to_string());
-            } else if let Some(rest) = line.strip_prefix("data:") {
-                let data = rest.strip_prefix(' ').unwrap_or(rest);
-                data_lines.push(data.to_string());
-            }
-        }
-        if data_lines.is_empty() {
-            continue;
-        }
-        events.push(RecordedSseEvent {
-            event: event_name,
-            data: data_lines.join("\n"),
-        });
-    }
-    events
+    let mut reader = SseLineReader::new();
+    reader.feed(body.as_bytes());
+    let mut records: Vec<SseRecord> = reader.by_ref().filter_map(Result::ok).collect();
+    records.extend(reader.finish().ok().flatten());
+    records
+        .into_iter()
+        .map(|record| RecordedSseEvent {
+            event: record.event,
+            data: record.data,
+        })
+        .collect()
 }
 
 #[cfg(test)]
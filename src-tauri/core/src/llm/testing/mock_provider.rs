@@ -0,0 +1,464 @@
+// Scripted provider and image client for tests
+// Nothing touches the network: streams replay a fixed event script, and completions, model
+// lists and images come from canned values or fail with a configured error
+
+use crate::llm::ai_services::types::TokenUsage;
+use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::error::LlmError;
+use crate::llm::protocols::{
+    header_builder::HeaderBuildContext,
+    openai_protocol::OpenAiProtocol,
+    request_builder::{ProtocolRequestBuilder, RequestBuildContext},
+    response_parser::CompletionResult,
+    sse,
+    stream_parser::{StreamParseContext, StreamParseState},
+};
+use crate::llm::providers::provider::{
+    Provider, ProviderCapabilities, ProviderContext, ProviderCredentials,
+};
+use crate::llm::testing::fixtures::provider_config;
+use crate::llm::types::{
+    AuthType, GeneratedImage, ImageGenerationRequest, ModelInfo, ProtocolType, ProviderConfig,
+    StreamEvent,
+};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Base URL of mock providers; never resolved, since nothing is sent
+pub const MOCK_BASE_URL: &str = "mock://provider";
+
+/// Configuration for a mock provider with the given id
+pub fn mock_config(id: &str) -> ProviderConfig {
+    ProviderConfig {
+        name: "Mock".to_string(),
+        api_key_name: "MOCK_API_KEY".to_string(),
+        auth_type: AuthType::None,
+        ..provider_config(id, ProtocolType::OpenAiCompatible, MOCK_BASE_URL)
+    }
+}
+
+/// Provider answering from a script; request bodies are built with the OpenAI protocol
+/// and recorded so tests can check what would have been sent
+pub struct MockProvider {
+    config: ProviderConfig,
+    capabilities: ProviderCapabilities,
+    events: Vec<StreamEvent>,
+    models: Vec<ModelInfo>,
+    error: Option<LlmError>,
+    requests: Mutex<Vec<Value>>,
+}
+
+impl MockProvider {
+    pub fn new(id: &str) -> Self {
+        Self {
+            config: mock_config(id),
+            capabilities: ProviderCapabilities::chat(),
+            events: Vec::new(),
+            models: Vec::new(),
+            error: None,
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Events every stream replays, in order
+    pub fn with_events(mut self, events: Vec<StreamEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Script a plain reply: `Start`, one `TextDelta` per chunk, `Usage`, then `Done`
+    pub fn with_text(self, model: &str, chunks: &[&str]) -> Self {
+        let output_tokens = chunks.len() as i32;
+        let mut events = vec![StreamEvent::Start {
+            model: model.to_string(),
+            system_fingerprint: None,
+        }];
+        events.extend(chunks.iter().map(|chunk| StreamEvent::TextDelta {
+            text: chunk.to_string(),
+        }));
+        events.push(StreamEvent::Usage {
+            input_tokens: 0,
+            output_tokens,
+            total_tokens: Some(output_tokens),
+            cached_input_tokens: None,
+            cache_creation_input_tokens: None,
+            timing: None,
+            estimated: false,
+        });
+        events.push(StreamEvent::Done {
+            finish_reason: Some("stop".to_string()),
+        });
+        self.with_events(events)
+    }
+
+    /// Models `list_models` reports
+    pub fn with_models(mut self, ids: &[&str]) -> Self {
+        self.models = ids
+            .iter()
+            .map(|id| ModelInfo {
+                id: id.to_string(),
+                created: 0,
+                owned_by: Some(self.config.id.clone()),
+                context_window: None,
+            })
+            .collect();
+        self
+    }
+
+    /// Fail every call with `error` once its request is built
+    pub fn with_error(mut self, error: LlmError) -> Self {
+        self.error = Some(error);
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: ProviderCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Bodies of the requests built so far, oldest first
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Stream the script for `ctx`, passing each event through `parse_stream_event_with_context`
+    /// as a serialized frame the way a runner would
+    pub async fn stream(&self, ctx: &ProviderContext<'_>) -> Result<Vec<StreamEvent>, LlmError> {
+        self.build_request(ctx).await?;
+        self.scripted_error()?;
+        let mut state = StreamParseState::default();
+        let mut events = Vec::new();
+        for event in &self.events {
+            let data = serde_json::to_string(event)
+                .map_err(|e| LlmError::Other(format!("Failed to serialize event: {}", e)))?;
+            let parsed = self
+                .parse_stream_event_with_context(ctx, None, &data, &mut state)
+                .await
+                .map_err(LlmError::Other)?;
+            events.extend(parsed);
+        }
+        Ok(events)
+    }
+
+    fn scripted_error(&self) -> Result<(), LlmError> {
+        match &self.error {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for MockProvider {
+    fn id(&self) -> &str {
+        &self.config.id
+    }
+
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn protocol_type(&self) -> ProtocolType {
+        self.config.protocol
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.config
+    }
+
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.capabilities
+    }
+
+    async fn resolve_base_url(&self, _ctx: &ProviderContext<'_>) -> Result<String, LlmError> {
+        Ok(self.config.base_url.clone())
+    }
+
+    async fn get_credentials(
+        &self,
+        _api_key_manager: &ApiKeyManager,
+    ) -> Result<ProviderCredentials, LlmError> {
+        Ok(ProviderCredentials::None)
+    }
+
+    fn build_protocol_headers(&self, _ctx: HeaderBuildContext) -> HashMap<String, String> {
+        HashMap::from([("Content-Type".to_string(), "application/json".to_string())])
+    }
+
+    fn build_protocol_request(&self, ctx: RequestBuildContext) -> Result<Value, String> {
+        let body = OpenAiProtocol.build_request(ctx)?;
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(body.clone());
+        Ok(body)
+    }
+
+    fn parse_protocol_stream_event(
+        &self,
+        ctx: StreamParseContext,
+        _state: &mut StreamParseState,
+    ) -> Result<Option<StreamEvent>, String> {
+        if sse::is_done(ctx.data) {
+            return Ok(None);
+        }
+        serde_json::from_str(ctx.data)
+            .map(Some)
+            .map_err(|e| format!("Invalid mock event: {}", e))
+    }
+
+    async fn complete(&self, ctx: &ProviderContext<'_>) -> Result<CompletionResult, String> {
+        let ctx = &ProviderContext {
+            stream: false,
+            ..ctx.clone()
+        };
+        self.build_request(ctx).await?;
+        self.scripted_error()?;
+        let mut result = CompletionResult {
+            text: String::new(),
+            usage: None,
+            finish_reason: None,
+        };
+        for event in &self.events {
+            match event {
+                StreamEvent::TextDelta { text } => result.text.push_str(text),
+                StreamEvent::Usage {
+                    input_tokens,
+                    output_tokens,
+                    cached_input_tokens,
+                    cache_creation_input_tokens,
+                    estimated,
+                    ..
+                } => {
+                    result.usage = Some(TokenUsage {
+                        input_tokens: *input_tokens as u32,
+                        output_tokens: *output_tokens as u32,
+                        cached_input_tokens: cached_input_tokens.map(|tokens| tokens as u32),
+                        cache_creation_input_tokens: cache_creation_input_tokens
+                            .map(|tokens| tokens as u32),
+                        estimated: *estimated,
+                    })
+                }
+                StreamEvent::Done { finish_reason } => result.finish_reason = finish_reason.clone(),
+                _ => {}
+            }
+        }
+        Ok(result)
+    }
+
+    async fn list_models(&self, _ctx: &ProviderContext<'_>) -> Result<Vec<ModelInfo>, LlmError> {
+        self.scripted_error()?;
+        Ok(self.models.clone())
+    }
+}
+
+/// Image client returning canned images, shaped like the provider image clients
+pub struct MockImageClient {
+    images: Vec<GeneratedImage>,
+    error: Option<String>,
+    requests: Mutex<Vec<(String, ImageGenerationRequest)>>,
+}
+
+impl MockImageClient {
+    pub fn new(images: Vec<GeneratedImage>) -> Self {
+        Self {
+            images,
+            error: None,
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Fail every generation with `error`
+    pub fn with_error(mut self, error: &str) -> Self {
+        self.error = Some(error.to_string());
+        self
+    }
+
+    /// Model and request of each generation so far, oldest first
+    pub fn requests(&self) -> Vec<(String, ImageGenerationRequest)> {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub async fn generate(
+        &self,
+        model: &str,
+        request: ImageGenerationRequest,
+    ) -> Result<Vec<GeneratedImage>, String> {
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((model.to_string(), request));
+        match &self.error {
+            Some(error) => Err(error.clone()),
+            None => Ok(self.images.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::llm::types::{Message, MessageContent};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    async fn api_keys() -> (TempDir, ApiKeyManager) {
+        let dir = TempDir::new().expect("temp dir");
+        let db_path = dir.path().join("test.db");
+        let db = Arc::new(Database::new(db_path.to_string_lossy().to_string()));
+        db.connect().await.expect("db connect");
+        let api_keys = ApiKeyManager::new(db, dir.path().to_path_buf());
+        (dir, api_keys)
+    }
+
+    fn context<'a>(
+        provider: &'a MockProvider,
+        api_keys: &'a ApiKeyManager,
+        messages: &'a [Message],
+    ) -> ProviderContext<'a> {
+        ProviderContext {
+            provider_config: provider.config(),
+            api_key_manager: api_keys,
+            model: "mock-model",
+            messages,
+            tools: None,
+            temperature: Some(0.5),
+            max_tokens: None,
+            top_p: None,
+            top_k: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stop: None,
+            response_format: None,
+            user_id: None,
+            base_url_override: None,
+            provider_options: None,
+            trace_context: None,
+            cancel_token: None,
+            stream: true,
+            logprobs: false,
+            top_logprobs: None,
+            dry_run: false,
+            builtin_tools: &[],
+            strip_headers: &[],
+            retry_budget: None,
+            reasoning_effort: None,
+            idempotent: true,
+        }
+    }
+
+    fn hello() -> Vec<Message> {
+        vec![Message::User {
+            content: MessageContent::Text("hello".to_string()),
+            provider_options: None,
+            cache: false,
+        }]
+    }
+
+    #[tokio::test]
+    async fn stream_replays_the_scripted_events_in_order() {
+        let (_dir, api_keys) = api_keys().await;
+        let messages = hello();
+        let provider = MockProvider::new("mock").with_text("mock-model", &["Hel", "lo"]);
+
+        let events = provider
+            .stream(&context(&provider, &api_keys, &messages))
+            .await
+            .expect("stream");
+
+        let events = serde_json::to_value(events).expect("serialize events");
+        assert_eq!(
+            events,
+            serde_json::json!([
+                { "type": "start", "model": "mock-model" },
+                { "type": "text-delta", "text": "Hel" },
+                { "type": "text-delta", "text": "lo" },
+                { "type": "usage", "input_tokens": 0, "output_tokens": 2, "total_tokens": 2,
+                  "cached_input_tokens": null, "cache_creation_input_tokens": null },
+                { "type": "done", "finish_reason": "stop" }
+            ])
+        );
+        let requests = provider.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["model"], "mock-model");
+        assert_eq!(requests[0]["temperature"], 0.5);
+    }
+
+    #[tokio::test]
+    async fn scripted_errors_fail_streams_completions_and_model_lists() {
+        let (_dir, api_keys) = api_keys().await;
+        let messages = hello();
+        let provider = MockProvider::new("mock")
+            .with_text("mock-model", &["never sent"])
+            .with_error(LlmError::Auth("bad key".to_string()));
+        let ctx = context(&provider, &api_keys, &messages);
+
+        let err = provider.stream(&ctx).await.expect_err("stream error");
+        assert!(matches!(err, LlmError::Auth(_)));
+        assert!(provider.complete(&ctx).await.is_err());
+        assert!(provider.list_models(&ctx).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn complete_joins_the_script_and_models_are_canned() {
+        let (_dir, api_keys) = api_keys().await;
+        let messages = hello();
+        let provider = MockProvider::new("mock")
+            .with_text("mock-model", &["Hel", "lo"])
+            .with_models(&["mock-small", "mock-large"]);
+        let ctx = context(&provider, &api_keys, &messages);
+
+        let result = provider.complete(&ctx).await.expect("complete");
+        let models = provider.list_models(&ctx).await.expect("models");
+
+        assert_eq!(result.text, "Hello");
+        assert_eq!(result.finish_reason.as_deref(), Some("stop"));
+        assert_eq!(result.usage.expect("usage").output_tokens, 2);
+        assert_eq!(provider.requests()[0]["stream"], false);
+        let ids: Vec<_> = models.iter().map(|model| model.id.as_str()).collect();
+        assert_eq!(ids, ["mock-small", "mock-large"]);
+    }
+
+    #[tokio::test]
+    async fn mock_image_client_returns_canned_images_and_records_requests() {
+        let image = GeneratedImage {
+            b64_json: Some("iVBORw0KGgo=".to_string()),
+            url: None,
+            mime_type: "image/png".to_string(),
+            revised_prompt: None,
+            expires_at: None,
+        };
+        let request: ImageGenerationRequest = serde_json::from_value(serde_json::json!({
+            "model": "mock-image",
+            "prompt": "a red fox"
+        }))
+        .expect("request");
+        let client = MockImageClient::new(vec![image]);
+
+        let images = client
+            .generate("mock-image", request.clone())
+            .await
+            .expect("images");
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(client.requests()[0].1.prompt, "a red fox");
+        let failing = MockImageClient::new(Vec::new()).with_error("quota exceeded");
+        assert_eq!(
+            failing
+                .generate("mock-image", request)
+                .await
+                .err()
+                .as_deref(),
+            Some("quota exceeded")
+        );
+    }
+}
@@ -3,9 +3,10 @@
 use crate::llm::testing::fixtures::{
     assert_json_matches, build_sse_body, ProviderFixture, RecordedResponse,
 };
-use std::net::TcpListener;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::Duration;
 
@@ -64,6 +65,282 @@ impl Drop for MockProviderServer {
     }
 }
 
+/// Serve the given `(status, body)` responses in order, one per incoming request
+/// Throttled (429) responses carry `Retry-After: 0` so retry tests stay fast
+/// Returns the base URL and a counter of requests received
+pub fn start_sequence_server(
+    responses: Vec<(u16, String)>,
+) -> Result<(String, Arc<AtomicUsize>), String> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to bind mock server: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read mock server address: {}", e))?;
+    let server = tiny_http::Server::from_listener(listener, None)
+        .map_err(|e| format!("Failed to start mock server: {}", e))?;
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    thread::spawn(move || {
+        for (status, body) in responses {
+            let Ok(request) = server.recv() else {
+                return;
+            };
+            counter.fetch_add(1, Ordering::SeqCst);
+            let mut response = tiny_http::Response::from_string(body).with_status_code(status);
+            if status == 429 {
+                if let Ok(header) = tiny_http::Header::from_bytes("Retry-After", "0") {
+                    response.add_header(header);
+                }
+            }
+            let _ = request.respond(response);
+        }
+    });
+
+    Ok((format!("http://{}", addr), hits))
+}
+
+/// Write each raw HTTP response in order, one per incoming connection, then close it
+/// A `Content-Length` longer than the body cuts the stream short mid-response
+pub fn start_raw_server(responses: Vec<String>) -> Result<(String, Arc<AtomicUsize>), String> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to bind mock server: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read mock server address: {}", e))?;
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    thread::spawn(move || {
+        for response in responses {
+            let Ok((mut stream, _)) = listener.accept() else {
+                return;
+            };
+            counter.fetch_add(1, Ordering::SeqCst);
+            read_raw_request(&mut stream);
+            let _ = stream.write_all(response.as_bytes());
+            let _ = stream.flush();
+        }
+    });
+
+    Ok((format!("http://{}", addr), hits))
+}
+
+/// Answer one request with a chunked `200` body, writing each chunk after the previous one
+/// has been released through the returned sender, so tests can tell streamed reads from
+/// buffered ones; the body ends once every chunk is written
+pub fn start_chunked_server(
+    content_type: &'static str,
+    chunks: Vec<Vec<u8>>,
+) -> Result<(String, mpsc::Sender<()>), String> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to bind mock server: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read mock server address: {}", e))?;
+
+    let (release, released) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        let Ok((mut stream, _)) = listener.accept() else {
+            return;
+        };
+        read_raw_request(&mut stream);
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nTransfer-Encoding: chunked\r\n\r\n",
+            content_type
+        );
+        if stream.write_all(head.as_bytes()).is_err() {
+            return;
+        }
+        for (index, chunk) in chunks.iter().enumerate() {
+            if index > 0 && released.recv().is_err() {
+                return;
+            }
+            let mut frame = format!("{:x}\r\n", chunk.len()).into_bytes();
+            frame.extend_from_slice(chunk);
+            frame.extend_from_slice(b"\r\n");
+            if stream
+                .write_all(&frame)
+                .and_then(|()| stream.flush())
+                .is_err()
+            {
+                return;
+            }
+        }
+        let _ = stream.write_all(b"0\r\n\r\n");
+        let _ = stream.flush();
+    });
+
+    Ok((format!("http://{}", addr), release))
+}
+
+/// Read one request off `stream`: headers, then as much body as `Content-Length` announces
+fn read_raw_request(stream: &mut TcpStream) {
+    let mut received = Vec::new();
+    let mut chunk = [0u8; 4096];
+    loop {
+        let read = match stream.read(&mut chunk) {
+            Ok(0) | Err(_) => return,
+            Ok(read) => read,
+        };
+        received.extend_from_slice(&chunk[..read]);
+        let Some(end) = received.windows(4).position(|window| window == b"\r\n\r\n") else {
+            continue;
+        };
+        let head = String::from_utf8_lossy(&received[..end]).to_ascii_lowercase();
+        let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("content-length:"))
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        if received.len() >= end + 4 + length {
+            return;
+        }
+    }
+}
+
+/// Request seen by `start_capture_server`
+#[derive(Debug, Clone)]
+pub struct CapturedRequest {
+    pub url: String,
+    pub content_type: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl CapturedRequest {
+    /// Value of a request header, matching the name case-insensitively
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(field, _)| field.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Answer a single request with `(status, body)` and hand back what the client sent
+pub fn start_capture_server(
+    status: u16,
+    body: Vec<u8>,
+) -> Result<(String, mpsc::Receiver<CapturedRequest>), String> {
+    start_capture_server_with_headers(status, body, vec![])
+}
+
+/// `start_capture_server` that also sends the given response headers
+pub fn start_capture_server_with_headers(
+    status: u16,
+    body: Vec<u8>,
+    headers: Vec<(&'static str, &'static str)>,
+) -> Result<(String, mpsc::Receiver<CapturedRequest>), String> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to bind mock server: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read mock server address: {}", e))?;
+    let server = tiny_http::Server::from_listener(listener, None)
+        .map_err(|e| format!("Failed to start mock server: {}", e))?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let Ok(mut request) = server.recv() else {
+            return;
+        };
+        let _ = tx.send(capture(&mut request));
+        let mut response = tiny_http::Response::from_data(body).with_status_code(status);
+        for (name, value) in headers {
+            if let Ok(header) = tiny_http::Header::from_bytes(name, value) {
+                response.add_header(header);
+            }
+        }
+        let _ = request.respond(response);
+    });
+
+    Ok((format!("http://{}", addr), rx))
+}
+
+/// `start_sequence_server` that also hands back every request the client sent
+pub fn start_capture_sequence_server(
+    responses: Vec<(u16, String)>,
+) -> Result<(String, mpsc::Receiver<CapturedRequest>), String> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to bind mock server: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read mock server address: {}", e))?;
+    let server = tiny_http::Server::from_listener(listener, None)
+        .map_err(|e| format!("Failed to start mock server: {}", e))?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for (status, body) in responses {
+            let Ok(mut request) = server.recv() else {
+                return;
+            };
+            let _ = tx.send(capture(&mut request));
+            let mut response = tiny_http::Response::from_string(body).with_status_code(status);
+            if status == 429 {
+                if let Ok(header) = tiny_http::Header::from_bytes("Retry-After", "0") {
+                    response.add_header(header);
+                }
+            }
+            let _ = request.respond(response);
+        }
+    });
+
+    Ok((format!("http://{}", addr), rx))
+}
+
+fn capture(request: &mut tiny_http::Request) -> CapturedRequest {
+    let mut received = Vec::new();
+    let _ = request.as_reader().read_to_end(&mut received);
+    let content_type = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Content-Type"))
+        .map(|header| header.value.to_string());
+    let headers = request
+        .headers()
+        .iter()
+        .map(|header| (header.field.to_string(), header.value.to_string()))
+        .collect();
+    CapturedRequest {
+        url: request.url().to_string(),
+        content_type,
+        headers,
+        body: received,
+    }
+}
+
+/// Answer a single request with `(status, body)` and the given response headers
+pub fn start_header_server(
+    status: u16,
+    body: String,
+    headers: Vec<(&'static str, &'static str)>,
+) -> Result<String, String> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to bind mock server: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read mock server address: {}", e))?;
+    let server = tiny_http::Server::from_listener(listener, None)
+        .map_err(|e| format!("Failed to start mock server: {}", e))?;
+
+    thread::spawn(move || {
+        let Ok(request) = server.recv() else {
+            return;
+        };
+        let mut response = tiny_http::Response::from_string(body).with_status_code(status);
+        for (name, value) in headers {
+            if let Ok(header) = tiny_http::Header::from_bytes(name, value) {
+                response.add_header(header);
+            }
+        }
+        let _ = request.respond(response);
+    });
+
+    Ok(format!("http://{}", addr))
+}
+
 fn handle_request(
     mut request: tiny_http::Request,
     fixture: &ProviderFixture,
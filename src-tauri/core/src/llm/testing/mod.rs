@@ -1,4 +1,6 @@
 pub mod fixtures;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock_provider;
 pub mod mock_server;
 pub mod recorder;
 
@@ -15,11 +15,13 @@ fn build_messages(count: usize) -> Vec<Message> {
             Message::User {
                 content,
                 provider_options: None,
+                cache: false,
             }
         } else {
             Message::Assistant {
                 content,
                 provider_options: None,
+                cache: false,
             }
         };
         messages.push(message);
@@ -40,8 +42,18 @@ fn perf_openai_build_request() {
         max_tokens: Some(2048),
         top_p: Some(0.9),
         top_k: Some(64),
+        frequency_penalty: None,
+        presence_penalty: None,
+        stop: None,
+        response_format: None,
+        user_id: None,
         provider_options: None,
         extra_body: None,
+        stream: true,
+        logprobs: false,
+        top_logprobs: None,
+        builtin_tools: &[],
+        reasoning_effort: None,
     };
 
     let iterations = 300;
@@ -29,6 +29,7 @@ fn build_test_context(
         messages: vec![Message::User {
             content: MessageContent::Text("hi".to_string()),
             provider_options: None,
+            cache: false,
         }],
         tools: None,
         stream: Some(true),
@@ -36,9 +37,28 @@ fn build_test_context(
         max_tokens: None,
         top_p: None,
         top_k,
+        frequency_penalty: None,
+        presence_penalty: None,
+        stop: None,
+        response_format: None,
+        user_id: None,
+        base_url_override: None,
+        template: None,
+        trim_strategy: None,
+        logprobs: None,
+        top_logprobs: None,
         provider_options: None,
         request_id: None,
         trace_context: None,
+        timeout_ms: None,
+        dry_run: None,
+        builtin_tools: None,
+        strip_headers: None,
+        max_attempts: None,
+        image_input_check: None,
+        reasoning_effort: None,
+        stall_timeout_ms: None,
+        idempotent: None,
     };
 
     (provider, api_keys, request)
@@ -59,8 +79,24 @@ async fn google_provider_strips_top_k() {
         max_tokens: request.max_tokens,
         top_p: request.top_p,
         top_k: request.top_k,
+        frequency_penalty: request.frequency_penalty,
+        presence_penalty: request.presence_penalty,
+        stop: request.stop.as_deref(),
+        response_format: request.response_format.as_ref(),
+        user_id: request.user_id.as_deref(),
+        base_url_override: request.base_url_override.as_deref(),
         provider_options: request.provider_options.as_ref(),
         trace_context: request.trace_context.as_ref(),
+        cancel_token: None,
+        stream: true,
+        logprobs: false,
+        top_logprobs: None,
+        dry_run: false,
+        builtin_tools: &[],
+        strip_headers: &[],
+        retry_budget: None,
+        reasoning_effort: None,
+        idempotent: true,
     };
 
     let body = provider.build_request(&ctx).await.expect("build request");
@@ -81,8 +117,24 @@ async fn non_google_provider_keeps_top_k() {
         max_tokens: request.max_tokens,
         top_p: request.top_p,
         top_k: request.top_k,
+        frequency_penalty: request.frequency_penalty,
+        presence_penalty: request.presence_penalty,
+        stop: request.stop.as_deref(),
+        response_format: request.response_format.as_ref(),
+        user_id: request.user_id.as_deref(),
+        base_url_override: request.base_url_override.as_deref(),
         provider_options: request.provider_options.as_ref(),
         trace_context: request.trace_context.as_ref(),
+        cancel_token: None,
+        stream: true,
+        logprobs: false,
+        top_logprobs: None,
+        dry_run: false,
+        builtin_tools: &[],
+        strip_headers: &[],
+        retry_budget: None,
+        reasoning_effort: None,
+        idempotent: true,
     };
 
     let body = provider.build_request(&ctx).await.expect("build request");
@@ -149,6 +149,9 @@ fn collect_events(protocol: &dyn LlmProtocol, fixture: &ProviderFixture) -> Vec<
         );
     }
 
+    // Recorded fixtures keep only the assembled tool calls
+    events.retain(|event| event.get("type").and_then(Value::as_str) != Some("tool-call-delta"));
+
     events
 }
 
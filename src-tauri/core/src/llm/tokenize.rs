@@ -0,0 +1,306 @@
+// Token estimation
+// Approximates token counts per tokenizer family for providers that report no usage, such as
+// local servers; estimates are flagged so cost tracking can decide whether to trust them
+
+use crate::llm::ai_services::types::TokenUsage;
+use crate::llm::types::{ContentPart, Message, MessageContent, ProtocolType, StreamEvent};
+use std::sync::{Arc, RwLock};
+
+/// Role markers and separators each message costs on top of its content
+const MESSAGE_OVERHEAD_TOKENS: u32 = 3;
+/// Flat estimate for an image or video part, whose real cost depends on its resolution
+const MEDIA_TOKENS: u32 = 1_000;
+
+/// Estimator installed with `set_estimator`; None uses `HeuristicEstimator`
+static ESTIMATOR: RwLock<Option<Arc<dyn TokenEstimator>>> = RwLock::new(None);
+
+/// Tokenizers that split text differently enough to be estimated apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenizerFamily {
+    /// OpenAI's BPE vocabularies, also a fair middle for other OpenAI-compatible servers
+    OpenAi,
+    Claude,
+    Gemini,
+    Cohere,
+}
+
+impl From<ProtocolType> for TokenizerFamily {
+    fn from(protocol: ProtocolType) -> Self {
+        match protocol {
+            ProtocolType::OpenAiCompatible => TokenizerFamily::OpenAi,
+            ProtocolType::Claude | ProtocolType::Anthropic => TokenizerFamily::Claude,
+            ProtocolType::Gemini => TokenizerFamily::Gemini,
+            ProtocolType::Cohere => TokenizerFamily::Cohere,
+        }
+    }
+}
+
+impl TokenizerFamily {
+    /// Average characters per token for English text and code
+    fn chars_per_token(self) -> f64 {
+        match self {
+            TokenizerFamily::OpenAi | TokenizerFamily::Gemini | TokenizerFamily::Cohere => 4.0,
+            TokenizerFamily::Claude => 3.5,
+        }
+    }
+}
+
+/// Counts tokens without the provider's tokenizer; install a better one with `set_estimator`
+pub trait TokenEstimator: Send + Sync {
+    fn count_tokens(&self, family: TokenizerFamily, text: &str) -> u32;
+}
+
+/// Characters-per-token ratio of the family, with each CJK character counted as one token
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicEstimator;
+
+impl TokenEstimator for HeuristicEstimator {
+    fn count_tokens(&self, family: TokenizerFamily, text: &str) -> u32 {
+        let (wide, narrow) = text.chars().fold((0u32, 0u32), |(wide, narrow), c| {
+            if is_cjk(c) {
+                (wide + 1, narrow)
+            } else {
+                (wide, narrow + 1)
+            }
+        });
+        wide + (f64::from(narrow) / family.chars_per_token()).ceil() as u32
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    // CJK radicals and ideographs, Hangul syllables, compatibility ideographs, full-width forms
+    matches!(
+        c,
+        '\u{2E80}'..='\u{9FFF}'
+            | '\u{AC00}'..='\u{D7AF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF00}'..='\u{FFEF}'
+    )
+}
+
+/// Use `estimator` for every estimate made from now on
+pub fn set_estimator(estimator: Arc<dyn TokenEstimator>) {
+    *ESTIMATOR.write().unwrap_or_else(|e| e.into_inner()) = Some(estimator);
+}
+
+pub fn estimator() -> Arc<dyn TokenEstimator> {
+    ESTIMATOR
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| Arc::new(HeuristicEstimator))
+}
+
+/// Estimated prompt tokens of `messages`, including per-message overhead
+pub fn count_message_tokens(
+    estimator: &dyn TokenEstimator,
+    family: TokenizerFamily,
+    messages: &[Message],
+) -> u32 {
+    let count = |text: &str| estimator.count_tokens(family, text);
+    let count_parts = |parts: &[ContentPart]| -> u32 {
+        parts
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text { text } | ContentPart::Reasoning { text, .. } => count(text),
+                ContentPart::Image { .. } | ContentPart::Video { .. } => MEDIA_TOKENS,
+                ContentPart::ToolCall {
+                    tool_name, input, ..
+                } => count(tool_name) + count(&input.to_string()),
+                ContentPart::ToolResult {
+                    tool_name, output, ..
+                } => count(tool_name) + count(&output.to_string()),
+            })
+            .sum()
+    };
+    let count_content = |content: &MessageContent| match content {
+        MessageContent::Text(text) => count(text),
+        MessageContent::Parts(parts) => count_parts(parts),
+    };
+    messages
+        .iter()
+        .map(|message| {
+            let content = match message {
+                Message::System { content, .. } => count(content),
+                Message::User { content, .. } => count_content(content),
+                Message::Assistant { content, .. } => count_content(content),
+                Message::Tool { content, .. } => count_parts(content),
+            };
+            content + MESSAGE_OVERHEAD_TOKENS
+        })
+        .sum()
+}
+
+/// Estimated usage of a completion that returned `output` for `messages`
+pub fn estimate_usage(protocol: ProtocolType, messages: &[Message], output: &str) -> TokenUsage {
+    let estimator = estimator();
+    let family = TokenizerFamily::from(protocol);
+    TokenUsage {
+        input_tokens: count_message_tokens(estimator.as_ref(), family, messages),
+        output_tokens: estimator.count_tokens(family, output),
+        cached_input_tokens: None,
+        cache_creation_input_tokens: None,
+        estimated: true,
+    }
+}
+
+/// Watches a stream and supplies estimated usage when it ends without reporting any
+pub struct UsageFallback {
+    estimator: Arc<dyn TokenEstimator>,
+    family: TokenizerFamily,
+    input_tokens: u32,
+    output: String,
+    reported: bool,
+}
+
+impl UsageFallback {
+    pub fn new(protocol: ProtocolType, messages: &[Message]) -> Self {
+        Self::with_estimator(estimator(), protocol, messages)
+    }
+
+    pub fn with_estimator(
+        estimator: Arc<dyn TokenEstimator>,
+        protocol: ProtocolType,
+        messages: &[Message],
+    ) -> Self {
+        let family = TokenizerFamily::from(protocol);
+        Self {
+            input_tokens: count_message_tokens(estimator.as_ref(), family, messages),
+            estimator,
+            family,
+            output: String::new(),
+            reported: false,
+        }
+    }
+
+    /// Usage to send ahead of `event` when it is the `Done` of a stream that reported none
+    pub fn before(&mut self, event: &StreamEvent) -> Option<StreamEvent> {
+        match event {
+            StreamEvent::TextDelta { text } | StreamEvent::ReasoningDelta { text, .. } => {
+                self.output.push_str(text);
+            }
+            StreamEvent::ToolCall {
+                tool_name, input, ..
+            } => {
+                self.output.push_str(tool_name);
+                self.output.push_str(&input.to_string());
+            }
+            StreamEvent::Usage { .. } => self.reported = true,
+            StreamEvent::Done { .. } if !self.reported => {
+                self.reported = true;
+                let output_tokens = self.estimator.count_tokens(self.family, &self.output);
+                return Some(StreamEvent::Usage {
+                    input_tokens: self.input_tokens as i32,
+                    output_tokens: output_tokens as i32,
+                    total_tokens: Some((self.input_tokens + output_tokens) as i32),
+                    cached_input_tokens: None,
+                    cache_creation_input_tokens: None,
+                    timing: None,
+                    estimated: true,
+                });
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system(text: &str) -> Message {
+        Message::System {
+            content: text.to_string(),
+            provider_options: None,
+            cache: false,
+        }
+    }
+
+    fn user(text: &str) -> Message {
+        Message::User {
+            content: MessageContent::Text(text.to_string()),
+            provider_options: None,
+            cache: false,
+        }
+    }
+
+    fn done() -> StreamEvent {
+        StreamEvent::Done {
+            finish_reason: Some("stop".to_string()),
+        }
+    }
+
+    #[test]
+    fn estimates_stay_close_to_reported_usage() {
+        // Usage the OpenAI API reports for this exchange with gpt-4
+        let messages = [
+            system("You are a helpful assistant."),
+            user("What is the capital of France?"),
+        ];
+        let output = "The capital of France is Paris.";
+        let (reported_input, reported_output) = (22.0, 7.0);
+
+        let usage = estimate_usage(ProtocolType::OpenAiCompatible, &messages, output);
+
+        let within = |estimate: u32, reported: f64| {
+            (f64::from(estimate) - reported).abs() / reported <= 0.25
+        };
+        assert!(within(usage.input_tokens, reported_input), "{:?}", usage);
+        assert!(within(usage.output_tokens, reported_output), "{:?}", usage);
+        assert!(usage.estimated);
+    }
+
+    #[test]
+    fn cjk_text_counts_one_token_per_character() {
+        let tokens = HeuristicEstimator.count_tokens(TokenizerFamily::Claude, "你好，世界");
+        assert_eq!(tokens, 5);
+    }
+
+    struct WordCount;
+
+    impl TokenEstimator for WordCount {
+        fn count_tokens(&self, _family: TokenizerFamily, text: &str) -> u32 {
+            text.split_whitespace().count() as u32
+        }
+    }
+
+    #[test]
+    fn fallback_fills_in_usage_only_when_none_was_reported() {
+        let messages = [user("count these four words")];
+        let mut fallback =
+            UsageFallback::with_estimator(Arc::new(WordCount), ProtocolType::Gemini, &messages);
+
+        assert!(fallback
+            .before(&StreamEvent::TextDelta {
+                text: "two words".to_string(),
+            })
+            .is_none());
+        let Some(StreamEvent::Usage {
+            input_tokens,
+            output_tokens,
+            total_tokens,
+            estimated,
+            ..
+        }) = fallback.before(&done())
+        else {
+            panic!("expected estimated usage");
+        };
+        assert_eq!((input_tokens, output_tokens), (4 + 3, 2));
+        assert_eq!(total_tokens, Some(9));
+        assert!(estimated);
+
+        let mut reported =
+            UsageFallback::with_estimator(Arc::new(WordCount), ProtocolType::Gemini, &messages);
+        reported.before(&StreamEvent::Usage {
+            input_tokens: 12,
+            output_tokens: 3,
+            total_tokens: Some(15),
+            cached_input_tokens: None,
+            cache_creation_input_tokens: None,
+            timing: None,
+            estimated: false,
+        });
+        assert!(reported.before(&done()).is_none());
+    }
+}
@@ -114,13 +114,11 @@ impl GoogleTranscriptionClient {
             api_key
         );
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
         let response = client
             .post(&url)
+            .timeout(Duration::from_secs(120))
             .header("Content-Type", "application/json")
             .json(&request)
             .send()
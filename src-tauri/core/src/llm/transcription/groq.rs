@@ -114,13 +114,11 @@ impl GroqTranscriptionClient {
             .unwrap_or_else(|| "verbose_json".to_string());
         form = form.text("response_format", response_format);
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(60))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
         let response = client
             .post(&url)
+            .timeout(Duration::from_secs(60))
             .bearer_auth(api_key)
             .multipart(form)
             .send()
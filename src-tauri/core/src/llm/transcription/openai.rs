@@ -1,4 +1,5 @@
-use crate::llm::auth::api_key_manager::ApiKeyManager;
+use crate::llm::auth::api_key_manager::{ApiKeyManager, ProviderCredentials};
+use crate::llm::http_client::SendWithMiddleware;
 use crate::llm::providers::provider::BaseProvider;
 use crate::llm::transcription::types::{TranscriptionContext, TranscriptionResult};
 use crate::llm::types::ProviderConfig;
@@ -14,6 +15,63 @@ struct OpenAIWhisperResponse {
     duration: Option<f32>,
 }
 
+/// Raw audio to transcribe, e.g. a dictated prompt that was never base64-encoded
+#[derive(Debug, Clone, Default)]
+pub struct AudioTranscriptionRequest {
+    pub audio: Vec<u8>,
+    /// File name sent with the audio part; providers detect the format from its extension
+    pub file_name: String,
+    /// Mime type of the audio part, guessed from `file_name` when unset
+    pub mime_type: Option<String>,
+    pub model: String,
+    /// ISO-639-1 language hint, e.g. "en"
+    pub language: Option<String>,
+    /// Text to guide spelling and style of the transcript
+    pub prompt: Option<String>,
+    pub temperature: Option<f32>,
+}
+
+impl AudioTranscriptionRequest {
+    /// Text fields of the multipart form, in the order they are sent
+    fn text_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("model", self.model.clone()),
+            ("response_format", "verbose_json".to_string()),
+        ];
+        let hints = [("language", &self.language), ("prompt", &self.prompt)];
+        for (name, value) in hints {
+            if let Some(value) = value.as_deref().map(str::trim) {
+                if !value.is_empty() {
+                    fields.push((name, value.to_string()));
+                }
+            }
+        }
+        if let Some(temperature) = self.temperature {
+            fields.push(("temperature", temperature.to_string()));
+        }
+        fields
+    }
+
+    fn into_form(self) -> Result<Form, String> {
+        let fields = self.text_fields();
+        let mime_type = self.mime_type.unwrap_or_else(|| {
+            mime_guess::from_path(&self.file_name)
+                .first_or_octet_stream()
+                .to_string()
+        });
+        let file_part = Part::bytes(self.audio)
+            .file_name(self.file_name)
+            .mime_str(&mime_type)
+            .map_err(|e| format!("Invalid mime type: {}", e))?;
+
+        let mut form = Form::new().part("file", file_part);
+        for (name, value) in fields {
+            form = form.text(name, value);
+        }
+        Ok(form)
+    }
+}
+
 pub struct OpenAITranscriptionClient {
     config: ProviderConfig,
 }
@@ -29,74 +87,73 @@ impl OpenAITranscriptionClient {
         model: &str,
         context: TranscriptionContext,
     ) -> Result<TranscriptionResult, String> {
-        let credentials = api_keys.get_credentials(&self.config).await?;
-        let api_key = match credentials {
-            crate::llm::auth::api_key_manager::ProviderCredentials::Token(token) => token,
-            _ => return Err("OpenAI API key not configured".to_string()),
-        };
-
-        let base = BaseProvider::new(self.config.clone());
-        let base_url = base.resolve_base_url_with_fallback(api_keys).await?;
-        let url = format!("{}/audio/transcriptions", base_url.trim_end_matches('/'));
-
         // Decode base64 audio
-        let audio_bytes = STANDARD
+        let audio = STANDARD
             .decode(context.audio_base64.as_bytes())
             .map_err(|e| format!("Invalid audio base64: {}", e))?;
 
         // Determine file extension from MIME type
         let file_ext = Self::detect_file_extension(&context.mime_type);
-        let file_name = format!("recording.{}", file_ext);
-
-        let file_part = Part::bytes(audio_bytes)
-            .file_name(file_name)
-            .mime_str(&context.mime_type)
-            .map_err(|e| format!("Invalid mime type: {}", e))?;
-
-        let mut form = Form::new()
-            .part("file", file_part)
-            .text("model", model.to_string())
-            .text("response_format", "verbose_json");
 
-        // Add optional parameters
-        if let Some(language) = context.language.as_ref() {
-            if !language.trim().is_empty() {
-                form = form.text("language", language.clone());
-            }
-        }
+        self.transcribe_audio(
+            api_keys,
+            AudioTranscriptionRequest {
+                audio,
+                file_name: format!("recording.{}", file_ext),
+                mime_type: Some(context.mime_type),
+                model: model.to_string(),
+                language: context.language,
+                prompt: context.prompt,
+                temperature: context.temperature,
+            },
+        )
+        .await
+    }
 
-        if let Some(prompt) = context.prompt.as_ref() {
-            if !prompt.trim().is_empty() {
-                form = form.text("prompt", prompt.clone());
+    /// Transcribe raw audio bytes
+    pub async fn transcribe_audio(
+        &self,
+        api_keys: &ApiKeyManager,
+        request: AudioTranscriptionRequest,
+    ) -> Result<TranscriptionResult, String> {
+        let name = &self.config.name;
+        let api_key = match api_keys.get_credentials_rotating(&self.config).await? {
+            ProviderCredentials::Token(token) => token,
+            ProviderCredentials::None => {
+                return Err(format!(
+                    "API key not configured for {} transcription / {} 语音转写未配置 API 密钥",
+                    name, name
+                ))
             }
-        }
-
-        if let Some(temperature) = context.temperature {
-            form = form.text("temperature", temperature.to_string());
-        }
+        };
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let base = BaseProvider::new(self.config.clone());
+        let base_url = base.resolve_base_url_with_fallback(api_keys).await?;
+        let url = format!("{}/audio/transcriptions", base_url.trim_end_matches('/'));
 
+        let client = crate::llm::http_client::shared_client()?;
+        // Multipart bodies cannot be cloned, so the request is sent without retries
         let response = client
             .post(&url)
-            .bearer_auth(api_key)
-            .multipart(form)
-            .send()
+            .timeout(Duration::from_secs(120))
+            .bearer_auth(&api_key)
+            .multipart(request.into_form()?)
+            .send_with_middleware()
             .await
-            .map_err(|e| format!("OpenAI transcription request failed: {}", e))?;
+            .map_err(|e| format!("{} transcription request failed: {}", name, e))?;
 
         if !response.status().is_success() {
             let status = response.status();
+            if status.as_u16() == 429 {
+                api_keys.mark_key_rate_limited(&api_key);
+            }
             let body = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
             return Err(format!(
-                "OpenAI transcription failed ({}): {}",
-                status, body
+                "{} transcription failed ({}): {} / {} 语音转写失败",
+                name, status, body, name
             ));
         }
 
@@ -136,6 +193,91 @@ impl OpenAITranscriptionClient {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::llm::testing::fixtures::{api_key_manager_with, provider_config};
+    use crate::llm::testing::mock_server::start_capture_server;
+    use crate::llm::types::ProtocolType;
+
+    fn dictation_request() -> AudioTranscriptionRequest {
+        AudioTranscriptionRequest {
+            audio: b"RIFF....WAVEfmt ".to_vec(),
+            file_name: "dictation.wav".to_string(),
+            mime_type: None,
+            model: "whisper-1".to_string(),
+            language: Some("en".to_string()),
+            prompt: Some("TalkCody, Tauri".to_string()),
+            temperature: None,
+        }
+    }
+
+    #[test]
+    fn text_fields_skip_empty_hints() {
+        let request = AudioTranscriptionRequest {
+            language: Some("  ".to_string()),
+            prompt: None,
+            ..dictation_request()
+        };
+
+        let names: Vec<&str> = request
+            .text_fields()
+            .iter()
+            .map(|(name, _)| *name)
+            .collect();
+
+        assert_eq!(names, vec!["model", "response_format"]);
+    }
+
+    #[tokio::test]
+    async fn transcribe_audio_sends_expected_multipart_fields() {
+        let body = br#"{"text":"hello world","language":"en","duration":1.5}"#.to_vec();
+        let (base_url, captured) = start_capture_server(200, body).expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+
+        let config = provider_config("openai", ProtocolType::OpenAiCompatible, &base_url);
+        let client = OpenAITranscriptionClient::new(config);
+        let result = client
+            .transcribe_audio(&api_keys, dictation_request())
+            .await
+            .expect("transcribe");
+
+        assert_eq!(result.text, "hello world");
+        assert_eq!(result.language.as_deref(), Some("en"));
+        let request = captured.recv().expect("captured request");
+        assert_eq!(request.url, "/audio/transcriptions");
+        assert!(request
+            .content_type
+            .as_deref()
+            .is_some_and(|value| value.starts_with("multipart/form-data")));
+        let body = String::from_utf8_lossy(&request.body);
+        assert!(body.contains(r#"name="file"; filename="dictation.wav""#));
+        assert!(body.contains("Content-Type: audio/wav"));
+        for field in ["model", "response_format", "language", "prompt"] {
+            assert!(
+                body.contains(&format!(r#"name="{}""#, field)),
+                "missing field {}",
+                field
+            );
+        }
+        assert!(body.contains("whisper-1"));
+        assert!(body.contains("TalkCody, Tauri"));
+    }
+
+    #[tokio::test]
+    async fn transcribe_audio_reports_provider_errors_bilingually() {
+        let (base_url, _captured) =
+            start_capture_server(400, br#"{"error":{"message":"bad audio"}}"#.to_vec())
+                .expect("start mock server");
+        let (_dir, api_keys) = api_key_manager_with(&[("api_key_openai", "test-key")]).await;
+
+        let config = provider_config("openai", ProtocolType::OpenAiCompatible, &base_url);
+        let client = OpenAITranscriptionClient::new(config);
+        let err = client
+            .transcribe_audio(&api_keys, dictation_request())
+            .await
+            .expect_err("provider error");
+
+        assert!(err.contains("bad audio"));
+        assert!(err.contains("语音转写失败"));
+    }
 
     #[test]
     fn detects_wav_extension() {
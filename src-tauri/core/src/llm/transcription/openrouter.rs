@@ -102,13 +102,11 @@ impl OpenRouterTranscriptionClient {
             }],
         };
 
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        let client = crate::llm::http_client::shared_client()?;
 
         let response = client
             .post(&url)
+            .timeout(Duration::from_secs(120))
             .bearer_auth(api_key)
             .header("HTTP-Referer", "https://talkcody.com")
             .header("X-Title", "TalkCody")
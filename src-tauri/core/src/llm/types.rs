@@ -1,3 +1,14 @@
+use crate::llm::context_window::TrimStrategy;
+use crate::llm::image_generation::batch::BatchImageError;
+use crate::llm::image_generation::cache::CacheValidators;
+use crate::llm::image_generation::types::sniff_base64_image_mime;
+use crate::llm::image_input::ImageInputCheck;
+use crate::llm::providers::provider::DryRunRequest;
+use crate::llm::rate_limit::RateLimitInfo;
+use crate::llm::request_id::RequestIds;
+use crate::llm::retry::RetryPolicy;
+use crate::llm::stream_metrics::StreamMetrics;
+use crate::llm::template::PromptTemplate;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -6,6 +17,9 @@ use std::collections::HashMap;
 pub enum ProtocolType {
     OpenAiCompatible,
     Claude,
+    Anthropic,
+    Gemini,
+    Cohere,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +46,36 @@ pub struct ProviderConfig {
     pub extra_body: Option<serde_json::Value>,
     #[serde(rename = "authType")]
     pub auth_type: AuthType,
+    #[serde(rename = "retryPolicy")]
+    pub retry_policy: Option<RetryPolicy>,
+    /// Short model names mapped to this provider's model IDs, e.g. `llama` -> `llama-3.3-70b`
+    #[serde(
+        default,
+        rename = "modelAliases",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub model_aliases: HashMap<String, String>,
+    /// Model used when a request does not name one
+    #[serde(default, rename = "defaultModel")]
+    pub default_model: Option<String>,
+    /// Repair or skip malformed stream chunks instead of failing the response, for vendors
+    /// that send concatenated objects or trailing commas
+    #[serde(default, rename = "tolerantStreamJson")]
+    pub tolerant_stream_json: bool,
+    /// Largest request body sent to this provider, in bytes; `None` uses
+    /// `DEFAULT_MAX_REQUEST_BYTES`
+    #[serde(default, rename = "maxRequestBytes")]
+    pub max_request_bytes: Option<usize>,
+    /// Upload vision images as multipart file fields instead of base64 data URLs, for custom
+    /// endpoints that accept them
+    #[serde(default, rename = "multipartImages")]
+    pub multipart_images: bool,
+    /// Sent as `OpenAI-Organization` to bill an organization other than the key's default
+    #[serde(default)]
+    pub organization: Option<String>,
+    /// Sent as `OpenAI-Project` to route usage to one project of the organization
+    #[serde(default)]
+    pub project: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -61,6 +105,7 @@ pub struct ModelConfig {
     #[serde(rename = "providerMappings")]
     pub provider_mappings: Option<HashMap<String, String>>,
     pub pricing: Option<ModelPricing>,
+    #[serde(alias = "contextWindow")]
     pub context_length: Option<u32>,
 }
 
@@ -99,6 +144,22 @@ pub struct AvailableModel {
     pub input_pricing: Option<String>,
 }
 
+/// A model reported by a provider's model discovery endpoint
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    pub created: i64,
+    #[serde(rename = "ownedBy")]
+    pub owned_by: Option<String>,
+    /// Prompt plus reply tokens the model accepts, when the model list reports it
+    #[serde(
+        default,
+        rename = "contextWindow",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub context_window: Option<u32>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TraceContext {
     #[serde(rename = "traceId")]
@@ -111,6 +172,21 @@ pub struct TraceContext {
     pub metadata: Option<HashMap<String, String>>,
 }
 
+/// Output format the model is asked to follow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Any syntactically valid JSON object
+    JsonObject,
+    /// JSON matching `schema`, enforced by providers that support structured output
+    JsonSchema {
+        name: String,
+        schema: serde_json::Value,
+        #[serde(default)]
+        strict: Option<bool>,
+    },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamTextRequest {
     pub model: String,
@@ -124,12 +200,67 @@ pub struct StreamTextRequest {
     pub top_p: Option<f32>,
     #[serde(rename = "topK")]
     pub top_k: Option<i32>,
+    #[serde(default, rename = "frequencyPenalty")]
+    pub frequency_penalty: Option<f32>,
+    #[serde(default, rename = "presencePenalty")]
+    pub presence_penalty: Option<f32>,
+    /// Sequences that end generation when produced
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+    #[serde(default, rename = "responseFormat")]
+    pub response_format: Option<ResponseFormat>,
+    /// End-user identifier for provider-side abuse monitoring
+    #[serde(default, rename = "userId")]
+    pub user_id: Option<String>,
+    /// One-off base URL used instead of the stored provider endpoint
+    #[serde(default, rename = "baseUrlOverride")]
+    pub base_url_override: Option<String>,
+    /// Variables filled into `{{name}}` placeholders in the message text
+    #[serde(default)]
+    pub template: Option<PromptTemplate>,
+    /// Trim the oldest messages when the prompt would not fit the model's context window
+    #[serde(default, rename = "trimStrategy")]
+    pub trim_strategy: Option<TrimStrategy>,
+    /// Ask for per-token log probabilities, on providers that report them
+    #[serde(default)]
+    pub logprobs: Option<bool>,
+    /// Most likely alternatives reported for each token, up to 20
+    #[serde(default, rename = "topLogprobs")]
+    pub top_logprobs: Option<u8>,
+    /// Build the request and report it as a `DryRun` event instead of sending it
+    #[serde(default, rename = "dryRun")]
+    pub dry_run: Option<bool>,
+    /// Provider-side tools such as web search, on providers that offer them
+    #[serde(default, rename = "builtinTools")]
+    pub builtin_tools: Option<Vec<BuiltinTool>>,
+    /// Thinking depth for reasoning models; other models get the request without it
+    #[serde(default, rename = "reasoningEffort")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Outgoing headers to drop for this request, on top of `HttpSettings::header_denylist`
+    #[serde(default, rename = "stripHeaders")]
+    pub strip_headers: Option<Vec<String>>,
+    /// Cap on HTTP attempts for this request, counting retries and stream replays
+    #[serde(default, rename = "maxAttempts")]
+    pub max_attempts: Option<u32>,
+    /// Whether images sent to a model without vision fail the request or only log a warning
+    #[serde(default, rename = "imageInputCheck")]
+    pub image_input_check: Option<ImageInputCheck>,
     #[serde(rename = "providerOptions")]
     pub provider_options: Option<serde_json::Value>,
     #[serde(rename = "requestId")]
     pub request_id: Option<String>,
     #[serde(rename = "traceContext")]
     pub trace_context: Option<TraceContext>,
+    /// Deadline for the whole request in milliseconds; 0 disables the per-request deadline
+    #[serde(rename = "timeoutMs")]
+    pub timeout_ms: Option<u64>,
+    /// Longest wait for the next chunk in milliseconds before the stream counts as stalled,
+    /// 60s when unset; 0 disables the check
+    #[serde(default, rename = "stallTimeoutMs")]
+    pub stall_timeout_ms: Option<u64>,
+    /// Whether the request may be sent again on a retry or stream replay, true when unset
+    #[serde(default)]
+    pub idempotent: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -144,24 +275,112 @@ pub enum Message {
         content: String,
         #[serde(default, rename = "providerOptions")]
         provider_options: Option<serde_json::Value>,
+        /// Ends a cacheable prompt prefix on providers with prompt caching
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        cache: bool,
     },
     User {
         content: MessageContent,
         #[serde(default, rename = "providerOptions")]
         provider_options: Option<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        cache: bool,
     },
     Assistant {
         content: MessageContent,
         #[serde(default, rename = "providerOptions")]
         provider_options: Option<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        cache: bool,
     },
     Tool {
         content: Vec<ContentPart>,
         #[serde(default, rename = "providerOptions")]
         provider_options: Option<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        cache: bool,
     },
 }
 
+/// Provider option paths the frontend uses to request a cache breakpoint
+const CACHE_CONTROL_OPTIONS: [&str; 3] = [
+    "/anthropic/cacheControl",
+    "/openrouter/cache_control",
+    "/openaiCompatible/cache_control",
+];
+
+impl Message {
+    /// Whether the message ends a cacheable prefix, flagged directly or via provider options
+    pub fn is_cache_breakpoint(&self) -> bool {
+        let (Message::System {
+            cache,
+            provider_options,
+            ..
+        }
+        | Message::User {
+            cache,
+            provider_options,
+            ..
+        }
+        | Message::Assistant {
+            cache,
+            provider_options,
+            ..
+        }
+        | Message::Tool {
+            cache,
+            provider_options,
+            ..
+        }) = self;
+        *cache
+            || provider_options.as_ref().is_some_and(|options| {
+                CACHE_CONTROL_OPTIONS
+                    .iter()
+                    .any(|path| options.pointer(path).is_some())
+            })
+    }
+}
+
+/// Check that every tool result answers a tool call made earlier in the conversation
+/// Providers reject a `tool` message whose `tool_call_id` they have not seen, usually with an
+/// error that does not say which one
+pub fn check_tool_results(messages: &[Message]) -> Result<(), String> {
+    let mut call_ids = std::collections::HashSet::new();
+    for message in messages {
+        match message {
+            Message::Assistant {
+                content: MessageContent::Parts(parts),
+                ..
+            } => {
+                for part in parts {
+                    if let ContentPart::ToolCall { tool_call_id, .. } = part {
+                        call_ids.insert(tool_call_id.as_str());
+                    }
+                }
+            }
+            Message::Tool { content, .. } => {
+                for part in content {
+                    if let ContentPart::ToolResult {
+                        tool_call_id,
+                        tool_name,
+                        ..
+                    } = part
+                    {
+                        if !call_ids.contains(tool_call_id.as_str()) {
+                            return Err(format!(
+                                "Tool result '{}' ({}) has no matching tool call before it / 工具结果 '{}' 没有对应的工具调用",
+                                tool_call_id, tool_name, tool_call_id
+                            ));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum MessageContent {
@@ -175,7 +394,12 @@ pub enum ContentPart {
     #[serde(rename = "text")]
     Text { text: String },
     #[serde(rename = "image")]
-    Image { image: String },
+    Image {
+        /// Base64 data, a `data:` URL, or an http(s) URL
+        image: String,
+        #[serde(default, rename = "mimeType")]
+        mime_type: Option<String>,
+    },
     #[serde(rename = "video")]
     Video {
         video: String,
@@ -208,6 +432,52 @@ pub enum ContentPart {
     },
 }
 
+/// Where the bytes of an image part come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSource<'a> {
+    /// Base64 payload, given raw or unwrapped from a `data:` URL
+    Base64 { mime_type: &'a str, data: &'a str },
+    /// http(s) URL the provider fetches itself
+    Url(&'a str),
+}
+
+impl<'a> ImageSource<'a> {
+    /// Split an image part into its source; a `data:` URL's own mime type wins over `mime_type`
+    pub fn of(image: &'a str, mime_type: Option<&'a str>) -> Self {
+        if image.starts_with("http://") || image.starts_with("https://") {
+            return Self::Url(image);
+        }
+        if let Some((header, data)) = image
+            .strip_prefix("data:")
+            .and_then(|rest| rest.split_once(','))
+        {
+            let declared = header.split(';').next().filter(|m| !m.trim().is_empty());
+            return Self::base64(data, declared.or(mime_type));
+        }
+        Self::base64(image, mime_type)
+    }
+
+    /// The explicit mime type wins; otherwise it is sniffed from the data, defaulting to PNG
+    fn base64(data: &'a str, mime_type: Option<&'a str>) -> Self {
+        let mime_type = mime_type
+            .filter(|m| !m.trim().is_empty())
+            .or_else(|| sniff_base64_image_mime(data))
+            .unwrap_or("image/png");
+        Self::Base64 { mime_type, data }
+    }
+}
+
+/// URL for an image part, wrapping raw base64 data in a `data:` URL
+pub fn image_url(image: &str, mime_type: Option<&str>) -> String {
+    if image.starts_with("data:") {
+        return image.to_string();
+    }
+    match ImageSource::of(image, mime_type) {
+        ImageSource::Url(url) => url.to_string(),
+        ImageSource::Base64 { mime_type, data } => format!("data:{};base64,{}", mime_type, data),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolDefinition {
     #[serde(rename = "type")]
@@ -218,13 +488,75 @@ pub struct ToolDefinition {
     pub strict: bool,
 }
 
+/// Tool the provider runs on its side, switched on by a request flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuiltinTool {
+    WebSearch,
+    CodeInterpreter,
+}
+
+impl BuiltinTool {
+    pub fn label(self) -> &'static str {
+        match self {
+            BuiltinTool::WebSearch => "web search",
+            BuiltinTool::CodeInterpreter => "code interpreter",
+        }
+    }
+}
+
+/// How hard a reasoning model thinks before answering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    /// Level name, as OpenAI's `reasoning_effort` takes it
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+
+    /// Thinking tokens for APIs that take a budget instead of a level
+    pub fn budget_tokens(self) -> u32 {
+        match self {
+            ReasoningEffort::Low => 1_024,
+            ReasoningEffort::Medium => 8_192,
+            ReasoningEffort::High => 24_576,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "kebab-case")]
 pub enum StreamEvent {
+    /// First event of a response, naming the model that is answering
+    /// `model` is the one the provider served, which may differ from the requested one
+    Start {
+        model: String,
+        /// Backend configuration that served the request, as OpenAI-style APIs report it
+        #[serde(
+            default,
+            rename = "systemFingerprint",
+            skip_serializing_if = "Option::is_none"
+        )]
+        system_fingerprint: Option<String>,
+    },
     TextStart,
     TextDelta {
         text: String,
     },
+    /// Log probabilities of the tokens in the text just streamed, when the request asked for them
+    LogProbs {
+        tokens: Vec<TokenLogProb>,
+    },
     ToolCall {
         #[serde(rename = "toolCallId")]
         tool_call_id: String,
@@ -234,6 +566,15 @@ pub enum StreamEvent {
         #[serde(default)]
         provider_metadata: Option<serde_json::Value>,
     },
+    /// Incremental tool-call fragment, emitted alongside the final `ToolCall`
+    /// `id` and `name` arrive on the first fragment of a call; `index` ties later ones to it
+    ToolCallDelta {
+        index: u32,
+        id: Option<String>,
+        name: Option<String>,
+        #[serde(rename = "argumentsChunk")]
+        arguments_chunk: String,
+    },
     ReasoningStart {
         id: String,
         #[serde(default)]
@@ -248,12 +589,36 @@ pub enum StreamEvent {
     ReasoningEnd {
         id: String,
     },
+    /// Source backing the response, such as a live search result
+    Citation {
+        url: String,
+        title: Option<String>,
+    },
     Usage {
         input_tokens: i32,
         output_tokens: i32,
         total_tokens: Option<i32>,
         cached_input_tokens: Option<i32>,
         cache_creation_input_tokens: Option<i32>,
+        /// Server-side timings, for providers that report them with usage
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timing: Option<UsageTiming>,
+        /// Counted by `tokenize` because the provider reported no usage
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        estimated: bool,
+    },
+    /// Provider quotas read from the response headers, sent before the body streams
+    RateLimit(RateLimitInfo),
+    /// Client and provider request IDs of the call, sent when the response arrives
+    RequestIds(RequestIds),
+    /// Why generation ended, from the final chunk: `stop`, `length`, `content_filter` or
+    /// `tool_calls`; the stream itself still ends with `Done`
+    Stop {
+        reason: String,
+        /// Categories the provider's moderation flagged, sent with `content_filter` when the
+        /// provider reports them (Azure's `content_filter_results`)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content_filter_results: Option<serde_json::Value>,
     },
     Done {
         finish_reason: Option<String>,
@@ -264,6 +629,73 @@ pub enum StreamEvent {
     Raw {
         raw_value: String,
     },
+    /// Timing of the whole stream, sent last, after `Done`, an error or a cancellation
+    Metrics(StreamMetrics),
+    /// The request a dry run built, sent in place of the provider's response
+    DryRun(DryRunRequest),
+}
+
+/// Time the provider spent on a request, in seconds, as reported next to token usage
+/// Groq sends `queue_time`, `prompt_time`, `completion_time` and `total_time`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTiming {
+    pub queue_time: Option<f64>,
+    pub prompt_time: Option<f64>,
+    pub completion_time: Option<f64>,
+    pub total_time: Option<f64>,
+}
+
+impl UsageTiming {
+    /// Timing fields of a usage object; `None` when it carries none of them
+    pub fn from_usage(usage: &serde_json::Value) -> Option<Self> {
+        let read = |key: &str| usage.get(key).and_then(|v| v.as_f64());
+        let timing = Self {
+            queue_time: read("queue_time"),
+            prompt_time: read("prompt_time"),
+            completion_time: read("completion_time"),
+            total_time: read("total_time"),
+        };
+        (timing != Self::default()).then_some(timing)
+    }
+}
+
+/// Log probability of one generated token, with the likeliest alternatives when requested
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogProb {
+    pub token: String,
+    pub logprob: f64,
+    #[serde(default, rename = "topLogprobs", skip_serializing_if = "Vec::is_empty")]
+    pub top_logprobs: Vec<TokenLogProb>,
+}
+
+impl TokenLogProb {
+    /// Tokens of an OpenAI `logprobs` object (`{"content": [...]}`); `None` when it has none
+    pub fn from_openai(logprobs: &serde_json::Value) -> Option<Vec<Self>> {
+        let tokens: Vec<Self> = logprobs
+            .get("content")?
+            .as_array()?
+            .iter()
+            .filter_map(Self::from_openai_entry)
+            .collect();
+        (!tokens.is_empty()).then_some(tokens)
+    }
+
+    fn from_openai_entry(entry: &serde_json::Value) -> Option<Self> {
+        Some(Self {
+            token: entry.get("token")?.as_str()?.to_string(),
+            logprob: entry.get("logprob")?.as_f64()?,
+            top_logprobs: entry
+                .get("top_logprobs")
+                .and_then(|v| v.as_array())
+                .map(|alternatives| {
+                    alternatives
+                        .iter()
+                        .filter_map(Self::from_openai_entry)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -292,6 +724,9 @@ pub struct ImageGenerationRequest {
     pub model: String,
     pub prompt: String,
     pub size: Option<String>,
+    /// Shape such as `16:9`, used when `size` is not set
+    #[serde(default, rename = "aspectRatio")]
+    pub aspect_ratio: Option<String>,
     pub quality: Option<String>,
     pub n: Option<u32>,
     #[serde(rename = "responseFormat")]
@@ -300,6 +735,33 @@ pub struct ImageGenerationRequest {
     pub provider_options: Option<serde_json::Value>,
     #[serde(rename = "requestId")]
     pub request_id: Option<String>,
+    /// Source image for image-to-image editing
+    pub image: Option<Vec<u8>>,
+    /// Inpainting mask applied to `image`
+    pub mask: Option<Vec<u8>>,
+    /// Client timeout in milliseconds, defaults to 120s; 0 means no timeout
+    #[serde(rename = "timeoutMs")]
+    pub timeout_ms: Option<u64>,
+    /// Fixed seed so the same prompt reproduces the same image
+    pub seed: Option<i64>,
+    /// Content the image should avoid
+    #[serde(rename = "negativePrompt")]
+    pub negative_prompt: Option<String>,
+    /// Send `size` as-is even when the model has a known list of supported sizes
+    #[serde(rename = "skipSizeValidation", default)]
+    pub skip_size_validation: bool,
+    /// Reject an `n` above the model's per-request limit instead of clamping or splitting it
+    #[serde(rename = "strictN", default)]
+    pub strict_n: bool,
+    /// Download URL-only results and inline them as base64, keeping the URL as well
+    #[serde(rename = "downloadUrls", default)]
+    pub download_urls: bool,
+    /// End-user identifier for provider-side abuse monitoring
+    #[serde(default, rename = "userId")]
+    pub user_id: Option<String>,
+    /// Variables filled into `{{name}}` placeholders in `prompt`
+    #[serde(default)]
+    pub template: Option<PromptTemplate>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -309,6 +771,22 @@ pub struct ImageGenerationResponse {
     pub images: Vec<GeneratedImage>,
     #[serde(rename = "requestId")]
     pub request_id: Option<String>,
+    /// Quotas from the provider's last response, when it reports them
+    #[serde(default, rename = "rateLimit", skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimitInfo>,
+    /// IDs to quote when matching this generation against the provider's logs
+    #[serde(
+        default,
+        rename = "requestIds",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub request_ids: Option<RequestIds>,
+    /// Requests of a fanned-out batch that failed while others returned images
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<BatchImageError>,
+    /// Validators the images came with, kept for the image cache and never sent to the UI
+    #[serde(skip)]
+    pub cache_validators: Option<CacheValidators>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -320,6 +798,26 @@ pub struct GeneratedImage {
     pub mime_type: String,
     #[serde(rename = "revisedPrompt")]
     pub revised_prompt: Option<String>,
+    /// Unix seconds after which `url` stops working, read from its signature when it has one
+    #[serde(default, rename = "expiresAt", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+}
+
+/// Request to embed a batch of texts with an OpenAI-compatible provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingRequest {
+    #[serde(rename = "providerId")]
+    pub provider_id: String,
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingResponse {
+    pub provider: String,
+    pub model: String,
+    /// One vector per input, in input order
+    pub embeddings: Vec<Vec<f32>>,
 }
 
 /// Request to download an image from a URL
@@ -336,6 +834,14 @@ pub struct ImageDownloadResponse {
     pub mime_type: String,
 }
 
+/// Request to renew a generated image whose URL may have expired
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageRefreshRequest {
+    pub image: GeneratedImage,
+    /// Request the image was generated from, sent again when its URL no longer works
+    pub request: ImageGenerationRequest,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CustomProviderConfig {
     pub id: String,
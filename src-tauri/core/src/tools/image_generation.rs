@@ -52,6 +52,7 @@ pub async fn execute(
         model: String::new(), // Empty string lets backend auto-select
         prompt: prompt.to_string(),
         size: Some(size.unwrap_or("1024x1024").to_string()),
+        aspect_ratio: None,
         quality: Some(
             if quality == Some("high") {
                 "hd"
@@ -64,6 +65,16 @@ pub async fn execute(
         response_format: Some("url".to_string()),
         provider_options: None,
         request_id: None,
+        image: None,
+        mask: None,
+        timeout_ms: None,
+        seed: None,
+        negative_prompt: None,
+        skip_size_validation: false,
+        strict_n: false,
+        download_urls: false,
+        user_id: None,
+        template: None,
     };
 
     // TODO: To enable actual image generation:
@@ -143,6 +154,7 @@ async fn _execute_full_impl(
         model: String::new(),
         prompt: prompt.to_string(),
         size: Some(size.unwrap_or("1024x1024").to_string()),
+        aspect_ratio: None,
         quality: Some(
             if quality == Some("high") {
                 "hd"
@@ -155,6 +167,16 @@ async fn _execute_full_impl(
         response_format: Some("url".to_string()),
         provider_options: None,
         request_id: None,
+        image: None,
+        mask: None,
+        timeout_ms: None,
+        seed: None,
+        negative_prompt: None,
+        skip_size_validation: false,
+        strict_n: false,
+        download_urls: false,
+        user_id: None,
+        template: None,
     };
 
     // This would work if LlmState was in ToolContext:
@@ -49,7 +49,6 @@ use std::process::Stdio;
 use std::sync::OnceLock;
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant, SystemTime};
-use talkcody_core::core::types::RuntimeEvent;
 use talkcody_server::{config::ServerConfig, state::ServerStateFactory};
 use tauri::{AppHandle, Emitter, Manager, State, WindowEvent};
 use tokio::io::BufReader;
@@ -808,12 +807,11 @@ pub fn run() {
 
             // Start Cloud Backend Server with full runtime
             let server_config = ServerConfig::new(app_data_dir.clone(), app_data_dir.clone());
-            let (event_tx, _event_rx) = tokio::sync::mpsc::unbounded_channel::<RuntimeEvent>();
 
             let server_handle = app.handle().clone();
             let server_config_clone = server_config.clone();
             tauri::async_runtime::spawn(async move {
-                match ServerStateFactory::create(server_config_clone, event_tx).await {
+                match ServerStateFactory::create(server_config_clone).await {
                     Ok(_server_state) => {
                         // Start server with the configured state
                         let bind_addr = std::net::SocketAddr::from(([127, 0, 0, 1], 0));
@@ -843,6 +841,8 @@ pub fn run() {
                 app_data_dir.clone(),
                 llm::providers::provider_configs::builtin_providers(),
             );
+            // No secret cipher yet: the frontend still reads and writes credentials in the
+            // settings table directly and would see ciphertext
             app.manage(llm_state);
 
             let model_sync_handle = app.handle().clone();
@@ -855,6 +855,13 @@ pub fn run() {
                         let guard = state.api_keys.lock().await;
                         guard.clone()
                     };
+                    // Apply saved proxy settings before the first outbound request
+                    if let Err(e) = llm::http_client::load_http_settings(&api_keys).await {
+                        log::warn!("Failed to load HTTP settings: {}", e);
+                    }
+                    if let Err(e) = llm::request_log::load_request_log_settings(&api_keys).await {
+                        log::warn!("Failed to load request log settings: {}", e);
+                    }
                     llm::models::model_sync::start_background_sync(
                         model_sync_handle.clone(),
                         api_keys,
@@ -895,6 +902,14 @@ pub fn run() {
                                         headers: None,
                                         extra_body: None,
                                         auth_type: crate::llm::types::AuthType::Bearer,
+                                        retry_policy: None,
+                                        model_aliases: Default::default(),
+                                        default_model: None,
+                                        tolerant_stream_json: false,
+                                        max_request_bytes: None,
+                                        multipart_images: false,
+                                        organization: None,
+                                        project: None,
                                     });
                                 }
                             }
@@ -1067,14 +1082,31 @@ pub fn run() {
             lsp::lsp_download_server,
             oauth_callback_server::start_oauth_callback_server,
             llm_commands::llm_stream_text,
+            llm_commands::llm_cancel_stream,
+            llm_commands::llm_dry_run_request,
+            llm_commands::llm_export_request_bundle,
+            llm_commands::llm_count_tokens,
             llm_commands::llm_list_available_models,
+            llm_commands::llm_list_provider_models,
+            llm_commands::llm_check_provider_health,
+            llm_commands::llm_get_http_settings,
+            llm_commands::llm_set_http_settings,
+            llm_commands::llm_get_request_log_settings,
+            llm_commands::llm_set_request_log_settings,
+            llm_commands::llm_get_price_overrides,
+            llm_commands::llm_set_price_overrides,
+            llm_commands::llm_estimate_cost,
             llm_commands::llm_register_custom_provider,
             llm_commands::llm_check_model_updates,
             llm_commands::llm_get_provider_configs,
+            llm_commands::llm_get_provider_presets,
             llm_commands::llm_get_models_config,
             llm_commands::llm_is_model_available,
             llm_commands::llm_transcribe_audio,
             llm_commands::llm_generate_image,
+            llm_commands::llm_refresh_image_url,
+            llm_commands::llm_create_embeddings,
+            llm_commands::llm_get_provider_capabilities,
             llm_commands::llm_download_image,
             llm_commands::llm_calculate_cost,
             llm_commands::llm_get_completion,
@@ -1099,6 +1131,8 @@ pub fn run() {
             llm::auth::oauth::llm_github_copilot_oauth_disconnect,
             llm::auth::oauth::llm_github_copilot_oauth_tokens,
             llm::auth::oauth::llm_oauth_status,
+            llm::auth::device_flow::llm_oauth_device_start,
+            llm::auth::device_flow::llm_oauth_device_poll,
             device_id::get_device_id,
             keep_awake::keep_awake_acquire,
             keep_awake::keep_awake_release,
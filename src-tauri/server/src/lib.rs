@@ -8,7 +8,6 @@ use std::env;
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 
-use talkcody_core::core::types::EventSender;
 use talkcody_core::security::api_key_middleware;
 
 use crate::state::ServerStateFactory;
@@ -20,12 +19,9 @@ pub struct ServerHandle {
     pub addr: SocketAddr,
 }
 
-pub async fn start_server(
-    config: ServerConfig,
-    event_sender: EventSender,
-) -> Result<ServerHandle, String> {
+pub async fn start_server(config: ServerConfig) -> Result<ServerHandle, String> {
     // Create server state with all dependencies
-    let state = ServerStateFactory::create(config, event_sender)
+    let state = ServerStateFactory::create(config)
         .await
         .map_err(|e| format!("Failed to create server state: {}", e))?;
 
@@ -8,7 +8,6 @@ use std::path::PathBuf;
 use tokio::net::TcpListener;
 use tower_http::cors::{Any, CorsLayer};
 
-use talkcody_core::core::types::RuntimeEvent;
 use talkcody_server::config::ServerConfig;
 use talkcody_server::routes;
 use talkcody_server::state::ServerStateFactory;
@@ -33,11 +32,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     log::info!("Loaded config: {:?}", config);
     log::info!("Server will bind to: {}", bind_addr);
 
-    // Create event channel
-    let (event_tx, _event_rx) = tokio::sync::mpsc::unbounded_channel::<RuntimeEvent>();
-
     // Create server state
-    let state = ServerStateFactory::create(config.clone(), event_tx)
+    let state = ServerStateFactory::create(config.clone())
         .await
         .map_err(|e| format!("Failed to create server state: {}", e))?;
 
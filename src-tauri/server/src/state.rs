@@ -1,14 +1,17 @@
 use std::collections::HashMap;
 use std::env;
 use std::sync::Arc;
-use talkcody_core::core::types::{EventSender, RuntimeEvent};
+use talkcody_core::core::types::{event_channel, RuntimeEvent};
 use talkcody_core::core::CoreRuntime;
 use talkcody_core::llm::auth::api_key_manager::ApiKeyManager;
+use talkcody_core::llm::auth::secret_store::{PassphraseKeySource, SecretCipher};
 use talkcody_core::llm::providers::provider_registry::ProviderRegistry;
 use talkcody_core::platform::Platform;
 use talkcody_core::storage::Storage;
 use talkcody_core::streaming::StreamingManager;
-use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio::sync::{broadcast, RwLock};
+
+const SECRETS_SALT_KEY: &str = "secrets_passphrase_salt";
 
 /// Server state shared across all request handlers
 #[derive(Clone)]
@@ -65,6 +68,33 @@ impl ServerState {
     }
 }
 
+/// Encrypt stored credentials with a key derived from `TALKCODY_SECRETS_PASSPHRASE`, if set
+async fn with_passphrase_encryption(
+    api_key_manager: ApiKeyManager,
+) -> Result<ApiKeyManager, String> {
+    let passphrase = match env::var("TALKCODY_SECRETS_PASSPHRASE") {
+        Ok(value) if !value.is_empty() => value,
+        _ => return Ok(api_key_manager),
+    };
+
+    // The salt is not secret but must stay stable for existing values to decrypt
+    let salt = match api_key_manager.get_setting(SECRETS_SALT_KEY).await? {
+        Some(encoded) if !encoded.is_empty() => hex::decode(encoded)
+            .map_err(|e| format!("Invalid {} setting: {}", SECRETS_SALT_KEY, e))?,
+        _ => {
+            let salt = PassphraseKeySource::generate_salt();
+            api_key_manager
+                .set_setting(SECRETS_SALT_KEY, &hex::encode(&salt))
+                .await?;
+            salt
+        }
+    };
+
+    let cipher = SecretCipher::from_source(&PassphraseKeySource::new(passphrase, salt))?;
+    log::info!("[ServerState] Stored credentials are encrypted with the configured passphrase");
+    Ok(api_key_manager.with_secret_cipher(cipher))
+}
+
 async fn bootstrap_provider_api_keys_from_env(
     api_key_manager: &ApiKeyManager,
     provider_registry: &ProviderRegistry,
@@ -160,10 +190,7 @@ pub struct ServerStateFactory;
 
 impl ServerStateFactory {
     /// Create server state with the given configuration
-    pub async fn create(
-        config: super::config::ServerConfig,
-        _event_sender: EventSender,
-    ) -> Result<ServerState, String> {
+    pub async fn create(config: super::config::ServerConfig) -> Result<ServerState, String> {
         // Create storage
         let storage =
             Storage::new(config.data_root.clone(), config.attachments_root.clone()).await?;
@@ -172,6 +199,7 @@ impl ServerStateFactory {
         let provider_registry = ProviderRegistry::default();
         let db = storage.settings.get_db();
         let api_key_manager = ApiKeyManager::new(db, config.data_root.clone());
+        let api_key_manager = with_passphrase_encryption(api_key_manager).await?;
 
         if let Err(error) =
             bootstrap_provider_api_keys_from_env(&api_key_manager, &provider_registry).await
@@ -187,7 +215,7 @@ impl ServerStateFactory {
 
         // Create an event forwarding channel
         // We need a receiver to forward events from the runtime to broadcast
-        let (forward_tx, mut forward_rx) = mpsc::unbounded_channel::<RuntimeEvent>();
+        let (forward_tx, mut forward_rx) = event_channel();
 
         // Clone broadcast_tx for the forwarding task
         let broadcast_tx_for_task = broadcast_tx.clone();
@@ -48,6 +48,7 @@ export function getDocLinks() {
       google: `${BASE_URL}/configuration/api-keys#google-ai`,
       groq: `${BASE_URL}/configuration/api-keys#groq`,
       deepseek: `${BASE_URL}/configuration/api-keys#deepseek`,
+      deepseek_coding: `${BASE_URL}/configuration/api-keys#deepseek`,
       ollama: `${BASE_URL}/configuration/api-keys#ollama`,
       lmstudio: `${BASE_URL}/configuration/api-keys#lm-studio`,
       tavily: `${BASE_URL}/configuration/api-keys#tavily`,
@@ -18,6 +18,14 @@ export const PROVIDER_CONFIGS: ProviderRegistry = {
     supportsOAuth: true, // Supports OpenAI ChatGPT Plus/Pro OAuth authentication
   },
 
+  azure: {
+    id: 'azure',
+    name: 'Azure OpenAI',
+    apiKeyName: 'AZURE_OPENAI_API_KEY',
+    required: false,
+    type: 'openai-compatible',
+  },
+
   github_copilot: {
     id: 'github_copilot',
     name: 'GitHub Copilot',
@@ -95,6 +103,44 @@ export const PROVIDER_CONFIGS: ProviderRegistry = {
     type: 'openai-compatible',
   },
 
+  xai: {
+    id: 'xai',
+    name: 'xAI Grok',
+    apiKeyName: 'XAI_API_KEY',
+    baseUrl: 'https://api.x.ai/v1',
+    required: false,
+    type: 'openai-compatible',
+  },
+
+  mistral: {
+    id: 'mistral',
+    name: 'Mistral',
+    apiKeyName: 'MISTRAL_API_KEY',
+    baseUrl: 'https://api.mistral.ai/v1',
+    required: false,
+    type: 'openai-compatible',
+  },
+
+  cohere: {
+    id: 'cohere',
+    name: 'Cohere',
+    apiKeyName: 'COHERE_API_KEY',
+    baseUrl: 'https://api.cohere.com/v2',
+    required: false,
+    type: 'custom',
+  },
+
+  deepseek_coding: {
+    id: 'deepseek_coding',
+    name: 'DeepSeek Coding Plan',
+    apiKeyName: 'DEEPSEEK_CODING_API_KEY',
+    baseUrl: 'https://api.deepseek.com/v1',
+    required: false,
+    type: 'openai-compatible',
+    supportsCodingPlan: true,
+    codingPlanBaseUrl: 'https://api.deepseek.com/beta',
+  },
+
   moonshot: {
     id: 'moonshot',
     name: 'Moonshot',
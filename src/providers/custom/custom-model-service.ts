@@ -28,6 +28,7 @@ export function isLocalProvider(providerId: string): boolean {
  */
 const PROVIDER_MODELS_ENDPOINTS: Record<string, string | null> = {
   openai: 'https://api.openai.com/v1/models',
+  azure: null, // Azure OpenAI deployments are managed per resource, not listed
   ollama: 'http://127.0.0.1:11434/v1/models',
   lmstudio: 'http://127.0.0.1:1234/v1/models',
   openRouter: 'https://openrouter.ai/api/v1/models',
@@ -35,6 +36,10 @@ const PROVIDER_MODELS_ENDPOINTS: Record<string, string | null> = {
   zai: 'https://api.z.ai/api/paas/v4/models',
   MiniMax: null, // MiniMax doesn't support /v1/models endpoint
   deepseek: 'https://api.deepseek.com/v1/models',
+  deepseek_coding: 'https://api.deepseek.com/v1/models',
+  xai: 'https://api.x.ai/v1/models',
+  mistral: 'https://api.mistral.ai/v1/models',
+  cohere: null, // Cohere lists models in its own shape, not OpenAI's /v1/models
   anthropic: 'https://api.anthropic.com/v1/models',
   google: 'https://generativelanguage.googleapis.com/v1beta/models', // API key as query param
   aiGateway: 'https://ai-gateway.vercel.sh/v1/models',
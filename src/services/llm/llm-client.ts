@@ -16,16 +16,25 @@ import type {
   CompletionResult,
   ContextCompactionRequest,
   ContextCompactionResult,
+  DryRunRequest,
+  EmbeddingRequest,
+  EmbeddingResponse,
+  GeneratedImage,
   GitMessageContext,
   GitMessageResult,
   ImageDownloadRequest,
   ImageDownloadResponse,
   ImageGenerationRequest,
   ImageGenerationResponse,
+  ImageRefreshRequest,
   Message,
+  OAuthDeviceFlowConfig,
   PromptEnhancementRequest,
   PromptEnhancementResult,
+  ProviderCapabilities,
   ProviderConfig,
+  ProviderHealthStatus,
+  RequestBundle,
   StreamEvent,
   StreamResponse,
   StreamTextRequest,
@@ -75,6 +84,10 @@ export class LlmClient {
       onAbort = () => {
         logger.info(`[LLM Client ${requestId}] Abort signal received, stopping`);
         stop();
+        // Drop the HTTP connection on the Rust side as well
+        invoke<boolean>('llm_cancel_stream', { requestId }).catch((error) => {
+          logger.warn(`[LLM Client ${requestId}] Failed to cancel stream`, error);
+        });
       };
       abortSignal.addEventListener('abort', onAbort, { once: true });
     }
@@ -161,6 +174,36 @@ export class LlmClient {
     return { text, finishReason };
   }
 
+  /** Build the request streamText would send, without sending it */
+  async dryRun(request: StreamTextRequest): Promise<DryRunRequest> {
+    return invoke<DryRunRequest>('llm_dry_run_request', { request });
+  }
+
+  /** Prompt tokens the messages would cost on the model, counted before sending */
+  async countTokens(messages: Message[], model: string): Promise<number> {
+    return invoke<number>('llm_count_tokens', { messages, model });
+  }
+
+  /** Bundle a request for support, credentials redacted, without sending it */
+  async exportRequestBundle(request: StreamTextRequest): Promise<RequestBundle> {
+    return invoke<RequestBundle>('llm_export_request_bundle', { request });
+  }
+
+  /** Send an exported bundle again with this machine's credentials */
+  async replayRequestBundle(
+    bundle: RequestBundle,
+    abortSignal?: AbortSignal
+  ): Promise<StreamTextResult> {
+    // Same request as RequestBundle::replay_request on the Rust side
+    const request: StreamTextRequest = {
+      ...bundle.request,
+      model: `${bundle.model}@${bundle.providerId}`,
+      dryRun: null,
+      requestId: null,
+    };
+    return this.streamText(request, abortSignal);
+  }
+
   async checkModelUpdates(): Promise<boolean> {
     return invoke<boolean>('llm_check_model_updates');
   }
@@ -173,6 +216,10 @@ export class LlmClient {
     return invoke<ProviderConfig[]>('llm_get_provider_configs');
   }
 
+  async getProviderPresets(): Promise<ProviderConfig[]> {
+    return invoke<ProviderConfig[]>('llm_get_provider_presets');
+  }
+
   async isModelAvailable(modelIdentifier: string): Promise<boolean> {
     return invoke<boolean>('llm_is_model_available', { modelIdentifier });
   }
@@ -211,6 +258,22 @@ export class LlmClient {
     return invoke<ImageGenerationResponse>('llm_generate_image', { request });
   }
 
+  async refreshImageUrl(request: ImageRefreshRequest): Promise<GeneratedImage> {
+    return invoke<GeneratedImage>('llm_refresh_image_url', { request });
+  }
+
+  async createEmbeddings(request: EmbeddingRequest): Promise<EmbeddingResponse> {
+    return invoke<EmbeddingResponse>('llm_create_embeddings', { request });
+  }
+
+  async getProviderCapabilities(providerId: string): Promise<ProviderCapabilities> {
+    return invoke<ProviderCapabilities>('llm_get_provider_capabilities', { providerId });
+  }
+
+  async checkProviderHealth(providerId: string, model?: string): Promise<ProviderHealthStatus> {
+    return invoke<ProviderHealthStatus>('llm_check_provider_health', { providerId, model });
+  }
+
   async downloadImage(request: ImageDownloadRequest): Promise<ImageDownloadResponse> {
     return invoke<ImageDownloadResponse>('llm_download_image', { request });
   }
@@ -350,6 +413,31 @@ export class LlmClient {
     return invoke('llm_github_copilot_oauth_tokens');
   }
 
+  async startOAuthDeviceFlow(params: {
+    providerId: string;
+    config: OAuthDeviceFlowConfig;
+  }): Promise<{
+    deviceCode: string;
+    userCode: string;
+    verificationUri: string;
+    verificationUriComplete?: string;
+    expiresIn: number;
+    interval: number;
+  }> {
+    return invoke('llm_oauth_device_start', { request: params });
+  }
+
+  async pollOAuthDeviceFlow(params: {
+    providerId: string;
+    config: OAuthDeviceFlowConfig;
+    deviceCode: string;
+  }): Promise<{
+    type: 'success' | 'failed' | 'pending' | 'slow_down';
+    error?: string;
+  }> {
+    return invoke('llm_oauth_device_poll', { request: params });
+  }
+
   async getOAuthStatus(): Promise<{
     anthropic?: {
       expiresAt?: number | null;
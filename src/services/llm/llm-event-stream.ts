@@ -100,6 +100,15 @@ export function logStreamEvent(event: StreamEvent, requestId: string): void {
     case 'done':
       logger.info(`[LLM Stream ${requestId}] Done: ${event.finish_reason ?? 'unknown'}`);
       break;
+    case 'start':
+      logger.debug(
+        `[LLM Stream ${requestId}] Start: ${event.model}` +
+          (event.systemFingerprint ? ` (${event.systemFingerprint})` : '')
+      );
+      break;
+    case 'stop':
+      logger.info(`[LLM Stream ${requestId}] Stop: ${event.reason}`);
+      break;
     case 'text-start':
       logger.debug(`[LLM Stream ${requestId}] Text start`);
       break;
@@ -111,6 +120,11 @@ export function logStreamEvent(event: StreamEvent, requestId: string): void {
         `[LLM Stream ${requestId}] Tool call: ${event.toolName} (id: ${event.toolCallId})`
       );
       break;
+    case 'tool-call-delta':
+      logger.debug(
+        `[LLM Stream ${requestId}] Tool call delta #${event.index}: ${event.argumentsChunk.length} chars`
+      );
+      break;
     case 'reasoning-start':
       logger.debug(`[LLM Stream ${requestId}] Reasoning start: ${event.id}`);
       break;
@@ -120,6 +134,14 @@ export function logStreamEvent(event: StreamEvent, requestId: string): void {
     case 'reasoning-end':
       logger.debug(`[LLM Stream ${requestId}] Reasoning end: ${event.id}`);
       break;
+    case 'citation':
+      logger.debug(`[LLM Stream ${requestId}] Citation: ${event.url}`);
+      break;
+    case 'rate-limit':
+      logger.debug(
+        `[LLM Stream ${requestId}] Rate limit: ${event.remaining_requests ?? '?'} requests left`
+      );
+      break;
     case 'usage':
       logger.debug(
         `[LLM Stream ${requestId}] Usage: ${event.input_tokens} in, ${event.output_tokens} out`
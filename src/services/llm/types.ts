@@ -7,21 +7,26 @@ export type Message =
       role: 'system';
       content: string;
       providerOptions?: ProviderOptions;
+      /** Ends a cacheable prompt prefix on providers with prompt caching */
+      cache?: boolean;
     }
   | {
       role: 'user';
       content: MessageContent;
       providerOptions?: ProviderOptions;
+      cache?: boolean;
     }
   | {
       role: 'assistant';
       content: MessageContent;
       providerOptions?: ProviderOptions;
+      cache?: boolean;
     }
   | {
       role: 'tool';
       content: ContentPart[];
       providerOptions?: ProviderOptions;
+      cache?: boolean;
     };
 
 export type ContentPart =
@@ -31,7 +36,9 @@ export type ContentPart =
     }
   | {
       type: 'image';
+      /** Base64 data, a `data:` URL, or an http(s) URL */
       image: string;
+      mimeType?: string;
     }
   | {
       type: 'video';
@@ -65,6 +72,12 @@ export type ToolDefinition = {
   strict: true;
 };
 
+/** Tools the provider runs itself, such as OpenAI web search or Gemini code execution */
+export type BuiltinTool = 'web_search' | 'code_interpreter';
+
+/** Thinking depth for reasoning models; ignored by models that cannot reason */
+export type ReasoningEffort = 'low' | 'medium' | 'high';
+
 export type TraceContext = {
   traceId: string;
   spanName: string;
@@ -72,6 +85,20 @@ export type TraceContext = {
   metadata?: Record<string, string>;
 };
 
+export type ResponseFormat =
+  | { type: 'json_object' }
+  | { type: 'json_schema'; name: string; schema: Record<string, unknown>; strict?: boolean };
+
+export type PromptTemplate = {
+  vars: Record<string, string>;
+  keepMissing?: boolean;
+};
+
+/** What happens to the oldest messages when a prompt would not fit the context window */
+export type TrimStrategy = 'drop_oldest' | 'summarize_oldest';
+
+export type ImageInputCheck = 'warn' | 'error';
+
 export type StreamTextRequest = {
   model: string;
   messages: Message[];
@@ -81,18 +108,97 @@ export type StreamTextRequest = {
   maxTokens?: number | null;
   topP?: number | null;
   topK?: number | null;
+  frequencyPenalty?: number | null;
+  presencePenalty?: number | null;
+  stop?: string[] | null;
+  responseFormat?: ResponseFormat | null;
+  userId?: string | null;
+  baseUrlOverride?: string | null;
+  template?: PromptTemplate | null;
+  trimStrategy?: TrimStrategy | null;
+  logprobs?: boolean | null;
+  topLogprobs?: number | null;
+  dryRun?: boolean | null;
+  builtinTools?: BuiltinTool[] | null;
+  reasoningEffort?: ReasoningEffort | null;
+  stripHeaders?: string[] | null;
+  maxAttempts?: number | null;
+  imageInputCheck?: ImageInputCheck | null;
   providerOptions?: ProviderOptions;
   requestId?: string | null;
   traceContext?: TraceContext | null;
+  /** Longest wait for the next chunk before the stream fails as stalled; 0 disables */
+  stallTimeoutMs?: number | null;
+  /** False sends the request exactly once, with no retries or stream replays */
+  idempotent?: boolean | null;
 };
 
 export type StreamResponse = {
   request_id: string;
 };
 
+export type RateLimitInfo = {
+  limit_requests?: number | null;
+  remaining_requests?: number | null;
+  reset_requests?: string | null;
+  limit_tokens?: number | null;
+  remaining_tokens?: number | null;
+  reset_tokens?: string | null;
+};
+
+/** Client-generated and provider-reported IDs of one call, for matching logs */
+export type RequestIds = {
+  clientRequestId: string;
+  providerRequestId?: string | null;
+};
+
+/** Log probability of one generated token, with its likeliest alternatives */
+export type TokenLogProb = {
+  token: string;
+  logprob: number;
+  topLogprobs?: TokenLogProb[];
+};
+
+/** Timing of one stream, sent as its last event */
+export type StreamMetrics = {
+  timeToFirstTokenMs?: number | null;
+  durationMs: number;
+  deltaCount: number;
+  tokensPerSecond?: number | null;
+  cancelled: boolean;
+};
+
+/** Request a dry run built instead of sending it; credentials are redacted */
+export type DryRunRequest = {
+  url: string;
+  headers: Record<string, string>;
+  body: unknown;
+};
+
+/** Exported request for support, replayable on another machine; credentials are redacted */
+export type RequestBundle = {
+  version: number;
+  appVersion: string;
+  exportedAt: number;
+  providerId: string;
+  model: string;
+  request: StreamTextRequest;
+  http: DryRunRequest;
+};
+
+/** Server-side timings in seconds, reported by providers such as Groq */
+export type UsageTiming = {
+  queue_time?: number | null;
+  prompt_time?: number | null;
+  completion_time?: number | null;
+  total_time?: number | null;
+};
+
 export type StreamEvent =
+  | { type: 'start'; model: string; systemFingerprint?: string }
   | { type: 'text-start' }
   | { type: 'text-delta'; text: string }
+  | { type: 'log-probs'; tokens: TokenLogProb[] }
   | {
       type: 'tool-call';
       toolCallId: string;
@@ -100,6 +206,13 @@ export type StreamEvent =
       input: unknown;
       providerMetadata?: ProviderOptions;
     }
+  | {
+      type: 'tool-call-delta';
+      index: number;
+      id?: string | null;
+      name?: string | null;
+      argumentsChunk: string;
+    }
   | {
       type: 'reasoning-start';
       id: string;
@@ -115,6 +228,7 @@ export type StreamEvent =
       type: 'reasoning-end';
       id: string;
     }
+  | { type: 'citation'; url: string; title?: string | null }
   | {
       type: 'usage';
       input_tokens: number;
@@ -122,10 +236,23 @@ export type StreamEvent =
       total_tokens?: number | null;
       cached_input_tokens?: number | null;
       cache_creation_input_tokens?: number | null;
+      timing?: UsageTiming;
+      /** Counted locally because the provider reported no usage */
+      estimated?: boolean;
+    }
+  | ({ type: 'rate-limit' } & RateLimitInfo)
+  | ({ type: 'request-ids' } & RequestIds)
+  | {
+      type: 'stop';
+      reason: string;
+      /** Moderation categories the provider flagged when `reason` is `content_filter` */
+      content_filter_results?: Record<string, unknown>;
     }
   | { type: 'done'; finish_reason?: string | null }
   | { type: 'error'; message: string; name?: string }
-  | { type: 'raw'; raw_value: string };
+  | { type: 'raw'; raw_value: string }
+  | ({ type: 'metrics' } & StreamMetrics)
+  | ({ type: 'dry-run' } & DryRunRequest);
 
 export type AvailableModel = {
   key: string;
@@ -139,6 +266,31 @@ export type AvailableModel = {
   inputPricing?: string;
 };
 
+export type ProviderCapabilities = {
+  supportsStreaming: boolean;
+  supportsTools: boolean;
+  supportsVision: boolean;
+  supportsImages: boolean;
+  supportsEmbeddings: boolean;
+  supportsStructuredOutput: boolean;
+  supportsLogprobs: boolean;
+  supportsWebSearch: boolean;
+  supportsCodeInterpreter: boolean;
+  supportsMultipartImages: boolean;
+};
+
+export type ProviderHealthStatus =
+  | { status: 'ok'; latencyMs: number }
+  | { status: 'authFailed'; latencyMs: number; message: string }
+  | { status: 'unreachable'; latencyMs: number; message: string };
+
+export type OAuthDeviceFlowConfig = {
+  deviceAuthorizationUrl: string;
+  tokenUrl: string;
+  clientId: string;
+  scope?: string;
+};
+
 export type ProviderConfig = {
   id: string;
   name: string;
@@ -152,6 +304,14 @@ export type ProviderConfig = {
   headers?: Record<string, string> | null;
   extraBody?: unknown;
   authType: string;
+  modelAliases?: Record<string, string>;
+  defaultModel?: string | null;
+  tolerantStreamJson?: boolean;
+  /** Largest request body in bytes; defaults to 32 MB */
+  maxRequestBytes?: number | null;
+  multipartImages?: boolean;
+  organization?: string | null;
+  project?: string | null;
 };
 
 export type TranscriptionRequest = {
@@ -174,11 +334,21 @@ export type ImageGenerationRequest = {
   model: string;
   prompt: string;
   size?: string | null;
+  /** Shape such as `16:9`, used when `size` is not set */
+  aspectRatio?: string | null;
   quality?: string | null;
   n?: number | null;
   responseFormat?: string | null;
   providerOptions?: ProviderOptions;
   requestId?: string | null;
+  seed?: number | null;
+  negativePrompt?: string | null;
+  skipSizeValidation?: boolean;
+  strictN?: boolean;
+  /** Download URL-only results into `b64Json`, keeping the URL */
+  downloadUrls?: boolean;
+  userId?: string | null;
+  template?: PromptTemplate | null;
 };
 
 export type GeneratedImage = {
@@ -186,12 +356,35 @@ export type GeneratedImage = {
   url?: string | null;
   mimeType: string;
   revisedPrompt?: string | null;
+  /** Unix seconds after which `url` stops working */
+  expiresAt?: number;
 };
 
 export type ImageGenerationResponse = {
   provider: string;
   images: GeneratedImage[];
   requestId?: string | null;
+  rateLimit?: RateLimitInfo;
+  requestIds?: RequestIds;
+  /** Requests of a fanned-out batch that failed while others returned images */
+  errors?: BatchImageError[];
+};
+
+export type BatchImageError = {
+  index: number;
+  message: string;
+};
+
+export type EmbeddingRequest = {
+  providerId: string;
+  model: string;
+  input: string[];
+};
+
+export type EmbeddingResponse = {
+  provider: string;
+  model: string;
+  embeddings: number[][];
 };
 
 export type ImageDownloadRequest = {
@@ -203,6 +396,11 @@ export type ImageDownloadResponse = {
   mimeType: string;
 };
 
+export type ImageRefreshRequest = {
+  image: GeneratedImage;
+  request: ImageGenerationRequest;
+};
+
 // AI Services Types
 
 export type CompletionContext = {
@@ -241,6 +439,7 @@ export type TokenUsage = {
   outputTokens: number;
   cachedInputTokens?: number | null;
   cacheCreationInputTokens?: number | null;
+  estimated?: boolean;
 };
 
 export type CalculateCostRequest = {